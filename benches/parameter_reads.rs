@@ -0,0 +1,81 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use oml::model::Model;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+const PARAMETERS: usize = 10_000;
+
+/// A training step: several passes over the parameters.
+fn train(params: &mut [f64]) {
+    for _ in 0..20 {
+        params
+            .iter_mut()
+            .for_each(|p| *p = (*p * 1.000_001).sin().abs() + 0.5);
+    }
+}
+
+/// Runs `write` in a loop on another thread until the returned flag is set.
+fn spawn_writer(write: impl Fn() + Send + 'static) -> (Arc<AtomicBool>, thread::JoinHandle<()>) {
+    let stop = Arc::new(AtomicBool::new(false));
+    let flag = stop.clone();
+    let handle = thread::spawn(move || {
+        while !flag.load(Ordering::Relaxed) {
+            write();
+        }
+    });
+    (stop, handle)
+}
+
+/// Inference-style reads (a dot product over every parameter) while a
+/// training thread keeps updating them: with a read-write lock readers wait
+/// for each write, with the snapshot-based `Model` they never do.
+fn reads_under_training(c: &mut Criterion) {
+    let features = vec![0.5f64; PARAMETERS];
+    let mut group = c.benchmark_group("reads_under_training");
+
+    let locked = Arc::new(RwLock::new(vec![1.0f64; PARAMETERS]));
+    let writer = locked.clone();
+    let (stop, handle) = spawn_writer(move || {
+        train(&mut writer.write().unwrap());
+    });
+    group.bench_function("rwlock", |b| {
+        b.iter(|| {
+            let params = locked.read().unwrap();
+            black_box(
+                params
+                    .iter()
+                    .zip(&features)
+                    .map(|(w, x)| w * x)
+                    .sum::<f64>(),
+            )
+        })
+    });
+    stop.store(true, Ordering::Relaxed);
+    handle.join().unwrap();
+
+    let model = Arc::new(Model::with_parameters(vec![1.0f64; PARAMETERS]));
+    let writer = model.clone();
+    let (stop, handle) = spawn_writer(move || {
+        writer.update_params(|params| train(params));
+    });
+    group.bench_function("model_snapshot", |b| {
+        b.iter(|| {
+            let params = model.read_params();
+            black_box(
+                params
+                    .iter()
+                    .zip(&features)
+                    .map(|(w, x)| w * x)
+                    .sum::<f64>(),
+            )
+        })
+    });
+    stop.store(true, Ordering::Relaxed);
+    handle.join().unwrap();
+
+    group.finish();
+}
+
+criterion_group!(benches, reads_under_training);
+criterion_main!(benches);
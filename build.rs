@@ -0,0 +1,37 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    compile_parameter_sync();
+}
+
+/// Generates the `oml.sync.v1.ParameterSync` service without `protoc`: the
+/// message types are hand-written prost structs in `src/grpc.rs`.
+#[cfg(feature = "grpc")]
+fn compile_parameter_sync() {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    let method = |name: &str, route: &str, input: &str, output: &str| {
+        Method::builder()
+            .name(name)
+            .route_name(route)
+            .input_type(format!("crate::grpc::{}", input))
+            .output_type(format!("crate::grpc::{}", output))
+            .codec_path("tonic::codec::ProstCodec")
+    };
+    let service = Service::builder()
+        .name("ParameterSync")
+        .package("oml.sync.v1")
+        .method(method("get_snapshot", "GetSnapshot", "SnapshotRequest", "Snapshot").build())
+        .method(
+            method(
+                "stream_deltas",
+                "StreamDeltas",
+                "StreamDeltasRequest",
+                "Delta",
+            )
+            .server_streaming()
+            .build(),
+        )
+        .method(method("push_update", "PushUpdate", "Delta", "PushUpdateReply").build())
+        .build();
+    Builder::new().compile(&[service]);
+}
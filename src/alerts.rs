@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Event posted as JSON to every configured alert webhook.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertEvent {
+    /// Machine-readable event type, e.g. `training_paused`.
+    pub kind: String,
+    pub message: String,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+    #[serde(default)]
+    pub details: serde_json::Value,
+}
+
+impl AlertEvent {
+    pub fn new(kind: &str, message: impl Into<String>, details: serde_json::Value) -> Self {
+        AlertEvent {
+            kind: kind.to_string(),
+            message: message.into(),
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            details,
+        }
+    }
+}
+
+/// Fire-and-forget delivery of [`AlertEvent`]s to HTTP webhooks.
+#[derive(Debug, Clone)]
+pub struct AlertSink {
+    webhooks: Vec<String>,
+    http: reqwest::Client,
+}
+
+impl AlertSink {
+    pub fn new(webhooks: Vec<String>) -> Self {
+        AlertSink {
+            webhooks,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    pub fn webhooks(&self) -> &[String] {
+        &self.webhooks
+    }
+
+    /// Posts `event` to every webhook in the background. Delivery failures
+    /// are ignored; without a running tokio runtime nothing is sent.
+    pub fn emit(&self, event: AlertEvent) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        for webhook in &self.webhooks {
+            let request = self.http.post(webhook).json(&event);
+            runtime.spawn(async move {
+                let _ = request.send().await;
+            });
+        }
+    }
+}
@@ -1,18 +1,193 @@
 use crate::errors::ModelError;
 use crate::model::Model;
 use num_traits::Float;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::fmt::Debug;
 use std::iter::Sum;
 use std::{thread, time};
 
+/// Additive breakdown of a prediction: the prediction equals the sum of the
+/// feature contributions plus the bias.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Contributions<T> {
+    /// Contribution (weight × value) of each feature.
+    pub features: Vec<T>,
+    pub bias: T,
+}
+
+/// Uncertainty of a prediction, in whichever form the algorithm provides it:
+/// a predictive variance (Bayesian models), confidence bounds (upper
+/// confidence bound bandits) or the disagreement of an ensemble.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Uncertainty<T> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variance: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lower: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upper: Option<T>,
+}
+
+impl<T: Float> Uncertainty<T> {
+    /// Variance of a Gaussian prediction with the symmetric bounds `z`
+    /// standard deviations away from `mean`.
+    pub fn gaussian(mean: T, variance: T, z: T) -> Self {
+        let margin = z * variance.max(T::zero()).sqrt();
+        Uncertainty {
+            variance: Some(variance),
+            lower: Some(mean - margin),
+            upper: Some(mean + margin),
+        }
+    }
+}
+
+/// A value an algorithm takes as input or returns as output.
+///
+/// Besides being exchanged as JSON, a value may expose a single number
+/// summarizing it, which the stages working on plain numbers (outlier
+/// filtering, metrics, calibration, model comparison) rely on. Values
+/// without one, such as structured features, simply skip those stages.
+pub trait Datum: Clone + Debug + Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// Numeric view of the value, if it has one.
+    fn score(&self) -> Option<f64> {
+        None
+    }
+
+    /// Builds a value from its numeric view, for stages that rewrite outputs.
+    fn from_score(score: f64) -> Option<Self> {
+        let _ = score;
+        None
+    }
+
+    /// Numeric features of the value, if it has them, for stages working
+    /// feature by feature (such as clipping).
+    fn features(&self) -> Option<Vec<f64>> {
+        None
+    }
+
+    /// The value with its features replaced by `features`.
+    fn with_features(&self, features: &[f64]) -> Option<Self> {
+        let _ = features;
+        None
+    }
+
+    /// Builds an unlabeled value from encoded features, for payloads
+    /// decoded through a [`PayloadSchema`](crate::schema::PayloadSchema).
+    fn from_features(features: &[f64]) -> Option<Self> {
+        let _ = features;
+        None
+    }
+
+    /// Class of a labeled training sample, for the class priors of the
+    /// model (see [`Model::observe_class`]).
+    fn class_label(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Text of a class label given as a number: integral values without a
+/// fraction, so `1.0` and `1` are the same class.
+pub(crate) fn numeric_class_label<D: Datum>(label: Option<&D>) -> Option<String> {
+    let score = label?.score().filter(|s| s.is_finite())?;
+    Some(
+        match score.fract() == 0.0 && score.abs() < i64::MAX as f64 {
+            true => (score as i64).to_string(),
+            false => score.to_string(),
+        },
+    )
+}
+
+macro_rules! impl_numeric_datum {
+    ($($ty:ty),*) => {$(
+        impl Datum for $ty {
+            fn score(&self) -> Option<f64> {
+                Some(*self as f64)
+            }
+
+            fn from_score(score: f64) -> Option<Self> {
+                score.is_finite().then(|| score as $ty)
+            }
+
+            fn features(&self) -> Option<Vec<f64>> {
+                Some(vec![*self as f64])
+            }
+
+            fn with_features(&self, features: &[f64]) -> Option<Self> {
+                Self::from_features(features)
+            }
+
+            fn from_features(features: &[f64]) -> Option<Self> {
+                match features {
+                    [single] => Self::from_score(*single),
+                    _ => None,
+                }
+            }
+        }
+    )*};
+}
+
+impl_numeric_datum!(f32, f64, i32, i64, u32, u64, usize);
+
+impl Datum for bool {
+    fn score(&self) -> Option<f64> {
+        Some(if *self { 1.0 } else { 0.0 })
+    }
+
+    fn from_score(score: f64) -> Option<Self> {
+        Some(score >= 0.5)
+    }
+}
+
+impl Datum for String {}
+
+/// Vectors (feature vectors, probability vectors) only have a numeric view
+/// when they hold a single element.
+impl<D: Datum> Datum for Vec<D> {
+    fn score(&self) -> Option<f64> {
+        match self.as_slice() {
+            [single] => single.score(),
+            _ => None,
+        }
+    }
+
+    fn features(&self) -> Option<Vec<f64>> {
+        self.iter().map(Datum::score).collect()
+    }
+
+    fn with_features(&self, features: &[f64]) -> Option<Self> {
+        if features.len() != self.len() {
+            return None;
+        }
+        Self::from_features(features)
+    }
+
+    fn from_features(features: &[f64]) -> Option<Self> {
+        features.iter().map(|f| D::from_score(*f)).collect()
+    }
+}
+
 /// Defines the behavior for machine learning algorithms.
 ///
 /// This trait should be implemented by any algorithm that can perform
-/// training and inference steps on a given model.
+/// training and inference steps on a given model. `T` is the type of the
+/// model parameters; inputs and outputs have their own types, so a
+/// classifier can return labels or probability vectors and a model can take
+/// structured features.
+///
+/// Training steps on a sparse model (see [`Model::sparse`]) update only the
+/// indices they touch, through [`Model::update_sparse`]. Dense training
+/// steps write through [`Model::write_trainable`] or
+/// [`Model::update_trainable`], which keep the frozen parameters (see
+/// [`Model::set_freeze_mask`]) as they were.
 pub trait Algorithm<T>: Send + Sync
 where
     T: Float + Debug + Send + Sync + Sum,
 {
+    /// Input of training and inference steps.
+    type Input: Datum;
+    /// Output of inference steps, also the type of labels and outcomes.
+    type Output: Datum;
+
     /// Performs a training step on the provided model with the given input `x`.
     ///
     /// # Arguments
@@ -23,7 +198,7 @@ where
     /// # Returns
     ///
     /// A result indicating whether the training step was successful or not.
-    fn training_step(&self, model: &Model<T>, x: T) -> Result<(), ModelError>;
+    fn training_step(&self, model: &Model<T>, x: Self::Input) -> Result<(), ModelError>;
 
     /// Performs an inference step on the provided model with the given input `x`.
     ///
@@ -35,7 +210,137 @@ where
     /// # Returns
     ///
     /// A result containing the inference output or an error.
-    fn inference_step(&self, model: &Model<T>, x: T) -> Result<T, ModelError>;
+    fn inference_step(&self, model: &Model<T>, x: Self::Input) -> Result<Self::Output, ModelError>;
+
+    /// Performs an inference step on the moving average of the parameters
+    /// (see [`Model::with_ema`]), which smooths out noisy online updates.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - A reference to the model whose moving average serves the step.
+    /// * `x` - The input value used for inference.
+    ///
+    /// # Returns
+    ///
+    /// The inference output. The default runs [`Algorithm::inference_step`]
+    /// on the moving average, or on the parameters if the model keeps none;
+    /// algorithms with state outside the dense parameters can override it.
+    fn ema_inference_step(
+        &self,
+        model: &Model<T>,
+        x: Self::Input,
+    ) -> Result<Self::Output, ModelError> {
+        self.inference_step(model.ema().unwrap_or(model), x)
+    }
+
+    /// Learns from an outcome observed after the prediction for `x` was served
+    /// (a delayed label or a reward).
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - A reference to the model to update.
+    /// * `x` - The input the prediction was made for.
+    /// * `outcome` - The observed label or reward.
+    ///
+    /// # Returns
+    ///
+    /// A result indicating whether the update was successful. Algorithms that
+    /// cannot learn from outcomes keep the default, which returns
+    /// [`ModelError::Unsupported`].
+    fn feedback_step(
+        &self,
+        model: &Model<T>,
+        x: Self::Input,
+        outcome: Self::Output,
+    ) -> Result<(), ModelError> {
+        let _ = (model, x, outcome);
+        Err(ModelError::Unsupported(
+            "this algorithm does not learn from feedback".to_string(),
+        ))
+    }
+
+    /// Performs a training step that counts `x` with `weight` in `[0, 1]`,
+    /// e.g. a late sample (see [`crate::event_time`]).
+    ///
+    /// By default the step runs as usual on a fork of the model and its
+    /// change to the dense parameters is scaled by `weight`, then published
+    /// as a single write, so no other writer can slip in between; sparse
+    /// parameters take the full step. Algorithms with a natural sample
+    /// weight override it.
+    fn weighted_training_step(
+        &self,
+        model: &Model<T>,
+        x: Self::Input,
+        weight: f64,
+    ) -> Result<(), ModelError> {
+        if weight >= 1.0 {
+            return self.training_step(model, x);
+        }
+        let mut params = model.write_trainable();
+        let stepped = model.fork().and_then(|fork| {
+            self.training_step(&fork, x)?;
+            if let (Some(sparse), Some(stepped)) = (model.sparse_params(), fork.sparse_params()) {
+                sparse.replace(&stepped.entries())?;
+            }
+            Ok(fork.snapshot())
+        });
+        let stepped = match stepped {
+            Ok(stepped) => stepped,
+            Err(e) => {
+                params.discard();
+                return Err(e);
+            }
+        };
+        let weight = T::from(weight.max(0.0)).unwrap_or_else(T::zero);
+        if params.len() == stepped.len() {
+            for (param, new) in params.iter_mut().zip(stepped.iter()) {
+                *param = *param + weight * (*new - *param);
+            }
+        }
+        Ok(())
+    }
+
+    /// Breaks the prediction for `x` down into per-feature contributions.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - A reference to the model used for the prediction.
+    /// * `x` - The input value to explain.
+    ///
+    /// # Returns
+    ///
+    /// The contributions of each feature and the bias term. Only linear-family
+    /// algorithms have such a breakdown; the default returns
+    /// [`ModelError::Unsupported`].
+    fn contributions(
+        &self,
+        model: &Model<T>,
+        x: Self::Input,
+    ) -> Result<Contributions<T>, ModelError> {
+        let _ = (model, x);
+        Err(ModelError::Unsupported(
+            "this algorithm does not report feature contributions".to_string(),
+        ))
+    }
+
+    /// Estimates the uncertainty of the prediction for `x`.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - A reference to the model used for the prediction.
+    /// * `x` - The input value.
+    ///
+    /// # Returns
+    ///
+    /// The uncertainty of the raw model output. Algorithms without an
+    /// uncertainty model keep the default, which returns
+    /// [`ModelError::Unsupported`].
+    fn uncertainty(&self, model: &Model<T>, x: Self::Input) -> Result<Uncertainty<T>, ModelError> {
+        let _ = (model, x);
+        Err(ModelError::Unsupported(
+            "this algorithm does not estimate uncertainty".to_string(),
+        ))
+    }
 }
 
 /// A dummy algorithm used for demonstration purposes.
@@ -47,23 +352,28 @@ pub struct DummyAlgorithm;
 
 impl<T> Algorithm<T> for DummyAlgorithm
 where
-    T: Float + Debug + Send + Sync + Sum,
+    T: Float + Debug + Send + Sync + Sum + Datum,
 {
+    type Input = T;
+    type Output = T;
+
     fn training_step(&self, model: &Model<T>, x: T) -> Result<(), ModelError> {
-        unsafe {
-            let params = model.get_parameters_mut();
-            thread::sleep(time::Duration::from_millis(5000)); // simulated delay
-            params.iter_mut().for_each(|param| *param = *param * x);
-            Ok(())
-        }
+        thread::sleep(time::Duration::from_millis(5000)); // simulated delay
+        model.update_trainable(|params| params.iter_mut().for_each(|param| *param = *param * x));
+        Ok(())
     }
 
     fn inference_step(&self, model: &Model<T>, x: T) -> Result<T, ModelError> {
-        unsafe {
-            let params = model.get_parameters();
-            thread::sleep(time::Duration::from_millis(500)); // simulated delay
-            Ok(params.iter().map(|param| *param * x).sum())
-        }
+        thread::sleep(time::Duration::from_millis(500)); // simulated delay
+        Ok(model.read_params().iter().map(|param| *param * x).sum())
+    }
+
+    fn contributions(&self, model: &Model<T>, x: T) -> Result<Contributions<T>, ModelError> {
+        let params = model.read_params();
+        Ok(Contributions {
+            features: params.iter().map(|param| *param * x).collect(),
+            bias: T::zero(),
+        })
     }
 }
 
@@ -90,6 +400,42 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_contributions_sum_to_prediction() {
+        let model = Model::with_parameters(vec![1.0, 2.0, 3.0]);
+        let contributions = DummyAlgorithm.contributions(&model, 2.0).unwrap();
+        assert_eq!(contributions.features, vec![2.0, 4.0, 6.0]);
+        assert_eq!(contributions.bias, 0.0);
+    }
+
+    #[test]
+    fn test_gaussian_uncertainty_bounds() {
+        let uncertainty = Uncertainty::gaussian(1.0, 4.0, 1.5);
+        assert_eq!(uncertainty.variance, Some(4.0));
+        assert_eq!(uncertainty.lower, Some(-2.0));
+        assert_eq!(uncertainty.upper, Some(4.0));
+    }
+
+    #[test]
+    fn test_datum_numeric_views() {
+        assert_eq!(3u64.score(), Some(3.0));
+        assert_eq!(bool::from_score(0.7), Some(true));
+        assert_eq!(vec![0.25f32].score(), Some(0.25));
+        assert_eq!(vec![0.25f32, 0.75].score(), None);
+        assert_eq!("cat".to_string().score(), None);
+    }
+
+    #[test]
+    fn test_weighted_training_step_publishes_once() {
+        let model = Model::with_parameters(vec![1.0f32, 2.0]);
+        let version = model.version();
+        DummyAlgorithm
+            .weighted_training_step(&model, 3.0, 0.5)
+            .unwrap();
+        assert_eq!(model.read_params().as_slice(), &[2.0, 4.0]);
+        assert_eq!(model.version(), version + 1);
+    }
+
     #[test]
     fn test_training_step() {
         let rt = setup();
@@ -101,7 +447,7 @@ mod tests {
             algorithm.training_step(&model, update_factor).unwrap();
         });
 
-        let params = unsafe { &*model.parameters.get() };
+        let params = model.read_params();
         let expected: Vec<f32> = vec![
             1.0 * update_factor,
             2.0 * update_factor,
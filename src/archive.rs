@@ -0,0 +1,223 @@
+use crate::algorithm::Algorithm;
+use crate::catalog::{LineageRecord, ModelSpec};
+use crate::errors::ModelError;
+use crate::handlers::AppState;
+use crate::metrics::MetricsSnapshot;
+use crate::model::SavedModel;
+use crate::personalization::SavedKey;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A catalog model in a [`ServerArchive`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedModel<T> {
+    pub name: String,
+    /// Live parameters, layout and metadata.
+    pub model: SavedModel<T>,
+    /// Lineage on the exporting instance, for reference.
+    pub lineage: Vec<LineageRecord>,
+}
+
+/// The state of a server, written by `GET /admin/export` and restored on
+/// another instance by `POST /admin/import`: the serving model (with its
+/// layout, which is its schema), the catalog models, the personalized keys
+/// and, for reference only, the metrics.
+///
+/// Algorithms are code, so the importing instance must be configured with
+/// the same ones; only their models' state travels.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerArchive<T> {
+    pub oml_version: String,
+    /// Milliseconds since the Unix epoch.
+    pub exported_at_ms: u64,
+    pub model: SavedModel<T>,
+    /// Catalog models other than the serving one.
+    pub catalog: Vec<ArchivedModel<T>>,
+    /// Persisted keys of the personalized models, if enabled.
+    pub personalization: Option<Vec<SavedKey<T>>>,
+    pub metrics: MetricsSnapshot,
+}
+
+/// What `POST /admin/import` restored.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ImportReport {
+    /// New version of the serving model.
+    pub version: u64,
+    /// Catalog models replaced, then created.
+    pub models_replaced: Vec<String>,
+    pub models_created: Vec<String>,
+    /// Catalog models skipped: the catalog is disabled or cannot create them.
+    pub models_skipped: Vec<String>,
+    pub personalized_keys: usize,
+}
+
+impl<T> ServerArchive<T>
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+{
+    /// Captures the state of `state`. Reads every model, so call it from a
+    /// blocking context.
+    pub fn capture<A: Algorithm<T> + 'static>(state: &AppState<T, A>) -> Result<Self, ModelError> {
+        let mut catalog = Vec::new();
+        if let Some(models) = &state.catalog {
+            for name in models.names() {
+                let Some((model, _)) = models.resolve(&name, None)? else {
+                    continue;
+                };
+                if Arc::ptr_eq(&model, &state.model) {
+                    continue;
+                }
+                catalog.push(ArchivedModel {
+                    model: model.to_saved(),
+                    lineage: models.lineage(&name)?,
+                    name,
+                });
+            }
+        }
+        Ok(ServerArchive {
+            oml_version: env!("CARGO_PKG_VERSION").to_string(),
+            exported_at_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            model: state.model.to_saved(),
+            catalog,
+            personalization: state
+                .personalization
+                .as_ref()
+                .map(|p| p.export())
+                .transpose()?,
+            metrics: state.metrics.snapshot(),
+        })
+    }
+
+    /// Restores the archive into `state`. The serving model must have the
+    /// same parameter count and layout; it keeps the replaced parameters as
+    /// a restore point, and catalog models record the archive as their
+    /// import source. Missing catalog models are created with the factory,
+    /// if any. Metrics are not restored.
+    pub fn restore<A: Algorithm<T> + 'static>(
+        self,
+        state: &AppState<T, A>,
+    ) -> Result<ImportReport, ModelError> {
+        let layout = state.model.layout();
+        if !self.model.layout.is_empty() && !layout.is_empty() && self.model.layout != *layout {
+            return Err(ModelError::InvalidInput(
+                "the parameter groups differ from the serving model's".to_string(),
+            ));
+        }
+        let mut report = ImportReport {
+            version: state.model.replace_parameters(self.model.parameters)?,
+            ..ImportReport::default()
+        };
+        if let Some(sparse) = &self.model.sparse {
+            state.model.replace_sparse(sparse)?;
+            report.version = state.model.version();
+        }
+
+        let source = format!("archive exported at {}", self.exported_at_ms);
+        for archived in self.catalog {
+            let name = archived.name;
+            let Some(catalog) = &state.catalog else {
+                report.models_skipped.push(name);
+                continue;
+            };
+            if catalog.contains(&name) {
+                catalog.import(&name, archived.model.parameters, &source)?;
+                report.models_replaced.push(name);
+                continue;
+            }
+            let spec = ModelSpec {
+                parameters: Some(archived.model.parameters.clone()),
+                config: archived
+                    .lineage
+                    .last()
+                    .map(|record| record.config.clone())
+                    .unwrap_or_default(),
+            };
+            match catalog.create(&name, &spec) {
+                Ok(()) => {
+                    catalog.import(&name, archived.model.parameters, &source)?;
+                    report.models_created.push(name);
+                }
+                Err(ModelError::Unsupported(_)) => report.models_skipped.push(name),
+                Err(e) => return Err(e),
+            }
+        }
+
+        if let (Some(personal), Some(keys)) = (&state.personalization, &self.personalization) {
+            report.personalized_keys = personal.import(keys)?;
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::ModelCatalog;
+    use crate::model::Model;
+    use crate::personalization::PersonalizationConfig;
+    use crate::test::ScalingAlgorithm;
+
+    fn state(catalog: ModelCatalog<f64, ScalingAlgorithm>) -> AppState<f64, ScalingAlgorithm> {
+        AppState::new(Model::with_parameters(vec![1.0, 1.0]), ScalingAlgorithm)
+            .with_model_catalog("main", catalog)
+            .with_personalization(PersonalizationConfig::default())
+    }
+
+    #[test]
+    fn test_archive_moves_models_and_keys_to_another_instance() {
+        let source = state(ModelCatalog::new(2).with_model(
+            "books",
+            Model::with_parameters(vec![3.0]),
+            ScalingAlgorithm,
+        ));
+        source.model.update_params(|p| p[0] = 2.0);
+        let personal = source.personalization.as_ref().unwrap();
+        personal
+            .train("alice", &source.model, &ScalingAlgorithm, 2.0)
+            .unwrap();
+        source.metrics.increment("training_steps");
+
+        let bytes = serde_json::to_vec(&ServerArchive::capture(&source).unwrap()).unwrap();
+        let archive: ServerArchive<f64> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(archive.catalog.len(), 1);
+        assert_eq!(archive.metrics.counters["training_steps"], 1);
+
+        let factory = |spec: &ModelSpec<f64>| {
+            let parameters = spec.parameters.clone().unwrap_or_default();
+            Ok((Model::with_parameters(parameters), ScalingAlgorithm))
+        };
+        let target = state(ModelCatalog::new(2).with_factory(factory));
+        let report = archive.clone().restore(&target).unwrap();
+        assert_eq!(report.version, 1);
+        assert_eq!(report.models_created, vec!["books"]);
+        assert_eq!(report.personalized_keys, 1);
+        assert_eq!(target.model.read_params().as_slice(), &[2.0, 1.0]);
+        let catalog = target.catalog.as_ref().unwrap();
+        let (books, _) = catalog.resolve("books", None).unwrap().unwrap();
+        assert_eq!(books.read_params().as_slice(), &[3.0]);
+        let lineage = catalog.lineage("books").unwrap();
+        assert!(lineage[0].source.as_deref().unwrap().starts_with("archive"));
+        let offsets = target.personalization.as_ref().unwrap().offsets("alice");
+        assert!(offsets.unwrap().is_some());
+
+        let report = archive.clone().restore(&target).unwrap();
+        assert_eq!(report.models_replaced, vec!["books"]);
+        let mut wrong = archive;
+        wrong.model.layout = crate::layout::ModelLayout::new().group("w", &[2]);
+        let other = AppState::new(
+            Model::builder()
+                .layout(crate::layout::ModelLayout::new().group("v", &[2]))
+                .build()
+                .unwrap(),
+            ScalingAlgorithm,
+        );
+        assert!(wrong.restore(&other).is_err());
+    }
+}
@@ -0,0 +1,123 @@
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::HttpRequest;
+use std::sync::{Arc, OnceLock};
+
+/// Bearer-token authentication for every route.
+///
+/// Nodes of a cluster (replication, gossip, membership, consistency,
+/// shared state, batch jobs) call each other's routes with the peer token,
+/// so every node must accept it.
+#[derive(Debug, Clone, Default)]
+pub struct AuthConfig {
+    /// Accepted tokens, sent as `Authorization: Bearer <token>`.
+    pub tokens: Vec<String>,
+    /// Paths served without a token (e.g. `/metrics` for a scraper).
+    pub public_paths: Vec<String>,
+    /// Token sent to peers; the first accepted token if unset.
+    pub peer_token: Option<String>,
+}
+
+impl AuthConfig {
+    pub fn bearer(tokens: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        AuthConfig {
+            tokens: tokens.into_iter().map(Into::into).collect(),
+            public_paths: Vec::new(),
+            peer_token: None,
+        }
+    }
+
+    pub fn with_public_path(mut self, path: impl Into<String>) -> Self {
+        self.public_paths.push(path.into());
+        self
+    }
+
+    pub fn with_peer_token(mut self, token: impl Into<String>) -> Self {
+        self.peer_token = Some(token.into());
+        self
+    }
+
+    /// The token this node authenticates with when calling its peers.
+    pub fn peer_token(&self) -> Option<&str> {
+        self.peer_token
+            .as_deref()
+            .or(self.tokens.first().map(String::as_str))
+    }
+
+    /// Whether `req` targets a public path or carries an accepted token.
+    pub fn authorizes(&self, req: &HttpRequest) -> bool {
+        if self.public_paths.iter().any(|p| p == req.path()) {
+            return true;
+        }
+        let token = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        match token {
+            Some(token) => self
+                .tokens
+                .iter()
+                .any(|t| constant_time_eq(t.as_bytes(), token.as_bytes())),
+            None => false,
+        }
+    }
+}
+
+/// HTTP client for the requests a node sends its peers, authenticating
+/// with the peer token of [`AuthConfig`] once the server is built with one.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PeerClient {
+    http: reqwest::Client,
+    token: Arc<OnceLock<String>>,
+}
+
+impl PeerClient {
+    /// Sends `token` with every later request; the first token set sticks.
+    pub(crate) fn set_token(&self, token: &str) {
+        let _ = self.token.set(token.to_string());
+    }
+
+    pub(crate) fn get(&self, url: impl reqwest::IntoUrl) -> reqwest::RequestBuilder {
+        self.authorize(self.http.get(url))
+    }
+
+    pub(crate) fn post(&self, url: impl reqwest::IntoUrl) -> reqwest::RequestBuilder {
+        self.authorize(self.http.post(url))
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self.token.get() {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+}
+
+/// Compares without short-circuiting, so response timing does not leak
+/// how much of a token matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_bearer_tokens_and_public_paths() {
+        let auth = AuthConfig::bearer(["secret"]).with_public_path("/metrics");
+        let request = |path: &str, header: Option<&str>| {
+            let mut req = TestRequest::post().uri(path);
+            if let Some(header) = header {
+                req = req.insert_header((AUTHORIZATION, header));
+            }
+            req.to_http_request()
+        };
+        assert!(auth.authorizes(&request("/training", Some("Bearer secret"))));
+        assert!(!auth.authorizes(&request("/training", Some("Bearer secreT"))));
+        assert!(!auth.authorizes(&request("/training", Some("secret"))));
+        assert!(!auth.authorizes(&request("/training", None)));
+        assert!(auth.authorizes(&request("/metrics", None)));
+    }
+}
@@ -0,0 +1,232 @@
+use crate::algorithm::{numeric_class_label, Algorithm, Contributions, Datum, Uncertainty};
+use crate::errors::ModelError;
+use crate::layout::ModelLayout;
+use crate::model::Model;
+use crate::tensors::Tensor;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::sync::Mutex;
+
+/// Features of a regression sample, with the target value when known.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegressionInput<T> {
+    pub features: Vec<T>,
+    /// Target value; required by training steps, ignored by inference.
+    pub label: Option<T>,
+}
+
+impl<T: Datum> Datum for RegressionInput<T> {
+    fn class_label(&self) -> Option<String> {
+        numeric_class_label(self.label.as_ref())
+    }
+
+    fn features(&self) -> Option<Vec<f64>> {
+        self.features.iter().map(Datum::score).collect()
+    }
+
+    fn with_features(&self, features: &[f64]) -> Option<Self> {
+        Some(RegressionInput {
+            features: self.features.with_features(features)?,
+            label: self.label.clone(),
+        })
+    }
+
+    fn from_features(features: &[f64]) -> Option<Self> {
+        Some(RegressionInput {
+            features: Vec::from_features(features)?,
+            label: None,
+        })
+    }
+}
+
+/// Bayesian linear regression with a Gaussian prior over the weights and
+/// known observation noise, updated exactly with every sample.
+///
+/// The model parameters hold the posterior mean: one weight per feature
+/// followed by the bias. The posterior covariance is kept by the algorithm,
+/// so predictions come with their variance `noise + xᵀ P x`. With a noise
+/// variance of 1 this is recursive least squares started from
+/// `P = prior_variance · I`.
+#[derive(Debug)]
+pub struct BayesianLinearRegression<T> {
+    dimension: usize,
+    noise_variance: T,
+    /// Standard deviations covered by the reported prediction interval.
+    z: T,
+    covariance: Mutex<Tensor<T>>,
+}
+
+impl<T> BayesianLinearRegression<T>
+where
+    T: Float + Debug + Send + Sync + Sum + Datum,
+{
+    /// Creates the prior `N(0, prior_variance · I)` over `dimension` weights
+    /// and the bias. Prediction intervals cover 1.96 standard deviations.
+    pub fn new(dimension: usize, prior_variance: T, noise_variance: T) -> Self {
+        BayesianLinearRegression {
+            dimension,
+            noise_variance,
+            z: T::from(1.96).unwrap_or_else(T::one),
+            covariance: Mutex::new(Tensor::diagonal(dimension + 1, prior_variance)),
+        }
+    }
+
+    /// Reports prediction intervals of `z` standard deviations instead.
+    pub fn with_interval(mut self, z: T) -> Self {
+        self.z = z;
+        self
+    }
+
+    /// A model at the prior mean (all weights and the bias zero), with
+    /// `weights` and `bias` parameter groups.
+    pub fn prior_model(&self) -> Model<T> {
+        Model::builder()
+            .layout(
+                ModelLayout::new()
+                    .group("weights", &[self.dimension])
+                    .group("bias", &[1]),
+            )
+            .build()
+            .expect("a zeroed dense model always builds")
+    }
+
+    /// Current posterior covariance of the weights and the bias.
+    pub fn covariance(&self) -> Result<Tensor<T>, ModelError> {
+        Ok(self.covariance.lock()?.clone())
+    }
+
+    /// `features` followed by the constant bias input.
+    fn augmented(&self, model: &Model<T>, features: &[T]) -> Result<Vec<T>, ModelError> {
+        if features.len() != self.dimension {
+            return Err(ModelError::DimensionMismatch {
+                expected: self.dimension,
+                actual: features.len(),
+            });
+        }
+        if model.read_params().len() != self.dimension + 1 {
+            return Err(ModelError::InvalidInput(format!(
+                "the model must hold {} weights and a bias",
+                self.dimension
+            )));
+        }
+        Ok(features.iter().copied().chain([T::one()]).collect())
+    }
+
+    fn update(&self, model: &Model<T>, features: &[T], label: T) -> Result<(), ModelError> {
+        let x = self.augmented(model, features)?;
+        let mut covariance = self.covariance.lock()?;
+        let px = covariance.matvec(&x);
+        let s = self.noise_variance + dot(&x, &px);
+        let mut weights = model.write_trainable();
+        let error = label - dot(&x, &weights);
+        for (w, p) in weights.iter_mut().zip(&px) {
+            *w = *w + *p / s * error;
+        }
+        covariance.add_outer(-T::one() / s, &px, &px);
+        Ok(())
+    }
+}
+
+fn dot<T: Float>(a: &[T], b: &[T]) -> T {
+    a.iter().zip(b).fold(T::zero(), |sum, (x, y)| sum + *x * *y)
+}
+
+impl<T> Algorithm<T> for BayesianLinearRegression<T>
+where
+    T: Float + Debug + Send + Sync + Sum + Datum,
+{
+    type Input = RegressionInput<T>;
+    type Output = T;
+
+    fn training_step(&self, model: &Model<T>, x: RegressionInput<T>) -> Result<(), ModelError> {
+        let label = x
+            .label
+            .ok_or_else(|| ModelError::InvalidInput("training samples need a label".to_string()))?;
+        self.update(model, &x.features, label)
+    }
+
+    fn inference_step(&self, model: &Model<T>, x: RegressionInput<T>) -> Result<T, ModelError> {
+        let x = self.augmented(model, &x.features)?;
+        Ok(dot(&x, &model.read_params()))
+    }
+
+    fn feedback_step(
+        &self,
+        model: &Model<T>,
+        x: RegressionInput<T>,
+        outcome: T,
+    ) -> Result<(), ModelError> {
+        self.update(model, &x.features, outcome)
+    }
+
+    fn contributions(
+        &self,
+        model: &Model<T>,
+        x: RegressionInput<T>,
+    ) -> Result<Contributions<T>, ModelError> {
+        self.augmented(model, &x.features)?;
+        let weights = model.read_params();
+        Ok(Contributions {
+            features: weights
+                .iter()
+                .zip(&x.features)
+                .map(|(w, x)| *w * *x)
+                .collect(),
+            bias: weights[self.dimension],
+        })
+    }
+
+    fn uncertainty(
+        &self,
+        model: &Model<T>,
+        x: RegressionInput<T>,
+    ) -> Result<Uncertainty<T>, ModelError> {
+        let x = self.augmented(model, &x.features)?;
+        let mean = dot(&x, &model.read_params());
+        let covariance = self.covariance.lock()?;
+        let variance = self.noise_variance + dot(&x, &covariance.matvec(&x));
+        Ok(Uncertainty::gaussian(mean, variance, self.z))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{assert_parameters_close, linear_stream};
+
+    #[test]
+    fn test_posterior_recovers_weights_and_narrows() {
+        let algorithm = BayesianLinearRegression::new(2, 100.0f64, 0.01);
+        let model = algorithm.prior_model();
+        let probe = RegressionInput {
+            features: vec![0.5, 0.5],
+            label: None,
+        };
+        let prior = algorithm.uncertainty(&model, probe.clone()).unwrap();
+
+        for sample in linear_stream(vec![2.0, -3.0], 1.0, 0.05, 11).take(200) {
+            let input = RegressionInput {
+                features: sample.input,
+                label: Some(sample.label),
+            };
+            algorithm.training_step(&model, input).unwrap();
+        }
+        assert_parameters_close(&model, &[2.0, -3.0, 1.0], 0.05);
+        assert!((model.read_params().group("bias").unwrap()[0] - 1.0).abs() < 0.05);
+
+        let posterior = algorithm.uncertainty(&model, probe.clone()).unwrap();
+        let mean = algorithm.inference_step(&model, probe).unwrap();
+        assert!((mean - 0.5).abs() < 0.05);
+        assert!(posterior.variance.unwrap() < prior.variance.unwrap() / 100.0);
+        assert!(posterior.variance.unwrap() >= 0.01);
+        assert!(posterior.lower.unwrap() < mean && mean < posterior.upper.unwrap());
+
+        let unlabeled = RegressionInput {
+            features: vec![1.0, 1.0],
+            label: None,
+        };
+        assert!(algorithm.training_step(&model, unlabeled).is_err());
+    }
+}
@@ -0,0 +1,287 @@
+use crate::algorithm::{Algorithm, Contributions, Datum};
+use crate::bayesian::RegressionInput;
+use crate::errors::ModelError;
+use crate::model::Model;
+use num_traits::Float;
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Per candidate split: residual sum and count left and right of it.
+const SPLIT_STATS: usize = 4;
+
+/// Online gradient boosting of a fixed number of depth-1 trees under squared
+/// loss.
+///
+/// Every feature has `candidates` split thresholds spread over one standard
+/// deviation around its running mean. Each stump accumulates, for every
+/// candidate, the residuals left by the base prediction (the label mean) and
+/// the stumps before it, and predicts with the split that explains them best.
+/// Everything lives in the model parameters, in order: the sample count, the
+/// label mean, the running mean and sum of squared deviations of every
+/// feature, then the split statistics of each stump.
+#[derive(Debug, Clone)]
+pub struct BoostedStumps {
+    dimension: usize,
+    stumps: usize,
+    candidates: usize,
+    learning_rate: f64,
+}
+
+/// Split a stump currently predicts with.
+struct Split<T> {
+    feature: usize,
+    threshold: T,
+    left: T,
+    right: T,
+}
+
+impl BoostedStumps {
+    /// `stumps` trees over `dimension` features, with three candidate
+    /// splits per feature and a learning rate of 0.3.
+    pub fn new(dimension: usize, stumps: usize) -> Self {
+        BoostedStumps {
+            dimension,
+            stumps,
+            candidates: 3,
+            learning_rate: 0.3,
+        }
+    }
+
+    /// Shrinks each stump's contribution by `learning_rate`.
+    pub fn with_learning_rate(mut self, learning_rate: f64) -> Self {
+        self.learning_rate = learning_rate;
+        self
+    }
+
+    /// Considers `candidates` thresholds per feature (at least one).
+    pub fn with_candidates(mut self, candidates: usize) -> Self {
+        self.candidates = candidates.max(1);
+        self
+    }
+
+    /// A model that has seen no samples and predicts zero.
+    pub fn initial_model<T: Float + Debug + Send + Sync>(&self) -> Model<T> {
+        Model::with_parameters(vec![T::zero(); self.len()])
+    }
+
+    fn len(&self) -> usize {
+        2 + 2 * self.dimension + self.stumps * self.stump_len()
+    }
+
+    fn stump_len(&self) -> usize {
+        self.dimension * self.candidates * SPLIT_STATS
+    }
+
+    fn check<T>(&self, params: &[T], features: &[T]) -> Result<(), ModelError> {
+        if features.len() != self.dimension {
+            return Err(ModelError::DimensionMismatch {
+                expected: self.dimension,
+                actual: features.len(),
+            });
+        }
+        if params.len() != self.len() {
+            return Err(ModelError::InvalidInput(format!(
+                "the model must hold {} values",
+                self.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Candidate thresholds of every feature, from its running statistics.
+    fn thresholds<T: Float>(&self, params: &[T]) -> Vec<T> {
+        let count = params[0];
+        let mut thresholds = Vec::with_capacity(self.dimension * self.candidates);
+        for f in 0..self.dimension {
+            let (mean, m2) = (params[2 + 2 * f], params[3 + 2 * f]);
+            let std = if count > T::one() {
+                (m2 / count).sqrt()
+            } else {
+                T::zero()
+            };
+            for k in 0..self.candidates {
+                let z = match self.candidates {
+                    1 => 0.0,
+                    n => -1.0 + 2.0 * k as f64 / (n - 1) as f64,
+                };
+                thresholds.push(mean + std * T::from(z).unwrap_or_else(T::zero));
+            }
+        }
+        thresholds
+    }
+
+    fn stump<'a, T: Float>(&self, params: &'a [T], m: usize) -> &'a [T] {
+        let start = 2 + 2 * self.dimension + m * self.stump_len();
+        &params[start..start + self.stump_len()]
+    }
+
+    /// The candidate of stump `m` that explains its residuals best, if it
+    /// has seen samples on both sides of one.
+    fn best_split<T: Float>(&self, params: &[T], m: usize, thresholds: &[T]) -> Option<Split<T>> {
+        let mut best: Option<(T, Split<T>)> = None;
+        for (c, stats) in self.stump(params, m).chunks(SPLIT_STATS).enumerate() {
+            let [left_sum, left_count, right_sum, right_count] =
+                [stats[0], stats[1], stats[2], stats[3]];
+            if left_count <= T::zero() || right_count <= T::zero() {
+                continue;
+            }
+            let gain = left_sum * left_sum / left_count + right_sum * right_sum / right_count;
+            if best.as_ref().is_none_or(|(g, _)| gain > *g) {
+                let split = Split {
+                    feature: c / self.candidates,
+                    threshold: thresholds[c],
+                    left: left_sum / left_count,
+                    right: right_sum / right_count,
+                };
+                best = Some((gain, split));
+            }
+        }
+        best.map(|(_, split)| split)
+    }
+
+    /// Shrunk output of stump `m` for `features`, with its feature.
+    fn stump_output<T: Float>(
+        &self,
+        params: &[T],
+        m: usize,
+        thresholds: &[T],
+        features: &[T],
+    ) -> Option<(usize, T)> {
+        let split = self.best_split(params, m, thresholds)?;
+        let value = match features[split.feature] <= split.threshold {
+            true => split.left,
+            false => split.right,
+        };
+        Some((
+            split.feature,
+            value * T::from(self.learning_rate).unwrap_or_else(T::one),
+        ))
+    }
+
+    fn predict<T: Float>(&self, params: &[T], features: &[T]) -> T {
+        let thresholds = self.thresholds(params);
+        (0..self.stumps)
+            .filter_map(|m| self.stump_output(params, m, &thresholds, features))
+            .fold(params[1], |sum, (_, value)| sum + value)
+    }
+}
+
+impl<T> Algorithm<T> for BoostedStumps
+where
+    T: Float + Debug + Send + Sync + Sum + Datum,
+{
+    type Input = RegressionInput<T>;
+    type Output = T;
+
+    fn training_step(&self, model: &Model<T>, x: RegressionInput<T>) -> Result<(), ModelError> {
+        let label = x
+            .label
+            .ok_or_else(|| ModelError::InvalidInput("training samples need a label".to_string()))?;
+        self.check(&model.read_params(), &x.features)?;
+        let mut params = model.write_trainable();
+
+        let thresholds = self.thresholds(&params);
+        let mut prediction = params[1];
+        for m in 0..self.stumps {
+            let residual = label - prediction;
+            let start = 2 + 2 * self.dimension + m * self.stump_len();
+            for (c, threshold) in thresholds.iter().enumerate() {
+                let side = match x.features[c / self.candidates] <= *threshold {
+                    true => 0,
+                    false => 2,
+                };
+                let stats = start + c * SPLIT_STATS + side;
+                params[stats] = params[stats] + residual;
+                params[stats + 1] = params[stats + 1] + T::one();
+            }
+            if let Some((_, value)) = self.stump_output(&params, m, &thresholds, &x.features) {
+                prediction = prediction + value;
+            }
+        }
+
+        // Welford updates of the label mean and the feature statistics.
+        let count = params[0] + T::one();
+        params[0] = count;
+        params[1] = params[1] + (label - params[1]) / count;
+        for (f, value) in x.features.iter().enumerate() {
+            let mean = params[2 + 2 * f];
+            let updated = mean + (*value - mean) / count;
+            params[3 + 2 * f] = params[3 + 2 * f] + (*value - mean) * (*value - updated);
+            params[2 + 2 * f] = updated;
+        }
+        Ok(())
+    }
+
+    fn inference_step(&self, model: &Model<T>, x: RegressionInput<T>) -> Result<T, ModelError> {
+        let params = model.read_params();
+        self.check(&params, &x.features)?;
+        Ok(self.predict(&params, &x.features))
+    }
+
+    fn contributions(
+        &self,
+        model: &Model<T>,
+        x: RegressionInput<T>,
+    ) -> Result<Contributions<T>, ModelError> {
+        let params = model.read_params();
+        self.check(&params, &x.features)?;
+        let thresholds = self.thresholds(&params);
+        let mut features = vec![T::zero(); self.dimension];
+        for m in 0..self.stumps {
+            if let Some((f, value)) = self.stump_output(&params, m, &thresholds, &x.features) {
+                features[f] = features[f] + value;
+            }
+        }
+        Ok(Contributions {
+            features,
+            bias: params[1],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::uniform_stream;
+
+    #[test]
+    fn test_stumps_learn_a_step_function() {
+        let algorithm = BoostedStumps::new(2, 10);
+        let model = algorithm.initial_model();
+        let sample = |x: f64, noise: f64| RegressionInput {
+            features: vec![x, noise],
+            label: Some(if x > 0.0 { 2.0 } else { -1.0 }),
+        };
+
+        let mut stream = uniform_stream::<f64>(-1.0, 1.0, 3);
+        for _ in 0..2000 {
+            let (x, noise) = (stream.next().unwrap(), stream.next().unwrap());
+            algorithm.training_step(&model, sample(x, noise)).unwrap();
+        }
+
+        for (x, expected) in [(-0.6, -1.0), (0.6, 2.0)] {
+            let prediction = algorithm.inference_step(&model, sample(x, 0.0)).unwrap();
+            assert!(
+                (prediction - expected).abs() < 0.2,
+                "{} -> {}",
+                x,
+                prediction
+            );
+        }
+        let contributions = algorithm.contributions(&model, sample(0.6, 0.0)).unwrap();
+        assert!(contributions.features[0].abs() > 10.0 * contributions.features[1].abs());
+        let wrong = RegressionInput {
+            features: vec![1.0],
+            label: None,
+        };
+        assert!(algorithm.inference_step(&model, wrong).is_err());
+
+        let version = model.version();
+        let wrong = RegressionInput {
+            features: vec![1.0],
+            label: Some(1.0),
+        };
+        assert!(algorithm.training_step(&model, wrong).is_err());
+        assert_eq!(model.version(), version);
+    }
+}
@@ -0,0 +1,302 @@
+use crate::encryption::{self, EncryptionKey};
+use crate::errors::ModelError;
+use crate::handlers::versions::BINCODE_CONTENT_TYPE;
+use crate::model::{ModelFormat, SavedModel, MODEL_MAGIC};
+use crate::safetensors::{self, SAFETENSORS_CONTENT_TYPE};
+use num_traits::Float;
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::time::Duration;
+
+/// Remote weights a fresh replica starts from, see
+/// [`ServerBuilder::with_model_url`].
+///
+/// The URL is `http(s)://`, or `s3://<bucket>/<key>` for an object that can
+/// be read without credentials; private objects need a presigned https URL.
+/// The artifact is anything `PUT /model/weights` accepts: a saved model in
+/// JSON or bincode, or safetensors, told apart by content type, extension
+/// or magic bytes, and possibly encrypted (see [`EncryptionKey`]).
+///
+/// Downloads fail closed: without an expected SHA-256 the artifact is
+/// refused unless `allow_unverified` is set, and bodies larger than
+/// `max_bytes` are abandoned midway.
+///
+/// [`ServerBuilder::with_model_url`]: crate::server::ServerBuilder::with_model_url
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelSource {
+    pub url: String,
+    /// Expected SHA-256 of the downloaded bytes, in hex.
+    pub sha256: Option<String>,
+    /// Loads the artifact even when no `sha256` is set.
+    pub allow_unverified: bool,
+    /// Largest body accepted, in bytes; 1 GiB by default.
+    pub max_bytes: u64,
+    /// Endpoint `s3://` URLs are resolved against, path-style (e.g. a
+    /// MinIO server); AWS's virtual-hosted endpoint by default.
+    pub s3_endpoint: Option<String>,
+    pub timeout: Duration,
+}
+
+impl ModelSource {
+    pub fn new(url: impl Into<String>) -> Self {
+        ModelSource {
+            url: url.into(),
+            sha256: None,
+            allow_unverified: false,
+            max_bytes: DEFAULT_MAX_BYTES,
+            s3_endpoint: None,
+            timeout: Duration::from_secs(300),
+        }
+    }
+
+    pub fn with_sha256(mut self, sha256: impl Into<String>) -> Self {
+        self.sha256 = Some(sha256.into());
+        self
+    }
+
+    /// Accepts an artifact without a checksum to verify it against.
+    pub fn allow_unverified(mut self) -> Self {
+        self.allow_unverified = true;
+        self
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn with_s3_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.s3_endpoint = Some(endpoint.into().trim_end_matches('/').to_string());
+        self
+    }
+
+    /// The URL the artifact is downloaded from.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oml::bootstrap::ModelSource;
+    ///
+    /// let source = ModelSource::new("s3://models/ctr/v3.bin");
+    /// assert_eq!(
+    ///     source.http_url().unwrap(),
+    ///     "https://models.s3.amazonaws.com/ctr/v3.bin"
+    /// );
+    /// let minio = source.with_s3_endpoint("http://minio:9000/");
+    /// assert_eq!(minio.http_url().unwrap(), "http://minio:9000/models/ctr/v3.bin");
+    /// ```
+    pub fn http_url(&self) -> Result<String, ModelError> {
+        if self.url.starts_with("http://") || self.url.starts_with("https://") {
+            return Ok(self.url.clone());
+        }
+        let object = self.url.strip_prefix("s3://").and_then(|rest| {
+            let (bucket, key) = rest.split_once('/')?;
+            Some((bucket, key)).filter(|_| !bucket.is_empty() && !key.is_empty())
+        });
+        match (object, &self.s3_endpoint) {
+            (Some((bucket, key)), Some(endpoint)) => Ok(format!("{}/{}/{}", endpoint, bucket, key)),
+            (Some((bucket, key)), None) => {
+                Ok(format!("https://{}.s3.amazonaws.com/{}", bucket, key))
+            }
+            (None, _) => Err(ModelError::InvalidInput(format!(
+                "unsupported model URL {}, expected http(s)://... or s3://<bucket>/<key>",
+                self.url
+            ))),
+        }
+    }
+
+    /// Checks `bytes` against the expected checksum; without one, fails
+    /// unless `allow_unverified` is set.
+    pub fn verify(&self, bytes: &[u8]) -> Result<(), ModelError> {
+        self.check_verifiable()?;
+        let Some(expected) = &self.sha256 else {
+            return Ok(());
+        };
+        let actual = sha256_hex(bytes);
+        if !actual.eq_ignore_ascii_case(expected.trim()) {
+            return Err(ModelError::RemoteError(format!(
+                "checksum mismatch for {}: expected {}, got {}",
+                self.url, expected, actual
+            )));
+        }
+        Ok(())
+    }
+
+    fn check_verifiable(&self) -> Result<(), ModelError> {
+        match self.sha256.is_some() || self.allow_unverified {
+            true => Ok(()),
+            false => Err(ModelError::InvalidInput(format!(
+                "no SHA-256 given for {}; set one or allow unverified downloads",
+                self.url
+            ))),
+        }
+    }
+
+    fn too_large(&self, size: u64) -> ModelError {
+        ModelError::RemoteError(format!(
+            "{} is larger than the {} byte limit ({} bytes)",
+            self.url, self.max_bytes, size
+        ))
+    }
+
+    /// Downloads and verifies the artifact, returning it with its content
+    /// type.
+    pub async fn download(&self) -> Result<(Vec<u8>, Option<String>), ModelError> {
+        self.check_verifiable()?;
+        let client = reqwest::Client::builder().timeout(self.timeout).build()?;
+        let mut response = client
+            .get(self.http_url()?)
+            .send()
+            .await?
+            .error_for_status()?;
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        if let Some(size) = response.content_length().filter(|&n| n > self.max_bytes) {
+            return Err(self.too_large(size));
+        }
+        // The declared length may be missing or wrong, so the body is capped
+        // as it streams in.
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            let size = (bytes.len() + chunk.len()) as u64;
+            if size > self.max_bytes {
+                return Err(self.too_large(size));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        self.verify(&bytes)?;
+        Ok((bytes, content_type))
+    }
+
+    /// Downloads, verifies and decodes the artifact, decrypting it with
+    /// `key` if it is encrypted.
+    ///
+    /// Blocks: the download runs on a thread and runtime of its own, so
+    /// this can be called from synchronous code running on an async
+    /// runtime, like [`ServerBuilder::build`].
+    ///
+    /// [`ServerBuilder::build`]: crate::server::ServerBuilder::build
+    pub fn fetch<T>(&self, key: Option<&EncryptionKey>) -> Result<SavedModel<T>, ModelError>
+    where
+        T: Float + Serialize + DeserializeOwned,
+    {
+        let (bytes, content_type) = std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()?
+                        .block_on(self.download())
+                })
+                .join()
+                .map_err(|_| ModelError::RemoteError("the download panicked".to_string()))?
+        })?;
+        let bytes = encryption::open(bytes, key)?;
+        let url = self.http_url()?;
+        let path = Path::new(url.split(['?', '#']).next().unwrap_or_default());
+        let content_type = content_type.as_deref();
+        if content_type == Some(SAFETENSORS_CONTENT_TYPE)
+            || path.extension().is_some_and(|e| e == "safetensors")
+        {
+            return safetensors::decode(&bytes);
+        }
+        let bincode = bytes.starts_with(MODEL_MAGIC)
+            || matches!(
+                content_type,
+                Some(BINCODE_CONTENT_TYPE | "application/octet-stream")
+            )
+            || ModelFormat::from_path(path) == ModelFormat::Bincode;
+        let format = match bincode {
+            true => ModelFormat::Bincode,
+            false => ModelFormat::Json,
+        };
+        SavedModel::decode(&bytes, format)
+    }
+}
+
+const DEFAULT_MAX_BYTES: u64 = 1 << 30;
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Model;
+    use crate::server::ServerBuilder;
+    use crate::test::ScalingAlgorithm;
+    use actix_web::{web, App, HttpResponse, HttpServer};
+
+    #[actix_rt::test]
+    async fn test_replica_warm_started_from_verified_download() {
+        let artifact = Model::with_parameters(vec![3.0f64, 4.0])
+            .to_saved()
+            .encode(ModelFormat::Bincode)
+            .unwrap();
+        let checksum = sha256_hex(&artifact);
+        let served = artifact.clone();
+        let origin = HttpServer::new(move || {
+            let served = served.clone();
+            App::new().route(
+                "/models/ctr",
+                web::get().to(move || {
+                    let served = served.clone();
+                    async move { HttpResponse::Ok().body(served) }
+                }),
+            )
+        })
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let url = format!("http://{}/models/ctr", origin.addrs()[0]);
+        let running = origin.run();
+        let handle = running.handle();
+        actix_rt::spawn(running);
+        // Serving starts once the runtime gets to it, before the builds below
+        // block this thread.
+        let body = reqwest::get(&url).await.unwrap().bytes().await.unwrap();
+        assert_eq!(body.as_ref(), artifact.as_slice());
+
+        let replica = |source: ModelSource| {
+            ServerBuilder::new()
+                .bind("127.0.0.1:0")
+                .model(Model::with_parameters(vec![0.0f64, 0.0]))
+                .algorithm(ScalingAlgorithm)
+                .with_model_url(source)
+                .build()
+        };
+        // Told apart from JSON by its magic bytes alone.
+        let server = replica(ModelSource::new(&url).with_sha256(checksum.to_uppercase())).unwrap();
+        assert_eq!(server.state().model.read_params().as_slice(), &[3.0, 4.0]);
+        assert_eq!(
+            server.state().metrics.snapshot().counters["model_bootstraps"],
+            1
+        );
+
+        // A corrupted or replaced artifact keeps the replica from starting.
+        let tampered = ModelSource::new(&url).with_sha256("00".repeat(32));
+        assert!(replica(tampered).is_err());
+        // So does a missing checksum, unless explicitly allowed.
+        assert!(replica(ModelSource::new(&url)).is_err());
+        let unverified = replica(ModelSource::new(&url).allow_unverified()).unwrap();
+        assert_eq!(
+            unverified.state().model.read_params().as_slice(),
+            &[3.0, 4.0]
+        );
+        // And an artifact over the size limit.
+        let capped = ModelSource::new(&url)
+            .with_sha256(&checksum)
+            .with_max_bytes(artifact.len() as u64 - 1);
+        assert!(replica(capped).is_err());
+        assert!(replica(ModelSource::new(format!("{}/missing", url))).is_err());
+        assert!(replica(ModelSource::new("ftp://models/ctr.bin")).is_err());
+        handle.stop(false).await;
+    }
+}
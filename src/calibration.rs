@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+
+/// Number of equal-width score bins used by [`CalibrationMethod::Isotonic`].
+const ISOTONIC_BINS: usize = 10;
+
+/// How raw scores are mapped to probabilities.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CalibrationMethod {
+    /// Logistic fit `sigmoid(a * score + b)` learned by online gradient descent.
+    Platt { learning_rate: f64 },
+    /// Monotone step function over score bins in `[0, 1]` (pool-adjacent-violators).
+    Isotonic,
+}
+
+impl Default for CalibrationMethod {
+    fn default() -> Self {
+        CalibrationMethod::Platt {
+            learning_rate: 0.05,
+        }
+    }
+}
+
+/// Online probability calibration stage applied to inference outputs.
+///
+/// The calibrator learns from labeled `(raw score, 0/1 outcome)` pairs as
+/// they arrive and never touches the model itself.
+#[derive(Debug, Clone)]
+pub struct Calibrator {
+    method: CalibrationMethod,
+    a: f64,
+    b: f64,
+    /// Per-bin `(sum of labels, count)` for the isotonic fit.
+    bins: Vec<(f64, u64)>,
+    observations: u64,
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+fn bin_of(score: f64) -> usize {
+    ((score.clamp(0.0, 1.0) * ISOTONIC_BINS as f64) as usize).min(ISOTONIC_BINS - 1)
+}
+
+impl Calibrator {
+    pub fn new(method: CalibrationMethod) -> Self {
+        Calibrator {
+            method,
+            a: 1.0,
+            b: 0.0,
+            bins: vec![(0.0, 0); ISOTONIC_BINS],
+            observations: 0,
+        }
+    }
+
+    pub fn method(&self) -> CalibrationMethod {
+        self.method
+    }
+
+    pub fn observations(&self) -> u64 {
+        self.observations
+    }
+
+    /// Learns from one raw score and its binary outcome.
+    pub fn update(&mut self, score: f64, positive: bool) {
+        if !score.is_finite() {
+            return;
+        }
+        let label = if positive { 1.0 } else { 0.0 };
+        match self.method {
+            CalibrationMethod::Platt { learning_rate } => {
+                let error = sigmoid(self.a * score + self.b) - label;
+                self.a -= learning_rate * error * score;
+                self.b -= learning_rate * error;
+            }
+            CalibrationMethod::Isotonic => {
+                let bin = &mut self.bins[bin_of(score)];
+                bin.0 += label;
+                bin.1 += 1;
+            }
+        }
+        self.observations += 1;
+    }
+
+    /// Maps a raw score to a calibrated probability. Before any observation
+    /// the score is returned unchanged.
+    pub fn calibrate(&self, score: f64) -> f64 {
+        if self.observations == 0 {
+            return score;
+        }
+        match self.method {
+            CalibrationMethod::Platt { .. } => sigmoid(self.a * score + self.b),
+            CalibrationMethod::Isotonic => self.isotonic(score),
+        }
+    }
+
+    fn isotonic(&self, score: f64) -> f64 {
+        // Pool adjacent violators over the non-empty bins: blocks of (sum, count, last bin).
+        let mut blocks: Vec<(f64, f64, usize)> = Vec::new();
+        for (index, &(sum, count)) in self.bins.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            blocks.push((sum, count as f64, index));
+            while blocks.len() > 1 {
+                let (s2, c2, i2) = blocks[blocks.len() - 1];
+                let (s1, c1, _) = blocks[blocks.len() - 2];
+                if s1 / c1 <= s2 / c2 {
+                    break;
+                }
+                blocks.pop();
+                *blocks.last_mut().unwrap() = (s1 + s2, c1 + c2, i2);
+            }
+        }
+        let bin = bin_of(score);
+        blocks
+            .iter()
+            .find(|(_, _, last)| *last >= bin)
+            .or(blocks.last())
+            .map(|(sum, count, _)| sum / count)
+            .unwrap_or(score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_platt_scaling_learns_offset() {
+        let mut calibrator = Calibrator::new(CalibrationMethod::default());
+        assert_eq!(calibrator.calibrate(0.3), 0.3);
+        // Scores of 0.9 are only right 20% of the time.
+        for i in 0..5000 {
+            calibrator.update(0.9, i % 5 == 0);
+        }
+        assert!((calibrator.calibrate(0.9) - 0.2).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_isotonic_is_monotone() {
+        let mut calibrator = Calibrator::new(CalibrationMethod::Isotonic);
+        calibrator.update(0.15, true);
+        calibrator.update(0.15, false);
+        calibrator.update(0.55, false);
+        calibrator.update(0.95, true);
+        // The violating 0.15 / 0.55 bins are pooled to 1/3.
+        assert!((calibrator.calibrate(0.1) - 1.0 / 3.0).abs() < 1e-12);
+        assert!((calibrator.calibrate(0.5) - 1.0 / 3.0).abs() < 1e-12);
+        assert_eq!(calibrator.calibrate(0.99), 1.0);
+    }
+}
@@ -0,0 +1,372 @@
+use crate::algorithm::{Algorithm, Datum};
+use crate::errors::ModelError;
+use crate::model::Model;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Configuration of a [`CanaryRollout`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanaryConfig {
+    /// Shares of the inference traffic served by the candidate, stage by
+    /// stage; it is promoted after the last one.
+    pub stages: Vec<f64>,
+    /// Requests the candidate serves in a stage before moving to the next.
+    pub requests_per_stage: u64,
+    /// Rolls back if the candidate's mean latency exceeds the live model's
+    /// by this factor.
+    pub max_latency_ratio: f64,
+    /// Rolls back if the candidate's mean absolute error on labeled
+    /// outcomes exceeds the live model's by this factor.
+    pub max_error_ratio: f64,
+    /// Observations of both models a guardrail needs before it applies.
+    pub min_samples: u64,
+}
+
+impl Default for CanaryConfig {
+    fn default() -> Self {
+        CanaryConfig {
+            stages: vec![0.01, 0.1, 0.5, 1.0],
+            requests_per_stage: 100,
+            max_latency_ratio: 1.5,
+            max_error_ratio: 1.2,
+            min_samples: 20,
+        }
+    }
+}
+
+/// Where a rollout stands, as reported by `GET /admin/canary`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum RolloutStatus {
+    Idle,
+    Running {
+        stage: usize,
+        fraction: f64,
+    },
+    /// The candidate replaced the live parameters as `version`.
+    Promoted {
+        version: u64,
+    },
+    RolledBack {
+        reason: String,
+    },
+}
+
+/// Running means of one side of the comparison.
+#[derive(Debug, Default)]
+struct Observed {
+    latency_us: f64,
+    latencies: u64,
+    error: f64,
+    errors: u64,
+}
+
+impl Observed {
+    fn mean_latency(&self) -> f64 {
+        self.latency_us / self.latencies.max(1) as f64
+    }
+
+    fn mean_error(&self) -> f64 {
+        self.error / self.errors.max(1) as f64
+    }
+}
+
+#[derive(Debug)]
+struct Rollout<T>
+where
+    T: Float + Debug + Send + Sync,
+{
+    candidate: Arc<Model<T>>,
+    stage: usize,
+    /// Requests routed in the current stage, and those sent to the candidate.
+    routed: u64,
+    to_candidate: u64,
+    live: Observed,
+    canary: Observed,
+}
+
+/// Shifts inference traffic from the live parameters to a candidate set
+/// gradually (by default 1%, 10%, 50%, then 100% of the requests), moving
+/// on once the candidate served enough requests of a stage, and promoting
+/// it after the last one.
+///
+/// Guardrails compare the candidate with the live model on latency and,
+/// when labeled outcomes come in, on the error of both models' predictions
+/// for the same input; a violation discards the candidate. The candidate
+/// is frozen while the live model keeps training, and promotion keeps the
+/// replaced parameters as a restore point.
+#[derive(Debug)]
+pub struct CanaryRollout<T>
+where
+    T: Float + Debug + Send + Sync,
+{
+    config: CanaryConfig,
+    state: Mutex<(Option<Rollout<T>>, RolloutStatus)>,
+}
+
+impl<T> CanaryRollout<T>
+where
+    T: Float + Debug + Send + Sync + Sum,
+{
+    pub fn new(config: CanaryConfig) -> Self {
+        CanaryRollout {
+            config,
+            state: Mutex::new((None, RolloutStatus::Idle)),
+        }
+    }
+
+    pub fn config(&self) -> &CanaryConfig {
+        &self.config
+    }
+
+    pub fn status(&self) -> Result<RolloutStatus, ModelError> {
+        Ok(self.state.lock()?.1.clone())
+    }
+
+    /// Starts rolling out `parameters` as a replacement of `live`'s.
+    pub fn start(&self, parameters: Vec<T>, live: &Model<T>) -> Result<RolloutStatus, ModelError> {
+        let expected = live.read_params().len();
+        if parameters.len() != expected {
+            return Err(ModelError::InvalidInput(format!(
+                "expected {} parameters, got {}",
+                expected,
+                parameters.len()
+            )));
+        }
+        if self.config.stages.is_empty() {
+            return Err(ModelError::InvalidInput(
+                "a canary rollout needs at least one stage".to_string(),
+            ));
+        }
+        let mut state = self.state.lock()?;
+        if state.0.is_some() {
+            return Err(ModelError::InvalidInput(
+                "a canary rollout is already running".to_string(),
+            ));
+        }
+        let candidate = Model::with_layout(live.layout().clone(), parameters)?;
+        state.0 = Some(Rollout {
+            candidate: Arc::new(candidate),
+            stage: 0,
+            routed: 0,
+            to_candidate: 0,
+            live: Observed::default(),
+            canary: Observed::default(),
+        });
+        state.1 = self.running(0);
+        Ok(state.1.clone())
+    }
+
+    /// Stops a running rollout, keeping the live parameters. Returns whether
+    /// one was running.
+    pub fn abort(&self, reason: &str) -> Result<bool, ModelError> {
+        let mut state = self.state.lock()?;
+        match state.0.take() {
+            Some(_) => {
+                state.1 = RolloutStatus::RolledBack {
+                    reason: reason.to_string(),
+                };
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn running(&self, stage: usize) -> RolloutStatus {
+        RolloutStatus::Running {
+            stage,
+            fraction: self.config.stages[stage],
+        }
+    }
+
+    /// Picks the model serving the next request: the candidate for the
+    /// current stage's share of the requests, `None` for the live model.
+    pub fn route(&self) -> Result<Option<Arc<Model<T>>>, ModelError> {
+        let mut state = self.state.lock()?;
+        let Some(rollout) = state.0.as_mut() else {
+            return Ok(None);
+        };
+        rollout.routed += 1;
+        let share = self.config.stages[rollout.stage];
+        if (rollout.to_candidate as f64) < share * rollout.routed as f64 {
+            rollout.to_candidate += 1;
+            return Ok(Some(rollout.candidate.clone()));
+        }
+        Ok(None)
+    }
+
+    /// Records how long a request served by the candidate (or the live
+    /// model) took, then checks the guardrails. Returns the new status if
+    /// the rollout moved on, was promoted or rolled back.
+    pub fn observe_latency(
+        &self,
+        candidate: bool,
+        elapsed: Duration,
+        live: &Model<T>,
+    ) -> Result<Option<RolloutStatus>, ModelError> {
+        self.observe(live, |rollout| {
+            let side = match candidate {
+                true => &mut rollout.canary,
+                false => &mut rollout.live,
+            };
+            side.latency_us += elapsed.as_secs_f64() * 1e6;
+            side.latencies += 1;
+        })
+    }
+
+    /// Scores the live model's `prediction` and the candidate's for a
+    /// labeled outcome, then checks the guardrails like
+    /// [`CanaryRollout::observe_latency`]. Runs inference on the candidate,
+    /// so call it from a blocking context.
+    pub fn observe_outcome<A: Algorithm<T>>(
+        &self,
+        algorithm: &A,
+        live: &Model<T>,
+        input: A::Input,
+        prediction: f64,
+        label: f64,
+    ) -> Result<Option<RolloutStatus>, ModelError> {
+        let Some(candidate) = self.state.lock()?.0.as_ref().map(|r| r.candidate.clone()) else {
+            return Ok(None);
+        };
+        let candidate_prediction = algorithm.inference_step(&candidate, input)?;
+        let live_error = (prediction - label).abs();
+        let canary_error = (candidate_prediction.score().unwrap_or(f64::NAN) - label).abs();
+        self.observe(live, |rollout| {
+            rollout.live.error += live_error;
+            rollout.live.errors += 1;
+            rollout.canary.error += canary_error;
+            rollout.canary.errors += 1;
+        })
+    }
+
+    fn observe(
+        &self,
+        live: &Model<T>,
+        record: impl FnOnce(&mut Rollout<T>),
+    ) -> Result<Option<RolloutStatus>, ModelError> {
+        let mut state = self.state.lock()?;
+        let Some(rollout) = state.0.as_mut() else {
+            return Ok(None);
+        };
+        record(rollout);
+
+        let config = &self.config;
+        let enough = |a: u64, b: u64| a >= config.min_samples && b >= config.min_samples;
+        let (live_side, canary) = (&rollout.live, &rollout.canary);
+        let violation = if enough(live_side.latencies, canary.latencies)
+            && canary.mean_latency() > config.max_latency_ratio * live_side.mean_latency()
+        {
+            Some(format!(
+                "mean latency {:.0}us against {:.0}us",
+                canary.mean_latency(),
+                live_side.mean_latency()
+            ))
+        } else if enough(live_side.errors, canary.errors)
+            && (canary.mean_error().is_nan()
+                || canary.mean_error() > config.max_error_ratio * live_side.mean_error())
+        {
+            Some(format!(
+                "mean absolute error {:.4} against {:.4}",
+                canary.mean_error(),
+                live_side.mean_error()
+            ))
+        } else {
+            None
+        };
+
+        let status = if let Some(reason) = violation {
+            state.0 = None;
+            RolloutStatus::RolledBack { reason }
+        } else if rollout.to_candidate < config.requests_per_stage {
+            return Ok(None);
+        } else if rollout.stage + 1 < config.stages.len() {
+            rollout.stage += 1;
+            rollout.routed = 0;
+            rollout.to_candidate = 0;
+            self.running(rollout.stage)
+        } else {
+            let candidate = rollout.candidate.snapshot().to_vec();
+            state.0 = None;
+            RolloutStatus::Promoted {
+                version: live.replace_parameters(candidate)?,
+            }
+        };
+        state.1 = status.clone();
+        Ok(Some(status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::ScalingAlgorithm;
+
+    fn rollout() -> CanaryRollout<f64> {
+        CanaryRollout::new(CanaryConfig {
+            stages: vec![0.1, 0.5, 1.0],
+            requests_per_stage: 10,
+            min_samples: 5,
+            ..CanaryConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_stages_advance_to_promotion() {
+        let live = Model::with_parameters(vec![1.0]);
+        let canary = rollout();
+        assert!(canary.route().unwrap().is_none());
+        canary.start(vec![1.1], &live).unwrap();
+        assert!(canary.start(vec![1.1], &live).is_err());
+
+        let mut served = [0, 0, 0];
+        while let RolloutStatus::Running { stage, .. } = canary.status().unwrap() {
+            let candidate = canary.route().unwrap().is_some();
+            served[stage] += 1;
+            let latency = Duration::from_millis(if candidate { 11 } else { 10 });
+            canary.observe_latency(candidate, latency, &live).unwrap();
+            if served[stage] % 3 == 0 {
+                canary
+                    .observe_outcome(&ScalingAlgorithm, &live, 1.0, 1.0, 1.1)
+                    .unwrap();
+            }
+        }
+        // 10 candidate requests per stage, at 10%, 50% and 100% of the traffic.
+        assert_eq!(served, [91, 19, 10]);
+        let status = canary.status().unwrap();
+        assert_eq!(status, RolloutStatus::Promoted { version: 1 });
+        assert_eq!(live.read_params()[0], 1.1);
+        assert_eq!(live.restore_points(), vec![0]);
+    }
+
+    #[test]
+    fn test_guardrail_violation_rolls_back() {
+        let live = Model::with_parameters(vec![1.0]);
+        let canary = rollout();
+        canary.start(vec![3.0], &live).unwrap();
+        let mut changes = Vec::new();
+        for _ in 0..6 {
+            changes.extend(
+                canary
+                    .observe_outcome(&ScalingAlgorithm, &live, 1.0, 1.0, 1.2)
+                    .unwrap(),
+            );
+        }
+        assert!(matches!(changes[..], [RolloutStatus::RolledBack { .. }]));
+        assert!(canary.route().unwrap().is_none());
+        assert_eq!(live.read_params()[0], 1.0);
+
+        canary.start(vec![1.0], &live).unwrap();
+        for candidate in [true, false].repeat(5) {
+            let latency = Duration::from_millis(if candidate { 30 } else { 10 });
+            canary.observe_latency(candidate, latency, &live).unwrap();
+        }
+        let status = canary.status().unwrap();
+        assert!(matches!(status, RolloutStatus::RolledBack { .. }));
+        assert!(!canary.abort("manual").unwrap());
+    }
+}
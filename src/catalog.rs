@@ -0,0 +1,550 @@
+use crate::algorithm::Algorithm;
+use crate::errors::ModelError;
+use crate::model::Model;
+use crate::quantization::QuantizedModel;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Header selecting the registered model serving an inference request.
+pub const MODEL_HEADER: &str = "X-Oml-Model";
+/// Header selecting a historical version of that model; the live one otherwise.
+pub const MODEL_VERSION_HEADER: &str = "X-Oml-Model-Version";
+
+/// A model together with the algorithm serving it.
+pub type Servable<T, A> = (Arc<Model<T>>, Arc<A>);
+
+/// Body of `POST /models/{name}`, passed to the catalog's [`ModelFactory`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelSpec<T> {
+    /// Initial parameters, if the caller provides them.
+    pub parameters: Option<Vec<T>>,
+    /// Free-form settings interpreted by the factory.
+    #[serde(default)]
+    pub config: serde_json::Value,
+}
+
+/// Builds the model and algorithm of a catalog entry created at runtime.
+pub type ModelFactory<T, A> =
+    Box<dyn Fn(&ModelSpec<T>) -> Result<(Model<T>, A), ModelError> + Send + Sync>;
+
+/// How the parameters of a catalog model version came to be, reported by
+/// `GET /models/{name}/lineage`. Times are milliseconds since the Unix epoch.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LineageRecord {
+    /// Snapshot version; `None` for the live parameters.
+    pub version: Option<u64>,
+    /// Version the parameters were trained on from, if any.
+    pub parent: Option<u64>,
+    /// Rust type of the algorithm in force.
+    pub algorithm: String,
+    /// Settings the model was created with (see [`ModelSpec::config`]).
+    pub config: serde_json::Value,
+    /// Training samples applied since the parent.
+    pub samples: u64,
+    pub first_sample_ms: Option<u64>,
+    pub last_sample_ms: Option<u64>,
+    /// Where parameters imported since the parent came from.
+    pub source: Option<String>,
+    /// When the snapshot was taken; now for the live parameters.
+    pub recorded_at_ms: u64,
+}
+
+/// A catalog model removed from serving by [`ModelCatalog::archive`],
+/// listed by `GET /models/archived`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedEntry {
+    pub name: String,
+    /// When the model was archived, in milliseconds since the Unix epoch.
+    pub archived_at_ms: u64,
+    /// Versions still retained, oldest first.
+    pub versions: Vec<u64>,
+}
+
+/// A retained version, quantized if the catalog stores them so.
+enum Frozen<T>
+where
+    T: Float + Debug + Send + Sync,
+{
+    Full(Arc<Model<T>>),
+    Quantized(QuantizedModel<T>),
+}
+
+struct CatalogEntry<T, A>
+where
+    T: Float + Debug + Send + Sync,
+{
+    live: Arc<Model<T>>,
+    algorithm: Arc<A>,
+    /// Frozen parameter snapshots by version, oldest first.
+    versions: BTreeMap<u64, Frozen<T>>,
+    /// Lineage of every snapshot taken, including those no longer retained.
+    lineage: Vec<LineageRecord>,
+    /// Lineage of the live parameters since the last snapshot.
+    pending: Mutex<LineageRecord>,
+    /// When the model was archived; archived models are kept but not served.
+    archived_at_ms: Option<u64>,
+}
+
+impl<T, A> CatalogEntry<T, A>
+where
+    T: Float + Debug + Send + Sync,
+{
+    fn new(live: Arc<Model<T>>, algorithm: Arc<A>, config: serde_json::Value) -> Self {
+        CatalogEntry {
+            live,
+            algorithm,
+            versions: BTreeMap::new(),
+            lineage: Vec::new(),
+            pending: Mutex::new(LineageRecord {
+                algorithm: std::any::type_name::<A>().to_string(),
+                config,
+                ..LineageRecord::default()
+            }),
+            archived_at_ms: None,
+        }
+    }
+
+    fn pending(&self) -> std::sync::MutexGuard<'_, LineageRecord> {
+        self.pending.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Named models, each with a bounded history of frozen versions, that
+/// inference requests can target through [`MODEL_HEADER`] and
+/// [`MODEL_VERSION_HEADER`], or through the `/models/{name}/...` routes,
+/// which also train them.
+///
+/// With a [`ModelFactory`], independent models (one per product, say) can
+/// be created and deleted at runtime. Models can also be archived instead
+/// of deleted: they stop serving but keep their versions and lineage, and
+/// can be restored later.
+pub struct ModelCatalog<T, A>
+where
+    T: Float + Debug + Send + Sync,
+{
+    max_versions: usize,
+    /// Whether snapshots are stored as int8.
+    quantized: bool,
+    entries: RwLock<BTreeMap<String, CatalogEntry<T, A>>>,
+    factory: Option<ModelFactory<T, A>>,
+}
+
+impl<T, A> ModelCatalog<T, A>
+where
+    T: Float + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    /// Creates an empty catalog keeping up to `max_versions` snapshots per model.
+    pub fn new(max_versions: usize) -> Self {
+        ModelCatalog {
+            max_versions,
+            quantized: false,
+            entries: RwLock::new(BTreeMap::new()),
+            factory: None,
+        }
+    }
+
+    /// Stores retained versions as int8 (see [`QuantizedModel`]), cutting
+    /// their memory at the cost of dequantizing them for every request and
+    /// of a small error.
+    pub fn with_quantized_versions(mut self) -> Self {
+        self.quantized = true;
+        self
+    }
+
+    /// Lets [`ModelCatalog::create`] build models with `factory`.
+    pub fn with_factory(
+        mut self,
+        factory: impl Fn(&ModelSpec<T>) -> Result<(Model<T>, A), ModelError> + Send + Sync + 'static,
+    ) -> Self {
+        self.factory = Some(Box::new(factory));
+        self
+    }
+
+    /// Builds a model from `spec` with the factory and registers it as
+    /// `name`, which must be free.
+    pub fn create(&self, name: &str, spec: &ModelSpec<T>) -> Result<(), ModelError> {
+        let factory = self.factory.as_ref().ok_or_else(|| {
+            ModelError::Unsupported("models cannot be created at runtime".to_string())
+        })?;
+        if self.entries.read()?.contains_key(name) {
+            return Err(ModelError::InvalidInput(format!(
+                "model {} already exists",
+                name
+            )));
+        }
+        let (model, algorithm) = factory(spec)?;
+        let mut entries = self.entries.write()?;
+        if entries.contains_key(name) {
+            return Err(ModelError::InvalidInput(format!(
+                "model {} already exists",
+                name
+            )));
+        }
+        let entry = CatalogEntry::new(
+            Arc::new(model.with_name(name)),
+            Arc::new(algorithm),
+            spec.config.clone(),
+        );
+        if spec.parameters.is_some() {
+            entry.pending().source = Some("request".to_string());
+        }
+        entries.insert(name.to_string(), entry);
+        Ok(())
+    }
+
+    /// Registers a deep copy of the live model `source` (see [`Model::fork`])
+    /// as `name`, which must be free, served by the same algorithm, so it
+    /// can be trained differently without risking `source`. The copy
+    /// starts without versions; its lineage records where it came from.
+    pub fn fork(&self, source: &str, name: &str) -> Result<(), ModelError> {
+        let mut entries = self.entries.write()?;
+        if entries.contains_key(name) {
+            return Err(ModelError::InvalidInput(format!(
+                "model {} already exists",
+                name
+            )));
+        }
+        let original = entries.get(source).ok_or_else(|| unknown_model(source))?;
+        let model = original.live.fork()?.with_name(name);
+        let entry = CatalogEntry::new(
+            Arc::new(model),
+            original.algorithm.clone(),
+            original.pending().config.clone(),
+        );
+        entry.pending().source = Some(format!(
+            "fork of {} at parameters version {}",
+            source,
+            original.live.version()
+        ));
+        entries.insert(name.to_string(), entry);
+        Ok(())
+    }
+
+    /// Unregisters `name` with its versions, whether it is archived or not.
+    /// Returns whether it existed; requests already holding the model finish
+    /// on it.
+    pub fn remove(&self, name: &str) -> Result<bool, ModelError> {
+        Ok(self.entries.write()?.remove(name).is_some())
+    }
+
+    /// Removes `name` from serving, keeping its live parameters, versions
+    /// and lineage until it is restored or removed. Returns whether it
+    /// exists; archiving an archived model changes nothing.
+    pub fn archive(&self, name: &str) -> Result<bool, ModelError> {
+        let mut entries = self.entries.write()?;
+        let Some(entry) = entries.get_mut(name) else {
+            return Ok(false);
+        };
+        entry.archived_at_ms.get_or_insert_with(now_ms);
+        Ok(true)
+    }
+
+    /// Serves the archived model `name` again. Returns whether it was
+    /// archived.
+    pub fn restore(&self, name: &str) -> Result<bool, ModelError> {
+        let mut entries = self.entries.write()?;
+        Ok(entries
+            .get_mut(name)
+            .and_then(|entry| entry.archived_at_ms.take())
+            .is_some())
+    }
+
+    /// Archived models, by name.
+    pub fn archived(&self) -> Result<Vec<ArchivedEntry>, ModelError> {
+        let entries = self.entries.read()?;
+        Ok(entries
+            .iter()
+            .filter_map(|(name, entry)| {
+                Some(ArchivedEntry {
+                    name: name.clone(),
+                    archived_at_ms: entry.archived_at_ms?,
+                    versions: entry.versions.keys().copied().collect(),
+                })
+            })
+            .collect())
+    }
+
+    pub fn is_archived(&self, name: &str) -> bool {
+        self.entries
+            .read()
+            .map(|entries| {
+                entries
+                    .get(name)
+                    .is_some_and(|e| e.archived_at_ms.is_some())
+            })
+            .unwrap_or(false)
+    }
+
+    /// Whether `name` is taken, by a served or an archived model.
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries
+            .read()
+            .map(|entries| entries.contains_key(name))
+            .unwrap_or(false)
+    }
+
+    /// Registers `model`, served by `algorithm`, as `name`.
+    pub fn with_model(self, name: impl Into<String>, model: Model<T>, algorithm: A) -> Self {
+        self.register(name, Arc::new(model), Arc::new(algorithm));
+        self
+    }
+
+    /// Registers an already shared model, replacing any entry named `name`.
+    pub fn register(&self, name: impl Into<String>, model: Arc<Model<T>>, algorithm: Arc<A>) {
+        if let Ok(mut entries) = self.entries.write() {
+            entries.insert(
+                name.into(),
+                CatalogEntry::new(model, algorithm, serde_json::Value::Null),
+            );
+        }
+    }
+
+    /// Names of the served models.
+    pub fn names(&self) -> Vec<String> {
+        self.entries
+            .read()
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter(|(_, entry)| entry.archived_at_ms.is_none())
+                    .map(|(name, _)| name.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Freezes the current parameters of `name` as a new version. Archived
+    /// models are frozen as they are and cannot be snapshotted.
+    pub fn snapshot(&self, name: &str) -> Result<u64, ModelError> {
+        let mut entries = self.entries.write()?;
+        let entry = entries.get_mut(name).ok_or_else(|| unknown_model(name))?;
+        if entry.archived_at_ms.is_some() {
+            return Err(ModelError::InvalidInput(format!(
+                "model {} is archived",
+                name
+            )));
+        }
+        let version = entry
+            .lineage
+            .last()
+            .and_then(|r| r.version)
+            .map_or(1, |v| v + 1);
+        let frozen = match self.quantized {
+            true => Frozen::Quantized(QuantizedModel::from_model(&entry.live)),
+            false => {
+                let parameters = entry.live.read_params().clone();
+                Frozen::Full(Arc::new(Model::with_parameters(parameters)))
+            }
+        };
+        entry.versions.insert(version, frozen);
+        while entry.versions.len() > self.max_versions {
+            entry.versions.pop_first();
+        }
+        let record = {
+            let mut pending = entry.pending();
+            let next = LineageRecord {
+                parent: Some(version),
+                algorithm: pending.algorithm.clone(),
+                config: pending.config.clone(),
+                ..LineageRecord::default()
+            };
+            std::mem::replace(&mut *pending, next)
+        };
+        entry.lineage.push(LineageRecord {
+            version: Some(version),
+            recorded_at_ms: now_ms(),
+            ..record
+        });
+        Ok(version)
+    }
+
+    /// Counts `samples` training samples applied to the live catalog model
+    /// `model` (compared by identity) in its lineage.
+    pub fn record_training(&self, model: &Arc<Model<T>>, samples: u64) -> Result<(), ModelError> {
+        let entries = self.entries.read()?;
+        if let Some(entry) = entries.values().find(|e| Arc::ptr_eq(&e.live, model)) {
+            let now = now_ms();
+            let mut pending = entry.pending();
+            pending.samples += samples;
+            pending.first_sample_ms.get_or_insert(now);
+            pending.last_sample_ms = Some(now);
+        }
+        Ok(())
+    }
+
+    /// Replaces the live parameters of `name` with `parameters` imported from
+    /// `source`, recorded in its lineage. Returns the new model version.
+    pub fn import(&self, name: &str, parameters: Vec<T>, source: &str) -> Result<u64, ModelError> {
+        let entries = self.entries.read()?;
+        let entry = entries.get(name).ok_or_else(|| unknown_model(name))?;
+        let version = entry.live.replace_parameters(parameters)?;
+        entry.pending().source = Some(source.to_string());
+        Ok(version)
+    }
+
+    /// Lineage of every snapshot of `name`, oldest first, then of its live
+    /// parameters.
+    pub fn lineage(&self, name: &str) -> Result<Vec<LineageRecord>, ModelError> {
+        let entries = self.entries.read()?;
+        let entry = entries.get(name).ok_or_else(|| unknown_model(name))?;
+        let mut lineage = entry.lineage.clone();
+        lineage.push(LineageRecord {
+            recorded_at_ms: now_ms(),
+            ..entry.pending().clone()
+        });
+        Ok(lineage)
+    }
+
+    /// Versions of `name` still retained, oldest first.
+    pub fn versions(&self, name: &str) -> Result<Vec<u64>, ModelError> {
+        let entries = self.entries.read()?;
+        let entry = entries.get(name).ok_or_else(|| unknown_model(name))?;
+        Ok(entry.versions.keys().copied().collect())
+    }
+
+    /// Model and algorithm serving `name` at `version`, or live without one.
+    /// `None` if either is unknown or the model is archived.
+    pub fn resolve(
+        &self,
+        name: &str,
+        version: Option<u64>,
+    ) -> Result<Option<Servable<T, A>>, ModelError> {
+        let entries = self.entries.read()?;
+        let Some(entry) = entries.get(name).filter(|e| e.archived_at_ms.is_none()) else {
+            return Ok(None);
+        };
+        let model = match version {
+            Some(version) => match entry.versions.get(&version) {
+                Some(Frozen::Full(model)) => Some(model.clone()),
+                Some(Frozen::Quantized(model)) => Some(Arc::new(model.to_model()?)),
+                None => None,
+            },
+            None => Some(entry.live.clone()),
+        };
+        Ok(model.map(|model| (model, entry.algorithm.clone())))
+    }
+}
+
+fn unknown_model(name: &str) -> ModelError {
+    ModelError::InvalidInput(format!("unknown model {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::DummyAlgorithm;
+
+    #[test]
+    fn test_versions_are_frozen_and_bounded() {
+        let catalog = ModelCatalog::new(2).with_model(
+            "canary",
+            Model::with_parameters(vec![1.0f32]),
+            DummyAlgorithm,
+        );
+        let (live, _) = catalog.resolve("canary", None).unwrap().unwrap();
+        assert_eq!(catalog.snapshot("canary").unwrap(), 1);
+        live.write_params()[0] = 2.0;
+        catalog.snapshot("canary").unwrap();
+        catalog.snapshot("canary").unwrap();
+
+        assert_eq!(catalog.versions("canary").unwrap(), vec![2, 3]);
+        assert!(catalog.resolve("canary", Some(1)).unwrap().is_none());
+        let (v2, _) = catalog.resolve("canary", Some(2)).unwrap().unwrap();
+        live.write_params()[0] = 3.0;
+        assert_eq!(v2.read_params()[0], 2.0);
+        assert!(catalog.resolve("other", None).unwrap().is_none());
+        assert!(catalog.snapshot("other").is_err());
+    }
+
+    #[test]
+    fn test_quantized_versions_resolve_approximately() {
+        let catalog = ModelCatalog::new(2).with_quantized_versions().with_model(
+            "ctr",
+            Model::with_parameters(vec![0.25f64, -1.0]),
+            DummyAlgorithm,
+        );
+        catalog.snapshot("ctr").unwrap();
+        let (v1, _) = catalog.resolve("ctr", Some(1)).unwrap().unwrap();
+        let params = v1.read_params();
+        assert!((params[0] - 0.25).abs() < 1.25 / 510.0 && (params[1] + 1.0).abs() < 1.25 / 510.0);
+    }
+
+    #[test]
+    fn test_lineage_follows_snapshots_and_imports() {
+        let catalog = ModelCatalog::new(1).with_model(
+            "ctr",
+            Model::with_parameters(vec![1.0f32]),
+            DummyAlgorithm,
+        );
+        let (live, _) = catalog.resolve("ctr", None).unwrap().unwrap();
+        catalog.record_training(&live, 3).unwrap();
+        catalog.snapshot("ctr").unwrap();
+        catalog
+            .import("ctr", vec![2.0], "s3://models/ctr.bin")
+            .unwrap();
+        catalog.record_training(&live, 2).unwrap();
+        catalog.snapshot("ctr").unwrap();
+        catalog.record_training(&Arc::new(Model::new()), 1).unwrap();
+
+        let lineage = catalog.lineage("ctr").unwrap();
+        let summary: Vec<_> = lineage
+            .iter()
+            .map(|r| (r.version, r.parent, r.samples, r.source.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (Some(1), None, 3, None),
+                (Some(2), Some(1), 2, Some("s3://models/ctr.bin")),
+                (None, Some(2), 0, None),
+            ]
+        );
+        assert!(lineage[0].first_sample_ms.is_some() && lineage[2].last_sample_ms.is_none());
+        assert!(lineage[0].algorithm.ends_with("DummyAlgorithm"));
+        // Lineage outlives the retained snapshots.
+        assert_eq!(catalog.versions("ctr").unwrap(), vec![2]);
+        assert!(catalog.lineage("other").is_err());
+    }
+
+    #[test]
+    fn test_archived_models_keep_history_until_restored() {
+        let catalog = ModelCatalog::new(2).with_model(
+            "ctr",
+            Model::with_parameters(vec![1.0f32]),
+            DummyAlgorithm,
+        );
+        catalog.snapshot("ctr").unwrap();
+        assert!(catalog.archive("ctr").unwrap());
+        assert!(!catalog.archive("other").unwrap());
+
+        assert!(catalog.resolve("ctr", None).unwrap().is_none());
+        assert!(catalog.resolve("ctr", Some(1)).unwrap().is_none());
+        assert!(catalog.names().is_empty() && catalog.contains("ctr"));
+        assert!(catalog.snapshot("ctr").is_err());
+        let archived = catalog.archived().unwrap();
+        assert_eq!(
+            (archived[0].name.as_str(), &archived[0].versions[..]),
+            ("ctr", &[1][..])
+        );
+        assert_eq!(catalog.lineage("ctr").unwrap().len(), 2);
+
+        assert!(catalog.restore("ctr").unwrap());
+        assert!(!catalog.restore("ctr").unwrap());
+        let (v1, _) = catalog.resolve("ctr", Some(1)).unwrap().unwrap();
+        assert_eq!(v1.read_params()[0], 1.0);
+        assert!(catalog.archived().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,192 @@
+use crate::algorithm::{Algorithm, Datum};
+use crate::errors::ModelError;
+use crate::model::Model;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Configuration of a champion/challenger comparison.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChallengerConfig {
+    /// Number of most recent labeled outcomes compared.
+    pub window: usize,
+    /// Outcomes required in the window before a verdict is reached.
+    pub min_samples: usize,
+    /// One-sided z-score of the sign test ("challenger closer to the label")
+    /// above which the challenger is considered significantly better.
+    pub z_threshold: f64,
+    /// Promote automatically on a significant win instead of waiting for
+    /// `POST /challenger/promote`.
+    pub auto_promote: bool,
+}
+
+impl Default for ChallengerConfig {
+    fn default() -> Self {
+        ChallengerConfig {
+            window: 500,
+            min_samples: 50,
+            z_threshold: 1.96,
+            auto_promote: false,
+        }
+    }
+}
+
+/// Comparison over the current window, as reported by `GET /challenger`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChallengerReport {
+    pub samples: usize,
+    pub champion_mae: f64,
+    pub challenger_mae: f64,
+    /// Outcomes on which the challenger was strictly closer to the label.
+    pub challenger_better: usize,
+    pub z_score: f64,
+    /// Set when the challenger wins significantly.
+    pub challenger_wins: bool,
+    pub promoted: bool,
+}
+
+/// A candidate model trained on the same stream as the serving (champion)
+/// model and compared with it on labeled outcomes.
+pub struct Challenger<T, A>
+where
+    T: Float + Debug + Send + Sync,
+{
+    config: ChallengerConfig,
+    model: Model<T>,
+    algorithm: Arc<A>,
+    /// `(champion error, challenger error)` per labeled outcome.
+    errors: Mutex<VecDeque<(f64, f64)>>,
+    promoted: AtomicBool,
+}
+
+impl<T, A> Challenger<T, A>
+where
+    T: Float + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    /// Creates a challenger starting from a copy of the champion's `parameters`.
+    pub fn new(config: ChallengerConfig, algorithm: A, parameters: &[T]) -> Self {
+        Challenger {
+            config,
+            model: Model::with_parameters(parameters.to_vec()),
+            algorithm: Arc::new(algorithm),
+            errors: Mutex::new(VecDeque::new()),
+            promoted: AtomicBool::new(false),
+        }
+    }
+
+    pub fn config(&self) -> &ChallengerConfig {
+        &self.config
+    }
+
+    pub fn algorithm(&self) -> Arc<A> {
+        self.algorithm.clone()
+    }
+
+    pub fn parameters(&self) -> Vec<T> {
+        self.model.read_params().clone()
+    }
+
+    pub fn is_promoted(&self) -> bool {
+        self.promoted.load(Ordering::SeqCst)
+    }
+
+    /// Trains the challenger on a sample of the shared training stream.
+    pub fn train(&self, input: A::Input) -> Result<(), ModelError> {
+        self.algorithm.training_step(&self.model, input)
+    }
+
+    /// Scores the challenger on a labeled outcome the champion predicted
+    /// as `champion_prediction`, and returns the updated comparison.
+    pub fn observe(
+        &self,
+        input: A::Input,
+        champion_prediction: f64,
+        label: f64,
+    ) -> Result<ChallengerReport, ModelError> {
+        let prediction = self.algorithm.inference_step(&self.model, input)?;
+        let challenger_error = (prediction.score().unwrap_or(f64::NAN) - label).abs();
+        let champion_error = (champion_prediction - label).abs();
+        if champion_error.is_finite() && challenger_error.is_finite() {
+            let mut errors = self.errors.lock()?;
+            if errors.len() >= self.config.window.max(1) {
+                errors.pop_front();
+            }
+            errors.push_back((champion_error, challenger_error));
+        }
+        self.report()
+    }
+
+    pub fn report(&self) -> Result<ChallengerReport, ModelError> {
+        let errors = self.errors.lock()?;
+        let n = errors.len();
+        let mean = |select: fn(&(f64, f64)) -> f64| {
+            if n == 0 {
+                0.0
+            } else {
+                errors.iter().map(select).sum::<f64>() / n as f64
+            }
+        };
+        let champion_mae = mean(|e| e.0);
+        let challenger_mae = mean(|e| e.1);
+        // Sign test over the outcomes where the two models differ.
+        let better = errors.iter().filter(|(c, h)| h < c).count();
+        let worse = errors.iter().filter(|(c, h)| h > c).count();
+        let decided = (better + worse) as f64;
+        let z_score = if decided > 0.0 {
+            (better as f64 - decided / 2.0) / (decided / 4.0).sqrt()
+        } else {
+            0.0
+        };
+        Ok(ChallengerReport {
+            samples: n,
+            champion_mae,
+            challenger_mae,
+            challenger_better: better,
+            z_score,
+            challenger_wins: n >= self.config.min_samples
+                && z_score >= self.config.z_threshold
+                && challenger_mae < champion_mae,
+            promoted: self.is_promoted(),
+        })
+    }
+
+    /// Marks the challenger as promoted; returns `false` if it already was.
+    pub(crate) fn mark_promoted(&self) -> bool {
+        !self.promoted.swap(true, Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::AdditiveAlgorithm;
+
+    #[test]
+    fn test_significant_win_requires_enough_samples() {
+        let challenger = Challenger::new(
+            ChallengerConfig {
+                min_samples: 10,
+                z_threshold: 1.5,
+                ..Default::default()
+            },
+            AdditiveAlgorithm::new(0.0),
+            &[1.0f32],
+        );
+        // The challenger predicts exactly `input`; the champion is off by one.
+        let mut report = challenger.observe(2.0, 3.0, 2.0).unwrap();
+        assert!(!report.challenger_wins);
+        for _ in 0..9 {
+            report = challenger.observe(2.0, 3.0, 2.0).unwrap();
+        }
+        assert_eq!(report.samples, 10);
+        assert_eq!(report.challenger_mae, 0.0);
+        assert!(report.challenger_wins);
+        assert!(challenger.mark_promoted());
+        assert!(!challenger.mark_promoted());
+    }
+}
@@ -0,0 +1,371 @@
+use crate::encryption::{self, EncryptionKey};
+use crate::errors::ModelError;
+use crate::metrics::Metrics;
+use crate::model::Model;
+use crate::wal::WriteAheadLog;
+use num_traits::Float;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt::Debug;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Checkpoint file written by earlier versions, still restored if no
+/// versioned checkpoint exists.
+const LEGACY_FILE: &str = "parameters.json";
+
+/// Configuration of periodic parameter checkpoints.
+#[derive(Debug, Clone)]
+pub struct CheckpointConfig {
+    /// Directory holding the `checkpoint-<version>.json` files; created if
+    /// missing.
+    pub directory: PathBuf,
+    pub interval: Duration,
+    /// Also checkpoint after this many training steps.
+    pub every_steps: Option<u64>,
+    /// Number of most recent checkpoints kept on disk (at least one).
+    pub retain: usize,
+    /// Logs every write to the parameters in the `wal` subdirectory, so a
+    /// restart also recovers the writes since the last checkpoint.
+    pub write_ahead_log: bool,
+    /// Flushes every log entry to the disk; see [`WriteAheadLog::with_sync`].
+    pub sync_log: bool,
+    /// Encrypts the checkpoints written; see [`CheckpointConfig::with_encryption`].
+    pub encryption: Option<EncryptionKey>,
+}
+
+impl CheckpointConfig {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        CheckpointConfig {
+            directory: directory.into(),
+            interval: Duration::from_secs(60),
+            every_steps: None,
+            retain: 5,
+            write_ahead_log: false,
+            sync_log: false,
+            encryption: None,
+        }
+    }
+
+    /// Keeps a write-ahead log next to the checkpoints, flushed to the disk
+    /// on every write if `sync`.
+    pub fn with_write_ahead_log(mut self, sync: bool) -> Self {
+        self.write_ahead_log = true;
+        self.sync_log = sync;
+        self
+    }
+
+    /// Encrypts the checkpoints with `key`. Plain checkpoints already in
+    /// the directory are still restored, and replaced as new ones are
+    /// written. The write-ahead log is not encrypted, so the two cannot be
+    /// combined.
+    pub fn with_encryption(mut self, key: EncryptionKey) -> Self {
+        self.encryption = Some(key);
+        self
+    }
+}
+
+/// Contents of a checkpoint file.
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredCheckpoint<T> {
+    version: u64,
+    values: Vec<T>,
+    /// Last write-ahead log entry the values include.
+    #[serde(default)]
+    log_position: u64,
+}
+
+/// Writes the live parameters to disk and restores them on startup.
+///
+/// Every checkpoint is a new file, written to a temporary name and renamed,
+/// so a crash mid-write leaves the previous ones intact; restoring picks the
+/// newest readable checkpoint. With a write-ahead log, restoring also
+/// replays the writes logged after the checkpoint, and each checkpoint
+/// removes the log entries it covers.
+#[derive(Debug)]
+pub struct Checkpointer<T> {
+    directory: PathBuf,
+    retain: usize,
+    every_steps: Option<u64>,
+    version: AtomicU64,
+    /// Training steps since the last checkpoint.
+    steps: AtomicU64,
+    /// Checkpoint interval in milliseconds; can change while running.
+    interval_ms: AtomicU64,
+    log: Option<Arc<WriteAheadLog>>,
+    /// Log entries replayed by the last restore.
+    replayed: AtomicU64,
+    encryption: Option<EncryptionKey>,
+    _marker: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T> Checkpointer<T>
+where
+    T: Float + Debug + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    pub fn new(config: CheckpointConfig) -> Result<Self, ModelError> {
+        if config.write_ahead_log && config.encryption.is_some() {
+            return Err(ModelError::Unsupported(
+                "the write-ahead log cannot be encrypted".to_string(),
+            ));
+        }
+        fs::create_dir_all(&config.directory)?;
+        let log = match config.write_ahead_log {
+            true => Some(Arc::new(
+                WriteAheadLog::open(config.directory.join("wal"))?.with_sync(config.sync_log),
+            )),
+            false => None,
+        };
+        Ok(Checkpointer {
+            directory: config.directory,
+            retain: config.retain.max(1),
+            every_steps: config.every_steps.filter(|n| *n > 0),
+            version: AtomicU64::new(0),
+            steps: AtomicU64::new(0),
+            interval_ms: AtomicU64::new(config.interval.as_millis() as u64),
+            log,
+            replayed: AtomicU64::new(0),
+            encryption: config.encryption,
+            _marker: std::marker::PhantomData,
+        })
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms.load(Ordering::SeqCst))
+    }
+
+    /// Changes the interval, effective after the pending checkpoint.
+    pub fn set_interval(&self, interval: Duration) {
+        self.interval_ms
+            .store(interval.as_millis() as u64, Ordering::SeqCst);
+    }
+
+    /// The write-ahead log, if configured.
+    pub fn log(&self) -> Option<&Arc<WriteAheadLog>> {
+        self.log.as_ref()
+    }
+
+    /// Number of log entries the last restore replayed.
+    pub fn replayed_entries(&self) -> u64 {
+        self.replayed.load(Ordering::SeqCst)
+    }
+
+    /// Version of the last checkpoint written or restored.
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
+    }
+
+    /// Versions of the checkpoints on disk, oldest first.
+    pub fn checkpoints(&self) -> Result<Vec<u64>, ModelError> {
+        let mut versions: Vec<u64> = fs::read_dir(&self.directory)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| parse_version(&entry.file_name().to_string_lossy()))
+            .collect();
+        versions.sort_unstable();
+        Ok(versions)
+    }
+
+    fn path(&self, version: u64) -> PathBuf {
+        self.directory
+            .join(format!("checkpoint-{:020}.json", version))
+    }
+
+    /// Loads the newest readable checkpoint into `model`, if there is one,
+    /// skipping files left unreadable by a crash. Returns its version.
+    /// Fails if only encrypted checkpoints are found and none can be
+    /// decrypted with the configured key, rather than starting afresh.
+    ///
+    /// With a write-ahead log, the writes logged after the checkpoint are
+    /// then replayed and the log is attached to `model`, which logs its
+    /// writes from then on.
+    pub fn restore(&self, model: &Model<T>) -> Result<Option<u64>, ModelError> {
+        let mut candidates: Vec<PathBuf> = vec![self.directory.join(LEGACY_FILE)];
+        candidates.extend(self.checkpoints()?.into_iter().map(|v| self.path(v)));
+        let key = self.encryption.as_ref();
+        let stored = candidates.iter().rev().find_map(|path| read(path, key));
+        if stored.is_none()
+            && candidates
+                .iter()
+                .any(|path| fs::read(path).is_ok_and(|bytes| encryption::open(bytes, key).is_err()))
+        {
+            return Err(ModelError::InvalidInput(format!(
+                "the checkpoints in {} cannot be opened with the configured key",
+                self.directory.display()
+            )));
+        }
+        if let Some(stored) = &stored {
+            let mut parameters = model.write_params();
+            if stored.values.len() != parameters.len() {
+                let count = parameters.len();
+                parameters.discard();
+                return Err(ModelError::InvalidInput(format!(
+                    "checkpoint has {} parameters, model has {}",
+                    stored.values.len(),
+                    count
+                )));
+            }
+            parameters.copy_from_slice(&stored.values);
+            self.version.fetch_max(stored.version, Ordering::SeqCst);
+        }
+        if let Some(log) = &self.log {
+            let position = stored.as_ref().map_or(0, |s| s.log_position);
+            let entries = log.entries_after(position)?;
+            if !entries.is_empty() {
+                let mut parameters = model.write_params();
+                let count = parameters.len();
+                let changes = entries.iter().flat_map(|e| &e.changes);
+                if let Some((index, _)) = changes.clone().find(|(index, _)| *index >= count) {
+                    parameters.discard();
+                    return Err(ModelError::InvalidInput(format!(
+                        "the write-ahead log changes parameter {}, model has {}",
+                        index, count
+                    )));
+                }
+                for (index, value) in changes {
+                    parameters[*index] = T::from(*value).unwrap_or_else(T::nan);
+                }
+            }
+            self.replayed.store(entries.len() as u64, Ordering::SeqCst);
+            model.set_write_ahead_log(log.clone())?;
+        }
+        Ok(stored.map(|s| s.version))
+    }
+
+    /// Writes the current parameters as a new checkpoint and removes the
+    /// ones beyond the retention limit. They also become a restore point of
+    /// the model (see [`Model::rollback`]).
+    pub fn save(&self, model: &Model<T>) -> Result<u64, ModelError> {
+        let version = self.version.fetch_add(1, Ordering::SeqCst) + 1;
+        self.steps.store(0, Ordering::SeqCst);
+        model.checkpoint();
+        let (values, log_position) = model.logged_snapshot();
+        let stored = StoredCheckpoint {
+            version,
+            values: values.to_vec(),
+            log_position,
+        };
+        let path = self.path(version);
+        let tmp = path.with_extension("json.tmp");
+        let mut bytes = serde_json::to_vec(&stored)?;
+        if let Some(key) = &self.encryption {
+            bytes = key.encrypt(&bytes)?;
+        }
+        // The checkpoint must be on the disk, under its final name, before
+        // the log entries it covers are removed.
+        let mut file = File::create(&tmp)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &path)?;
+        File::open(&self.directory)?.sync_all()?;
+        if let Some(log) = &self.log {
+            log.truncate_through(log_position)?;
+        }
+
+        let versions = self.checkpoints()?;
+        for old in &versions[..versions.len().saturating_sub(self.retain)] {
+            let _ = fs::remove_file(self.path(*old));
+        }
+        Ok(version)
+    }
+
+    /// Counts a training step and checkpoints if `every_steps` were reached.
+    /// Returns the version written, if any.
+    pub fn record_step(&self, model: &Model<T>) -> Result<Option<u64>, ModelError> {
+        let Some(every) = self.every_steps else {
+            return Ok(None);
+        };
+        if self.steps.fetch_add(1, Ordering::SeqCst) + 1 < every {
+            return Ok(None);
+        }
+        self.save(model).map(Some)
+    }
+
+    /// Spawns the checkpoint loop on the current runtime.
+    pub fn spawn(
+        self: Arc<Self>,
+        model: Arc<Model<T>>,
+        metrics: Arc<Metrics>,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(self.interval()).await;
+                let checkpointer = self.clone();
+                let model = model.clone();
+                match tokio::task::spawn_blocking(move || checkpointer.save(&model)).await {
+                    Ok(Ok(version)) => metrics.set_gauge("checkpoint_version", version as f64),
+                    _ => metrics.increment("checkpoint_failures"),
+                }
+                if let Some(log) = &self.log {
+                    metrics.set_gauge("wal_failures", log.failures() as f64);
+                }
+            }
+        })
+    }
+}
+
+fn parse_version(name: &str) -> Option<u64> {
+    name.strip_prefix("checkpoint-")?
+        .strip_suffix(".json")?
+        .parse()
+        .ok()
+}
+
+fn read<T: DeserializeOwned>(
+    path: &Path,
+    key: Option<&EncryptionKey>,
+) -> Option<StoredCheckpoint<T>> {
+    let bytes = encryption::open(fs::read(path).ok()?, key).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpointer = Checkpointer::new(CheckpointConfig::new(dir.path())).unwrap();
+        assert_eq!(
+            checkpointer
+                .save(&Model::with_parameters(vec![1.0, 2.0]))
+                .unwrap(),
+            1
+        );
+
+        let restarted = Checkpointer::new(CheckpointConfig::new(dir.path())).unwrap();
+        let model = Model::with_parameters(vec![0.0, 0.0]);
+        assert_eq!(restarted.restore(&model).unwrap(), Some(1));
+        assert_eq!(model.read_params().clone(), vec![1.0, 2.0]);
+        assert!(restarted
+            .restore(&Model::with_parameters(vec![0.0]))
+            .is_err());
+    }
+
+    #[test]
+    fn test_step_checkpoints_retention_and_recovery() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = CheckpointConfig::new(dir.path());
+        config.every_steps = Some(2);
+        config.retain = 2;
+        let checkpointer = Checkpointer::new(config.clone()).unwrap();
+        let model = Model::with_parameters(vec![0.0f64]);
+        for step in 1..=6 {
+            model.update_params(|p| p[0] = step as f64);
+            let written = checkpointer.record_step(&model).unwrap();
+            assert_eq!(written.is_some(), step % 2 == 0);
+        }
+        assert_eq!(checkpointer.checkpoints().unwrap(), vec![2, 3]);
+
+        // A crash while writing checkpoint 4 left a torn file behind.
+        fs::write(checkpointer.path(4), b"{\"version\": 4, \"val").unwrap();
+        let restarted = Checkpointer::new(config).unwrap();
+        let restored = Model::with_parameters(vec![0.0f64]);
+        assert_eq!(restarted.restore(&restored).unwrap(), Some(3));
+        assert_eq!(restored.read_params()[0], 6.0);
+        assert_eq!(restarted.save(&restored).unwrap(), 4);
+    }
+}
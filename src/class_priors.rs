@@ -0,0 +1,209 @@
+use crate::labels::LabelEncoder;
+use serde::{Deserialize, Serialize};
+use std::sync::{Mutex, PoisonError};
+
+/// How a model tracks its class frequencies, see [`ClassPriors`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassPriorsConfig {
+    /// Whether training samples get inverse-frequency weights.
+    pub reweight: bool,
+    /// Smallest weight given to a sample of the most frequent classes.
+    pub min_weight: f64,
+    /// Samples seen before weights apply, so the first classes seen are not
+    /// mistaken for the frequent ones.
+    pub warmup: u64,
+}
+
+impl Default for ClassPriorsConfig {
+    /// Tracks frequencies without weighting samples.
+    fn default() -> Self {
+        ClassPriorsConfig {
+            reweight: false,
+            min_weight: 0.05,
+            warmup: 100,
+        }
+    }
+}
+
+impl ClassPriorsConfig {
+    /// Weights training samples by inverse class frequency.
+    pub fn reweighting() -> Self {
+        ClassPriorsConfig {
+            reweight: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_min_weight(mut self, min_weight: f64) -> Self {
+        self.min_weight = min_weight.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_warmup(mut self, warmup: u64) -> Self {
+        self.warmup = warmup;
+        self
+    }
+}
+
+/// Frequency of one class, served on `GET /model/priors`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassPrior {
+    pub class_id: usize,
+    pub label: String,
+    pub count: u64,
+    /// Share of the labeled training samples.
+    pub frequency: f64,
+    /// Weight given to the next training sample of the class.
+    pub weight: f64,
+}
+
+/// Running class frequencies of a model's labeled training samples (by
+/// class id of [`Model::labels`]), with optional inverse-frequency sample
+/// weights so the minority classes of an imbalanced stream are not washed
+/// out by the majority ones.
+///
+/// The weights stay within `[0, 1]` as [`Algorithm::weighted_training_step`]
+/// expects: the rarest class seen gets 1 and a class `n` times as frequent
+/// gets `1 / n`, down to the configured minimum. The counts start over when
+/// the model is loaded.
+///
+/// [`Model::labels`]: crate::model::Model::labels
+/// [`Algorithm::weighted_training_step`]: crate::algorithm::Algorithm::weighted_training_step
+///
+/// # Examples
+///
+/// ```
+/// use oml::class_priors::ClassPriorsConfig;
+/// use oml::model::Model;
+///
+/// let config = ClassPriorsConfig::reweighting().with_warmup(0);
+/// let model = Model::with_parameters(vec![0.0f64]).with_class_priors(config);
+/// for _ in 0..9 {
+///     model.observe_class("ok").unwrap();
+/// }
+/// assert_eq!(model.observe_class("fraud").unwrap(), 1.0);
+/// assert_eq!(model.observe_class("ok").unwrap(), 0.1);
+/// ```
+#[derive(Debug)]
+pub struct ClassPriors {
+    config: ClassPriorsConfig,
+    /// Samples by class id.
+    counts: Mutex<Vec<u64>>,
+}
+
+impl Clone for ClassPriors {
+    fn clone(&self) -> Self {
+        let counts = self.counts.lock().unwrap_or_else(PoisonError::into_inner);
+        ClassPriors {
+            config: self.config.clone(),
+            counts: Mutex::new(counts.clone()),
+        }
+    }
+}
+
+impl ClassPriors {
+    pub fn new(config: ClassPriorsConfig) -> Self {
+        ClassPriors {
+            config,
+            counts: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn config(&self) -> &ClassPriorsConfig {
+        &self.config
+    }
+
+    /// Counts a training sample of class `class_id` and returns its weight.
+    pub fn observe(&self, class_id: usize) -> f64 {
+        let mut counts = self.counts.lock().unwrap_or_else(PoisonError::into_inner);
+        if counts.len() <= class_id {
+            counts.resize(class_id + 1, 0);
+        }
+        counts[class_id] += 1;
+        self.weight(&counts, class_id)
+    }
+
+    fn weight(&self, counts: &[u64], class_id: usize) -> f64 {
+        let total: u64 = counts.iter().sum();
+        if !self.config.reweight || total < self.config.warmup {
+            return 1.0;
+        }
+        let rarest = counts.iter().copied().filter(|c| *c > 0).min();
+        match (rarest, counts.get(class_id).copied()) {
+            (Some(rarest), Some(count)) if count > 0 => {
+                (rarest as f64 / count as f64).clamp(self.config.min_weight, 1.0)
+            }
+            _ => 1.0,
+        }
+    }
+
+    /// Frequencies of the classes seen, by class id.
+    pub fn priors(&self, labels: &LabelEncoder) -> Vec<ClassPrior> {
+        let counts = self.counts.lock().unwrap_or_else(PoisonError::into_inner);
+        let total: u64 = counts.iter().sum();
+        counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(class_id, count)| ClassPrior {
+                class_id,
+                label: labels.label(class_id).unwrap_or_default(),
+                count: *count,
+                frequency: *count as f64 / total as f64,
+                weight: self.weight(&counts, class_id),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bayesian::{BayesianLinearRegression, RegressionInput};
+    use crate::class_priors::{ClassPrior, ClassPriorsConfig};
+    use crate::handlers::AppState;
+    use crate::model::Model;
+    use crate::server::configure;
+    use crate::service::OmlService;
+    use actix_web::{test as actix_test, web, App};
+
+    #[actix_rt::test]
+    async fn test_minority_class_keeps_full_weight() {
+        let model = Model::with_parameters(vec![0.0f64; 2])
+            .with_class_priors(ClassPriorsConfig::reweighting().with_warmup(10));
+        let algorithm = BayesianLinearRegression::new(1, 1.0, 1.0);
+        let service = OmlService::new(AppState::new(model, algorithm));
+        for i in 0..20 {
+            let label = if i % 4 == 0 { 1.0 } else { 0.0 };
+            let sample = RegressionInput {
+                features: vec![label],
+                label: Some(label),
+            };
+            service.train(sample, false).await.unwrap();
+        }
+
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::from(service.state().clone()))
+                .configure(configure::<f64, BayesianLinearRegression<f64>>),
+        )
+        .await;
+        let request = actix_test::TestRequest::get()
+            .uri("/model/priors")
+            .to_request();
+        let priors: Vec<ClassPrior> = actix_test::call_and_read_body_json(&app, request).await;
+        // Class ids follow the order the labels first appeared.
+        assert_eq!(
+            priors
+                .iter()
+                .map(|p| (p.label.as_str(), p.count, p.weight))
+                .collect::<Vec<_>>(),
+            vec![("1", 5, 1.0), ("0", 15, 1.0 / 3.0)]
+        );
+        assert_eq!(priors[0].frequency, 0.25);
+
+        // A model without priors leaves weights and labels alone.
+        let plain = Model::with_parameters(vec![0.0f64]);
+        assert_eq!(plain.observe_class("0").unwrap(), 1.0);
+        assert!(plain.labels().is_empty());
+    }
+}
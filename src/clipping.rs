@@ -0,0 +1,166 @@
+use crate::algorithm::Datum;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Configuration of a [`FeatureClipper`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClipperConfig {
+    /// Number of recent values per feature the quantiles are computed over.
+    pub window: usize,
+    /// Quantile below which values are raised to it.
+    pub lower_quantile: f64,
+    /// Quantile above which values are lowered to it.
+    pub upper_quantile: f64,
+    /// Values pass unchanged until the window holds this many.
+    pub min_samples: usize,
+}
+
+impl Default for ClipperConfig {
+    fn default() -> Self {
+        ClipperConfig {
+            window: 512,
+            lower_quantile: 0.01,
+            upper_quantile: 0.99,
+            min_samples: 64,
+        }
+    }
+}
+
+/// Stage winsorizing training inputs feature by feature to the rolling
+/// quantiles of their recent values, so rare extreme inputs cannot blow up
+/// gradient steps.
+///
+/// The ranges follow the raw inputs, before clipping. Inputs without
+/// numeric features (see [`Datum::features`]) pass unchanged; a change in
+/// the number of features restarts the windows.
+#[derive(Debug)]
+pub struct FeatureClipper {
+    config: ClipperConfig,
+    windows: Vec<VecDeque<f64>>,
+}
+
+impl FeatureClipper {
+    pub fn new(config: ClipperConfig) -> Self {
+        FeatureClipper {
+            config,
+            windows: Vec::new(),
+        }
+    }
+
+    /// Current `(low, high)` range of every feature, `None` while its
+    /// window holds fewer than `min_samples` values.
+    pub fn ranges(&self) -> Vec<Option<(f64, f64)>> {
+        self.windows
+            .iter()
+            .map(|window| {
+                if window.len() < self.config.min_samples.max(1) {
+                    return None;
+                }
+                let mut sorted: Vec<f64> = window.iter().copied().collect();
+                sorted.sort_by(f64::total_cmp);
+                Some((
+                    quantile(&sorted, self.config.lower_quantile),
+                    quantile(&sorted, self.config.upper_quantile),
+                ))
+            })
+            .collect()
+    }
+
+    /// Clips `x` to the current ranges, then records its raw features.
+    /// Returns the input to train on and how many features were clipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oml::clipping::{ClipperConfig, FeatureClipper};
+    ///
+    /// let mut clipper = FeatureClipper::new(ClipperConfig {
+    ///     min_samples: 3,
+    ///     ..ClipperConfig::default()
+    /// });
+    /// for x in [1.0, 2.0, 3.0] {
+    ///     clipper.clip(vec![x]);
+    /// }
+    /// let (clipped, count) = clipper.clip(vec![1000.0]);
+    /// assert!(clipped[0] <= 3.0);
+    /// assert_eq!(count, 1);
+    /// ```
+    pub fn clip<D: Datum>(&mut self, x: D) -> (D, usize) {
+        let Some(features) = x.features().filter(|f| f.iter().all(|v| v.is_finite())) else {
+            return (x, 0);
+        };
+        if features.len() != self.windows.len() {
+            self.windows = vec![VecDeque::new(); features.len()];
+        }
+
+        let mut clipped = features.clone();
+        let mut count = 0;
+        for (value, range) in clipped.iter_mut().zip(self.ranges()) {
+            if let Some((low, high)) = range {
+                let bounded = value.clamp(low, high);
+                if bounded != *value {
+                    *value = bounded;
+                    count += 1;
+                }
+            }
+        }
+        for (window, value) in self.windows.iter_mut().zip(features) {
+            if window.len() == self.config.window.max(1) {
+                window.pop_front();
+            }
+            window.push_back(value);
+        }
+
+        match count {
+            0 => (x, 0),
+            _ => match x.with_features(&clipped) {
+                Some(clipped) => (clipped, count),
+                None => (x, 0),
+            },
+        }
+    }
+}
+
+/// Linearly interpolated quantile `q` of sorted, non-empty `values`.
+fn quantile(values: &[f64], q: f64) -> f64 {
+    let position = q.clamp(0.0, 1.0) * (values.len() - 1) as f64;
+    let (below, above) = (position.floor() as usize, position.ceil() as usize);
+    let fraction = position - below as f64;
+    values[below] + (values[above] - values[below]) * fraction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bayesian::RegressionInput;
+
+    #[test]
+    fn test_clips_each_feature_to_its_range() {
+        let mut clipper = FeatureClipper::new(ClipperConfig {
+            window: 100,
+            lower_quantile: 0.05,
+            upper_quantile: 0.95,
+            min_samples: 20,
+        });
+        let input = |a: f64, b: f64| RegressionInput {
+            features: vec![a, b],
+            label: Some(1.0),
+        };
+        let (passed, _) = clipper.clip(input(1e9, 0.0));
+        assert_eq!(passed.features[0], 1e9);
+        for i in 0..100 {
+            let x = ((i * 37) % 100) as f64;
+            clipper.clip(input(x, -x));
+        }
+
+        let (clipped, count) = clipper.clip(input(1e9, -50.0));
+        assert_eq!(count, 1);
+        assert!((clipped.features[0] - 94.05).abs() < 1e-9);
+        assert_eq!(clipped.features[1], -50.0);
+        assert_eq!(clipped.label, Some(1.0));
+
+        assert_eq!(clipper.clip("text".to_string()), ("text".to_string(), 0));
+        assert_eq!(clipper.clip(vec![1.0f32]).1, 0);
+        assert_eq!(clipper.ranges(), vec![None]);
+    }
+}
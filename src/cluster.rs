@@ -0,0 +1,343 @@
+use crate::auth::PeerClient;
+use crate::errors::ModelError;
+use crate::metrics::Metrics;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Configuration of the membership layer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MembershipConfig {
+    /// Unique identifier of this instance.
+    pub node_id: String,
+    /// Base URL peers use to reach this instance.
+    pub advertised_address: String,
+    /// Statically configured peer base URLs.
+    pub seeds: Vec<String>,
+    /// Optional `host:port` resolved on every round; each address becomes a peer.
+    pub dns: Option<String>,
+    pub heartbeat_interval: Duration,
+    /// Members not heard from within this window are reported as down.
+    pub failure_timeout: Duration,
+    /// Members not heard from within this window are forgotten.
+    #[serde(default = "default_prune_timeout")]
+    pub prune_timeout: Duration,
+    /// Id of this instance as a secure-aggregation client, if it is one.
+    #[serde(default)]
+    pub client_id: Option<u32>,
+}
+
+fn default_prune_timeout() -> Duration {
+    Duration::from_secs(60)
+}
+
+impl Default for MembershipConfig {
+    fn default() -> Self {
+        MembershipConfig {
+            node_id: "oml".to_string(),
+            advertised_address: "http://127.0.0.1:8080".to_string(),
+            seeds: Vec::new(),
+            dns: None,
+            heartbeat_interval: Duration::from_secs(2),
+            failure_timeout: Duration::from_secs(10),
+            prune_timeout: default_prune_timeout(),
+            client_id: None,
+        }
+    }
+}
+
+/// Heartbeat sent by a member to announce itself.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Heartbeat {
+    pub node_id: String,
+    pub address: String,
+    #[serde(default)]
+    pub client_id: Option<u32>,
+}
+
+/// What this instance knows about one member.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Member {
+    pub node_id: String,
+    pub address: String,
+    /// Milliseconds since the Unix epoch of the last direct or relayed heartbeat.
+    pub last_seen_ms: u64,
+    pub alive: bool,
+    /// Secure-aggregation client id announced by the member.
+    #[serde(default)]
+    pub client_id: Option<u32>,
+}
+
+/// Cluster view returned by `GET /cluster` and in heartbeat replies.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClusterState {
+    pub node_id: String,
+    pub members: Vec<Member>,
+}
+
+/// Lightweight heartbeat-based membership.
+///
+/// Each round the node heartbeats every known address (static seeds, DNS
+/// results and previously learned members). Replies carry the responder's
+/// member list, so membership spreads transitively. Members silent for
+/// longer than the prune timeout are forgotten.
+///
+/// The live members feed the other distributed features: gossip peers,
+/// orchestrator workers, the leader of a replication follower given as a
+/// node id, and the clients of a secure-aggregation round.
+#[derive(Debug)]
+pub struct Membership {
+    config: MembershipConfig,
+    members: Mutex<BTreeMap<String, Member>>,
+    http: PeerClient,
+}
+
+impl Membership {
+    pub fn new(config: MembershipConfig) -> Self {
+        Membership {
+            config,
+            members: Mutex::new(BTreeMap::new()),
+            http: PeerClient::default(),
+        }
+    }
+
+    /// Client of the requests to peers, see [`AuthConfig`](crate::auth::AuthConfig).
+    pub(crate) fn peer_client(&self) -> &PeerClient {
+        &self.http
+    }
+
+    pub fn node_id(&self) -> &str {
+        &self.config.node_id
+    }
+
+    fn heartbeat_message(&self) -> Heartbeat {
+        Heartbeat {
+            node_id: self.config.node_id.clone(),
+            address: self.config.advertised_address.clone(),
+            client_id: self.config.client_id,
+        }
+    }
+
+    fn observe(
+        &self,
+        node_id: &str,
+        address: &str,
+        client_id: Option<u32>,
+        seen_ms: u64,
+    ) -> Result<(), ModelError> {
+        if node_id == self.config.node_id {
+            return Ok(());
+        }
+        let mut members = self.members.lock()?;
+        let member = members
+            .entry(node_id.to_string())
+            .or_insert_with(|| Member {
+                node_id: node_id.to_string(),
+                address: address.to_string(),
+                last_seen_ms: 0,
+                alive: false,
+                client_id,
+            });
+        if seen_ms >= member.last_seen_ms {
+            member.address = address.to_string();
+            member.last_seen_ms = seen_ms;
+            member.client_id = client_id;
+        }
+        Ok(())
+    }
+
+    /// Records a heartbeat received from a peer and returns the local view.
+    pub fn receive_heartbeat(&self, heartbeat: &Heartbeat) -> Result<ClusterState, ModelError> {
+        self.observe(
+            &heartbeat.node_id,
+            &heartbeat.address,
+            heartbeat.client_id,
+            now_ms(),
+        )?;
+        self.state()
+    }
+
+    /// Current view of the cluster, including this instance.
+    pub fn state(&self) -> Result<ClusterState, ModelError> {
+        let now = now_ms();
+        let timeout = self.config.failure_timeout.as_millis() as u64;
+        let mut members: Vec<Member> = self
+            .members
+            .lock()?
+            .values()
+            .map(|m| Member {
+                alive: now.saturating_sub(m.last_seen_ms) <= timeout,
+                ..m.clone()
+            })
+            .collect();
+        members.push(Member {
+            node_id: self.config.node_id.clone(),
+            address: self.config.advertised_address.clone(),
+            last_seen_ms: now,
+            alive: true,
+            client_id: self.config.client_id,
+        });
+        members.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+        Ok(ClusterState {
+            node_id: self.config.node_id.clone(),
+            members,
+        })
+    }
+
+    /// Base URLs of the live peers (excluding this instance).
+    pub fn live_peers(&self) -> Vec<String> {
+        self.state()
+            .map(|state| {
+                state
+                    .members
+                    .into_iter()
+                    .filter(|m| m.alive && m.node_id != self.config.node_id)
+                    .map(|m| m.address)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Address of the live member `node_id`, this instance included.
+    pub fn address_of(&self, node_id: &str) -> Option<String> {
+        self.state()
+            .ok()?
+            .members
+            .into_iter()
+            .find(|m| m.alive && m.node_id == node_id)
+            .map(|m| m.address)
+    }
+
+    /// Secure-aggregation client ids of the live members, this instance
+    /// included.
+    pub fn live_clients(&self) -> BTreeSet<u32> {
+        self.state()
+            .map(|state| {
+                state
+                    .members
+                    .into_iter()
+                    .filter(|m| m.alive)
+                    .filter_map(|m| m.client_id)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Forgets the members not heard from within the prune timeout.
+    pub fn prune(&self) -> Result<usize, ModelError> {
+        let horizon = now_ms().saturating_sub(self.config.prune_timeout.as_millis() as u64);
+        let mut members = self.members.lock()?;
+        let before = members.len();
+        members.retain(|_, m| m.last_seen_ms >= horizon);
+        Ok(before - members.len())
+    }
+
+    async fn targets(&self) -> BTreeSet<String> {
+        let mut targets: BTreeSet<String> = self.config.seeds.iter().cloned().collect();
+        if let Some(dns) = &self.config.dns {
+            if let Ok(addresses) = tokio::net::lookup_host(dns.as_str()).await {
+                targets.extend(addresses.map(|a| format!("http://{}", a)));
+            }
+        }
+        if let Ok(members) = self.members.lock() {
+            targets.extend(members.values().map(|m| m.address.clone()));
+        }
+        targets.remove(&self.config.advertised_address);
+        targets
+    }
+
+    /// Heartbeats every known address once; returns how many answered.
+    pub async fn run_round(&self) -> usize {
+        let heartbeat = self.heartbeat_message();
+        let mut answered = 0;
+        for target in self.targets().await {
+            let reply = self
+                .http
+                .post(format!(
+                    "{}/cluster/heartbeat",
+                    target.trim_end_matches('/')
+                ))
+                .json(&heartbeat)
+                .send()
+                .await
+                .and_then(|r| r.error_for_status());
+            let Ok(reply) = reply else { continue };
+            let Ok(state) = reply.json::<ClusterState>().await else {
+                continue;
+            };
+            answered += 1;
+            let now = now_ms();
+            for member in state.members {
+                // The responder itself was just seen; others are relayed as-is.
+                let seen = if member.node_id == state.node_id {
+                    now
+                } else {
+                    member.last_seen_ms
+                };
+                let _ = self.observe(&member.node_id, &member.address, member.client_id, seen);
+            }
+        }
+        let _ = self.prune();
+        answered
+    }
+
+    /// Spawns the heartbeat loop on the current runtime.
+    pub fn spawn(self: Arc<Self>, metrics: Arc<Metrics>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.config.heartbeat_interval);
+            loop {
+                interval.tick().await;
+                self.run_round().await;
+                metrics.set_gauge("cluster_live_peers", self.live_peers().len() as f64);
+            }
+        })
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeats_track_liveness() {
+        let membership = Membership::new(MembershipConfig {
+            node_id: "a".to_string(),
+            failure_timeout: Duration::from_millis(30),
+            prune_timeout: Duration::from_millis(60),
+            client_id: Some(1),
+            ..Default::default()
+        });
+        let state = membership
+            .receive_heartbeat(&Heartbeat {
+                node_id: "b".to_string(),
+                address: "http://b".to_string(),
+                client_id: Some(2),
+            })
+            .unwrap();
+        assert_eq!(state.members.len(), 2);
+        assert_eq!(membership.live_peers(), vec!["http://b".to_string()]);
+        assert_eq!(membership.address_of("b").as_deref(), Some("http://b"));
+        assert_eq!(membership.live_clients(), BTreeSet::from([1, 2]));
+
+        std::thread::sleep(Duration::from_millis(40));
+        assert!(membership.live_peers().is_empty());
+        assert_eq!(membership.address_of("b"), None);
+        let b = &membership.state().unwrap().members[1];
+        assert_eq!(b.node_id, "b");
+        assert!(!b.alive);
+
+        // Silent past the prune timeout, `b` is forgotten.
+        assert_eq!(membership.prune().unwrap(), 0);
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(membership.prune().unwrap(), 1);
+        assert_eq!(membership.state().unwrap().members.len(), 1);
+    }
+}
@@ -0,0 +1,376 @@
+use crate::auth::PeerClient;
+use crate::errors::ModelError;
+use crate::metrics::Metrics;
+use crate::model::Model;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Debug;
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Notify;
+
+/// Local deltas kept for shipping at most; older ones are dropped.
+const OUTBOX_CAPACITY: usize = 1024;
+
+/// Per-node update counters used to order deltas across instances.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionVector(pub BTreeMap<String, u64>);
+
+/// How two version vectors relate causally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Causality {
+    Equal,
+    /// The left vector happened before the right one.
+    Before,
+    /// The left vector happened after the right one.
+    After,
+    /// Neither dominates: the updates were made concurrently.
+    Concurrent,
+}
+
+impl VersionVector {
+    pub fn new() -> Self {
+        VersionVector::default()
+    }
+
+    pub fn get(&self, node_id: &str) -> u64 {
+        self.0.get(node_id).copied().unwrap_or(0)
+    }
+
+    /// Advances `node_id`'s counter and returns its new value.
+    pub fn increment(&mut self, node_id: &str) -> u64 {
+        let counter = self.0.entry(node_id.to_string()).or_insert(0);
+        *counter += 1;
+        *counter
+    }
+
+    /// Pointwise maximum of both vectors.
+    pub fn merge(&mut self, other: &VersionVector) {
+        for (node, &counter) in &other.0 {
+            let local = self.0.entry(node.clone()).or_insert(0);
+            *local = (*local).max(counter);
+        }
+    }
+
+    pub fn compare(&self, other: &VersionVector) -> Causality {
+        let mut ordering = Ordering::Equal;
+        for node in self.0.keys().chain(other.0.keys()) {
+            match (self.get(node).cmp(&other.get(node)), ordering) {
+                (Ordering::Equal, _) => {}
+                (o, Ordering::Equal) => ordering = o,
+                (o, current) if o != current => return Causality::Concurrent,
+                _ => {}
+            }
+        }
+        match ordering {
+            Ordering::Equal => Causality::Equal,
+            Ordering::Less => Causality::Before,
+            Ordering::Greater => Causality::After,
+        }
+    }
+}
+
+/// Additive parameter delta stamped with the origin's version vector.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VersionedDelta<T> {
+    pub origin: String,
+    /// Origin's vector after producing this delta.
+    pub clock: VersionVector,
+    /// Wall-clock time the delta was produced, used for last-writer-wins.
+    pub timestamp_ms: u64,
+    pub values: Vec<T>,
+}
+
+/// Strategy for deltas concurrent with local state.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictResolution {
+    /// Keep whichever of the conflicting writes is newest (ties broken by origin).
+    #[default]
+    LastWriterWins,
+    /// Combine both through the merge hook (additive by default).
+    Merge,
+}
+
+/// What happened to a received delta.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeltaOutcome {
+    /// Causally newer than the local state and applied.
+    Applied,
+    /// Already covered by the local state and ignored.
+    Stale,
+    /// Concurrent with local state; `applied` tells whether resolution kept it.
+    Conflict { applied: bool },
+}
+
+/// Applies a conflicting remote delta to the parameters.
+pub type MergeHook<T> = Box<dyn Fn(&mut [T], &[T]) + Send + Sync>;
+
+struct ClockState {
+    clock: VersionVector,
+    last_write: (u64, String),
+}
+
+/// Tracks causality of model mutations made on several instances.
+///
+/// Local updates advance this node's entry; remote deltas are compared
+/// against the local vector to drop duplicates, apply causally newer
+/// updates and resolve concurrent ones. Once shipping is spawned (see
+/// [`ConsistencyTracker::spawn`]) every local delta is also pushed to the
+/// `/consistency/deltas` route of the peers.
+pub struct ConsistencyTracker<T> {
+    node_id: String,
+    resolution: ConflictResolution,
+    merge_hook: MergeHook<T>,
+    state: Mutex<ClockState>,
+    shipping: AtomicBool,
+    outbox: Mutex<VecDeque<VersionedDelta<T>>>,
+    queued: Notify,
+    http: PeerClient,
+}
+
+impl<T> ConsistencyTracker<T>
+where
+    T: Float + Debug + Send + Sync + 'static,
+{
+    pub fn new(node_id: &str, resolution: ConflictResolution) -> Self {
+        ConsistencyTracker {
+            node_id: node_id.to_string(),
+            resolution,
+            merge_hook: Box::new(add_delta),
+            state: Mutex::new(ClockState {
+                clock: VersionVector::new(),
+                last_write: (0, String::new()),
+            }),
+            shipping: AtomicBool::new(false),
+            outbox: Mutex::new(VecDeque::new()),
+            queued: Notify::new(),
+            http: PeerClient::default(),
+        }
+    }
+
+    /// Client of the requests to peers, see [`AuthConfig`](crate::auth::AuthConfig).
+    pub(crate) fn peer_client(&self) -> &PeerClient {
+        &self.http
+    }
+
+    /// Replaces the hook used by [`ConflictResolution::Merge`].
+    pub fn with_merge_hook(mut self, hook: MergeHook<T>) -> Self {
+        self.merge_hook = hook;
+        self
+    }
+
+    pub fn node_id(&self) -> &str {
+        &self.node_id
+    }
+
+    pub fn clock(&self) -> Result<VersionVector, ModelError> {
+        Ok(self.state.lock()?.clock.clone())
+    }
+
+    /// Runs `step`, a local change to `model`, and stamps what it changed
+    /// so it can be shipped to other instances; queued for the peers when
+    /// shipping runs. Remote deltas and other tracked steps wait meanwhile,
+    /// so the delta holds only the changes of `step`.
+    pub fn record_local<R>(
+        &self,
+        model: &Model<T>,
+        step: impl FnOnce() -> Result<R, ModelError>,
+    ) -> Result<(R, VersionedDelta<T>), ModelError> {
+        let mut state = self.state.lock()?;
+        let before = model.snapshot();
+        let result = step()?;
+        let after = model.read_params();
+        let values = after
+            .iter()
+            .zip(before.iter())
+            .map(|(new, old)| *new - *old)
+            .collect();
+        state.clock.increment(&self.node_id);
+        let timestamp_ms = now_ms();
+        state.last_write = (timestamp_ms, self.node_id.clone());
+        let delta = VersionedDelta {
+            origin: self.node_id.clone(),
+            clock: state.clock.clone(),
+            timestamp_ms,
+            values,
+        };
+        if self.shipping.load(atomic::Ordering::SeqCst) {
+            let mut outbox = self.outbox.lock()?;
+            if outbox.len() >= OUTBOX_CAPACITY {
+                outbox.pop_front();
+            }
+            outbox.push_back(delta.clone());
+            self.queued.notify_one();
+        }
+        Ok((result, delta))
+    }
+
+    /// Applies a delta received from another instance according to its causality.
+    pub fn receive(
+        &self,
+        model: &Model<T>,
+        delta: &VersionedDelta<T>,
+    ) -> Result<DeltaOutcome, ModelError> {
+        let mut state = self.state.lock()?;
+        let mut params = model.write_params();
+        if params.len() != delta.values.len() {
+            let count = params.len();
+            params.discard();
+            return Err(ModelError::InvalidInput(format!(
+                "delta has {} values, model has {}",
+                delta.values.len(),
+                count
+            )));
+        }
+        // A delta is new if it carries an origin update the local clock has not seen.
+        let unseen = delta.clock.get(&delta.origin) > state.clock.get(&delta.origin);
+        let outcome = match delta.clock.compare(&state.clock) {
+            _ if !unseen => DeltaOutcome::Stale,
+            Causality::After => {
+                add_delta(&mut params, &delta.values);
+                DeltaOutcome::Applied
+            }
+            _ => {
+                let applied = match self.resolution {
+                    ConflictResolution::LastWriterWins => {
+                        let incoming = (delta.timestamp_ms, delta.origin.clone());
+                        let wins = incoming > state.last_write;
+                        if wins {
+                            add_delta(&mut params, &delta.values);
+                        }
+                        wins
+                    }
+                    ConflictResolution::Merge => {
+                        (self.merge_hook)(&mut params, &delta.values);
+                        true
+                    }
+                };
+                DeltaOutcome::Conflict { applied }
+            }
+        };
+        if outcome != DeltaOutcome::Stale {
+            state.clock.merge(&delta.clock);
+            let incoming = (delta.timestamp_ms, delta.origin.clone());
+            if incoming > state.last_write {
+                state.last_write = incoming;
+            }
+        }
+        Ok(outcome)
+    }
+}
+
+impl<T> ConsistencyTracker<T>
+where
+    T: Float + Serialize + Debug + Send + Sync + 'static,
+{
+    /// Spawns the loop pushing local deltas to the peers returned by
+    /// `peers` (the gossip peers or the live cluster members). Delivery is
+    /// best effort: a peer that misses a delta catches up through gossip.
+    pub fn spawn(
+        self: Arc<Self>,
+        peers: impl Fn() -> Vec<String> + Send + 'static,
+        metrics: Arc<Metrics>,
+    ) -> tokio::task::JoinHandle<()> {
+        self.shipping.store(true, atomic::Ordering::SeqCst);
+        tokio::spawn(async move {
+            loop {
+                self.queued.notified().await;
+                let deltas: Vec<_> = match self.outbox.lock() {
+                    Ok(mut outbox) => outbox.drain(..).collect(),
+                    Err(_) => continue,
+                };
+                let peers = peers();
+                for delta in &deltas {
+                    for peer in &peers {
+                        let shipped = self
+                            .http
+                            .post(format!("{}/consistency/deltas", peer.trim_end_matches('/')))
+                            .json(delta)
+                            .send()
+                            .await
+                            .and_then(|r| r.error_for_status());
+                        match shipped {
+                            Ok(_) => metrics.increment("consistency_deltas_shipped"),
+                            Err(_) => metrics.increment("consistency_ship_failures"),
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+fn add_delta<T: Float>(params: &mut [T], delta: &[T]) {
+    params
+        .iter_mut()
+        .zip(delta.iter())
+        .for_each(|(p, d)| *p = *p + *d);
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vector(entries: &[(&str, u64)]) -> VersionVector {
+        VersionVector(entries.iter().map(|(n, c)| (n.to_string(), *c)).collect())
+    }
+
+    #[test]
+    fn test_compare_version_vectors() {
+        let a = vector(&[("a", 1)]);
+        let ab = vector(&[("a", 1), ("b", 1)]);
+        let b = vector(&[("b", 1)]);
+        assert_eq!(a.compare(&a), Causality::Equal);
+        assert_eq!(a.compare(&ab), Causality::Before);
+        assert_eq!(ab.compare(&b), Causality::After);
+        assert_eq!(a.compare(&b), Causality::Concurrent);
+    }
+
+    fn set(model: &Model<f64>, value: f64) -> impl FnOnce() -> Result<(), ModelError> + '_ {
+        move || {
+            model.write_params()[0] = value;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_receive_detects_stale_and_concurrent_deltas() {
+        let model = Model::with_parameters(vec![0.0f64]);
+        let local = ConsistencyTracker::new("a", ConflictResolution::Merge);
+        let remote = ConsistencyTracker::new("b", ConflictResolution::Merge);
+
+        let remote_model = Model::with_parameters(vec![0.0f64]);
+        let ((), delta) = remote
+            .record_local(&remote_model, set(&remote_model, 2.0))
+            .unwrap();
+        assert_eq!(
+            local.receive(&model, &delta).unwrap(),
+            DeltaOutcome::Applied
+        );
+        assert_eq!(local.receive(&model, &delta).unwrap(), DeltaOutcome::Stale);
+
+        local.record_local(&model, set(&model, 3.0)).unwrap();
+        let ((), concurrent) = remote
+            .record_local(&remote_model, set(&remote_model, 4.0))
+            .unwrap();
+        assert_eq!(
+            local.receive(&model, &concurrent).unwrap(),
+            DeltaOutcome::Conflict { applied: true }
+        );
+        assert_eq!(model.read_params()[0], 5.0);
+        assert_eq!(local.clock().unwrap(), vector(&[("a", 1), ("b", 2)]));
+    }
+}
@@ -0,0 +1,168 @@
+use crate::errors::ModelError;
+use crate::layout::ModelLayout;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+
+/// Range some of the parameters must stay in.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Bounds {
+    /// Layout groups bounded.
+    #[serde(default)]
+    pub groups: Vec<String>,
+    /// Further parameters bounded.
+    #[serde(default)]
+    pub indices: Vec<usize>,
+    #[serde(default)]
+    pub min: Option<f64>,
+    #[serde(default)]
+    pub max: Option<f64>,
+}
+
+/// Limits enforced on the dense parameters after every training step (see
+/// [`Model::set_constraints`]), so a single outlier sample cannot send the
+/// weights to infinity.
+///
+/// They apply in order: parameters a step left NaN or infinite get their
+/// previous value back (with `finite`), then the bounds clamp the
+/// parameters they name, non-negativity clamps every parameter at zero, and
+/// the parameters are scaled down to `max_norm` if their L2 norm exceeds
+/// it (an infinite norm is left to `finite`). Frozen parameters are left
+/// alone.
+///
+/// [`Model::set_constraints`]: crate::model::Model::set_constraints
+///
+/// # Examples
+///
+/// ```
+/// use oml::constraints::ParameterConstraints;
+/// use oml::model::Model;
+///
+/// let model = Model::with_parameters(vec![0.0f64, 0.0]);
+/// let constraints = ParameterConstraints {
+///     max_norm: Some(5.0),
+///     non_negative: true,
+///     ..Default::default()
+/// };
+/// model.set_constraints(constraints).unwrap();
+/// model.update_trainable(|params| params.copy_from_slice(&[-1.0, 100.0]));
+/// assert_eq!(model.read_params().as_slice(), &[0.0, 5.0]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ParameterConstraints {
+    /// Largest L2 norm of the parameters.
+    #[serde(default)]
+    pub max_norm: Option<f64>,
+    /// Per-parameter ranges.
+    #[serde(default)]
+    pub bounds: Vec<Bounds>,
+    /// Whether every parameter must be at least zero.
+    #[serde(default)]
+    pub non_negative: bool,
+    /// Whether parameters that become NaN or infinite keep their value.
+    #[serde(default)]
+    pub finite: bool,
+}
+
+impl ParameterConstraints {
+    /// Whether the constraints leave every parameter alone.
+    pub fn is_empty(&self) -> bool {
+        self.max_norm.is_none() && self.bounds.is_empty() && !self.non_negative && !self.finite
+    }
+
+    /// Checks that the bounds name groups of `layout` and parameters within
+    /// `len`, and that the limits are ordered.
+    pub fn validate(&self, layout: &ModelLayout, len: usize) -> Result<(), ModelError> {
+        if self
+            .max_norm
+            .is_some_and(|norm| norm.is_nan() || norm < 0.0)
+        {
+            return Err(ModelError::InvalidInput(
+                "max_norm must be at least zero".to_string(),
+            ));
+        }
+        for bounds in &self.bounds {
+            if let Some(group) = bounds.groups.iter().find(|g| layout.get(g).is_none()) {
+                return Err(ModelError::InvalidInput(format!("unknown group {}", group)));
+            }
+            if let Some(index) = bounds.indices.iter().find(|i| **i >= len) {
+                return Err(ModelError::InvalidInput(format!(
+                    "parameter {} out of range, the model has {}",
+                    index, len
+                )));
+            }
+            if let (Some(min), Some(max)) = (bounds.min, bounds.max) {
+                if min > max {
+                    return Err(ModelError::InvalidInput(format!(
+                        "bounds [{}, {}] are empty",
+                        min, max
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Enforces the constraints on `params`, written over `previous`,
+    /// except on the `frozen` ones. Returns the number of parameters
+    /// changed.
+    pub fn apply<T: Float>(
+        &self,
+        params: &mut [T],
+        previous: &[T],
+        layout: &ModelLayout,
+        frozen: &[bool],
+    ) -> usize {
+        let original = params.to_vec();
+        let free = |i: usize| !frozen.get(i).copied().unwrap_or(false);
+        if self.finite {
+            for (i, (param, old)) in params.iter_mut().zip(previous).enumerate() {
+                if !param.is_finite() && free(i) {
+                    *param = *old;
+                }
+            }
+        }
+        for bounds in &self.bounds {
+            let min = bounds.min.and_then(T::from);
+            let max = bounds.max.and_then(T::from);
+            let ranges = bounds
+                .groups
+                .iter()
+                .filter_map(|g| layout.get(g))
+                .flat_map(|g| g.range());
+            for i in ranges.chain(bounds.indices.iter().copied()) {
+                if let Some(param) = params.get_mut(i).filter(|_| free(i)) {
+                    *param = clamp(*param, min, max);
+                }
+            }
+        }
+        if self.non_negative {
+            for (i, param) in params.iter_mut().enumerate() {
+                if free(i) {
+                    *param = param.max(T::zero());
+                }
+            }
+        }
+        if let Some(max_norm) = self.max_norm.and_then(T::from) {
+            let norm = params
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| free(*i))
+                .fold(T::zero(), |sum, (_, p)| sum + *p * *p)
+                .sqrt();
+            if norm.is_finite() && norm > max_norm {
+                let scale = max_norm / norm;
+                for (i, param) in params.iter_mut().enumerate() {
+                    if free(i) {
+                        *param = *param * scale;
+                    }
+                }
+            }
+        }
+        params.iter().zip(&original).filter(|(a, b)| a != b).count()
+    }
+}
+
+fn clamp<T: Float>(value: T, min: Option<T>, max: Option<T>) -> T {
+    let value = min.map_or(value, |min| value.max(min));
+    max.map_or(value, |max| value.min(max))
+}
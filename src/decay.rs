@@ -0,0 +1,203 @@
+use crate::errors::ModelError;
+use crate::metrics::Metrics;
+use crate::model::Model;
+use crate::personalization::PersonalizedModels;
+use num_traits::Float;
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Configuration of a [`Decay`] schedule.
+#[derive(Debug, Clone)]
+pub struct DecayConfig {
+    pub interval: Duration,
+    /// Fraction of the distance to the prior removed at every interval.
+    pub rate: f64,
+    /// Parameters decayed towards; those of the model when the schedule is
+    /// set up otherwise.
+    pub prior: Option<Vec<f64>>,
+    /// Only decays this parameter group (see [`crate::layout::ModelLayout`]).
+    pub group: Option<String>,
+    /// Also forgets personalized keys unused for this long (serving model only).
+    pub key_ttl: Option<Duration>,
+}
+
+impl DecayConfig {
+    pub fn new(interval: Duration, rate: f64) -> Self {
+        DecayConfig {
+            interval,
+            rate,
+            prior: None,
+            group: None,
+            key_ttl: None,
+        }
+    }
+}
+
+/// Periodically shrinks a model's parameters towards a prior, so state
+/// learned from traffic that has since stopped fades instead of lingering.
+///
+/// # Examples
+///
+/// ```
+/// use oml::decay::{Decay, DecayConfig};
+/// use oml::model::Model;
+/// use std::time::Duration;
+///
+/// let model = Model::with_parameters(vec![0.0, 0.0]);
+/// let decay = Decay::new(DecayConfig::new(Duration::from_secs(60), 0.5)).prior_from(&model);
+/// model.update_params(|p| p.copy_from_slice(&[4.0, -2.0]));
+/// decay.apply(&model).unwrap();
+/// assert_eq!(model.read_params().as_slice(), &[2.0, -1.0]);
+/// ```
+#[derive(Debug)]
+pub struct Decay<T> {
+    config: DecayConfig,
+    /// Set from the configuration, on setup or on the first application.
+    prior: Mutex<Option<Vec<T>>>,
+}
+
+impl<T> Decay<T>
+where
+    T: Float + Debug + Serialize + DeserializeOwned + Send + Sync + Sum + 'static,
+{
+    /// A schedule without a configured prior takes the parameters of the
+    /// model it first applies to as the prior, leaving them unchanged.
+    pub fn new(config: DecayConfig) -> Self {
+        let prior = config.prior.as_ref().map(|p| {
+            p.iter()
+                .map(|v| T::from(*v).unwrap_or_else(T::zero))
+                .collect()
+        });
+        Decay {
+            config,
+            prior: Mutex::new(prior),
+        }
+    }
+
+    /// Takes the current parameters of `model` as the prior, unless one is
+    /// configured.
+    pub fn prior_from(self, model: &Model<T>) -> Self {
+        if let Ok(mut prior) = self.prior.lock() {
+            prior.get_or_insert_with(|| model.snapshot().to_vec());
+        }
+        self
+    }
+
+    pub fn config(&self) -> &DecayConfig {
+        &self.config
+    }
+
+    /// Moves the parameters (of the configured group) `rate` of the way
+    /// towards the prior.
+    pub fn apply(&self, model: &Model<T>) -> Result<(), ModelError> {
+        let mut prior = self.prior.lock()?;
+        let len = model.read_params().len();
+        let prior = match prior.as_ref() {
+            Some(p) if p.len() == len => p,
+            Some(_) if self.config.prior.is_some() => {
+                return Err(ModelError::InvalidInput(format!(
+                    "the decay prior does not have {} parameters",
+                    len
+                )))
+            }
+            // A catalog model seen for the first time, or recreated.
+            _ => {
+                *prior = Some(model.snapshot().to_vec());
+                return Ok(());
+            }
+        };
+        let range = match &self.config.group {
+            Some(name) => model
+                .layout()
+                .get(name)
+                .ok_or_else(|| ModelError::InvalidInput(format!("unknown group {}", name)))?
+                .range(),
+            None => 0..len,
+        };
+        let rate = T::from(self.config.rate.clamp(0.0, 1.0)).unwrap_or_else(T::zero);
+        model.update_params(|params| {
+            for i in range {
+                params[i] = params[i] + rate * (prior[i] - params[i]);
+            }
+        });
+        Ok(())
+    }
+
+    /// Spawns the decay loop on the current runtime. `model` resolves the
+    /// model at every interval (it may be absent, e.g. a deleted catalog
+    /// model); idle keys of `personalization` expire after `key_ttl`.
+    pub fn spawn(
+        self: Arc<Self>,
+        model: impl Fn() -> Option<Arc<Model<T>>> + Send + Sync + 'static,
+        personalization: Option<Arc<PersonalizedModels<T>>>,
+        metrics: Arc<Metrics>,
+    ) -> tokio::task::JoinHandle<()> {
+        let model = Arc::new(model);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(self.config.interval).await;
+                let decay = self.clone();
+                let model = model.clone();
+                let personalization = personalization.clone();
+                let task = tokio::task::spawn_blocking(move || {
+                    if let Some(model) = model() {
+                        decay.apply(&model)?;
+                    }
+                    match (decay.config.key_ttl, personalization) {
+                        (Some(ttl), Some(personal)) => {
+                            Ok::<_, ModelError>(Some((personal.expire(ttl)?, personal.len()?)))
+                        }
+                        _ => Ok(None),
+                    }
+                });
+                match task.await {
+                    Ok(Ok(expired)) => {
+                        metrics.increment("decay_steps");
+                        if let Some((expired, keys)) = expired {
+                            metrics.add("personalized_keys_expired", expired as u64);
+                            metrics.set_gauge("personalized_keys", keys as f64);
+                        }
+                    }
+                    _ => metrics.increment("decay_failures"),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::ModelLayout;
+
+    #[test]
+    fn test_decays_the_group_towards_the_prior() {
+        let layout = ModelLayout::new()
+            .group("weights", &[2])
+            .group("bias", &[1]);
+        let model = Model::with_layout(layout, vec![8.0, -8.0, 5.0]).unwrap();
+        let mut config = DecayConfig::new(Duration::from_secs(1), 0.25);
+        config.prior = Some(vec![0.0, 0.0, 0.0]);
+        config.group = Some("weights".to_string());
+        let decay = Decay::new(config.clone());
+        decay.apply(&model).unwrap();
+        decay.apply(&model).unwrap();
+        assert_eq!(model.read_params().as_slice(), &[4.5, -4.5, 5.0]);
+
+        config.group = Some("missing".to_string());
+        assert!(Decay::new(config.clone()).apply(&model).is_err());
+        config.prior = Some(vec![0.0]);
+        config.group = None;
+        assert!(Decay::new(config).apply(&model).is_err());
+
+        // Without a prior the first application only captures one.
+        let lazy = Decay::new(DecayConfig::new(Duration::from_secs(1), 1.0));
+        lazy.apply(&model).unwrap();
+        model.update_params(|p| p[2] = 0.0);
+        lazy.apply(&model).unwrap();
+        assert_eq!(model.read_params()[2], 5.0);
+    }
+}
@@ -0,0 +1,237 @@
+use crate::errors::ModelError;
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashSet, VecDeque};
+use std::hash::Hasher;
+use std::mem::size_of;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Configuration of a [`DedupWindow`].
+#[derive(Debug, Clone)]
+pub struct DedupConfig {
+    /// How long a sample is remembered after it is first seen.
+    pub window: Duration,
+    /// Most samples remembered at once; the oldest are forgotten first.
+    pub max_entries: usize,
+}
+
+impl DedupConfig {
+    pub fn new(window: Duration) -> Self {
+        DedupConfig {
+            window,
+            max_entries: 100_000,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Seen {
+    /// Hashes by the time they were first seen, oldest first.
+    order: VecDeque<(Instant, u64)>,
+    hashes: HashSet<u64>,
+}
+
+/// Skips training samples that are exact duplicates of one seen within a
+/// time window, as stream sources often deliver a message twice.
+///
+/// Samples are compared by a 64-bit hash of their JSON encoding, so two
+/// samples are duplicates when every field matches; nothing else (headers,
+/// entity keys) is taken into account. A sample that then fails to train
+/// is [forgotten](DedupWindow::forget), so the client's retry is not
+/// skipped.
+///
+/// # Examples
+///
+/// ```
+/// use oml::dedup::{DedupConfig, DedupWindow};
+/// use std::time::Duration;
+///
+/// let dedup = DedupWindow::new(DedupConfig::new(Duration::from_secs(60)));
+/// assert!(!dedup.is_duplicate(&vec![1.0, 2.0]).unwrap());
+/// assert!(dedup.is_duplicate(&vec![1.0, 2.0]).unwrap());
+/// assert!(!dedup.is_duplicate(&vec![1.0, 3.0]).unwrap());
+/// ```
+#[derive(Debug)]
+pub struct DedupWindow {
+    config: DedupConfig,
+    seen: Mutex<Seen>,
+}
+
+impl DedupWindow {
+    pub fn new(config: DedupConfig) -> Self {
+        DedupWindow {
+            config,
+            seen: Mutex::new(Seen::default()),
+        }
+    }
+
+    pub fn config(&self) -> &DedupConfig {
+        &self.config
+    }
+
+    /// Number of samples currently remembered.
+    pub fn len(&self) -> usize {
+        self.seen.lock().map(|s| s.hashes.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Approximate bytes of memory the remembered samples hold.
+    pub fn memory_bytes(&self) -> usize {
+        self.len() * (size_of::<(Instant, u64)>() + size_of::<u64>())
+    }
+
+    /// Whether `sample` was seen within the window; remembers it if not.
+    pub fn is_duplicate<D: Serialize>(&self, sample: &D) -> Result<bool, ModelError> {
+        self.check(hash(sample)?, Instant::now())
+    }
+
+    /// Forgets `sample`, e.g. after failing to train on it, so that it is
+    /// no longer taken for a duplicate.
+    pub fn forget<D: Serialize>(&self, sample: &D) -> Result<(), ModelError> {
+        let hash = hash(sample)?;
+        let mut seen = self.seen.lock()?;
+        if seen.hashes.remove(&hash) {
+            seen.order.retain(|&(_, old)| old != hash);
+        }
+        Ok(())
+    }
+
+    fn check(&self, hash: u64, now: Instant) -> Result<bool, ModelError> {
+        let mut seen = self.seen.lock()?;
+        while let Some(&(at, old)) = seen.order.front() {
+            if now.duration_since(at) < self.config.window {
+                break;
+            }
+            seen.order.pop_front();
+            seen.hashes.remove(&old);
+        }
+        if seen.hashes.contains(&hash) {
+            return Ok(true);
+        }
+        while seen.order.len() >= self.config.max_entries.max(1) {
+            if let Some((_, old)) = seen.order.pop_front() {
+                seen.hashes.remove(&old);
+            }
+        }
+        seen.hashes.insert(hash);
+        seen.order.push_back((now, hash));
+        Ok(false)
+    }
+}
+
+fn hash<D: Serialize>(sample: &D) -> Result<u64, ModelError> {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(&serde_json::to_vec(sample)?);
+    Ok(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::early_stopping::GuardConfig;
+    use crate::handlers::AppState;
+    use crate::model::Model;
+    use crate::server::configure;
+    use crate::service::{OmlService, TrainingOutcome};
+    use crate::test::ScalingAlgorithm;
+    use actix_web::{test as actix_test, web, App};
+
+    #[test]
+    fn test_duplicates_forgotten_after_window_or_capacity() {
+        let mut config = DedupConfig::new(Duration::from_secs(10));
+        config.max_entries = 2;
+        let dedup = DedupWindow::new(config);
+        let start = Instant::now();
+        assert!(!dedup.check(1, start).unwrap());
+        assert!(dedup.check(1, start + Duration::from_secs(9)).unwrap());
+        assert!(!dedup.check(1, start + Duration::from_secs(10)).unwrap());
+
+        let later = start + Duration::from_secs(11);
+        assert!(!dedup.check(2, later).unwrap());
+        assert!(!dedup.check(3, later).unwrap());
+        assert_eq!(dedup.len(), 2);
+        // Capacity pushed the first sample out early.
+        assert!(!dedup.check(1, later).unwrap());
+        assert!(dedup.check(3, later).unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn test_double_delivered_sample_trained_once() {
+        let state = AppState::new(Model::with_parameters(vec![1.0f64]), ScalingAlgorithm)
+            .with_dedup(DedupConfig::new(Duration::from_secs(60)));
+        let service = OmlService::new(state);
+        assert_eq!(
+            service.train(2.0, false).await.unwrap(),
+            TrainingOutcome::Applied
+        );
+        assert_eq!(
+            service.train(2.0, false).await.unwrap(),
+            TrainingOutcome::Duplicate
+        );
+        service.train(3.0, false).await.unwrap();
+        assert_eq!(service.metrics().counters["duplicate_samples_skipped"], 1);
+        assert_eq!(service.metrics().counters["training_steps"], 2);
+    }
+
+    #[actix_rt::test]
+    async fn test_retry_after_failed_step_is_trained() {
+        let dedup = DedupConfig::new(Duration::from_secs(60));
+        let guard = GuardConfig {
+            patience: 1,
+            ..Default::default()
+        };
+        let state = web::Data::new(
+            AppState::new(Model::with_parameters(vec![1.0f64]), ScalingAlgorithm)
+                .with_dedup(dedup.clone())
+                .with_training_guard(guard),
+        );
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .configure(configure::<f64, ScalingAlgorithm>),
+        )
+        .await;
+        let train = || {
+            actix_test::TestRequest::post()
+                .uri("/training")
+                .set_json(2.0)
+                .to_request()
+        };
+
+        // A degraded evaluation pauses training.
+        let guard = state.training_guard.as_ref().unwrap();
+        guard.observe(&state.model, 1.0).unwrap();
+        guard.observe(&state.model, 2.0).unwrap();
+        assert_eq!(actix_test::call_service(&app, train()).await.status(), 503);
+        guard.resume().unwrap();
+        assert_eq!(actix_test::call_service(&app, train()).await.status(), 200);
+        assert_eq!(state.model.read_params()[0], 2.0);
+        let response = actix_test::call_service(&app, train()).await;
+        assert_eq!(response.status(), 202);
+        let body: serde_json::Value = actix_test::read_body_json(response).await;
+        assert_eq!(body["duplicate"], true);
+
+        // A rejected sample is rejected again rather than skipped.
+        let state = web::Data::new(
+            AppState::new(
+                Model::with_parameters(vec![1.0f64]).with_input_dimension(2),
+                ScalingAlgorithm,
+            )
+            .with_dedup(dedup),
+        );
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .configure(configure::<f64, ScalingAlgorithm>),
+        )
+        .await;
+        for _ in 0..2 {
+            assert_eq!(actix_test::call_service(&app, train()).await.status(), 400);
+        }
+        assert!(state.dedup.as_ref().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,234 @@
+use crate::errors::ModelError;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::mem::size_of;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Header through which clients attach an event id to an inference request.
+pub const EVENT_ID_HEADER: &str = "X-Oml-Event-Id";
+
+/// Configuration of the pending-predictions store.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DelayedLabelConfig {
+    /// Maximum number of predictions awaiting a label; the oldest are evicted.
+    pub capacity: usize,
+    /// Predictions older than this can no longer be joined with a label.
+    pub ttl: Duration,
+    /// Pass joined labels to [`Algorithm::feedback_step`](crate::algorithm::Algorithm::feedback_step).
+    pub train_on_feedback: bool,
+}
+
+impl Default for DelayedLabelConfig {
+    fn default() -> Self {
+        DelayedLabelConfig {
+            capacity: 100_000,
+            ttl: Duration::from_secs(24 * 60 * 60),
+            train_on_feedback: false,
+        }
+    }
+}
+
+/// A served prediction waiting for its label.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingPrediction<I, O = I> {
+    pub input: I,
+    /// Raw model output, before calibration.
+    pub prediction: O,
+    /// Segment the inference request was tagged with, for sliced metrics.
+    pub segment: Option<String>,
+    pub recorded_at: Instant,
+}
+
+#[derive(Debug)]
+struct Entries<I, O> {
+    by_id: HashMap<String, PendingPrediction<I, O>>,
+    /// Insertion order, for eviction. May hold ids already taken.
+    order: VecDeque<(String, Instant)>,
+    /// Ids whose label is being applied, see [`PendingPredictions::claim`].
+    claimed: HashSet<String>,
+}
+
+/// Predictions keyed by client-supplied event id, joined later with labels.
+#[derive(Debug)]
+pub struct PendingPredictions<I, O = I> {
+    config: DelayedLabelConfig,
+    entries: Mutex<Entries<I, O>>,
+}
+
+impl<I, O> PendingPredictions<I, O> {
+    pub fn new(config: DelayedLabelConfig) -> Self {
+        PendingPredictions {
+            config,
+            entries: Mutex::new(Entries {
+                by_id: HashMap::new(),
+                order: VecDeque::new(),
+                claimed: HashSet::new(),
+            }),
+        }
+    }
+
+    pub fn config(&self) -> &DelayedLabelConfig {
+        &self.config
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().map(|e| e.by_id.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Approximate bytes of memory the pending predictions hold; what the
+    /// inputs and predictions themselves point to is not counted.
+    pub fn memory_bytes(&self) -> usize {
+        self.entries.lock().map_or(0, |entries| {
+            let by_id: usize = entries
+                .by_id
+                .iter()
+                .map(|(id, p)| {
+                    id.len()
+                        + p.segment.as_ref().map_or(0, String::len)
+                        + size_of::<(String, PendingPrediction<I, O>)>()
+                })
+                .sum();
+            let order: usize = entries
+                .order
+                .iter()
+                .map(|(id, _)| id.len() + size_of::<(String, Instant)>())
+                .sum();
+            by_id + order
+        })
+    }
+
+    /// Records the prediction served for `event_id`, replacing any earlier one.
+    pub fn record(&self, event_id: &str, input: I, prediction: O) -> Result<(), ModelError> {
+        self.record_in_segment(event_id, None, input, prediction)
+    }
+
+    /// Records the prediction served for `event_id` to a request tagged
+    /// with `segment`.
+    pub fn record_in_segment(
+        &self,
+        event_id: &str,
+        segment: Option<String>,
+        input: I,
+        prediction: O,
+    ) -> Result<(), ModelError> {
+        let now = Instant::now();
+        let mut entries = self.entries.lock()?;
+        entries.by_id.insert(
+            event_id.to_string(),
+            PendingPrediction {
+                input,
+                prediction,
+                segment,
+                recorded_at: now,
+            },
+        );
+        entries.order.push_back((event_id.to_string(), now));
+        while let Some((id, at)) = entries.order.front().cloned() {
+            let expired = now.duration_since(at) > self.config.ttl;
+            if !expired && entries.by_id.len() <= self.config.capacity {
+                break;
+            }
+            entries.order.pop_front();
+            // Only evict if the entry was not re-recorded since.
+            if entries.by_id.get(&id).is_some_and(|p| p.recorded_at == at) {
+                entries.by_id.remove(&id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes and returns the prediction for `event_id`, unless unknown or expired.
+    pub fn take(&self, event_id: &str) -> Result<Option<PendingPrediction<I, O>>, ModelError> {
+        let pending = self.entries.lock()?.by_id.remove(event_id);
+        Ok(pending.filter(|p| p.recorded_at.elapsed() <= self.config.ttl))
+    }
+
+    /// Returns the prediction for `event_id` without removing it, unless
+    /// unknown, expired or already claimed, and claims it until
+    /// [`complete`](Self::complete) or [`release`](Self::release): a label
+    /// that fails to apply can be posted again, and one posted twice
+    /// concurrently is applied once.
+    pub fn claim(&self, event_id: &str) -> Result<Option<PendingPrediction<I, O>>, ModelError>
+    where
+        I: Clone,
+        O: Clone,
+    {
+        let mut entries = self.entries.lock()?;
+        let pending = entries
+            .by_id
+            .get(event_id)
+            .filter(|p| p.recorded_at.elapsed() <= self.config.ttl)
+            .cloned();
+        if pending.is_none() || !entries.claimed.insert(event_id.to_string()) {
+            return Ok(None);
+        }
+        Ok(pending)
+    }
+
+    /// Removes the `claimed` prediction for `event_id` once its label was
+    /// applied; one recorded again since is kept.
+    pub fn complete(
+        &self,
+        event_id: &str,
+        claimed: &PendingPrediction<I, O>,
+    ) -> Result<(), ModelError> {
+        let mut entries = self.entries.lock()?;
+        entries.claimed.remove(event_id);
+        if entries
+            .by_id
+            .get(event_id)
+            .is_some_and(|p| p.recorded_at == claimed.recorded_at)
+        {
+            entries.by_id.remove(event_id);
+        }
+        Ok(())
+    }
+
+    /// Gives up the claim on `event_id`, keeping its prediction pending.
+    pub fn release(&self, event_id: &str) -> Result<(), ModelError> {
+        self.entries.lock()?.claimed.remove(event_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capacity_and_ttl_eviction() {
+        let store = PendingPredictions::new(DelayedLabelConfig {
+            capacity: 2,
+            ttl: Duration::from_millis(50),
+            train_on_feedback: false,
+        });
+        store.record("a", 1.0, 0.5).unwrap();
+        store.record("b", 2.0, 0.6).unwrap();
+        store.record("c", 3.0, 0.7).unwrap();
+        assert_eq!(store.len(), 2);
+        assert!(store.take("a").unwrap().is_none());
+        assert_eq!(store.take("b").unwrap().unwrap().prediction, 0.6);
+        assert!(store.take("b").unwrap().is_none());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(store.take("c").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_claimed_prediction_kept_until_completed() {
+        let store = PendingPredictions::new(DelayedLabelConfig::default());
+        store.record("a", 1.0, 0.5).unwrap();
+        let claimed = store.claim("a").unwrap().unwrap();
+        assert!(store.claim("a").unwrap().is_none());
+        store.release("a").unwrap();
+        assert_eq!(store.claim("a").unwrap(), Some(claimed.clone()));
+        store.complete("a", &claimed).unwrap();
+        assert!(store.is_empty());
+        assert!(store.claim("a").unwrap().is_none());
+    }
+}
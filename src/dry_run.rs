@@ -0,0 +1,113 @@
+use crate::algorithm::Algorithm;
+use crate::errors::ModelError;
+use crate::model::Model;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Parameter update a training sample would cause, computed without
+/// touching the live model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParameterUpdate<T> {
+    /// New minus current value, per parameter.
+    pub delta: Vec<T>,
+    /// Euclidean norm of `delta`.
+    pub delta_norm: f64,
+    /// Indices of the parameters the update changes.
+    pub affected: Vec<usize>,
+}
+
+/// Holdout MAE before and after the update.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricChange {
+    pub mae_before: f64,
+    pub mae_after: f64,
+}
+
+/// Response body of `POST /training/dry-run`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DryRunReport<T> {
+    #[serde(flatten)]
+    pub update: ParameterUpdate<T>,
+    /// Predicted effect on the holdout set; `None` without a registered one.
+    pub metric_change: Option<MetricChange>,
+}
+
+/// Applies `x` to a copy of `model` and returns the copy together with the
+/// update it received.
+pub fn preview_update<T, A>(
+    model: &Model<T>,
+    algorithm: &A,
+    x: A::Input,
+) -> Result<(Model<T>, ParameterUpdate<T>), ModelError>
+where
+    T: Float + Debug + Send + Sync + Sum,
+    A: Algorithm<T> + ?Sized,
+{
+    let before = model.read_params().clone();
+    let scratch = Model::with_parameters(before.clone());
+    algorithm.training_step(&scratch, x)?;
+
+    let after = scratch.read_params().clone();
+    if after.len() != before.len() {
+        return Err(ModelError::InvalidInput(format!(
+            "training step resized the model from {} to {} parameters",
+            before.len(),
+            after.len()
+        )));
+    }
+    let delta: Vec<T> = after.iter().zip(&before).map(|(a, b)| *a - *b).collect();
+    let affected = delta
+        .iter()
+        .enumerate()
+        .filter(|(_, d)| !d.is_zero())
+        .map(|(i, _)| i)
+        .collect();
+    let delta_norm = delta
+        .iter()
+        .map(|d| d.to_f64().unwrap_or(f64::NAN).powi(2))
+        .sum::<f64>()
+        .sqrt();
+    Ok((
+        scratch,
+        ParameterUpdate {
+            delta,
+            delta_norm,
+            affected,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Adds the input to the second parameter only.
+    struct SecondAlgorithm;
+
+    impl Algorithm<f64> for SecondAlgorithm {
+        type Input = f64;
+        type Output = f64;
+
+        fn training_step(&self, model: &Model<f64>, x: f64) -> Result<(), ModelError> {
+            model.write_params()[1] += x;
+            Ok(())
+        }
+
+        fn inference_step(&self, _model: &Model<f64>, x: f64) -> Result<f64, ModelError> {
+            Ok(x)
+        }
+    }
+
+    #[test]
+    fn test_preview_leaves_model_untouched() {
+        let model = Model::with_parameters(vec![1.0, 2.0, 3.0]);
+        let (scratch, update) = preview_update(&model, &SecondAlgorithm, 4.0).unwrap();
+        assert_eq!(update.delta, vec![0.0, 4.0, 0.0]);
+        assert_eq!(update.affected, vec![1]);
+        assert_eq!(update.delta_norm, 4.0);
+        assert_eq!(scratch.read_params()[1], 6.0);
+        assert_eq!(model.read_params()[1], 2.0);
+    }
+}
@@ -0,0 +1,205 @@
+use crate::errors::ModelError;
+use crate::model::Model;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, RwLock};
+
+/// What the guard does once degradation is sustained.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuardAction {
+    /// Reject training until an operator resumes it (`POST /training/resume`).
+    #[default]
+    Pause,
+    /// Restore the parameters of the best evaluation and keep training.
+    Revert,
+}
+
+/// Configuration of the training kill-switch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GuardConfig {
+    /// Relative increase of the evaluation MAE over the best one seen that
+    /// counts as degraded (0.1 = 10% worse).
+    pub tolerance: f64,
+    /// Consecutive degraded evaluations before the guard trips.
+    pub patience: usize,
+    pub action: GuardAction,
+}
+
+impl Default for GuardConfig {
+    fn default() -> Self {
+        GuardConfig {
+            tolerance: 0.1,
+            patience: 3,
+            action: GuardAction::Pause,
+        }
+    }
+}
+
+/// Outcome of feeding one evaluation to the guard.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuardVerdict {
+    Healthy,
+    Degrading { streak: usize },
+    Tripped { action: GuardAction },
+}
+
+/// State reported by `GET /training/guard`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GuardStatus {
+    pub paused: bool,
+    pub best_mae: Option<f64>,
+    pub degraded_streak: usize,
+    pub trips: u64,
+}
+
+#[derive(Debug)]
+struct GuardState<T> {
+    best_mae: Option<f64>,
+    /// Parameters at the time of the best evaluation.
+    snapshot: Option<Vec<T>>,
+    streak: usize,
+    trips: u64,
+}
+
+/// Early-stopping guard watching rolling evaluation metrics.
+///
+/// Every evaluation improving on the best MAE so far snapshots the
+/// parameters. Evaluations worse than the best by more than `tolerance`
+/// extend a streak; after `patience` of them training is paused or the
+/// snapshot is restored.
+#[derive(Debug)]
+pub struct TrainingGuard<T> {
+    config: RwLock<GuardConfig>,
+    state: Mutex<GuardState<T>>,
+    paused: AtomicBool,
+}
+
+impl<T> TrainingGuard<T>
+where
+    T: Float + Debug + Send + Sync,
+{
+    pub fn new(config: GuardConfig) -> Self {
+        TrainingGuard {
+            config: RwLock::new(config),
+            state: Mutex::new(GuardState {
+                best_mae: None,
+                snapshot: None,
+                streak: 0,
+                trips: 0,
+            }),
+            paused: AtomicBool::new(false),
+        }
+    }
+
+    pub fn config(&self) -> Result<GuardConfig, ModelError> {
+        Ok(self.config.read()?.clone())
+    }
+
+    /// Replaces the thresholds; the current streak and best MAE are kept.
+    pub fn set_config(&self, config: GuardConfig) -> Result<(), ModelError> {
+        *self.config.write()? = config;
+        Ok(())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Re-enables training after a pause and starts a fresh streak.
+    pub fn resume(&self) -> Result<(), ModelError> {
+        self.state.lock()?.streak = 0;
+        self.paused.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub fn status(&self) -> Result<GuardStatus, ModelError> {
+        let state = self.state.lock()?;
+        Ok(GuardStatus {
+            paused: self.is_paused(),
+            best_mae: state.best_mae,
+            degraded_streak: state.streak,
+            trips: state.trips,
+        })
+    }
+
+    /// Feeds the MAE of an evaluation of `model`'s current parameters.
+    pub fn observe(&self, model: &Model<T>, mae: f64) -> Result<GuardVerdict, ModelError> {
+        if !mae.is_finite() {
+            return Ok(GuardVerdict::Healthy);
+        }
+        let config = self.config()?;
+        let mut state = self.state.lock()?;
+        match state.best_mae {
+            Some(best) if mae > best * (1.0 + config.tolerance) => {}
+            Some(best) if mae > best => {
+                state.streak = 0;
+                return Ok(GuardVerdict::Healthy);
+            }
+            _ => {
+                state.best_mae = Some(mae);
+                state.snapshot = Some(model.read_params().clone());
+                state.streak = 0;
+                return Ok(GuardVerdict::Healthy);
+            }
+        }
+
+        state.streak += 1;
+        if state.streak < config.patience.max(1) {
+            return Ok(GuardVerdict::Degrading {
+                streak: state.streak,
+            });
+        }
+        state.streak = 0;
+        state.trips += 1;
+        match config.action {
+            GuardAction::Pause => self.paused.store(true, Ordering::SeqCst),
+            GuardAction::Revert => {
+                if let Some(snapshot) = &state.snapshot {
+                    let mut params = model.write_params();
+                    if params.len() == snapshot.len() {
+                        params.copy_from_slice(snapshot);
+                    }
+                }
+            }
+        }
+        Ok(GuardVerdict::Tripped {
+            action: config.action,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sustained_degradation_trips_guard() {
+        let model = Model::with_parameters(vec![1.0f64]);
+        let guard = TrainingGuard::new(GuardConfig {
+            tolerance: 0.5,
+            patience: 2,
+            action: GuardAction::Revert,
+        });
+        assert_eq!(guard.observe(&model, 1.0).unwrap(), GuardVerdict::Healthy);
+
+        model.write_params()[0] = 9.0;
+        assert_eq!(guard.observe(&model, 1.2).unwrap(), GuardVerdict::Healthy);
+        assert_eq!(
+            guard.observe(&model, 2.0).unwrap(),
+            GuardVerdict::Degrading { streak: 1 }
+        );
+        assert_eq!(
+            guard.observe(&model, 2.0).unwrap(),
+            GuardVerdict::Tripped {
+                action: GuardAction::Revert
+            }
+        );
+        assert_eq!(model.read_params()[0], 1.0);
+        assert!(!guard.is_paused());
+        assert_eq!(guard.status().unwrap().trips, 1);
+    }
+}
@@ -0,0 +1,248 @@
+use crate::errors::ModelError;
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use rand::RngCore;
+use std::fmt;
+
+/// Environment variable holding the key of [`EncryptionKey::from_env`].
+pub const ENCRYPTION_KEY_ENV: &str = "OML_ENCRYPTION_KEY";
+
+/// Environment variable that, set to `1` or `true`, lets the key of
+/// [`EncryptionKey::from_env`] read plain files, see
+/// [`EncryptionKey::with_plaintext_migration`].
+pub const PLAINTEXT_MIGRATION_ENV: &str = "OML_ENCRYPTION_MIGRATE";
+
+/// First bytes of an encrypted file, followed by the nonce and the
+/// ciphertext with its authentication tag.
+pub const ENCRYPTED_MAGIC: &[u8; 8] = b"OMLCRYPT";
+
+const NONCE_BYTES: usize = 12;
+
+/// AES-256-GCM key encrypting the model files and checkpoints written to
+/// disk, since learned weights can leak information about the samples
+/// they were trained on.
+///
+/// Every encryption draws a fresh random nonce from the operating system,
+/// never from [`seed::rng`](crate::seed::rng), so deterministic mode does
+/// not reuse nonces. The authentication tag makes a wrong key or a
+/// tampered file fail to decrypt rather than decode to garbage.
+///
+/// Plain files are refused where a key is configured, so one cannot be
+/// swapped in for an encrypted file, unless the key is built
+/// [`with_plaintext_migration`](EncryptionKey::with_plaintext_migration)
+/// while existing files are being moved to encryption.
+///
+/// # Examples
+///
+/// ```
+/// use oml::encryption::EncryptionKey;
+///
+/// let key = EncryptionKey::from_hex(&"2a".repeat(32)).unwrap();
+/// let sealed = key.encrypt(b"weights").unwrap();
+/// assert_eq!(key.decrypt(&sealed).unwrap(), b"weights");
+/// assert!(EncryptionKey::generate().decrypt(&sealed).is_err());
+/// ```
+#[derive(Clone)]
+pub struct EncryptionKey {
+    bytes: [u8; 32],
+    /// Plain files are read too, see [`EncryptionKey::with_plaintext_migration`].
+    plaintext_migration: bool,
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("EncryptionKey([redacted])")
+    }
+}
+
+impl EncryptionKey {
+    pub fn new(bytes: [u8; 32]) -> Self {
+        EncryptionKey {
+            bytes,
+            plaintext_migration: false,
+        }
+    }
+
+    /// A random key.
+    pub fn generate() -> Self {
+        let mut bytes = [0; 32];
+        rand::rngs::OsRng.fill_bytes(&mut bytes);
+        Self::new(bytes)
+    }
+
+    /// Parses a key written as 64 hexadecimal digits.
+    pub fn from_hex(hex: &str) -> Result<Self, ModelError> {
+        let hex = hex.trim();
+        let invalid = || {
+            ModelError::InvalidInput("an encryption key must be 64 hexadecimal digits".to_string())
+        };
+        if hex.len() != 64 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let mut bytes = [0; 32];
+        for (byte, digits) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            let digits = std::str::from_utf8(digits).map_err(|_| invalid())?;
+            *byte = u8::from_str_radix(digits, 16).map_err(|_| invalid())?;
+        }
+        Ok(Self::new(bytes))
+    }
+
+    /// The key in `OML_ENCRYPTION_KEY`, `None` if the variable is unset,
+    /// reading plain files too if `OML_ENCRYPTION_MIGRATE` is set.
+    pub fn from_env() -> Result<Option<Self>, ModelError> {
+        let Ok(hex) = std::env::var(ENCRYPTION_KEY_ENV) else {
+            return Ok(None);
+        };
+        let key = Self::from_hex(&hex)?;
+        match std::env::var(PLAINTEXT_MIGRATION_ENV).as_deref() {
+            Ok("1" | "true") => Ok(Some(key.with_plaintext_migration())),
+            _ => Ok(Some(key)),
+        }
+    }
+
+    /// Also reads plain files, for a deployment moving existing model
+    /// files and checkpoints to encryption; everything written is still
+    /// encrypted. Drop it once the files were rewritten.
+    pub fn with_plaintext_migration(mut self) -> Self {
+        self.plaintext_migration = true;
+        self
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.bytes))
+    }
+
+    /// Seals `plaintext` as [`ENCRYPTED_MAGIC`], a random nonce and the
+    /// authenticated ciphertext.
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, ModelError> {
+        let mut nonce = [0; NONCE_BYTES];
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+        let ciphertext = self
+            .cipher()
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .map_err(|_| ModelError::StorageError("encryption failed".to_string()))?;
+        let mut sealed = ENCRYPTED_MAGIC.to_vec();
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    /// Opens bytes sealed by [`EncryptionKey::encrypt`]; fails if they were
+    /// sealed with another key, altered or cut short.
+    pub fn decrypt(&self, sealed: &[u8]) -> Result<Vec<u8>, ModelError> {
+        let body = sealed
+            .strip_prefix(ENCRYPTED_MAGIC.as_slice())
+            .ok_or_else(|| ModelError::InvalidInput("the data is not encrypted".to_string()))?;
+        if body.len() < NONCE_BYTES {
+            return Err(ModelError::StorageError(
+                "truncated encrypted data".to_string(),
+            ));
+        }
+        let (nonce, ciphertext) = body.split_at(NONCE_BYTES);
+        self.cipher()
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| {
+                ModelError::InvalidInput(
+                    "decryption failed: wrong key or corrupted data".to_string(),
+                )
+            })
+    }
+}
+
+/// Whether `bytes` were written by [`EncryptionKey::encrypt`].
+pub fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(ENCRYPTED_MAGIC)
+}
+
+/// Decrypts `bytes` if they are encrypted, failing without a key, and
+/// returns plain ones as they are when no key is configured or the key
+/// allows [plaintext migration](EncryptionKey::with_plaintext_migration).
+pub fn open(bytes: Vec<u8>, key: Option<&EncryptionKey>) -> Result<Vec<u8>, ModelError> {
+    match (is_encrypted(&bytes), key) {
+        (false, Some(key)) if !key.plaintext_migration => Err(ModelError::InvalidInput(
+            "the data is not encrypted but a key is configured".to_string(),
+        )),
+        (false, _) => Ok(bytes),
+        (true, Some(key)) => key.decrypt(&bytes),
+        (true, None) => Err(ModelError::InvalidInput(
+            "the data is encrypted and no key is configured".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::{CheckpointConfig, Checkpointer};
+    use crate::model::{Model, SavedModel};
+    use std::fs;
+
+    #[test]
+    fn test_model_files_and_checkpoints_encrypted_at_rest() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = EncryptionKey::generate();
+        let model = Model::with_parameters(vec![1.5f64, -2.25]);
+        for file in ["model.json", "model.bin"] {
+            let path = dir.path().join(file);
+            model.save_encrypted(&path, &key).unwrap();
+            let bytes = fs::read(&path).unwrap();
+            assert!(is_encrypted(&bytes));
+            assert!(!String::from_utf8_lossy(&bytes).contains("parameters"));
+            let loaded: Model<f64> = Model::load_encrypted(&path, &key).unwrap();
+            assert_eq!(loaded.read_params().as_slice(), &[1.5, -2.25]);
+            // Without the key, or with another one, the file is refused.
+            assert!(Model::<f64>::load(&path).is_err());
+            assert!(Model::<f64>::load_encrypted(&path, &EncryptionKey::generate()).is_err());
+        }
+        // Plain files are refused when a key is configured, unless migrating.
+        let plain = dir.path().join("plain.json");
+        model.save(&plain).unwrap();
+        assert!(SavedModel::<f64>::read_encrypted(&plain, &key).is_err());
+        let migrating = key.clone().with_plaintext_migration();
+        assert!(SavedModel::<f64>::read_encrypted(&plain, &migrating).is_ok());
+
+        let checkpoints = dir.path().join("checkpoints");
+        let config = CheckpointConfig::new(&checkpoints).with_encryption(key.clone());
+        let checkpointer = Checkpointer::new(config.clone()).unwrap();
+        checkpointer.save(&model).unwrap();
+        let file = checkpoints.join(format!("checkpoint-{:020}.json", 1));
+        assert!(is_encrypted(&fs::read(file).unwrap()));
+        let restored = Model::with_parameters(vec![0.0f64; 2]);
+        assert_eq!(
+            Checkpointer::new(config)
+                .unwrap()
+                .restore(&restored)
+                .unwrap(),
+            Some(1)
+        );
+        assert_eq!(restored.read_params().as_slice(), &[1.5, -2.25]);
+        // Restoring without the key fails rather than starting afresh.
+        let keyless = Checkpointer::<f64>::new(CheckpointConfig::new(&checkpoints)).unwrap();
+        assert!(keyless.restore(&restored).is_err());
+        // So do plain checkpoints with a key, unless migrating.
+        let plain = dir.path().join("plain-checkpoints");
+        Checkpointer::new(CheckpointConfig::new(&plain))
+            .unwrap()
+            .save(&model)
+            .unwrap();
+        let strict = CheckpointConfig::new(&plain).with_encryption(key.clone());
+        assert!(Checkpointer::<f64>::new(strict)
+            .unwrap()
+            .restore(&restored)
+            .is_err());
+        let migrating = CheckpointConfig::new(&plain).with_encryption(migrating);
+        assert_eq!(
+            Checkpointer::<f64>::new(migrating)
+                .unwrap()
+                .restore(&restored)
+                .unwrap(),
+            Some(1)
+        );
+        // The write-ahead log is not encrypted, so it is refused.
+        let logged = CheckpointConfig::new(&checkpoints)
+            .with_encryption(key)
+            .with_write_ahead_log(false);
+        assert!(Checkpointer::<f64>::new(logged).is_err());
+        assert!(EncryptionKey::from_hex("abc").is_err());
+    }
+}
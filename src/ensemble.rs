@@ -0,0 +1,616 @@
+use crate::algorithm::{Algorithm, Datum};
+use crate::errors::ModelError;
+use crate::service::{InferenceRequest, InferenceResponse, OmlService, ServiceError};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// How an ensemble turns the predictions of its members into its own.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Combiner {
+    /// Plain average of the member scores.
+    Mean,
+    /// Average of the member scores weighted by the member weights.
+    WeightedMean,
+    /// The prediction with the largest total member weight, ties going to
+    /// the earliest member; for class labels and other non-numeric outputs.
+    MajorityVote,
+    /// A learned linear blend of the member scores: meta-weights starting
+    /// at the normalized member weights, plus a bias, updated by normalized
+    /// least mean squares on every labeled sample of
+    /// `POST /ensembles/{name}/feedback`.
+    Stacking { learning_rate: f64 },
+}
+
+/// A catalog model taking part in an ensemble.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnsembleMember {
+    pub model: String,
+    /// A retained version of the model; the live parameters if unset.
+    #[serde(default)]
+    pub version: Option<u64>,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+impl EnsembleMember {
+    pub fn new(model: impl Into<String>) -> Self {
+        EnsembleMember {
+            model: model.into(),
+            version: None,
+            weight: default_weight(),
+        }
+    }
+
+    pub fn with_version(mut self, version: u64) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    pub fn with_weight(mut self, weight: f64) -> Self {
+        self.weight = weight;
+        self
+    }
+}
+
+/// Body of `PUT /ensembles/{name}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnsembleSpec {
+    pub members: Vec<EnsembleMember>,
+    pub combiner: Combiner,
+}
+
+impl EnsembleSpec {
+    pub fn new(combiner: Combiner) -> Self {
+        EnsembleSpec {
+            members: Vec::new(),
+            combiner,
+        }
+    }
+
+    pub fn member(mut self, member: EnsembleMember) -> Self {
+        self.members.push(member);
+        self
+    }
+
+    /// Checks the spec can combine predictions: at least one member, finite
+    /// non-negative weights not all zero, and a positive learning rate.
+    pub fn validate(&self) -> Result<(), ModelError> {
+        let invalid = |message: &str| Err(ModelError::InvalidInput(message.to_string()));
+        if self.members.is_empty() {
+            return invalid("an ensemble needs at least one member");
+        }
+        if self
+            .members
+            .iter()
+            .any(|m| !m.weight.is_finite() || m.weight < 0.0)
+        {
+            return invalid("member weights must be finite and non-negative");
+        }
+        if self.members.iter().all(|m| m.weight == 0.0) {
+            return invalid("at least one member weight must be positive");
+        }
+        match self.combiner {
+            Combiner::Stacking { learning_rate }
+                if learning_rate.is_nan() || learning_rate <= 0.0 =>
+            {
+                invalid("the stacking learning rate must be positive")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Listing entry of `GET /ensembles`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnsembleInfo {
+    pub name: String,
+    #[serde(flatten)]
+    pub spec: EnsembleSpec,
+    /// Current meta-weights of a stacking ensemble, one per member.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta_weights: Option<Vec<f64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bias: Option<f64>,
+    /// Labeled samples the meta-weights learned from.
+    pub updates: u64,
+}
+
+/// The prediction of one member, as reported by ensemble inference.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemberPrediction<O> {
+    pub model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+    pub prediction: O,
+}
+
+/// Response of `POST /ensembles/{name}/inference`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnsemblePrediction<O> {
+    pub prediction: O,
+    pub members: Vec<MemberPrediction<O>>,
+}
+
+#[derive(Debug, Clone)]
+struct MetaWeights {
+    weights: Vec<f64>,
+    bias: f64,
+    updates: u64,
+}
+
+/// A virtual model serving the combined predictions of several catalog
+/// models, e.g. models of different algorithms or configurations trained
+/// on the same stream, or successive versions of one model.
+///
+/// # Examples
+///
+/// ```
+/// use oml::ensemble::{Combiner, Ensemble, EnsembleMember, EnsembleSpec};
+///
+/// let ensemble = Ensemble::new(
+///     EnsembleSpec::new(Combiner::WeightedMean)
+///         .member(EnsembleMember::new("linear").with_weight(3.0))
+///         .member(EnsembleMember::new("trees")),
+/// );
+/// assert_eq!(ensemble.combine(&[2.0f64, 6.0]).unwrap(), 3.0);
+/// ```
+#[derive(Debug)]
+pub struct Ensemble {
+    spec: EnsembleSpec,
+    meta: Mutex<MetaWeights>,
+}
+
+impl Ensemble {
+    pub fn new(spec: EnsembleSpec) -> Self {
+        let total: f64 = spec.members.iter().map(|m| m.weight).sum();
+        let weights = spec
+            .members
+            .iter()
+            .map(|m| match total > 0.0 {
+                true => m.weight / total,
+                false => 0.0,
+            })
+            .collect();
+        Ensemble {
+            spec,
+            meta: Mutex::new(MetaWeights {
+                weights,
+                bias: 0.0,
+                updates: 0,
+            }),
+        }
+    }
+
+    pub fn spec(&self) -> &EnsembleSpec {
+        &self.spec
+    }
+
+    fn meta(&self) -> MutexGuard<'_, MetaWeights> {
+        self.meta.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn info(&self, name: &str) -> EnsembleInfo {
+        let meta = self.meta().clone();
+        let stacking = matches!(self.spec.combiner, Combiner::Stacking { .. });
+        EnsembleInfo {
+            name: name.to_string(),
+            spec: self.spec.clone(),
+            meta_weights: stacking.then_some(meta.weights),
+            bias: stacking.then_some(meta.bias),
+            updates: meta.updates,
+        }
+    }
+
+    /// Combines `predictions`, one per member in order.
+    pub fn combine<O: Datum>(&self, predictions: &[O]) -> Result<O, ModelError> {
+        if predictions.len() != self.spec.members.len() || predictions.is_empty() {
+            return Err(ModelError::InvalidInput(format!(
+                "expected {} member predictions, got {}",
+                self.spec.members.len(),
+                predictions.len()
+            )));
+        }
+        if self.spec.combiner == Combiner::MajorityVote {
+            return self.vote(predictions);
+        }
+        let scores = scores(predictions)?;
+        let score = match &self.spec.combiner {
+            Combiner::Mean => scores.iter().sum::<f64>() / scores.len() as f64,
+            Combiner::WeightedMean => {
+                let weights = self.spec.members.iter().map(|m| m.weight);
+                let total: f64 = weights.clone().sum();
+                if total.is_nan() || total <= 0.0 {
+                    return Err(ModelError::InvalidInput(
+                        "the member weights sum to zero".to_string(),
+                    ));
+                }
+                scores.iter().zip(weights).map(|(s, w)| s * w).sum::<f64>() / total
+            }
+            _ => {
+                let meta = self.meta();
+                meta.bias + dot(&meta.weights, &scores)
+            }
+        };
+        O::from_score(score).ok_or_else(|| {
+            ModelError::Unsupported(format!("{} is not a prediction of the members", score))
+        })
+    }
+
+    fn vote<O: Datum>(&self, predictions: &[O]) -> Result<O, ModelError> {
+        // Outputs are compared as JSON, so labels of any type can be voted on.
+        let mut tally: Vec<(serde_json::Value, f64, &O)> = Vec::new();
+        for (prediction, member) in predictions.iter().zip(&self.spec.members) {
+            let value = serde_json::to_value(prediction)?;
+            match tally.iter_mut().find(|(v, _, _)| *v == value) {
+                Some((_, weight, _)) => *weight += member.weight,
+                None => tally.push((value, member.weight, prediction)),
+            }
+        }
+        let mut winner = &tally[0];
+        for candidate in &tally[1..] {
+            if candidate.1 > winner.1 {
+                winner = candidate;
+            }
+        }
+        Ok(winner.2.clone())
+    }
+
+    /// Moves the stacking meta-weights towards `label` given the member
+    /// `predictions`, one normalized least mean squares step. Returns the
+    /// error of the blend before the step.
+    pub fn update<O: Datum>(&self, predictions: &[O], label: &O) -> Result<f64, ModelError> {
+        let Combiner::Stacking { learning_rate } = self.spec.combiner else {
+            return Err(ModelError::Unsupported(
+                "only stacking ensembles learn from labels".to_string(),
+            ));
+        };
+        let scores = scores(predictions)?;
+        let target = label
+            .score()
+            .filter(|s| s.is_finite())
+            .ok_or_else(|| ModelError::InvalidInput("the label has no score".to_string()))?;
+        let mut meta = self.meta();
+        if scores.len() != meta.weights.len() {
+            return Err(ModelError::InvalidInput(format!(
+                "expected {} member predictions, got {}",
+                meta.weights.len(),
+                scores.len()
+            )));
+        }
+        let error = meta.bias + dot(&meta.weights, &scores) - target;
+        // Normalized by the energy of the inputs (and the bias's unit input)
+        // so the step size does not depend on the scale of the scores.
+        let step = learning_rate * error / (1.0 + dot(&scores, &scores));
+        for (weight, score) in meta.weights.iter_mut().zip(&scores) {
+            *weight -= step * score;
+        }
+        meta.bias -= step;
+        meta.updates += 1;
+        Ok(error)
+    }
+}
+
+fn scores<O: Datum>(predictions: &[O]) -> Result<Vec<f64>, ModelError> {
+    predictions
+        .iter()
+        .map(|p| p.score().filter(|s| s.is_finite()))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| {
+            ModelError::Unsupported(
+                "averaging and stacking need numeric member predictions".to_string(),
+            )
+        })
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+/// Ensembles by name, the virtual models of the `/ensembles` routes.
+#[derive(Debug, Default)]
+pub struct Ensembles {
+    entries: RwLock<BTreeMap<String, Arc<Ensemble>>>,
+}
+
+impl Ensembles {
+    pub fn new() -> Self {
+        Ensembles::default()
+    }
+
+    fn entries(&self) -> RwLockReadGuard<'_, BTreeMap<String, Arc<Ensemble>>> {
+        self.entries.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn entries_mut(&self) -> RwLockWriteGuard<'_, BTreeMap<String, Arc<Ensemble>>> {
+        self.entries.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Defines `name` as `spec`, replacing any earlier definition and its
+    /// learned meta-weights.
+    pub fn define(&self, name: impl Into<String>, spec: EnsembleSpec) -> Result<(), ModelError> {
+        spec.validate()?;
+        self.insert(name, spec);
+        Ok(())
+    }
+
+    /// Defines `name` as `spec` without validating it; an invalid spec
+    /// fails the requests to the ensemble instead.
+    pub fn insert(&self, name: impl Into<String>, spec: EnsembleSpec) {
+        self.entries_mut()
+            .insert(name.into(), Arc::new(Ensemble::new(spec)));
+    }
+
+    pub fn remove(&self, name: &str) -> bool {
+        self.entries_mut().remove(name).is_some()
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<Ensemble>> {
+        self.entries().get(name).cloned()
+    }
+
+    pub fn list(&self) -> Vec<EnsembleInfo> {
+        self.entries()
+            .iter()
+            .map(|(name, ensemble)| ensemble.info(name))
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries().is_empty()
+    }
+}
+
+/// Predictions of the members of `ensemble` for `input`, each through the
+/// full inference pipeline of its catalog model.
+async fn member_predictions<T, A>(
+    service: &OmlService<T, A>,
+    ensemble: &Ensemble,
+    input: &A::Input,
+) -> Result<Vec<A::Output>, ServiceError>
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    let mut predictions = Vec::with_capacity(ensemble.spec.members.len());
+    for member in &ensemble.spec.members {
+        let mut request = InferenceRequest::new(input.clone());
+        request.model = Some(member.model.clone());
+        request.model_version = member.version;
+        predictions.push(match service.infer(request).await? {
+            InferenceResponse::Prediction(prediction) => prediction,
+            InferenceResponse::Detailed(detailed) => detailed.prediction,
+        });
+    }
+    Ok(predictions)
+}
+
+fn lookup<T, A>(service: &OmlService<T, A>, name: &str) -> Result<Arc<Ensemble>, ServiceError>
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    let ensemble = service
+        .state()
+        .ensembles
+        .get(name)
+        .ok_or_else(|| ServiceError::NotFound(format!("unknown ensemble {}", name)))?;
+    ensemble.spec.validate()?;
+    Ok(ensemble)
+}
+
+/// Serves `input` from the ensemble `name`: every member predicts through
+/// the catalog, then the predictions are combined.
+pub async fn infer<T, A>(
+    service: &OmlService<T, A>,
+    name: &str,
+    input: A::Input,
+) -> Result<EnsemblePrediction<A::Output>, ServiceError>
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    let ensemble = lookup(service, name)?;
+    let predictions = member_predictions(service, &ensemble, &input).await?;
+    let prediction = ensemble.combine(&predictions)?;
+    service.state().metrics.increment("ensemble_inferences");
+    Ok(EnsemblePrediction {
+        prediction,
+        members: ensemble
+            .spec
+            .members
+            .iter()
+            .zip(predictions)
+            .map(|(member, prediction)| MemberPrediction {
+                model: member.model.clone(),
+                version: member.version,
+                prediction,
+            })
+            .collect(),
+    })
+}
+
+/// Updates the meta-weights of the stacking ensemble `name` on a labeled
+/// sample. The members themselves are trained through their own routes.
+pub async fn learn<T, A>(
+    service: &OmlService<T, A>,
+    name: &str,
+    input: A::Input,
+    label: A::Output,
+) -> Result<EnsembleInfo, ServiceError>
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    let ensemble = lookup(service, name)?;
+    if !matches!(ensemble.spec.combiner, Combiner::Stacking { .. }) {
+        return Err(ModelError::Unsupported(
+            "only stacking ensembles learn from labels".to_string(),
+        )
+        .into());
+    }
+    let predictions = member_predictions(service, &ensemble, &input).await?;
+    ensemble.update(&predictions, &label)?;
+    service.state().metrics.increment("ensemble_updates");
+    Ok(ensemble.info(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::ModelCatalog;
+    use crate::evaluation::LabeledSample;
+    use crate::handlers::AppState;
+    use crate::model::Model;
+    use crate::server::configure;
+    use crate::test::ScalingAlgorithm;
+    use actix_web::{http, test as actix_test, web, App};
+    use serde_json::json;
+
+    #[actix_rt::test]
+    async fn test_ensembles_served_as_virtual_models() {
+        let catalog = ModelCatalog::new(2)
+            .with_model(
+                "low",
+                Model::with_parameters(vec![1.0f64]),
+                ScalingAlgorithm,
+            )
+            .with_model(
+                "high",
+                Model::with_parameters(vec![3.0f64]),
+                ScalingAlgorithm,
+            );
+        let state = AppState::new(Model::with_parameters(vec![2.0f64]), ScalingAlgorithm)
+            .with_model_catalog("main", catalog)
+            .with_ensemble(
+                "average",
+                EnsembleSpec::new(Combiner::Mean)
+                    .member(EnsembleMember::new("low"))
+                    .member(EnsembleMember::new("high")),
+            );
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(configure::<f64, ScalingAlgorithm>),
+        )
+        .await;
+        let infer = |name: &str| {
+            actix_test::TestRequest::post()
+                .uri(&format!("/ensembles/{}/inference", name))
+                .set_json(2.0)
+                .to_request()
+        };
+        let averaged: EnsemblePrediction<f64> =
+            actix_test::call_and_read_body_json(&app, infer("average")).await;
+        assert_eq!(averaged.prediction, 4.0);
+        assert_eq!(averaged.members[1].prediction, 6.0);
+
+        // The main model is outvoted, the weights tip the mean towards it.
+        let request = actix_test::TestRequest::put()
+            .uri("/ensembles/vote")
+            .set_json(json!({
+                "combiner": {"method": "majority_vote"},
+                "members": [{"model": "main"}, {"model": "low"}, {"model": "low"}],
+            }))
+            .to_request();
+        assert!(actix_test::call_service(&app, request)
+            .await
+            .status()
+            .is_success());
+        let voted: EnsemblePrediction<f64> =
+            actix_test::call_and_read_body_json(&app, infer("vote")).await;
+        assert_eq!(voted.prediction, 2.0);
+        let weighted = json!({
+            "combiner": {"method": "weighted_mean"},
+            "members": [{"model": "main", "weight": 3.0}, {"model": "high"}],
+        });
+        let request = actix_test::TestRequest::put()
+            .uri("/ensembles/weighted")
+            .set_json(weighted)
+            .to_request();
+        actix_test::call_service(&app, request).await;
+        let weighted: EnsemblePrediction<f64> =
+            actix_test::call_and_read_body_json(&app, infer("weighted")).await;
+        assert_eq!(weighted.prediction, 4.5);
+
+        // Unknown members and invalid specs are refused.
+        let request = actix_test::TestRequest::put()
+            .uri("/ensembles/broken")
+            .set_json(json!({"combiner": {"method": "mean"}, "members": [{"model": "nope"}]}))
+            .to_request();
+        let status = actix_test::call_service(&app, request).await.status();
+        assert_eq!(status, http::StatusCode::BAD_REQUEST);
+        let status = actix_test::call_service(&app, infer("missing"))
+            .await
+            .status();
+        assert_eq!(status, http::StatusCode::NOT_FOUND);
+        let request = actix_test::TestRequest::get()
+            .uri("/ensembles")
+            .to_request();
+        let listed: Vec<EnsembleInfo> = actix_test::call_and_read_body_json(&app, request).await;
+        assert_eq!(listed.len(), 3);
+    }
+
+    #[actix_rt::test]
+    async fn test_stacking_learns_meta_weights_online() {
+        let catalog = ModelCatalog::new(2)
+            .with_model(
+                "good",
+                Model::with_parameters(vec![1.0f64]),
+                ScalingAlgorithm,
+            )
+            .with_model(
+                "biased",
+                Model::with_parameters(vec![5.0f64]),
+                ScalingAlgorithm,
+            );
+        let state = AppState::new(Model::with_parameters(vec![1.0f64]), ScalingAlgorithm)
+            .with_model_catalog("main", catalog)
+            .with_ensemble(
+                "stacked",
+                EnsembleSpec::new(Combiner::Stacking { learning_rate: 0.5 })
+                    .member(EnsembleMember::new("good"))
+                    .member(EnsembleMember::new("biased")),
+            );
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(configure::<f64, ScalingAlgorithm>),
+        )
+        .await;
+
+        // The targets follow the "good" member; the blend learns to match
+        // them instead of starting halfway to the "biased" one.
+        for i in 0..200 {
+            let x = 1.0 + (i % 5) as f64 / 4.0;
+            let request = actix_test::TestRequest::post()
+                .uri("/ensembles/stacked/feedback")
+                .set_json(LabeledSample { input: x, label: x })
+                .to_request();
+            assert!(actix_test::call_service(&app, request)
+                .await
+                .status()
+                .is_success());
+        }
+        let request = actix_test::TestRequest::get()
+            .uri("/ensembles")
+            .to_request();
+        let listed: Vec<EnsembleInfo> = actix_test::call_and_read_body_json(&app, request).await;
+        assert_eq!(listed[0].updates, 200);
+        let request = actix_test::TestRequest::post()
+            .uri("/ensembles/stacked/inference")
+            .set_json(1.5)
+            .to_request();
+        let stacked: EnsemblePrediction<f64> =
+            actix_test::call_and_read_body_json(&app, request).await;
+        assert!((stacked.prediction - 1.5).abs() < 0.05);
+    }
+}
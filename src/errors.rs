@@ -5,6 +5,20 @@ use std::{error::Error, sync::PoisonError};
 #[derive(Debug)]
 pub enum ModelError {
     LockError(String),
+    InvalidInput(String),
+    /// An input has `actual` features where the model takes `expected`.
+    DimensionMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    StaleUpdate {
+        staleness: u64,
+        max_staleness: u64,
+    },
+    RemoteError(String),
+    NotLeader(String),
+    StorageError(String),
+    Unsupported(String),
 }
 
 impl Error for ModelError {}
@@ -13,6 +27,26 @@ impl fmt::Display for ModelError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             ModelError::LockError(ref err) => write!(f, "LockError: {}", err),
+            ModelError::InvalidInput(ref err) => write!(f, "InvalidInput: {}", err),
+            ModelError::DimensionMismatch { expected, actual } => write!(
+                f,
+                "DimensionMismatch: expected {} input features, got {}",
+                expected, actual
+            ),
+            ModelError::StaleUpdate {
+                staleness,
+                max_staleness,
+            } => write!(
+                f,
+                "StaleUpdate: update is {} steps behind (max {})",
+                staleness, max_staleness
+            ),
+            ModelError::RemoteError(ref err) => write!(f, "RemoteError: {}", err),
+            ModelError::StorageError(ref err) => write!(f, "StorageError: {}", err),
+            ModelError::Unsupported(ref err) => write!(f, "Unsupported: {}", err),
+            ModelError::NotLeader(ref leader) => {
+                write!(f, "NotLeader: training is handled by {}", leader)
+            }
         }
     }
 }
@@ -21,3 +55,41 @@ impl<T> From<PoisonError<T>> for ModelError {
         ModelError::LockError(error.to_string())
     }
 }
+
+impl From<reqwest::Error> for ModelError {
+    fn from(error: reqwest::Error) -> Self {
+        ModelError::RemoteError(error.to_string())
+    }
+}
+
+impl From<std::io::Error> for ModelError {
+    fn from(error: std::io::Error) -> Self {
+        ModelError::StorageError(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for ModelError {
+    fn from(error: serde_json::Error) -> Self {
+        ModelError::StorageError(error.to_string())
+    }
+}
+
+impl From<bincode::Error> for ModelError {
+    fn from(error: bincode::Error) -> Self {
+        ModelError::StorageError(error.to_string())
+    }
+}
+
+#[cfg(feature = "grpc")]
+impl From<tonic::Status> for ModelError {
+    fn from(status: tonic::Status) -> Self {
+        ModelError::RemoteError(status.to_string())
+    }
+}
+
+#[cfg(feature = "grpc")]
+impl From<tonic::transport::Error> for ModelError {
+    fn from(error: tonic::transport::Error) -> Self {
+        ModelError::RemoteError(error.to_string())
+    }
+}
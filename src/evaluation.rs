@@ -0,0 +1,151 @@
+use crate::algorithm::{Algorithm, Datum};
+use crate::errors::ModelError;
+use crate::model::Model;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Predictions are clamped to `[EPSILON, 1 - EPSILON]` when computing log-loss.
+const EPSILON: f64 = 1e-15;
+
+/// Input together with its expected output.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LabeledSample<I, L = I> {
+    pub input: I,
+    pub label: L,
+}
+
+/// Body of `POST /evaluate`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvaluationRequest<I, L = I> {
+    pub samples: Vec<LabeledSample<I, L>>,
+}
+
+/// Metrics restricted to the samples of one class.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClassMetrics {
+    pub count: usize,
+    pub correct: usize,
+    pub accuracy: f64,
+    pub mae: f64,
+}
+
+/// Aggregate metrics of a model over a labeled batch.
+///
+/// Regression metrics (`mae`, `rmse`) use raw predictions. For accuracy and
+/// the per-class breakdown both predictions and labels are rounded to the
+/// nearest integer class id, so a probability output is thresholded at 0.5.
+/// `log_loss` is only reported when every label is 0 or 1.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvaluationReport {
+    pub samples: usize,
+    pub mae: f64,
+    pub rmse: f64,
+    pub accuracy: f64,
+    pub log_loss: Option<f64>,
+    pub per_class: BTreeMap<i64, ClassMetrics>,
+}
+
+/// Class id of a prediction or label.
+pub fn class_of(value: f64) -> i64 {
+    value.round() as i64
+}
+
+/// Runs inference on every sample and returns `(prediction, label)` pairs.
+///
+/// Predictions and labels are compared through their numeric view; outputs
+/// without one cannot be scored.
+pub fn score<T, A>(
+    algorithm: &A,
+    model: &Model<T>,
+    samples: &[LabeledSample<A::Input, A::Output>],
+) -> Result<Vec<(f64, f64)>, ModelError>
+where
+    T: Float + Debug + Send + Sync + Sum,
+    A: Algorithm<T> + ?Sized,
+{
+    samples
+        .iter()
+        .map(|sample| {
+            let prediction = algorithm.inference_step(model, sample.input.clone())?;
+            match (prediction.score(), sample.label.score()) {
+                (Some(prediction), Some(label)) => Ok((prediction, label)),
+                _ => Err(ModelError::InvalidInput(
+                    "predictions and labels must have a numeric value to be scored".to_string(),
+                )),
+            }
+        })
+        .collect()
+}
+
+/// Computes an [`EvaluationReport`] from `(prediction, label)` pairs.
+pub fn evaluate(pairs: &[(f64, f64)]) -> Result<EvaluationReport, ModelError> {
+    if pairs.is_empty() {
+        return Err(ModelError::InvalidInput(
+            "evaluation batch is empty".to_string(),
+        ));
+    }
+    let n = pairs.len() as f64;
+    let mut absolute = 0.0;
+    let mut squared = 0.0;
+    let mut correct = 0;
+    let mut per_class: BTreeMap<i64, ClassMetrics> = BTreeMap::new();
+    for &(prediction, label) in pairs {
+        let error = (prediction - label).abs();
+        absolute += error;
+        squared += error * error;
+        let hit = class_of(prediction) == class_of(label);
+        correct += hit as usize;
+        let class = per_class.entry(class_of(label)).or_default();
+        class.count += 1;
+        class.correct += hit as usize;
+        class.mae += error;
+    }
+    for class in per_class.values_mut() {
+        class.accuracy = class.correct as f64 / class.count as f64;
+        class.mae /= class.count as f64;
+    }
+
+    let binary = pairs.iter().all(|&(_, label)| label == 0.0 || label == 1.0);
+    let log_loss = binary.then(|| {
+        pairs
+            .iter()
+            .map(|&(prediction, label)| {
+                let p = prediction.clamp(EPSILON, 1.0 - EPSILON);
+                -(label * p.ln() + (1.0 - label) * (1.0 - p).ln())
+            })
+            .sum::<f64>()
+            / n
+    });
+
+    Ok(EvaluationReport {
+        samples: pairs.len(),
+        mae: absolute / n,
+        rmse: (squared / n).sqrt(),
+        accuracy: correct as f64 / n,
+        log_loss,
+        per_class,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate_binary_batch() {
+        let report = evaluate(&[(0.9, 1.0), (0.2, 0.0), (0.6, 0.0), (0.4, 1.0)]).unwrap();
+        assert_eq!(report.samples, 4);
+        assert!((report.mae - 0.375).abs() < 1e-12);
+        assert_eq!(report.accuracy, 0.5);
+        assert!(report.log_loss.unwrap() > 0.0);
+        assert_eq!(report.per_class[&0].count, 2);
+        assert_eq!(report.per_class[&0].correct, 1);
+        assert_eq!(report.per_class[&1].accuracy, 0.5);
+
+        assert!(evaluate(&[(1.5, 3.0)]).unwrap().log_loss.is_none());
+        assert!(evaluate(&[]).is_err());
+    }
+}
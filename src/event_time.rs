@@ -0,0 +1,305 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Request header carrying the event time of a training sample, in
+/// milliseconds since the Unix epoch.
+pub const EVENT_TIME_HEADER: &str = "X-Oml-Event-Time";
+
+/// Default of [`EventTimeConfig::max_future_ms`].
+pub const DEFAULT_MAX_FUTURE_MS: u64 = 60_000;
+
+/// What happens to a training sample whose event time is behind the
+/// watermark.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "policy", rename_all = "snake_case")]
+pub enum LatePolicy {
+    /// Trained on like any other sample.
+    Apply,
+    /// Dropped without training.
+    Discard,
+    /// Trained on with its step scaled by `0.5^(lateness / half_life_ms)`.
+    DownWeight { half_life_ms: u64 },
+}
+
+/// Configuration of an [`EventClock`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EventTimeConfig {
+    /// How far behind the latest event time a sample may be and still
+    /// count as on time.
+    pub allowed_lateness_ms: u64,
+    pub policy: LatePolicy,
+    /// How far ahead of the wall clock an event time may be. Later ones
+    /// are rejected rather than moving the watermark past every sample to
+    /// come.
+    #[serde(default = "default_max_future_ms")]
+    pub max_future_ms: u64,
+}
+
+fn default_max_future_ms() -> u64 {
+    DEFAULT_MAX_FUTURE_MS
+}
+
+/// How an [`EventClock`] admits a sample.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Admission {
+    /// At or after the watermark; `out_of_order` if behind the latest
+    /// event time seen.
+    OnTime { out_of_order: bool },
+    /// Behind the watermark by `lateness_ms`, to be trained on with
+    /// `weight` in `(0, 1]`.
+    Late { lateness_ms: u64, weight: f64 },
+    /// Behind the watermark by `lateness_ms` and dropped.
+    Discarded { lateness_ms: u64 },
+    /// Ahead of the wall clock by `ahead_ms`, more than
+    /// [`EventTimeConfig::max_future_ms`]; rejected without moving the
+    /// watermark.
+    Future { ahead_ms: u64 },
+}
+
+/// Tracks the event times of training samples, which may arrive late or
+/// out of order, and admits each one by its [`LatePolicy`]: the watermark
+/// trails the latest event time seen by the allowed lateness, and samples
+/// behind it are applied, discarded or down-weighted by how late they are.
+///
+/// Samples without an event time are always applied. The watermark is kept
+/// per instance; the weight of a down-weighted sample travels with it to
+/// replication followers and the shared-state writer. Event times too far
+/// in the future are rejected, and [`EventClock::reset`] forgets the
+/// watermark, e.g. after replaying old data.
+///
+/// # Examples
+///
+/// ```
+/// use oml::event_time::{Admission, EventClock, EventTimeConfig, LatePolicy};
+///
+/// let clock = EventClock::new(EventTimeConfig {
+///     allowed_lateness_ms: 1_000,
+///     policy: LatePolicy::DownWeight { half_life_ms: 500 },
+///     max_future_ms: 60_000,
+/// });
+/// clock.admit(10_000);
+/// assert_eq!(clock.admit(9_500), Admission::OnTime { out_of_order: true });
+/// assert_eq!(clock.watermark_ms(), Some(9_000));
+/// assert_eq!(
+///     clock.admit(8_500),
+///     Admission::Late { lateness_ms: 500, weight: 0.5 }
+/// );
+/// ```
+#[derive(Debug)]
+pub struct EventClock {
+    config: EventTimeConfig,
+    /// Latest event time seen, 0 before the first sample.
+    latest_ms: AtomicU64,
+}
+
+impl EventClock {
+    pub fn new(config: EventTimeConfig) -> Self {
+        EventClock {
+            config,
+            latest_ms: AtomicU64::new(0),
+        }
+    }
+
+    pub fn config(&self) -> &EventTimeConfig {
+        &self.config
+    }
+
+    /// Event time behind which samples are late, once one has been seen.
+    pub fn watermark_ms(&self) -> Option<u64> {
+        match self.latest_ms.load(Ordering::SeqCst) {
+            0 => None,
+            latest => Some(latest.saturating_sub(self.config.allowed_lateness_ms)),
+        }
+    }
+
+    /// Admits a sample with event time `event_time_ms`, advancing the
+    /// watermark if it is the latest seen.
+    pub fn admit(&self, event_time_ms: u64) -> Admission {
+        let ahead_ms = event_time_ms.saturating_sub(now_ms());
+        if ahead_ms > self.config.max_future_ms {
+            return Admission::Future { ahead_ms };
+        }
+        let latest = self.latest_ms.fetch_max(event_time_ms, Ordering::SeqCst);
+        let watermark = latest.saturating_sub(self.config.allowed_lateness_ms);
+        if latest == 0 || event_time_ms >= watermark {
+            return Admission::OnTime {
+                out_of_order: event_time_ms < latest,
+            };
+        }
+        let lateness_ms = watermark - event_time_ms;
+        match self.config.policy {
+            LatePolicy::Apply => Admission::Late {
+                lateness_ms,
+                weight: 1.0,
+            },
+            LatePolicy::Discard => Admission::Discarded { lateness_ms },
+            LatePolicy::DownWeight { half_life_ms } => Admission::Late {
+                lateness_ms,
+                weight: 0.5f64.powf(lateness_ms as f64 / half_life_ms.max(1) as f64),
+            },
+        }
+    }
+
+    /// Forgets the latest event time, so the next sample admitted sets the
+    /// watermark afresh.
+    pub fn reset(&self) {
+        self.latest_ms.store(0, Ordering::SeqCst);
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::AppState;
+    use crate::model::Model;
+    use crate::server::configure;
+    use crate::shared_state::{FORWARDED_HEADER, SAMPLE_WEIGHT_HEADER};
+    use crate::test::ScalingAlgorithm;
+    use actix_web::{http, test as actix_test, web, App};
+
+    #[test]
+    fn test_discard_policy_drops_samples_behind_watermark() {
+        let clock = EventClock::new(EventTimeConfig {
+            allowed_lateness_ms: 100,
+            policy: LatePolicy::Discard,
+            max_future_ms: DEFAULT_MAX_FUTURE_MS,
+        });
+        assert_eq!(clock.watermark_ms(), None);
+        assert_eq!(
+            clock.admit(1_000),
+            Admission::OnTime {
+                out_of_order: false
+            }
+        );
+        assert_eq!(clock.admit(900), Admission::OnTime { out_of_order: true });
+        assert_eq!(clock.admit(899), Admission::Discarded { lateness_ms: 1 });
+        assert_eq!(
+            clock.admit(2_000),
+            Admission::OnTime {
+                out_of_order: false
+            }
+        );
+        assert_eq!(clock.watermark_ms(), Some(1_900));
+
+        clock.reset();
+        assert_eq!(clock.watermark_ms(), None);
+        assert_eq!(
+            clock.admit(899),
+            Admission::OnTime {
+                out_of_order: false
+            }
+        );
+    }
+
+    #[test]
+    fn test_future_event_time_is_rejected() {
+        let clock = EventClock::new(EventTimeConfig {
+            allowed_lateness_ms: 100,
+            policy: LatePolicy::Discard,
+            max_future_ms: 60_000,
+        });
+        let now = now_ms();
+        assert!(matches!(
+            clock.admit(now + 3_600_000),
+            Admission::Future { ahead_ms } if ahead_ms > 60_000
+        ));
+        assert_eq!(clock.watermark_ms(), None);
+        assert!(matches!(clock.admit(now), Admission::OnTime { .. }));
+    }
+
+    #[actix_rt::test]
+    async fn test_late_sample_step_is_down_weighted() {
+        let state = AppState::new(Model::with_parameters(vec![1.0f64]), ScalingAlgorithm)
+            .with_event_time(EventTimeConfig {
+                allowed_lateness_ms: 0,
+                policy: LatePolicy::DownWeight {
+                    half_life_ms: 1_000,
+                },
+                max_future_ms: DEFAULT_MAX_FUTURE_MS,
+            });
+        let state = web::Data::new(state);
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .configure(configure::<f64, ScalingAlgorithm>),
+        )
+        .await;
+        let train = |time: &str| {
+            actix_test::TestRequest::post()
+                .uri("/training")
+                .insert_header((EVENT_TIME_HEADER, time.to_string()))
+                .set_json(3.0f64)
+                .to_request()
+        };
+
+        let resp = actix_test::call_service(&app, train("5000")).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!(state.model.read_params()[0], 3.0);
+        // One half-life late: the step from 3 to 9 only goes half way.
+        let resp = actix_test::call_service(&app, train("4000")).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!(state.model.read_params()[0], 6.0);
+        assert_eq!(state.metrics.counter("late_samples"), 1);
+
+        let resp = actix_test::call_service(&app, train("soon")).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+        let resp = actix_test::call_service(&app, train(&(now_ms() + 3_600_000).to_string())).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+        assert_eq!(state.model.read_params()[0], 6.0);
+
+        let resp = actix_test::call_service(
+            &app,
+            actix_test::TestRequest::post()
+                .uri("/training/watermark/reset")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!(state.event_clock.as_ref().unwrap().watermark_ms(), None);
+        // A sample older than the forgotten watermark is on time again.
+        let resp = actix_test::call_service(&app, train("1000")).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!(state.model.read_params()[0], 18.0);
+    }
+
+    #[actix_rt::test]
+    async fn test_forwarded_sample_keeps_its_weight() {
+        let state = web::Data::new(AppState::new(
+            Model::with_parameters(vec![1.0f64]),
+            ScalingAlgorithm,
+        ));
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .configure(configure::<f64, ScalingAlgorithm>),
+        )
+        .await;
+        let train = |forwarded: bool| {
+            let request = actix_test::TestRequest::post()
+                .uri("/training")
+                .insert_header((SAMPLE_WEIGHT_HEADER, "0.5"));
+            match forwarded {
+                true => request.insert_header((FORWARDED_HEADER, "replica-1")),
+                false => request,
+            }
+            .set_json(3.0f64)
+            .to_request()
+        };
+
+        // Clients cannot set the weight themselves.
+        let resp = actix_test::call_service(&app, train(false)).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!(state.model.read_params()[0], 3.0);
+        let resp = actix_test::call_service(&app, train(true)).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!(state.model.read_params()[0], 6.0);
+    }
+}
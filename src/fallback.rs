@@ -0,0 +1,168 @@
+use crate::algorithm::Algorithm;
+use crate::errors::ModelError;
+use crate::model::Model;
+use num_traits::Float;
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Response header set when the fallback model served a prediction.
+pub const FALLBACK_HEADER: &str = "X-Oml-Fallback";
+
+/// Fallback models by catalog model name, `None` for the serving model.
+pub type FallbackRoutes<T, A> = Vec<(Option<String>, Arc<FallbackModel<T, A>>)>;
+
+/// A simpler or frozen model standing in for a route's primary model when
+/// its inference step fails or, with a timeout, is too slow, so the route
+/// keeps answering during incidents.
+///
+/// Fallback predictions are bare: they skip calibration, explanations and
+/// delayed-label bookkeeping. A timed-out primary step still runs to
+/// completion in the background.
+///
+/// # Examples
+///
+/// ```
+/// use oml::fallback::FallbackModel;
+/// use oml::model::Model;
+/// use oml::test::ScalingAlgorithm;
+/// use std::time::Duration;
+///
+/// let model = Model::with_parameters(vec![2.0f32]);
+/// let fallback = FallbackModel::frozen(&model, ScalingAlgorithm)
+///     .with_timeout(Duration::from_millis(50));
+/// model.update_params(|params| params[0] = 3.0);
+/// assert_eq!(fallback.infer(1.0).unwrap(), 2.0);
+/// ```
+pub struct FallbackModel<T, A>
+where
+    T: Float + Debug + Send + Sync,
+{
+    model: Arc<Model<T>>,
+    algorithm: Arc<A>,
+    timeout: Option<Duration>,
+}
+
+impl<T, A> FallbackModel<T, A>
+where
+    T: Float + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    pub fn new(model: Model<T>, algorithm: A) -> Self {
+        FallbackModel {
+            model: Arc::new(model),
+            algorithm: Arc::new(algorithm),
+            timeout: None,
+        }
+    }
+
+    /// A fallback serving a copy of the current parameters of `model`.
+    pub fn frozen(model: &Model<T>, algorithm: A) -> Self {
+        let frozen = Model::with_layout(model.layout().clone(), model.snapshot().to_vec())
+            .unwrap_or_else(|_| Model::with_parameters(model.snapshot().to_vec()));
+        Self::new(frozen, algorithm)
+    }
+
+    /// Also serves the fallback when the primary step takes longer than
+    /// `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub fn model(&self) -> &Arc<Model<T>> {
+        &self.model
+    }
+
+    /// Runs the fallback's inference step on `x`.
+    pub fn infer(&self, x: A::Input) -> Result<A::Output, ModelError> {
+        self.algorithm.inference_step(&self.model, x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::{AppState, PredictionEnvelope, ResponseWarning};
+    use crate::server::configure;
+    use crate::service::{InferenceRequest, InferenceResponse, OmlService};
+    use actix_web::{http, test, web, App};
+
+    /// Scales the input by the first parameter, failing or stalling on demand.
+    struct Flaky {
+        fail: bool,
+        delay: Duration,
+    }
+
+    impl Algorithm<f32> for Flaky {
+        type Input = f32;
+        type Output = f32;
+
+        fn training_step(&self, _model: &Model<f32>, _x: f32) -> Result<(), ModelError> {
+            Ok(())
+        }
+
+        fn inference_step(&self, model: &Model<f32>, x: f32) -> Result<f32, ModelError> {
+            std::thread::sleep(self.delay);
+            if self.fail {
+                return Err(ModelError::StorageError("feature store down".to_string()));
+            }
+            Ok(model.read_params()[0] * x)
+        }
+    }
+
+    fn flaky(fail: bool, delay_ms: u64) -> Flaky {
+        Flaky {
+            fail,
+            delay: Duration::from_millis(delay_ms),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_failing_primary_served_by_fallback() {
+        let fallback = FallbackModel::new(Model::with_parameters(vec![1.0]), flaky(false, 0));
+        let state = AppState::new(Model::with_parameters(vec![2.0]), flaky(true, 0))
+            .with_fallback(fallback);
+        let state = web::Data::new(state);
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .configure(configure::<f32, Flaky>),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/inference")
+            .set_json(3.0f32)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!(resp.headers().get(FALLBACK_HEADER).unwrap(), "true");
+        let response: PredictionEnvelope<f32> = test::read_body_json(resp).await;
+        assert_eq!(response.prediction, 3.0);
+        assert_eq!(response.warnings, vec![ResponseWarning::Fallback]);
+        assert_eq!(state.metrics.counter("fallback_model_inferences"), 1);
+    }
+
+    #[actix_rt::test]
+    async fn test_slow_primary_times_out_to_fallback() {
+        let fallback = FallbackModel::new(Model::with_parameters(vec![1.0]), flaky(false, 0))
+            .with_timeout(Duration::from_millis(20));
+        let state = AppState::new(Model::with_parameters(vec![2.0]), flaky(false, 300))
+            .with_fallback(fallback);
+        let service = OmlService::new(state);
+
+        let (response, fell_back) = service
+            .infer_or_fallback(InferenceRequest::new(3.0))
+            .await
+            .unwrap();
+        assert!(fell_back);
+        assert!(matches!(response, InferenceResponse::Prediction(p) if p == 3.0));
+        assert_eq!(service.metrics().counters["fallback_timeouts"], 1);
+    }
+}
@@ -0,0 +1,422 @@
+use crate::algorithm::{Algorithm, Datum};
+use crate::errors::ModelError;
+use crate::model::Model;
+use crate::tensors::Tensor;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::sync::Mutex;
+
+/// Longest forecast a single inference step may ask for.
+pub const MAX_HORIZON: usize = 10_000;
+
+/// Input of the forecasters: training steps ingest the latest observation
+/// of the series, inference steps ask for the next `horizon` values.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeriesInput<T> {
+    Observation(T),
+    Horizon(usize),
+}
+
+/// Observations have a numeric view, so stages such as outlier filtering
+/// apply to them; horizons do not.
+impl<T: Datum> Datum for SeriesInput<T> {
+    fn score(&self) -> Option<f64> {
+        match self {
+            SeriesInput::Observation(value) => value.score(),
+            SeriesInput::Horizon(_) => None,
+        }
+    }
+}
+
+impl<T> SeriesInput<T> {
+    fn observation(self) -> Result<T, ModelError> {
+        match self {
+            SeriesInput::Observation(value) => Ok(value),
+            SeriesInput::Horizon(_) => Err(ModelError::InvalidInput(
+                "training steps take an observation".to_string(),
+            )),
+        }
+    }
+
+    fn horizon(self) -> Result<usize, ModelError> {
+        match self {
+            SeriesInput::Horizon(horizon) if horizon <= MAX_HORIZON => Ok(horizon),
+            SeriesInput::Horizon(horizon) => Err(ModelError::InvalidInput(format!(
+                "horizon {} exceeds the maximum of {}",
+                horizon, MAX_HORIZON
+            ))),
+            SeriesInput::Observation(_) => Err(ModelError::InvalidInput(
+                "inference steps take a horizon".to_string(),
+            )),
+        }
+    }
+}
+
+/// Holt-Winters exponential smoothing with additive trend and seasonality.
+///
+/// The whole state lives in the model parameters, laid out as
+/// `[level, trend, season_0, .., season_{period-1}, observations]`, so it is
+/// checkpointed and replicated like any other model. The first observation
+/// initializes the level; trend and seasonal terms start at zero.
+#[derive(Debug, Clone)]
+pub struct ExponentialSmoothing<T> {
+    /// Smoothing factor of the level, in `(0, 1]`.
+    alpha: T,
+    /// Smoothing factor of the trend; no trend without one.
+    beta: Option<T>,
+    /// Season length and smoothing factor of the seasonal terms.
+    seasonality: Option<(usize, T)>,
+}
+
+impl<T> ExponentialSmoothing<T>
+where
+    T: Float + Debug + Send + Sync + Sum + Datum,
+{
+    /// Simple exponential smoothing of the level with factor `alpha`.
+    pub fn new(alpha: T) -> Self {
+        ExponentialSmoothing {
+            alpha,
+            beta: None,
+            seasonality: None,
+        }
+    }
+
+    /// Also tracks a linear trend, smoothed with factor `beta`.
+    pub fn with_trend(mut self, beta: T) -> Self {
+        self.beta = Some(beta);
+        self
+    }
+
+    /// Also tracks `period` additive seasonal terms, smoothed with `gamma`.
+    pub fn with_seasonality(mut self, period: usize, gamma: T) -> Self {
+        self.seasonality = Some((period, gamma));
+        self
+    }
+
+    fn period(&self) -> usize {
+        self.seasonality.map_or(0, |(period, _)| period)
+    }
+
+    /// A model holding the empty state expected by this configuration.
+    pub fn initial_model(&self) -> Model<T> {
+        Model::with_parameters(vec![T::zero(); self.period() + 3])
+    }
+
+    fn check(&self, state: &[T]) -> Result<(), ModelError> {
+        if state.len() != self.period() + 3 {
+            return Err(ModelError::InvalidInput(format!(
+                "the model must hold {} state values",
+                self.period() + 3
+            )));
+        }
+        Ok(())
+    }
+
+    /// Index of the seasonal term of the `t`-th observation.
+    fn season(&self, t: usize) -> usize {
+        2 + t % self.period().max(1)
+    }
+}
+
+impl<T> Algorithm<T> for ExponentialSmoothing<T>
+where
+    T: Float + Debug + Send + Sync + Sum + Datum,
+{
+    type Input = SeriesInput<T>;
+    type Output = Vec<T>;
+
+    fn training_step(&self, model: &Model<T>, x: SeriesInput<T>) -> Result<(), ModelError> {
+        let y = x.observation()?;
+        let mut state = model.write_trainable();
+        self.check(&state)?;
+        let count = state.len() - 1;
+        let t = state[count].to_usize().unwrap_or(0);
+        let season = self.season(t);
+        let seasonal = |state: &[T]| match self.seasonality {
+            Some(_) => state[season],
+            None => T::zero(),
+        };
+
+        if t == 0 {
+            state[0] = y;
+        } else {
+            let (level, trend) = (state[0], state[1]);
+            state[0] =
+                self.alpha * (y - seasonal(&state)) + (T::one() - self.alpha) * (level + trend);
+            if let Some(beta) = self.beta {
+                state[1] = beta * (state[0] - level) + (T::one() - beta) * trend;
+            }
+        }
+        if let Some((_, gamma)) = self.seasonality {
+            state[season] = gamma * (y - state[0]) + (T::one() - gamma) * state[season];
+        }
+        state[count] = state[count] + T::one();
+        Ok(())
+    }
+
+    fn inference_step(&self, model: &Model<T>, x: SeriesInput<T>) -> Result<Vec<T>, ModelError> {
+        let horizon = x.horizon()?;
+        let state = model.read_params();
+        self.check(&state)?;
+        let t = state[state.len() - 1].to_usize().unwrap_or(0);
+        Ok((1..=horizon)
+            .map(|h| {
+                let seasonal = match self.seasonality {
+                    Some(_) => state[self.season(t + h - 1)],
+                    None => T::zero(),
+                };
+                state[0] + T::from(h).unwrap_or_else(T::zero) * state[1] + seasonal
+            })
+            .collect())
+    }
+}
+
+/// Initial diagonal of the recursive-least-squares covariance: large, so
+/// the first observations dominate the zero starting coefficients.
+const RLS_INITIAL_VARIANCE: f64 = 1000.0;
+
+/// Autoregressive forecaster AR(p), optionally on the first differences of
+/// the series (ARI(p, 1)), fitted online by recursive least squares.
+///
+/// The model parameters hold, in order, the `p` coefficients, the
+/// intercept, the last `p` (differenced) values newest first, the last raw
+/// observation and the number of observations. The RLS covariance is kept
+/// by the algorithm. Multi-step forecasts feed predictions back as lags.
+#[derive(Debug)]
+pub struct Autoregressive<T> {
+    order: usize,
+    /// RLS forgetting factor in `(0, 1]`; below 1 old samples fade out.
+    forgetting: T,
+    differencing: bool,
+    covariance: Mutex<Tensor<T>>,
+}
+
+impl<T> Autoregressive<T>
+where
+    T: Float + Debug + Send + Sync + Sum + Datum,
+{
+    /// An AR model of order `order` on the raw series, without forgetting.
+    pub fn new(order: usize) -> Self {
+        let variance = T::from(RLS_INITIAL_VARIANCE).unwrap_or_else(T::one);
+        Autoregressive {
+            order,
+            forgetting: T::one(),
+            differencing: false,
+            covariance: Mutex::new(Tensor::diagonal(order + 1, variance)),
+        }
+    }
+
+    /// Discounts past samples by `forgetting` per step, to track drift.
+    pub fn with_forgetting(mut self, forgetting: T) -> Self {
+        self.forgetting = forgetting;
+        self
+    }
+
+    /// Fits the model on first differences, for series with a trend.
+    pub fn with_differencing(mut self) -> Self {
+        self.differencing = true;
+        self
+    }
+
+    /// A model holding zero coefficients and no observations.
+    pub fn initial_model(&self) -> Model<T> {
+        Model::with_parameters(vec![T::zero(); self.len()])
+    }
+
+    fn len(&self) -> usize {
+        2 * self.order + 3
+    }
+
+    fn check(&self, state: &[T]) -> Result<(), ModelError> {
+        if state.len() != self.len() {
+            return Err(ModelError::InvalidInput(format!(
+                "the model must hold {} state values",
+                self.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Values fed to the autoregression so far.
+    fn fitted(&self, state: &[T]) -> usize {
+        let observations = state[self.len() - 1].to_usize().unwrap_or(0);
+        observations.saturating_sub(self.differencing as usize)
+    }
+
+    /// Regressors: the lags followed by the constant intercept input.
+    fn regressors(&self, state: &[T]) -> Vec<T> {
+        let lags = &state[self.order + 1..2 * self.order + 1];
+        lags.iter().copied().chain([T::one()]).collect()
+    }
+
+    fn predict(&self, state: &[T]) -> T {
+        let x = self.regressors(state);
+        state[..=self.order]
+            .iter()
+            .zip(&x)
+            .fold(T::zero(), |sum, (c, x)| sum + *c * *x)
+    }
+
+    /// Pushes `value` as the newest lag.
+    fn push_lag(&self, state: &mut [T], value: T) {
+        let lags = &mut state[self.order + 1..2 * self.order + 1];
+        if !lags.is_empty() {
+            lags.rotate_right(1);
+            lags[0] = value;
+        }
+    }
+}
+
+impl<T> Algorithm<T> for Autoregressive<T>
+where
+    T: Float + Debug + Send + Sync + Sum + Datum,
+{
+    type Input = SeriesInput<T>;
+    type Output = Vec<T>;
+
+    fn training_step(&self, model: &Model<T>, x: SeriesInput<T>) -> Result<(), ModelError> {
+        let y = x.observation()?;
+        let mut state = model.write_trainable();
+        self.check(&state)?;
+        let (last, count) = (self.len() - 2, self.len() - 1);
+        let observations = state[count].to_usize().unwrap_or(0);
+        state[count] = state[count] + T::one();
+        let z = match self.differencing {
+            true if observations == 0 => {
+                state[last] = y;
+                return Ok(());
+            }
+            true => y - std::mem::replace(&mut state[last], y),
+            false => {
+                state[last] = y;
+                y
+            }
+        };
+
+        if observations - self.differencing as usize >= self.order {
+            let x = self.regressors(&state);
+            let error = z - self.predict(&state);
+            let mut covariance = self.covariance.lock()?;
+            let px = covariance.matvec(&x);
+            let s = self.forgetting + x.iter().zip(&px).fold(T::zero(), |a, (x, p)| a + *x * *p);
+            for (c, p) in state[..=self.order].iter_mut().zip(&px) {
+                *c = *c + *p / s * error;
+            }
+            covariance.add_outer(-T::one() / s, &px, &px);
+            if self.forgetting < T::one() {
+                covariance.scale(T::one() / self.forgetting);
+            }
+        }
+        self.push_lag(&mut state, z);
+        Ok(())
+    }
+
+    fn inference_step(&self, model: &Model<T>, x: SeriesInput<T>) -> Result<Vec<T>, ModelError> {
+        let horizon = x.horizon()?;
+        let params = model.read_params();
+        self.check(&params)?;
+        if self.fitted(&params) < self.order {
+            return Err(ModelError::InvalidInput(format!(
+                "forecasting needs at least {} observations",
+                self.order + self.differencing as usize
+            )));
+        }
+        let mut state = params.to_vec();
+        let mut level = state[self.len() - 2];
+        Ok((0..horizon)
+            .map(|_| {
+                let z = self.predict(&state);
+                self.push_lag(&mut state, z);
+                match self.differencing {
+                    true => {
+                        level = level + z;
+                        level
+                    }
+                    false => z,
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forecast_follows_trend_and_season() {
+        let algorithm = ExponentialSmoothing::new(0.5)
+            .with_trend(0.3)
+            .with_seasonality(4, 0.5);
+        let model = algorithm.initial_model();
+        let series = |t: usize| 10.0 + 0.5 * t as f64 + [2.0, -1.0, 0.0, -1.0][t % 4];
+        for t in 0..200 {
+            let observation = SeriesInput::Observation(series(t));
+            algorithm.training_step(&model, observation).unwrap();
+        }
+
+        let forecast = algorithm
+            .inference_step(&model, SeriesInput::Horizon(8))
+            .unwrap();
+        assert_eq!(forecast.len(), 8);
+        for (h, value) in forecast.iter().enumerate() {
+            assert!((value - series(200 + h)).abs() < 0.1, "step {}", h + 1);
+        }
+
+        assert!(algorithm
+            .inference_step(&model, SeriesInput::Observation(1.0))
+            .is_err());
+        assert!(algorithm
+            .training_step(&model, SeriesInput::Horizon(1))
+            .is_err());
+    }
+
+    #[test]
+    fn test_autoregression_fits_and_forecasts() {
+        let algorithm = Autoregressive::new(2);
+        let model = algorithm.initial_model();
+        assert!(algorithm
+            .inference_step(&model, SeriesInput::Horizon(1))
+            .is_err());
+
+        let noise = crate::test::uniform_stream::<f64>(-0.01, 0.01, 5);
+        let (mut y1, mut y2) = (0.0, 0.0);
+        for e in noise.take(500) {
+            let y = 1.0 + 0.6 * y1 - 0.3 * y2 + e;
+            algorithm
+                .training_step(&model, SeriesInput::Observation(y))
+                .unwrap();
+            (y1, y2) = (y, y1);
+        }
+        let params = model.read_params();
+        assert!((params[0] - 0.6).abs() < 0.05 && (params[1] + 0.3).abs() < 0.05);
+
+        let forecast = algorithm
+            .inference_step(&model, SeriesInput::Horizon(2))
+            .unwrap();
+        let next = 1.0 + 0.6 * y1 - 0.3 * y2;
+        assert!((forecast[0] - next).abs() < 0.05);
+        assert!((forecast[1] - (1.0 + 0.6 * next - 0.3 * y1)).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_differencing_extrapolates_trend() {
+        let algorithm = Autoregressive::new(1).with_differencing();
+        let model = algorithm.initial_model();
+        for t in 0..50 {
+            let y = 3.0 + 2.0 * t as f64;
+            algorithm
+                .training_step(&model, SeriesInput::Observation(y))
+                .unwrap();
+        }
+        let forecast = algorithm
+            .inference_step(&model, SeriesInput::Horizon(3))
+            .unwrap();
+        for (h, value) in forecast.iter().enumerate() {
+            assert!((value - (3.0 + 2.0 * (50 + h) as f64)).abs() < 0.01);
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use crate::auth::PeerClient;
+use crate::cluster::Membership;
+use crate::errors::ModelError;
+use crate::metrics::Metrics;
+use crate::model::Model;
+use crate::seed::{self, SeededRng};
+use num_traits::Float;
+use rand::seq::SliceRandom;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+/// Configuration of the peer-sync subsystem.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GossipConfig {
+    /// Identifier of this instance, sent along with every exchange.
+    pub node_id: String,
+    /// Base URLs of the peers to gossip with (e.g. `http://10.0.0.2:8080`).
+    pub peers: Vec<String>,
+    /// Time between anti-entropy rounds.
+    pub interval: Duration,
+    /// Number of peers contacted per round.
+    pub fanout: usize,
+    /// Weight given to the peer's parameters when merging (0.5 = plain averaging).
+    pub mixing: f64,
+}
+
+impl Default for GossipConfig {
+    fn default() -> Self {
+        GossipConfig {
+            node_id: "oml".to_string(),
+            peers: Vec::new(),
+            interval: Duration::from_secs(10),
+            fanout: 1,
+            mixing: 0.5,
+        }
+    }
+}
+
+/// Payload exchanged between peers during a gossip round.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GossipMessage<T> {
+    pub node_id: String,
+    pub round: u64,
+    pub values: Vec<T>,
+}
+
+/// Summary of one gossip round.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GossipRoundReport {
+    pub round: u64,
+    pub contacted: usize,
+    pub merged: usize,
+    pub failed: usize,
+}
+
+/// Decentralized model synchronization via periodic push-pull gossip.
+///
+/// Each round the node sends its parameters to `fanout` randomly chosen
+/// peers; the peer answers with its own pre-merge parameters and both sides
+/// move toward each other by `mixing`. With plain averaging every exchange
+/// preserves the sum of the two replicas, so repeated rounds converge all
+/// replicas to the same model without a coordinator.
+#[derive(Debug)]
+pub struct PeerSync {
+    config: GossipConfig,
+    round: AtomicU64,
+    membership: OnceLock<Arc<Membership>>,
+    /// Peer selection; repeatable in deterministic mode.
+    rng: Mutex<SeededRng>,
+    http: PeerClient,
+}
+
+impl PeerSync {
+    pub fn new(config: GossipConfig) -> Self {
+        PeerSync {
+            config,
+            round: AtomicU64::new(0),
+            membership: OnceLock::new(),
+            rng: Mutex::new(seed::rng("gossip")),
+            http: PeerClient::default(),
+        }
+    }
+
+    /// Client of the requests to peers, see [`AuthConfig`](crate::auth::AuthConfig).
+    pub(crate) fn peer_client(&self) -> &PeerClient {
+        &self.http
+    }
+
+    pub fn config(&self) -> &GossipConfig {
+        &self.config
+    }
+
+    /// Adds the live members reported by `membership` to the configured peers.
+    pub fn set_membership(&self, membership: Arc<Membership>) {
+        let _ = self.membership.set(membership);
+    }
+
+    /// Configured peers plus, if set, the live cluster members.
+    pub fn peers(&self) -> Vec<String> {
+        let mut peers = self.config.peers.clone();
+        if let Some(membership) = self.membership.get() {
+            for peer in membership.live_peers() {
+                if !peers.contains(&peer) {
+                    peers.push(peer);
+                }
+            }
+        }
+        peers
+    }
+
+    /// Number of rounds started by this node.
+    pub fn round(&self) -> u64 {
+        self.round.load(Ordering::SeqCst)
+    }
+
+    /// Builds the message describing this node's current parameters.
+    pub fn message<T>(&self, model: &Model<T>) -> GossipMessage<T>
+    where
+        T: Float + Debug + Send + Sync,
+    {
+        GossipMessage {
+            node_id: self.config.node_id.clone(),
+            round: self.round(),
+            values: model.read_params().clone(),
+        }
+    }
+
+    /// Moves the local parameters toward `remote` by the configured mixing weight.
+    pub fn merge<T>(&self, model: &Model<T>, remote: &GossipMessage<T>) -> Result<(), ModelError>
+    where
+        T: Float + Debug + Send + Sync,
+    {
+        let mut params = model.write_params();
+        if params.len() != remote.values.len() {
+            let count = params.len();
+            params.discard();
+            return Err(ModelError::InvalidInput(format!(
+                "peer {} has {} parameters, local model has {}",
+                remote.node_id,
+                remote.values.len(),
+                count
+            )));
+        }
+        let mixing = T::from(self.config.mixing).unwrap_or_else(T::zero);
+        params
+            .iter_mut()
+            .zip(remote.values.iter())
+            .for_each(|(local, remote)| *local = *local + mixing * (*remote - *local));
+        Ok(())
+    }
+
+    /// Runs one anti-entropy round against `fanout` random peers.
+    pub async fn run_round<T>(&self, model: &Model<T>) -> GossipRoundReport
+    where
+        T: Float + Serialize + DeserializeOwned + Debug + Send + Sync,
+    {
+        let round = self.round.fetch_add(1, Ordering::SeqCst) + 1;
+        let candidates = self.peers();
+        let peers: Vec<&String> = match self.rng.lock() {
+            Ok(mut rng) => candidates
+                .choose_multiple(&mut *rng, self.config.fanout)
+                .collect(),
+            Err(_) => candidates.iter().take(self.config.fanout).collect(),
+        };
+        let mut report = GossipRoundReport {
+            round,
+            contacted: peers.len(),
+            ..Default::default()
+        };
+        for peer in peers {
+            let outgoing = self.message(model);
+            match self.exchange(peer, &outgoing).await {
+                Ok(reply) if self.merge(model, &reply).is_ok() => report.merged += 1,
+                _ => report.failed += 1,
+            }
+        }
+        report
+    }
+
+    async fn exchange<T>(
+        &self,
+        peer: &str,
+        message: &GossipMessage<T>,
+    ) -> Result<GossipMessage<T>, ModelError>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let response = self
+            .http
+            .post(format!("{}/gossip/exchange", peer.trim_end_matches('/')))
+            .json(message)
+            .send()
+            .await?;
+        Ok(response.error_for_status()?.json().await?)
+    }
+
+    /// Spawns the periodic gossip loop on the current actix/tokio runtime.
+    pub fn spawn<T>(
+        self: Arc<Self>,
+        model: Arc<Model<T>>,
+        metrics: Arc<Metrics>,
+    ) -> tokio::task::JoinHandle<()>
+    where
+        T: Float + Serialize + DeserializeOwned + Debug + Send + Sync + 'static,
+    {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.config.interval);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let report = self.run_round(&model).await;
+                metrics.increment("gossip_rounds");
+                metrics.add("gossip_merges", report.merged as u64);
+                metrics.add("gossip_failures", report.failed as u64);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_averages_parameters() {
+        let sync = PeerSync::new(GossipConfig::default());
+        let model = Model::with_parameters(vec![0.0, 4.0]);
+        let remote = GossipMessage {
+            node_id: "b".to_string(),
+            round: 1,
+            values: vec![2.0, 0.0],
+        };
+        sync.merge(&model, &remote).unwrap();
+        assert_eq!(model.read_params().clone(), vec![1.0, 2.0]);
+
+        let mismatched = GossipMessage {
+            values: vec![1.0],
+            ..remote
+        };
+        let version = model.version();
+        assert!(sync.merge(&model, &mismatched).is_err());
+        assert_eq!(model.version(), version);
+    }
+}
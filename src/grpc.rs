@@ -0,0 +1,507 @@
+use crate::errors::ModelError;
+use crate::metrics::Metrics;
+use crate::model::Model;
+use crate::replication::Follower;
+use num_traits::Float;
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+use tonic::codec::CompressionEncoding;
+use tonic::transport::Channel;
+use tonic::{Request, Response, Status};
+
+mod generated {
+    include!(concat!(env!("OUT_DIR"), "/oml.sync.v1.ParameterSync.rs"));
+}
+
+pub use generated::parameter_sync_client::ParameterSyncClient;
+pub use generated::parameter_sync_server::{ParameterSync, ParameterSyncServer};
+
+/// Version of the wire protocol. Peers refuse requests carrying another version.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Request for the full parameter state.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SnapshotRequest {
+    #[prost(uint32, tag = "1")]
+    pub protocol_version: u32,
+}
+
+/// Full parameter state of a node at `version`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Snapshot {
+    #[prost(uint32, tag = "1")]
+    pub protocol_version: u32,
+    #[prost(uint64, tag = "2")]
+    pub version: u64,
+    #[prost(double, repeated, tag = "3")]
+    pub values: Vec<f64>,
+    #[prost(string, tag = "4")]
+    pub node_id: String,
+}
+
+/// Request to tail every delta applied after `since_version`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StreamDeltasRequest {
+    #[prost(uint32, tag = "1")]
+    pub protocol_version: u32,
+    #[prost(uint64, tag = "2")]
+    pub since_version: u64,
+}
+
+/// Sparse additive parameter update: `params[indices[i]] += values[i]`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Delta {
+    #[prost(uint32, tag = "1")]
+    pub protocol_version: u32,
+    /// Version produced by applying this delta (ignored on push; assigned by the receiver).
+    #[prost(uint64, tag = "2")]
+    pub version: u64,
+    #[prost(uint32, repeated, tag = "3")]
+    pub indices: Vec<u32>,
+    #[prost(double, repeated, tag = "4")]
+    pub values: Vec<f64>,
+    #[prost(string, tag = "5")]
+    pub node_id: String,
+}
+
+/// Acknowledgement of a pushed delta.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PushUpdateReply {
+    #[prost(uint64, tag = "1")]
+    pub version: u64,
+}
+
+#[allow(clippy::result_large_err)] // `Status` is tonic's error type
+fn check_protocol(version: u32) -> Result<(), Status> {
+    if version == PROTOCOL_VERSION {
+        Ok(())
+    } else {
+        Err(Status::failed_precondition(format!(
+            "protocol version {} is not supported (expected {})",
+            version, PROTOCOL_VERSION
+        )))
+    }
+}
+
+/// gRPC parameter-sync endpoint exposing a model to other nodes.
+///
+/// Every applied delta gets the next version number, is retained in a
+/// bounded history for catch-up, and is broadcast to `StreamDeltas`
+/// subscribers. Subscribers that fall behind the history must reload a
+/// snapshot (`OUT_OF_RANGE`).
+pub struct SyncService<T>
+where
+    T: Float + Debug + Send + Sync,
+{
+    node_id: String,
+    model: Arc<Model<T>>,
+    version: AtomicU64,
+    history: Mutex<VecDeque<Delta>>,
+    history_capacity: usize,
+    deltas: broadcast::Sender<Delta>,
+}
+
+impl<T> SyncService<T>
+where
+    T: Float + Debug + Send + Sync + 'static,
+{
+    pub fn new(node_id: &str, model: Arc<Model<T>>, history_capacity: usize) -> Self {
+        let (deltas, _) = broadcast::channel(history_capacity.max(1));
+        SyncService {
+            node_id: node_id.to_string(),
+            model,
+            version: AtomicU64::new(0),
+            history: Mutex::new(VecDeque::new()),
+            history_capacity: history_capacity.max(1),
+            deltas,
+        }
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
+    }
+
+    /// Applies `delta` to the model, assigns it the next version and
+    /// publishes it to subscribers.
+    pub fn apply(&self, delta: Delta) -> Result<u64, ModelError> {
+        let mut history = self.history.lock()?;
+        apply_delta(&self.model, &delta)?;
+        Ok(self.record(&mut history, delta))
+    }
+
+    /// Records a change already made to the model outside of this service
+    /// (e.g. by a local training step) as a delta against `before`.
+    pub fn publish_local(&self, before: &[T]) -> Result<u64, ModelError> {
+        let mut history = self.history.lock()?;
+        let delta = self.diff(before);
+        Ok(self.record(&mut history, delta))
+    }
+
+    /// Runs `step`, a change to the model made outside of this service (a
+    /// local training step), and publishes what it changed, if anything.
+    /// Deltas pushed meanwhile wait, so the published one holds only the
+    /// changes of `step`.
+    pub fn record_local<R>(
+        &self,
+        step: impl FnOnce() -> Result<R, ModelError>,
+    ) -> Result<R, ModelError> {
+        let mut history = self.history.lock()?;
+        let before = self.model.snapshot();
+        let result = step()?;
+        let delta = self.diff(&before);
+        if !delta.indices.is_empty() {
+            self.record(&mut history, delta);
+        }
+        Ok(result)
+    }
+
+    fn diff(&self, before: &[T]) -> Delta {
+        let after = self.model.read_params();
+        let mut indices = Vec::new();
+        let mut values = Vec::new();
+        for (i, (old, new)) in before.iter().zip(after.iter()).enumerate() {
+            if old != new {
+                indices.push(i as u32);
+                values.push((*new - *old).to_f64().unwrap_or(0.0));
+            }
+        }
+        Delta {
+            protocol_version: PROTOCOL_VERSION,
+            version: 0,
+            indices,
+            values,
+            node_id: self.node_id.clone(),
+        }
+    }
+
+    fn record(&self, history: &mut VecDeque<Delta>, mut delta: Delta) -> u64 {
+        let version = self.version.fetch_add(1, Ordering::SeqCst) + 1;
+        delta.version = version;
+        delta.protocol_version = PROTOCOL_VERSION;
+        if history.len() >= self.history_capacity {
+            history.pop_front();
+        }
+        history.push_back(delta.clone());
+        // No subscribers is not an error.
+        let _ = self.deltas.send(delta);
+        version
+    }
+
+    /// Wraps the service in a tonic server with gzip compression enabled.
+    pub fn into_server(self) -> ParameterSyncServer<Self> {
+        Arc::new(self).server()
+    }
+
+    /// Like [`SyncService::into_server`], for a service also used locally.
+    pub fn server(self: Arc<Self>) -> ParameterSyncServer<Self> {
+        ParameterSyncServer::from_arc(self)
+            .send_compressed(CompressionEncoding::Gzip)
+            .accept_compressed(CompressionEncoding::Gzip)
+    }
+
+    /// Serves the protocol on `address` from the current runtime.
+    pub fn spawn(
+        self: Arc<Self>,
+        address: SocketAddr,
+        metrics: Arc<Metrics>,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let served = tonic::transport::Server::builder()
+                .add_service(self.server())
+                .serve(address)
+                .await;
+            if served.is_err() {
+                metrics.increment("parameter_sync_failures");
+            }
+        })
+    }
+}
+
+/// Adds `delta` to the parameters of `model`. A delta that does not fit
+/// them changes nothing.
+fn apply_delta<T>(model: &Model<T>, delta: &Delta) -> Result<(), ModelError>
+where
+    T: Float + Debug + Send + Sync,
+{
+    if delta.indices.len() != delta.values.len() {
+        return Err(ModelError::InvalidInput(
+            "delta indices and values differ in length".to_string(),
+        ));
+    }
+    let mut params = model.write_params();
+    if let Some(index) = delta.indices.iter().find(|&&i| i as usize >= params.len()) {
+        let count = params.len();
+        params.discard();
+        return Err(ModelError::InvalidInput(format!(
+            "delta index {} is out of bounds for {} parameters",
+            index, count
+        )));
+    }
+    for (&index, &value) in delta.indices.iter().zip(delta.values.iter()) {
+        let param = &mut params[index as usize];
+        *param = *param + T::from(value).unwrap_or_else(T::zero);
+    }
+    Ok(())
+}
+
+type DeltaStream = Pin<Box<dyn Stream<Item = Result<Delta, Status>> + Send + 'static>>;
+
+#[tonic::async_trait]
+impl<T> ParameterSync for SyncService<T>
+where
+    T: Float + Debug + Send + Sync + 'static,
+{
+    async fn get_snapshot(
+        &self,
+        request: Request<SnapshotRequest>,
+    ) -> Result<Response<Snapshot>, Status> {
+        check_protocol(request.get_ref().protocol_version)?;
+        let _history = self
+            .history
+            .lock()
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let values = self
+            .model
+            .read_params()
+            .iter()
+            .map(|v| v.to_f64().unwrap_or(f64::NAN))
+            .collect();
+        Ok(Response::new(Snapshot {
+            protocol_version: PROTOCOL_VERSION,
+            version: self.version(),
+            values,
+            node_id: self.node_id.clone(),
+        }))
+    }
+
+    type StreamDeltasStream = DeltaStream;
+
+    async fn stream_deltas(
+        &self,
+        request: Request<StreamDeltasRequest>,
+    ) -> Result<Response<Self::StreamDeltasStream>, Status> {
+        let request = request.into_inner();
+        check_protocol(request.protocol_version)?;
+        // Subscribe before reading the history so no delta falls in between.
+        let receiver = self.deltas.subscribe();
+        let backlog: Vec<Delta> = {
+            let history = self
+                .history
+                .lock()
+                .map_err(|e| Status::internal(e.to_string()))?;
+            let oldest = history
+                .front()
+                .map(|d| d.version)
+                .unwrap_or(self.version() + 1);
+            if request.since_version + 1 < oldest {
+                return Err(Status::out_of_range(
+                    "requested deltas are no longer retained, load a snapshot",
+                ));
+            }
+            history
+                .iter()
+                .filter(|d| d.version > request.since_version)
+                .cloned()
+                .collect()
+        };
+        let replayed_up_to = backlog
+            .last()
+            .map(|d| d.version)
+            .unwrap_or(request.since_version);
+        let live = BroadcastStream::new(receiver).filter_map(move |delta| match delta {
+            Ok(delta) if delta.version > replayed_up_to => Some(Ok(delta)),
+            Ok(_) => None,
+            Err(e) => Some(Err(Status::data_loss(e.to_string()))),
+        });
+        let stream = tokio_stream::iter(backlog.into_iter().map(Ok)).chain(live);
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn push_update(
+        &self,
+        request: Request<Delta>,
+    ) -> Result<Response<PushUpdateReply>, Status> {
+        let delta = request.into_inner();
+        check_protocol(delta.protocol_version)?;
+        match self.apply(delta) {
+            Ok(version) => Ok(Response::new(PushUpdateReply { version })),
+            Err(ModelError::InvalidInput(e)) => Err(Status::invalid_argument(e)),
+            Err(e) => Err(Status::internal(e.to_string())),
+        }
+    }
+}
+
+/// Connects to a peer's parameter-sync endpoint with gzip compression enabled.
+pub async fn connect(endpoint: &str) -> Result<ParameterSyncClient<Channel>, ModelError> {
+    let client = ParameterSyncClient::connect(endpoint.to_string()).await?;
+    Ok(client
+        .send_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Gzip))
+}
+
+/// Follows the leader of `follower`: loads a snapshot when the follower
+/// needs one, then applies the deltas the leader streams, calling
+/// `on_delta` with the version of each, until the stream breaks. Returns
+/// the number of deltas applied; a leader that no longer retains the next
+/// delta makes the follower load a snapshot on the next call.
+pub(crate) async fn follow<T>(
+    follower: &Follower,
+    model: &Model<T>,
+    mut on_delta: impl FnMut(u64),
+) -> Result<usize, ModelError>
+where
+    T: Float + Debug + Send + Sync,
+{
+    let mut client = connect(&follower.leader_address()).await?;
+    if follower.needs_snapshot() {
+        let snapshot = client
+            .get_snapshot(SnapshotRequest {
+                protocol_version: PROTOCOL_VERSION,
+            })
+            .await?
+            .into_inner();
+        *model.write_params() = snapshot
+            .values
+            .iter()
+            .map(|v| T::from(*v).unwrap_or_else(T::nan))
+            .collect();
+        follower.loaded_snapshot(snapshot.version);
+    }
+    let request = StreamDeltasRequest {
+        protocol_version: PROTOCOL_VERSION,
+        since_version: follower.applied_seq(),
+    };
+    let mut deltas = match client.stream_deltas(request).await {
+        Ok(deltas) => deltas.into_inner(),
+        Err(status) if status.code() == tonic::Code::OutOfRange => {
+            follower.fell_behind();
+            return Ok(0);
+        }
+        Err(status) => return Err(status.into()),
+    };
+    let mut applied = 0;
+    while let Some(delta) = deltas.next().await {
+        let delta = delta?;
+        apply_delta(model, &delta)?;
+        follower.applied(delta.version);
+        on_delta(delta.version);
+        applied += 1;
+    }
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::AppState;
+    use crate::service::apply_training_step;
+    use crate::test::ScalingAlgorithm;
+    use std::time::Duration;
+    use tokio_stream::wrappers::TcpListenerStream;
+
+    #[tokio::test]
+    async fn test_snapshot_push_and_stream() {
+        let model = Arc::new(Model::with_parameters(vec![1.0f32, 2.0, 3.0]));
+        let service = SyncService::new("leader", model.clone(), 16);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(service.into_server())
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let mut client = connect(&format!("http://{}", address)).await.unwrap();
+        let reply = client
+            .push_update(Delta {
+                protocol_version: PROTOCOL_VERSION,
+                version: 0,
+                indices: vec![2],
+                values: vec![1.5],
+                node_id: "worker".to_string(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(reply.get_ref().version, 1);
+
+        let snapshot = client
+            .get_snapshot(SnapshotRequest {
+                protocol_version: PROTOCOL_VERSION,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(snapshot.version, 1);
+        assert_eq!(snapshot.values, vec![1.0, 2.0, 4.5]);
+
+        let mut deltas = client
+            .stream_deltas(StreamDeltasRequest {
+                protocol_version: PROTOCOL_VERSION,
+                since_version: 0,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(deltas.next().await.unwrap().unwrap().indices, vec![2]);
+
+        let unsupported = client
+            .get_snapshot(SnapshotRequest {
+                protocol_version: PROTOCOL_VERSION + 1,
+            })
+            .await;
+        assert_eq!(
+            unsupported.unwrap_err().code(),
+            tonic::Code::FailedPrecondition
+        );
+    }
+
+    #[tokio::test]
+    async fn test_follower_tracks_training_steps_over_grpc() {
+        let address = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let leader = AppState::new(Model::with_parameters(vec![1.0f64]), ScalingAlgorithm)
+            .with_parameter_sync("leader", address, 16);
+        let (sync, _) = leader.parameter_sync.clone().unwrap();
+        sync.spawn(address, leader.metrics.clone());
+        apply_training_step(&leader, &ScalingAlgorithm, 2.0, 1.0).unwrap();
+
+        let follower = Arc::new(Follower::over_grpc(
+            &format!("http://{}", address),
+            Duration::from_millis(10),
+        ));
+        let model = Arc::new(Model::with_parameters(vec![0.0f64]));
+        let metrics = Arc::new(Metrics::new());
+        let following =
+            follower
+                .clone()
+                .spawn(model.clone(), Arc::new(ScalingAlgorithm), metrics.clone());
+        let caught_up = |value: f64| {
+            let model = model.clone();
+            async move {
+                while model.read_params()[0] != value {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            }
+        };
+        // A snapshot first, then the deltas of later steps.
+        tokio::time::timeout(Duration::from_secs(5), caught_up(2.0))
+            .await
+            .unwrap();
+        apply_training_step(&leader, &ScalingAlgorithm, 3.0, 1.0).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), caught_up(6.0))
+            .await
+            .unwrap();
+        assert_eq!(follower.applied_seq(), 2);
+        assert_eq!(metrics.counter("replication_events_applied"), 1);
+        following.abort();
+    }
+}
@@ -1,11 +1,103 @@
-use crate::algorithm::Algorithm;
+use crate::alerts::{AlertEvent, AlertSink};
+use crate::algorithm::{Algorithm, Contributions, Datum, Uncertainty};
+use crate::calibration::{CalibrationMethod, Calibrator};
+use crate::canary::{CanaryConfig, CanaryRollout, RolloutStatus};
+use crate::catalog::{ModelCatalog, MODEL_HEADER, MODEL_VERSION_HEADER};
+use crate::challenger::{Challenger, ChallengerConfig};
+use crate::checkpoint::Checkpointer;
+use crate::clipping::{ClipperConfig, FeatureClipper};
+use crate::cluster::{Membership, MembershipConfig};
+use crate::consistency::{ConflictResolution, ConsistencyTracker};
+use crate::decay::{Decay, DecayConfig};
+use crate::dedup::{DedupConfig, DedupWindow};
+use crate::delayed_labels::{DelayedLabelConfig, PendingPredictions, EVENT_ID_HEADER};
+use crate::early_stopping::{GuardAction, GuardConfig, GuardVerdict, TrainingGuard};
+use crate::ensemble::{EnsembleSpec, Ensembles};
+use crate::errors::ModelError;
+use crate::evaluation::{class_of, EvaluationReport};
+use crate::event_time::{EventClock, EventTimeConfig, EVENT_TIME_HEADER};
+use crate::fallback::{FallbackModel, FallbackRoutes, FALLBACK_HEADER};
+use crate::gossip::{GossipConfig, PeerSync};
+#[cfg(feature = "grpc")]
+use crate::grpc::SyncService;
+use crate::holdout::{Holdout, HoldoutConfig};
+use crate::memory::{
+    MemoryBudget, DEDUP_ACCOUNT, MODEL_ACCOUNT, PENDING_PREDICTIONS_ACCOUNT,
+    PERSONALIZATION_ACCOUNT,
+};
+use crate::metrics::segments::SEGMENT_HEADER;
+use crate::metrics::Metrics;
+use crate::middleware::{OmlMiddleware, SharedMiddleware};
 use crate::model::Model;
-use actix_web::{web, HttpResponse, Responder};
+use crate::orchestrator::Orchestrator;
+use crate::outliers::OutlierFilter;
+use crate::parameter_server::{ParameterServer, ParameterServerConfig};
+use crate::personalization::{PersonalizationConfig, PersonalizedModels, ENTITY_KEY_HEADER};
+use crate::pipeline::PipelineConfig;
+use crate::prediction_log::{PredictionLog, PredictionLogConfig};
+use crate::recorder::{RecorderConfig, TraceRecorder};
+use crate::registry::RegistryFor;
+use crate::reload::{read_runtime_config, ReloadReport, RuntimeConfig};
+use crate::replication::{Follower, Replication, ReplicationLog};
+use crate::schema::{PayloadSchema, PayloadSchemas};
+#[cfg(feature = "scripting")]
+use crate::scripting::ScriptHooks;
+use crate::secure_aggregation::SecureAggregator;
+use crate::self_test::{SelfTest, SelfTestConfig, SelfTestReport};
+use crate::service::{
+    InferenceRequest, InferenceResponse, OmlService, ServiceError, TrainingOutcome,
+};
+use crate::shared_state::{
+    ParameterStore, SharedStateConfig, SharedStateReplica, FORWARDED_HEADER, SAMPLE_WEIGHT_HEADER,
+};
+use crate::simulation::TraceEvent;
+use crate::stale_reads::{ReadConsistency, ServingSnapshot, SnapshotCache, STALENESS_HEADER};
+use crate::tuning::{Tuner, TunerConfig};
+use crate::warmup::{Warmup, WarmupConfig, WarmupFallback, COLD_HEADER};
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
 use num_traits::Float;
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 use std::iter::Sum;
-use std::sync::Arc;
+#[cfg(feature = "grpc")]
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use versions::PARAMETERS_VERSION_HEADER;
+
+pub mod algorithms;
+pub mod archive;
+pub mod canary;
+pub mod catalog;
+pub mod challenger;
+pub mod checkpoint;
+pub mod cluster;
+pub mod consistency;
+pub mod constraints;
+pub mod dry_run;
+pub mod ensemble;
+pub mod evaluation;
+pub mod event_time;
+pub mod federation;
+pub mod feedback;
+pub mod freeze;
+pub mod gossip;
+pub mod guard;
+pub mod holdout;
+pub mod jobs;
+pub mod parameter_server;
+pub mod pipeline;
+#[cfg(feature = "wasm")]
+pub mod plugins;
+pub mod quarantine;
+pub mod reload;
+pub mod replication;
+#[cfg(feature = "scripting")]
+pub mod scripts;
+pub mod self_test;
+pub mod tuning;
+pub mod versions;
 
 /// Shared application state for use in Actix web server handlers.
 ///
@@ -18,70 +110,1252 @@ where
 {
     pub model: Arc<Model<T>>,
     pub algorithm: Arc<A>,
+    pub metrics: Arc<Metrics>,
+    /// Optional guard stage run on every training sample before it reaches the algorithm.
+    pub outlier_filter: Option<Mutex<OutlierFilter<A::Input>>>,
+    /// Set when training inputs are winsorized feature by feature.
+    pub clipper: Option<Mutex<FeatureClipper>>,
+    /// Set when the process acts as a parameter server for remote workers.
+    pub parameter_server: Option<ParameterServer>,
+    /// Set when the model is kept in sync with peers via gossip.
+    pub gossip: Option<Arc<PeerSync>>,
+    /// Set when this instance takes part in leader/follower replication.
+    pub replication: Option<Arc<Replication<A::Input>>>,
+    /// Set when the `ParameterSync` gRPC protocol is served on the address,
+    /// streaming local training steps to followers.
+    #[cfg(feature = "grpc")]
+    pub parameter_sync: Option<(Arc<SyncService<T>>, SocketAddr)>,
+    /// Set when this instance is one of several replicas sharing a parameter store.
+    pub shared_state: Option<Arc<SharedStateReplica<T>>>,
+    /// Set when federated clients submit masked updates for secure aggregation.
+    pub secure_aggregation: Option<Mutex<SecureAggregator>>,
+    /// Set when this instance tracks cluster membership via heartbeats.
+    pub membership: Option<Arc<Membership>>,
+    /// Set when deltas from several writers are ordered with version vectors.
+    pub consistency: Option<Arc<ConsistencyTracker<T>>>,
+    /// Set when this instance distributes batch jobs across worker instances.
+    pub orchestrator: Option<Arc<Orchestrator>>,
+    /// Optional calibration stage applied to inference outputs.
+    pub calibrator: Option<Mutex<Calibrator>>,
+    /// Set when a registered holdout dataset is evaluated on a schedule.
+    pub holdout: Option<Arc<Holdout<A::Input, A::Output>>>,
+    /// Set while candidate configurations race for the serving slot.
+    pub tuner: Option<Arc<Tuner<T, A>>>,
+    /// Set when a challenger model is compared against the serving model.
+    pub challenger: Option<Arc<Challenger<T, A>>>,
+    /// Algorithm promoted to the serving slot by the tuner or a challenger.
+    pub promoted_algorithm: RwLock<Option<Arc<A>>>,
+    /// Webhooks notified of operational events such as a tripped training guard.
+    pub alerts: Option<AlertSink>,
+    /// Set when training is stopped or reverted on sustained metric degradation.
+    pub training_guard: Option<TrainingGuard<T>>,
+    /// Set when predictions are kept until their delayed label arrives.
+    pub pending_predictions: Option<PendingPredictions<A::Input, A::Output>>,
+    /// Set when further named algorithms operate on the same model.
+    pub algorithms: Option<Arc<RegistryFor<T, A>>>,
+    /// Set when operator scripts pre- and post-process requests.
+    #[cfg(feature = "scripting")]
+    pub scripts: Option<Arc<ScriptHooks>>,
+    /// Hooks registered by library users, run in registration order.
+    pub middleware: Vec<SharedMiddleware<A::Input, A::Output>>,
+    /// Set when inference requests can target other models or versions by header.
+    pub catalog: Option<Arc<ModelCatalog<T, A>>>,
+    /// Set when requests with an entity key get per-key models.
+    pub personalization: Option<Arc<PersonalizedModels<T>>>,
+    /// Set when every inference and training request is recorded to a trace.
+    pub recorder: Option<Arc<TraceRecorder<A::Input, A::Output>>>,
+    /// Set when a sample of the predictions is logged for offline use.
+    pub prediction_log: Option<Arc<PredictionLog>>,
+    /// Set when plain inference reads a periodically refreshed copy of the
+    /// model, see [`AppState::with_read_consistency`].
+    pub stale_reads: Option<SnapshotCache<T>>,
+    /// Threading of `POST /training/batch`.
+    pub batch_pipeline: PipelineConfig,
+    /// Set when new parameters are rolled out gradually instead of swapped in.
+    pub canary: Option<Arc<CanaryRollout<T>>>,
+    /// Models standing in for failing ones, by catalog model name, `None`
+    /// for the serving model.
+    pub fallbacks: FallbackRoutes<T, A>,
+    /// Virtual models combining the predictions of catalog models.
+    pub ensembles: Ensembles,
+    /// Set when an unfitted serving model is flagged or stood in for.
+    pub warmup: Option<Arc<Warmup<T>>>,
+    /// Decay schedules by catalog model name, `None` for the serving model.
+    pub decay: Vec<(Option<String>, Arc<Decay<T>>)>,
+    /// Set when the parameters are checkpointed periodically.
+    pub checkpointer: Option<Arc<Checkpointer<T>>>,
+    /// Runtime configuration file re-read on `POST /admin/reload` and SIGHUP.
+    pub config_file: Option<PathBuf>,
+    /// Default body of inference responses.
+    pub response_format: ResponseFormat,
+    /// Schemas decoding mixed-type inference payloads, by catalog model
+    /// name, `None` for the serving model.
+    pub payload_schemas: PayloadSchemas,
+    /// Set when training samples with an event time are admitted by a
+    /// watermark.
+    pub event_clock: Option<Arc<EventClock>>,
+    /// Set when duplicate training samples within a window are skipped.
+    pub dedup: Option<Arc<DedupWindow>>,
+    /// Set when the memory held by the model and its stores is capped.
+    pub memory_budget: Option<Arc<MemoryBudget>>,
+    /// Set when the serving model must pass validation probes to report ready.
+    pub self_test: Option<Arc<SelfTest<A::Input>>>,
 }
 
-/// Asynchronous handler for inference requests.
+impl<T, A> AppState<T, A>
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    /// Creates the state for `model` and `algorithm` with every optional stage disabled.
+    pub fn new(model: Model<T>, algorithm: A) -> Self {
+        AppState {
+            model: Arc::new(model),
+            algorithm: Arc::new(algorithm),
+            metrics: Arc::new(Metrics::new()),
+            outlier_filter: None,
+            clipper: None,
+            parameter_server: None,
+            gossip: None,
+            replication: None,
+            #[cfg(feature = "grpc")]
+            parameter_sync: None,
+            shared_state: None,
+            secure_aggregation: None,
+            membership: None,
+            consistency: None,
+            orchestrator: None,
+            calibrator: None,
+            holdout: None,
+            tuner: None,
+            challenger: None,
+            promoted_algorithm: RwLock::new(None),
+            alerts: None,
+            training_guard: None,
+            pending_predictions: None,
+            algorithms: None,
+            #[cfg(feature = "scripting")]
+            scripts: None,
+            middleware: Vec::new(),
+            catalog: None,
+            personalization: None,
+            recorder: None,
+            prediction_log: None,
+            stale_reads: None,
+            batch_pipeline: PipelineConfig::default(),
+            canary: None,
+            fallbacks: Vec::new(),
+            ensembles: Ensembles::new(),
+            warmup: None,
+            decay: Vec::new(),
+            checkpointer: None,
+            config_file: None,
+            response_format: ResponseFormat::default(),
+            payload_schemas: Vec::new(),
+            event_clock: None,
+            dedup: None,
+            memory_budget: None,
+            self_test: None,
+        }
+    }
+
+    /// Enables outlier filtering of training samples.
+    pub fn with_outlier_filter(mut self, filter: OutlierFilter<A::Input>) -> Self {
+        self.outlier_filter = Some(Mutex::new(filter));
+        self
+    }
+
+    /// Enables clipping of training inputs to rolling per-feature quantiles.
+    pub fn with_feature_clipper(mut self, config: ClipperConfig) -> Self {
+        self.clipper = Some(Mutex::new(FeatureClipper::new(config)));
+        self
+    }
+
+    /// Enables parameter-server mode (`/ps/*` routes).
+    pub fn with_parameter_server(mut self, config: ParameterServerConfig) -> Self {
+        self.parameter_server = Some(ParameterServer::new(config));
+        self
+    }
+
+    /// Enables gossip-based synchronization with the configured peers.
+    pub fn with_gossip(mut self, config: GossipConfig) -> Self {
+        self.gossip = Some(Arc::new(PeerSync::new(config)));
+        self
+    }
+
+    /// Makes this instance the replication leader, retaining the last
+    /// `log_capacity` training events for followers to catch up from.
+    pub fn with_replication_leader(mut self, log_capacity: usize) -> Self {
+        self.replication = Some(Arc::new(Replication::Leader(ReplicationLog::new(
+            log_capacity,
+        ))));
+        self
+    }
+
+    /// Makes this instance a read-only follower of the leader at `leader_url`,
+    /// or of the member with that node id when membership is enabled.
+    pub fn with_replication_follower(mut self, leader_url: &str, poll_interval: Duration) -> Self {
+        self.replication = Some(Arc::new(Replication::Follower(Arc::new(Follower::new(
+            leader_url,
+            poll_interval,
+        )))));
+        self
+    }
+
+    /// Serves the `ParameterSync` gRPC protocol on `address` as `node_id`,
+    /// retaining the last `history` deltas: every local training step is
+    /// streamed as a delta to the followers created with
+    /// [`AppState::with_grpc_replication_follower`].
+    #[cfg(feature = "grpc")]
+    pub fn with_parameter_sync(
+        mut self,
+        node_id: &str,
+        address: SocketAddr,
+        history: usize,
+    ) -> Self {
+        let service = SyncService::new(node_id, self.model.clone(), history);
+        self.parameter_sync = Some((Arc::new(service), address));
+        self
+    }
+
+    /// Makes this instance a read-only follower of the `ParameterSync`
+    /// endpoint at `leader_url`, applying the parameter deltas it streams.
+    #[cfg(feature = "grpc")]
+    pub fn with_grpc_replication_follower(
+        mut self,
+        leader_url: &str,
+        retry_interval: Duration,
+    ) -> Self {
+        self.replication = Some(Arc::new(Replication::Follower(Arc::new(
+            Follower::over_grpc(leader_url, retry_interval),
+        ))));
+        self
+    }
+
+    /// Makes this instance one replica of a horizontally scaled deployment
+    /// backed by `store`.
+    pub fn with_shared_state(
+        mut self,
+        config: SharedStateConfig,
+        store: Arc<dyn ParameterStore<T>>,
+    ) -> Self {
+        self.shared_state = Some(Arc::new(SharedStateReplica::new(config, store)));
+        self
+    }
+
+    /// Accepts federated updates from `clients` through secure aggregation.
+    pub fn with_secure_aggregation(mut self, clients: impl IntoIterator<Item = u32>) -> Self {
+        self.secure_aggregation = Some(Mutex::new(SecureAggregator::new(clients)));
+        self
+    }
+
+    /// Enables the membership layer (`/cluster` routes). Live members are
+    /// also used as gossip peers and orchestrator workers, can name the
+    /// leader of a replication follower and make up the clients of
+    /// secure-aggregation rounds.
+    pub fn with_membership(mut self, config: MembershipConfig) -> Self {
+        self.membership = Some(Arc::new(Membership::new(config)));
+        self
+    }
+
+    /// Stamps local training with version vectors and accepts deltas from
+    /// other writers (`/consistency/*` routes). The server pushes the local
+    /// deltas to the gossip peers, or else the live cluster members.
+    pub fn with_consistency(mut self, node_id: &str, resolution: ConflictResolution) -> Self {
+        self.consistency = Some(Arc::new(ConsistencyTracker::new(node_id, resolution)));
+        self
+    }
+
+    /// Enables the jobs API, sharding uploaded batches across `workers`.
+    pub fn with_orchestrator(mut self, workers: Vec<String>) -> Self {
+        self.orchestrator = Some(Arc::new(Orchestrator::new(workers)));
+        self
+    }
+
+    /// Calibrates inference outputs into probabilities, learning the mapping
+    /// from labeled outcomes.
+    pub fn with_calibration(mut self, method: CalibrationMethod) -> Self {
+        self.calibrator = Some(Mutex::new(Calibrator::new(method)));
+        self
+    }
+
+    /// Enables scheduled evaluation against an uploaded holdout dataset
+    /// (`PUT /model/holdout`, `GET /model/evaluations`).
+    pub fn with_holdout(mut self, config: HoldoutConfig) -> Self {
+        self.holdout = Some(Arc::new(Holdout::new(config)));
+        self
+    }
+
+    /// Races `candidates` on labeled samples (`POST /tuning/samples`); the
+    /// winner's parameters and algorithm replace the serving ones.
+    pub fn with_tuner(mut self, config: TunerConfig, candidates: Vec<(String, A)>) -> Self {
+        let parameters = self.model.read_params().clone();
+        self.tuner = Some(Arc::new(Tuner::new(config, candidates, &parameters)));
+        self
+    }
+
+    /// Registers a challenger that trains on the same stream as the serving
+    /// model and is compared with it on labeled outcomes (`/challenger` routes).
+    pub fn with_challenger(mut self, config: ChallengerConfig, algorithm: A) -> Self {
+        let parameters = self.model.read_params().clone();
+        self.challenger = Some(Arc::new(Challenger::new(config, algorithm, &parameters)));
+        self
+    }
+
+    /// Posts operational events as JSON to `webhooks`.
+    pub fn with_alert_webhooks(mut self, webhooks: Vec<String>) -> Self {
+        self.alerts = Some(AlertSink::new(webhooks));
+        self
+    }
+
+    /// Pauses training or reverts to the best snapshot when evaluation
+    /// metrics degrade for a sustained window.
+    pub fn with_training_guard(mut self, config: GuardConfig) -> Self {
+        self.training_guard = Some(TrainingGuard::new(config));
+        self
+    }
+
+    /// Keeps predictions served with an event id until `POST /feedback/{id}`
+    /// supplies their label.
+    pub fn with_delayed_labels(mut self, config: DelayedLabelConfig) -> Self {
+        self.pending_predictions = Some(PendingPredictions::new(config));
+        self
+    }
+
+    /// Serves the algorithms of `registry` against the model
+    /// (`/models/{name}/algorithms/*` routes).
+    pub fn with_algorithms(mut self, registry: RegistryFor<T, A>) -> Self {
+        self.algorithms = Some(Arc::new(registry));
+        self
+    }
+
+    /// Runs operator scripts around the algorithm (`/scripts` routes).
+    #[cfg(feature = "scripting")]
+    pub fn with_scripts(mut self, hooks: ScriptHooks) -> Self {
+        self.scripts = Some(Arc::new(hooks));
+        self
+    }
+
+    /// Lets `X-Oml-Model`/`X-Oml-Model-Version` headers on `/inference`
+    /// select a model of `catalog`; the serving model is registered in it as
+    /// `name`, so its snapshots can be targeted too.
+    pub fn with_model_catalog(mut self, name: &str, catalog: ModelCatalog<T, A>) -> Self {
+        catalog.register(name, self.model.clone(), self.algorithm.clone());
+        self.catalog = Some(Arc::new(catalog));
+        self
+    }
+
+    /// Layers a per-key model on the serving model for requests carrying an
+    /// `X-Oml-Entity-Key` header.
+    pub fn with_personalization(mut self, config: PersonalizationConfig) -> Self {
+        self.personalization = Some(Arc::new(PersonalizedModels::new(config)));
+        self
+    }
+
+    /// Records every inference and training request, with the parameters
+    /// version, to a binary trace that `oml simulate` can replay.
+    pub fn with_trace_recorder(mut self, config: RecorderConfig) -> Self {
+        self.recorder = Some(Arc::new(TraceRecorder::new(config)));
+        self
+    }
+
+    /// Records `event` if a recorder is set; failures are only counted.
+    pub(crate) fn record(&self, event: TraceEvent<A::Input, A::Output>, version: Option<u64>) {
+        if let Some(recorder) = &self.recorder {
+            match recorder.record(event, version) {
+                Ok(()) => self.metrics.increment("trace_records"),
+                Err(_) => self.metrics.increment("trace_failures"),
+            }
+        }
+    }
+
+    /// Logs a sample of the answered inference requests, with redacted
+    /// features, to NDJSON files (see [`PredictionLog`]).
+    pub fn with_prediction_log(mut self, config: PredictionLogConfig) -> Self {
+        self.prediction_log = Some(Arc::new(PredictionLog::new(config)));
+        self
+    }
+
+    /// Sets how fresh the parameters serving plain inference requests must
+    /// be. Under [`ReadConsistency::Bounded`] they are served from a copy of
+    /// the model refreshed at most every `refresh_ms`, and responses report
+    /// its staleness in an `X-Oml-Staleness-Ms` header.
+    pub fn with_read_consistency(mut self, consistency: ReadConsistency) -> Self {
+        self.stale_reads = match consistency {
+            ReadConsistency::Fresh => None,
+            ReadConsistency::Bounded { refresh_ms } => {
+                Some(SnapshotCache::new(Duration::from_millis(refresh_ms)))
+            }
+        };
+        self
+    }
+
+    /// The parameters plain inference requests are served from: the live
+    /// ones, or their cached copy (see [`AppState::with_read_consistency`]).
+    pub fn serving_snapshot(&self) -> Result<ServingSnapshot<T>, ModelError> {
+        let Some(cache) = &self.stale_reads else {
+            return Ok(ServingSnapshot {
+                model: self.model.clone(),
+                version: self.model.version(),
+                staleness: Duration::ZERO,
+            });
+        };
+        let (snapshot, refreshed) = cache.get(&self.model)?;
+        if refreshed {
+            self.metrics.increment("snapshot_refreshes");
+        }
+        Ok(snapshot)
+    }
+
+    /// Logs a prediction if a prediction log is set and samples it;
+    /// failures are only counted.
+    pub(crate) fn log_prediction(
+        &self,
+        input: &A::Input,
+        prediction: &A::Output,
+        version: Option<u64>,
+        latency: Duration,
+    ) {
+        let Some(log) = &self.prediction_log else {
+            return;
+        };
+        let logged = log.sample().and_then(|sampled| match sampled {
+            true => log
+                .record(input, prediction, version, latency)
+                .map(|_| true),
+            false => Ok(false),
+        });
+        match logged {
+            Ok(true) => self.metrics.increment("prediction_log_records"),
+            Ok(false) => {}
+            Err(_) => self.metrics.increment("prediction_log_failures"),
+        }
+    }
+
+    /// Sets how `POST /training/batch` spreads decoding, transforming and
+    /// training a batch over threads.
+    pub fn with_batch_pipeline(mut self, config: PipelineConfig) -> Self {
+        self.batch_pipeline = config;
+        self
+    }
+
+    /// Rolls out weights uploaded with `PUT /model/weights?canary=true`
+    /// gradually, rolling back if they serve worse than the live ones.
+    pub fn with_canary(mut self, config: CanaryConfig) -> Self {
+        self.canary = Some(Arc::new(CanaryRollout::new(config)));
+        self
+    }
+
+    /// Flags or stands in for the predictions of the serving model until it
+    /// is warm, see [`Warmup`].
+    pub fn with_warmup(mut self, config: WarmupConfig, fallback: WarmupFallback<T>) -> Self {
+        self.warmup = Some(Arc::new(Warmup::new(config, fallback)));
+        self
+    }
+
+    /// Whether the serving model is still warming up.
+    pub fn is_cold(&self) -> bool {
+        self.warmup
+            .as_ref()
+            .is_some_and(|warmup| warmup.is_cold(&self.model, &self.metrics))
+    }
+
+    /// Counts and alerts on a change of the canary rollout.
+    pub(crate) fn canary_changed(&self, change: Result<Option<RolloutStatus>, ModelError>) {
+        match change {
+            Ok(Some(RolloutStatus::Running { fraction, .. })) => {
+                self.metrics.increment("canary_stages");
+                self.metrics.set_gauge("canary_fraction", fraction);
+            }
+            Ok(Some(RolloutStatus::Promoted { version })) => {
+                self.metrics.increment("canary_promotions");
+                self.metrics.set_gauge("canary_fraction", 0.0);
+                self.alert(AlertEvent::new(
+                    "canary_promoted",
+                    "canary parameters promoted",
+                    serde_json::json!({ "version": version }),
+                ));
+            }
+            Ok(Some(RolloutStatus::RolledBack { reason })) => {
+                self.metrics.increment("canary_rollbacks");
+                self.metrics.set_gauge("canary_fraction", 0.0);
+                self.alert(AlertEvent::new(
+                    "canary_rolled_back",
+                    format!("canary parameters rolled back: {}", reason),
+                    serde_json::json!({ "reason": reason }),
+                ));
+            }
+            Ok(_) => {}
+            Err(_) => self.metrics.increment("canary_failures"),
+        }
+    }
+
+    /// Periodically decays the serving model towards its current parameters
+    /// (or the configured prior) and expires idle personalized keys.
+    pub fn with_decay(mut self, config: DecayConfig) -> Self {
+        let decay = Decay::new(config).prior_from(&self.model);
+        self.decay.push((None, Arc::new(decay)));
+        self
+    }
+
+    /// Periodically decays the catalog model `name`, while it exists; its
+    /// prior defaults to its parameters when the decay first applies.
+    pub fn with_model_decay(mut self, name: &str, config: DecayConfig) -> Self {
+        let mut decay = Decay::new(config);
+        let model = self
+            .catalog
+            .as_ref()
+            .and_then(|c| c.resolve(name, None).ok().flatten());
+        if let Some((model, _)) = model {
+            decay = decay.prior_from(&model);
+        }
+        self.decay.push((Some(name.to_string()), Arc::new(decay)));
+        self
+    }
+
+    /// Serves `fallback` when the serving model's inference step fails or
+    /// times out.
+    pub fn with_fallback(mut self, fallback: FallbackModel<T, A>) -> Self {
+        self.fallbacks.push((None, Arc::new(fallback)));
+        self
+    }
+
+    /// Serves `fallback` when the catalog model `name` fails or times out.
+    pub fn with_model_fallback(mut self, name: &str, fallback: FallbackModel<T, A>) -> Self {
+        self.fallbacks
+            .push((Some(name.to_string()), Arc::new(fallback)));
+        self
+    }
+
+    /// Serves the catalog models of `spec` combined as the ensemble `name`
+    /// (`/ensembles/{name}` routes). A spec failing
+    /// [`EnsembleSpec::validate`] fails the ensemble's requests.
+    pub fn with_ensemble(self, name: &str, spec: EnsembleSpec) -> Self {
+        self.ensembles.insert(name, spec);
+        self
+    }
+
+    /// The fallback of catalog model `model`, or of the serving model.
+    pub(crate) fn fallback_for(&self, model: Option<&str>) -> Option<Arc<FallbackModel<T, A>>> {
+        self.fallbacks
+            .iter()
+            .find(|(name, _)| name.as_deref() == model)
+            .map(|(_, fallback)| fallback.clone())
+    }
+
+    /// Admits training samples sent with an event time by a watermark, so
+    /// late and out-of-order ones are handled by the policy of `config`.
+    pub fn with_event_time(mut self, config: EventTimeConfig) -> Self {
+        self.event_clock = Some(Arc::new(EventClock::new(config)));
+        self
+    }
+
+    /// Skips training samples identical to one seen within the window of
+    /// `config`, e.g. double-delivered by a stream source.
+    pub fn with_dedup(mut self, config: DedupConfig) -> Self {
+        self.dedup = Some(Arc::new(DedupWindow::new(config)));
+        self
+    }
+
+    /// Validates the serving model with `config` when the server is built
+    /// and on `POST /admin/selftest`; see [`SelfTest`].
+    pub fn with_self_test(mut self, config: SelfTestConfig<A::Input>) -> Self {
+        self.self_test = Some(Arc::new(SelfTest::new(config)));
+        self
+    }
+
+    /// Has every component calling peers (replication follower, gossip,
+    /// membership, consistency shipping, shared state, batch jobs)
+    /// authenticate with `token`.
+    pub(crate) fn set_peer_token(&self, token: &str) {
+        let follower = match self.replication.as_deref() {
+            Some(Replication::Follower(follower)) => Some(follower.peer_client()),
+            _ => None,
+        };
+        let clients = [
+            follower,
+            self.gossip.as_ref().map(|g| g.peer_client()),
+            self.membership.as_ref().map(|m| m.peer_client()),
+            self.consistency.as_ref().map(|c| c.peer_client()),
+            self.shared_state.as_ref().map(|s| s.peer_client()),
+            self.orchestrator.as_ref().map(|o| o.peer_client()),
+        ];
+        for client in clients.into_iter().flatten() {
+            client.set_token(token);
+        }
+    }
+
+    /// Runs the self-test on the serving model and algorithm, recording the
+    /// outcome in the `self_test_passed` gauge. `None` without a self-test.
+    pub fn run_self_test(&self) -> Option<SelfTestReport> {
+        let self_test = self.self_test.as_ref()?;
+        let report = self_test.run(&self.model, self.active_algorithm().as_ref());
+        self.metrics
+            .set_gauge("self_test_passed", if report.passed { 1.0 } else { 0.0 });
+        if !report.passed {
+            self.metrics.increment("self_test_failures");
+        }
+        Some(report)
+    }
+
+    /// Whether the instance is ready to serve: always, unless a self-test is
+    /// configured and its last run did not pass.
+    pub fn is_ready(&self) -> bool {
+        self.self_test.as_ref().is_none_or(|t| t.is_ready())
+    }
+
+    /// Caps the memory held by the model, personalized keys, pending
+    /// predictions and the dedup window at about `limit_bytes`; see
+    /// [`MemoryBudget`].
+    pub fn with_memory_budget(mut self, limit_bytes: usize) -> Self {
+        self.memory_budget = Some(Arc::new(MemoryBudget::new(limit_bytes)));
+        self
+    }
+
+    /// Measures the memory held by the model and its stores against the
+    /// budget, evicting least recently used personalized keys while over
+    /// it, and publishes the usage as gauges. Returns whether the state
+    /// fits the budget; always true without one.
+    pub fn enforce_memory_budget(&self) -> Result<bool, ModelError> {
+        let Some(budget) = &self.memory_budget else {
+            return Ok(true);
+        };
+        budget.set_usage(MODEL_ACCOUNT, self.model.memory_bytes());
+        if let Some(pending) = &self.pending_predictions {
+            budget.set_usage(PENDING_PREDICTIONS_ACCOUNT, pending.memory_bytes());
+        }
+        if let Some(dedup) = &self.dedup {
+            budget.set_usage(DEDUP_ACCOUNT, dedup.memory_bytes());
+        }
+        if let Some(personal) = &self.personalization {
+            let mut bytes = personal.memory_bytes()?;
+            budget.set_usage(PERSONALIZATION_ACCOUNT, bytes);
+            let excess = budget.used_bytes().saturating_sub(budget.limit_bytes());
+            if excess > 0 && bytes > 0 {
+                // Evict about enough keys of average size, then measure again.
+                let per_key = (bytes / personal.len()?.max(1)).max(1);
+                let mut evicted = 0;
+                while evicted < excess.div_ceil(per_key) && personal.evict_oldest()?.is_some() {
+                    evicted += 1;
+                }
+                self.metrics.add("memory_evictions", evicted as u64);
+                self.metrics
+                    .set_gauge("personalized_keys", personal.len()? as f64);
+                bytes = personal.memory_bytes()?;
+                budget.set_usage(PERSONALIZATION_ACCOUNT, bytes);
+            }
+        }
+        for (account, bytes) in budget.usage() {
+            self.metrics
+                .set_gauge(&format!("memory_{}_bytes", account), bytes as f64);
+        }
+        self.metrics
+            .set_gauge("memory_used_bytes", budget.used_bytes() as f64);
+        self.metrics
+            .set_gauge("memory_budget_bytes", budget.limit_bytes() as f64);
+        Ok(!budget.is_exceeded())
+    }
+
+    /// Decodes inference payloads sent as JSON objects with `schema`.
+    pub fn with_payload_schema(mut self, schema: PayloadSchema) -> Self {
+        self.payload_schemas.push((None, Arc::new(schema)));
+        self
+    }
+
+    /// Decodes inference payloads for the catalog model `name` with `schema`.
+    pub fn with_model_payload_schema(mut self, name: &str, schema: PayloadSchema) -> Self {
+        self.payload_schemas
+            .push((Some(name.to_string()), Arc::new(schema)));
+        self
+    }
+
+    /// The inference input in `payload`: encoded with the schema of catalog
+    /// model `model` (or of the serving model) when it is an object and
+    /// there is one, taken as is otherwise.
+    pub(crate) fn decode_input(
+        &self,
+        model: Option<&str>,
+        payload: serde_json::Value,
+    ) -> Result<A::Input, ModelError> {
+        let schema = self
+            .payload_schemas
+            .iter()
+            .find(|(name, _)| name.as_deref() == model)
+            .map(|(_, schema)| schema);
+        match schema {
+            Some(schema) if payload.is_object() => {
+                A::Input::from_features(&schema.encode(&payload)?).ok_or_else(|| {
+                    ModelError::Unsupported(
+                        "the algorithm does not take encoded features".to_string(),
+                    )
+                })
+            }
+            _ => {
+                serde_json::from_value(payload).map_err(|e| ModelError::InvalidInput(e.to_string()))
+            }
+        }
+    }
+
+    /// Answers inference requests in `format` unless they ask for another,
+    /// e.g. [`ResponseFormat::Bare`] for clients of the original format.
+    pub fn with_response_format(mut self, format: ResponseFormat) -> Self {
+        self.response_format = format;
+        self
+    }
+
+    /// Reloads runtime settings from `path` on `POST /admin/reload` and SIGHUP.
+    pub fn with_config_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config_file = Some(path.into());
+        self
+    }
+
+    /// Registers `middleware` after the already registered ones.
+    pub fn with_middleware(
+        mut self,
+        middleware: Arc<dyn OmlMiddleware<A::Input, A::Output>>,
+    ) -> Self {
+        self.middleware.push(middleware);
+        self
+    }
+
+    /// Applies the preprocessing script, if any, to an algorithm input.
+    pub(crate) fn preprocess(&self, x: A::Input) -> Result<A::Input, ModelError> {
+        #[cfg(feature = "scripting")]
+        if let Some(scripts) = &self.scripts {
+            return scripts.preprocess(x);
+        }
+        Ok(x)
+    }
+
+    /// Applies the postprocessing script, if any, to the prediction served for `x`.
+    #[cfg_attr(not(feature = "scripting"), allow(unused_variables))]
+    pub(crate) fn postprocess(
+        &self,
+        x: &A::Input,
+        prediction: A::Output,
+    ) -> Result<A::Output, ModelError> {
+        #[cfg(feature = "scripting")]
+        if let Some(scripts) = &self.scripts {
+            return scripts.postprocess(x, prediction);
+        }
+        Ok(prediction)
+    }
+
+    /// Applies the settings present in `config` to the running stages.
+    pub fn reload(&self, config: &RuntimeConfig) -> Result<ReloadReport, ModelError> {
+        let mut report = ReloadReport::default();
+        if let Some(interval) = config.checkpoint_interval {
+            if let Some(checkpointer) = &self.checkpointer {
+                checkpointer.set_interval(interval);
+            }
+            report.record("checkpoint_interval", self.checkpointer.is_some());
+        }
+        if let Some(guard_config) = &config.training_guard {
+            if let Some(guard) = &self.training_guard {
+                guard.set_config(guard_config.clone())?;
+            }
+            report.record("training_guard", self.training_guard.is_some());
+        }
+        self.metrics.increment("config_reloads");
+        Ok(report)
+    }
+
+    /// Re-reads the configuration file and applies it.
+    pub fn reload_from_file(&self) -> Result<ReloadReport, ModelError> {
+        let Some(path) = &self.config_file else {
+            return Err(ModelError::InvalidInput(
+                "no configuration file is set".to_string(),
+            ));
+        };
+        let result = read_runtime_config(path).and_then(|config| self.reload(&config));
+        if result.is_err() {
+            self.metrics.increment("config_reload_failures");
+        }
+        result
+    }
+
+    /// Sends `event` to the alert webhooks, if any are configured.
+    pub fn alert(&self, event: AlertEvent) {
+        if let Some(alerts) = &self.alerts {
+            self.metrics.increment("alerts_emitted");
+            alerts.emit(event);
+        }
+    }
+
+    /// Feeds the result of a scheduled holdout evaluation of the current
+    /// model to the training guard.
+    pub(crate) fn observe_evaluation(&self, report: &EvaluationReport) {
+        let Some(guard) = &self.training_guard else {
+            return;
+        };
+        match guard.observe(&self.model, report.mae) {
+            Ok(GuardVerdict::Tripped { action }) => {
+                self.metrics.increment("training_guard_trips");
+                let (kind, message) = match action {
+                    GuardAction::Pause => ("training_paused", "training paused"),
+                    GuardAction::Revert => ("training_reverted", "model reverted"),
+                };
+                self.alert(AlertEvent::new(
+                    kind,
+                    format!("{} after sustained metric degradation", message),
+                    serde_json::json!({ "mae": report.mae }),
+                ));
+            }
+            Ok(_) => {}
+            Err(_) => self.metrics.increment("training_guard_failures"),
+        }
+    }
+
+    /// Algorithm currently in the serving slot: the last promoted one, the
+    /// configured algorithm otherwise.
+    pub fn active_algorithm(&self) -> Arc<A> {
+        self.promoted_algorithm
+            .read()
+            .ok()
+            .and_then(|promoted| promoted.clone())
+            .unwrap_or_else(|| self.algorithm.clone())
+    }
+
+    /// Puts `algorithm` in the serving slot with `parameters` as the model state.
+    pub(crate) fn promote(&self, algorithm: Arc<A>, parameters: Vec<T>) -> Result<(), ModelError> {
+        let mut promoted = self.promoted_algorithm.write()?;
+        *self.model.write_params() = parameters;
+        *promoted = Some(algorithm);
+        Ok(())
+    }
+
+    /// Replaces the serving model with the challenger. Returns `false` if the
+    /// challenger had already been promoted.
+    pub fn promote_challenger(&self) -> Result<bool, ModelError> {
+        let Some(challenger) = &self.challenger else {
+            return Err(ModelError::InvalidInput(
+                "no challenger is registered".to_string(),
+            ));
+        };
+        if !challenger.mark_promoted() {
+            return Ok(false);
+        }
+        self.promote(challenger.algorithm(), challenger.parameters())?;
+        self.metrics.increment("challenger_promotions");
+        Ok(true)
+    }
+
+    /// Records one scored sample (raw model output and true label) in the
+    /// online classification metrics (also sliced by `segment`, if any)
+    /// and, for binary labels, in the ROC-AUC estimator. Nothing learns
+    /// from it.
+    pub(crate) fn record_outcome(&self, prediction: f64, label: f64, segment: Option<&str>) {
+        self.metrics
+            .record_classification(class_of(label), class_of(prediction));
+        if let Some(segment) = segment {
+            self.metrics
+                .record_segment_outcome(segment, prediction, label);
+        }
+        if label == 0.0 || label == 1.0 {
+            self.metrics.record_score(prediction, label == 1.0);
+        }
+    }
+
+    /// Feeds one outcome of the live labeled stream (input, raw model output
+    /// and true label) to [`AppState::record_outcome`], to the calibrator
+    /// for binary labels, to the challenger comparison, which may promote
+    /// the challenger, and to the canary.
+    ///
+    /// May run inference on the challenger and the canary parameters, so
+    /// call it from a blocking context.
+    pub(crate) fn observe_outcome(
+        &self,
+        input: A::Input,
+        prediction: f64,
+        label: f64,
+        segment: Option<&str>,
+    ) {
+        self.record_outcome(prediction, label, segment);
+        if let (Some(calibrator), true) = (&self.calibrator, label == 0.0 || label == 1.0) {
+            if let Ok(mut calibrator) = calibrator.lock() {
+                calibrator.update(prediction, label == 1.0);
+            }
+        }
+        if let Some(challenger) = self.challenger.as_ref().filter(|c| !c.is_promoted()) {
+            match challenger.observe(input.clone(), prediction, label) {
+                Ok(report) if report.challenger_wins && challenger.config().auto_promote => {
+                    if self.promote_challenger().is_err() {
+                        self.metrics.increment("challenger_promotion_failures");
+                    }
+                }
+                Ok(_) => {}
+                Err(_) => self.metrics.increment("challenger_failures"),
+            }
+        }
+        if let Some(canary) = &self.canary {
+            let algorithm = self.active_algorithm();
+            let change = canary.observe_outcome(&*algorithm, &self.model, input, prediction, label);
+            self.canary_changed(change);
+        }
+    }
+}
+
+/// Maps a [`ModelError`] to the HTTP response the handlers return for it.
+pub(crate) fn error_response(error: &ModelError) -> HttpResponse {
+    match error {
+        ModelError::InvalidInput(_) | ModelError::DimensionMismatch { .. } => {
+            HttpResponse::BadRequest().body(error.to_string())
+        }
+        ModelError::StaleUpdate { .. } | ModelError::NotLeader(_) => {
+            HttpResponse::Conflict().body(error.to_string())
+        }
+        ModelError::RemoteError(_) => HttpResponse::BadGateway().body(error.to_string()),
+        ModelError::Unsupported(_) => HttpResponse::NotImplemented().body(error.to_string()),
+        ModelError::LockError(_) | ModelError::StorageError(_) => {
+            HttpResponse::InternalServerError().body(error.to_string())
+        }
+    }
+}
+
+/// Maps a [`ServiceError`] to the HTTP response the handlers return for it.
+pub(crate) fn service_response(error: &ServiceError) -> HttpResponse {
+    match error {
+        ServiceError::Model(e) => error_response(e),
+        // A mismatched input fails inside the step but is the caller's fault,
+        // and a step the algorithm does not implement is not a failure.
+        ServiceError::Step(
+            e @ (ModelError::DimensionMismatch { .. } | ModelError::Unsupported(_)),
+        ) => error_response(e),
+        ServiceError::NotFound(message) => HttpResponse::NotFound().body(message.clone()),
+        ServiceError::Unavailable(message) => {
+            HttpResponse::ServiceUnavailable().body(message.clone())
+        }
+        ServiceError::Step(_) | ServiceError::Internal(_) => {
+            HttpResponse::InternalServerError().body(error.to_string())
+        }
+    }
+}
+
+/// Query string of the inference endpoint.
+#[derive(Debug, Deserialize)]
+pub struct InferenceQuery {
+    /// Also return the per-feature contributions of the prediction.
+    #[serde(default)]
+    pub explain: bool,
+    /// Also return the uncertainty of the prediction.
+    #[serde(default)]
+    pub uncertainty: bool,
+    /// Serve from the moving average of the parameters, if the model keeps one.
+    #[serde(default)]
+    pub ema: bool,
+    /// Overrides the server's [`ResponseFormat`].
+    #[serde(default)]
+    pub format: Option<ResponseFormat>,
+}
+
+/// Body of inference responses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseFormat {
+    /// A [`PredictionEnvelope`].
+    #[default]
+    Envelope,
+    /// The bare prediction, or a [`DetailedPrediction`] when `explain` or
+    /// `uncertainty` is set, as before envelopes existed.
+    Bare,
+}
+
+/// Something a client should know about how a prediction was served.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseWarning {
+    /// The serving model is still warming up.
+    ColdModel,
+    /// A fallback model answered for the failing one.
+    Fallback,
+}
+
+/// Response body of the inference endpoints: the prediction with the model
+/// that served it and its metadata.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PredictionEnvelope<T, O = T> {
+    pub prediction: O,
+    /// Name of the serving model, if it has one, or of the catalog model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Version of the parameters that served the request, when known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+    /// Time spent serving the request, in milliseconds.
+    pub latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contributions: Option<Contributions<T>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uncertainty: Option<Uncertainty<T>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<ResponseWarning>,
+}
+
+impl<T, O> PredictionEnvelope<T, O> {
+    /// Wraps `response`, with no model, version or warnings yet.
+    pub fn new(response: InferenceResponse<T, O>, latency: Duration) -> Self {
+        let (prediction, contributions, uncertainty) = match response {
+            InferenceResponse::Prediction(prediction) => (prediction, None, None),
+            InferenceResponse::Detailed(detailed) => (
+                detailed.prediction,
+                detailed.contributions,
+                detailed.uncertainty,
+            ),
+        };
+        PredictionEnvelope {
+            prediction,
+            model: None,
+            version: None,
+            latency_ms: latency.as_secs_f64() * 1000.0,
+            contributions,
+            uncertainty,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+/// Response body of `POST /inference` when `explain` or `uncertainty` is set.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DetailedPrediction<T, O = T> {
+    pub prediction: O,
+    /// Contributions to the raw model output, before calibration.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contributions: Option<Contributions<T>>,
+    /// Uncertainty of the raw model output, before calibration.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uncertainty: Option<Uncertainty<T>>,
+}
+
+/// Response body returned by the training endpoint when a late sample is
+/// discarded.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DiscardedResponse {
+    pub discarded: bool,
+    pub lateness_ms: u64,
+}
+
+/// Response body returned by the training endpoint when a sample duplicates
+/// one trained on within the dedup window.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DuplicateResponse {
+    pub duplicate: bool,
+}
+
+/// Response body returned by the training endpoint when a sample is quarantined.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuarantineResponse {
+    pub quarantined: bool,
+    pub score: f64,
+}
+
+/// Asynchronous handler for inference requests, adapting
+/// [`OmlService::infer`] to HTTP.
 ///
 /// # Arguments
 ///
+/// * `req` - The request; an `X-Oml-Event-Id` header records the prediction
+///   for a later delayed label, the `X-Oml-Model` headers select a catalog
+///   model, an `X-Oml-Entity-Key` header the personalized model of a key
+///   and an `X-Oml-Segment` header slices the request's metrics.
 /// * `data` - Extracted application state including model and algorithm.
-/// * `input` - JSON-parsed input value of type `T`.
+/// * `query` - `explain=true` and `uncertainty=true` add the contributions
+///   and uncertainty of the prediction; `ema=true` serves from the moving
+///   average of the parameters; `format=bare` answers with the bare
+///   prediction (or a [`DetailedPrediction`]) instead of a
+///   [`PredictionEnvelope`].
+/// * `input` - JSON-parsed input of the algorithm's input type.
 ///
 /// # Returns
 ///
 /// A responder that will result in an HTTP response indicating the outcome
-/// of the inference operation.
+/// of the inference operation. With calibration enabled the raw output is
+/// mapped to a calibrated probability; while the serving model warms up
+/// the response carries an `X-Oml-Cold: true` header, and when a fallback
+/// model answered for a failing one an `X-Oml-Fallback: true` header.
 pub async fn handle_inference_step<T, A>(
+    req: HttpRequest,
     data: web::Data<AppState<T, A>>,
-    input: web::Json<T>,
+    query: web::Query<InferenceQuery>,
+    payload: web::Json<serde_json::Value>,
 ) -> impl Responder
 where
     T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
     A: Algorithm<T>,
 {
-    let model = data.model.clone(); // clone the Arc (not the model)
-    let algorithm = data.algorithm.clone(); // clone the Arc (not the algo)
-
-    match tokio::task::spawn_blocking(move || algorithm.inference_step(&model, *input)).await {
-        Ok(response) => match response {
-            Ok(result) => HttpResponse::Ok().json(result),
-            Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
-        },
-        Err(e) => HttpResponse::InternalServerError().body(format!("Task failed: {:?}", e)),
+    let header = |name| req.headers().get(name).and_then(|v| v.to_str().ok());
+    let input = match data.decode_input(header(MODEL_HEADER), payload.into_inner()) {
+        Ok(input) => input,
+        Err(e) => return error_response(&e),
+    };
+    let mut request = InferenceRequest::new(input);
+    request.explain = query.explain;
+    request.uncertainty = query.uncertainty;
+    request.ema = query.ema;
+    request.event_id = header(EVENT_ID_HEADER).map(str::to_string);
+    request.model = header(MODEL_HEADER).map(str::to_string);
+    request.key = header(ENTITY_KEY_HEADER).map(str::to_string);
+    request.segment = header(SEGMENT_HEADER).map(str::to_string);
+    if request.model.is_some() {
+        request.model_version = match header(MODEL_VERSION_HEADER).map(str::parse::<u64>) {
+            Some(Ok(version)) => Some(version),
+            Some(Err(_)) => return HttpResponse::BadRequest().body("invalid model version"),
+            None => None,
+        };
+    }
+    // Reported up front, like the version: a refresh racing the request
+    // only serves it fresher parameters than reported.
+    let snapshot = match request.model.is_none().then(|| data.serving_snapshot()) {
+        Some(Ok(snapshot)) => Some(snapshot),
+        Some(Err(e)) => return error_response(&e),
+        None => None,
+    };
+    let version = snapshot.as_ref().map(|s| s.version);
+    let cold = request.model.is_none() && data.is_cold();
+    let format = query.format.unwrap_or(data.response_format);
+    let name = match &request.model {
+        Some(name) => Some(name.clone()),
+        None => data.model.name().map(str::to_string),
+    };
+    let pinned = request.model_version;
+    let started = Instant::now();
+    match OmlService::from_shared(data.into_inner())
+        .infer_or_fallback(request)
+        .await
+    {
+        Ok((response, fell_back)) => {
+            let mut ok = HttpResponse::Ok();
+            if let Some(snapshot) = &snapshot {
+                ok.insert_header((PARAMETERS_VERSION_HEADER, snapshot.version.to_string()));
+                // Rounded up, so stale parameters never read as fresh.
+                let staleness = snapshot.staleness.as_micros().div_ceil(1000);
+                ok.insert_header((STALENESS_HEADER, staleness.to_string()));
+            }
+            if fell_back {
+                ok.insert_header((FALLBACK_HEADER, "true"));
+            }
+            if cold {
+                ok.insert_header((COLD_HEADER, "true"));
+            }
+            if format == ResponseFormat::Bare {
+                return ok.json(response);
+            }
+            let mut envelope = PredictionEnvelope::new(response, started.elapsed());
+            envelope.model = name;
+            envelope.version = version.or(pinned).filter(|_| !fell_back);
+            if cold {
+                envelope.warnings.push(ResponseWarning::ColdModel);
+            }
+            if fell_back {
+                envelope.warnings.push(ResponseWarning::Fallback);
+            }
+            ok.json(envelope)
+        }
+        Err(e) => service_response(&e),
     }
 }
 
-/// Asynchronous handler for training requests.
+/// Asynchronous handler for training requests, adapting
+/// [`OmlService::train`] to HTTP.
 ///
 /// # Arguments
 ///
+/// * `req` - The request; an `X-Oml-Forwarded-By` header marks it as
+///   forwarded by another replica, an `X-Oml-Entity-Key` header also
+///   trains the personalized model of that key, and an `X-Oml-Event-Time`
+///   header (milliseconds since the Unix epoch) dates a sample without one.
 /// * `data` - Extracted application state including model and algorithm.
-/// * `input` - JSON-parsed input value of type `T`.
+/// * `input` - JSON-parsed input of the algorithm's input type.
 ///
 /// # Returns
 ///
 /// A responder that will result in an HTTP response indicating the outcome
-/// of the training operation.
+/// of the training operation. Samples held back by the outlier filter are
+/// answered with `202 Accepted` and a [`QuarantineResponse`] body, and late
+/// samples discarded with a [`DiscardedResponse`] one, duplicates with a
+/// [`DuplicateResponse`] one. Replicas that are not
+/// the shared-state writer forward the request to it, along with the
+/// sample's weight (see [`SAMPLE_WEIGHT_HEADER`]).
 pub async fn handle_training_step<T, A>(
+    req: HttpRequest,
     data: web::Data<AppState<T, A>>,
-    input: web::Json<T>,
+    input: web::Json<A::Input>,
 ) -> impl Responder
 where
     T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
     A: Algorithm<T>,
 {
-    let model = data.model.clone(); // clone the Arc (not the model)
-    let algorithm = data.algorithm.clone(); // clone the Arc (not the algo)
-
-    match tokio::task::spawn_blocking(move || algorithm.training_step(&model, *input)).await {
-        Ok(response) => match response {
-            Ok(_) => HttpResponse::Ok().finish(),
-            Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    let forwarded = req.headers().contains_key(FORWARDED_HEADER);
+    let key = req
+        .headers()
+        .get(ENTITY_KEY_HEADER)
+        .and_then(|v| v.to_str().ok());
+    let event_time = match req.headers().get(EVENT_TIME_HEADER) {
+        Some(value) => match value.to_str().ok().and_then(|v| v.parse::<u64>().ok()) {
+            Some(time) => Some(time),
+            None => return HttpResponse::BadRequest().body("invalid event time"),
+        },
+        None => None,
+    };
+    let weight = match req
+        .headers()
+        .get(SAMPLE_WEIGHT_HEADER)
+        .filter(|_| forwarded)
+    {
+        Some(value) => match value
+            .to_str()
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|w| (0.0..=1.0).contains(w))
+        {
+            Some(weight) => weight,
+            None => return HttpResponse::BadRequest().body("invalid sample weight"),
         },
-        Err(e) => HttpResponse::InternalServerError().body(format!("Task failed: {:?}", e)),
+        None => 1.0,
+    };
+    let model = data.model.clone();
+    let service = OmlService::from_shared(data.into_inner());
+    let outcome = match (key, event_time) {
+        (Some(key), _) => service.train_for(key, input.into_inner(), forwarded).await,
+        (None, Some(time)) => service.train_at(input.into_inner(), time, forwarded).await,
+        (None, None) => {
+            service
+                .train_weighted(input.into_inner(), weight, forwarded)
+                .await
+        }
+    };
+    match outcome {
+        Ok(TrainingOutcome::Applied) => HttpResponse::Ok()
+            .insert_header((PARAMETERS_VERSION_HEADER, model.version().to_string()))
+            .finish(),
+        Ok(TrainingOutcome::Forwarded) => HttpResponse::Ok().finish(),
+        Ok(TrainingOutcome::Duplicate) => {
+            HttpResponse::Accepted().json(DuplicateResponse { duplicate: true })
+        }
+        Ok(TrainingOutcome::Quarantined { score }) => {
+            HttpResponse::Accepted().json(QuarantineResponse {
+                quarantined: true,
+                score,
+            })
+        }
+        Ok(TrainingOutcome::Discarded { lateness_ms }) => {
+            HttpResponse::Accepted().json(DiscardedResponse {
+                discarded: true,
+                lateness_ms,
+            })
+        }
+        Err(e) => service_response(&e),
     }
 }
 
+/// Handler returning a snapshot of every counter and gauge.
+pub async fn handle_metrics<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    HttpResponse::Ok().json(OmlService::from_shared(data.into_inner()).metrics())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -95,12 +1369,7 @@ mod tests {
         T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
         A: Algorithm<T> + 'static,
     {
-        let model_arc = Arc::new(model);
-        let algorithm_arc = Arc::new(algorithm);
-        web::Data::new(AppState {
-            model: model_arc,
-            algorithm: algorithm_arc,
-        })
+        web::Data::new(AppState::new(model, algorithm))
     }
 
     #[actix_rt::test]
@@ -109,7 +1378,7 @@ mod tests {
         let algorithm = DummyAlgorithm {}; // Use your DummyAlgorithm for testing
         let app_state = create_app_state(model, algorithm);
 
-        let mut app = test::init_service(App::new().app_data(app_state.clone()).route(
+        let app = test::init_service(App::new().app_data(app_state.clone()).route(
             "/inference",
             web::post().to(handle_inference_step::<f32, DummyAlgorithm>),
         ))
@@ -117,14 +1386,64 @@ mod tests {
 
         let req = test::TestRequest::post()
             .uri("/inference")
-            .set_json(&3.5f32)
+            .set_json(3.5f32)
             .to_request();
 
-        let resp = test::call_service(&mut app, req).await;
+        let resp = test::call_service(&app, req).await;
         assert_eq!(resp.status(), http::StatusCode::OK);
 
-        let result: f32 = test::read_body_json(resp).await;
-        assert_eq!(result, 10.5f32); // (1.0 * 3.5) + (2.0 * 3.5)
+        let result: PredictionEnvelope<f32> = test::read_body_json(resp).await;
+        assert_eq!(result.prediction, 10.5f32); // (1.0 * 3.5) + (2.0 * 3.5)
+        assert_eq!((result.version, result.warnings.len()), (Some(0), 0));
+        assert!(result.latency_ms > 0.0);
+    }
+
+    #[actix_rt::test]
+    async fn test_inference_contributions() {
+        let model = Model::<f32>::with_parameters(vec![1.0, 2.0]);
+        let app_state = create_app_state(model, DummyAlgorithm);
+        let app = test::init_service(App::new().app_data(app_state.clone()).route(
+            "/inference",
+            web::post().to(handle_inference_step::<f32, DummyAlgorithm>),
+        ))
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/inference?explain=true")
+            .set_json(3.5f32)
+            .to_request();
+        let result: PredictionEnvelope<f32> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(result.prediction, 10.5f32);
+        assert_eq!(result.contributions.unwrap().features, vec![3.5f32, 7.0]);
+        assert_eq!(result.uncertainty, None);
+
+        // The original format, for older clients.
+        let req = test::TestRequest::post()
+            .uri("/inference?explain=true&format=bare")
+            .set_json(3.5f32)
+            .to_request();
+        let result: DetailedPrediction<f32> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(result.prediction, 10.5f32);
+        assert!(result.contributions.is_some());
+    }
+
+    #[actix_rt::test]
+    async fn test_uncertainty_without_uncertainty_model() {
+        let model = Model::<f32>::with_parameters(vec![1.0, 2.0]);
+        let app_state = create_app_state(model, DummyAlgorithm);
+        let app = test::init_service(App::new().app_data(app_state.clone()).route(
+            "/inference",
+            web::post().to(handle_inference_step::<f32, DummyAlgorithm>),
+        ))
+        .await;
+
+        // The dummy algorithm has no uncertainty model.
+        let req = test::TestRequest::post()
+            .uri("/inference?uncertainty=true")
+            .set_json(3.5f32)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::NOT_IMPLEMENTED);
     }
 
     #[actix_rt::test]
@@ -133,7 +1452,7 @@ mod tests {
         let algorithm = DummyAlgorithm {}; // Use your DummyAlgorithm for testing
         let app_state = create_app_state(model, algorithm);
 
-        let mut app = test::init_service(App::new().app_data(app_state.clone()).route(
+        let app = test::init_service(App::new().app_data(app_state.clone()).route(
             "/training",
             web::post().to(handle_training_step::<f32, DummyAlgorithm>),
         ))
@@ -142,21 +1461,136 @@ mod tests {
         let training_input = 1.1f32;
         let req = test::TestRequest::post()
             .uri("/training")
-            .set_json(&training_input)
+            .set_json(training_input)
             .to_request();
 
-        test::call_service(&mut app, req).await;
+        test::call_service(&app, req).await;
 
         // Unwrap the AppState to get the Model
         let model = &app_state.model;
 
-        unsafe {
-            // Inspect updated model state
-            let updated_parameters = model.get_parameters().clone();
+        // Inspect updated model state
+        let updated_parameters = model.read_params().clone();
+
+        // Ensure parameters have been updated correctly
+        let expected_parameters: Vec<f32> = vec![1.0 * training_input, 2.0 * training_input];
+        assert_eq!(updated_parameters, expected_parameters);
+    }
+
+    /// Classifies a feature vector as "positive" when its weighted sum is.
+    struct SignClassifier;
+
+    impl Algorithm<f32> for SignClassifier {
+        type Input = Vec<f32>;
+        type Output = String;
 
-            // Ensure parameters have been updated correctly
-            let expected_parameters: Vec<f32> = vec![1.0 * training_input, 2.0 * training_input];
-            assert_eq!(updated_parameters, expected_parameters);
+        fn training_step(&self, _model: &Model<f32>, _x: Vec<f32>) -> Result<(), ModelError> {
+            Ok(())
         }
+
+        fn inference_step(&self, model: &Model<f32>, x: Vec<f32>) -> Result<String, ModelError> {
+            let params = model.read_params();
+            let sum: f32 = params.iter().zip(&x).map(|(w, v)| w * v).sum();
+            Ok(if sum > 0.0 { "positive" } else { "negative" }.to_string())
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_structured_input_and_output() {
+        let model = Model::with_parameters(vec![1.0f32, -1.0]).with_input_dimension(2);
+        let app_state = create_app_state(model, SignClassifier);
+        let app = test::init_service(
+            App::new()
+                .app_data(app_state.clone())
+                .configure(crate::server::configure::<f32, SignClassifier>),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/inference")
+            .set_json(vec![3.0f32, 1.0])
+            .to_request();
+        let label: PredictionEnvelope<f32, String> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(label.prediction, "positive");
+
+        let req = test::TestRequest::post()
+            .uri("/inference")
+            .set_json(vec![3.0f32, 1.0, 2.0])
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn test_inference_served_from_ema_weights() {
+        let model = Model::with_parameters(vec![1.0f32]).with_ema(0.5);
+        let app_state = create_app_state(model, crate::test::ScalingAlgorithm);
+        let app = test::init_service(
+            App::new()
+                .app_data(app_state.clone())
+                .configure(crate::server::configure::<f32, crate::test::ScalingAlgorithm>),
+        )
+        .await;
+        let post = |uri: &str| {
+            test::TestRequest::post()
+                .uri(uri)
+                .set_json(3.0f32)
+                .to_request()
+        };
+
+        let resp = test::call_service(&app, post("/training")).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let raw: PredictionEnvelope<f32> =
+            test::call_and_read_body_json(&app, post("/inference")).await;
+        let ema: PredictionEnvelope<f32> =
+            test::call_and_read_body_json(&app, post("/inference?ema=true")).await;
+        assert_eq!((raw.prediction, ema.prediction), (9.0, 6.0));
+        assert_eq!(app_state.metrics.snapshot().counters["ema_inferences"], 1);
+
+        let plain = create_app_state(Model::with_parameters(vec![1.0f32]), DummyAlgorithm);
+        let app = test::init_service(
+            App::new()
+                .app_data(plain)
+                .configure(crate::server::configure::<f32, DummyAlgorithm>),
+        )
+        .await;
+        let resp = test::call_service(&app, post("/inference?ema=true")).await;
+        assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn test_prior_model_serves_until_warm() {
+        let prior = Arc::new(Model::with_parameters(vec![5.0f32]));
+        let state = AppState::new(
+            Model::with_parameters(vec![1.0f32]),
+            crate::test::ScalingAlgorithm,
+        )
+        .with_warmup(
+            WarmupConfig {
+                min_samples: 1,
+                metric: None,
+            },
+            WarmupFallback::Prior(prior),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(crate::server::configure::<f32, crate::test::ScalingAlgorithm>),
+        )
+        .await;
+        let post = |uri: &str, x: f32| test::TestRequest::post().uri(uri).set_json(x).to_request();
+
+        let resp = test::call_service(&app, post("/inference", 2.0)).await;
+        assert_eq!(resp.headers().get(COLD_HEADER).unwrap(), "true");
+        let cold: PredictionEnvelope<f32> = test::read_body_json(resp).await;
+        assert_eq!(cold.prediction, 10.0);
+        assert_eq!(cold.warnings, vec![ResponseWarning::ColdModel]);
+
+        let resp = test::call_service(&app, post("/training", 3.0)).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let resp = test::call_service(&app, post("/inference", 2.0)).await;
+        assert!(resp.headers().get(COLD_HEADER).is_none());
+        let warm: PredictionEnvelope<f32> = test::read_body_json(resp).await;
+        assert_eq!((warm.prediction, warm.warnings.len()), (6.0, 0));
     }
 }
@@ -0,0 +1,205 @@
+use crate::algorithm::Algorithm;
+use crate::handlers::{error_response, AppState};
+use crate::registry::RegistryFor;
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::sync::Arc;
+
+/// Resolves the registry serving model `name`, or the 404 to return.
+fn registry_for<T, A>(
+    data: &AppState<T, A>,
+    name: &str,
+) -> Result<Arc<RegistryFor<T, A>>, HttpResponse>
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(registry) = &data.algorithms else {
+        return Err(HttpResponse::NotFound().body("algorithm registry is not enabled"));
+    };
+    if registry.model_name() != name {
+        return Err(HttpResponse::NotFound().body(format!("unknown model {}", name)));
+    }
+    Ok(registry.clone())
+}
+
+/// Handler listing the algorithms registered against model `name`.
+pub async fn handle_list_algorithms<T, A>(
+    data: web::Data<AppState<T, A>>,
+    path: web::Path<String>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    match registry_for(&data, &path) {
+        Ok(registry) => HttpResponse::Ok().json(registry.list()),
+        Err(response) => response,
+    }
+}
+
+/// Handler running an inference step of one named algorithm.
+pub async fn handle_named_inference<T, A>(
+    data: web::Data<AppState<T, A>>,
+    path: web::Path<(String, String)>,
+    input: web::Json<A::Input>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let (name, algorithm) = path.into_inner();
+    let registry = match registry_for(&data, &name) {
+        Ok(registry) => registry,
+        Err(response) => return response,
+    };
+    if registry.get(&algorithm).is_none() {
+        return HttpResponse::NotFound().body(format!("unknown algorithm {}", algorithm));
+    }
+    let model = data.model.clone();
+    let input = input.into_inner();
+    match tokio::task::spawn_blocking(move || registry.infer(&model, &algorithm, input)).await {
+        Ok(Ok(prediction)) => {
+            data.metrics.increment("named_inference_steps");
+            HttpResponse::Ok().json(prediction)
+        }
+        Ok(Err(e)) => error_response(&e),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Task failed: {:?}", e)),
+    }
+}
+
+/// Handler running a training step of one named trainer.
+pub async fn handle_named_training<T, A>(
+    data: web::Data<AppState<T, A>>,
+    path: web::Path<(String, String)>,
+    input: web::Json<A::Input>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let (name, algorithm) = path.into_inner();
+    let registry = match registry_for(&data, &name) {
+        Ok(registry) => registry,
+        Err(response) => return response,
+    };
+    if registry.get(&algorithm).is_none() {
+        return HttpResponse::NotFound().body(format!("unknown algorithm {}", algorithm));
+    }
+    if data.training_guard.as_ref().is_some_and(|g| g.is_paused()) {
+        return HttpResponse::ServiceUnavailable().body("training is paused by the guard");
+    }
+    let model = data.model.clone();
+    let input = input.into_inner();
+    match tokio::task::spawn_blocking(move || registry.train(&model, &algorithm, input)).await {
+        Ok(Ok(())) => {
+            data.metrics.increment("named_training_steps");
+            HttpResponse::Ok().finish()
+        }
+        Ok(Err(e)) => error_response(&e),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Task failed: {:?}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::{Algorithm, DummyAlgorithm};
+    use crate::errors::ModelError;
+    use crate::handlers::AppState;
+    use crate::model::Model;
+    use crate::registry::{AlgorithmInfo, AlgorithmRegistry, AlgorithmRole};
+    use crate::server::configure;
+    use actix_web::{http, test, web, App};
+
+    /// Adds the input to the first parameter.
+    struct Shift;
+
+    impl Algorithm<f32> for Shift {
+        type Input = f32;
+        type Output = f32;
+
+        fn training_step(&self, model: &Model<f32>, x: f32) -> Result<(), ModelError> {
+            model.write_params()[0] += x;
+            Ok(())
+        }
+
+        fn inference_step(&self, model: &Model<f32>, _x: f32) -> Result<f32, ModelError> {
+            Ok(model.read_params()[0])
+        }
+    }
+
+    /// Scores the input against the first parameter, never trains.
+    struct Residual;
+
+    impl Algorithm<f32> for Residual {
+        type Input = f32;
+        type Output = f32;
+
+        fn training_step(&self, _model: &Model<f32>, _x: f32) -> Result<(), ModelError> {
+            Ok(())
+        }
+
+        fn inference_step(&self, model: &Model<f32>, x: f32) -> Result<f32, ModelError> {
+            Ok(x - model.read_params()[0])
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_named_algorithms_share_the_model() {
+        let registry = AlgorithmRegistry::new("prices")
+            .with_algorithm("sgd", AlgorithmRole::Trainer, Shift)
+            .with_algorithm("residual", AlgorithmRole::Scorer, Residual);
+        let state = web::Data::new(
+            AppState::new(Model::with_parameters(vec![1.0f32, 2.0]), DummyAlgorithm)
+                .with_algorithms(registry),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .configure(configure::<f32, DummyAlgorithm>),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/models/prices/algorithms")
+            .to_request();
+        let listed: Vec<AlgorithmInfo> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(listed.len(), 2);
+
+        let req = test::TestRequest::post()
+            .uri("/models/prices/algorithms/sgd/training")
+            .set_json(1.0f32)
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            http::StatusCode::OK
+        );
+
+        let req = test::TestRequest::post()
+            .uri("/models/prices/algorithms/residual/inference")
+            .set_json(3.0f32)
+            .to_request();
+        let residual: f32 = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(residual, 1.0);
+
+        let req = test::TestRequest::post()
+            .uri("/models/prices/algorithms/residual/training")
+            .set_json(1.0f32)
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            http::StatusCode::BAD_REQUEST
+        );
+        let req = test::TestRequest::post()
+            .uri("/models/other/algorithms/sgd/inference")
+            .set_json(1.0f32)
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            http::StatusCode::NOT_FOUND
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use crate::algorithm::Algorithm;
+use crate::archive::ServerArchive;
+use crate::errors::ModelError;
+use crate::handlers::{error_response, AppState};
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Handler writing the whole server state as a single JSON archive.
+pub async fn handle_export<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    let state = data.clone(); // clone the Arc (not the state)
+    let task = tokio::task::spawn_blocking(move || {
+        let archive = ServerArchive::capture(&state)?;
+        Ok::<_, ModelError>(serde_json::to_vec(&archive)?)
+    });
+    match task.await {
+        Ok(Ok(bytes)) => {
+            data.metrics.increment("state_exports");
+            HttpResponse::Ok()
+                .content_type("application/json")
+                .body(bytes)
+        }
+        Ok(Err(e)) => error_response(&e),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Task failed: {:?}", e)),
+    }
+}
+
+/// Handler restoring an archive written by `GET /admin/export`.
+pub async fn handle_import<T, A>(
+    data: web::Data<AppState<T, A>>,
+    body: web::Bytes,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    let state = data.clone(); // clone the Arc (not the state)
+    let task = tokio::task::spawn_blocking(move || {
+        let archive: ServerArchive<T> = serde_json::from_slice(&body)
+            .map_err(|e| ModelError::InvalidInput(format!("invalid archive: {}", e)))?;
+        archive.restore(&state)
+    });
+    match task.await {
+        Ok(Ok(report)) => {
+            data.metrics.increment("state_imports");
+            HttpResponse::Ok().json(report)
+        }
+        Ok(Err(e)) => error_response(&e),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Task failed: {:?}", e)),
+    }
+}
@@ -0,0 +1,108 @@
+use crate::algorithm::Algorithm;
+use crate::handlers::{error_response, AppState};
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Handler reporting the state of the canary rollout.
+pub async fn handle_canary_status<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(canary) = &data.canary else {
+        return HttpResponse::NotFound().body("canary rollouts are not enabled");
+    };
+    match canary.status() {
+        Ok(status) => HttpResponse::Ok().json(status),
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler rolling back the running canary rollout, keeping the live parameters.
+pub async fn handle_abort_canary<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(canary) = &data.canary else {
+        return HttpResponse::NotFound().body("canary rollouts are not enabled");
+    };
+    match canary.abort("aborted by an operator") {
+        Ok(true) => {
+            data.metrics.increment("canary_rollbacks");
+            data.metrics.set_gauge("canary_fraction", 0.0);
+            match canary.status() {
+                Ok(status) => HttpResponse::Ok().json(status),
+                Err(e) => error_response(&e),
+            }
+        }
+        Ok(false) => HttpResponse::NotFound().body("no canary rollout is running"),
+        Err(e) => error_response(&e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canary::{CanaryConfig, RolloutStatus};
+    use crate::handlers::{AppState, PredictionEnvelope};
+    use crate::model::Model;
+    use crate::server::configure;
+    use crate::test::ScalingAlgorithm;
+    use actix_web::{test, web, App};
+
+    #[actix_rt::test]
+    async fn test_uploaded_weights_rolled_out_to_promotion() {
+        let state = AppState::new(Model::with_parameters(vec![1.0f32, 1.0]), ScalingAlgorithm)
+            .with_canary(CanaryConfig {
+                stages: vec![0.5, 1.0],
+                requests_per_stage: 2,
+                min_samples: u64::MAX,
+                ..CanaryConfig::default()
+            });
+        let model = state.model.clone();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(configure::<f32, ScalingAlgorithm>),
+        )
+        .await;
+
+        let upload = test::TestRequest::put()
+            .uri("/model/weights?canary=true")
+            .set_json(vec![2.0f32, 2.0])
+            .to_request();
+        let status: RolloutStatus = test::call_and_read_body_json(&app, upload).await;
+        assert_eq!(
+            status,
+            RolloutStatus::Running {
+                stage: 0,
+                fraction: 0.5
+            }
+        );
+
+        let mut predictions = Vec::new();
+        for _ in 0..5 {
+            let infer = test::TestRequest::post()
+                .uri("/inference")
+                .set_json(1.0f32)
+                .to_request();
+            let response: PredictionEnvelope<f32> =
+                test::call_and_read_body_json(&app, infer).await;
+            predictions.push(response.prediction);
+        }
+        // Every other request at 50%, then every request until promotion.
+        assert_eq!(predictions, vec![4.0, 2.0, 4.0, 4.0, 4.0]);
+        let status = test::TestRequest::get().uri("/admin/canary").to_request();
+        let status: RolloutStatus = test::call_and_read_body_json(&app, status).await;
+        assert_eq!(status, RolloutStatus::Promoted { version: 1 });
+        assert_eq!(model.read_params().as_slice(), &[2.0, 2.0]);
+
+        let abort = test::TestRequest::delete()
+            .uri("/admin/canary")
+            .to_request();
+        assert_eq!(test::call_service(&app, abort).await.status(), 404);
+    }
+}
@@ -0,0 +1,492 @@
+use crate::algorithm::Algorithm;
+use crate::catalog::ModelSpec;
+use crate::fallback::FALLBACK_HEADER;
+use crate::handlers::{
+    error_response, service_response, AppState, InferenceQuery, PredictionEnvelope, ResponseFormat,
+    ResponseWarning,
+};
+use crate::service::{InferenceRequest, OmlService};
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::time::Instant;
+
+/// Reply to a snapshot request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotCreated {
+    pub version: u64,
+}
+
+/// Handler freezing the current parameters of a catalog model as a new version.
+pub async fn handle_snapshot_model<T, A>(
+    data: web::Data<AppState<T, A>>,
+    name: web::Path<String>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(catalog) = &data.catalog else {
+        return HttpResponse::NotFound().body("model catalog is not enabled");
+    };
+    match catalog.snapshot(&name) {
+        Ok(version) => HttpResponse::Created().json(SnapshotCreated { version }),
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler listing the retained versions of a catalog model.
+pub async fn handle_model_versions<T, A>(
+    data: web::Data<AppState<T, A>>,
+    name: web::Path<String>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(catalog) = &data.catalog else {
+        return HttpResponse::NotFound().body("model catalog is not enabled");
+    };
+    match catalog.versions(&name) {
+        Ok(versions) => HttpResponse::Ok().json(versions),
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler reporting the lineage of a catalog model's versions.
+pub async fn handle_model_lineage<T, A>(
+    data: web::Data<AppState<T, A>>,
+    name: web::Path<String>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(catalog) = &data.catalog else {
+        return HttpResponse::NotFound().body("model catalog is not enabled");
+    };
+    match catalog.lineage(&name) {
+        Ok(lineage) => HttpResponse::Ok().json(lineage),
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler listing the catalog models.
+pub async fn handle_list_models<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(catalog) = &data.catalog else {
+        return HttpResponse::NotFound().body("model catalog is not enabled");
+    };
+    HttpResponse::Ok().json(catalog.names())
+}
+
+/// Handler creating a catalog model through the catalog's factory.
+pub async fn handle_create_model<T, A>(
+    data: web::Data<AppState<T, A>>,
+    name: web::Path<String>,
+    spec: web::Json<ModelSpec<T>>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(catalog) = &data.catalog else {
+        return HttpResponse::NotFound().body("model catalog is not enabled");
+    };
+    if catalog.contains(&name) {
+        return HttpResponse::Conflict().body(format!("model {} already exists", name));
+    }
+    match catalog.create(&name, &spec) {
+        Ok(()) => HttpResponse::Created().finish(),
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Body of `POST /models/{name}/fork`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ForkRequest {
+    /// Name of the copy, which must be free.
+    pub name: String,
+}
+
+/// Handler copying a catalog model into a new entry (see
+/// [`ModelCatalog::fork`]), to experiment on without touching it.
+///
+/// [`ModelCatalog::fork`]: crate::catalog::ModelCatalog::fork
+pub async fn handle_fork_model<T, A>(
+    data: web::Data<AppState<T, A>>,
+    name: web::Path<String>,
+    request: web::Json<ForkRequest>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(catalog) = &data.catalog else {
+        return HttpResponse::NotFound().body("model catalog is not enabled");
+    };
+    if !catalog.contains(&name) {
+        return HttpResponse::NotFound().body(format!("unknown model {}", name));
+    }
+    if catalog.contains(&request.name) {
+        return HttpResponse::Conflict().body(format!("model {} already exists", request.name));
+    }
+    match catalog.fork(&name, &request.name) {
+        Ok(()) => {
+            data.metrics.increment("model_forks");
+            HttpResponse::Created().finish()
+        }
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler deleting a catalog model.
+pub async fn handle_delete_model<T, A>(
+    data: web::Data<AppState<T, A>>,
+    name: web::Path<String>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(catalog) = &data.catalog else {
+        return HttpResponse::NotFound().body("model catalog is not enabled");
+    };
+    match catalog.remove(&name) {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().body(format!("unknown model {}", name)),
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler archiving a catalog model: it stops serving but keeps its
+/// versions and lineage.
+pub async fn handle_archive_model<T, A>(
+    data: web::Data<AppState<T, A>>,
+    name: web::Path<String>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(catalog) = &data.catalog else {
+        return HttpResponse::NotFound().body("model catalog is not enabled");
+    };
+    match catalog.archive(&name) {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().body(format!("unknown model {}", name)),
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler serving an archived catalog model again.
+pub async fn handle_restore_model<T, A>(
+    data: web::Data<AppState<T, A>>,
+    name: web::Path<String>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(catalog) = &data.catalog else {
+        return HttpResponse::NotFound().body("model catalog is not enabled");
+    };
+    match catalog.restore(&name) {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().body(format!("no archived model {}", name)),
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler listing the archived catalog models.
+pub async fn handle_archived_models<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(catalog) = &data.catalog else {
+        return HttpResponse::NotFound().body("model catalog is not enabled");
+    };
+    match catalog.archived() {
+        Ok(archived) => HttpResponse::Ok().json(archived),
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler training the live catalog model `name`.
+pub async fn handle_model_training<T, A>(
+    data: web::Data<AppState<T, A>>,
+    name: web::Path<String>,
+    input: web::Json<A::Input>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    match OmlService::from_shared(data.into_inner())
+        .train_model(&name, input.into_inner())
+        .await
+    {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(e) => service_response(&e),
+    }
+}
+
+/// Handler serving an inference request from the live catalog model `name`.
+pub async fn handle_model_inference<T, A>(
+    data: web::Data<AppState<T, A>>,
+    name: web::Path<String>,
+    query: web::Query<InferenceQuery>,
+    payload: web::Json<serde_json::Value>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    let input = match data.decode_input(Some(&name), payload.into_inner()) {
+        Ok(input) => input,
+        Err(e) => return error_response(&e),
+    };
+    let mut request = InferenceRequest::new(input);
+    request.explain = query.explain;
+    request.uncertainty = query.uncertainty;
+    request.ema = query.ema;
+    request.model = Some(name.clone());
+    let format = query.format.unwrap_or(data.response_format);
+    let started = Instant::now();
+    match OmlService::from_shared(data.into_inner())
+        .infer_or_fallback(request)
+        .await
+    {
+        Ok((response, fell_back)) => {
+            let mut ok = HttpResponse::Ok();
+            if fell_back {
+                ok.insert_header((FALLBACK_HEADER, "true"));
+            }
+            if format == ResponseFormat::Bare {
+                return ok.json(response);
+            }
+            let mut envelope = PredictionEnvelope::new(response, started.elapsed());
+            envelope.model = Some(name.into_inner());
+            if fell_back {
+                envelope.warnings.push(ResponseWarning::Fallback);
+            }
+            ok.json(envelope)
+        }
+        Err(e) => service_response(&e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::DummyAlgorithm;
+    use crate::catalog::{
+        ArchivedEntry, LineageRecord, ModelCatalog, ModelSpec, MODEL_HEADER, MODEL_VERSION_HEADER,
+    };
+    use crate::handlers::{AppState, PredictionEnvelope};
+    use crate::model::Model;
+    use crate::server::configure;
+    use crate::test::ScalingAlgorithm;
+    use actix_web::{http, test, web, App};
+    use serde_json::json;
+
+    #[actix_rt::test]
+    async fn test_headers_select_model_and_version() {
+        let catalog = ModelCatalog::new(5).with_model(
+            "canary",
+            Model::with_parameters(vec![10.0f32]),
+            DummyAlgorithm,
+        );
+        let state = web::Data::new(
+            AppState::new(Model::with_parameters(vec![1.0f32]), DummyAlgorithm)
+                .with_model_catalog("main", catalog),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .configure(configure::<f32, DummyAlgorithm>),
+        )
+        .await;
+        let infer = |headers: &[(&'static str, &'static str)]| {
+            let mut req = test::TestRequest::post().uri("/inference").set_json(1.0f32);
+            for header in headers {
+                req = req.insert_header(*header);
+            }
+            req.to_request()
+        };
+
+        let req = test::TestRequest::post()
+            .uri("/models/main/versions")
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            http::StatusCode::CREATED
+        );
+        state.model.write_params()[0] = 2.0;
+
+        let live: PredictionEnvelope<f32> = test::call_and_read_body_json(&app, infer(&[])).await;
+        assert_eq!((live.prediction, live.version), (2.0, Some(1)));
+        let canary: PredictionEnvelope<f32> =
+            test::call_and_read_body_json(&app, infer(&[(MODEL_HEADER, "canary")])).await;
+        assert_eq!(canary.prediction, 10.0);
+        assert_eq!(canary.model.as_deref(), Some("canary"));
+        let pinned: PredictionEnvelope<f32> = test::call_and_read_body_json(
+            &app,
+            infer(&[(MODEL_HEADER, "main"), (MODEL_VERSION_HEADER, "1")]),
+        )
+        .await;
+        assert_eq!((pinned.prediction, pinned.version), (1.0, Some(1)));
+
+        let resp = test::call_service(
+            &app,
+            infer(&[(MODEL_HEADER, "main"), (MODEL_VERSION_HEADER, "7")]),
+        )
+        .await;
+        assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn test_models_created_trained_archived_and_deleted_at_runtime() {
+        let catalog = ModelCatalog::new(5).with_factory(|spec: &ModelSpec<f32>| {
+            let parameters = spec.parameters.clone().unwrap_or_else(|| vec![1.0]);
+            Ok((Model::with_parameters(parameters), ScalingAlgorithm))
+        });
+        let state = AppState::new(Model::with_parameters(vec![1.0f32]), ScalingAlgorithm)
+            .with_model_catalog("main", catalog);
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(configure::<f32, ScalingAlgorithm>),
+        )
+        .await;
+        let post = |uri: &str, body: serde_json::Value| {
+            test::TestRequest::post()
+                .uri(uri)
+                .set_json(body)
+                .to_request()
+        };
+
+        let created = test::call_service(&app, post("/models/books", json!({"parameters": [2.0]})));
+        assert_eq!(created.await.status(), http::StatusCode::CREATED);
+        let again = test::call_service(&app, post("/models/books", json!({})));
+        assert_eq!(again.await.status(), http::StatusCode::CONFLICT);
+
+        let trained = test::call_service(&app, post("/models/books/training", json!(3.0)));
+        assert_eq!(trained.await.status(), http::StatusCode::OK);
+        let books: PredictionEnvelope<f32> =
+            test::call_and_read_body_json(&app, post("/models/books/inference", json!(1.0))).await;
+        let main: PredictionEnvelope<f32> =
+            test::call_and_read_body_json(&app, post("/inference", json!(1.0))).await;
+        assert_eq!((books.prediction, main.prediction), (6.0, 1.0));
+
+        let list = test::TestRequest::get().uri("/models").to_request();
+        let names: Vec<String> = test::call_and_read_body_json(&app, list).await;
+        assert_eq!(names, vec!["books", "main"]);
+
+        let trained = test::call_service(&app, post("/training", json!(1.0)));
+        assert_eq!(trained.await.status(), http::StatusCode::OK);
+        let lineage = |name: &str| {
+            test::TestRequest::get()
+                .uri(&format!("/models/{}/lineage", name))
+                .to_request()
+        };
+        let books: Vec<LineageRecord> = test::call_and_read_body_json(&app, lineage("books")).await;
+        let main: Vec<LineageRecord> = test::call_and_read_body_json(&app, lineage("main")).await;
+        assert_eq!(
+            (books[0].samples, books[0].source.as_deref()),
+            (1, Some("request"))
+        );
+        assert_eq!((main[0].samples, main[0].source.as_deref()), (1, None));
+
+        let archive = test::call_service(&app, post("/models/books/archive", json!(null)));
+        assert_eq!(archive.await.status(), http::StatusCode::NO_CONTENT);
+        let archived = test::TestRequest::get()
+            .uri("/models/archived")
+            .to_request();
+        let archived: Vec<ArchivedEntry> = test::call_and_read_body_json(&app, archived).await;
+        assert_eq!(archived[0].name, "books");
+        let hidden = test::call_service(&app, post("/models/books/inference", json!(1.0)));
+        assert_eq!(hidden.await.status(), http::StatusCode::NOT_FOUND);
+        let taken = test::call_service(&app, post("/models/books", json!({})));
+        assert_eq!(taken.await.status(), http::StatusCode::CONFLICT);
+        let restore = test::call_service(&app, post("/models/books/restore", json!(null)));
+        assert_eq!(restore.await.status(), http::StatusCode::NO_CONTENT);
+        let restored: PredictionEnvelope<f32> =
+            test::call_and_read_body_json(&app, post("/models/books/inference", json!(1.0))).await;
+        assert_eq!(restored.prediction, 6.0);
+
+        let delete = || {
+            test::TestRequest::delete()
+                .uri("/models/books")
+                .to_request()
+        };
+        let deleted = test::call_service(&app, delete()).await;
+        assert_eq!(deleted.status(), http::StatusCode::NO_CONTENT);
+        assert_eq!(
+            test::call_service(&app, delete()).await.status(),
+            http::StatusCode::NOT_FOUND
+        );
+        let gone = test::call_service(&app, post("/models/books/inference", json!(1.0)));
+        assert_eq!(gone.await.status(), http::StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn test_forked_model_trains_apart_from_its_source() {
+        let model = Model::with_parameters(vec![2.0f32, 1.0]).with_ema(0.5);
+        model.set_freeze_mask(vec![false, true]).unwrap();
+        let catalog = ModelCatalog::new(5);
+        let state = web::Data::new(
+            AppState::new(model, ScalingAlgorithm).with_model_catalog("main", catalog),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .configure(configure::<f32, ScalingAlgorithm>),
+        )
+        .await;
+        let post = |uri: &str, body: serde_json::Value| {
+            test::TestRequest::post()
+                .uri(uri)
+                .set_json(body)
+                .to_request()
+        };
+
+        let fork = test::call_service(&app, post("/models/main/fork", json!({"name": "trial"})));
+        assert_eq!(fork.await.status(), http::StatusCode::CREATED);
+        let taken = test::call_service(&app, post("/models/main/fork", json!({"name": "trial"})));
+        assert_eq!(taken.await.status(), http::StatusCode::CONFLICT);
+        let unknown = test::call_service(&app, post("/models/nope/fork", json!({"name": "x"})));
+        assert_eq!(unknown.await.status(), http::StatusCode::NOT_FOUND);
+
+        // Training the fork leaves the serving model alone, and the fork
+        // keeps its source's frozen parameters.
+        let trained = test::call_service(&app, post("/models/trial/training", json!(3.0)));
+        assert_eq!(trained.await.status(), http::StatusCode::OK);
+        let (trial, _) = state
+            .catalog
+            .as_ref()
+            .unwrap()
+            .resolve("trial", None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(trial.read_params().as_slice(), &[6.0, 1.0]);
+        assert_eq!(state.model.read_params().as_slice(), &[2.0, 1.0]);
+        assert!(trial.ema().is_some());
+        assert_eq!(trial.name(), Some("trial"));
+        assert_ne!(trial.metadata().id, state.model.metadata().id);
+
+        let lineage = test::TestRequest::get()
+            .uri("/models/trial/lineage")
+            .to_request();
+        let lineage: Vec<LineageRecord> = test::call_and_read_body_json(&app, lineage).await;
+        assert_eq!(
+            lineage[0].source.as_deref(),
+            Some("fork of main at parameters version 0")
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use crate::algorithm::Algorithm;
+use crate::handlers::{error_response, AppState};
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Reply to `POST /challenger/promote`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PromotionResponse {
+    /// `false` if the challenger had already been promoted.
+    pub promoted: bool,
+}
+
+/// Handler comparing the challenger with the serving model over the window.
+pub async fn handle_challenger_report<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(challenger) = &data.challenger else {
+        return HttpResponse::NotFound().body("no challenger is registered");
+    };
+    match challenger.report() {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler through which an operator approves the challenger's promotion.
+pub async fn handle_promote_challenger<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    if data.challenger.is_none() {
+        return HttpResponse::NotFound().body("no challenger is registered");
+    }
+    match data.promote_challenger() {
+        Ok(promoted) => HttpResponse::Ok().json(PromotionResponse { promoted }),
+        Err(e) => error_response(&e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::challenger::{ChallengerConfig, ChallengerReport};
+    use crate::delayed_labels::{DelayedLabelConfig, EVENT_ID_HEADER};
+    use crate::evaluation::{EvaluationRequest, LabeledSample};
+    use crate::handlers::AppState;
+    use crate::model::Model;
+    use crate::server::configure;
+    use crate::test::AdditiveAlgorithm;
+    use actix_web::{test, web, App};
+
+    #[actix_rt::test]
+    async fn test_challenger_trains_on_stream_and_is_auto_promoted() {
+        let state = web::Data::new(
+            AppState::new(
+                Model::<f32>::with_parameters(vec![0.0]),
+                AdditiveAlgorithm::new(0.0),
+            )
+            .with_challenger(
+                ChallengerConfig {
+                    min_samples: 5,
+                    z_threshold: 2.0,
+                    auto_promote: true,
+                    ..Default::default()
+                },
+                AdditiveAlgorithm::new(1.0),
+            )
+            .with_delayed_labels(DelayedLabelConfig::default()),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .configure(configure::<f32, AdditiveAlgorithm>),
+        )
+        .await;
+
+        // Only the challenger learns from this: its parameter becomes 1.
+        let req = test::TestRequest::post()
+            .uri("/training")
+            .set_json(1.0f32)
+            .to_request();
+        test::call_service(&app, req).await;
+        assert_eq!(state.model.read_params()[0], 0.0);
+
+        // An evaluation is not part of the live stream: nothing is compared.
+        let samples = (0..5)
+            .map(|_| LabeledSample {
+                input: 2.0f32,
+                label: 2.0,
+            })
+            .collect();
+        let req = test::TestRequest::post()
+            .uri("/evaluate")
+            .set_json(EvaluationRequest { samples })
+            .to_request();
+        test::call_service(&app, req).await;
+        let report = state.challenger.as_ref().unwrap().report().unwrap();
+        assert_eq!(report.samples, 0);
+
+        for i in 0..5 {
+            let event_id = format!("evt-{}", i);
+            let req = test::TestRequest::post()
+                .uri("/inference")
+                .insert_header((EVENT_ID_HEADER, event_id.as_str()))
+                .set_json(2.0f32)
+                .to_request();
+            test::call_service(&app, req).await;
+            let req = test::TestRequest::post()
+                .uri(&format!("/feedback/{}", event_id))
+                .set_json(2.0f32)
+                .to_request();
+            test::call_service(&app, req).await;
+        }
+
+        let req = test::TestRequest::get().uri("/challenger").to_request();
+        let report: ChallengerReport = test::call_and_read_body_json(&app, req).await;
+        assert!(report.challenger_wins);
+        assert!(report.promoted);
+        assert_eq!(state.model.read_params()[0], 1.0);
+        assert_eq!(state.metrics.counter("challenger_promotions"), 1);
+    }
+}
@@ -0,0 +1,88 @@
+use crate::algorithm::Algorithm;
+use crate::handlers::{error_response, AppState};
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Response of `POST /admin/checkpoint`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CheckpointReport {
+    pub version: u64,
+    /// Versions of the checkpoints kept on disk, oldest first.
+    pub retained: Vec<u64>,
+}
+
+/// Handler writing a checkpoint right away.
+pub async fn handle_checkpoint<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    let Some(checkpointer) = data.checkpointer.clone() else {
+        return HttpResponse::NotFound().body("checkpointing is not enabled");
+    };
+    let model = data.model.clone(); // clone the Arc (not the model)
+    let result = tokio::task::spawn_blocking(move || {
+        let version = checkpointer.save(&model)?;
+        Ok(CheckpointReport {
+            version,
+            retained: checkpointer.checkpoints()?,
+        })
+    })
+    .await;
+    match result {
+        Ok(Ok(report)) => {
+            data.metrics
+                .set_gauge("checkpoint_version", report.version as f64);
+            HttpResponse::Ok().json(report)
+        }
+        Ok(Err(e)) => error_response(&e),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Task failed: {:?}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::DummyAlgorithm;
+    use crate::checkpoint::{CheckpointConfig, Checkpointer};
+    use crate::model::Model;
+    use crate::server::configure;
+    use actix_web::{test, App};
+    use std::sync::Arc;
+
+    #[actix_rt::test]
+    async fn test_admin_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::new(Model::with_parameters(vec![1.0f32]), DummyAlgorithm);
+        let disabled = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(
+                    Model::with_parameters(vec![1.0f32]),
+                    DummyAlgorithm,
+                )))
+                .configure(configure::<f32, DummyAlgorithm>),
+        )
+        .await;
+        let request = test::TestRequest::post().uri("/admin/checkpoint");
+        let response = test::call_service(&disabled, request.to_request()).await;
+        assert_eq!(response.status(), 404);
+
+        let config = CheckpointConfig::new(dir.path());
+        state.checkpointer = Some(Arc::new(Checkpointer::new(config).unwrap()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(configure::<f32, DummyAlgorithm>),
+        )
+        .await;
+        for expected in 1..=2 {
+            let request = test::TestRequest::post().uri("/admin/checkpoint");
+            let report: CheckpointReport =
+                test::call_and_read_body_json(&app, request.to_request()).await;
+            assert_eq!(report.version, expected);
+        }
+    }
+}
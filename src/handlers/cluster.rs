@@ -0,0 +1,99 @@
+use crate::algorithm::Algorithm;
+use crate::cluster::Heartbeat;
+use crate::handlers::{error_response, AppState};
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Handler returning this instance's view of the cluster.
+pub async fn handle_cluster_state<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(membership) = &data.membership else {
+        return HttpResponse::NotFound().body("cluster membership is not enabled");
+    };
+    match membership.state() {
+        Ok(state) => HttpResponse::Ok().json(state),
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler recording a peer's heartbeat; replies with the local view so
+/// membership spreads transitively.
+pub async fn handle_heartbeat<T, A>(
+    data: web::Data<AppState<T, A>>,
+    heartbeat: web::Json<Heartbeat>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(membership) = &data.membership else {
+        return HttpResponse::NotFound().body("cluster membership is not enabled");
+    };
+    match membership.receive_heartbeat(&heartbeat) {
+        Ok(state) => HttpResponse::Ok().json(state),
+        Err(e) => error_response(&e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::DummyAlgorithm;
+    use crate::cluster::{ClusterState, Membership, MembershipConfig};
+    use crate::handlers::AppState;
+    use crate::model::Model;
+    use crate::server::configure;
+    use actix_web::{test, web, App, HttpServer};
+
+    #[actix_rt::test]
+    async fn test_members_discover_each_other() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let state = web::Data::new(
+            AppState::new(Model::<f32>::with_parameters(vec![0.0]), DummyAlgorithm)
+                .with_membership(MembershipConfig {
+                    node_id: "seed".to_string(),
+                    advertised_address: address.clone(),
+                    ..Default::default()
+                }),
+        );
+        let app_state = state.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(app_state.clone())
+                .configure(configure::<f32, DummyAlgorithm>)
+        })
+        .listen(listener)
+        .unwrap();
+        let running = server.run();
+        let handle = running.handle();
+        actix_rt::spawn(running);
+
+        let joiner = Membership::new(MembershipConfig {
+            node_id: "joiner".to_string(),
+            advertised_address: "http://joiner".to_string(),
+            seeds: vec![address.clone()],
+            ..Default::default()
+        });
+        assert_eq!(joiner.run_round().await, 1);
+        assert_eq!(joiner.live_peers(), vec![address.clone()]);
+
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .configure(configure::<f32, DummyAlgorithm>),
+        )
+        .await;
+        let req = test::TestRequest::get().uri("/cluster").to_request();
+        let cluster: ClusterState = test::call_and_read_body_json(&app, req).await;
+        let ids: Vec<&str> = cluster.members.iter().map(|m| m.node_id.as_str()).collect();
+        assert_eq!(ids, vec!["joiner", "seed"]);
+
+        handle.stop(true).await;
+    }
+}
@@ -0,0 +1,117 @@
+use crate::algorithm::Algorithm;
+use crate::consistency::{DeltaOutcome, VersionedDelta};
+use crate::handlers::{error_response, AppState};
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Handler returning the local version vector.
+pub async fn handle_consistency_clock<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(consistency) = &data.consistency else {
+        return HttpResponse::NotFound().body("version vectors are not enabled");
+    };
+    match consistency.clock() {
+        Ok(clock) => HttpResponse::Ok().json(clock),
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler applying a delta produced by another writer.
+///
+/// Conflicts are counted in `consistency_conflicts` (and
+/// `consistency_conflicts_discarded` when resolution dropped the delta) so
+/// divergence between instances is visible in `/metrics`.
+pub async fn handle_versioned_delta<T, A>(
+    data: web::Data<AppState<T, A>>,
+    delta: web::Json<VersionedDelta<T>>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(consistency) = &data.consistency else {
+        return HttpResponse::NotFound().body("version vectors are not enabled");
+    };
+    match consistency.receive(&data.model, &delta) {
+        Ok(outcome) => {
+            match outcome {
+                DeltaOutcome::Applied => data.metrics.increment("consistency_deltas_applied"),
+                DeltaOutcome::Stale => data.metrics.increment("consistency_deltas_stale"),
+                DeltaOutcome::Conflict { applied } => {
+                    data.metrics.increment("consistency_conflicts");
+                    if !applied {
+                        data.metrics.increment("consistency_conflicts_discarded");
+                    }
+                }
+            }
+            HttpResponse::Ok().json(outcome)
+        }
+        Err(e) => error_response(&e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::consistency::{ConflictResolution, ConsistencyTracker};
+    use crate::handlers::AppState;
+    use crate::metrics::Metrics;
+    use crate::model::Model;
+    use crate::server::configure;
+    use crate::test::ScalingAlgorithm;
+    use actix_web::{web, App, HttpServer};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[actix_rt::test]
+    async fn test_local_deltas_shipped_to_peers() {
+        let peer = web::Data::new(
+            AppState::new(Model::with_parameters(vec![1.0f64]), ScalingAlgorithm)
+                .with_consistency("b", ConflictResolution::LastWriterWins),
+        );
+        let app_state = peer.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(app_state.clone())
+                .configure(configure::<f64, ScalingAlgorithm>)
+        })
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let address = format!("http://{}", server.addrs()[0]);
+        let running = server.run();
+        let handle = running.handle();
+        actix_rt::spawn(running);
+
+        let tracker = Arc::new(ConsistencyTracker::new(
+            "a",
+            ConflictResolution::LastWriterWins,
+        ));
+        let metrics = Arc::new(Metrics::new());
+        let shipping = tracker
+            .clone()
+            .spawn(move || vec![address.clone()], metrics.clone());
+        let model = Model::with_parameters(vec![1.0f64]);
+        tracker
+            .record_local(&model, || {
+                model.write_params()[0] = 3.0;
+                Ok(())
+            })
+            .unwrap();
+
+        for _ in 0..500 {
+            if metrics.counter("consistency_deltas_shipped") == 1 {
+                break;
+            }
+            actix_rt::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(peer.model.read_params()[0], 3.0);
+        assert_eq!(peer.metrics.counter("consistency_deltas_applied"), 1);
+        shipping.abort();
+        handle.stop(true).await;
+    }
+}
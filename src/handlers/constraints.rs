@@ -0,0 +1,133 @@
+use crate::algorithm::Algorithm;
+use crate::constraints::ParameterConstraints;
+use crate::handlers::{error_response, AppState};
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Response of the constraint endpoints.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConstraintsReport {
+    pub constraints: ParameterConstraints,
+    /// Parameter values the constraints changed so far.
+    pub adjusted: u64,
+}
+
+fn report<T, A>(data: &AppState<T, A>) -> ConstraintsReport
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    ConstraintsReport {
+        constraints: data.model.constraints().as_ref().clone(),
+        adjusted: data.model.constrained_count(),
+    }
+}
+
+/// Handler reporting the constraints of the serving model.
+pub async fn handle_constraints_status<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    HttpResponse::Ok().json(report(&data))
+}
+
+/// Handler replacing the constraints of the serving model; an empty body
+/// lifts them.
+pub async fn handle_set_constraints<T, A>(
+    data: web::Data<AppState<T, A>>,
+    request: web::Json<ParameterConstraints>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    match data.model.set_constraints(request.into_inner()) {
+        Ok(_) => {
+            data.metrics.increment("constraint_changes");
+            HttpResponse::Ok().json(report(&data))
+        }
+        Err(e) => error_response(&e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::ModelLayout;
+    use crate::model::Model;
+    use crate::server::configure;
+    use crate::test::ScalingAlgorithm;
+    use actix_web::{test, App};
+    use serde_json::json;
+
+    #[actix_rt::test]
+    async fn test_outlier_sample_kept_within_bounds_and_norm() {
+        let layout = ModelLayout::new()
+            .group("weights", &[2])
+            .group("bias", &[1]);
+        let model = Model::with_layout(layout, vec![1.0f64, 1.0, 1.0]).unwrap();
+        let state = AppState::new(model, ScalingAlgorithm);
+        let model = state.model.clone();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(configure::<f64, ScalingAlgorithm>),
+        )
+        .await;
+        let constrain = |body: serde_json::Value| {
+            test::TestRequest::put()
+                .uri("/admin/constraints")
+                .set_json(body)
+                .to_request()
+        };
+        let train = |x: f64| {
+            test::TestRequest::post()
+                .uri("/training")
+                .set_json(x)
+                .to_request()
+        };
+
+        let body = json!({
+            "max_norm": 5.0,
+            "bounds": [{"groups": ["bias"], "min": -0.5, "max": 0.5}],
+        });
+        let set: ConstraintsReport = test::call_and_read_body_json(&app, constrain(body)).await;
+        assert_eq!(set.constraints.max_norm, Some(5.0));
+        assert_eq!(test::call_service(&app, train(1e6)).await.status(), 200);
+        let params = model.read_params().clone();
+        // The bias is clamped first, then scaled down with the weights.
+        assert!(params[2] > 0.0 && params[2] < 0.5);
+        assert_eq!(params[0], params[1]);
+        let norm = params.iter().map(|p| p * p).sum::<f64>().sqrt();
+        assert!((norm - 5.0).abs() < 1e-9);
+
+        let unknown = json!({"bounds": [{"groups": ["gamma"], "max": 1.0}]});
+        assert_eq!(
+            test::call_service(&app, constrain(unknown)).await.status(),
+            400
+        );
+        let empty = json!({"bounds": [{"indices": [0], "min": 1.0, "max": 0.0}]});
+        assert_eq!(
+            test::call_service(&app, constrain(empty)).await.status(),
+            400
+        );
+
+        // Lifting the constraints lets the next step through untouched.
+        let lifted: ConstraintsReport =
+            test::call_and_read_body_json(&app, constrain(json!({}))).await;
+        assert!(lifted.constraints.is_empty());
+        assert_eq!(lifted.adjusted, 3);
+        test::call_service(&app, train(10.0)).await;
+        let scaled: Vec<f64> = params.iter().map(|p| p * 10.0).collect();
+        assert_eq!(model.read_params().as_slice(), scaled.as_slice());
+        let status = test::TestRequest::get()
+            .uri("/admin/constraints")
+            .to_request();
+        let status: ConstraintsReport = test::call_and_read_body_json(&app, status).await;
+        assert_eq!(status.adjusted, 3);
+    }
+}
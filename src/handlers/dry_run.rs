@@ -0,0 +1,103 @@
+use crate::algorithm::Algorithm;
+use crate::dry_run::{preview_update, DryRunReport, MetricChange};
+use crate::handlers::{error_response, AppState};
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Handler computing the update a training sample would apply, without
+/// applying it.
+///
+/// The step runs against a copy of the live parameters with the serving
+/// algorithm. When a holdout dataset is registered, the copy is also scored
+/// on it to predict the metric change.
+pub async fn handle_dry_run<T, A>(
+    data: web::Data<AppState<T, A>>,
+    input: web::Json<A::Input>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    let state = data.clone(); // clone the Arc (not the state)
+    let input = input.into_inner();
+
+    let result = tokio::task::spawn_blocking(move || {
+        let algorithm = state.active_algorithm();
+        let (scratch, update) = preview_update(&state.model, algorithm.as_ref(), input)?;
+        let metric_change = match &state.holdout {
+            Some(holdout) => {
+                let before = holdout.evaluate(&state.model, algorithm.as_ref())?;
+                let after = holdout.evaluate(&scratch, algorithm.as_ref())?;
+                before.zip(after).map(|(before, after)| MetricChange {
+                    mae_before: before.mae,
+                    mae_after: after.mae,
+                })
+            }
+            None => None,
+        };
+        Ok(DryRunReport {
+            update,
+            metric_change,
+        })
+    })
+    .await;
+    match result {
+        Ok(Ok(report)) => {
+            data.metrics.increment("training_dry_runs");
+            HttpResponse::Ok().json(report)
+        }
+        Ok(Err(e)) => error_response(&e),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Task failed: {:?}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dry_run::DryRunReport;
+    use crate::evaluation::LabeledSample;
+    use crate::handlers::AppState;
+    use crate::holdout::HoldoutConfig;
+    use crate::model::Model;
+    use crate::server::configure;
+    use crate::test::AdditiveAlgorithm;
+    use actix_web::{test, web, App};
+
+    #[actix_rt::test]
+    async fn test_dry_run_predicts_holdout_change() {
+        let state = web::Data::new(
+            AppState::new(
+                Model::with_parameters(vec![1.0f32]),
+                AdditiveAlgorithm::default(),
+            )
+            .with_holdout(HoldoutConfig::default()),
+        );
+        state
+            .holdout
+            .as_ref()
+            .unwrap()
+            .set_dataset(vec![LabeledSample {
+                input: 1.0,
+                label: 2.0,
+            }])
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .configure(configure::<f32, AdditiveAlgorithm>),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/training/dry-run")
+            .set_json(3.0f32)
+            .to_request();
+        let report: DryRunReport<f32> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(report.update.delta, vec![3.0]);
+        let change = report.metric_change.unwrap();
+        assert_eq!((change.mae_before, change.mae_after), (1.0, 2.0));
+        assert_eq!(state.model.read_params()[0], 1.0);
+    }
+}
@@ -0,0 +1,101 @@
+use crate::algorithm::Algorithm;
+use crate::ensemble::{self, EnsembleSpec};
+use crate::evaluation::LabeledSample;
+use crate::handlers::{error_response, service_response, AppState};
+use crate::service::OmlService;
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Handler listing the ensembles with their learned meta-weights.
+pub async fn handle_list_ensembles<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    HttpResponse::Ok().json(data.ensembles.list())
+}
+
+/// Handler defining or redefining an ensemble over catalog models.
+pub async fn handle_define_ensemble<T, A>(
+    data: web::Data<AppState<T, A>>,
+    name: web::Path<String>,
+    spec: web::Json<EnsembleSpec>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(catalog) = &data.catalog else {
+        return HttpResponse::NotFound().body("model catalog is not enabled");
+    };
+    let spec = spec.into_inner();
+    if let Some(member) = spec.members.iter().find(|m| !catalog.contains(&m.model)) {
+        return HttpResponse::BadRequest().body(format!("unknown model {}", member.model));
+    }
+    match data.ensembles.define(name.as_str(), spec) {
+        Ok(()) => match data.ensembles.get(&name) {
+            Some(ensemble) => HttpResponse::Ok().json(ensemble.info(&name)),
+            None => HttpResponse::NotFound().body(format!("unknown ensemble {}", name)),
+        },
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler removing an ensemble; its members are left as they are.
+pub async fn handle_delete_ensemble<T, A>(
+    data: web::Data<AppState<T, A>>,
+    name: web::Path<String>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    match data.ensembles.remove(&name) {
+        true => HttpResponse::NoContent().finish(),
+        false => HttpResponse::NotFound().body(format!("unknown ensemble {}", name)),
+    }
+}
+
+/// Handler serving an inference request from an ensemble, with the
+/// predictions of its members.
+pub async fn handle_ensemble_inference<T, A>(
+    data: web::Data<AppState<T, A>>,
+    name: web::Path<String>,
+    payload: web::Json<serde_json::Value>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    let input = match data.decode_input(None, payload.into_inner()) {
+        Ok(input) => input,
+        Err(e) => return error_response(&e),
+    };
+    let service = OmlService::from_shared(data.into_inner());
+    match ensemble::infer(&service, &name, input).await {
+        Ok(prediction) => HttpResponse::Ok().json(prediction),
+        Err(e) => service_response(&e),
+    }
+}
+
+/// Handler updating the meta-weights of a stacking ensemble on a labeled
+/// sample.
+pub async fn handle_ensemble_feedback<T, A>(
+    data: web::Data<AppState<T, A>>,
+    name: web::Path<String>,
+    sample: web::Json<LabeledSample<A::Input, A::Output>>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    let sample = sample.into_inner();
+    let service = OmlService::from_shared(data.into_inner());
+    match ensemble::learn(&service, &name, sample.input, sample.label).await {
+        Ok(info) => HttpResponse::Ok().json(info),
+        Err(e) => service_response(&e),
+    }
+}
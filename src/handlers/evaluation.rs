@@ -0,0 +1,153 @@
+use crate::algorithm::Algorithm;
+use crate::evaluation::{evaluate, score, EvaluationRequest};
+use crate::handlers::{error_response, AppState};
+use crate::metrics::segments::SEGMENT_HEADER;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Handler scoring a labeled batch against the current model.
+///
+/// Only inference is run, so the model is never modified. The headline
+/// results of the latest evaluation are also kept as `evaluation_*` gauges,
+/// and every scored sample is recorded in the online metrics, sliced by the
+/// segment of an `X-Oml-Segment` header, if any. Nothing learns from the
+/// batch: the calibrator, the challenger and the canary only follow the
+/// live labeled stream, and the training guard only the scheduled holdout
+/// evaluation, since a client-chosen batch could pause or revert training.
+/// Scores are the raw model outputs, before calibration.
+pub async fn handle_evaluate<T, A>(
+    req: HttpRequest,
+    data: web::Data<AppState<T, A>>,
+    request: web::Json<EvaluationRequest<A::Input, A::Output>>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let state = data.clone(); // clone the Arc (not the state)
+    let samples = request.into_inner().samples;
+    let segment = req
+        .headers()
+        .get(SEGMENT_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+
+    let result = tokio::task::spawn_blocking(move || {
+        let pairs = score(state.active_algorithm().as_ref(), &state.model, &samples)?;
+        let report = evaluate(&pairs)?;
+        for (prediction, label) in pairs {
+            state.record_outcome(prediction, label, segment.as_deref());
+        }
+        Ok(report)
+    })
+    .await;
+    let report = match result {
+        Ok(Ok(report)) => report,
+        Ok(Err(e)) => return error_response(&e),
+        Err(e) => return HttpResponse::InternalServerError().body(format!("Task failed: {:?}", e)),
+    };
+
+    data.metrics.increment("evaluations");
+    data.metrics
+        .add("evaluation_samples", report.samples as u64);
+    data.metrics.set_gauge("evaluation_mae", report.mae);
+    data.metrics
+        .set_gauge("evaluation_accuracy", report.accuracy);
+    HttpResponse::Ok().json(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::DummyAlgorithm;
+    use crate::calibration::CalibrationMethod;
+    use crate::early_stopping::GuardConfig;
+    use crate::evaluation::{EvaluationReport, EvaluationRequest, LabeledSample};
+    use crate::handlers::AppState;
+    use crate::metrics::segments::SEGMENT_HEADER;
+    use crate::metrics::MetricsSnapshot;
+    use crate::model::Model;
+    use crate::server::configure;
+    use actix_web::{test, web, App};
+
+    #[actix_rt::test]
+    async fn test_evaluate_does_not_train() {
+        let state = web::Data::new(
+            AppState::new(
+                Model::<f32>::with_parameters(vec![0.5, 0.5]),
+                DummyAlgorithm,
+            )
+            .with_training_guard(GuardConfig::default())
+            .with_calibration(CalibrationMethod::default()),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .configure(configure::<f32, DummyAlgorithm>),
+        )
+        .await;
+        let req = test::TestRequest::post()
+            .uri("/evaluate")
+            .set_json(EvaluationRequest {
+                samples: vec![
+                    LabeledSample {
+                        input: 1.0f32,
+                        label: 1.0,
+                    },
+                    LabeledSample {
+                        input: 3.0,
+                        label: 2.0,
+                    },
+                ],
+            })
+            .to_request();
+        let report: EvaluationReport = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(report.samples, 2);
+        assert_eq!(report.mae, 0.5);
+        assert_eq!(report.accuracy, 0.5);
+        assert_eq!(state.model.read_params().clone(), vec![0.5, 0.5]);
+        assert_eq!(state.metrics.counter("evaluation_samples"), 2);
+        let classification = state.metrics.snapshot().classification.unwrap();
+        assert_eq!(classification.cumulative.matrix[&2][&3], 1);
+        let calibrator = state.calibrator.as_ref().unwrap().lock().unwrap();
+        assert_eq!(calibrator.observations(), 0);
+        // Only the scheduled holdout evaluation drives the guard.
+        let guard = state.training_guard.as_ref().unwrap().status().unwrap();
+        assert_eq!(guard.best_mae, None);
+    }
+
+    #[actix_rt::test]
+    async fn test_evaluation_sliced_by_segment() {
+        let state = web::Data::new(AppState::new(
+            Model::<f32>::with_parameters(vec![0.5, 0.5]),
+            DummyAlgorithm,
+        ));
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .configure(configure::<f32, DummyAlgorithm>),
+        )
+        .await;
+        for (segment, label) in [("mobile", 1.0f32), ("desktop", 3.0)] {
+            let req = test::TestRequest::post()
+                .uri("/evaluate")
+                .insert_header((SEGMENT_HEADER, segment))
+                .set_json(EvaluationRequest {
+                    samples: vec![LabeledSample {
+                        input: 1.0f32,
+                        label,
+                    }],
+                })
+                .to_request();
+            assert!(test::call_service(&app, req).await.status().is_success());
+        }
+
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let metrics: MetricsSnapshot = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(metrics.segments["mobile"].mae, Some(0.0));
+        assert_eq!(metrics.segments["desktop"].mae, Some(2.0));
+        assert_eq!(metrics.segments["desktop"].samples, 1);
+    }
+}
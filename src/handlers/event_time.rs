@@ -0,0 +1,43 @@
+use crate::algorithm::Algorithm;
+use crate::handlers::AppState;
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Body of the watermark endpoints.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WatermarkStatus {
+    /// Event time behind which samples are late, once one has been seen.
+    pub watermark_ms: Option<u64>,
+}
+
+/// Handler reporting the watermark of the event clock.
+pub async fn handle_watermark<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(clock) = &data.event_clock else {
+        return HttpResponse::NotFound().body("event time is not enabled");
+    };
+    HttpResponse::Ok().json(WatermarkStatus {
+        watermark_ms: clock.watermark_ms(),
+    })
+}
+
+/// Handler forgetting the watermark, e.g. after a client sent event times
+/// from a skewed clock or before replaying older data.
+pub async fn handle_reset_watermark<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(clock) = &data.event_clock else {
+        return HttpResponse::NotFound().body("event time is not enabled");
+    };
+    clock.reset();
+    data.metrics.set_gauge("event_time_watermark_ms", 0.0);
+    HttpResponse::Ok().json(WatermarkStatus { watermark_ms: None })
+}
@@ -0,0 +1,73 @@
+use crate::algorithm::Algorithm;
+use crate::handlers::{error_response, AppState};
+use crate::secure_aggregation::{MaskedUpdate, SecureAggregator};
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Takes the clients of a round that has not started yet from the live
+/// members announcing a client id, if any do.
+fn follow_membership<T, A>(data: &AppState<T, A>, aggregator: &mut SecureAggregator)
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(membership) = &data.membership else {
+        return;
+    };
+    let clients = membership.live_clients();
+    if !clients.is_empty() {
+        aggregator.set_clients(clients);
+    }
+}
+
+/// Handler reporting the progress of the current secure-aggregation round.
+pub async fn handle_federation_status<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(aggregator) = &data.secure_aggregation else {
+        return HttpResponse::NotFound().body("secure aggregation is not enabled");
+    };
+    match aggregator.lock() {
+        Ok(mut aggregator) => {
+            follow_membership(&data, &mut aggregator);
+            HttpResponse::Ok().json(aggregator.status())
+        }
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// Handler accepting one client's masked update for the current round.
+pub async fn handle_masked_update<T, A>(
+    data: web::Data<AppState<T, A>>,
+    update: web::Json<MaskedUpdate>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(aggregator) = &data.secure_aggregation else {
+        return HttpResponse::NotFound().body("secure aggregation is not enabled");
+    };
+    let result = match aggregator.lock() {
+        Ok(mut aggregator) => {
+            follow_membership(&data, &mut aggregator);
+            aggregator.submit(&data.model, &update)
+        }
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    match result {
+        Ok(status) => {
+            data.metrics.increment("federation_updates_received");
+            if status.completed {
+                data.metrics.increment("federation_rounds_completed");
+            }
+            HttpResponse::Ok().json(status)
+        }
+        Err(e) => error_response(&e),
+    }
+}
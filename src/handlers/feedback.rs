@@ -0,0 +1,321 @@
+use crate::algorithm::{Algorithm, Datum};
+use crate::handlers::{error_response, AppState};
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Body of `POST /feedback`: an outcome for either a recorded event or a raw
+/// context.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeedbackRequest<I, O = I> {
+    /// Event id sent with the inference request (`X-Oml-Event-Id`).
+    pub event_id: Option<String>,
+    /// Input the outcome refers to, for clients that did not record an event.
+    pub context: Option<I>,
+    /// Observed label or reward.
+    pub outcome: O,
+}
+
+/// Response body of the feedback endpoints.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeedbackResponse<O> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_id: Option<String>,
+    /// Raw model output recorded at inference time, when the outcome was
+    /// joined with an event.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prediction: Option<O>,
+    pub label: O,
+    /// Absolute difference between prediction and label.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<f64>,
+    /// Set when the outcome was also used to train the model.
+    pub trained: bool,
+}
+
+/// Handler joining the true label of an event with the prediction served
+/// for it.
+///
+/// The pair feeds the online metrics, the calibrator and the challenger like
+/// an evaluated sample. With `train_on_feedback` the algorithm also learns
+/// from it through [`Algorithm::feedback_step`], unless the training guard
+/// has paused training. The prediction stays pending until the label was
+/// applied, so a label that failed can be posted again.
+pub async fn handle_feedback<T, A>(
+    data: web::Data<AppState<T, A>>,
+    event_id: web::Path<String>,
+    label: web::Json<A::Output>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    let Some(pending) = &data.pending_predictions else {
+        return HttpResponse::NotFound().body("delayed labels are not enabled");
+    };
+    let train = pending.config().train_on_feedback
+        && !data.training_guard.as_ref().is_some_and(|g| g.is_paused());
+    apply_feedback(
+        data,
+        Some(event_id.into_inner()),
+        None,
+        label.into_inner(),
+        train,
+    )
+    .await
+}
+
+/// Handler routing a reward or outcome signal to the algorithm's
+/// [`Algorithm::feedback_step`], for algorithms learning from delayed
+/// rewards (bandits, delayed-label classifiers).
+///
+/// The outcome refers either to an `event_id` recorded at inference time,
+/// in which case it is also joined with the served prediction, or to a raw
+/// `context`. Unlike `POST /feedback/{id}` the outcome is always learned
+/// from; algorithms without feedback support answer 501.
+pub async fn handle_outcome<T, A>(
+    data: web::Data<AppState<T, A>>,
+    request: web::Json<FeedbackRequest<A::Input, A::Output>>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    if data.training_guard.as_ref().is_some_and(|g| g.is_paused()) {
+        return HttpResponse::ServiceUnavailable().body("training is paused by the guard");
+    }
+    let request = request.into_inner();
+    match (&request.event_id, &request.context) {
+        (Some(_), None) if data.pending_predictions.is_none() => {
+            HttpResponse::NotFound().body("delayed labels are not enabled")
+        }
+        (Some(_), None) | (None, Some(_)) => {
+            apply_feedback(
+                data,
+                request.event_id,
+                request.context,
+                request.outcome,
+                true,
+            )
+            .await
+        }
+        _ => HttpResponse::BadRequest().body("exactly one of event_id and context is required"),
+    }
+}
+
+/// Applies `label` to the prediction recorded for `event_id`, or to the raw
+/// `context` when no event is given.
+async fn apply_feedback<T, A>(
+    data: web::Data<AppState<T, A>>,
+    event_id: Option<String>,
+    context: Option<A::Input>,
+    label: A::Output,
+    train: bool,
+) -> HttpResponse
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    // The recorded prediction stays pending until the label is applied, so
+    // a failed step can be retried with the same event id.
+    let recorded = match (&event_id, &data.pending_predictions) {
+        (Some(event_id), Some(pending)) => match pending.claim(event_id) {
+            Ok(Some(recorded)) => Some(recorded),
+            Ok(None) => {
+                data.metrics.increment("feedback_unmatched");
+                return HttpResponse::NotFound()
+                    .body(format!("no pending prediction for {}", event_id));
+            }
+            Err(e) => return error_response(&e),
+        },
+        _ => None,
+    };
+    let (input, prediction, segment) = match recorded.clone() {
+        Some(recorded) => (recorded.input, Some(recorded.prediction), recorded.segment),
+        None => match context {
+            Some(context) => (context, None, None),
+            None => {
+                return HttpResponse::BadRequest().body("feedback needs an event id or a context")
+            }
+        },
+    };
+    let scores = prediction.as_ref().map(|p| {
+        (
+            p.score().unwrap_or(f64::NAN),
+            label.score().unwrap_or(f64::NAN),
+        )
+    });
+
+    let state = data.clone(); // clone the Arc (not the state)
+    let outcome = label.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        if train {
+            let algorithm = state.active_algorithm();
+            state
+                .model
+                .logged_step(|| algorithm.feedback_step(&state.model, input.clone(), outcome))?;
+        }
+        if let Some((prediction, label)) = scores {
+            state.observe_outcome(input, prediction, label, segment.as_deref());
+        }
+        Ok(())
+    })
+    .await;
+    if let (Some(event_id), Some(pending), Some(recorded)) =
+        (&event_id, &data.pending_predictions, &recorded)
+    {
+        let _ = match &result {
+            Ok(Ok(())) => pending.complete(event_id, recorded),
+            _ => pending.release(event_id),
+        };
+        data.metrics
+            .set_gauge("pending_predictions", pending.len() as f64);
+    }
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => return error_response(&e),
+        Err(e) => return HttpResponse::InternalServerError().body(format!("Task failed: {:?}", e)),
+    }
+
+    data.metrics.increment("feedback_received");
+    if train {
+        data.metrics.increment("feedback_training_steps");
+    }
+    HttpResponse::Ok().json(FeedbackResponse {
+        event_id,
+        prediction,
+        label,
+        error: scores
+            .map(|(prediction, label)| (prediction - label).abs())
+            .filter(|error| error.is_finite()),
+        trained: train,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delayed_labels::{DelayedLabelConfig, EVENT_ID_HEADER};
+    use crate::model::Model;
+    use crate::server::configure;
+    use crate::test::{AdditiveAlgorithm, LastValueAlgorithm};
+    use actix_web::{test, App};
+
+    #[actix_rt::test]
+    async fn test_feedback_joins_recorded_prediction() {
+        let state = web::Data::new(
+            AppState::new(Model::with_parameters(vec![0.25f32]), LastValueAlgorithm)
+                .with_delayed_labels(DelayedLabelConfig {
+                    train_on_feedback: true,
+                    ..Default::default()
+                }),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .configure(configure::<f32, LastValueAlgorithm>),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/inference")
+            .insert_header((EVENT_ID_HEADER, "evt-1"))
+            .set_json(3.0f32)
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::post()
+            .uri("/feedback/evt-1")
+            .set_json(1.0f32)
+            .to_request();
+        let response: FeedbackResponse<f32> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(response.prediction, Some(0.25));
+        assert_eq!(response.error, Some(0.75));
+        assert!(response.trained);
+        assert_eq!(state.model.read_params()[0], 1.0);
+
+        // The pending prediction is consumed by the first label.
+        let req = test::TestRequest::post()
+            .uri("/feedback/evt-1")
+            .set_json(1.0f32)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 404);
+    }
+
+    #[actix_rt::test]
+    async fn test_failed_feedback_keeps_prediction_pending() {
+        // The algorithm does not learn from feedback, so every step fails.
+        let state = web::Data::new(
+            AppState::new(
+                Model::with_parameters(vec![0.25f32]),
+                AdditiveAlgorithm::default(),
+            )
+            .with_delayed_labels(DelayedLabelConfig {
+                train_on_feedback: true,
+                ..Default::default()
+            }),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .configure(configure::<f32, AdditiveAlgorithm>),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/inference")
+            .insert_header((EVENT_ID_HEADER, "evt-1"))
+            .set_json(3.0f32)
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        for _ in 0..2 {
+            let req = test::TestRequest::post()
+                .uri("/feedback/evt-1")
+                .set_json(1.0f32)
+                .to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), 501);
+        }
+        assert_eq!(state.pending_predictions.as_ref().unwrap().len(), 1);
+        let counters = state.metrics.snapshot().counters;
+        assert!(!counters.contains_key("feedback_received"));
+    }
+
+    #[actix_rt::test]
+    async fn test_outcome_for_raw_context() {
+        let state = web::Data::new(AppState::new(
+            Model::with_parameters(vec![0.25f32]),
+            LastValueAlgorithm,
+        ));
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .configure(configure::<f32, LastValueAlgorithm>),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/feedback")
+            .set_json(FeedbackRequest {
+                event_id: None,
+                context: Some(3.0f32),
+                outcome: 2.0,
+            })
+            .to_request();
+        let response: FeedbackResponse<f32> = test::call_and_read_body_json(&app, req).await;
+        assert!(response.trained);
+        assert_eq!(response.prediction, None);
+        assert_eq!(state.model.read_params()[0], 2.0);
+
+        let req = test::TestRequest::post()
+            .uri("/feedback")
+            .set_json(FeedbackRequest::<f32> {
+                event_id: None,
+                context: None,
+                outcome: 2.0,
+            })
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::algorithm::Algorithm;
+use crate::handlers::{error_response, AppState};
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Body of `PUT /admin/freeze`; an empty body unfreezes every parameter.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FreezeRequest {
+    /// Layout groups to freeze.
+    #[serde(default)]
+    pub groups: Vec<String>,
+    /// Further parameters to freeze.
+    #[serde(default)]
+    pub indices: Vec<usize>,
+}
+
+/// Response of the freeze endpoints.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FreezeReport {
+    pub frozen: usize,
+    pub trainable: usize,
+}
+
+fn report<T, A>(data: &AppState<T, A>) -> FreezeReport
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    let frozen = data.model.frozen_count();
+    FreezeReport {
+        frozen,
+        trainable: data.model.read_params().len() - frozen,
+    }
+}
+
+/// Handler reporting how many parameters of the serving model are frozen.
+pub async fn handle_freeze_status<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    HttpResponse::Ok().json(report(&data))
+}
+
+/// Handler replacing the set of frozen parameters of the serving model.
+pub async fn handle_set_freeze<T, A>(
+    data: web::Data<AppState<T, A>>,
+    request: web::Json<FreezeRequest>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    match data.model.freeze(&request.groups, &request.indices) {
+        Ok(_) => {
+            data.metrics.increment("freeze_changes");
+            HttpResponse::Ok().json(report(&data))
+        }
+        Err(e) => error_response(&e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::ModelLayout;
+    use crate::model::Model;
+    use crate::server::configure;
+    use crate::test::ScalingAlgorithm;
+    use actix_web::{test, App};
+    use serde_json::json;
+
+    #[actix_rt::test]
+    async fn test_frozen_bias_left_alone_by_training() {
+        let layout = ModelLayout::new()
+            .group("weights", &[2])
+            .group("bias", &[1]);
+        let model = Model::with_layout(layout, vec![1.0f32, 1.0, 1.0]).unwrap();
+        let state = AppState::new(model, ScalingAlgorithm);
+        let model = state.model.clone();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(configure::<f32, ScalingAlgorithm>),
+        )
+        .await;
+        let freeze = |body: serde_json::Value| {
+            test::TestRequest::put()
+                .uri("/admin/freeze")
+                .set_json(body)
+                .to_request()
+        };
+
+        let frozen: FreezeReport =
+            test::call_and_read_body_json(&app, freeze(json!({"groups": ["bias"]}))).await;
+        assert_eq!((frozen.frozen, frozen.trainable), (1, 2));
+        let train = test::TestRequest::post()
+            .uri("/training")
+            .set_json(3.0f32)
+            .to_request();
+        assert_eq!(test::call_service(&app, train).await.status(), 200);
+        assert_eq!(model.read_params().as_slice(), &[3.0, 3.0, 1.0]);
+
+        let unknown = test::call_service(&app, freeze(json!({"groups": ["gamma"]}))).await;
+        assert_eq!(unknown.status(), 400);
+        let cleared: FreezeReport = test::call_and_read_body_json(&app, freeze(json!({}))).await;
+        assert_eq!(cleared.frozen, 0);
+        let status = test::TestRequest::get().uri("/admin/freeze").to_request();
+        let status: FreezeReport = test::call_and_read_body_json(&app, status).await;
+        assert_eq!(status.trainable, 3);
+    }
+}
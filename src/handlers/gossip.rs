@@ -0,0 +1,81 @@
+use crate::algorithm::Algorithm;
+use crate::gossip::GossipMessage;
+use crate::handlers::{error_response, AppState};
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Handler for the receiving side of a push-pull gossip exchange.
+///
+/// Replies with this node's parameters as they were *before* merging the
+/// incoming message, so both peers apply the same symmetric update.
+pub async fn handle_gossip_exchange<T, A>(
+    data: web::Data<AppState<T, A>>,
+    message: web::Json<GossipMessage<T>>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(gossip) = &data.gossip else {
+        return HttpResponse::NotFound().body("gossip is not enabled");
+    };
+    let reply = gossip.message(&data.model);
+    match gossip.merge(&data.model, &message) {
+        Ok(()) => {
+            data.metrics.increment("gossip_exchanges_received");
+            HttpResponse::Ok().json(reply)
+        }
+        Err(e) => error_response(&e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::DummyAlgorithm;
+    use crate::gossip::{GossipConfig, PeerSync};
+    use crate::handlers::AppState;
+    use crate::model::Model;
+    use crate::server::configure;
+    use actix_web::{web, App, HttpServer};
+
+    #[actix_rt::test]
+    async fn test_round_converges_two_replicas() {
+        let state = web::Data::new(
+            AppState::new(
+                Model::<f32>::with_parameters(vec![4.0, 0.0]),
+                DummyAlgorithm,
+            )
+            .with_gossip(GossipConfig::default()),
+        );
+        let app_state = state.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(app_state.clone())
+                .configure(configure::<f32, DummyAlgorithm>)
+        })
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let address = server.addrs()[0];
+        let running = server.run();
+        let handle = running.handle();
+        actix_rt::spawn(running);
+
+        let local = Model::<f32>::with_parameters(vec![0.0, 2.0]);
+        let sync = PeerSync::new(GossipConfig {
+            node_id: "local".to_string(),
+            peers: vec![format!("http://{}", address)],
+            ..Default::default()
+        });
+        let report = sync.run_round(&local).await;
+        assert_eq!(report.merged, 1);
+
+        let expected = vec![2.0, 1.0];
+        assert_eq!(local.read_params().clone(), expected);
+        assert_eq!(state.model.read_params().clone(), expected);
+
+        handle.stop(true).await;
+    }
+}
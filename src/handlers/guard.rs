@@ -0,0 +1,37 @@
+use crate::algorithm::Algorithm;
+use crate::handlers::{error_response, AppState};
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Handler reporting the state of the training guard.
+pub async fn handle_guard_status<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(guard) = &data.training_guard else {
+        return HttpResponse::NotFound().body("training guard is not enabled");
+    };
+    match guard.status() {
+        Ok(status) => HttpResponse::Ok().json(status),
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler re-enabling training after the guard paused it.
+pub async fn handle_resume_training<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(guard) = &data.training_guard else {
+        return HttpResponse::NotFound().body("training guard is not enabled");
+    };
+    match guard.resume().and_then(|_| guard.status()) {
+        Ok(status) => HttpResponse::Ok().json(status),
+        Err(e) => error_response(&e),
+    }
+}
@@ -0,0 +1,47 @@
+use crate::algorithm::Algorithm;
+use crate::evaluation::EvaluationRequest;
+use crate::handlers::{error_response, AppState};
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Reply to a holdout upload.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HoldoutRegistered {
+    pub samples: usize,
+}
+
+/// Handler registering (or replacing) the holdout dataset.
+pub async fn handle_set_holdout<T, A>(
+    data: web::Data<AppState<T, A>>,
+    request: web::Json<EvaluationRequest<A::Input, A::Output>>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(holdout) = &data.holdout else {
+        return HttpResponse::NotFound().body("holdout evaluation is not enabled");
+    };
+    match holdout.set_dataset(request.into_inner().samples) {
+        Ok(samples) => HttpResponse::Ok().json(HoldoutRegistered { samples }),
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler returning the history of scheduled holdout evaluations, oldest first.
+pub async fn handle_holdout_history<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(holdout) = &data.holdout else {
+        return HttpResponse::NotFound().body("holdout evaluation is not enabled");
+    };
+    match holdout.history() {
+        Ok(history) => HttpResponse::Ok().json(history),
+        Err(e) => error_response(&e),
+    }
+}
@@ -0,0 +1,194 @@
+use crate::algorithm::Algorithm;
+use crate::handlers::{error_response, AppState};
+use crate::orchestrator::{train_shard, BatchJobRequest, ShardRequest, ShardResult};
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Body of `POST /jobs/workers`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkerRegistration {
+    pub address: String,
+}
+
+/// Handler sharding an uploaded batch across the workers; answers `202 Accepted`
+/// with the initial job status.
+pub async fn handle_submit_job<T, A>(
+    data: web::Data<AppState<T, A>>,
+    job: web::Json<BatchJobRequest<A::Input>>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(orchestrator) = &data.orchestrator else {
+        return HttpResponse::NotFound().body("batch orchestration is not enabled");
+    };
+    match orchestrator.submit(data.model.clone(), job.into_inner().inputs) {
+        Ok(status) => {
+            data.metrics.increment("jobs_submitted");
+            HttpResponse::Accepted().json(status)
+        }
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler listing every job and its status.
+pub async fn handle_list_jobs<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(orchestrator) = &data.orchestrator else {
+        return HttpResponse::NotFound().body("batch orchestration is not enabled");
+    };
+    match orchestrator.jobs() {
+        Ok(jobs) => HttpResponse::Ok().json(jobs),
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler returning the consolidated status of one job.
+pub async fn handle_job_status<T, A>(
+    data: web::Data<AppState<T, A>>,
+    id: web::Path<u64>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(orchestrator) = &data.orchestrator else {
+        return HttpResponse::NotFound().body("batch orchestration is not enabled");
+    };
+    match orchestrator.job(*id) {
+        Ok(Some(status)) => HttpResponse::Ok().json(status),
+        Ok(None) => HttpResponse::NotFound().body(format!("job {} does not exist", id)),
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler registering a worker instance with the orchestrator.
+pub async fn handle_register_worker<T, A>(
+    data: web::Data<AppState<T, A>>,
+    registration: web::Json<WorkerRegistration>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(orchestrator) = &data.orchestrator else {
+        return HttpResponse::NotFound().body("batch orchestration is not enabled");
+    };
+    match orchestrator.register_worker(&registration.address) {
+        Ok(workers) => HttpResponse::Ok().json(workers),
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Worker-side handler training one shard on a copy of the shipped parameters.
+pub async fn handle_train_shard<T, A>(
+    data: web::Data<AppState<T, A>>,
+    request: web::Json<ShardRequest<T, A::Input>>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let algorithm = data.algorithm.clone(); // clone the Arc (not the algo)
+    let request = request.into_inner();
+
+    match tokio::task::spawn_blocking(move || {
+        train_shard(algorithm.as_ref(), &request.parameters, &request.inputs).map(|delta| {
+            ShardResult {
+                job_id: request.job_id,
+                shard: request.shard,
+                delta,
+            }
+        })
+    })
+    .await
+    {
+        Ok(Ok(result)) => {
+            data.metrics.increment("job_shards_trained");
+            HttpResponse::Ok().json(result)
+        }
+        Ok(Err(e)) => error_response(&e),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Task failed: {:?}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::handlers::AppState;
+    use crate::model::Model;
+    use crate::orchestrator::{BatchJobRequest, JobState, JobStatus};
+    use crate::server::configure;
+    use crate::test::AdditiveAlgorithm;
+    use actix_web::{http, test, web, App, HttpServer};
+    use std::time::Duration;
+
+    #[actix_rt::test]
+    async fn test_job_is_sharded_and_deltas_averaged() {
+        let worker = web::Data::new(AppState::new(
+            Model::<f32>::with_parameters(vec![100.0]),
+            AdditiveAlgorithm::default(),
+        ));
+        let worker_state = worker.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(worker_state.clone())
+                .configure(configure::<f32, AdditiveAlgorithm>)
+        })
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let address = format!("http://{}", server.addrs()[0]);
+        let running = server.run();
+        let handle = running.handle();
+        actix_rt::spawn(running);
+
+        // The same worker registered twice gets two shards.
+        let orchestrator = web::Data::new(
+            AppState::new(
+                Model::<f32>::with_parameters(vec![0.0]),
+                AdditiveAlgorithm::default(),
+            )
+            .with_orchestrator(vec![address.clone(), format!("{}/", address)]),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(orchestrator.clone())
+                .configure(configure::<f32, AdditiveAlgorithm>),
+        )
+        .await;
+        let req = test::TestRequest::post()
+            .uri("/jobs")
+            .set_json(BatchJobRequest {
+                inputs: vec![1.0f32, 2.0, 3.0, 4.0],
+            })
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::ACCEPTED);
+        let submitted: JobStatus = test::read_body_json(resp).await;
+        assert_eq!(submitted.shards.len(), 2);
+
+        let mut status = submitted;
+        for _ in 0..50 {
+            let req = test::TestRequest::get()
+                .uri(&format!("/jobs/{}", status.id))
+                .to_request();
+            status = test::call_and_read_body_json(&app, req).await;
+            if status.state != JobState::Running {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(status.state, JobState::Completed);
+        // Shards [1, 2] and [3, 4] give deltas 3 and 7; their mean is applied.
+        assert_eq!(orchestrator.model.read_params().clone(), vec![5.0]);
+        assert_eq!(worker.model.read_params().clone(), vec![100.0]);
+
+        handle.stop(true).await;
+    }
+}
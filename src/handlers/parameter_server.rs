@@ -0,0 +1,124 @@
+use crate::algorithm::Algorithm;
+use crate::handlers::{error_response, AppState};
+use crate::parameter_server::GradientPush;
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Handler returning the current parameters and server clock to a worker.
+pub async fn handle_ps_pull<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    match &data.parameter_server {
+        Some(server) => {
+            data.metrics.increment("ps_pulls");
+            HttpResponse::Ok().json(server.pull(&data.model))
+        }
+        None => HttpResponse::NotFound().body("parameter server mode is not enabled"),
+    }
+}
+
+/// Handler applying a gradient pushed by a worker.
+///
+/// Stale pushes beyond the configured bound are answered with `409 Conflict`
+/// so the worker can pull fresh parameters and recompute. Like training
+/// steps, the push is applied on the blocking pool.
+pub async fn handle_ps_push<T, A>(
+    data: web::Data<AppState<T, A>>,
+    push: web::Json<GradientPush<T>>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    let state = data.clone(); // clone the Arc (not the state)
+    let push = push.into_inner();
+    let result = tokio::task::spawn_blocking(move || {
+        let server = state.parameter_server.as_ref()?;
+        Some(server.push(&state.model, &push))
+    })
+    .await;
+    match result {
+        Ok(Some(Ok(ack))) => {
+            data.metrics.increment("ps_pushes_applied");
+            data.metrics.set_gauge("ps_clock", ack.clock as f64);
+            HttpResponse::Ok().json(ack)
+        }
+        Ok(Some(Err(e))) => {
+            data.metrics.increment("ps_pushes_rejected");
+            error_response(&e)
+        }
+        Ok(None) => HttpResponse::NotFound().body("parameter server mode is not enabled"),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Task failed: {:?}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::DummyAlgorithm;
+    use crate::errors::ModelError;
+    use crate::handlers::AppState;
+    use crate::model::Model;
+    use crate::parameter_server::{GradientPush, ParameterServerClient, ParameterServerConfig};
+    use crate::server::configure;
+    use actix_web::{web, App, HttpServer};
+
+    #[actix_rt::test]
+    async fn test_worker_pull_and_push_round_trip() {
+        let state = web::Data::new(
+            AppState::new(
+                Model::<f32>::with_parameters(vec![1.0, 1.0]),
+                DummyAlgorithm,
+            )
+            .with_parameter_server(ParameterServerConfig {
+                learning_rate: 1.0,
+                max_staleness: 0,
+                ..Default::default()
+            }),
+        );
+        let app_state = state.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(app_state.clone())
+                .configure(configure::<f32, DummyAlgorithm>)
+        })
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let address = server.addrs()[0];
+        let handle = server.run();
+        let server_handle = handle.handle();
+        actix_rt::spawn(handle);
+
+        let client = ParameterServerClient::new(&format!("http://{}", address));
+        let snapshot = client.pull::<f32>().await.unwrap();
+        assert_eq!(snapshot.clock, 0);
+
+        let ack = client
+            .push(&GradientPush {
+                worker_id: "w".to_string(),
+                base_clock: snapshot.clock,
+                gradient: vec![0.5, -1.0],
+            })
+            .await
+            .unwrap();
+        assert_eq!(ack.clock, 1);
+        assert_eq!(client.pull::<f32>().await.unwrap().values, vec![0.5, 2.0]);
+
+        // Re-pushing against the old snapshot exceeds max_staleness = 0.
+        let stale = client
+            .push(&GradientPush {
+                worker_id: "w".to_string(),
+                base_clock: 0,
+                gradient: vec![0.5, -1.0],
+            })
+            .await;
+        assert!(matches!(stale, Err(ModelError::RemoteError(_))));
+        assert_eq!(state.metrics.counter("ps_pushes_rejected"), 1);
+
+        server_handle.stop(true).await;
+    }
+}
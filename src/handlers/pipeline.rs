@@ -0,0 +1,36 @@
+use crate::algorithm::Algorithm;
+use crate::handlers::{service_response, AppState};
+use crate::pipeline::train_pipelined;
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Largest upload accepted by `POST /training/batch`.
+pub const MAX_BATCH_BYTES: usize = 256 * 1024 * 1024;
+
+/// Handler training the serving model on an upload of samples, one JSON
+/// sample per line, through the pipelined batch trainer (see
+/// [`train_pipelined`]).
+pub async fn handle_batch_training<T, A>(
+    data: web::Data<AppState<T, A>>,
+    body: web::Bytes,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    let state = data.clone(); // clone the Arc (not the state)
+    let result =
+        tokio::task::spawn_blocking(move || train_pipelined(&state, &body, &state.batch_pipeline))
+            .await;
+    match result {
+        Ok(Ok(report)) => {
+            data.metrics.increment("training_batches");
+            HttpResponse::Ok().json(report)
+        }
+        Ok(Err(e)) => service_response(&e),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Task failed: {:?}", e)),
+    }
+}
@@ -0,0 +1,118 @@
+use crate::handlers::{error_response, AppState};
+use crate::plugins::WasmAlgorithm;
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Largest module accepted by `PUT /plugins/module`.
+const MAX_MODULE_BYTES: usize = 16 * 1024 * 1024;
+
+/// Handler compiling an uploaded WASM module and swapping it in as the
+/// serving algorithm.
+pub async fn handle_load_module<T>(
+    data: web::Data<AppState<T, WasmAlgorithm>>,
+    body: web::Bytes,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+{
+    let state = data.clone(); // clone the Arc (not the state)
+    match tokio::task::spawn_blocking(move || state.algorithm.load(&body)).await {
+        Ok(Ok(info)) => {
+            data.metrics.increment("plugin_loads");
+            data.metrics
+                .set_gauge("plugin_version", info.version as f64);
+            HttpResponse::Ok().json(info)
+        }
+        Ok(Err(e)) => error_response(&e),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Task failed: {:?}", e)),
+    }
+}
+
+/// Handler describing the loaded module.
+pub async fn handle_module_info<T>(data: web::Data<AppState<T, WasmAlgorithm>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+{
+    match data.algorithm.info() {
+        Some(info) => HttpResponse::Ok().json(info),
+        None => HttpResponse::NotFound().body("no plugin module is loaded"),
+    }
+}
+
+/// Registers the plugin routes; add it next to
+/// [`configure`](crate::server::configure) for servers running a [`WasmAlgorithm`].
+pub fn configure_plugins<T>(cfg: &mut web::ServiceConfig)
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + 'static + Debug + Send + Sync + Sum,
+{
+    cfg.service(
+        web::resource("/plugins/module")
+            .app_data(web::PayloadConfig::new(MAX_MODULE_BYTES))
+            .route(web::put().to(handle_load_module::<T>))
+            .route(web::get().to(handle_module_info::<T>)),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::PredictionEnvelope;
+    use crate::model::Model;
+    use crate::server::configure;
+    use actix_web::{http, test, App};
+
+    /// Predicts the first feature plus the first parameter.
+    const OFFSET: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (global $next (mut i32) (i32.const 1024))
+          (func (export "oml_alloc") (param $len i32) (result i32)
+            (local $ptr i32)
+            (local.set $ptr (global.get $next))
+            (global.set $next (i32.add (global.get $next) (local.get $len)))
+            (local.get $ptr))
+          (func (export "oml_train") (param i32 i32 i32 i32) (result i32)
+            (i32.const 0))
+          (func (export "oml_infer") (param $p i32) (param $pn i32) (param $x i32) (param $xn i32) (result f64)
+            (f64.add (f64.load (local.get $p)) (f64.load (local.get $x)))))
+    "#;
+
+    #[actix_rt::test]
+    async fn test_module_upload_swaps_the_algorithm() {
+        let state = web::Data::new(AppState::new(
+            Model::with_parameters(vec![1.0f32]),
+            WasmAlgorithm::new(None).unwrap(),
+        ));
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .configure(configure::<f32, WasmAlgorithm>)
+                .configure(configure_plugins::<f32>),
+        )
+        .await;
+
+        let infer = || {
+            test::TestRequest::post()
+                .uri("/inference")
+                .set_json(vec![2.0])
+                .to_request()
+        };
+        let resp = test::call_service(&app, infer()).await;
+        assert_eq!(resp.status(), http::StatusCode::INTERNAL_SERVER_ERROR);
+
+        let req = test::TestRequest::put()
+            .uri("/plugins/module")
+            .set_payload(OFFSET)
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            http::StatusCode::OK
+        );
+        let response: PredictionEnvelope<f32, f64> =
+            test::call_and_read_body_json(&app, infer()).await;
+        assert_eq!(response.prediction, 3.0);
+    }
+}
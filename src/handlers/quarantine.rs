@@ -0,0 +1,98 @@
+use crate::algorithm::Algorithm;
+use crate::handlers::AppState;
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Handler listing the training samples currently held in the outlier quarantine.
+///
+/// Responds with `404 Not Found` when no outlier filter is configured.
+pub async fn handle_quarantine<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    match &data.outlier_filter {
+        Some(filter) => match filter.lock() {
+            Ok(filter) => HttpResponse::Ok().json(filter.quarantined()),
+            Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+        },
+        None => HttpResponse::NotFound().body("outlier filter is not enabled"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::DummyAlgorithm;
+    use crate::handlers::{handle_metrics, handle_training_step};
+    use crate::metrics::MetricsSnapshot;
+    use crate::model::Model;
+    use crate::outliers::{OutlierConfig, OutlierFilter, OutlierMethod, QuarantinedSample};
+    use actix_web::{http, test, App};
+
+    #[actix_rt::test]
+    async fn test_outlier_is_quarantined_instead_of_trained() {
+        let filter = OutlierFilter::new(OutlierConfig {
+            method: OutlierMethod::ZScore,
+            threshold: 3.0,
+            window: 8,
+            min_samples: 0,
+            quarantine_capacity: 8,
+        });
+        let app_state = web::Data::new(
+            AppState::new(Model::<f32>::with_parameters(vec![1.0]), DummyAlgorithm)
+                .with_outlier_filter(filter),
+        );
+        // Seed the window without going through the (slow) training handler.
+        for value in [1.0, 1.1, 0.9, 1.0] {
+            app_state
+                .outlier_filter
+                .as_ref()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .check(value);
+        }
+
+        let app = test::init_service(
+            App::new()
+                .app_data(app_state.clone())
+                .route(
+                    "/training",
+                    web::post().to(handle_training_step::<f32, DummyAlgorithm>),
+                )
+                .route(
+                    "/training/quarantine",
+                    web::get().to(handle_quarantine::<f32, DummyAlgorithm>),
+                )
+                .route(
+                    "/metrics",
+                    web::get().to(handle_metrics::<f32, DummyAlgorithm>),
+                ),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/training")
+            .set_json(50.0f32)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::ACCEPTED);
+        assert_eq!(app_state.model.read_params().clone(), vec![1.0]);
+
+        let req = test::TestRequest::get()
+            .uri("/training/quarantine")
+            .to_request();
+        let quarantined: Vec<QuarantinedSample<f32>> =
+            test::call_and_read_body_json(&app, req).await;
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(quarantined[0].value, 50.0);
+
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let metrics: MetricsSnapshot = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(metrics.counters["outliers_quarantined"], 1);
+    }
+}
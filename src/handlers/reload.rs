@@ -0,0 +1,90 @@
+use crate::algorithm::Algorithm;
+use crate::handlers::{error_response, AppState};
+use crate::reload::RuntimeConfig;
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Handler reloading the runtime settings: from the JSON body if one is
+/// sent, from the configuration file otherwise.
+pub async fn handle_reload<T, A>(
+    data: web::Data<AppState<T, A>>,
+    body: web::Bytes,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    let result = if body.is_empty() {
+        let state = data.clone(); // clone the Arc (not the state)
+        match tokio::task::spawn_blocking(move || state.reload_from_file()).await {
+            Ok(result) => result,
+            Err(e) => {
+                return HttpResponse::InternalServerError().body(format!("Task failed: {:?}", e))
+            }
+        }
+    } else {
+        match serde_json::from_slice::<RuntimeConfig>(&body) {
+            Ok(config) => data.reload(&config),
+            Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+        }
+    };
+    match result {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(e) => error_response(&e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::DummyAlgorithm;
+    use crate::early_stopping::GuardConfig;
+    use crate::handlers::AppState;
+    use crate::model::Model;
+    use crate::reload::ReloadReport;
+    use crate::server::configure;
+    use actix_web::{http, test, web, App};
+
+    #[actix_rt::test]
+    async fn test_reload_from_body_and_file() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(
+            file.path(),
+            r#"{"training_guard": {"tolerance": 0.5, "patience": 7, "action": "pause"}}"#,
+        )
+        .unwrap();
+        let state = web::Data::new(
+            AppState::new(Model::with_parameters(vec![1.0f32]), DummyAlgorithm)
+                .with_training_guard(GuardConfig::default())
+                .with_config_file(file.path()),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .configure(configure::<f32, DummyAlgorithm>),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/admin/reload")
+            .set_payload(r#"{"checkpoint_interval": {"secs": 5, "nanos": 0}}"#)
+            .to_request();
+        let report: ReloadReport = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(report.ignored, vec!["checkpoint_interval"]);
+
+        let req = test::TestRequest::post().uri("/admin/reload").to_request();
+        let report: ReloadReport = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(report.applied, vec!["training_guard"]);
+        let guard = state.training_guard.as_ref().unwrap();
+        assert_eq!(guard.config().unwrap().patience, 7);
+
+        std::fs::write(file.path(), "{").unwrap();
+        let req = test::TestRequest::post().uri("/admin/reload").to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            http::StatusCode::BAD_REQUEST
+        );
+    }
+}
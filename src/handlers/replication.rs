@@ -0,0 +1,213 @@
+use crate::algorithm::Algorithm;
+use crate::handlers::{error_response, AppState};
+use crate::replication::Replication;
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Query string of the event stream endpoint.
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    #[serde(default)]
+    pub since: u64,
+}
+
+/// Handler returning the leader's training events after `since`.
+///
+/// Responds with `410 Gone` when those events were already truncated from
+/// the log, signalling the follower to restart from a snapshot.
+pub async fn handle_replication_events<T, A>(
+    data: web::Data<AppState<T, A>>,
+    query: web::Query<EventsQuery>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(Replication::Leader(log)) = data.replication.as_deref() else {
+        return HttpResponse::NotFound().body("this instance is not a replication leader");
+    };
+    match log.since(query.since) {
+        Ok(Some(events)) => HttpResponse::Ok().json(events),
+        Ok(None) => HttpResponse::Gone().body("requested events were truncated, load a snapshot"),
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler returning the leader's parameters and the matching sequence number.
+pub async fn handle_replication_snapshot<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(Replication::Leader(log)) = data.replication.as_deref() else {
+        return HttpResponse::NotFound().body("this instance is not a replication leader");
+    };
+    match log.snapshot(&data.model) {
+        Ok(snapshot) => HttpResponse::Ok().json(snapshot),
+        Err(e) => error_response(&e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::Algorithm;
+    use crate::errors::ModelError;
+    use crate::handlers::AppState;
+    use crate::model::Model;
+    use crate::replication::Follower;
+    use crate::server::configure;
+    use crate::service::apply_training_step;
+    use crate::test::AdditiveAlgorithm;
+    use actix_web::{http, test, web, App, HttpServer};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Like `AdditiveAlgorithm`, but rejects negative inputs.
+    struct RejectNegative;
+
+    impl Algorithm<f32> for RejectNegative {
+        type Input = f32;
+        type Output = f32;
+
+        fn training_step(&self, model: &Model<f32>, x: f32) -> Result<(), ModelError> {
+            if x < 0.0 {
+                return Err(ModelError::InvalidInput("negative input".to_string()));
+            }
+            AdditiveAlgorithm::default().training_step(model, x)
+        }
+
+        fn inference_step(&self, model: &Model<f32>, x: f32) -> Result<f32, ModelError> {
+            AdditiveAlgorithm::default().inference_step(model, x)
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_follower_catches_up_from_snapshot_and_stream() {
+        let leader = web::Data::new(
+            AppState::new(
+                Model::<f32>::with_parameters(vec![0.0]),
+                AdditiveAlgorithm::default(),
+            )
+            .with_replication_leader(2),
+        );
+        let app_state = leader.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(app_state.clone())
+                .configure(configure::<f32, AdditiveAlgorithm>)
+        })
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let address = server.addrs()[0];
+        let running = server.run();
+        let handle = running.handle();
+        actix_rt::spawn(running);
+
+        let client = reqwest::Client::new();
+        for input in [1.0f32, 2.0, 3.0] {
+            let status = client
+                .post(format!("http://{}/training", address))
+                .json(&input)
+                .send()
+                .await
+                .unwrap()
+                .status();
+            assert!(status.is_success());
+        }
+
+        // A fresh follower starts from the snapshot (seq 3, params [6.0]).
+        let follower = Follower::new(&format!("http://{}", address), Duration::from_secs(1));
+        let model = Arc::new(Model::<f32>::with_parameters(vec![0.0]));
+        let algorithm = Arc::new(AdditiveAlgorithm::default());
+        assert_eq!(follower.sync(&model, &algorithm).await.unwrap(), 0);
+        assert_eq!(follower.applied_seq(), 3);
+
+        client
+            .post(format!("http://{}/training", address))
+            .json(&4.0f32)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(follower.sync(&model, &algorithm).await.unwrap(), 1);
+        assert_eq!(model.read_params().clone(), vec![10.0]);
+
+        // An event failing on the follower keeps the ones before it applied.
+        for input in [5.0f32, -1.0] {
+            client
+                .post(format!("http://{}/training", address))
+                .json(&input)
+                .send()
+                .await
+                .unwrap();
+        }
+        assert!(follower
+            .sync(&model, &Arc::new(RejectNegative))
+            .await
+            .is_err());
+        assert_eq!(follower.applied_seq(), 5);
+        assert_eq!(model.read_params().clone(), vec![15.0]);
+
+        handle.stop(true).await;
+    }
+
+    #[actix_rt::test]
+    async fn test_follower_replays_sample_weights() {
+        let leader = web::Data::new(
+            AppState::new(
+                Model::<f32>::with_parameters(vec![0.0]),
+                AdditiveAlgorithm::default(),
+            )
+            .with_replication_leader(4),
+        );
+        let app_state = leader.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(app_state.clone())
+                .configure(configure::<f32, AdditiveAlgorithm>)
+        })
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let address = server.addrs()[0];
+        let running = server.run();
+        let handle = running.handle();
+        actix_rt::spawn(running);
+
+        let follower = Follower::new(&format!("http://{}", address), Duration::from_secs(1));
+        let model = Arc::new(Model::<f32>::with_parameters(vec![0.0]));
+        let algorithm = Arc::new(AdditiveAlgorithm::default());
+        follower.sync(&model, &algorithm).await.unwrap();
+        // E.g. a majority-class sample down-weighted by the class priors.
+        apply_training_step(&leader, &AdditiveAlgorithm::default(), 4.0, 0.25).unwrap();
+        assert_eq!(follower.sync(&model, &algorithm).await.unwrap(), 1);
+        assert_eq!(model.read_params().clone(), vec![1.0]);
+        assert_eq!(leader.model.read_params().clone(), vec![1.0]);
+
+        handle.stop(true).await;
+    }
+
+    #[actix_rt::test]
+    async fn test_follower_rejects_direct_training() {
+        let follower = web::Data::new(
+            AppState::new(
+                Model::<f32>::with_parameters(vec![0.0]),
+                AdditiveAlgorithm::default(),
+            )
+            .with_replication_follower("http://leader:8080", Duration::from_secs(1)),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(follower.clone())
+                .configure(configure::<f32, AdditiveAlgorithm>),
+        )
+        .await;
+        let req = test::TestRequest::post()
+            .uri("/training")
+            .set_json(1.0f32)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::CONFLICT);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::algorithm::Algorithm;
+use crate::handlers::{error_response, AppState};
+use crate::scripting::HookStage;
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Handler listing the attached scripts.
+pub async fn handle_list_scripts<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(scripts) = &data.scripts else {
+        return HttpResponse::NotFound().body("scripting is not enabled");
+    };
+    match scripts.list() {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler attaching (or hot-reloading) the script of a stage; the body is
+/// the Rhai source.
+pub async fn handle_set_script<T, A>(
+    data: web::Data<AppState<T, A>>,
+    stage: web::Path<HookStage>,
+    source: String,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(scripts) = &data.scripts else {
+        return HttpResponse::NotFound().body("scripting is not enabled");
+    };
+    match scripts.set(stage.into_inner(), &source) {
+        Ok(info) => {
+            data.metrics.increment("script_reloads");
+            HttpResponse::Ok().json(info)
+        }
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler detaching the script of a stage.
+pub async fn handle_remove_script<T, A>(
+    data: web::Data<AppState<T, A>>,
+    stage: web::Path<HookStage>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(scripts) = &data.scripts else {
+        return HttpResponse::NotFound().body("scripting is not enabled");
+    };
+    match scripts.remove(stage.into_inner()) {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().body("no script is attached to this stage"),
+        Err(e) => error_response(&e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::DummyAlgorithm;
+    use crate::handlers::{AppState, PredictionEnvelope};
+    use crate::model::Model;
+    use crate::scripting::ScriptHooks;
+    use crate::server::configure;
+    use actix_web::{http, test, web, App};
+
+    #[actix_rt::test]
+    async fn test_scripts_wrap_inference() {
+        let state = web::Data::new(
+            AppState::new(Model::with_parameters(vec![1.0f32, 2.0]), DummyAlgorithm)
+                .with_scripts(ScriptHooks::new(10_000)),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .configure(configure::<f32, DummyAlgorithm>),
+        )
+        .await;
+        let put = |stage: &str, source: &'static str| {
+            test::TestRequest::put()
+                .uri(&format!("/scripts/{}", stage))
+                .set_payload(source)
+                .to_request()
+        };
+        let infer = || {
+            test::TestRequest::post()
+                .uri("/inference")
+                .set_json(2.0f32)
+                .to_request()
+        };
+
+        let req = put("preprocess", "x / 2.0");
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            http::StatusCode::OK
+        );
+        let req = put(
+            "postprocess",
+            "if prediction > 100.0 { 100.0 } else { prediction }",
+        );
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            http::StatusCode::OK
+        );
+        // (1.0 + 2.0) * (2.0 / 2)
+        let prediction: PredictionEnvelope<f32> =
+            test::call_and_read_body_json(&app, infer()).await;
+        assert_eq!(prediction.prediction, 3.0);
+
+        let req = put("preprocess", "x * 100.0");
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            http::StatusCode::OK
+        );
+        let prediction: PredictionEnvelope<f32> =
+            test::call_and_read_body_json(&app, infer()).await;
+        assert_eq!(prediction.prediction, 100.0);
+
+        let req = put("preprocess", "x *");
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            http::StatusCode::BAD_REQUEST
+        );
+        let req = test::TestRequest::delete()
+            .uri("/scripts/preprocess")
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            http::StatusCode::NO_CONTENT
+        );
+    }
+}
@@ -0,0 +1,42 @@
+use crate::algorithm::Algorithm;
+use crate::handlers::AppState;
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Handler re-running the self-test on the serving model. Answers with the
+/// report, with `503` if it failed.
+pub async fn handle_self_test<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    if data.self_test.is_none() {
+        return HttpResponse::NotFound().body("self-test is not enabled");
+    }
+    let state = data.into_inner();
+    match tokio::task::spawn_blocking(move || state.run_self_test()).await {
+        Ok(Some(report)) if report.passed => HttpResponse::Ok().json(report),
+        Ok(Some(report)) => HttpResponse::ServiceUnavailable().json(report),
+        Ok(None) => HttpResponse::NotFound().body("self-test is not enabled"),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Task failed: {:?}", e)),
+    }
+}
+
+/// Readiness probe: `200` once the self-test passed (or when none is
+/// configured), `503` with the last report otherwise.
+pub async fn handle_ready<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    if data.is_ready() {
+        return HttpResponse::Ok().body("ready");
+    }
+    match data.self_test.as_ref().and_then(|t| t.last_report()) {
+        Some(report) => HttpResponse::ServiceUnavailable().json(report),
+        None => HttpResponse::ServiceUnavailable().body("self-test has not run"),
+    }
+}
@@ -0,0 +1,71 @@
+use crate::algorithm::Algorithm;
+use crate::evaluation::LabeledSample;
+use crate::handlers::{error_response, AppState};
+use actix_web::{web, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Handler reporting the state of the hyperparameter race.
+pub async fn handle_tuning_status<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(tuner) = &data.tuner else {
+        return HttpResponse::NotFound().body("hyperparameter tuning is not enabled");
+    };
+    match tuner.status() {
+        Ok(status) => HttpResponse::Ok().json(status),
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler feeding one labeled sample to every surviving candidate.
+///
+/// When the sample decides the race, the winner is promoted to the serving
+/// slot with the parameters it trained.
+pub async fn handle_tuning_sample<T, A>(
+    data: web::Data<AppState<T, A>>,
+    sample: web::Json<LabeledSample<A::Input, A::Output>>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let Some(tuner) = data.tuner.clone() else {
+        return HttpResponse::NotFound().body("hyperparameter tuning is not enabled");
+    };
+    let state = data.clone(); // clone the Arc (not the state)
+    let sample = sample.into_inner();
+
+    let result = tokio::task::spawn_blocking(move || {
+        let decided = tuner.observe(&sample)?;
+        if decided {
+            if let (Some(algorithm), Some(parameters)) =
+                (tuner.winning_algorithm(), tuner.winning_parameters()?)
+            {
+                state.promote(algorithm, parameters)?;
+            }
+        }
+        tuner.status().map(|status| (decided, status))
+    })
+    .await;
+
+    match result {
+        Ok(Ok((decided, status))) => {
+            data.metrics.increment("tuning_samples");
+            data.metrics.set_gauge(
+                "tuning_alive_candidates",
+                status.candidates.iter().filter(|c| c.alive).count() as f64,
+            );
+            if decided {
+                data.metrics.increment("tuning_promotions");
+            }
+            HttpResponse::Ok().json(status)
+        }
+        Ok(Err(e)) => error_response(&e),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Task failed: {:?}", e)),
+    }
+}
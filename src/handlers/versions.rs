@@ -0,0 +1,609 @@
+use crate::algorithm::Algorithm;
+use crate::canary::RolloutStatus;
+use crate::errors::ModelError;
+use crate::handlers::{error_response, AppState};
+use crate::model::{ModelFormat, SavedModel};
+use crate::safetensors::{self, SAFETENSORS_CONTENT_TYPE};
+use crate::snapshot_diff::{SnapshotDiff, DEFAULT_TOP_MOVED};
+use actix_web::http::header::CONTENT_TYPE;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Response header carrying the version of the live parameters that served
+/// an inference request, or that a training step produced.
+pub const PARAMETERS_VERSION_HEADER: &str = "X-Oml-Parameters-Version";
+
+/// Content type of a bincode artifact uploaded to `PUT /model/weights`.
+pub const BINCODE_CONTENT_TYPE: &str = "application/x-bincode";
+
+/// Largest artifact accepted by `PUT /model/weights` and `POST /admin/import`.
+pub const MAX_WEIGHTS_BYTES: usize = 256 * 1024 * 1024;
+
+/// Response of `GET /model/version`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionReport {
+    pub version: u64,
+    /// Versions `POST /admin/rollback` can return to, oldest first.
+    pub restore_points: Vec<u64>,
+}
+
+/// Query of `PUT /model/weights`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WeightsQuery {
+    /// Rolls the weights out gradually instead of swapping them in.
+    #[serde(default)]
+    pub canary: bool,
+}
+
+/// Encoding of the weights downloaded from `GET /model/weights`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WeightsFormat {
+    /// The saved model as written by [`Model::save`](crate::model::Model::save) to a JSON file.
+    #[default]
+    Json,
+    /// The saved model as written to a `.bin` file.
+    Bincode,
+    /// The dense parameters as safetensors, for other ML tools.
+    Safetensors,
+}
+
+/// Query of `GET /model/weights`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WeightsDownloadQuery {
+    #[serde(default)]
+    pub format: WeightsFormat,
+}
+
+/// Query of `GET /model/delta`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DeltaQuery {
+    /// Version the caller last synced to.
+    #[serde(default)]
+    pub since: u64,
+}
+
+/// Query of `GET /model/snapshots/{a}/diff/{b}`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotDiffQuery {
+    /// Number of most moved parameters listed.
+    #[serde(default = "default_top_moved")]
+    pub top: usize,
+}
+
+fn default_top_moved() -> usize {
+    DEFAULT_TOP_MOVED
+}
+
+/// Body of `POST /admin/rollback`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RollbackRequest {
+    pub version: u64,
+}
+
+/// Handler reporting the live model version and its restore points.
+pub async fn handle_model_version<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    HttpResponse::Ok().json(VersionReport {
+        version: data.model.version(),
+        restore_points: data.model.restore_points(),
+    })
+}
+
+/// Handler reporting the identity and training counters of the live model.
+pub async fn handle_model_info<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    HttpResponse::Ok().json(data.model.metadata())
+}
+
+/// Handler reporting norms and ranges of the live parameters.
+pub async fn handle_model_stats<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let model = data.model.clone();
+    match tokio::task::spawn_blocking(move || model.stats()).await {
+        Ok(stats) => HttpResponse::Ok().json(stats),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Task failed: {:?}", e)),
+    }
+}
+
+/// Handler listing the labels the model has seen, by class id, so clients
+/// can decode class predictions.
+pub async fn handle_model_labels<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    HttpResponse::Ok().json(data.model.labels().labels())
+}
+
+/// Handler reporting the class frequencies of the training samples and the
+/// weights they get.
+pub async fn handle_model_priors<T, A>(data: web::Data<AppState<T, A>>) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    match data.model.class_priors() {
+        Some(priors) => HttpResponse::Ok().json(priors.priors(data.model.labels())),
+        None => HttpResponse::NotFound().body("class priors are not tracked"),
+    }
+}
+
+/// Handler returning the parameters changed since a version, for replicas
+/// and edge devices syncing incrementally.
+pub async fn handle_model_delta<T, A>(
+    data: web::Data<AppState<T, A>>,
+    query: web::Query<DeltaQuery>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    match data.model.diff(query.since) {
+        Ok(delta) => {
+            data.metrics.increment("model_deltas");
+            HttpResponse::Ok().json(delta)
+        }
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler summarizing how the parameters changed between two stored
+/// versions (see [`Model::stored_versions`](crate::model::Model::stored_versions)).
+pub async fn handle_snapshot_diff<T, A>(
+    data: web::Data<AppState<T, A>>,
+    path: web::Path<(u64, u64)>,
+    query: web::Query<SnapshotDiffQuery>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let (a, b) = path.into_inner();
+    let (Some(from), Some(to)) = (data.model.stored_version(a), data.model.stored_version(b))
+    else {
+        return HttpResponse::NotFound().body(format!(
+            "versions {} and {} are not both stored; stored versions are {:?}",
+            a,
+            b,
+            data.model.stored_versions()
+        ));
+    };
+    data.metrics.increment("snapshot_diffs");
+    HttpResponse::Ok().json(SnapshotDiff::between(
+        &from,
+        &to,
+        data.model.layout(),
+        query.top,
+    ))
+}
+
+/// Handler publishing the parameters of a restore point again.
+pub async fn handle_rollback<T, A>(
+    data: web::Data<AppState<T, A>>,
+    request: web::Json<RollbackRequest>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    match data.model.rollback(request.version) {
+        Ok(version) => {
+            data.metrics.increment("model_rollbacks");
+            HttpResponse::Ok().json(VersionReport {
+                version,
+                restore_points: data.model.restore_points(),
+            })
+        }
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Body of `POST /admin/reset`; without parameters, those the model was
+/// created or loaded with are restored.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ResetRequest<T> {
+    pub parameters: Option<Vec<T>>,
+}
+
+/// Handler resetting the parameters to their initial values, or to supplied
+/// ones, keeping the replaced parameters as a restore point.
+pub async fn handle_reset<T, A>(
+    data: web::Data<AppState<T, A>>,
+    request: Option<web::Json<ResetRequest<T>>>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let parameters = request.and_then(|r| r.into_inner().parameters);
+    let reset = match parameters {
+        Some(parameters) => data.model.replace_parameters(parameters),
+        None => data.model.reset(),
+    };
+    match reset {
+        Ok(version) => {
+            data.metrics.increment("model_resets");
+            HttpResponse::Ok().json(VersionReport {
+                version,
+                restore_points: data.model.restore_points(),
+            })
+        }
+        Err(e) => error_response(&e),
+    }
+}
+
+/// Handler downloading the live parameters with their layout and metadata,
+/// as JSON, bincode or safetensors (`?format=`).
+pub async fn handle_download_weights<T, A>(
+    data: web::Data<AppState<T, A>>,
+    query: web::Query<WeightsDownloadQuery>,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    let format = query.format;
+    let model = data.model.clone(); // clone the Arc (not the model)
+    let task = tokio::task::spawn_blocking(move || {
+        let saved = model.to_saved();
+        Ok::<_, ModelError>(match format {
+            WeightsFormat::Json => ("application/json", serde_json::to_vec(&saved)?),
+            WeightsFormat::Bincode => (BINCODE_CONTENT_TYPE, saved.encode(ModelFormat::Bincode)?),
+            WeightsFormat::Safetensors => (SAFETENSORS_CONTENT_TYPE, safetensors::encode(&saved)?),
+        })
+    });
+    match task.await {
+        Ok(Ok((content_type, bytes))) => {
+            data.metrics.increment("weights_downloaded");
+            HttpResponse::Ok().content_type(content_type).body(bytes)
+        }
+        Ok(Err(e)) => error_response(&e),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Task failed: {:?}", e)),
+    }
+}
+
+/// What became of uploaded weights.
+enum Upload {
+    Swapped(u64),
+    Canary(RolloutStatus),
+}
+
+/// Handler swapping in the parameters of an uploaded artifact: a model
+/// written by [`Model::save`](crate::model::Model::save), as JSON or (with
+/// an `application/x-bincode` or `application/octet-stream` content type)
+/// bincode, safetensors (with an `application/x-safetensors` content type)
+/// or a bare JSON array. The replaced parameters become a restore
+/// point. With `?canary=true` the weights are rolled out gradually instead
+/// (`202 Accepted`, see [`crate::canary::CanaryRollout`]).
+pub async fn handle_replace_weights<T, A>(
+    req: HttpRequest,
+    data: web::Data<AppState<T, A>>,
+    query: web::Query<WeightsQuery>,
+    body: web::Bytes,
+) -> impl Responder
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    let canary = match (query.canary, &data.canary) {
+        (false, _) => None,
+        (true, Some(canary)) => Some(canary.clone()),
+        (true, None) => return HttpResponse::NotFound().body("canary rollouts are not enabled"),
+    };
+    let content_type = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok());
+    let tensors = content_type == Some(SAFETENSORS_CONTENT_TYPE);
+    let format = match content_type {
+        Some(BINCODE_CONTENT_TYPE | "application/octet-stream") => ModelFormat::Bincode,
+        _ => ModelFormat::Json,
+    };
+    let state = data.clone(); // clone the Arc (not the state)
+    let task = tokio::task::spawn_blocking(move || {
+        let invalid = |e: ModelError| ModelError::InvalidInput(e.to_string());
+        let parameters = match body.trim_ascii_start().first() {
+            Some(b'[') if format == ModelFormat::Json && !tensors => {
+                serde_json::from_slice(&body).map_err(|e| invalid(e.into()))?
+            }
+            _ => {
+                let saved = match tensors {
+                    true => safetensors::decode::<T>(&body)?,
+                    false => SavedModel::<T>::decode(&body, format).map_err(invalid)?,
+                };
+                let layout = state.model.layout();
+                if !saved.layout.is_empty() && !layout.is_empty() && saved.layout != *layout {
+                    return Err(ModelError::InvalidInput(
+                        "the parameter groups differ from the model's".to_string(),
+                    ));
+                }
+                saved.parameters
+            }
+        };
+        match canary {
+            Some(canary) => canary.start(parameters, &state.model).map(Upload::Canary),
+            None => state
+                .model
+                .replace_parameters(parameters)
+                .map(Upload::Swapped),
+        }
+    });
+    match task.await {
+        Ok(Ok(Upload::Swapped(version))) => {
+            data.metrics.increment("weights_replaced");
+            HttpResponse::Ok().json(VersionReport {
+                version,
+                restore_points: data.model.restore_points(),
+            })
+        }
+        Ok(Ok(Upload::Canary(rollout))) => {
+            data.metrics.increment("canary_rollouts");
+            HttpResponse::Accepted().json(rollout)
+        }
+        Ok(Err(e)) => error_response(&e),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Task failed: {:?}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::ModelLayout;
+    use crate::model::{Model, ModelDelta, ModelMetadata, ModelStats};
+    use crate::server::configure;
+    use crate::test::ScalingAlgorithm;
+    use actix_web::{test, App};
+
+    #[actix_rt::test]
+    async fn test_versions_in_responses_and_rollback() {
+        let state = AppState::new(Model::with_parameters(vec![1.0f32, 2.0]), ScalingAlgorithm);
+        let model = state.model.clone();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(configure::<f32, ScalingAlgorithm>),
+        )
+        .await;
+
+        let train = test::TestRequest::post()
+            .uri("/training")
+            .set_json(1.0f32)
+            .to_request();
+        let response = test::call_service(&app, train).await;
+        assert_eq!(
+            response.headers().get(PARAMETERS_VERSION_HEADER).unwrap(),
+            "1"
+        );
+        let good = model.checkpoint();
+        let before = model.read_params().clone();
+        let train = test::TestRequest::post()
+            .uri("/training")
+            .set_json(5.0f32)
+            .to_request();
+        test::call_service(&app, train).await;
+
+        let infer = test::TestRequest::post()
+            .uri("/inference")
+            .set_json(1.0f32)
+            .to_request();
+        let response = test::call_service(&app, infer).await;
+        assert_eq!(
+            response.headers().get(PARAMETERS_VERSION_HEADER).unwrap(),
+            "2"
+        );
+        let info = test::TestRequest::get().uri("/model/info").to_request();
+        let info: ModelMetadata = test::call_and_read_body_json(&app, info).await;
+        assert_eq!((info.training_steps, info.parameter_count), (2, 2));
+        assert_eq!(info.id, model.metadata().id);
+
+        let rollback = test::TestRequest::post()
+            .uri("/admin/rollback")
+            .set_json(RollbackRequest { version: good })
+            .to_request();
+        let report: VersionReport = test::call_and_read_body_json(&app, rollback).await;
+        assert_eq!((report.version, report.restore_points), (3, vec![1]));
+        assert_eq!(model.read_params().clone(), before);
+
+        let unknown = test::TestRequest::post()
+            .uri("/admin/rollback")
+            .set_json(RollbackRequest { version: 2 })
+            .to_request();
+        assert_eq!(test::call_service(&app, unknown).await.status(), 400);
+
+        let reset = test::TestRequest::post().uri("/admin/reset").to_request();
+        let report: VersionReport = test::call_and_read_body_json(&app, reset).await;
+        assert_eq!((report.version, report.restore_points), (4, vec![1, 3]));
+        assert_eq!(model.read_params().as_slice(), &[1.0, 2.0]);
+        let reset = test::TestRequest::post()
+            .uri("/admin/reset")
+            .set_json(ResetRequest {
+                parameters: Some(vec![0.5f32]),
+            })
+            .to_request();
+        assert_eq!(test::call_service(&app, reset).await.status(), 400);
+    }
+
+    #[actix_rt::test]
+    async fn test_stats_summarize_parameters_by_group() {
+        let layout = ModelLayout::new()
+            .group("weights", &[3])
+            .group("bias", &[1]);
+        let model = Model::with_layout(layout, vec![1.0f64, 0.0, f64::NAN, -2.0]).unwrap();
+        let state = AppState::new(model, ScalingAlgorithm);
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(configure::<f64, ScalingAlgorithm>),
+        )
+        .await;
+        let stats = test::TestRequest::get().uri("/model/stats").to_request();
+        let stats: ModelStats = test::call_and_read_body_json(&app, stats).await;
+        let all = &stats.parameters;
+        assert_eq!((all.count, all.non_finite, all.zeros), (4, 1, 1));
+        assert_eq!(
+            (all.l1_norm, all.mean, all.max),
+            (3.0, Some(-1.0 / 3.0), Some(1.0))
+        );
+        assert_eq!(stats.groups["weights"].l2_norm, 1.0);
+        assert_eq!(stats.groups["bias"].min, Some(-2.0));
+    }
+
+    #[actix_rt::test]
+    async fn test_replica_synced_by_deltas() {
+        let source = Model::with_parameters(vec![1.0f32, 2.0, 3.0]).with_delta_log(4);
+        let state = AppState::new(source, ScalingAlgorithm);
+        let model = state.model.clone();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(configure::<f32, ScalingAlgorithm>),
+        )
+        .await;
+        let delta = |since: u64| {
+            test::TestRequest::get()
+                .uri(&format!("/model/delta?since={}", since))
+                .to_request()
+        };
+
+        let replica = Model::with_parameters(vec![1.0f32, 2.0, 3.0]);
+        model.update_params(|params| params[1] = 5.0);
+        let first: ModelDelta<f32> = test::call_and_read_body_json(&app, delta(0)).await;
+        assert_eq!((first.indices, first.full), (vec![1], false));
+        model.update_params(|params| params[2] = 6.0);
+        let second: ModelDelta<f32> = test::call_and_read_body_json(&app, delta(1)).await;
+        replica.apply_delta(&second).unwrap();
+        assert_eq!(replica.read_params().as_slice(), &[1.0, 2.0, 6.0]);
+
+        for _ in 0..4 {
+            model.update_params(|params| params[0] += 1.0);
+        }
+        let stale: ModelDelta<f32> = test::call_and_read_body_json(&app, delta(1)).await;
+        assert!(stale.full);
+        replica.apply_delta(&stale).unwrap();
+        assert_eq!(replica.read_params().clone(), model.read_params().clone());
+        let current: ModelDelta<f32> = test::call_and_read_body_json(&app, delta(6)).await;
+        assert!(current.indices.is_empty());
+
+        // Rejected deltas leave the replica and its version alone.
+        let version = replica.version();
+        let resized = ModelDelta {
+            len: 4,
+            ..current.clone()
+        };
+        assert!(replica.apply_delta(&resized).is_err());
+        let out_of_range = ModelDelta {
+            indices: vec![3],
+            values: vec![9.0],
+            ..current
+        };
+        assert!(replica.apply_delta(&out_of_range).is_err());
+        assert_eq!(replica.version(), version);
+        assert_eq!(replica.read_params().clone(), model.read_params().clone());
+    }
+
+    #[actix_rt::test]
+    async fn test_weights_uploaded_as_json_bincode_and_safetensors() {
+        let state = AppState::new(Model::with_parameters(vec![1.0f32, 2.0]), ScalingAlgorithm);
+        let model = state.model.clone();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(configure::<f32, ScalingAlgorithm>),
+        )
+        .await;
+        let upload = |content_type: &str, body: Vec<u8>| {
+            test::TestRequest::put()
+                .uri("/model/weights")
+                .insert_header((CONTENT_TYPE, content_type))
+                .set_payload(body)
+                .to_request()
+        };
+
+        let bare = upload("application/json", b"[3.0, 4.0]".to_vec());
+        let report: VersionReport = test::call_and_read_body_json(&app, bare).await;
+        assert_eq!((report.version, report.restore_points), (1, vec![0]));
+        assert_eq!(model.read_params().as_slice(), &[3.0, 4.0]);
+
+        let artifact = bincode::serialize(&SavedModel::new(vec![5.0f32, 6.0])).unwrap();
+        let binary = upload(BINCODE_CONTENT_TYPE, artifact);
+        let report: VersionReport = test::call_and_read_body_json(&app, binary).await;
+        assert_eq!((report.version, report.restore_points), (2, vec![0, 1]));
+        assert_eq!(model.read_params().as_slice(), &[5.0, 6.0]);
+
+        let saved = serde_json::to_vec(&SavedModel::new(vec![7.0f32])).unwrap();
+        let wrong_length = upload("application/json", saved);
+        assert_eq!(test::call_service(&app, wrong_length).await.status(), 400);
+        let f64_artifact = bincode::serialize(&SavedModel::new(vec![5.0f64, 6.0])).unwrap();
+        let wrong_scalar = upload(BINCODE_CONTENT_TYPE, f64_artifact);
+        assert_eq!(test::call_service(&app, wrong_scalar).await.status(), 400);
+        assert_eq!(model.version(), 2);
+
+        let download = test::TestRequest::get()
+            .uri("/model/weights?format=safetensors")
+            .to_request();
+        let tensors = test::call_and_read_body(&app, download).await;
+        let exported = Model::<f64>::from_safetensors(&tensors).unwrap();
+        assert_eq!(exported.read_params().as_slice(), &[5.0, 6.0]);
+        exported.update_params(|params| params[1] = 8.0);
+        let tensors = upload(SAFETENSORS_CONTENT_TYPE, exported.to_safetensors().unwrap());
+        test::call_service(&app, tensors).await;
+        assert_eq!(model.read_params().as_slice(), &[5.0, 8.0]);
+    }
+
+    #[actix_rt::test]
+    async fn test_snapshot_diff_between_restore_point_and_current() {
+        let layout = ModelLayout::new()
+            .group("weights", &[3])
+            .group("bias", &[1]);
+        let model = Model::with_layout(layout, vec![1.0f64, 2.0, 3.0, 0.0]).unwrap();
+        let state = AppState::new(model, ScalingAlgorithm);
+        let model = state.model.clone();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(configure::<f64, ScalingAlgorithm>),
+        )
+        .await;
+        let good = model.checkpoint();
+        model.update_params(|params| {
+            params[1] = 2.5;
+            params[3] = 12.0;
+        });
+
+        let uri = format!("/model/snapshots/{}/diff/1?top=1", good);
+        let diff: SnapshotDiff<f64> =
+            test::call_and_read_body_json(&app, test::TestRequest::get().uri(&uri).to_request())
+                .await;
+        assert_eq!(
+            (diff.from_version, diff.to_version, diff.changed),
+            (0, 1, 2)
+        );
+        assert_eq!(diff.max_abs_delta, Some(12.0));
+        assert_eq!(diff.group_norms["weights"], Some(0.5));
+        assert_eq!(diff.top_moved.len(), 1);
+        assert_eq!(diff.top_moved[0].group.as_deref(), Some("bias"));
+        assert_eq!((diff.top_moved[0].from, diff.top_moved[0].to), (0.0, 12.0));
+        assert!(diff
+            .metadata_changes
+            .iter()
+            .all(|change| change.field == "published_at_ms"));
+
+        let unknown = test::TestRequest::get()
+            .uri("/model/snapshots/0/diff/7")
+            .to_request();
+        assert_eq!(test::call_service(&app, unknown).await.status(), 404);
+    }
+}
@@ -0,0 +1,193 @@
+use crate::algorithm::{Algorithm, Datum};
+use crate::errors::ModelError;
+use crate::evaluation::{evaluate, score, EvaluationReport, LabeledSample};
+use crate::metrics::Metrics;
+use crate::model::Model;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Configuration of scheduled holdout evaluation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HoldoutConfig {
+    /// Time between two evaluations of the registered dataset.
+    pub interval: Duration,
+    /// Number of past evaluations kept for `GET /model/evaluations`.
+    pub history_capacity: usize,
+}
+
+impl Default for HoldoutConfig {
+    fn default() -> Self {
+        HoldoutConfig {
+            interval: Duration::from_secs(60),
+            history_capacity: 100,
+        }
+    }
+}
+
+/// One scheduled evaluation of the model against the holdout set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvaluationRecord {
+    /// Milliseconds since the Unix epoch at which the evaluation finished.
+    pub timestamp_ms: u64,
+    pub report: EvaluationReport,
+}
+
+/// Operator-registered holdout dataset and the history of its evaluations.
+///
+/// Holdout scores only go into the history and `holdout_*` gauges; they do
+/// not feed the online metrics or the calibrator, which track live traffic.
+#[derive(Debug)]
+pub struct Holdout<I, L = I> {
+    config: HoldoutConfig,
+    dataset: Mutex<Vec<LabeledSample<I, L>>>,
+    history: Mutex<VecDeque<EvaluationRecord>>,
+}
+
+impl<I, L> Holdout<I, L>
+where
+    I: Datum,
+    L: Datum,
+{
+    pub fn new(config: HoldoutConfig) -> Self {
+        Holdout {
+            config,
+            dataset: Mutex::new(Vec::new()),
+            history: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Replaces the registered dataset; returns its size.
+    pub fn set_dataset(&self, samples: Vec<LabeledSample<I, L>>) -> Result<usize, ModelError> {
+        if samples.is_empty() {
+            return Err(ModelError::InvalidInput(
+                "holdout dataset is empty".to_string(),
+            ));
+        }
+        let len = samples.len();
+        *self.dataset.lock()? = samples;
+        Ok(len)
+    }
+
+    pub fn history(&self) -> Result<Vec<EvaluationRecord>, ModelError> {
+        Ok(self.history.lock()?.iter().cloned().collect())
+    }
+
+    /// Evaluates the model against the dataset without recording the result.
+    /// Returns `None` while no dataset is registered.
+    pub fn evaluate<T, A>(
+        &self,
+        model: &Model<T>,
+        algorithm: &A,
+    ) -> Result<Option<EvaluationReport>, ModelError>
+    where
+        T: Float + Debug + Send + Sync + Sum,
+        A: Algorithm<T, Input = I, Output = L> + ?Sized,
+    {
+        let pairs = {
+            let dataset = self.dataset.lock()?;
+            if dataset.is_empty() {
+                return Ok(None);
+            }
+            score(algorithm, model, &dataset)?
+        };
+        Ok(Some(evaluate(&pairs)?))
+    }
+
+    /// Evaluates the model against the dataset now and appends the result to
+    /// the history. Returns `None` while no dataset is registered.
+    pub fn run<T, A>(
+        &self,
+        model: &Model<T>,
+        algorithm: &A,
+    ) -> Result<Option<EvaluationRecord>, ModelError>
+    where
+        T: Float + Debug + Send + Sync + Sum,
+        A: Algorithm<T, Input = I, Output = L> + ?Sized,
+    {
+        let Some(report) = self.evaluate(model, algorithm)? else {
+            return Ok(None);
+        };
+        let record = EvaluationRecord {
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            report,
+        };
+        let mut history = self.history.lock()?;
+        if history.len() >= self.config.history_capacity.max(1) {
+            history.pop_front();
+        }
+        history.push_back(record.clone());
+        Ok(Some(record))
+    }
+
+    /// Spawns the periodic evaluation loop on the current runtime;
+    /// `on_record` is called with every successful evaluation.
+    pub fn spawn<T, A, F>(
+        self: Arc<Self>,
+        model: Arc<Model<T>>,
+        algorithm: Arc<A>,
+        metrics: Arc<Metrics>,
+        on_record: F,
+    ) -> tokio::task::JoinHandle<()>
+    where
+        T: Float + Debug + Send + Sync + Sum + 'static,
+        A: Algorithm<T, Input = I, Output = L> + 'static,
+        F: Fn(&EvaluationRecord) + Send + 'static,
+    {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.config.interval);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let (holdout, model, algorithm) = (self.clone(), model.clone(), algorithm.clone());
+                match tokio::task::spawn_blocking(move || holdout.run(&model, algorithm.as_ref()))
+                    .await
+                {
+                    Ok(Ok(Some(record))) => {
+                        metrics.increment("holdout_evaluations");
+                        metrics.set_gauge("holdout_mae", record.report.mae);
+                        metrics.set_gauge("holdout_accuracy", record.report.accuracy);
+                        on_record(&record);
+                    }
+                    Ok(Ok(None)) => {}
+                    _ => metrics.increment("holdout_evaluation_failures"),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::DummyAlgorithm;
+
+    #[test]
+    fn test_history_is_bounded() {
+        let holdout = Holdout::new(HoldoutConfig {
+            history_capacity: 2,
+            ..Default::default()
+        });
+        let model = Model::with_parameters(vec![1.0f32]);
+        assert_eq!(holdout.run(&model, &DummyAlgorithm).unwrap(), None);
+
+        holdout
+            .set_dataset(vec![LabeledSample {
+                input: 2.0,
+                label: 1.0,
+            }])
+            .unwrap();
+        for _ in 0..3 {
+            let record = holdout.run(&model, &DummyAlgorithm).unwrap().unwrap();
+            assert_eq!(record.report.mae, 1.0);
+        }
+        assert_eq!(holdout.history().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,223 @@
+use crate::algorithm::{Algorithm, Datum};
+use crate::bayesian::RegressionInput;
+use crate::errors::ModelError;
+use crate::metrics::distance::{Distance, RunningCovariance};
+use crate::model::Model;
+use num_traits::Float;
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// k-nearest-neighbors classifier over the last `window` labeled samples.
+///
+/// Inference returns the label with the largest sum of inverse-distance
+/// weights among the `k` nearest stored samples. The samples are kept in the
+/// model parameters as a ring buffer, so they are checkpointed and replicated
+/// like any other model: the number of stored samples, the next slot to
+/// overwrite, then `window` slots of the features followed by the label.
+#[derive(Debug, Clone)]
+pub struct WindowedKnn {
+    dimension: usize,
+    window: usize,
+    k: usize,
+    distance: Distance,
+}
+
+impl WindowedKnn {
+    /// Votes among the 5 nearest of the last `window` samples by Euclidean
+    /// distance.
+    pub fn new(dimension: usize, window: usize) -> Self {
+        WindowedKnn {
+            dimension,
+            window: window.max(1),
+            k: 5,
+            distance: Distance::Euclidean,
+        }
+    }
+
+    pub fn with_k(mut self, k: usize) -> Self {
+        self.k = k.max(1);
+        self
+    }
+
+    pub fn with_distance(mut self, distance: Distance) -> Self {
+        self.distance = distance;
+        self
+    }
+
+    /// A model with an empty window.
+    pub fn initial_model<T: Float + Debug + Send + Sync>(&self) -> Model<T> {
+        Model::with_parameters(vec![T::zero(); 2 + self.window * (self.dimension + 1)])
+    }
+
+    fn check<T>(&self, params: &[T], features: &[T]) -> Result<(), ModelError> {
+        if features.len() != self.dimension {
+            return Err(ModelError::DimensionMismatch {
+                expected: self.dimension,
+                actual: features.len(),
+            });
+        }
+        if params.len() != 2 + self.window * (self.dimension + 1) {
+            return Err(ModelError::InvalidInput(format!(
+                "the model must hold a window of {} samples",
+                self.window
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl<T> Algorithm<T> for WindowedKnn
+where
+    T: Float + Debug + Send + Sync + Sum + Datum,
+{
+    type Input = RegressionInput<T>;
+    type Output = T;
+
+    fn training_step(&self, model: &Model<T>, x: RegressionInput<T>) -> Result<(), ModelError> {
+        let label = x
+            .label
+            .ok_or_else(|| ModelError::InvalidInput("training samples need a label".to_string()))?;
+        self.check(&model.read_params(), &x.features)?;
+        let mut params = model.write_params();
+        let slot = params[1].to_usize().unwrap_or(0) % self.window;
+        let start = 2 + slot * (self.dimension + 1);
+        params[start..start + self.dimension].copy_from_slice(&x.features);
+        params[start + self.dimension] = label;
+        params[0] = (params[0] + T::one()).min(T::from(self.window).unwrap_or_else(T::one));
+        params[1] = T::from((slot + 1) % self.window).unwrap_or_else(T::zero);
+        Ok(())
+    }
+
+    fn inference_step(&self, model: &Model<T>, x: RegressionInput<T>) -> Result<T, ModelError> {
+        let params = model.read_params();
+        self.check(&params, &x.features)?;
+        let stored = params[0].to_usize().unwrap_or(0);
+        if stored == 0 {
+            return Err(ModelError::InvalidInput(
+                "no labeled samples in the window yet".to_string(),
+            ));
+        }
+
+        let samples = params[2..].chunks(self.dimension + 1).take(stored);
+        // Mahalanobis distances use the covariance of the samples in the window.
+        let mahalanobis = (self.distance == Distance::Mahalanobis).then(|| {
+            let mut covariance = RunningCovariance::new(self.dimension);
+            for sample in samples.clone() {
+                covariance.observe(&sample[..self.dimension]);
+            }
+            covariance.mahalanobis()
+        });
+        let mut neighbors: Vec<(T, T)> = samples
+            .map(|sample| {
+                let features = &sample[..self.dimension];
+                let distance = match &mahalanobis {
+                    Some(mahalanobis) => mahalanobis.between(features, &x.features),
+                    None => self.distance.between(features, &x.features),
+                };
+                (distance, sample[self.dimension])
+            })
+            .collect();
+        neighbors.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        neighbors.truncate(self.k);
+
+        let epsilon = T::epsilon().sqrt();
+        let mut votes: Vec<(T, T)> = Vec::new();
+        for (distance, label) in neighbors {
+            let weight = T::one() / (distance + epsilon);
+            match votes.iter_mut().find(|(l, _)| *l == label) {
+                Some((_, total)) => *total = *total + weight,
+                None => votes.push((label, weight)),
+            }
+        }
+        Ok(votes
+            .into_iter()
+            .fold((T::zero(), T::neg_infinity()), |best, vote| {
+                if vote.1 > best.1 {
+                    vote
+                } else {
+                    best
+                }
+            })
+            .0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(x: f64, y: f64, label: f64) -> RegressionInput<f64> {
+        RegressionInput {
+            features: vec![x, y],
+            label: Some(label),
+        }
+    }
+
+    #[test]
+    fn test_votes_follow_the_window() {
+        let algorithm = WindowedKnn::new(2, 20).with_k(3);
+        let model = algorithm.initial_model();
+        assert!(algorithm
+            .inference_step(&model, sample(0.0, 0.0, 0.0))
+            .is_err());
+
+        let mut points = crate::test::uniform_stream::<f64>(-0.5, 0.5, 9);
+        let mut feed = |left: f64, right: f64| {
+            for _ in 0..10 {
+                let (dx, dy) = (points.next().unwrap(), points.next().unwrap());
+                algorithm
+                    .training_step(&model, sample(-2.0 + dx, dy, left))
+                    .unwrap();
+                algorithm
+                    .training_step(&model, sample(2.0 + dx, dy, right))
+                    .unwrap();
+            }
+        };
+        feed(0.0, 1.0);
+        let predict = |x: f64| {
+            algorithm
+                .inference_step(&model, sample(x, 0.0, 0.0))
+                .unwrap()
+        };
+        assert_eq!((predict(-2.0), predict(2.0)), (0.0, 1.0));
+
+        // The labels swap; once the window has turned over, so do the votes.
+        feed(1.0, 0.0);
+        assert_eq!((predict(-2.0), predict(2.0)), (1.0, 0.0));
+
+        // A sample of the wrong dimension changes nothing.
+        let version = model.version();
+        let wide = RegressionInput {
+            features: vec![0.0, 0.0, 0.0],
+            label: Some(1.0),
+        };
+        assert!(algorithm.training_step(&model, wide).is_err());
+        assert_eq!(model.version(), version);
+
+        let manhattan = WindowedKnn::new(2, 20).with_distance(Distance::Manhattan);
+        assert_eq!(manhattan.distance.between(&[0.0, 0.0], &[1.0, -2.0]), 3.0);
+        assert!(Distance::Cosine.between(&[1.0, 0.0], &[2.0, 0.0]) < 1e-12);
+
+        // With features on very different scales, the Mahalanobis neighbor of
+        // (0, -0.8) is the one close in the narrow feature.
+        let feed = |distance: Distance| {
+            let algorithm = WindowedKnn::new(2, 4).with_k(1).with_distance(distance);
+            let model = algorithm.initial_model();
+            for (x, y, label) in [
+                (0.0, 1.0, 0.0),
+                (3.0, -1.0, 1.0),
+                (-20.0, 1.0, 0.0),
+                (20.0, -1.0, 1.0),
+            ] {
+                algorithm
+                    .training_step(&model, sample(x, y, label))
+                    .unwrap();
+            }
+            algorithm
+                .inference_step(&model, sample(0.0, -0.8, 0.0))
+                .unwrap()
+        };
+        assert_eq!(feed(Distance::Euclidean), 0.0);
+        assert_eq!(feed(Distance::Mahalanobis), 1.0);
+    }
+}
@@ -0,0 +1,192 @@
+use crate::errors::ModelError;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{PoisonError, RwLock};
+
+/// Maps the label values of a stream to class ids `0, 1, 2, ...` in the
+/// order the labels first appear, so multiclass algorithms can work with an
+/// open label set. A label keeps its id for the model's lifetime: the
+/// encoder is saved and loaded with the model (see [`Model::labels`]).
+///
+/// Labels are compared by their text, so the integer `3` and the string
+/// `"3"` are the same class.
+///
+/// [`Model::labels`]: crate::model::Model::labels
+///
+/// # Examples
+///
+/// ```
+/// use oml::labels::LabelEncoder;
+///
+/// let labels = LabelEncoder::new();
+/// assert_eq!(labels.encode("cat").unwrap(), 0);
+/// assert_eq!(labels.encode("dog").unwrap(), 1);
+/// assert_eq!(labels.encode("cat").unwrap(), 0);
+/// assert_eq!(labels.label(1).as_deref(), Some("dog"));
+/// ```
+#[derive(Debug, Default)]
+pub struct LabelEncoder {
+    classes: RwLock<Classes>,
+    /// Most classes accepted, for algorithms with a fixed number of outputs.
+    max_classes: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+struct Classes {
+    ids: HashMap<String, usize>,
+    /// Labels by class id.
+    labels: Vec<String>,
+}
+
+impl LabelEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An encoder knowing `labels` already, with the ids of their positions;
+    /// repeated labels keep their first id.
+    pub fn from_labels(labels: Vec<String>) -> Self {
+        let encoder = Self::new();
+        for label in labels {
+            let _ = encoder.encode(label);
+        }
+        encoder
+    }
+
+    /// Rejects labels beyond the first `max_classes`.
+    pub fn with_max_classes(mut self, max_classes: usize) -> Self {
+        self.max_classes = Some(max_classes);
+        self
+    }
+
+    pub fn max_classes(&self) -> Option<usize> {
+        self.max_classes
+    }
+
+    /// Id of `label`, assigning the next one if it is new.
+    pub fn encode(&self, label: impl Into<String>) -> Result<usize, ModelError> {
+        let label = label.into();
+        if let Some(id) = self.id(&label) {
+            return Ok(id);
+        }
+        let mut classes = self.classes.write().unwrap_or_else(PoisonError::into_inner);
+        if let Some(id) = classes.ids.get(&label) {
+            return Ok(*id);
+        }
+        let id = classes.labels.len();
+        if let Some(max) = self.max_classes.filter(|max| id >= *max) {
+            return Err(ModelError::InvalidInput(format!(
+                "label {} would exceed the limit of {} classes",
+                label, max
+            )));
+        }
+        classes.ids.insert(label.clone(), id);
+        classes.labels.push(label);
+        Ok(id)
+    }
+
+    /// Like [`LabelEncoder::encode`], for a label in a JSON request: a
+    /// string, an integer or a boolean.
+    pub fn encode_value(&self, label: &Value) -> Result<usize, ModelError> {
+        match label {
+            Value::String(text) => self.encode(text.as_str()),
+            Value::Number(n) if n.is_i64() || n.is_u64() => self.encode(n.to_string()),
+            Value::Bool(b) => self.encode(b.to_string()),
+            other => Err(ModelError::InvalidInput(format!(
+                "labels must be strings, integers or booleans, got {}",
+                other
+            ))),
+        }
+    }
+
+    /// Id of a label seen before.
+    pub fn id(&self, label: &str) -> Option<usize> {
+        self.classes
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .ids
+            .get(label)
+            .copied()
+    }
+
+    /// The label with class id `id`, e.g. to answer a prediction.
+    pub fn label(&self, id: usize) -> Option<String> {
+        self.classes
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .labels
+            .get(id)
+            .cloned()
+    }
+
+    /// Every label seen, by class id.
+    pub fn labels(&self) -> Vec<String> {
+        self.classes
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .labels
+            .clone()
+    }
+
+    /// Number of classes seen.
+    pub fn len(&self) -> usize {
+        self.classes
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .labels
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::handlers::AppState;
+    use crate::labels::LabelEncoder;
+    use crate::model::Model;
+    use crate::server::configure;
+    use crate::test::ScalingAlgorithm;
+    use actix_web::{test as actix_test, web, App};
+    use serde_json::json;
+
+    #[actix_rt::test]
+    async fn test_class_ids_stay_stable_across_save_and_load() {
+        let model = Model::with_parameters(vec![0.0f64; 3]).with_max_classes(3);
+        let labels = model.labels();
+        assert_eq!(labels.encode_value(&json!("spam")).unwrap(), 0);
+        assert_eq!(labels.encode_value(&json!(7)).unwrap(), 1);
+        assert_eq!(labels.encode("7").unwrap(), 1);
+        assert!(labels.encode_value(&json!(0.5)).is_err());
+        assert_eq!(labels.encode_value(&json!(true)).unwrap(), 2);
+        assert!(labels.encode("ham").is_err());
+        assert_eq!(labels.len(), 3);
+
+        let dir = tempfile::tempdir().unwrap();
+        for file in ["model.json", "model.bin"] {
+            let path = dir.path().join(file);
+            model.save(&path).unwrap();
+            let loaded: Model<f64> = Model::load(&path).unwrap();
+            assert_eq!(loaded.labels().labels(), vec!["spam", "7", "true"]);
+            assert_eq!(loaded.labels().id("true"), Some(2));
+        }
+
+        let state = AppState::new(model, ScalingAlgorithm);
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(configure::<f64, ScalingAlgorithm>),
+        )
+        .await;
+        let request = actix_test::TestRequest::get()
+            .uri("/model/labels")
+            .to_request();
+        let served: Vec<String> = actix_test::call_and_read_body_json(&app, request).await;
+        assert_eq!(served, vec!["spam", "7", "true"]);
+
+        let encoder = LabelEncoder::from_labels(vec!["a".into(), "b".into(), "a".into()]);
+        assert_eq!((encoder.len(), encoder.label(1).as_deref()), (2, Some("b")));
+    }
+}
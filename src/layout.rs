@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+/// A named block of parameters, such as the weights of one layer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParamGroup {
+    pub name: String,
+    /// Index of the group's first parameter in the flat parameter vector.
+    pub offset: usize,
+    /// Dimensions of the group, row-major like [`crate::tensors::Tensor`].
+    pub shape: Vec<usize>,
+}
+
+impl ParamGroup {
+    pub fn len(&self) -> usize {
+        self.shape.iter().product()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Position of the group in the flat parameter vector.
+    pub fn range(&self) -> std::ops::Range<usize> {
+        self.offset..self.offset + self.len()
+    }
+}
+
+/// Named parameter groups laid out back to back in a model's flat
+/// parameter vector, so algorithms with several layers (or weights and a
+/// bias) can address their parameters by name.
+///
+/// # Examples
+///
+/// ```
+/// use oml::layout::ModelLayout;
+/// use oml::model::Model;
+///
+/// let layout = ModelLayout::new().group("weights", &[2, 3]).group("bias", &[3]);
+/// let model: Model<f64> = Model::builder().layout(layout).build().unwrap();
+/// model.write_params().group_mut("bias").unwrap().fill(1.0);
+/// assert_eq!(model.read_params().group("bias"), Some(&[1.0, 1.0, 1.0][..]));
+/// assert_eq!(model.read_params().len(), 9);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelLayout {
+    groups: Vec<ParamGroup>,
+}
+
+impl ModelLayout {
+    /// A layout without groups, as used by models built from a bare vector.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a group of the given shape after the existing ones.
+    ///
+    /// # Panics
+    ///
+    /// If a group named `name` already exists.
+    pub fn group(mut self, name: impl Into<String>, shape: &[usize]) -> Self {
+        let name = name.into();
+        if self.get(&name).is_some() {
+            panic!("Parameter group {} is defined twice.", name);
+        }
+        self.groups.push(ParamGroup {
+            offset: self.len(),
+            name,
+            shape: shape.to_vec(),
+        });
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ParamGroup> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+
+    pub fn groups(&self) -> &[ParamGroup] {
+        &self.groups
+    }
+
+    /// Number of parameters covered by the groups.
+    pub fn len(&self) -> usize {
+        self.groups.last().map_or(0, |group| group.range().end)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+}
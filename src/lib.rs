@@ -1,6 +1,83 @@
+pub mod alerts;
 pub mod algorithm;
+pub mod archive;
+pub mod auth;
+pub mod bayesian;
+pub mod boosting;
+pub mod bootstrap;
+pub mod calibration;
+pub mod canary;
+pub mod catalog;
+pub mod challenger;
+pub mod checkpoint;
+pub mod class_priors;
+pub mod clipping;
+pub mod cluster;
+pub mod consistency;
+pub mod constraints;
+pub mod decay;
+pub mod dedup;
+pub mod delayed_labels;
+pub mod dry_run;
+pub mod early_stopping;
+pub mod encryption;
+pub mod ensemble;
 pub mod errors;
+pub mod evaluation;
+pub mod event_time;
+pub mod fallback;
+pub mod forecasting;
+pub mod gossip;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 pub mod handlers;
+pub mod holdout;
+pub mod knn;
+pub mod labels;
+pub mod layout;
+pub mod memory;
+pub mod metrics;
+pub mod middleware;
+#[cfg(feature = "mmap")]
+pub mod mmap;
 pub mod model;
+pub mod model_builder;
+#[cfg(feature = "onnx")]
+pub mod onnx;
+pub mod orchestrator;
+pub mod outliers;
+pub mod parameter_server;
+pub mod personalization;
+pub mod pipeline;
+#[cfg(feature = "wasm")]
+pub mod plugins;
+pub mod precision;
+pub mod prediction_log;
+pub mod quantization;
+pub mod recorder;
+pub mod registry;
+pub mod reload;
+pub mod replication;
+pub mod rotation;
+pub mod safetensors;
+pub mod schema;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod secure_aggregation;
+pub mod seed;
+pub mod self_test;
 pub mod server;
+pub mod service;
+pub mod shared_state;
+pub mod simulation;
+pub mod sketch;
+pub mod snapshot_diff;
+pub mod sparse;
+pub mod stale_reads;
 pub mod tensors;
+pub mod test;
+#[cfg(feature = "tower")]
+pub mod tower;
+pub mod tuning;
+pub mod wal;
+pub mod warmup;
@@ -1,12 +1,119 @@
 use oml::algorithm::DummyAlgorithm;
+use oml::bootstrap::ModelSource;
+use oml::encryption::EncryptionKey;
+use oml::handlers::{AppState, ResponseFormat};
 use oml::model::Model;
-use oml::server::run_server;
+use oml::prediction_log::PredictionLogConfig;
+use oml::recorder::RecorderConfig;
+use oml::server::ServerBuilder;
+use oml::simulation::{read_trace, simulate, SimulationConfig};
+use oml::stale_reads::ReadConsistency;
+
+const USAGE: &str = "usage: oml [--model <file>] [--model-url <url> [--model-sha256 <hex> | --allow-unverified-model]] \
+                     [--record <dir>] \
+                     [--log-predictions <dir>] [--log-sample-rate <rate>] \
+                     [--response-format envelope|bare] [--stale-reads <ms>] | \
+                     oml simulate --trace <file|dir> [--speed <factor>]";
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     let model: Model<f32> = Model::with_parameters(vec![1.0, 2.0]); // Create an instance of the Model for f32
     let algorithm = DummyAlgorithm;
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("simulate") {
+        return run_simulation(&args[1..], model, algorithm);
+    }
+
     // Start the server and pass the server data to it
-    run_server("127.0.0.1:8080", model, algorithm).await
+    let mut state = AppState::new(model, algorithm);
+    let mut builder = ServerBuilder::new().bind("127.0.0.1:8080");
+    // OML_ENCRYPTION_KEY encrypts the model file at rest; OML_ENCRYPTION_MIGRATE
+    // also reads a plain one while moving to encryption.
+    let key = EncryptionKey::from_env()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string()))?;
+    if let Some(key) = key {
+        builder = builder.with_encryption(key);
+    }
+    let mut prediction_log = None;
+    let mut model_url = None;
+    let mut model_sha256 = None;
+    let mut allow_unverified = false;
+    let mut sample_rate = 1.0;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--allow-unverified-model" {
+            allow_unverified = true;
+            continue;
+        }
+        match (arg.as_str(), args.next()) {
+            ("--model", Some(path)) => builder = builder.with_model_file(path),
+            ("--model-url", Some(url)) => model_url = Some(ModelSource::new(url)),
+            ("--model-sha256", Some(hex)) => model_sha256 = Some(hex),
+            ("--log-predictions", Some(dir)) => prediction_log = Some(dir),
+            ("--log-sample-rate", Some(rate)) => {
+                sample_rate = rate
+                    .parse()
+                    .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, USAGE))?;
+            }
+            ("--stale-reads", Some(ms)) => {
+                let refresh_ms = ms
+                    .parse()
+                    .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, USAGE))?;
+                state = state.with_read_consistency(ReadConsistency::Bounded { refresh_ms });
+            }
+            ("--record", Some(dir)) => {
+                state = state.with_trace_recorder(RecorderConfig::new(dir));
+            }
+            ("--response-format", Some(format)) => {
+                let format = match format.as_str() {
+                    "envelope" => ResponseFormat::Envelope,
+                    "bare" => ResponseFormat::Bare,
+                    _ => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, USAGE)),
+                };
+                state = state.with_response_format(format);
+            }
+            _ => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, USAGE)),
+        }
+    }
+    if let Some(mut source) = model_url {
+        source.sha256 = model_sha256.cloned();
+        source.allow_unverified = allow_unverified;
+        builder = builder.with_model_url(source);
+    }
+    if let Some(dir) = prediction_log {
+        let config = PredictionLogConfig::new(dir).with_sample_rate(sample_rate);
+        state = state.with_prediction_log(config);
+    }
+    builder.state(state).build()?.run().await
+}
+
+/// Replays a recorded trace against the model and prints the report as JSON.
+fn run_simulation(
+    args: &[String],
+    model: Model<f32>,
+    algorithm: DummyAlgorithm,
+) -> std::io::Result<()> {
+    let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, message);
+    let mut trace = None;
+    let mut config = SimulationConfig::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--trace", Some(path)) => trace = Some(path.clone()),
+            ("--speed", Some(speed)) => {
+                config.speed = speed
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid speed {}", speed)))?
+            }
+            _ => return Err(invalid(USAGE.to_string())),
+        }
+    }
+    let trace = trace.ok_or_else(|| invalid(USAGE.to_string()))?;
+
+    let records = read_trace::<f32, f32>(&trace).map_err(|e| invalid(e.to_string()))?;
+    let report =
+        simulate(&model, &algorithm, &records, &config).map_err(|e| invalid(e.to_string()))?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
 }
@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// Account of the serving model, its snapshots and moving average.
+pub const MODEL_ACCOUNT: &str = "model";
+/// Account of the offsets of personalized keys.
+pub const PERSONALIZATION_ACCOUNT: &str = "personalization";
+/// Account of the predictions awaiting a delayed label.
+pub const PENDING_PREDICTIONS_ACCOUNT: &str = "pending_predictions";
+/// Account of the samples remembered by the dedup window.
+pub const DEDUP_ACCOUNT: &str = "dedup";
+
+/// Bytes of memory used by the model and the stores that grow with traffic,
+/// by account, against a limit.
+///
+/// Usage is measured rather than tracked allocation by allocation: each
+/// account is set to the approximate footprint of its component, as
+/// reported by its `memory_bytes` method. The state enforces the budget
+/// through [`AppState::enforce_memory_budget`]: least recently used
+/// personalized keys are evicted first, and past that training samples and
+/// pending predictions are turned away until usage falls back under the
+/// limit.
+///
+/// [`AppState::enforce_memory_budget`]: crate::handlers::AppState::enforce_memory_budget
+///
+/// # Examples
+///
+/// ```
+/// use oml::memory::MemoryBudget;
+///
+/// let budget = MemoryBudget::new(1024);
+/// budget.set_usage("model", 800);
+/// budget.set_usage("dedup", 200);
+/// assert_eq!((budget.used_bytes(), budget.available_bytes()), (1000, 24));
+/// budget.set_usage("dedup", 300);
+/// assert!(budget.is_exceeded());
+/// ```
+#[derive(Debug)]
+pub struct MemoryBudget {
+    limit_bytes: usize,
+    usage: Mutex<BTreeMap<String, usize>>,
+}
+
+impl MemoryBudget {
+    pub fn new(limit_bytes: usize) -> Self {
+        MemoryBudget {
+            limit_bytes,
+            usage: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn limit_bytes(&self) -> usize {
+        self.limit_bytes
+    }
+
+    /// Records that `account` now uses `bytes`.
+    pub fn set_usage(&self, account: &str, bytes: usize) {
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        match usage.get_mut(account) {
+            Some(used) => *used = bytes,
+            None => {
+                usage.insert(account.to_string(), bytes);
+            }
+        }
+    }
+
+    /// Bytes used by each account.
+    pub fn usage(&self) -> BTreeMap<String, usize> {
+        self.usage.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Bytes used by all accounts.
+    pub fn used_bytes(&self) -> usize {
+        self.usage
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .sum()
+    }
+
+    /// Bytes left before the limit is reached.
+    pub fn available_bytes(&self) -> usize {
+        self.limit_bytes.saturating_sub(self.used_bytes())
+    }
+
+    pub fn is_exceeded(&self) -> bool {
+        self.used_bytes() > self.limit_bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::handlers::AppState;
+    use crate::model::Model;
+    use crate::personalization::PersonalizationConfig;
+    use crate::service::{OmlService, ServiceError};
+    use crate::test::ScalingAlgorithm;
+
+    #[actix_rt::test]
+    async fn test_budget_evicts_keys_then_rejects_training() {
+        let model = Model::with_parameters(vec![1.0f64; 16]);
+        let model_bytes = model.memory_bytes();
+        let state = AppState::new(model, ScalingAlgorithm)
+            .with_personalization(PersonalizationConfig::new(100))
+            .with_memory_budget(model_bytes + 1024);
+        let service = OmlService::new(state);
+        for key in 0..50 {
+            service
+                .train_for(&key.to_string(), 1.0, false)
+                .await
+                .unwrap();
+        }
+        let state = service.state();
+        let personal = state.personalization.as_ref().unwrap();
+        // Keys were evicted to stay within the budget, the oldest first.
+        assert!(personal.len().unwrap() < 50);
+        assert!(personal.offsets("49").unwrap().is_some());
+        assert!(personal.offsets("0").unwrap().is_none());
+        assert!(state.metrics.counter("memory_evictions") > 0);
+        assert!(state.metrics.gauge("memory_used_bytes").unwrap() <= (model_bytes + 1024) as f64);
+
+        let state = AppState::new(Model::with_parameters(vec![1.0f64; 16]), ScalingAlgorithm)
+            .with_memory_budget(64);
+        let service = OmlService::new(state);
+        assert!(matches!(
+            service.train(1.0, false).await,
+            Err(ServiceError::Unavailable(_))
+        ));
+        assert_eq!(service.metrics().counters["memory_rejections"], 1);
+        assert_eq!(service.metrics().gauges["memory_model_bytes"], 128.0);
+    }
+}
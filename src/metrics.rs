@@ -0,0 +1,200 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Mutex, PoisonError};
+
+pub mod auc;
+pub mod classification;
+pub mod distance;
+pub mod segments;
+
+use auc::AucEstimator;
+use classification::{ClassificationMetrics, ConfusionTracker};
+use segments::{SegmentMetrics, SegmentTracker, MAX_SEGMENTS, OTHER_SEGMENT};
+
+/// Process-wide registry of named counters and gauges.
+///
+/// Handlers and subsystems record into it and `GET /metrics` exposes a
+/// snapshot. Metric names are free-form strings; by convention they are
+/// `snake_case` and prefixed with the subsystem that owns them.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    counters: Mutex<BTreeMap<String, u64>>,
+    gauges: Mutex<BTreeMap<String, f64>>,
+    classification: Mutex<ConfusionTracker>,
+    auc: Mutex<AucEstimator>,
+    segments: Mutex<BTreeMap<String, SegmentTracker>>,
+}
+
+/// A point-in-time copy of every metric in a [`Metrics`] registry.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    pub counters: BTreeMap<String, u64>,
+    pub gauges: BTreeMap<String, f64>,
+    /// Present once at least one labeled classification outcome was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classification: Option<ClassificationMetrics>,
+    /// Sliding-window ROC-AUC, once both classes were scored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roc_auc: Option<f64>,
+    /// Metrics sliced by the segment requests were tagged with.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub segments: BTreeMap<String, SegmentMetrics>,
+}
+
+impl Metrics {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Increments the counter `name` by one.
+    pub fn increment(&self, name: &str) {
+        self.add(name, 1);
+    }
+
+    /// Increments the counter `name` by `value`, creating it if needed.
+    pub fn add(&self, name: &str, value: u64) {
+        let mut counters = self.counters.lock().unwrap_or_else(PoisonError::into_inner);
+        *counters.entry(name.to_string()).or_insert(0) += value;
+    }
+
+    /// Sets the gauge `name` to `value`.
+    pub fn set_gauge(&self, name: &str, value: f64) {
+        let mut gauges = self.gauges.lock().unwrap_or_else(PoisonError::into_inner);
+        gauges.insert(name.to_string(), value);
+    }
+
+    /// Returns the current value of counter `name` (zero if never recorded).
+    pub fn counter(&self, name: &str) -> u64 {
+        let counters = self.counters.lock().unwrap_or_else(PoisonError::into_inner);
+        counters.get(name).copied().unwrap_or(0)
+    }
+
+    /// Returns the current value of gauge `name`, if it has been set.
+    pub fn gauge(&self, name: &str) -> Option<f64> {
+        let gauges = self.gauges.lock().unwrap_or_else(PoisonError::into_inner);
+        gauges.get(name).copied()
+    }
+
+    /// Records a labeled classification outcome in the confusion matrix.
+    pub fn record_classification(&self, actual: i64, predicted: i64) {
+        self.classification
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .record(actual, predicted);
+    }
+
+    /// Records a probabilistic classifier's score for a binary outcome.
+    pub fn record_score(&self, score: f64, positive: bool) {
+        self.auc
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .record(score, positive);
+    }
+
+    /// Counts a prediction served to `segment`.
+    pub fn record_segment_inference(&self, segment: &str) {
+        self.with_segment(segment, SegmentTracker::record_inference);
+    }
+
+    /// Records a labeled outcome of `segment` in its sliced metrics.
+    pub fn record_segment_outcome(&self, segment: &str, prediction: f64, label: f64) {
+        self.with_segment(segment, |tracker| tracker.record_outcome(prediction, label));
+    }
+
+    /// Returns the sliced metrics of `segment`, if it has any.
+    pub fn segment(&self, segment: &str) -> Option<SegmentMetrics> {
+        let segments = self.segments.lock().unwrap_or_else(PoisonError::into_inner);
+        segments.get(segment).map(SegmentTracker::metrics)
+    }
+
+    /// Runs `record` on the tracker of `segment`, or of [`OTHER_SEGMENT`]
+    /// once it would take the last of the [`MAX_SEGMENTS`] slots.
+    fn with_segment(&self, segment: &str, record: impl FnOnce(&mut SegmentTracker)) {
+        let mut segments = self.segments.lock().unwrap_or_else(PoisonError::into_inner);
+        let segment = match segments.contains_key(segment) || segments.len() + 1 < MAX_SEGMENTS {
+            true => segment,
+            false => OTHER_SEGMENT,
+        };
+        record(segments.entry(segment.to_string()).or_default());
+    }
+
+    /// Copies every counter and gauge into a serializable snapshot.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            counters: self
+                .counters
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+            gauges: self
+                .gauges
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+            classification: {
+                let tracker = self
+                    .classification
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner);
+                (!tracker.is_empty()).then(|| tracker.metrics())
+            },
+            roc_auc: self
+                .auc
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .auc(),
+            segments: self
+                .segments
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .iter()
+                .map(|(name, tracker)| (name.clone(), tracker.metrics()))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_and_gauges() {
+        let metrics = Metrics::new();
+        metrics.increment("training_steps");
+        metrics.add("training_steps", 2);
+        metrics.set_gauge("loss", 0.5);
+
+        assert_eq!(metrics.counter("training_steps"), 3);
+        assert_eq!(metrics.counter("missing"), 0);
+        assert_eq!(metrics.gauge("loss"), Some(0.5));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.counters["training_steps"], 3);
+        assert_eq!(snapshot.gauges["loss"], 0.5);
+    }
+
+    #[test]
+    fn test_outcomes_sliced_by_segment() {
+        let metrics = Metrics::new();
+        metrics.record_segment_inference("de");
+        metrics.record_segment_outcome("de", 0.9, 1.0);
+        metrics.record_segment_outcome("de", 0.2, 0.0);
+        metrics.record_segment_outcome("fr", 0.8, 0.0);
+
+        let de = metrics.segment("de").unwrap();
+        assert_eq!((de.inferences, de.samples), (1, 2));
+        assert!((de.mae.unwrap() - 0.15).abs() < 1e-12);
+        assert_eq!(de.roc_auc, Some(1.0));
+        let fr = metrics.segment("fr").unwrap();
+        assert_eq!(fr.classification.unwrap().cumulative.accuracy, 0.0);
+        assert_eq!(metrics.snapshot().segments.len(), 2);
+
+        for i in 0..MAX_SEGMENTS {
+            metrics.record_segment_inference(&format!("s{}", i));
+        }
+        assert_eq!(metrics.snapshot().segments.len(), MAX_SEGMENTS);
+        assert_eq!(metrics.segment(OTHER_SEGMENT).unwrap().inferences, 3);
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::VecDeque;
+
+/// Streaming ROC-AUC over the last `window` scored binary outcomes.
+///
+/// AUC is computed from ranks (Mann-Whitney U), with tied scores sharing
+/// their average rank. Each estimate sorts the window, so its cost is
+/// `O(window log window)` and independent of how many samples were seen.
+#[derive(Debug, Clone)]
+pub struct AucEstimator {
+    window: usize,
+    samples: VecDeque<(f64, bool)>,
+}
+
+impl Default for AucEstimator {
+    fn default() -> Self {
+        AucEstimator::new(super::classification::DEFAULT_WINDOW)
+    }
+}
+
+impl AucEstimator {
+    pub fn new(window: usize) -> Self {
+        AucEstimator {
+            window: window.max(1),
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Records the model's score for a sample and whether it was a positive.
+    /// Non-finite scores are ignored.
+    pub fn record(&mut self, score: f64, positive: bool) {
+        if !score.is_finite() {
+            return;
+        }
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((score, positive));
+    }
+
+    /// Area under the ROC curve, or `None` until the window holds both classes.
+    pub fn auc(&self) -> Option<f64> {
+        let positives = self.samples.iter().filter(|(_, p)| *p).count();
+        let negatives = self.samples.len() - positives;
+        if positives == 0 || negatives == 0 {
+            return None;
+        }
+        let mut sorted: Vec<(f64, bool)> = self.samples.iter().copied().collect();
+        sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut positive_rank_sum = 0.0;
+        let mut start = 0;
+        while start < sorted.len() {
+            let mut end = start;
+            while end + 1 < sorted.len() && sorted[end + 1].0 == sorted[start].0 {
+                end += 1;
+            }
+            // Ranks are 1-based; tied scores share the average rank of the run.
+            let rank = (start + end) as f64 / 2.0 + 1.0;
+            let tied_positives = sorted[start..=end].iter().filter(|(_, p)| *p).count();
+            positive_rank_sum += rank * tied_positives as f64;
+            start = end + 1;
+        }
+        let (p, n) = (positives as f64, negatives as f64);
+        Some((positive_rank_sum - p * (p + 1.0) / 2.0) / (p * n))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auc_from_ranks() {
+        let mut estimator = AucEstimator::new(10);
+        estimator.record(0.9, true);
+        assert_eq!(estimator.auc(), None);
+        estimator.record(0.1, false);
+        assert_eq!(estimator.auc(), Some(1.0));
+        estimator.record(0.8, false);
+        estimator.record(0.4, true);
+        // Pairs (pos, neg): (0.9,0.1) (0.9,0.8) (0.4,0.1) correct, (0.4,0.8) wrong.
+        assert_eq!(estimator.auc(), Some(0.75));
+
+        let mut ties = AucEstimator::new(10);
+        ties.record(0.5, true);
+        ties.record(0.5, false);
+        assert_eq!(ties.auc(), Some(0.5));
+    }
+}
@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+/// Number of most recent outcomes kept for the sliding-window view by default.
+pub const DEFAULT_WINDOW: usize = 1000;
+
+/// Precision, recall and F1 of one class.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClassScores {
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+    /// Number of samples whose actual class is this one.
+    pub support: u64,
+}
+
+/// Confusion matrix and the classification report derived from it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClassificationReport {
+    pub samples: u64,
+    pub accuracy: f64,
+    pub macro_f1: f64,
+    /// `matrix[actual][predicted]` counts.
+    pub matrix: BTreeMap<i64, BTreeMap<i64, u64>>,
+    pub per_class: BTreeMap<i64, ClassScores>,
+}
+
+/// Both views exposed under `classification` in `GET /metrics`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClassificationMetrics {
+    pub cumulative: ClassificationReport,
+    pub window: ClassificationReport,
+}
+
+/// Rolling confusion matrix over `(actual, predicted)` class ids.
+///
+/// Keeps a cumulative matrix since startup and the last `window` outcomes
+/// for a view that follows drift.
+#[derive(Debug, Clone)]
+pub struct ConfusionTracker {
+    window: usize,
+    cumulative: BTreeMap<(i64, i64), u64>,
+    recent: VecDeque<(i64, i64)>,
+}
+
+impl Default for ConfusionTracker {
+    fn default() -> Self {
+        ConfusionTracker::new(DEFAULT_WINDOW)
+    }
+}
+
+impl ConfusionTracker {
+    pub fn new(window: usize) -> Self {
+        ConfusionTracker {
+            window: window.max(1),
+            cumulative: BTreeMap::new(),
+            recent: VecDeque::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cumulative.is_empty()
+    }
+
+    pub fn record(&mut self, actual: i64, predicted: i64) {
+        *self.cumulative.entry((actual, predicted)).or_insert(0) += 1;
+        if self.recent.len() == self.window {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((actual, predicted));
+    }
+
+    pub fn metrics(&self) -> ClassificationMetrics {
+        let mut window = BTreeMap::new();
+        for &cell in &self.recent {
+            *window.entry(cell).or_insert(0) += 1;
+        }
+        ClassificationMetrics {
+            cumulative: report(&self.cumulative),
+            window: report(&window),
+        }
+    }
+}
+
+fn ratio(numerator: u64, denominator: u64) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+fn report(cells: &BTreeMap<(i64, i64), u64>) -> ClassificationReport {
+    let mut matrix: BTreeMap<i64, BTreeMap<i64, u64>> = BTreeMap::new();
+    let mut actual_totals: BTreeMap<i64, u64> = BTreeMap::new();
+    let mut predicted_totals: BTreeMap<i64, u64> = BTreeMap::new();
+    let mut samples = 0;
+    let mut correct = 0;
+    for (&(actual, predicted), &count) in cells {
+        matrix.entry(actual).or_default().insert(predicted, count);
+        *actual_totals.entry(actual).or_insert(0) += count;
+        *predicted_totals.entry(predicted).or_insert(0) += count;
+        samples += count;
+        if actual == predicted {
+            correct += count;
+        }
+    }
+
+    let classes: Vec<i64> = actual_totals
+        .keys()
+        .chain(predicted_totals.keys())
+        .copied()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let per_class: BTreeMap<i64, ClassScores> = classes
+        .iter()
+        .map(|&class| {
+            let hits = cells.get(&(class, class)).copied().unwrap_or(0);
+            let support = actual_totals.get(&class).copied().unwrap_or(0);
+            let precision = ratio(hits, predicted_totals.get(&class).copied().unwrap_or(0));
+            let recall = ratio(hits, support);
+            let f1 = if precision + recall > 0.0 {
+                2.0 * precision * recall / (precision + recall)
+            } else {
+                0.0
+            };
+            let scores = ClassScores {
+                precision,
+                recall,
+                f1,
+                support,
+            };
+            (class, scores)
+        })
+        .collect();
+    let macro_f1 = if per_class.is_empty() {
+        0.0
+    } else {
+        per_class.values().map(|s| s.f1).sum::<f64>() / per_class.len() as f64
+    };
+
+    ClassificationReport {
+        samples,
+        accuracy: ratio(correct, samples),
+        macro_f1,
+        matrix,
+        per_class,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cumulative_and_window_reports() {
+        let mut tracker = ConfusionTracker::new(2);
+        tracker.record(1, 1);
+        tracker.record(1, 0);
+        tracker.record(0, 0);
+        tracker.record(0, 0);
+
+        let metrics = tracker.metrics();
+        let cumulative = &metrics.cumulative;
+        assert_eq!(cumulative.samples, 4);
+        assert_eq!(cumulative.accuracy, 0.75);
+        assert_eq!(cumulative.matrix[&1][&0], 1);
+        assert_eq!(cumulative.per_class[&1].precision, 1.0);
+        assert_eq!(cumulative.per_class[&1].recall, 0.5);
+        assert!((cumulative.per_class[&0].precision - 2.0 / 3.0).abs() < 1e-12);
+
+        // Only the last two (both correct class-0) outcomes remain in the window.
+        assert_eq!(metrics.window.samples, 2);
+        assert_eq!(metrics.window.accuracy, 1.0);
+        assert!(!metrics.window.per_class.contains_key(&1));
+    }
+}
@@ -0,0 +1,257 @@
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+
+/// Distance between two feature vectors, chosen in an algorithm's
+/// configuration (`WindowedKnn::with_distance`) and serialized by name.
+///
+/// [`Distance::between`] measures Mahalanobis distance against the identity
+/// covariance, which is the Euclidean distance; measure it against observed
+/// data through [`RunningCovariance::mahalanobis`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Distance {
+    Euclidean,
+    Manhattan,
+    /// One minus the cosine similarity.
+    Cosine,
+    /// Euclidean distance after decorrelating and rescaling the features by
+    /// their covariance.
+    Mahalanobis,
+}
+
+impl Distance {
+    pub fn between<T: Float>(&self, a: &[T], b: &[T]) -> T {
+        let pairs = a.iter().zip(b);
+        match self {
+            Distance::Euclidean | Distance::Mahalanobis => pairs
+                .fold(T::zero(), |sum, (x, y)| sum + (*x - *y) * (*x - *y))
+                .sqrt(),
+            Distance::Manhattan => pairs.fold(T::zero(), |sum, (x, y)| sum + (*x - *y).abs()),
+            Distance::Cosine => {
+                let (dot, na, nb) = pairs.fold((T::zero(), T::zero(), T::zero()), |acc, (x, y)| {
+                    (acc.0 + *x * *y, acc.1 + *x * *x, acc.2 + *y * *y)
+                });
+                let norm = (na * nb).sqrt();
+                match norm > T::zero() {
+                    true => T::one() - dot / norm,
+                    false => T::one(),
+                }
+            }
+        }
+    }
+}
+
+/// Mean and covariance of a stream of feature vectors, updated one sample at
+/// a time (Welford's algorithm), for Mahalanobis distances.
+///
+/// # Examples
+///
+/// ```
+/// use oml::metrics::distance::RunningCovariance;
+///
+/// let mut covariance = RunningCovariance::new(2);
+/// for x in [[0.0, 0.0], [1.0, 1.0], [2.0, 2.1], [3.0, 2.9]] {
+///     covariance.observe(&x);
+/// }
+/// let mahalanobis = covariance.mahalanobis();
+/// // Along the correlation a step is short; across it, long.
+/// assert!(mahalanobis.between(&[0.0, 0.0], &[1.0, 1.0]) < 1.0);
+/// assert!(mahalanobis.between(&[0.0, 0.0], &[1.0, -1.0]) > 5.0);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunningCovariance<T> {
+    count: usize,
+    mean: Vec<T>,
+    /// Sum of the outer products of the deviations, row-major.
+    comoment: Vec<T>,
+}
+
+impl<T: Float> RunningCovariance<T> {
+    pub fn new(dimension: usize) -> Self {
+        RunningCovariance {
+            count: 0,
+            mean: vec![T::zero(); dimension],
+            comoment: vec![T::zero(); dimension * dimension],
+        }
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.mean.len()
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn mean(&self) -> &[T] {
+        &self.mean
+    }
+
+    /// Adds a sample. Samples of another dimension or with non-finite
+    /// features are ignored.
+    pub fn observe(&mut self, x: &[T]) {
+        if x.len() != self.dimension() || x.iter().any(|v| !v.is_finite()) {
+            return;
+        }
+        self.count += 1;
+        let n = T::from(self.count).unwrap_or_else(T::one);
+        let before: Vec<T> = x.iter().zip(&self.mean).map(|(x, m)| *x - *m).collect();
+        for (m, d) in self.mean.iter_mut().zip(&before) {
+            *m = *m + *d / n;
+        }
+        let after: Vec<T> = x.iter().zip(&self.mean).map(|(x, m)| *x - *m).collect();
+        let dimension = self.dimension();
+        for (i, row) in self.comoment.chunks_mut(dimension).enumerate() {
+            for (c, a) in row.iter_mut().zip(&after) {
+                *c = *c + before[i] * *a;
+            }
+        }
+    }
+
+    /// Sample covariance matrix, row-major; the identity until two samples
+    /// were observed.
+    pub fn covariance(&self) -> Vec<T> {
+        let dimension = self.dimension();
+        match self.count {
+            0 | 1 => (0..dimension * dimension)
+                .map(|i| match i % (dimension + 1) == 0 {
+                    true => T::one(),
+                    false => T::zero(),
+                })
+                .collect(),
+            count => {
+                let n = T::from(count - 1).unwrap_or_else(T::one);
+                self.comoment.iter().map(|c| *c / n).collect()
+            }
+        }
+    }
+
+    /// Mahalanobis distance under the current covariance, factored once so
+    /// it can measure many pairs.
+    ///
+    /// The diagonal is slightly regularized, so features that never varied
+    /// or that are exact combinations of others still get a finite distance.
+    pub fn mahalanobis(&self) -> Mahalanobis<T> {
+        let dimension = self.dimension();
+        let mut covariance = self.covariance();
+        let trace = (0..dimension).fold(T::zero(), |sum, i| sum + covariance[i * (dimension + 1)]);
+        let scale = match dimension {
+            0 => T::one(),
+            d => (trace / T::from(d).unwrap_or_else(T::one)).max(T::one()),
+        };
+        let ridge = T::epsilon().sqrt() * scale;
+        for i in 0..dimension {
+            covariance[i * (dimension + 1)] = covariance[i * (dimension + 1)] + ridge;
+        }
+
+        // Cholesky factor, lower triangular.
+        let mut lower = vec![T::zero(); dimension * dimension];
+        for i in 0..dimension {
+            for j in 0..=i {
+                let dot = (0..j).fold(T::zero(), |sum, k| {
+                    sum + lower[i * dimension + k] * lower[j * dimension + k]
+                });
+                let value = covariance[i * dimension + j] - dot;
+                lower[i * dimension + j] = match i == j {
+                    true => value.max(ridge).sqrt(),
+                    false => value / lower[j * dimension + j],
+                };
+            }
+        }
+        Mahalanobis { dimension, lower }
+    }
+}
+
+/// Mahalanobis distance under a fixed covariance, from
+/// [`RunningCovariance::mahalanobis`].
+#[derive(Debug, Clone)]
+pub struct Mahalanobis<T> {
+    dimension: usize,
+    lower: Vec<T>,
+}
+
+impl<T: Float> Mahalanobis<T> {
+    /// The norm of `L⁻¹ (a - b)`, where `L` is the Cholesky factor of the
+    /// covariance.
+    pub fn between(&self, a: &[T], b: &[T]) -> T {
+        let n = self.dimension;
+        let mut solved: Vec<T> = Vec::with_capacity(n);
+        for (i, (x, y)) in a.iter().zip(b).take(n).enumerate() {
+            let row = &self.lower[i * n..i * n + i];
+            let dot = row
+                .iter()
+                .zip(&solved)
+                .fold(T::zero(), |sum, (l, s)| sum + *l * *s);
+            solved.push((*x - *y - dot) / self.lower[i * n + i]);
+        }
+        solved.iter().fold(T::zero(), |sum, s| sum + *s * *s).sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_running_covariance_matches_batch_and_scales_distances() {
+        let samples = [
+            [1.0, 2.0, 0.5],
+            [2.0, 3.5, -0.5],
+            [0.0, 1.0, 1.0],
+            [4.0, 6.0, 0.0],
+        ];
+        let mut running = RunningCovariance::new(3);
+        for x in &samples {
+            running.observe(x);
+        }
+        running.observe(&[1.0, f64::NAN, 0.0]);
+        running.observe(&[1.0, 2.0]);
+        assert_eq!(running.count(), 4);
+
+        let n = samples.len() as f64;
+        let mean: Vec<f64> = (0..3)
+            .map(|j| samples.iter().map(|x| x[j]).sum::<f64>() / n)
+            .collect();
+        let covariance = running.covariance();
+        for i in 0..3 {
+            assert!((running.mean()[i] - mean[i]).abs() < 1e-12);
+            for j in 0..3 {
+                let batch = samples
+                    .iter()
+                    .map(|x| (x[i] - mean[i]) * (x[j] - mean[j]))
+                    .sum::<f64>()
+                    / (n - 1.0);
+                assert!((covariance[i * 3 + j] - batch).abs() < 1e-12);
+            }
+        }
+
+        // Independent features with standard deviations 2 and 0.5: a distance
+        // counts standard deviations, unlike the Euclidean one.
+        let mut scaled = RunningCovariance::new(2);
+        for x in [[2.0, 0.5], [-2.0, -0.5], [2.0, -0.5], [-2.0, 0.5]] {
+            scaled.observe(&x);
+        }
+        let identity = RunningCovariance::<f64>::new(2).mahalanobis();
+        let mahalanobis = scaled.mahalanobis();
+        let (origin, wide, narrow) = ([0.0, 0.0], [2.0, 0.0], [0.0, 2.0]);
+        assert!((identity.between(&origin, &wide) - 2.0).abs() < 1e-6);
+        assert_eq!(Distance::Mahalanobis.between(&origin, &wide), 2.0);
+        // var(x) = 16 / 3, var(y) = 1 / 3.
+        let expected = (3.0f64 / 4.0).sqrt();
+        assert!((mahalanobis.between(&origin, &wide) - expected).abs() < 1e-6);
+        assert!((mahalanobis.between(&origin, &narrow) - 4.0 * expected).abs() < 1e-6);
+
+        // A feature that never varies still gives finite distances.
+        let mut constant = RunningCovariance::new(2);
+        constant.observe(&[1.0, 3.0]);
+        constant.observe(&[2.0, 3.0]);
+        assert!(constant
+            .mahalanobis()
+            .between(&[0.0, 0.0], &[1.0, 1.0])
+            .is_finite());
+        assert_eq!(
+            serde_json::to_string(&Distance::Mahalanobis).unwrap(),
+            "\"mahalanobis\""
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use super::auc::AucEstimator;
+use super::classification::{ClassificationMetrics, ConfusionTracker};
+use serde::{Deserialize, Serialize};
+
+/// Request header tagging inference and evaluation requests with the
+/// segment (country, device, ...) their metrics are sliced by.
+pub const SEGMENT_HEADER: &str = "X-Oml-Segment";
+
+/// Most segments tracked, [`OTHER_SEGMENT`] included, which collects the
+/// requests of segments past the others.
+pub const MAX_SEGMENTS: usize = 256;
+
+/// Segment collecting the requests of segments past [`MAX_SEGMENTS`].
+pub const OTHER_SEGMENT: &str = "other";
+
+/// Metrics of one segment, under `segments` in `GET /metrics`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SegmentMetrics {
+    /// Predictions served to the segment.
+    pub inferences: u64,
+    /// Labeled outcomes recorded for the segment.
+    pub samples: u64,
+    /// Mean absolute error of the labeled outcomes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mae: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classification: Option<ClassificationMetrics>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roc_auc: Option<f64>,
+}
+
+/// Running metrics of one segment: the counters, the mean absolute error,
+/// the confusion matrix and ROC-AUC of its outcomes.
+#[derive(Debug, Clone, Default)]
+pub struct SegmentTracker {
+    inferences: u64,
+    samples: u64,
+    scored: u64,
+    absolute_error: f64,
+    classification: ConfusionTracker,
+    auc: AucEstimator,
+}
+
+impl SegmentTracker {
+    pub fn record_inference(&mut self) {
+        self.inferences += 1;
+    }
+
+    /// Records a labeled outcome; outcomes without a finite prediction and
+    /// label only count as samples.
+    pub fn record_outcome(&mut self, prediction: f64, label: f64) {
+        self.samples += 1;
+        if !prediction.is_finite() || !label.is_finite() {
+            return;
+        }
+        self.scored += 1;
+        self.absolute_error += (prediction - label).abs();
+        self.classification
+            .record(label.round() as i64, prediction.round() as i64);
+        if label == 0.0 || label == 1.0 {
+            self.auc.record(prediction, label == 1.0);
+        }
+    }
+
+    pub fn metrics(&self) -> SegmentMetrics {
+        SegmentMetrics {
+            inferences: self.inferences,
+            samples: self.samples,
+            mae: (self.scored > 0).then(|| self.absolute_error / self.scored as f64),
+            classification: (!self.classification.is_empty())
+                .then(|| self.classification.metrics()),
+            roc_auc: self.auc.auc(),
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use crate::errors::ModelError;
+use std::sync::Arc;
+
+/// Hooks around the inference and training routes, for cross-cutting
+/// concerns (custom logging, feature enrichment, shadow copies) that should
+/// not require forking the handlers.
+///
+/// Registered middleware runs in registration order. Every method has a
+/// pass-through default, so implementations override only what they need.
+/// `on_inference_request` and `on_training_applied` run on the blocking
+/// pool; `on_prediction` runs on the request's async worker and should stay
+/// cheap (hand heavier work, such as shadow calls, to a spawned task).
+pub trait OmlMiddleware<I, O>: Send + Sync {
+    /// Called with every inference input before the algorithm sees it;
+    /// returns the input to use. An error rejects the request.
+    fn on_inference_request(&self, input: I) -> Result<I, ModelError> {
+        Ok(input)
+    }
+
+    /// Called with every prediction before it is returned; returns the
+    /// prediction to serve. An error rejects the request.
+    fn on_prediction(&self, _input: &I, prediction: O) -> Result<O, ModelError> {
+        Ok(prediction)
+    }
+
+    /// Called after a training step was applied to the model.
+    fn on_training_applied(&self, _input: &I) {}
+}
+
+/// Middleware as stored in the application state.
+pub type SharedMiddleware<I, O> = Arc<dyn OmlMiddleware<I, O>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::DummyAlgorithm;
+    use crate::handlers::{AppState, PredictionEnvelope};
+    use crate::model::Model;
+    use crate::server::configure;
+    use actix_web::{test, web, App};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Doubles inputs, counts predictions and rounds them.
+    #[derive(Default)]
+    struct Rounding {
+        predictions: AtomicUsize,
+    }
+
+    impl OmlMiddleware<f32, f32> for Rounding {
+        fn on_inference_request(&self, input: f32) -> Result<f32, ModelError> {
+            Ok(input * 2.0)
+        }
+
+        fn on_prediction(&self, _input: &f32, prediction: f32) -> Result<f32, ModelError> {
+            self.predictions.fetch_add(1, Ordering::SeqCst);
+            Ok(prediction.round())
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_middleware_wraps_inference() {
+        let rounding = Arc::new(Rounding::default());
+        let state = web::Data::new(
+            AppState::new(Model::with_parameters(vec![0.5f32, 0.6]), DummyAlgorithm)
+                .with_middleware(rounding.clone()),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .configure(configure::<f32, DummyAlgorithm>),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/inference")
+            .set_json(1.0f32)
+            .to_request();
+        let response: PredictionEnvelope<f32> = test::call_and_read_body_json(&app, req).await;
+        // (0.5 + 0.6) * 2.0 = 2.2, rounded.
+        assert_eq!(response.prediction, 2.0);
+        assert_eq!(rounding.predictions.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,205 @@
+use crate::errors::ModelError;
+use memmap2::MmapMut;
+use std::fs::{File, OpenOptions};
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Parameter types that can be stored in a [`MappedParameters`] file: plain
+/// values valid for any bit pattern.
+///
+/// # Safety
+///
+/// Implementors must be `Copy` types without padding, pointers or invalid
+/// bit patterns.
+pub unsafe trait MappedScalar: Copy + Send + Sync + 'static {}
+
+unsafe impl MappedScalar for f32 {}
+unsafe impl MappedScalar for f64 {}
+
+/// Parameters of a model too large to keep comfortably in RAM, stored in a
+/// file mapped into memory and paged in by the OS as they are touched.
+///
+/// The file holds the parameters as a bare array in native byte order, so
+/// it is only portable between machines of the same endianness. Unlike the
+/// dense parameters of a [`Model`](crate::model::Model), there are no
+/// snapshots: a write locks out readers while it runs, and writes reach the
+/// file when the OS flushes them or on [`MappedParameters::flush`].
+///
+/// Attached to a model through [`Model::mapped`](crate::model::Model::mapped).
+///
+/// # Examples
+///
+/// ```
+/// use oml::mmap::MappedParameters;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let params = MappedParameters::<f32>::create(dir.path().join("w.bin"), 1 << 20).unwrap();
+/// params.update(|p| p[7] = 1.5);
+/// assert_eq!((params.read()[7], params.len()), (1.5, 1 << 20));
+/// ```
+#[derive(Debug)]
+pub struct MappedParameters<T> {
+    path: PathBuf,
+    map: RwLock<MmapMut>,
+    len: usize,
+    _scalar: PhantomData<fn() -> T>,
+}
+
+impl<T> MappedParameters<T> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl<T: MappedScalar> MappedParameters<T> {
+    /// Creates (or truncates) the file at `path` with `len` zeroed
+    /// parameters and maps it.
+    pub fn create(path: impl AsRef<Path>, len: usize) -> Result<Self, ModelError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path.as_ref())?;
+        file.set_len((len * size_of::<T>()) as u64)?;
+        Self::map(path.as_ref(), &file, len)
+    }
+
+    /// Maps the parameters already in the file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ModelError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path.as_ref())?;
+        let bytes = file.metadata()?.len() as usize;
+        if !bytes.is_multiple_of(size_of::<T>()) {
+            return Err(ModelError::StorageError(format!(
+                "{} is not a whole number of parameters",
+                path.as_ref().display()
+            )));
+        }
+        Self::map(path.as_ref(), &file, bytes / size_of::<T>())
+    }
+
+    fn map(path: &Path, file: &File, len: usize) -> Result<Self, ModelError> {
+        // Empty files cannot be mapped.
+        if len == 0 {
+            return Err(ModelError::InvalidInput(
+                "mapped parameters cannot be empty".to_string(),
+            ));
+        }
+        // SAFETY: the file is ours for the lifetime of the mapping; other
+        // processes changing it under us is not supported.
+        let map = unsafe { MmapMut::map_mut(file)? };
+        Ok(MappedParameters {
+            path: path.to_path_buf(),
+            map: RwLock::new(map),
+            len,
+            _scalar: PhantomData,
+        })
+    }
+
+    /// Read access to the parameters, held off while a write runs.
+    pub fn read(&self) -> MappedReadGuard<'_, T> {
+        MappedReadGuard {
+            map: self.map.read().unwrap_or_else(|e| e.into_inner()),
+            len: self.len,
+            _scalar: PhantomData,
+        }
+    }
+
+    /// Write access to the parameters, in place.
+    pub fn write(&self) -> MappedWriteGuard<'_, T> {
+        MappedWriteGuard {
+            map: self.map.write().unwrap_or_else(|e| e.into_inner()),
+            len: self.len,
+            _scalar: PhantomData,
+        }
+    }
+
+    /// Edits the parameters in place.
+    pub fn update<R>(&self, update: impl FnOnce(&mut [T]) -> R) -> R {
+        update(&mut self.write())
+    }
+
+    /// Writes modified pages back to the file.
+    pub fn flush(&self) -> Result<(), ModelError> {
+        self.map.read()?.flush()?;
+        Ok(())
+    }
+}
+
+/// Read access to [`MappedParameters`].
+pub struct MappedReadGuard<'a, T> {
+    map: RwLockReadGuard<'a, MmapMut>,
+    len: usize,
+    _scalar: PhantomData<fn() -> T>,
+}
+
+impl<T: MappedScalar> Deref for MappedReadGuard<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        // SAFETY: the mapping is page aligned and `len` values of `T` long,
+        // and any bit pattern is a valid `T`.
+        unsafe { std::slice::from_raw_parts(self.map.as_ptr() as *const T, self.len) }
+    }
+}
+
+/// Write access to [`MappedParameters`].
+pub struct MappedWriteGuard<'a, T> {
+    map: RwLockWriteGuard<'a, MmapMut>,
+    len: usize,
+    _scalar: PhantomData<fn() -> T>,
+}
+
+impl<T: MappedScalar> Deref for MappedWriteGuard<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        // SAFETY: as for `MappedReadGuard`.
+        unsafe { std::slice::from_raw_parts(self.map.as_ptr() as *const T, self.len) }
+    }
+}
+
+impl<T: MappedScalar> DerefMut for MappedWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        // SAFETY: as for `MappedReadGuard`, and the write lock is held.
+        unsafe { std::slice::from_raw_parts_mut(self.map.as_mut_ptr() as *mut T, self.len) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Model;
+
+    #[test]
+    fn test_mapped_parameters_persist_across_opens() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("params.bin");
+        let model = Model::mapped(MappedParameters::<f64>::create(&path, 1000).unwrap());
+        model.update_mapped(|p| p[999] = -2.5).unwrap();
+        assert_eq!(model.version(), 1);
+        model.mapped_params().unwrap().flush().unwrap();
+        drop(model);
+
+        let reopened = MappedParameters::<f64>::open(&path).unwrap();
+        assert_eq!(reopened.len(), 1000);
+        assert_eq!((reopened.read()[0], reopened.read()[999]), (0.0, -2.5));
+        std::fs::write(&path, [0u8; 12]).unwrap();
+        assert!(MappedParameters::<f64>::open(&path).is_err());
+        assert!(Model::<f64>::new().update_mapped(|_| ()).is_err());
+    }
+}
@@ -1,42 +1,108 @@
+use crate::algorithm::Datum;
+use crate::class_priors::{ClassPriors, ClassPriorsConfig};
+use crate::constraints::ParameterConstraints;
+use crate::encryption::{self, EncryptionKey};
+use crate::errors::ModelError;
+use crate::labels::LabelEncoder;
+use crate::layout::ModelLayout;
+#[cfg(feature = "mmap")]
+use crate::mmap::{MappedParameters, MappedScalar};
+use crate::model_builder::ModelBuilder;
+use crate::safetensors;
+use crate::seed;
+use crate::sparse::{SparseEntries, SparseParameters};
+use crate::wal::WriteAheadLog;
+use arc_swap::ArcSwap;
 use num_traits::Float;
-use std::cell::UnsafeCell;
+use rand::Rng;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::{BTreeMap, VecDeque};
 use std::fmt::Debug;
+use std::fs;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-// SAFETY: This type is marked `Sync` on the promise that it is only
-// ever mutated (via calls to unsafe get_mut()) by one thread at a time.
-// The user must ensure that when writing is occurring, no other writes
-// are concurrently active. Reads can occur simultaneously without restriction,
-// and this concurrent read behavior while writing is happening is acceptable
-// per the external guarantees provided by the caller.
-#[derive(Debug)]
-pub struct SyncUnsafeCell<T>(UnsafeCell<T>);
-
-unsafe impl<T> Sync for SyncUnsafeCell<T> where T: Send {}
-
-impl<T> SyncUnsafeCell<T> {
-    pub fn new(value: T) -> Self {
-        SyncUnsafeCell(UnsafeCell::new(value))
-    }
+/// Restore points kept for [`Model::rollback`]; older ones are dropped.
+pub const MAX_RESTORE_POINTS: usize = 16;
 
-    // Allow immutable access.
-    pub unsafe fn get(&self) -> &T {
-        &*self.0.get()
-    }
-
-    // Allow mutable access. This is only safe if we can guarantee that no other
-    // mutable references exist. This puts the burden of
-    // guaranteeing no mutable aliasing on the caller.
-    pub unsafe fn get_mut(&self) -> &mut T {
-        &mut *self.0.get()
-    }
-}
 /// A generic Model struct that holds a set of parameters.
+///
+/// The parameters are double-buffered: inference steps grab the published
+/// front buffer through [`Model::read_params`] without ever blocking, while
+/// a training step edits the back buffer through [`Model::update_params`]
+/// or [`Model::write_params`] and flips the two atomically when done.
+/// Writers are serialized; readers see either the version before or after a
+/// step, never a partial one. The replaced front becomes the next back
+/// buffer, reused without allocating once no reader holds it any more. A
+/// step that panicked while writing is discarded.
+///
+/// Every published write bumps the model version. [`Model::checkpoint`]
+/// keeps the current parameters as a restore point that
+/// [`Model::rollback`] can publish again, e.g. after a bad training batch.
+///
+/// Models over millions of mostly untouched parameters can keep them
+/// sparse instead (see [`Model::sparse`]); restore points, checkpoints,
+/// replication and the moving average of [`Model::with_ema`] cover the
+/// dense parameters only.
+///
+/// A model also carries its identity and training counters, reported by
+/// [`Model::metadata`] and saved with the parameters.
+///
+/// [`Model::builder`] configures the size, initial values, metadata,
+/// persistence path and parameter backend of a new model in one place.
 #[derive(Debug)]
 pub struct Model<T>
 where
     T: Float + Debug + Send + Sync,
 {
-    pub parameters: SyncUnsafeCell<Vec<T>>,
+    parameters: ArcSwap<Vec<T>>,
+    /// Parameters the model was created or loaded with, see [`Model::reset`].
+    initial: Arc<Vec<T>>,
+    /// Named groups of the parameters; fixed for the model's lifetime.
+    layout: ModelLayout,
+    /// Serializes writers and keeps the back buffer, the parameters last
+    /// replaced by a write.
+    writer: Mutex<Option<Arc<Vec<T>>>>,
+    version: AtomicU64,
+    /// Restore points by version, oldest first.
+    restore_points: Mutex<VecDeque<StoredVersion<T>>>,
+    /// Set for models created with [`Model::sparse`].
+    sparse: Option<SparseParameters<T>>,
+    /// Set for models created with [`Model::mapped`].
+    #[cfg(feature = "mmap")]
+    mapped: Option<MappedParameters<T>>,
+    id: String,
+    name: Option<String>,
+    created_at_ms: u64,
+    /// Number of features inputs must have, if known.
+    input_dimension: Option<usize>,
+    training_steps: AtomicU64,
+    /// Time of the last published write, 0 before the first one.
+    updated_at_ms: AtomicU64,
+    /// Decay and moving average of the parameters, see [`Model::with_ema`].
+    ema: Option<(T, Box<Model<T>>)>,
+    /// Recent published snapshots, see [`Model::with_delta_log`].
+    delta_log: Option<DeltaLog<T>>,
+    /// Parameters training leaves alone, see [`Model::set_freeze_mask`];
+    /// empty when none is frozen.
+    freeze_mask: ArcSwap<Vec<bool>>,
+    /// Limits training steps must respect, see [`Model::set_constraints`].
+    constraints: ArcSwap<ParameterConstraints>,
+    /// Parameters changed by the constraints so far.
+    constrained: AtomicU64,
+    /// File the model is loaded from and persisted to, see [`Model::persist`].
+    path: Option<PathBuf>,
+    /// Class ids of the labels seen, see [`Model::labels`].
+    labels: LabelEncoder,
+    /// Class frequencies of the training samples, see
+    /// [`Model::with_class_priors`].
+    class_priors: Option<ClassPriors>,
+    /// Log of the published writes, see [`Model::set_write_ahead_log`].
+    log: OnceLock<Arc<WriteAheadLog>>,
 }
 
 impl<T> Model<T>
@@ -53,9 +119,7 @@ where
     /// let model: Model<f32> = Model::new();
     /// ```
     pub fn new() -> Self {
-        Model {
-            parameters: SyncUnsafeCell::new(Vec::new()),
-        }
+        Self::with_parameters(Vec::new())
     }
 
     /// Creates a new Model with the specified parameters.
@@ -73,44 +137,2250 @@ where
     /// let model = Model::with_parameters(initial_params);
     /// ```
     pub fn with_parameters(params: Vec<T>) -> Self {
+        let params = Arc::new(params);
         Model {
-            parameters: SyncUnsafeCell::new(params),
+            parameters: ArcSwap::new(params.clone()),
+            initial: params,
+            layout: ModelLayout::new(),
+            writer: Mutex::new(None),
+            version: AtomicU64::new(0),
+            restore_points: Mutex::new(VecDeque::new()),
+            sparse: None,
+            #[cfg(feature = "mmap")]
+            mapped: None,
+            id: new_model_id(),
+            name: None,
+            created_at_ms: now_ms(),
+            input_dimension: None,
+            training_steps: AtomicU64::new(0),
+            updated_at_ms: AtomicU64::new(0),
+            ema: None,
+            delta_log: None,
+            freeze_mask: ArcSwap::from_pointee(Vec::new()),
+            constraints: ArcSwap::from_pointee(ParameterConstraints::default()),
+            constrained: AtomicU64::new(0),
+            path: None,
+            labels: LabelEncoder::new(),
+            class_priors: None,
+            log: OnceLock::new(),
         }
     }
 
-    /// Provides mutable access to the parameters.
-    pub unsafe fn get_parameters_mut(&self) -> &mut Vec<T> {
-        &mut *self.parameters.get_mut()
+    /// A builder configuring a new model before it is created.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oml::model::Model;
+    /// use oml::model_builder::Initializer;
+    ///
+    /// let model: Model<f32> = Model::builder()
+    ///     .dimension(4)
+    ///     .initializer(Initializer::Constant { value: 0.5 })
+    ///     .name("ctr")
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(model.read_params().as_slice(), &[0.5; 4]);
+    /// ```
+    pub fn builder() -> ModelBuilder<T> {
+        ModelBuilder::new()
     }
 
-    /// Provides immutable access to the parameters.
-    pub unsafe fn get_parameters(&self) -> &Vec<T> {
-        &*self.parameters.get()
+    /// Names the model, e.g. after the catalog entry holding it.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
 
-    #[test]
-    fn test_new() {
-        let model: Model<f32> = Model::new();
-        unsafe {
-            let params = model.get_parameters();
-            assert!(params.is_empty());
+    /// Sets the file [`Model::persist`] writes the model to.
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// The file the model was loaded from or is persisted to, if any.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Declares the number of features inputs must have; see
+    /// [`Model::check_input`].
+    pub fn with_input_dimension(mut self, dimension: usize) -> Self {
+        self.input_dimension = Some(dimension);
+        self
+    }
+
+    /// Accepts at most `max_classes` distinct labels; see [`Model::labels`].
+    pub fn with_max_classes(mut self, max_classes: usize) -> Self {
+        self.labels = LabelEncoder::from_labels(self.labels.labels()).with_max_classes(max_classes);
+        self
+    }
+
+    /// Class ids of the labels the model has seen, assigned as new labels
+    /// appear and saved with the parameters, for multiclass algorithms over
+    /// an open label set.
+    pub fn labels(&self) -> &LabelEncoder {
+        &self.labels
+    }
+
+    /// Tracks the class frequencies of the labeled training samples and,
+    /// if `config` says so, weights the samples by inverse frequency; see
+    /// [`ClassPriors`].
+    pub fn with_class_priors(mut self, config: ClassPriorsConfig) -> Self {
+        self.class_priors = Some(ClassPriors::new(config));
+        self
+    }
+
+    pub fn class_priors(&self) -> Option<&ClassPriors> {
+        self.class_priors.as_ref()
+    }
+
+    /// Counts a training sample of class `label` in the class priors and
+    /// returns the weight to train it with; 1 for models without priors.
+    pub fn observe_class(&self, label: &str) -> Result<f64, ModelError> {
+        match &self.class_priors {
+            Some(priors) => Ok(priors.observe(self.labels.encode(label)?)),
+            None => Ok(1.0),
         }
     }
 
-    #[test]
-    fn test_new_from_parameters() {
-        let model = Model::with_parameters(vec![1.0, 2.0, 3.0]);
-        let expected = vec![1.0, 2.0, 3.0];
-        unsafe {
-            let params = model.get_parameters();
-            for (a, b) in params.iter().zip(expected.iter()) {
-                assert_eq!(a, b);
+    /// Keeps an exponential moving average of the dense parameters next to
+    /// them, starting from their current values: every
+    /// [`Model::record_training_step`] moves it to
+    /// `decay * average + (1 - decay) * parameters`. Replacing or rolling
+    /// back the parameters resets it. `decay` is clamped to `[0, 1]`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oml::model::Model;
+    ///
+    /// let model = Model::with_parameters(vec![0.0f64]).with_ema(0.75);
+    /// model.update_params(|params| params[0] = 4.0);
+    /// model.record_training_step();
+    /// assert_eq!(model.ema().unwrap().read_params()[0], 1.0);
+    /// ```
+    pub fn with_ema(mut self, decay: f64) -> Self {
+        let decay = T::from(decay.clamp(0.0, 1.0)).unwrap_or_else(T::one);
+        let average = Model {
+            layout: self.layout.clone(),
+            ..Self::with_parameters(self.snapshot().to_vec())
+        };
+        self.ema = Some((decay, Box::new(average)));
+        self
+    }
+
+    /// A deep copy of the model, to train differently without touching it:
+    /// its parameters, layout, sparse parameters, labels and training
+    /// counters, with the state training builds on (moving average, frozen
+    /// parameters, constraints and class frequencies).
+    ///
+    /// The copy is a new model: it gets its own id and creation time and
+    /// starts at version 0, while restore points, the delta log, the
+    /// write-ahead log and the file stay with the original.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oml::model::Model;
+    ///
+    /// let model = Model::with_parameters(vec![1.0f64, 2.0]).with_ema(0.9);
+    /// let fork = model.fork().unwrap();
+    /// fork.update_params(|params| params[0] = 5.0);
+    /// assert_eq!(model.read_params().as_slice(), &[1.0, 2.0]);
+    /// assert_eq!(fork.ema().unwrap().read_params().as_slice(), &[1.0, 2.0]);
+    /// assert_ne!(fork.metadata().id, model.metadata().id);
+    /// ```
+    pub fn fork(&self) -> Result<Self, ModelError> {
+        let sparse = self
+            .sparse
+            .as_ref()
+            .map(|sparse| SparseParameters::from_entries(&sparse.entries()))
+            .transpose()?;
+        let ema = match &self.ema {
+            Some((decay, average)) => Some((*decay, Box::new(average.fork()?))),
+            None => None,
+        };
+        Ok(Model {
+            sparse,
+            name: self.name.clone(),
+            input_dimension: self.input_dimension,
+            training_steps: AtomicU64::new(self.training_steps()),
+            updated_at_ms: AtomicU64::new(self.updated_at_ms.load(Ordering::SeqCst)),
+            ema,
+            freeze_mask: ArcSwap::new(self.freeze_mask.load_full()),
+            constraints: ArcSwap::new(self.constraints.load_full()),
+            labels: self.copy_labels(),
+            class_priors: self.class_priors.clone(),
+            ..Self::with_layout(self.layout.clone(), self.snapshot().to_vec())?
+        })
+    }
+
+    /// A dense model of another scalar type holding `params` converted with
+    /// `convert`, for running steps at another precision (see
+    /// [`MixedPrecision`]): the layout, input dimension, labels, class
+    /// frequencies and moving average carry over, while sparse and
+    /// memory-mapped parameters do not.
+    ///
+    /// [`MixedPrecision`]: crate::precision::MixedPrecision
+    pub(crate) fn converted<U>(
+        &self,
+        params: &[T],
+        convert: impl Fn(T) -> U + Copy,
+    ) -> Result<Model<U>, ModelError>
+    where
+        U: Float + Debug + Send + Sync,
+    {
+        let ema = match &self.ema {
+            Some((decay, average)) => {
+                let decay = U::from(*decay).unwrap_or_else(U::one);
+                let average = average.converted(&average.read_params(), convert)?;
+                Some((decay, Box::new(average)))
+            }
+            None => None,
+        };
+        let params = params.iter().map(|p| convert(*p)).collect();
+        Ok(Model {
+            name: self.name.clone(),
+            input_dimension: self.input_dimension,
+            ema,
+            labels: self.copy_labels(),
+            class_priors: self.class_priors.clone(),
+            ..Model::with_layout(self.layout.clone(), params)?
+        })
+    }
+
+    fn copy_labels(&self) -> LabelEncoder {
+        let labels = LabelEncoder::from_labels(self.labels.labels());
+        match self.labels.max_classes() {
+            Some(max_classes) => labels.with_max_classes(max_classes),
+            None => labels,
+        }
+    }
+
+    /// The moving average of the parameters, for models built with
+    /// [`Model::with_ema`].
+    pub fn ema(&self) -> Option<&Model<T>> {
+        self.ema.as_ref().map(|(_, average)| &**average)
+    }
+
+    /// Identity and training counters of the model, as of now.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oml::model::Model;
+    ///
+    /// let model = Model::with_parameters(vec![1.0f32, 2.0]).with_name("ctr");
+    /// model.record_training_step();
+    /// let metadata = model.metadata();
+    /// assert_eq!(metadata.name.as_deref(), Some("ctr"));
+    /// assert_eq!((metadata.parameter_count, metadata.training_steps), (2, 1));
+    /// ```
+    pub fn metadata(&self) -> ModelMetadata {
+        let mut metadata = ModelMetadata::new::<T>(self.read_params().len());
+        metadata.id = self.id.clone();
+        metadata.name = self.name.clone();
+        metadata.created_at_ms = self.created_at_ms;
+        metadata.input_dimension = self.input_dimension;
+        metadata.training_steps = self.training_steps();
+        metadata.updated_at_ms = Some(self.updated_at_ms.load(Ordering::SeqCst)).filter(|t| *t > 0);
+        metadata
+    }
+
+    /// Training steps recorded on the model, saved ones included.
+    pub fn training_steps(&self) -> u64 {
+        self.training_steps.load(Ordering::SeqCst)
+    }
+
+    /// Runs `step`, failing it if the write-ahead log rejected one of the
+    /// writes it made (see [`Model::set_write_ahead_log`]). Such a write is
+    /// not published, since it could not be rebuilt after a crash.
+    pub fn logged_step<R>(
+        &self,
+        step: impl FnOnce() -> Result<R, ModelError>,
+    ) -> Result<R, ModelError> {
+        let before = UNLOGGED_WRITES.with(Cell::get);
+        let result = step()?;
+        if UNLOGGED_WRITES.with(Cell::get) != before {
+            return Err(ModelError::StorageError(
+                "the write-ahead log failed to record the step".to_string(),
+            ));
+        }
+        Ok(result)
+    }
+
+    /// Counts a training step applied to the model and folds the new
+    /// parameters into their moving average, if it keeps one.
+    pub fn record_training_step(&self) {
+        self.training_steps.fetch_add(1, Ordering::SeqCst);
+        if let Some((decay, average)) = &self.ema {
+            let params = self.snapshot();
+            average.update_params(|avg| {
+                if avg.len() != params.len() {
+                    *avg = params.to_vec();
+                    return;
+                }
+                for (a, p) in avg.iter_mut().zip(params.iter()) {
+                    *a = *decay * *a + (T::one() - *decay) * *p;
+                }
+            });
+        }
+    }
+
+    /// Restarts the moving average from the current parameters.
+    fn reset_ema(&self) {
+        if let Some((_, average)) = &self.ema {
+            *average.write_params() = self.snapshot().to_vec();
+        }
+    }
+
+    /// Fails with [`ModelError::DimensionMismatch`] if the model declares an
+    /// input dimension and `x` has another number of features. Algorithms
+    /// and handlers call it before feeding `x` to the parameters.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oml::errors::ModelError;
+    /// use oml::model::Model;
+    ///
+    /// let model = Model::with_parameters(vec![0.0f64; 3]).with_input_dimension(2);
+    /// assert!(model.check_input(&vec![1.0, 2.0]).is_ok());
+    /// assert!(matches!(
+    ///     model.check_input(&vec![1.0, 2.0, 3.0]),
+    ///     Err(ModelError::DimensionMismatch { expected: 2, actual: 3 })
+    /// ));
+    /// ```
+    pub fn check_input<D: Datum>(&self, x: &D) -> Result<(), ModelError> {
+        match x.features() {
+            Some(features) => self.check_dimension(features.len()),
+            None => Ok(()),
+        }
+    }
+
+    /// Fails with [`ModelError::DimensionMismatch`] if the model declares an
+    /// input dimension other than `actual`.
+    pub fn check_dimension(&self, actual: usize) -> Result<(), ModelError> {
+        match self.input_dimension {
+            Some(expected) if expected != actual => {
+                Err(ModelError::DimensionMismatch { expected, actual })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Creates a new Model without dense parameters and `dimension` sparse
+    /// ones, all zero, of which only the non-zero ones take memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oml::model::Model;
+    ///
+    /// let model: Model<f64> = Model::sparse(1 << 30);
+    /// model.update_sparse(&[7, 1 << 20], |_, w| w + 1.0).unwrap();
+    /// let sparse = model.sparse_params().unwrap();
+    /// assert_eq!((sparse.get(7).unwrap(), sparse.stored()), (1.0, 2));
+    /// ```
+    pub fn sparse(dimension: usize) -> Self {
+        Model {
+            sparse: Some(SparseParameters::new(dimension)),
+            ..Self::new()
+        }
+    }
+
+    /// The sparse parameters, for models created with [`Model::sparse`].
+    pub fn sparse_params(&self) -> Option<&SparseParameters<T>> {
+        self.sparse.as_ref()
+    }
+
+    /// Sets each sparse parameter in `indices` to `update(index, value)`,
+    /// touching no other parameter, and bumps the version. Writers are
+    /// serialized with those of the dense parameters.
+    pub fn update_sparse(
+        &self,
+        indices: &[usize],
+        update: impl FnMut(usize, T) -> T,
+    ) -> Result<(), ModelError> {
+        let Some(sparse) = &self.sparse else {
+            return Err(ModelError::Unsupported(
+                "the model has no sparse parameters".to_string(),
+            ));
+        };
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        sparse.update(indices, update)?;
+        self.version.fetch_add(1, Ordering::SeqCst);
+        self.updated_at_ms.store(now_ms(), Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Replaces all sparse parameters with `saved`, e.g. restored from a
+    /// file, and bumps the version.
+    pub fn replace_sparse(&self, saved: &SparseEntries<T>) -> Result<(), ModelError> {
+        let Some(sparse) = &self.sparse else {
+            return Err(ModelError::Unsupported(
+                "the model has no sparse parameters".to_string(),
+            ));
+        };
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        sparse.replace(saved)?;
+        self.version.fetch_add(1, Ordering::SeqCst);
+        self.updated_at_ms.store(now_ms(), Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Creates a new Model with zeroed parameters addressable through the
+    /// named groups of `layout`.
+    #[deprecated(note = "use `Model::builder().layout(layout).build()`")]
+    pub fn from_layout(layout: ModelLayout) -> Self {
+        let params = vec![T::zero(); layout.len()];
+        Model {
+            layout,
+            ..Self::with_parameters(params)
+        }
+    }
+
+    /// Creates a new Model with `params` laid out as `layout` describes.
+    pub fn with_layout(layout: ModelLayout, params: Vec<T>) -> Result<Self, ModelError> {
+        if !layout.is_empty() && layout.len() != params.len() {
+            return Err(ModelError::InvalidInput(format!(
+                "the layout covers {} parameters, got {}",
+                layout.len(),
+                params.len()
+            )));
+        }
+        Ok(Model {
+            layout,
+            ..Self::with_parameters(params)
+        })
+    }
+
+    /// Named groups of the parameters; empty for models built from a bare
+    /// vector.
+    pub fn layout(&self) -> &ModelLayout {
+        &self.layout
+    }
+
+    /// Creates a new Model with `len` parameters drawn uniformly from
+    /// `[-scale, scale]`, repeatably in deterministic mode (see [`seed`]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(deprecated)]
+    /// use oml::model::Model;
+    ///
+    /// let model: Model<f64> = Model::with_random_parameters(3, 0.1);
+    /// ```
+    #[deprecated(note = "use `Model::builder()` with `Initializer::Uniform`")]
+    pub fn with_random_parameters(len: usize, scale: f64) -> Self {
+        let mut rng = seed::rng("init");
+        let params = (0..len)
+            .map(|_| T::from(rng.gen_range(-1.0..=1.0) * scale).unwrap_or_else(T::zero))
+            .collect();
+        Self::with_parameters(params)
+    }
+
+    /// The current parameters. Never blocks; the snapshot stays unchanged
+    /// while held, even if a training step publishes a newer version.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oml::model::Model;
+    ///
+    /// let model = Model::with_parameters(vec![1.0, 2.0]);
+    /// let sum: f64 = model.read_params().iter().sum();
+    /// assert_eq!(sum, 3.0);
+    /// ```
+    pub fn read_params(&self) -> ParamsReadGuard<'_, T> {
+        ParamsReadGuard {
+            snapshot: self.parameters.load(),
+            layout: &self.layout,
+        }
+    }
+
+    /// Shared handle to the current parameters, for holding them longer.
+    pub fn snapshot(&self) -> Arc<Vec<T>> {
+        self.parameters.load_full()
+    }
+
+    /// Exclusive access to a copy of the parameters, published to readers
+    /// when the guard is dropped (unless it is discarded, see
+    /// [`ParamsWriteGuard::discard`]). Other writers wait until then.
+    ///
+    /// The copy is made in the back buffer unless a reader still holds that
+    /// older version, in which case it is left to the reader and the copy
+    /// gets new memory.
+    pub fn write_params(&self) -> ParamsWriteGuard<'_, T> {
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let front = self.parameters.load();
+        let working = match writer.take().and_then(|back| Arc::try_unwrap(back).ok()) {
+            Some(mut back) => {
+                back.clear();
+                back.extend_from_slice(&front);
+                back
             }
+            None => front.to_vec(),
+        };
+        ParamsWriteGuard {
+            working,
+            parameters: &self.parameters,
+            layout: &self.layout,
+            version: &self.version,
+            updated_at_ms: &self.updated_at_ms,
+            delta_log: self.delta_log.as_ref(),
+            log: self.log.get().map(Arc::as_ref),
+            frozen: Vec::new(),
+            constraints: None,
+            constrained: &self.constrained,
+            writer,
+            discarded: false,
+        }
+    }
+
+    /// Logs every write to `log` before publishing it, so the parameters can
+    /// be rebuilt after a crash; see [`WriteAheadLog`]. Only the dense
+    /// parameters are logged, and a model keeps its first log.
+    pub fn set_write_ahead_log(&self, log: Arc<WriteAheadLog>) -> Result<(), ModelError> {
+        self.log.set(log).map_err(|_| {
+            ModelError::InvalidInput("the model already has a write-ahead log".to_string())
+        })
+    }
+
+    pub fn write_ahead_log(&self) -> Option<&Arc<WriteAheadLog>> {
+        self.log.get()
+    }
+
+    /// The current parameters with the position of the last write logged
+    /// for them (0 without a log), taken together so that a checkpoint of
+    /// the parameters covers exactly the entries up to that position.
+    pub fn logged_snapshot(&self) -> (Arc<Vec<T>>, u64) {
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let position = self.log.get().map_or(0, |log| log.position());
+        (self.snapshot(), position)
+    }
+
+    /// Like [`Model::write_params`], but the frozen parameters (see
+    /// [`Model::set_freeze_mask`]) get their values back when the guard
+    /// publishes, whatever was written to them, and the others are brought
+    /// within the constraints (see [`Model::set_constraints`]). Training
+    /// steps write through it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oml::model::Model;
+    ///
+    /// let model = Model::with_parameters(vec![1.0, 1.0]);
+    /// model.set_freeze_mask(vec![false, true]).unwrap();
+    /// model.write_trainable().iter_mut().for_each(|p| *p += 1.0);
+    /// assert_eq!(model.read_params().as_slice(), &[2.0, 1.0]);
+    /// ```
+    pub fn write_trainable(&self) -> ParamsWriteGuard<'_, T> {
+        let mut guard = self.write_params();
+        let mask = self.freeze_mask.load_full();
+        let constraints = self.constraints.load_full();
+        if !constraints.is_empty() {
+            guard.constraints = Some((constraints, mask.clone()));
+        }
+        guard.frozen = mask
+            .iter()
+            .zip(guard.working.iter())
+            .enumerate()
+            .filter(|(_, (frozen, _))| **frozen)
+            .map(|(i, (_, value))| (i, *value))
+            .collect();
+        guard
+    }
+
+    /// Runs `update` on a copy of the parameters and publishes the result,
+    /// leaving the frozen ones as they were; see [`Model::write_trainable`].
+    pub fn update_trainable<R>(&self, update: impl FnOnce(&mut Vec<T>) -> R) -> R {
+        update(&mut self.write_trainable())
+    }
+
+    /// Constrains the parameters written by training steps (see
+    /// [`Model::write_trainable`]) from the next step on; the default,
+    /// empty constraints lift them.
+    pub fn set_constraints(&self, constraints: ParameterConstraints) -> Result<(), ModelError> {
+        constraints.validate(&self.layout, self.read_params().len())?;
+        self.constraints.store(Arc::new(constraints));
+        Ok(())
+    }
+
+    pub fn constraints(&self) -> Arc<ParameterConstraints> {
+        self.constraints.load_full()
+    }
+
+    /// Number of parameter values the constraints changed so far.
+    pub fn constrained_count(&self) -> u64 {
+        self.constrained.load(Ordering::SeqCst)
+    }
+
+    /// Freezes the parameters set in `mask`, one flag per parameter, so
+    /// training steps leave them alone, e.g. the bias terms or an embedding
+    /// block trained offline. An empty mask unfreezes every parameter.
+    pub fn set_freeze_mask(&self, mask: Vec<bool>) -> Result<(), ModelError> {
+        let expected = self.read_params().len();
+        if !mask.is_empty() && mask.len() != expected {
+            return Err(ModelError::InvalidInput(format!(
+                "expected a mask of {} flags, got {}",
+                expected,
+                mask.len()
+            )));
+        }
+        let mask = match mask.contains(&true) {
+            true => mask,
+            false => Vec::new(),
+        };
+        self.freeze_mask.store(Arc::new(mask));
+        Ok(())
+    }
+
+    /// Freezes the parameter groups of the layout named in `groups` and the
+    /// parameters at `indices`, unfreezing every other one, and returns the
+    /// number of frozen parameters.
+    pub fn freeze(&self, groups: &[String], indices: &[usize]) -> Result<usize, ModelError> {
+        let mut mask = vec![false; self.read_params().len()];
+        for name in groups {
+            let group = self
+                .layout
+                .get(name)
+                .ok_or_else(|| ModelError::InvalidInput(format!("unknown group {}", name)))?;
+            mask[group.range()]
+                .iter_mut()
+                .for_each(|frozen| *frozen = true);
+        }
+        for &i in indices {
+            *mask.get_mut(i).ok_or_else(|| {
+                ModelError::InvalidInput(format!("parameter index {} out of range", i))
+            })? = true;
+        }
+        self.set_freeze_mask(mask)?;
+        Ok(self.frozen_count())
+    }
+
+    /// One flag per parameter, set for frozen ones; empty when none is.
+    pub fn freeze_mask(&self) -> Arc<Vec<bool>> {
+        self.freeze_mask.load_full()
+    }
+
+    /// Number of frozen parameters.
+    pub fn frozen_count(&self) -> usize {
+        self.freeze_mask
+            .load()
+            .iter()
+            .filter(|frozen| **frozen)
+            .count()
+    }
+
+    /// Number of writes published since the model was created.
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
+    }
+
+    /// Keeps the current parameters as a restore point and returns their
+    /// version. At most [`MAX_RESTORE_POINTS`] are kept.
+    pub fn checkpoint(&self) -> u64 {
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let version = self.version();
+        let mut points = self
+            .restore_points
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if points.back().map(|p| p.version) != Some(version) {
+            points.push_back(StoredVersion {
+                version,
+                published_at_ms: self.published_at_ms(),
+                parameters: self.parameters.load_full(),
+            });
+        }
+        while points.len() > MAX_RESTORE_POINTS {
+            points.pop_front();
+        }
+        version
+    }
+
+    /// Versions that can be rolled back to, oldest first.
+    pub fn restore_points(&self) -> Vec<u64> {
+        self.restore_points
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|p| p.version)
+            .collect()
+    }
+
+    /// When the current parameters were published: the time of the last
+    /// write, or of the model's creation before the first one.
+    fn published_at_ms(&self) -> u64 {
+        match self.updated_at_ms.load(Ordering::SeqCst) {
+            0 => self.created_at_ms,
+            updated => updated,
+        }
+    }
+
+    /// Versions of the dense parameters the model still holds: its restore
+    /// points, the snapshots of its delta log and the current version, in
+    /// ascending order.
+    pub fn stored_versions(&self) -> Vec<u64> {
+        let mut versions = self.restore_points();
+        if let Some(log) = &self.delta_log {
+            versions.extend(
+                log.snapshots
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .iter()
+                    .map(|s| s.version),
+            );
+        }
+        versions.push(self.version());
+        versions.sort_unstable();
+        versions.dedup();
+        versions
+    }
+
+    /// The dense parameters of `version`, if it is one of
+    /// [`Model::stored_versions`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oml::model::Model;
+    ///
+    /// let model = Model::with_parameters(vec![1.0f64]).with_delta_log(4);
+    /// model.update_params(|params| params[0] = 2.0);
+    /// model.update_params(|params| params[0] = 3.0);
+    /// assert_eq!(model.stored_versions(), vec![0, 1, 2]);
+    /// assert_eq!(model.stored_version(1).unwrap().parameters[0], 2.0);
+    /// assert!(model.stored_version(3).is_none());
+    /// ```
+    pub fn stored_version(&self, version: u64) -> Option<StoredVersion<T>> {
+        let current = {
+            let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+            (self.version() == version).then(|| StoredVersion {
+                version,
+                published_at_ms: self.published_at_ms(),
+                parameters: self.snapshot(),
+            })
+        };
+        let find = |stored: &VecDeque<StoredVersion<T>>| {
+            stored.iter().find(|s| s.version == version).cloned()
+        };
+        current
+            .or_else(|| {
+                find(
+                    &self
+                        .restore_points
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner),
+                )
+            })
+            .or_else(|| {
+                let log = self.delta_log.as_ref()?;
+                find(&log.snapshots.lock().unwrap_or_else(PoisonError::into_inner))
+            })
+    }
+
+    /// Approximate bytes of memory the model holds: its dense parameters
+    /// and back buffer, restore points, delta log, sparse entries and moving
+    /// average, with snapshots shared between them counted once. Mapped
+    /// parameters are paged in by the OS and not counted.
+    pub fn memory_bytes(&self) -> usize {
+        let mut snapshots = vec![self.snapshot(), self.initial.clone()];
+        snapshots.extend(
+            self.writer
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+        );
+        snapshots.extend(
+            self.restore_points
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .iter()
+                .map(|p| p.parameters.clone()),
+        );
+        if let Some(log) = &self.delta_log {
+            snapshots.extend(
+                log.snapshots
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .iter()
+                    .map(|s| s.parameters.clone()),
+            );
+        }
+        snapshots.sort_by_key(Arc::as_ptr);
+        snapshots.dedup_by(|a, b| Arc::ptr_eq(a, b));
+        let dense: usize = snapshots.iter().map(|s| s.len()).sum();
+        let sparse = self.sparse.as_ref().map_or(0, |sparse| {
+            sparse.stored() * (std::mem::size_of::<usize>() + std::mem::size_of::<T>())
+        });
+        let ema = self.ema.as_ref().map_or(0, |(_, ema)| ema.memory_bytes());
+        dense * std::mem::size_of::<T>() + sparse + ema
+    }
+
+    /// Summary statistics of the dense parameters, overall and by layout
+    /// group, served on `GET /model/stats` to watch the weights without
+    /// downloading them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oml::model::Model;
+    ///
+    /// let model = Model::with_parameters(vec![3.0, 0.0, -4.0]);
+    /// let stats = model.stats();
+    /// assert_eq!((stats.parameters.l1_norm, stats.parameters.l2_norm), (7.0, 5.0));
+    /// assert_eq!((stats.parameters.min, stats.parameters.zeros), (Some(-4.0), 1));
+    /// ```
+    pub fn stats(&self) -> ModelStats {
+        let params = self.read_params();
+        ModelStats {
+            version: self.version(),
+            parameters: ParameterStats::of(&params),
+            groups: self
+                .layout
+                .groups()
+                .iter()
+                .map(|g| (g.name.clone(), ParameterStats::of(&params[g.range()])))
+                .collect(),
+        }
+    }
+
+    /// Publishes the parameters of restore point `version` again, as a new
+    /// version which is returned. Versions stay monotonic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oml::model::Model;
+    ///
+    /// let model = Model::with_parameters(vec![1.0]);
+    /// let good = model.checkpoint();
+    /// model.update_params(|params| params[0] = f64::NAN);
+    /// assert_eq!(model.rollback(good).unwrap(), 2);
+    /// assert_eq!(model.read_params()[0], 1.0);
+    /// ```
+    pub fn rollback(&self, version: u64) -> Result<u64, ModelError> {
+        let restored = self
+            .restore_points
+            .lock()?
+            .iter()
+            .find(|p| p.version == version)
+            .map(|p| p.parameters.clone())
+            .ok_or_else(|| {
+                ModelError::InvalidInput(format!("version {} is not a restore point", version))
+            })?;
+        let mut params = self.write_params();
+        *params = restored.to_vec();
+        drop(params);
+        self.reset_ema();
+        Ok(self.version())
+    }
+
+    /// Publishes `params`, e.g. weights trained offline, as a new version
+    /// which is returned. The current parameters are kept as a restore point
+    /// first, so the swap can be rolled back.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oml::model::Model;
+    ///
+    /// let model = Model::with_parameters(vec![1.0, 2.0]);
+    /// assert_eq!(model.replace_parameters(vec![3.0, 4.0]).unwrap(), 1);
+    /// assert_eq!(model.restore_points(), vec![0]);
+    /// assert!(model.replace_parameters(vec![5.0]).is_err());
+    /// ```
+    pub fn replace_parameters(&self, params: Vec<T>) -> Result<u64, ModelError> {
+        let expected = self.read_params().len();
+        if params.len() != expected {
+            return Err(ModelError::InvalidInput(format!(
+                "expected {} parameters, got {}",
+                expected,
+                params.len()
+            )));
         }
+        self.checkpoint();
+        let mut current = self.write_params();
+        *current = params;
+        drop(current);
+        self.reset_ema();
+        Ok(self.version())
+    }
+
+    /// Publishes the parameters the model was created or loaded with again,
+    /// e.g. to recover a corrupted online model without redeploying, and
+    /// returns the new version. The current parameters are kept as a
+    /// restore point first; sparse parameters are left as they are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oml::model::Model;
+    ///
+    /// let model = Model::with_parameters(vec![1.0, 2.0]);
+    /// model.update_params(|params| params[0] = f64::NAN);
+    /// assert_eq!(model.reset().unwrap(), 2);
+    /// assert_eq!(model.read_params().as_slice(), &[1.0, 2.0]);
+    /// ```
+    pub fn reset(&self) -> Result<u64, ModelError> {
+        self.replace_parameters(self.initial.to_vec())
+    }
+
+    /// Runs `update` on a copy of the parameters and publishes the result.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oml::model::Model;
+    ///
+    /// let model = Model::with_parameters(vec![1.0, 2.0]);
+    /// model.update_params(|params| params.iter_mut().for_each(|p| *p *= 2.0));
+    /// assert_eq!(model.read_params().as_slice(), &[2.0, 4.0]);
+    /// ```
+    pub fn update_params<R>(&self, update: impl FnOnce(&mut Vec<T>) -> R) -> R {
+        update(&mut self.write_params())
+    }
+
+    /// Keeps the last `capacity` published snapshots, so [`Model::diff`]
+    /// can tell which parameters changed since any of their versions.
+    pub fn with_delta_log(mut self, capacity: usize) -> Self {
+        let log = DeltaLog::new(capacity);
+        log.record(StoredVersion {
+            version: self.version(),
+            published_at_ms: self.published_at_ms(),
+            parameters: self.snapshot(),
+        });
+        self.delta_log = Some(log);
+        self
+    }
+
+    /// The dense parameters that changed since `since_version`, with their
+    /// current values, for a replica or edge device to catch up with
+    /// [`Model::apply_delta`] instead of pulling every parameter.
+    ///
+    /// Versions older than the snapshots kept by [`Model::with_delta_log`]
+    /// (every version, without one) get a full delta.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oml::model::Model;
+    ///
+    /// let source = Model::with_parameters(vec![0.0f64; 1000]).with_delta_log(8);
+    /// let replica = Model::with_parameters(vec![0.0f64; 1000]);
+    /// source.update_params(|params| params[42] = 1.5);
+    /// let delta = source.diff(0).unwrap();
+    /// assert_eq!((delta.indices.as_slice(), delta.version), (&[42][..], 1));
+    /// replica.apply_delta(&delta).unwrap();
+    /// assert_eq!(replica.read_params()[42], 1.5);
+    /// ```
+    pub fn diff(&self, since_version: u64) -> Result<ModelDelta<T>, ModelError> {
+        let (current, version, base) = {
+            let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+            let base = match &self.delta_log {
+                Some(log) if since_version <= self.version() => log.at(since_version)?,
+                _ => None,
+            };
+            (self.snapshot(), self.version(), base)
+        };
+        let (full, indices) = match base {
+            Some(base) if base.len() == current.len() => (
+                false,
+                (0..current.len())
+                    .filter(|&i| base[i] != current[i])
+                    .collect(),
+            ),
+            _ if since_version == version => (false, Vec::new()),
+            _ => (true, (0..current.len()).collect::<Vec<_>>()),
+        };
+        Ok(ModelDelta {
+            since_version,
+            version,
+            full,
+            len: current.len(),
+            values: indices.iter().map(|&i| current[i]).collect(),
+            indices,
+        })
+    }
+
+    /// Sets the parameters listed in `delta` (see [`Model::diff`]) to its
+    /// values as one published write, and returns the new version, which
+    /// is this model's own and not the delta's.
+    pub fn apply_delta(&self, delta: &ModelDelta<T>) -> Result<u64, ModelError> {
+        let mut params = self.write_params();
+        if delta.len != params.len() || delta.indices.len() != delta.values.len() {
+            let count = params.len();
+            params.discard();
+            return Err(ModelError::InvalidInput(format!(
+                "delta of {} values over {} parameters does not fit {} parameters",
+                delta.values.len(),
+                delta.len,
+                count
+            )));
+        }
+        if let Some(i) = delta.indices.iter().find(|&&i| i >= params.len()) {
+            params.discard();
+            return Err(ModelError::InvalidInput(format!(
+                "parameter index {} out of range",
+                i
+            )));
+        }
+        for (&i, &value) in delta.indices.iter().zip(&delta.values) {
+            params[i] = value;
+        }
+        drop(params);
+        Ok(self.version())
+    }
+
+    /// A new model whose parameters are the average of those of `models`
+    /// weighted by `weights`, e.g. sample counts for federated averaging.
+    /// Weights are normalized; they must be non-negative and not all zero.
+    ///
+    /// The models must have the same number of dense parameters, matching
+    /// layouts and, if any has sparse parameters, sparse parameters of the
+    /// same dimension, which are averaged over the union of their stored
+    /// entries. The result takes the first model's layout and metadata
+    /// (but not its name) and starts at version 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oml::model::Model;
+    ///
+    /// let a = Model::with_parameters(vec![1.0f64, 0.0]);
+    /// let b = Model::with_parameters(vec![4.0f64, 3.0]);
+    /// let merged = Model::average(&[&a, &b], &[2.0, 1.0]).unwrap();
+    /// assert_eq!(merged.read_params().as_slice(), &[2.0, 1.0]);
+    /// ```
+    pub fn average(models: &[&Model<T>], weights: &[f64]) -> Result<Model<T>, ModelError> {
+        let Some(first) = models.first() else {
+            return Err(ModelError::InvalidInput("no models to average".to_string()));
+        };
+        if weights.len() != models.len() {
+            return Err(ModelError::InvalidInput(format!(
+                "expected {} weights, got {}",
+                models.len(),
+                weights.len()
+            )));
+        }
+        if weights.iter().any(|w| !w.is_finite() || *w < 0.0) {
+            return Err(ModelError::InvalidInput(
+                "weights must be finite and non-negative".to_string(),
+            ));
+        }
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return Err(ModelError::InvalidInput(
+                "weights must not all be zero".to_string(),
+            ));
+        }
+        let weights: Vec<T> = weights
+            .iter()
+            .map(|w| T::from(w / total).unwrap_or_else(T::zero))
+            .collect();
+
+        let snapshots: Vec<_> = models.iter().map(|m| m.snapshot()).collect();
+        let len = snapshots[0].len();
+        for (model, params) in models.iter().zip(&snapshots).skip(1) {
+            if params.len() != len {
+                return Err(ModelError::InvalidInput(format!(
+                    "expected {} parameters, got {}",
+                    len,
+                    params.len()
+                )));
+            }
+            let layout = model.layout();
+            if !layout.is_empty() && !first.layout.is_empty() && *layout != first.layout {
+                return Err(ModelError::InvalidInput(
+                    "the parameter groups differ between models".to_string(),
+                ));
+            }
+        }
+        let mut params = vec![T::zero(); len];
+        for (snapshot, &weight) in snapshots.iter().zip(&weights) {
+            for (p, &value) in params.iter_mut().zip(snapshot.iter()) {
+                *p = *p + weight * value;
+            }
+        }
+
+        let sparse = match models.iter().find_map(|m| m.sparse.as_ref()) {
+            None => None,
+            Some(reference) => {
+                let dimension = reference.dimension();
+                let mut merged = BTreeMap::new();
+                for (model, &weight) in models.iter().zip(&weights) {
+                    let entries = match &model.sparse {
+                        Some(sparse) if sparse.dimension() == dimension => sparse.entries(),
+                        Some(sparse) => {
+                            return Err(ModelError::InvalidInput(format!(
+                                "expected {} sparse parameters, got {}",
+                                dimension,
+                                sparse.dimension()
+                            )))
+                        }
+                        None => {
+                            return Err(ModelError::InvalidInput(
+                                "cannot average sparse and dense-only models".to_string(),
+                            ))
+                        }
+                    };
+                    for (index, value) in entries.entries {
+                        let sum = merged.entry(index).or_insert_with(T::zero);
+                        *sum = *sum + weight * value;
+                    }
+                }
+                let entries = SparseEntries {
+                    dimension,
+                    entries: merged.into_iter().filter(|(_, v)| !v.is_zero()).collect(),
+                };
+                Some(SparseParameters::from_entries(&entries)?)
+            }
+        };
+
+        Ok(Model {
+            layout: first.layout.clone(),
+            sparse,
+            input_dimension: first.input_dimension,
+            ..Self::with_parameters(params)
+        })
+    }
+
+    /// Provides mutable access to the published parameters, in place.
+    ///
+    /// # Safety
+    ///
+    /// Only one writer may hold the returned reference at a time, readers
+    /// of the current snapshot observe its writes, and it dangles once a
+    /// write through [`Model::write_params`] publishes a new version.
+    #[deprecated(note = "use `Model::update_params` or `Model::write_params`")]
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn get_parameters_mut(&self) -> &mut Vec<T> {
+        &mut *(Arc::as_ptr(&self.parameters.load()) as *mut Vec<T>)
+    }
+
+    /// Provides immutable access to the published parameters.
+    ///
+    /// # Safety
+    ///
+    /// The reference dangles once a write through [`Model::write_params`]
+    /// publishes a new version.
+    #[deprecated(note = "use `Model::read_params`")]
+    pub unsafe fn get_parameters(&self) -> &Vec<T> {
+        &*Arc::as_ptr(&self.parameters.load())
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl<T> Model<T>
+where
+    T: Float + Debug + Send + Sync,
+{
+    /// Creates a new Model without dense parameters whose parameters live
+    /// in the memory-mapped file of `parameters` and are paged in on demand.
+    pub fn mapped(parameters: MappedParameters<T>) -> Self {
+        Model {
+            mapped: Some(parameters),
+            ..Self::new()
+        }
+    }
+
+    /// The memory-mapped parameters, for models created with
+    /// [`Model::mapped`].
+    pub fn mapped_params(&self) -> Option<&MappedParameters<T>> {
+        self.mapped.as_ref()
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl<T> Model<T>
+where
+    T: Float + Debug + Send + Sync + MappedScalar,
+{
+    /// Edits the memory-mapped parameters in place and bumps the version.
+    /// Writers are serialized with those of the dense parameters.
+    pub fn update_mapped<R>(&self, update: impl FnOnce(&mut [T]) -> R) -> Result<R, ModelError> {
+        let Some(mapped) = &self.mapped else {
+            return Err(ModelError::Unsupported(
+                "the model has no memory-mapped parameters".to_string(),
+            ));
+        };
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let result = mapped.update(update);
+        self.version.fetch_add(1, Ordering::SeqCst);
+        self.updated_at_ms.store(now_ms(), Ordering::SeqCst);
+        Ok(result)
+    }
+}
+
+impl<T> Model<T>
+where
+    T: Float + Debug + Send + Sync + Serialize + DeserializeOwned,
+{
+    /// Writes the parameters and their metadata to `path`, in the format
+    /// chosen by its extension (see [`ModelFormat::from_path`]). The file is
+    /// replaced atomically.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oml::model::Model;
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let path = dir.path().join("model.bin");
+    /// Model::with_parameters(vec![1.0f32, 2.0]).save(&path).unwrap();
+    /// let model: Model<f32> = Model::load(&path).unwrap();
+    /// assert_eq!(model.read_params().as_slice(), &[1.0, 2.0]);
+    /// ```
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ModelError> {
+        self.to_saved().write(path)
+    }
+
+    /// Saves the model like [`Model::save`], encrypted with `key`.
+    pub fn save_encrypted(
+        &self,
+        path: impl AsRef<Path>,
+        key: &EncryptionKey,
+    ) -> Result<(), ModelError> {
+        self.to_saved().write_encrypted(path, key)
+    }
+
+    /// What [`Model::save`] writes: the current parameters with their
+    /// metadata, layout, sparse parameters and labels.
+    pub fn to_saved(&self) -> SavedModel<T> {
+        let mut saved = SavedModel::new(self.snapshot().to_vec());
+        saved.metadata = self.metadata();
+        saved.layout = self.layout.clone();
+        saved.sparse = self.sparse.as_ref().map(SparseParameters::entries);
+        saved.labels = self.labels.labels();
+        saved
+    }
+
+    /// Reads a model written by [`Model::save`], with its layout, sparse
+    /// parameters and metadata.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ModelError> {
+        Ok(Model {
+            path: Some(path.as_ref().to_path_buf()),
+            ..Self::from_saved(SavedModel::read(path.as_ref())?)?
+        })
+    }
+
+    /// Reads a model written by [`Model::save_encrypted`] with `key`, or a
+    /// plain one.
+    pub fn load_encrypted(path: impl AsRef<Path>, key: &EncryptionKey) -> Result<Self, ModelError> {
+        Ok(Model {
+            path: Some(path.as_ref().to_path_buf()),
+            ..Self::from_saved(SavedModel::read_encrypted(path.as_ref(), key)?)?
+        })
+    }
+
+    /// Creates a model from the contents of a saved one, with its layout,
+    /// sparse parameters, labels and metadata.
+    pub fn from_saved(saved: SavedModel<T>) -> Result<Self, ModelError> {
+        let saved = saved.migrate()?;
+        let sparse = saved
+            .sparse
+            .as_ref()
+            .map(SparseParameters::from_entries)
+            .transpose()?;
+        let metadata = saved.metadata;
+        let model = Self::with_layout(saved.layout, saved.parameters)?;
+        Ok(Model {
+            sparse,
+            id: metadata.id,
+            name: metadata.name,
+            created_at_ms: metadata.created_at_ms,
+            input_dimension: metadata.input_dimension,
+            training_steps: AtomicU64::new(metadata.training_steps),
+            updated_at_ms: AtomicU64::new(metadata.updated_at_ms.unwrap_or(0)),
+            labels: LabelEncoder::from_labels(saved.labels),
+            ..model
+        })
+    }
+
+    /// The dense parameters in the safetensors format, one tensor per
+    /// layout group; see [`safetensors::encode`].
+    pub fn to_safetensors(&self) -> Result<Vec<u8>, ModelError> {
+        safetensors::encode(&self.to_saved())
+    }
+
+    /// Creates a model from weights in the safetensors format, e.g.
+    /// exported by another ML tool; see [`safetensors::decode`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oml::model::Model;
+    ///
+    /// let model = Model::with_parameters(vec![1.0f32, 2.0]);
+    /// let bytes = model.to_safetensors().unwrap();
+    /// let copy: Model<f64> = Model::from_safetensors(&bytes).unwrap();
+    /// assert_eq!(copy.read_params().as_slice(), &[1.0, 2.0]);
+    /// ```
+    pub fn from_safetensors(bytes: &[u8]) -> Result<Self, ModelError> {
+        Self::from_saved(safetensors::decode(bytes)?)
+    }
+
+    /// Saves the model to its path (see [`Model::path`]).
+    pub fn persist(&self) -> Result<(), ModelError> {
+        match &self.path {
+            Some(path) => self.save(path),
+            None => Err(ModelError::Unsupported(
+                "the model has no persistence path".to_string(),
+            )),
+        }
+    }
+}
+
+/// On-disk encoding of a saved model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelFormat {
+    Json,
+    Bincode,
+}
+
+impl ModelFormat {
+    /// Bincode for `.bin` and `.bincode` files, JSON otherwise.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("bin" | "bincode") => ModelFormat::Bincode,
+            _ => ModelFormat::Json,
+        }
+    }
+}
+
+/// Describes a model, as saved with its parameters and reported by
+/// `GET /model/info`. Times are milliseconds since the Unix epoch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelMetadata {
+    /// Version of oml that wrote the file.
+    pub oml_version: String,
+    /// Rust type of the parameters, e.g. `f32`.
+    pub scalar: String,
+    pub parameter_count: usize,
+    /// When the file was written, or the metadata taken.
+    pub saved_at_ms: u64,
+    /// Random identifier given to the model when created.
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub created_at_ms: u64,
+    /// Training steps applied since the model was created.
+    #[serde(default)]
+    pub training_steps: u64,
+    /// Last time the parameters changed, if ever.
+    #[serde(default)]
+    pub updated_at_ms: Option<u64>,
+    /// Number of features inputs must have, if declared.
+    #[serde(default)]
+    pub input_dimension: Option<usize>,
+}
+
+impl ModelMetadata {
+    /// Metadata of a new model with `parameter_count` parameters of type `T`.
+    pub fn new<T>(parameter_count: usize) -> Self {
+        let now = now_ms();
+        ModelMetadata {
+            oml_version: env!("CARGO_PKG_VERSION").to_string(),
+            scalar: std::any::type_name::<T>().to_string(),
+            parameter_count,
+            saved_at_ms: now,
+            id: new_model_id(),
+            name: None,
+            created_at_ms: now,
+            training_steps: 0,
+            updated_at_ms: None,
+            input_dimension: None,
+        }
+    }
+}
+
+/// [`ModelMetadata`] as written before models had an identity.
+#[derive(Deserialize)]
+struct LegacyMetadata {
+    oml_version: String,
+    scalar: String,
+    parameter_count: usize,
+    saved_at_ms: u64,
+}
+
+impl From<LegacyMetadata> for ModelMetadata {
+    fn from(legacy: LegacyMetadata) -> Self {
+        ModelMetadata {
+            oml_version: legacy.oml_version,
+            scalar: legacy.scalar,
+            parameter_count: legacy.parameter_count,
+            saved_at_ms: legacy.saved_at_ms,
+            id: String::new(),
+            name: None,
+            created_at_ms: 0,
+            training_steps: 0,
+            updated_at_ms: None,
+            input_dimension: None,
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn new_model_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+/// Schema of the saved models this version of oml writes; older ones are
+/// upgraded when read (see [`SavedModel::migrate`]):
+///
+/// 1. metadata without an identity, sparse parameters appended to binary
+///    files if any;
+/// 2. the model's identity, name, counters and sparse parameters in the
+///    metadata;
+/// 3. the labels of [`Model::labels`];
+/// 4. the schema version itself, binary files starting with
+///    [`MODEL_MAGIC`].
+///
+/// Files of a newer schema are rejected rather than misread.
+pub const MODEL_SCHEMA_VERSION: u32 = 4;
+
+/// First bytes of a binary saved model, followed by the bincode
+/// [`SavedModel`], whose schema version comes first.
+pub const MODEL_MAGIC: &[u8; 8] = b"OMLMODEL";
+
+/// Contents of a file written by [`Model::save`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedModel<T> {
+    /// See [`MODEL_SCHEMA_VERSION`]; 0 in JSON files that predate it.
+    #[serde(default)]
+    pub schema_version: u32,
+    pub metadata: ModelMetadata,
+    /// Named parameter groups; empty for models without.
+    pub layout: ModelLayout,
+    pub parameters: Vec<T>,
+    /// Sparse parameters of models created with [`Model::sparse`].
+    pub sparse: Option<SparseEntries<T>>,
+    /// Labels by class id, see [`Model::labels`].
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+/// Binary [`SavedModel`] of schema 3, without magic or schema version.
+#[derive(Serialize, Deserialize)]
+struct UnversionedSavedModel<T> {
+    metadata: ModelMetadata,
+    layout: ModelLayout,
+    parameters: Vec<T>,
+    sparse: Option<SparseEntries<T>>,
+    labels: Vec<String>,
+}
+
+/// Binary [`SavedModel`] of schema 2, before models kept their labels.
+#[derive(Deserialize)]
+struct UnlabeledSavedModel<T> {
+    metadata: ModelMetadata,
+    layout: ModelLayout,
+    parameters: Vec<T>,
+    sparse: Option<SparseEntries<T>>,
+}
+
+/// Binary [`SavedModel`] of schema 1, before models had an identity (JSON
+/// files only lack the fields). The sparse parameters follow, unless the
+/// file predates them too.
+#[derive(Deserialize)]
+struct LegacySavedModel<T> {
+    metadata: LegacyMetadata,
+    layout: ModelLayout,
+    parameters: Vec<T>,
+}
+
+impl<T: Serialize + DeserializeOwned> SavedModel<T> {
+    pub fn new(parameters: Vec<T>) -> Self {
+        SavedModel {
+            schema_version: MODEL_SCHEMA_VERSION,
+            metadata: ModelMetadata::new::<T>(parameters.len()),
+            layout: ModelLayout::new(),
+            parameters,
+            sparse: None,
+            labels: Vec::new(),
+        }
+    }
+
+    /// The saved model in `format`, as [`SavedModel::write`] writes it.
+    pub fn encode(&self, format: ModelFormat) -> Result<Vec<u8>, ModelError> {
+        Ok(match format {
+            ModelFormat::Json => serde_json::to_vec_pretty(self)?,
+            ModelFormat::Bincode => {
+                let mut bytes = MODEL_MAGIC.to_vec();
+                bincode::serialize_into(&mut bytes, self)?;
+                bytes
+            }
+        })
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), ModelError> {
+        self.write_with(path.as_ref(), None)
+    }
+
+    /// Writes the saved model encrypted with `key`; see [`EncryptionKey`].
+    pub fn write_encrypted(
+        &self,
+        path: impl AsRef<Path>,
+        key: &EncryptionKey,
+    ) -> Result<(), ModelError> {
+        self.write_with(path.as_ref(), Some(key))
+    }
+
+    fn write_with(&self, path: &Path, key: Option<&EncryptionKey>) -> Result<(), ModelError> {
+        let mut bytes = self.encode(ModelFormat::from_path(path))?;
+        if let Some(key) = key {
+            bytes = key.encrypt(&bytes)?;
+        }
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, bytes)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Reads and checks a saved model. Binary files must hold the same
+    /// scalar type; JSON ones are converted. Encrypted files are refused.
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, ModelError> {
+        Self::read_with(path.as_ref(), None)
+    }
+
+    /// Reads a saved model written by [`SavedModel::write_encrypted`] with
+    /// `key`, or a plain one.
+    pub fn read_encrypted(path: impl AsRef<Path>, key: &EncryptionKey) -> Result<Self, ModelError> {
+        Self::read_with(path.as_ref(), Some(key))
+    }
+
+    fn read_with(path: &Path, key: Option<&EncryptionKey>) -> Result<Self, ModelError> {
+        let bytes = fs::read(path)?;
+        encryption::open(bytes, key)
+            .and_then(|bytes| Self::decode(&bytes, ModelFormat::from_path(path)))
+            .map_err(|e| match e {
+                ModelError::InvalidInput(m) => {
+                    ModelError::InvalidInput(format!("{}: {}", path.display(), m))
+                }
+                ModelError::StorageError(m) => {
+                    ModelError::StorageError(format!("{}: {}", path.display(), m))
+                }
+                e => e,
+            })
+    }
+
+    /// Decodes and checks a saved model received as bytes, e.g. an
+    /// uploaded artifact, with the same rules as [`SavedModel::read`].
+    /// Older schemas are migrated.
+    pub fn decode(bytes: &[u8], format: ModelFormat) -> Result<Self, ModelError> {
+        let saved: Self = match format {
+            ModelFormat::Json => serde_json::from_slice(bytes)?,
+            ModelFormat::Bincode => match bytes.strip_prefix(MODEL_MAGIC.as_slice()) {
+                Some(rest) => {
+                    let schema = rest
+                        .get(..4)
+                        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                        .ok_or_else(|| ModelError::StorageError("truncated model".to_string()))?;
+                    check_schema(schema)?;
+                    bincode::deserialize(rest)?
+                }
+                None => Self::decode_unversioned(bytes)?,
+            },
+        };
+        let saved = saved.migrate()?;
+        if format == ModelFormat::Bincode && saved.metadata.scalar != std::any::type_name::<T>() {
+            return Err(ModelError::InvalidInput(format!(
+                "holds {} parameters, expected {}",
+                saved.metadata.scalar,
+                std::any::type_name::<T>()
+            )));
+        }
+        if saved.parameters.len() != saved.metadata.parameter_count {
+            return Err(ModelError::StorageError("truncated model".to_string()));
+        }
+        Ok(saved)
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> SavedModel<T> {
+    /// Reads a binary saved model written before schema 4, trying the
+    /// newest schema first. Bare bincode of the current struct, e.g. built
+    /// by a client, is accepted too.
+    fn decode_unversioned(bytes: &[u8]) -> Result<Self, bincode::Error> {
+        // Older schemas start with the length of the oml version instead.
+        if bytes.starts_with(&MODEL_SCHEMA_VERSION.to_le_bytes()) {
+            return bincode::deserialize(bytes);
+        }
+        bincode::deserialize::<UnversionedSavedModel<T>>(bytes)
+            .map(|saved| SavedModel {
+                schema_version: 3,
+                metadata: saved.metadata,
+                layout: saved.layout,
+                parameters: saved.parameters,
+                sparse: saved.sparse,
+                labels: saved.labels,
+            })
+            .or_else(|e| {
+                if let Ok(unlabeled) = bincode::deserialize::<UnlabeledSavedModel<T>>(bytes) {
+                    return Ok(SavedModel {
+                        schema_version: 2,
+                        metadata: unlabeled.metadata,
+                        layout: unlabeled.layout,
+                        parameters: unlabeled.parameters,
+                        sparse: unlabeled.sparse,
+                        labels: Vec::new(),
+                    });
+                }
+                let mut rest = bytes;
+                let legacy: LegacySavedModel<T> =
+                    bincode::deserialize_from(&mut rest).map_err(|_| e)?;
+                Ok(SavedModel {
+                    schema_version: 1,
+                    metadata: legacy.metadata.into(),
+                    layout: legacy.layout,
+                    parameters: legacy.parameters,
+                    sparse: match rest.is_empty() {
+                        true => None,
+                        false => bincode::deserialize(rest)?,
+                    },
+                    labels: Vec::new(),
+                })
+            })
+    }
+}
+
+impl<T> SavedModel<T> {
+    /// Upgrades a saved model of an older schema to
+    /// [`MODEL_SCHEMA_VERSION`]; current ones are returned as they are.
+    ///
+    /// JSON files that predate schema versions are told apart by their
+    /// fields: without an identity they are of schema 1.
+    pub fn migrate(mut self) -> Result<Self, ModelError> {
+        check_schema(self.schema_version)?;
+        let from = match self.schema_version {
+            0 if self.metadata.id.is_empty() => 1,
+            0 => 3,
+            schema => schema,
+        };
+        if from < 2 {
+            // Models saved before they had an identity get one, created
+            // when they were saved.
+            self.metadata.id = new_model_id();
+            if self.metadata.created_at_ms == 0 {
+                self.metadata.created_at_ms = self.metadata.saved_at_ms;
+            }
+        }
+        // Schema 3 added the labels and 4 the version, which default.
+        self.schema_version = MODEL_SCHEMA_VERSION;
+        Ok(self)
+    }
+}
+
+/// Rejects saved models of a schema newer than this version of oml reads.
+fn check_schema(schema: u32) -> Result<(), ModelError> {
+    match schema > MODEL_SCHEMA_VERSION {
+        true => Err(ModelError::Unsupported(format!(
+            "saved model schema {} is newer than the supported {}, upgrade oml to read it",
+            schema, MODEL_SCHEMA_VERSION
+        ))),
+        false => Ok(()),
+    }
+}
+
+/// Snapshot of the parameters returned by [`Model::read_params`].
+pub struct ParamsReadGuard<'a, T> {
+    snapshot: arc_swap::Guard<Arc<Vec<T>>>,
+    layout: &'a ModelLayout,
+}
+
+impl<T> ParamsReadGuard<'_, T> {
+    /// Parameters of the group `name` of the model's layout.
+    pub fn group(&self, name: &str) -> Option<&[T]> {
+        self.snapshot.get(self.layout.get(name)?.range())
+    }
+}
+
+impl<T> Deref for ParamsReadGuard<'_, T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Vec<T> {
+        &self.snapshot
+    }
+}
+
+/// Summary statistics of a set of parameters. NaN and infinite parameters
+/// are only counted in `non_finite`; the other fields cover the finite ones.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ParameterStats {
+    pub count: usize,
+    pub l1_norm: f64,
+    pub l2_norm: f64,
+    pub mean: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Parameters exactly zero.
+    pub zeros: usize,
+    pub non_finite: usize,
+}
+
+impl ParameterStats {
+    pub fn of<T: Float>(values: &[T]) -> Self {
+        let mut stats = ParameterStats {
+            count: values.len(),
+            ..ParameterStats::default()
+        };
+        let mut sum = 0.0;
+        let mut squares = 0.0;
+        for value in values {
+            let value = value.to_f64().unwrap_or(f64::NAN);
+            if !value.is_finite() {
+                stats.non_finite += 1;
+                continue;
+            }
+            sum += value;
+            stats.l1_norm += value.abs();
+            squares += value * value;
+            stats.zeros += usize::from(value == 0.0);
+            stats.min = Some(stats.min.map_or(value, |min| min.min(value)));
+            stats.max = Some(stats.max.map_or(value, |max| max.max(value)));
+        }
+        stats.l2_norm = squares.sqrt();
+        let finite = stats.count - stats.non_finite;
+        stats.mean = (finite > 0).then(|| sum / finite as f64);
+        stats
+    }
+}
+
+/// Statistics of the dense parameters of a model, returned by [`Model::stats`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelStats {
+    /// Version of the parameters summarized.
+    pub version: u64,
+    pub parameters: ParameterStats,
+    /// Statistics of each layout group.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub groups: BTreeMap<String, ParameterStats>,
+}
+
+/// Parameters changed between two versions of a model, produced by
+/// [`Model::diff`] and applied with [`Model::apply_delta`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelDelta<T> {
+    pub since_version: u64,
+    /// Version of the source model the delta brings a replica to.
+    pub version: u64,
+    /// Set when `since_version` was too old to diff and every parameter is
+    /// listed.
+    pub full: bool,
+    /// Number of parameters of the source model.
+    pub len: usize,
+    pub indices: Vec<usize>,
+    pub values: Vec<T>,
+}
+
+/// A published version of the dense parameters, kept by a model as a
+/// restore point or in its delta log; see [`Model::stored_version`].
+#[derive(Debug, Clone)]
+pub struct StoredVersion<T> {
+    pub version: u64,
+    pub published_at_ms: u64,
+    pub parameters: Arc<Vec<T>>,
+}
+
+/// Bounded history of published snapshots by version, oldest first.
+#[derive(Debug)]
+struct DeltaLog<T> {
+    capacity: usize,
+    snapshots: Mutex<VecDeque<StoredVersion<T>>>,
+}
+
+impl<T> DeltaLog<T> {
+    fn new(capacity: usize) -> Self {
+        DeltaLog {
+            capacity: capacity.max(1),
+            snapshots: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn record(&self, snapshot: StoredVersion<T>) {
+        let mut snapshots = self
+            .snapshots
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if snapshots.len() >= self.capacity {
+            snapshots.pop_front();
+        }
+        snapshots.push_back(snapshot);
+    }
+
+    /// The dense parameters as of `version`: the newest snapshot at or
+    /// before it, as sparse writes bump the version without one. `None` if
+    /// older snapshots were dropped.
+    fn at(&self, version: u64) -> Result<Option<Arc<Vec<T>>>, ModelError> {
+        let snapshots = self.snapshots.lock()?;
+        Ok(snapshots
+            .iter()
+            .rev()
+            .find(|s| s.version <= version)
+            .map(|s| s.parameters.clone()))
+    }
+}
+
+thread_local! {
+    /// Writes of this thread that the write-ahead log rejected, so that
+    /// [`Model::logged_step`] can tell which steps lost one.
+    static UNLOGGED_WRITES: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Working copy of the parameters returned by [`Model::write_params`].
+pub struct ParamsWriteGuard<'a, T: Float> {
+    working: Vec<T>,
+    parameters: &'a ArcSwap<Vec<T>>,
+    layout: &'a ModelLayout,
+    version: &'a AtomicU64,
+    updated_at_ms: &'a AtomicU64,
+    delta_log: Option<&'a DeltaLog<T>>,
+    log: Option<&'a WriteAheadLog>,
+    /// Values restored before publishing, set by [`Model::write_trainable`].
+    frozen: Vec<(usize, T)>,
+    /// Constraints enforced before publishing and the freeze mask they
+    /// skip, set by [`Model::write_trainable`].
+    constraints: Option<(Arc<ParameterConstraints>, Arc<Vec<bool>>)>,
+    constrained: &'a AtomicU64,
+    /// Where the replaced parameters are kept as the next back buffer.
+    writer: MutexGuard<'a, Option<Arc<Vec<T>>>>,
+    /// Set by [`ParamsWriteGuard::discard`]: nothing is published.
+    discarded: bool,
+}
+
+impl<T: Float> ParamsWriteGuard<'_, T> {
+    pub fn group(&self, name: &str) -> Option<&[T]> {
+        self.working.get(self.layout.get(name)?.range())
+    }
+
+    pub fn group_mut(&mut self, name: &str) -> Option<&mut [T]> {
+        self.working.get_mut(self.layout.get(name)?.range())
+    }
+
+    /// Drops the working copy without publishing it, for writes that fail
+    /// halfway: readers keep the current parameters and version.
+    pub fn discard(mut self) {
+        self.discarded = true;
+    }
+}
+
+impl<T: Float> Deref for ParamsWriteGuard<'_, T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Vec<T> {
+        &self.working
+    }
+}
+
+impl<T: Float> DerefMut for ParamsWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Vec<T> {
+        &mut self.working
+    }
+}
+
+impl<T: Float> Drop for ParamsWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        if self.discarded {
+            *self.writer = Some(Arc::new(std::mem::take(&mut self.working)));
+        } else if !std::thread::panicking() {
+            let mut working = std::mem::take(&mut self.working);
+            for (i, value) in self.frozen.drain(..) {
+                if let Some(slot) = working.get_mut(i) {
+                    *slot = value;
+                }
+            }
+            if let Some((constraints, frozen)) = self.constraints.take() {
+                let changed =
+                    constraints.apply(&mut working, &self.parameters.load(), self.layout, &frozen);
+                self.constrained.fetch_add(changed as u64, Ordering::SeqCst);
+            }
+            if let Some(log) = self.log {
+                let front = self.parameters.load();
+                let changes: Vec<(usize, f64)> = working
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, value)| front.get(*i) != Some(*value))
+                    .map(|(i, value)| (i, value.to_f64().unwrap_or(f64::NAN)))
+                    .collect();
+                if !changes.is_empty() && log.append(changes).is_err() {
+                    log.record_failure();
+                    UNLOGGED_WRITES.with(|count| count.set(count.get() + 1));
+                    *self.writer = Some(Arc::new(working));
+                    return;
+                }
+            }
+            let working = Arc::new(working);
+            *self.writer = Some(self.parameters.swap(working.clone()));
+            let version = self.version.fetch_add(1, Ordering::SeqCst) + 1;
+            let published_at_ms = now_ms();
+            self.updated_at_ms.store(published_at_ms, Ordering::SeqCst);
+            if let Some(log) = self.delta_log {
+                log.record(StoredVersion {
+                    version,
+                    published_at_ms,
+                    parameters: working,
+                });
+            }
+        }
+    }
+}
+
+impl<T> Default for Model<T>
+where
+    T: Float + Debug + Send + Sync,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model_builder::Initializer;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_new() {
+        let model: Model<f32> = Model::new();
+        assert!(model.read_params().is_empty());
+    }
+
+    #[test]
+    fn test_new_from_parameters() {
+        let model = Model::with_parameters(vec![1.0, 2.0, 3.0]);
+        let expected = [1.0, 2.0, 3.0];
+        let params = model.read_params();
+        for (a, b) in params.iter().zip(expected.iter()) {
+            assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn test_random_parameters_within_scale() {
+        let model: Model<f64> = Model::builder()
+            .dimension(16)
+            .initializer(Initializer::Uniform { scale: 0.5 })
+            .build()
+            .unwrap();
+        let params = model.read_params();
+        assert_eq!(params.len(), 16);
+        assert!(params.iter().all(|p| p.abs() <= 0.5));
+    }
+
+    #[test]
+    fn test_ema_follows_training_and_resets_on_replace() {
+        let model = Model::with_parameters(vec![0.0f64, 10.0]).with_ema(0.5);
+        model.update_params(|params| params[0] = 8.0);
+        model.record_training_step();
+        model.record_training_step();
+        assert_eq!(model.ema().unwrap().read_params().as_slice(), &[6.0, 10.0]);
+
+        // Unrecorded writes are not averaged in until the next step.
+        model.update_params(|params| params[1] = 2.0);
+        assert_eq!(model.ema().unwrap().read_params()[1], 10.0);
+        model.replace_parameters(vec![1.0, 1.0]).unwrap();
+        assert_eq!(model.ema().unwrap().read_params().as_slice(), &[1.0, 1.0]);
+        assert!(Model::with_parameters(vec![1.0f64]).ema().is_none());
+    }
+
+    #[test]
+    fn test_average_weights_dense_and_sparse_parameters() {
+        let layout = ModelLayout::new().group("w", &[2]);
+        let a = Model::with_layout(layout.clone(), vec![1.0f64, 2.0]).unwrap();
+        let b = Model::with_layout(layout.clone(), vec![3.0f64, 6.0]).unwrap();
+        let merged = Model::average(&[&a, &b], &[1.0, 3.0]).unwrap();
+        assert_eq!(merged.read_params().as_slice(), &[2.5, 5.0]);
+        assert_eq!(merged.layout(), &layout);
+
+        let short = Model::with_parameters(vec![1.0f64]);
+        assert!(Model::average(&[&a, &short], &[1.0, 1.0]).is_err());
+        assert!(Model::average(&[&a, &b], &[1.0]).is_err());
+        assert!(Model::average(&[&a, &b], &[0.0, 0.0]).is_err());
+        assert!(Model::average(&[&a, &b], &[-1.0, 2.0]).is_err());
+        assert!(Model::<f64>::average(&[], &[]).is_err());
+
+        let (x, y) = (Model::<f64>::sparse(100), Model::<f64>::sparse(100));
+        x.update_sparse(&[3, 50], |_, _| 2.0).unwrap();
+        y.update_sparse(&[3, 99], |i, _| if i == 3 { -2.0 } else { 4.0 })
+            .unwrap();
+        let merged = Model::average(&[&x, &y], &[1.0, 1.0]).unwrap();
+        let sparse = merged.sparse_params().unwrap();
+        assert_eq!(sparse.gather(&[3, 50, 99]).unwrap(), vec![0.0, 1.0, 2.0]);
+        assert_eq!(sparse.stored(), 2);
+        assert!(Model::average(&[&x, &Model::sparse(10)], &[1.0, 1.0]).is_err());
+        assert!(Model::average(&[&x, &Model::new()], &[1.0, 1.0]).is_err());
+    }
+
+    #[test]
+    fn test_concurrent_updates_are_not_lost() {
+        let model = Arc::new(Model::with_parameters(vec![0.0f64]));
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let model = model.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        model.update_params(|params| params[0] += 1.0);
+                    }
+                })
+            })
+            .collect();
+        writers.into_iter().for_each(|w| w.join().unwrap());
+        assert_eq!(model.read_params()[0], 4000.0);
+    }
+
+    #[test]
+    fn test_readers_keep_their_snapshot() {
+        let model = Model::with_parameters(vec![1.0f32, 2.0]);
+        let before = model.read_params();
+        let mut params = model.write_params();
+        params[0] = 5.0;
+        assert_eq!(model.read_params()[0], 1.0);
+        drop(params);
+
+        assert_eq!(model.read_params().as_slice(), &[5.0, 2.0]);
+        assert_eq!(before.as_slice(), &[1.0, 2.0]);
+
+        let result = std::panic::catch_unwind(|| {
+            model.update_params(|params| {
+                params[1] = 9.0;
+                panic!("failed step");
+            })
+        });
+        assert!(result.is_err());
+        assert_eq!(model.read_params()[1], 2.0);
+
+        let version = model.version();
+        let mut params = model.write_params();
+        params[1] = 9.0;
+        params.discard();
+        assert_eq!(model.read_params()[1], 2.0);
+        assert_eq!(model.version(), version);
+    }
+
+    #[test]
+    fn test_writes_flip_buffers_and_leave_held_snapshots_alone() {
+        let model = Model::with_parameters(vec![0.0f64; 64]);
+        let front = |model: &Model<f64>| model.read_params().as_ptr();
+        // The initial parameters are kept for `reset`, so the first two
+        // writes need new buffers; later ones flip between them.
+        model.update_params(|params| params.fill(1.0));
+        let first = front(&model);
+        model.update_params(|params| params.fill(2.0));
+        let second = front(&model);
+        model.update_params(|params| params.fill(3.0));
+        assert_eq!(front(&model), first);
+        model.update_params(|params| params.fill(3.0));
+        assert_eq!(front(&model), second);
+
+        let held = model.read_params();
+        model.update_params(|params| params.fill(4.0));
+        model.update_params(|params| params.fill(5.0));
+        assert!(held.iter().all(|p| *p == 3.0));
+        assert_ne!(front(&model), held.as_ptr());
+        drop(held);
+
+        // Readers racing a writer only ever see whole versions.
+        let model = Arc::new(model);
+        let writer = {
+            let model = model.clone();
+            std::thread::spawn(move || {
+                for step in 6..2000 {
+                    model.update_params(|params| params.fill(step as f64));
+                }
+            })
+        };
+        while !writer.is_finished() {
+            let params = model.read_params();
+            assert!(params.iter().all(|p| *p == params[0]));
+        }
+        writer.join().unwrap();
+        assert_eq!(model.read_params()[63], 1999.0);
+    }
+
+    #[test]
+    fn test_save_and_load_formats() {
+        let dir = tempfile::tempdir().unwrap();
+        let model = Model::with_parameters(vec![0.5f64, -1.25, 3.0]);
+        for name in ["model.json", "model.bin"] {
+            let path = dir.path().join(name);
+            model.save(&path).unwrap();
+            let loaded: Model<f64> = Model::load(&path).unwrap();
+            assert_eq!(loaded.read_params().as_slice(), &[0.5, -1.25, 3.0]);
+        }
+
+        let saved = SavedModel::<f64>::read(dir.path().join("model.json")).unwrap();
+        assert_eq!(saved.metadata.scalar, "f64");
+        assert_eq!(saved.metadata.parameter_count, 3);
+        assert!(Model::<f32>::load(dir.path().join("model.json")).is_ok());
+        assert!(Model::<f32>::load(dir.path().join("model.bin")).is_err());
+        assert!(Model::<f64>::load(dir.path().join("missing.json")).is_err());
+    }
+
+    #[test]
+    fn test_metadata_survives_save_and_load() {
+        let model = Model::with_parameters(vec![1.0f64, 2.0])
+            .with_name("ctr")
+            .with_input_dimension(1);
+        assert!(model.metadata().updated_at_ms.is_none());
+        model.update_params(|p| p[0] = 3.0);
+        model.record_training_step();
+        assert!(model.check_input(&1.0).is_ok());
+        assert!(matches!(
+            Model::<f64>::new()
+                .with_input_dimension(3)
+                .check_input(&1.0),
+            Err(ModelError::DimensionMismatch {
+                expected: 3,
+                actual: 1
+            })
+        ));
+
+        let dir = tempfile::tempdir().unwrap();
+        for file in ["model.json", "model.bin"] {
+            model.save(dir.path().join(file)).unwrap();
+            let loaded = Model::<f64>::load(dir.path().join(file)).unwrap();
+            let (before, after) = (model.metadata(), loaded.metadata());
+            assert_eq!((&after.id, &after.name), (&before.id, &before.name));
+            assert_eq!(after.training_steps, 1);
+            assert_eq!(after.input_dimension, Some(1));
+            assert_eq!(after.created_at_ms, before.created_at_ms);
+            assert!(after.updated_at_ms.is_some());
+        }
+
+        // Binary files written before models had an identity.
+        #[derive(Serialize)]
+        struct OldFile {
+            metadata: (String, String, usize, u64),
+            layout: ModelLayout,
+            parameters: Vec<f64>,
+        }
+        let old = OldFile {
+            metadata: ("0.1.0".to_string(), "f64".to_string(), 1, 42),
+            layout: ModelLayout::new(),
+            parameters: vec![5.0],
+        };
+        let path = dir.path().join("old.bin");
+        fs::write(&path, bincode::serialize(&old).unwrap()).unwrap();
+        let loaded = Model::<f64>::load(&path).unwrap();
+        assert_eq!(loaded.read_params().as_slice(), &[5.0]);
+        assert_eq!(loaded.metadata().created_at_ms, 42);
+        assert_eq!(loaded.metadata().id.len(), 16);
+    }
+
+    #[test]
+    fn test_older_schemas_migrated_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let model = Model::with_parameters(vec![1.0f64, 2.0]);
+        model.labels().encode("spam").unwrap();
+        let path = dir.path().join("model.bin");
+        model.save(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        assert!(bytes.starts_with(MODEL_MAGIC));
+        assert_eq!(bytes[8..12], MODEL_SCHEMA_VERSION.to_le_bytes());
+
+        // Schema 3: bare bincode with labels.
+        let saved = model.to_saved();
+        let unversioned = UnversionedSavedModel {
+            metadata: saved.metadata.clone(),
+            layout: saved.layout.clone(),
+            parameters: saved.parameters.clone(),
+            sparse: None,
+            labels: saved.labels.clone(),
+        };
+        fs::write(&path, bincode::serialize(&unversioned).unwrap()).unwrap();
+        let loaded = Model::<f64>::load(&path).unwrap();
+        assert_eq!(loaded.read_params().as_slice(), &[1.0, 2.0]);
+        assert_eq!(loaded.labels().labels(), vec!["spam"]);
+        assert_eq!(loaded.metadata().id, model.metadata().id);
+
+        // JSON without a schema version or an identity: schema 1.
+        let json = dir.path().join("old.json");
+        let old = r#"{"metadata": {"oml_version": "0.1.0", "scalar": "f64",
+            "parameter_count": 1, "saved_at_ms": 42},
+            "layout": {"groups": []}, "parameters": [5.0], "sparse": null}"#;
+        fs::write(&json, old).unwrap();
+        let saved = SavedModel::<f64>::read(&json).unwrap();
+        assert_eq!(saved.schema_version, MODEL_SCHEMA_VERSION);
+        assert_eq!(
+            (saved.metadata.id.len(), saved.metadata.created_at_ms),
+            (16, 42)
+        );
+
+        // Files of a newer schema are refused, whatever the format.
+        let mut newer = MODEL_MAGIC.to_vec();
+        newer.extend_from_slice(&99u32.to_le_bytes());
+        fs::write(&path, &newer).unwrap();
+        assert!(matches!(
+            Model::<f64>::load(&path),
+            Err(ModelError::Unsupported(_))
+        ));
+        let mut saved = model.to_saved();
+        saved.schema_version = 99;
+        fs::write(&json, serde_json::to_vec(&saved).unwrap()).unwrap();
+        assert!(matches!(
+            Model::<f64>::load(&json),
+            Err(ModelError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_versions_and_rollback() {
+        let model = Model::with_parameters(vec![1.0f64]);
+        assert_eq!(model.version(), 0);
+        model.update_params(|p| p[0] = 2.0);
+        let good = model.checkpoint();
+        assert_eq!(good, 1);
+        for _ in 0..3 {
+            model.update_params(|p| p[0] = f64::NAN);
+        }
+        assert_eq!(model.version(), 4);
+        assert!(model.rollback(3).is_err());
+
+        assert_eq!(model.rollback(good).unwrap(), 5);
+        assert_eq!(model.read_params()[0], 2.0);
+        assert_eq!(model.restore_points(), vec![1]);
+        for _ in 0..MAX_RESTORE_POINTS + 1 {
+            model.update_params(|p| p[0] += 1.0);
+            model.checkpoint();
+        }
+        assert_eq!(model.restore_points().len(), MAX_RESTORE_POINTS);
+        assert!(model.rollback(good).is_err());
+    }
+
+    #[test]
+    fn test_groups_survive_save_and_load() {
+        let layout = ModelLayout::new()
+            .group("weights", &[2])
+            .group("bias", &[1]);
+        assert!(Model::with_layout(layout.clone(), vec![1.0f64]).is_err());
+        let model = Model::with_layout(layout, vec![1.0f64, 2.0, 3.0]).unwrap();
+        assert_eq!(model.read_params().group("weights"), Some(&[1.0, 2.0][..]));
+        assert_eq!(model.read_params().group("missing"), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.bin");
+        model.save(&path).unwrap();
+        let loaded: Model<f64> = Model::load(&path).unwrap();
+        assert_eq!(loaded.layout(), model.layout());
+        assert_eq!(loaded.read_params().group("bias"), Some(&[3.0][..]));
     }
 }
@@ -0,0 +1,399 @@
+use crate::errors::ModelError;
+use crate::layout::ModelLayout;
+#[cfg(feature = "mmap")]
+use crate::mmap::MappedParameters;
+use crate::model::Model;
+use crate::seed;
+use num_traits::Float;
+use rand::Rng;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt::Debug;
+use std::path::PathBuf;
+
+/// Initial values of the dense parameters of a new model.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Initializer {
+    #[default]
+    Zeros,
+    Constant {
+        value: f64,
+    },
+    /// Drawn uniformly from `[-scale, scale]`, repeatably in deterministic
+    /// mode (see [`seed`]).
+    Uniform {
+        scale: f64,
+    },
+}
+
+impl Initializer {
+    /// `len` initial values.
+    pub fn values<T: Float>(&self, len: usize) -> Vec<T> {
+        match *self {
+            Initializer::Zeros => vec![T::zero(); len],
+            Initializer::Constant { value } => vec![T::from(value).unwrap_or_else(T::zero); len],
+            Initializer::Uniform { scale } => {
+                let mut rng = seed::rng("init");
+                (0..len)
+                    .map(|_| T::from(rng.gen_range(-1.0..=1.0) * scale).unwrap_or_else(T::zero))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Where the parameters of a new model live.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParameterBackend {
+    /// In memory, published as snapshots.
+    #[default]
+    Dense,
+    /// In memory, storing only the non-zero ones; see [`Model::sparse`].
+    Sparse,
+    /// In a memory-mapped file, given to [`ModelBuilder::mapped`].
+    #[cfg(feature = "mmap")]
+    Mapped,
+}
+
+/// Configures a new [`Model`]: its size (a dimension, a layout or explicit
+/// parameters, which must agree), the initial values of its parameters,
+/// metadata, the file it is persisted to and where its parameters live.
+///
+/// The type of the parameters is the builder's, e.g. `ModelBuilder::<f32>`.
+/// [`ModelBuilder::build`] always creates a new model, while
+/// [`ModelBuilder::open`] loads the one persisted at the path if there is
+/// one.
+///
+/// # Examples
+///
+/// ```
+/// use oml::layout::ModelLayout;
+/// use oml::model::Model;
+/// use oml::model_builder::Initializer;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let path = dir.path().join("model.json");
+/// let builder = || {
+///     Model::<f64>::builder()
+///         .layout(ModelLayout::new().group("weights", &[3]).group("bias", &[1]))
+///         .initializer(Initializer::Uniform { scale: 0.01 })
+///         .input_dimension(3)
+///         .path(&path)
+/// };
+/// let model = builder().open().unwrap();
+/// model.write_params().group_mut("bias").unwrap()[0] = 2.0;
+/// model.persist().unwrap();
+///
+/// let reopened = builder().open().unwrap();
+/// assert_eq!(reopened.read_params().group("bias"), Some(&[2.0][..]));
+/// assert_eq!(reopened.metadata().input_dimension, Some(3));
+/// ```
+#[derive(Debug)]
+pub struct ModelBuilder<T> {
+    dimension: Option<usize>,
+    layout: ModelLayout,
+    initializer: Initializer,
+    parameters: Option<Vec<T>>,
+    name: Option<String>,
+    input_dimension: Option<usize>,
+    ema: Option<f64>,
+    delta_log: Option<usize>,
+    path: Option<PathBuf>,
+    backend: ParameterBackend,
+    #[cfg(feature = "mmap")]
+    mapped: Option<MappedParameters<T>>,
+}
+
+impl<T> Default for ModelBuilder<T> {
+    fn default() -> Self {
+        ModelBuilder {
+            dimension: None,
+            layout: ModelLayout::new(),
+            initializer: Initializer::Zeros,
+            parameters: None,
+            name: None,
+            input_dimension: None,
+            ema: None,
+            delta_log: None,
+            path: None,
+            backend: ParameterBackend::Dense,
+            #[cfg(feature = "mmap")]
+            mapped: None,
+        }
+    }
+}
+
+impl<T> ModelBuilder<T>
+where
+    T: Float + Debug + Send + Sync,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of parameters.
+    pub fn dimension(mut self, dimension: usize) -> Self {
+        self.dimension = Some(dimension);
+        self
+    }
+
+    /// Named groups of the dense parameters, which it also sizes.
+    pub fn layout(mut self, layout: ModelLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// How the dense parameters are initialized; zeros by default.
+    pub fn initializer(mut self, initializer: Initializer) -> Self {
+        self.initializer = initializer;
+        self
+    }
+
+    /// Initial dense parameters, instead of an initializer.
+    pub fn parameters(mut self, parameters: Vec<T>) -> Self {
+        self.parameters = Some(parameters);
+        self
+    }
+
+    /// See [`Model::with_name`].
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// See [`Model::with_input_dimension`].
+    pub fn input_dimension(mut self, dimension: usize) -> Self {
+        self.input_dimension = Some(dimension);
+        self
+    }
+
+    /// See [`Model::with_ema`].
+    pub fn ema(mut self, decay: f64) -> Self {
+        self.ema = Some(decay);
+        self
+    }
+
+    /// See [`Model::with_delta_log`].
+    pub fn delta_log(mut self, capacity: usize) -> Self {
+        self.delta_log = Some(capacity);
+        self
+    }
+
+    /// File the model is loaded from by [`ModelBuilder::open`] and saved to
+    /// by [`Model::persist`].
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Where the parameters live; dense by default.
+    pub fn backend(mut self, backend: ParameterBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Keeps the parameters in the memory-mapped file of `parameters`, with
+    /// the values already in it.
+    #[cfg(feature = "mmap")]
+    pub fn mapped(mut self, parameters: MappedParameters<T>) -> Self {
+        self.mapped = Some(parameters);
+        self.backend = ParameterBackend::Mapped;
+        self
+    }
+
+    /// The number of parameters, if configured, after checking the ways of
+    /// setting it agree.
+    fn resolved_dimension(&self) -> Result<Option<usize>, ModelError> {
+        #[allow(unused_mut)]
+        let mut sizes = vec![
+            ("dimension", self.dimension),
+            (
+                "layout",
+                Some(self.layout.len()).filter(|_| !self.layout.is_empty()),
+            ),
+            ("parameters", self.parameters.as_ref().map(Vec::len)),
+        ];
+        #[cfg(feature = "mmap")]
+        sizes.push(("mapped parameters", self.mapped.as_ref().map(|m| m.len())));
+        let mut resolved: Option<(&str, usize)> = None;
+        for (source, size) in sizes {
+            match (resolved, size) {
+                (Some((first, expected)), Some(size)) if size != expected => {
+                    return Err(ModelError::InvalidInput(format!(
+                        "the {} has {} parameters but the {} has {}",
+                        first, expected, source, size
+                    )))
+                }
+                (None, Some(size)) => resolved = Some((source, size)),
+                _ => {}
+            }
+        }
+        Ok(resolved.map(|(_, size)| size))
+    }
+
+    /// Creates the model.
+    pub fn build(self) -> Result<Model<T>, ModelError> {
+        let dimension = self.resolved_dimension()?.unwrap_or(0);
+        let starts_at_zero = self.parameters.is_none() && self.initializer == Initializer::Zeros;
+        let model = match self.backend {
+            ParameterBackend::Dense => {
+                let parameters = match self.parameters {
+                    Some(parameters) => parameters,
+                    None => self.initializer.values(dimension),
+                };
+                Model::with_layout(self.layout, parameters)?
+            }
+            ParameterBackend::Sparse => {
+                if !starts_at_zero || !self.layout.is_empty() {
+                    return Err(ModelError::InvalidInput(
+                        "sparse parameters start at zero and have no layout".to_string(),
+                    ));
+                }
+                Model::sparse(dimension)
+            }
+            #[cfg(feature = "mmap")]
+            ParameterBackend::Mapped => {
+                let Some(mapped) = self.mapped else {
+                    return Err(ModelError::InvalidInput(
+                        "mapped parameters are set with ModelBuilder::mapped".to_string(),
+                    ));
+                };
+                if !starts_at_zero || !self.layout.is_empty() {
+                    return Err(ModelError::InvalidInput(
+                        "mapped parameters keep the values in their file and have no layout"
+                            .to_string(),
+                    ));
+                }
+                Model::mapped(mapped)
+            }
+        };
+        Ok(Self::configure(
+            model,
+            self.name,
+            self.input_dimension,
+            self.ema,
+            self.delta_log,
+            self.path,
+        ))
+    }
+
+    fn configure(
+        mut model: Model<T>,
+        name: Option<String>,
+        input_dimension: Option<usize>,
+        ema: Option<f64>,
+        delta_log: Option<usize>,
+        path: Option<PathBuf>,
+    ) -> Model<T> {
+        if let Some(name) = name {
+            model = model.with_name(name);
+        }
+        if let Some(dimension) = input_dimension {
+            model = model.with_input_dimension(dimension);
+        }
+        if let Some(decay) = ema {
+            model = model.with_ema(decay);
+        }
+        if let Some(capacity) = delta_log {
+            model = model.with_delta_log(capacity);
+        }
+        match path {
+            Some(path) => model.with_path(path),
+            None => model,
+        }
+    }
+}
+
+impl<T> ModelBuilder<T>
+where
+    T: Float + Debug + Send + Sync + Serialize + DeserializeOwned,
+{
+    /// Loads the model persisted at the path if the file exists, checking
+    /// it has the configured number of parameters and backend, and creates
+    /// it otherwise. The name, input dimension, moving average and delta log
+    /// configured apply to a loaded model too.
+    pub fn open(self) -> Result<Model<T>, ModelError> {
+        let Some(path) = self.path.clone().filter(|path| path.exists()) else {
+            return self.build();
+        };
+        let sparse = match self.backend {
+            ParameterBackend::Dense => false,
+            ParameterBackend::Sparse => true,
+            #[cfg(feature = "mmap")]
+            ParameterBackend::Mapped => {
+                return Err(ModelError::InvalidInput(
+                    "mapped parameters persist to their own file".to_string(),
+                ))
+            }
+        };
+        let dimension = self.resolved_dimension()?;
+        let model = Model::load(&path)?;
+        let loaded = match sparse {
+            true => model.sparse_params().map(|sparse| sparse.dimension()),
+            false => Some(model.read_params().len()),
+        };
+        if loaded.is_none() || dimension.is_some_and(|d| loaded != Some(d)) {
+            return Err(ModelError::InvalidInput(format!(
+                "{} does not hold a {} model of {} parameters",
+                path.display(),
+                if sparse { "sparse" } else { "dense" },
+                dimension.map_or("any number of".to_string(), |d| d.to_string()),
+            )));
+        }
+        Ok(Self::configure(
+            model,
+            self.name,
+            self.input_dimension,
+            self.ema,
+            self.delta_log,
+            self.path,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_checks_sizes_and_backends_agree() {
+        let err = Model::<f64>::builder()
+            .dimension(3)
+            .parameters(vec![1.0, 2.0])
+            .build();
+        assert!(matches!(err, Err(ModelError::InvalidInput(_))));
+        let sparse = Model::<f64>::builder()
+            .dimension(1 << 20)
+            .backend(ParameterBackend::Sparse)
+            .name("hashed")
+            .build()
+            .unwrap();
+        assert_eq!(sparse.sparse_params().unwrap().dimension(), 1 << 20);
+        assert_eq!(sparse.name(), Some("hashed"));
+        let uniform = Model::<f64>::builder()
+            .backend(ParameterBackend::Sparse)
+            .initializer(Initializer::Uniform { scale: 1.0 })
+            .build();
+        assert!(uniform.is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.bin");
+        let model = Model::<f32>::builder()
+            .parameters(vec![1.0, 2.0])
+            .path(&path)
+            .build()
+            .unwrap();
+        model.persist().unwrap();
+        let bigger = Model::<f32>::builder().dimension(3).path(&path).open();
+        assert!(bigger.is_err());
+        let as_sparse = Model::<f32>::builder()
+            .backend(ParameterBackend::Sparse)
+            .path(&path)
+            .open();
+        assert!(as_sparse.is_err());
+        let reopened = Model::<f32>::builder().path(&path).open().unwrap();
+        assert_eq!(reopened.read_params().as_slice(), &[1.0, 2.0]);
+        assert_eq!(reopened.path(), Some(path.as_path()));
+        assert!(Model::<f32>::new().persist().is_err());
+    }
+}
@@ -0,0 +1,1137 @@
+use crate::algorithm::{Algorithm, Datum};
+use crate::errors::ModelError;
+use crate::layout::ModelLayout;
+use crate::model::Model;
+use num_traits::Float;
+use prost::Message;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::mem::size_of;
+use std::path::Path;
+
+/// ONNX IR version written by [`export`] (ONNX 1.10).
+pub const IR_VERSION: i64 = 8;
+/// Version of the default operator set the exported graphs use.
+pub const OPSET_VERSION: i64 = 13;
+/// Name of the graph input, a `[batch, features]` tensor.
+pub const INPUT_NAME: &str = "input";
+/// Name of the graph output, a `[batch, outputs]` tensor.
+pub const OUTPUT_NAME: &str = "output";
+
+/// `TensorProto.DataType` of `f32` values.
+const FLOAT: i32 = 1;
+/// `TensorProto.DataType` of `f64` values.
+const DOUBLE: i32 = 11;
+
+/// The subset of `onnx.ModelProto` written by the exporter. Like the gRPC
+/// messages, the ONNX messages are hand-written prost structs, with the
+/// field numbers of `onnx.proto`.
+#[derive(Clone, PartialEq, Message)]
+pub struct ModelProto {
+    #[prost(int64, tag = "1")]
+    pub ir_version: i64,
+    #[prost(string, tag = "2")]
+    pub producer_name: String,
+    #[prost(string, tag = "3")]
+    pub producer_version: String,
+    #[prost(int64, tag = "5")]
+    pub model_version: i64,
+    #[prost(message, optional, tag = "7")]
+    pub graph: Option<GraphProto>,
+    #[prost(message, repeated, tag = "8")]
+    pub opset_import: Vec<OperatorSetIdProto>,
+    #[prost(message, repeated, tag = "14")]
+    pub metadata_props: Vec<StringStringEntryProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct OperatorSetIdProto {
+    #[prost(string, tag = "1")]
+    pub domain: String,
+    #[prost(int64, tag = "2")]
+    pub version: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct StringStringEntryProto {
+    #[prost(string, tag = "1")]
+    pub key: String,
+    #[prost(string, tag = "2")]
+    pub value: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct GraphProto {
+    #[prost(message, repeated, tag = "1")]
+    pub node: Vec<NodeProto>,
+    #[prost(string, tag = "2")]
+    pub name: String,
+    #[prost(message, repeated, tag = "5")]
+    pub initializer: Vec<TensorProto>,
+    #[prost(message, repeated, tag = "11")]
+    pub input: Vec<ValueInfoProto>,
+    #[prost(message, repeated, tag = "12")]
+    pub output: Vec<ValueInfoProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct NodeProto {
+    #[prost(string, repeated, tag = "1")]
+    pub input: Vec<String>,
+    #[prost(string, repeated, tag = "2")]
+    pub output: Vec<String>,
+    #[prost(string, tag = "3")]
+    pub name: String,
+    #[prost(string, tag = "4")]
+    pub op_type: String,
+    #[prost(message, repeated, tag = "5")]
+    pub attribute: Vec<AttributeProto>,
+}
+
+/// A node attribute; only the scalar `f` and `i` alternatives are read.
+#[derive(Clone, PartialEq, Message)]
+pub struct AttributeProto {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(float, tag = "2")]
+    pub f: f32,
+    #[prost(int64, tag = "3")]
+    pub i: i64,
+}
+
+/// A constant tensor, holding `f32` values in `float_data` or `f64` values
+/// in `double_data`, or either as little-endian bytes in `raw_data` (as
+/// most exporters write them).
+#[derive(Clone, PartialEq, Message)]
+pub struct TensorProto {
+    #[prost(int64, repeated, tag = "1")]
+    pub dims: Vec<i64>,
+    #[prost(int32, tag = "2")]
+    pub data_type: i32,
+    #[prost(float, repeated, tag = "4")]
+    pub float_data: Vec<f32>,
+    #[prost(string, tag = "8")]
+    pub name: String,
+    #[prost(bytes = "vec", tag = "9")]
+    pub raw_data: Vec<u8>,
+    #[prost(double, repeated, tag = "10")]
+    pub double_data: Vec<f64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ValueInfoProto {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    /// `TypeProto`, whose `tensor_type` alternative is field 1.
+    #[prost(message, optional, tag = "2")]
+    pub r#type: Option<TypeProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct TypeProto {
+    #[prost(message, optional, tag = "1")]
+    pub tensor_type: Option<TensorTypeProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct TensorTypeProto {
+    #[prost(int32, tag = "1")]
+    pub elem_type: i32,
+    #[prost(message, optional, tag = "2")]
+    pub shape: Option<TensorShapeProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct TensorShapeProto {
+    #[prost(message, repeated, tag = "1")]
+    pub dim: Vec<Dimension>,
+}
+
+/// A dimension, fixed (`dim_value`) or named and left free (`dim_param`).
+#[derive(Clone, PartialEq, Message)]
+pub struct Dimension {
+    #[prost(int64, optional, tag = "1")]
+    pub dim_value: Option<i64>,
+    #[prost(string, optional, tag = "2")]
+    pub dim_param: Option<String>,
+}
+
+/// Elementwise activation applied after a dense layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activation {
+    Relu,
+    Sigmoid,
+    Tanh,
+}
+
+impl Activation {
+    fn op_type(self) -> &'static str {
+        match self {
+            Activation::Relu => "Relu",
+            Activation::Sigmoid => "Sigmoid",
+            Activation::Tanh => "Tanh",
+        }
+    }
+}
+
+/// A fully connected layer `activation(x · weights + bias)` whose weights
+/// (shaped `[inputs, outputs]`, or `[inputs]` for a single output) and bias
+/// (`[outputs]`) are layout groups of the model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DenseLayer {
+    pub weights: String,
+    pub bias: String,
+    pub activation: Option<Activation>,
+}
+
+impl DenseLayer {
+    pub fn new(weights: impl Into<String>, bias: impl Into<String>) -> Self {
+        DenseLayer {
+            weights: weights.into(),
+            bias: bias.into(),
+            activation: None,
+        }
+    }
+
+    pub fn with_activation(mut self, activation: Activation) -> Self {
+        self.activation = Some(activation);
+        self
+    }
+}
+
+/// How the parameters of a model compute its prediction, which the model
+/// itself does not record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Architecture {
+    /// Linear regression `x · weights + bias`, over the `weights` and `bias`
+    /// groups or, without a layout, over the parameters as one weight per
+    /// feature followed by the bias (like [`BayesianLinearRegression`]).
+    ///
+    /// [`BayesianLinearRegression`]: crate::bayesian::BayesianLinearRegression
+    Linear,
+    /// Logistic regression `sigmoid(x · weights + bias)`, over the same
+    /// parameters as [`Architecture::Linear`].
+    Logistic,
+    /// A multilayer perceptron, its dense layers in order.
+    Mlp(Vec<DenseLayer>),
+}
+
+/// One dense layer with its parameters read from the model.
+struct Layer<T> {
+    name: String,
+    inputs: usize,
+    outputs: usize,
+    weights: Vec<T>,
+    bias: Vec<T>,
+    activation: Option<Activation>,
+}
+
+impl Architecture {
+    fn layers<T: Float + Debug + Send + Sync>(
+        &self,
+        model: &Model<T>,
+    ) -> Result<Vec<Layer<T>>, ModelError> {
+        let params = model.read_params();
+        let linear = |activation| {
+            if !model.layout().is_empty() {
+                return Architecture::Mlp(vec![DenseLayer {
+                    weights: "weights".to_string(),
+                    bias: "bias".to_string(),
+                    activation,
+                }])
+                .layers(model);
+            }
+            if params.len() < 2 {
+                return Err(ModelError::InvalidInput(
+                    "a linear model holds at least a weight and a bias".to_string(),
+                ));
+            }
+            let (weights, bias) = params.split_at(params.len() - 1);
+            Ok(vec![Layer {
+                name: "layer0".to_string(),
+                inputs: weights.len(),
+                outputs: 1,
+                weights: weights.to_vec(),
+                bias: bias.to_vec(),
+                activation,
+            }])
+        };
+        let layers = match self {
+            Architecture::Linear => return linear(None),
+            Architecture::Logistic => return linear(Some(Activation::Sigmoid)),
+            Architecture::Mlp(layers) => layers,
+        };
+        if layers.is_empty() {
+            return Err(ModelError::InvalidInput(
+                "a multilayer perceptron has at least one layer".to_string(),
+            ));
+        }
+        let group = |name: &str| {
+            let group = model.layout().get(name).ok_or_else(|| {
+                ModelError::InvalidInput(format!("the model has no {} parameter group", name))
+            })?;
+            Ok::<_, ModelError>((group.shape.clone(), params[group.range()].to_vec()))
+        };
+        let mut dense: Vec<Layer<T>> = Vec::with_capacity(layers.len());
+        for layer in layers {
+            let (shape, weights) = group(&layer.weights)?;
+            let (inputs, outputs) = match shape[..] {
+                [inputs] => (inputs, 1),
+                [inputs, outputs] => (inputs, outputs),
+                _ => {
+                    return Err(ModelError::InvalidInput(format!(
+                        "the {} weights are not a vector or a matrix",
+                        layer.weights
+                    )))
+                }
+            };
+            let (_, bias) = group(&layer.bias)?;
+            if bias.len() != outputs {
+                return Err(ModelError::InvalidInput(format!(
+                    "the {} bias has {} values for {} outputs",
+                    layer.bias,
+                    bias.len(),
+                    outputs
+                )));
+            }
+            if let Some(previous) = dense.last().filter(|p| p.outputs != inputs) {
+                return Err(ModelError::InvalidInput(format!(
+                    "the {} weights take {} inputs but the previous layer has {} outputs",
+                    layer.weights, inputs, previous.outputs
+                )));
+            }
+            dense.push(Layer {
+                name: format!("layer{}", dense.len()),
+                inputs,
+                outputs,
+                weights,
+                bias,
+                activation: layer.activation,
+            });
+        }
+        Ok(dense)
+    }
+}
+
+fn tensor<T: Float>(name: String, dims: &[usize], values: &[T], data_type: i32) -> TensorProto {
+    let mut tensor = TensorProto {
+        dims: dims.iter().map(|&d| d as i64).collect(),
+        data_type,
+        name,
+        ..TensorProto::default()
+    };
+    match data_type {
+        FLOAT => {
+            tensor.float_data = values
+                .iter()
+                .map(|v| v.to_f32().unwrap_or(f32::NAN))
+                .collect()
+        }
+        _ => {
+            tensor.double_data = values
+                .iter()
+                .map(|v| v.to_f64().unwrap_or(f64::NAN))
+                .collect()
+        }
+    }
+    tensor
+}
+
+fn value_info(name: &str, columns: usize, elem_type: i32) -> ValueInfoProto {
+    let batch = Dimension {
+        dim_param: Some("batch".to_string()),
+        ..Dimension::default()
+    };
+    let columns = Dimension {
+        dim_value: Some(columns as i64),
+        ..Dimension::default()
+    };
+    ValueInfoProto {
+        name: name.to_string(),
+        r#type: Some(TypeProto {
+            tensor_type: Some(TensorTypeProto {
+                elem_type,
+                shape: Some(TensorShapeProto {
+                    dim: vec![batch, columns],
+                }),
+            }),
+        }),
+    }
+}
+
+fn node(op_type: &str, name: String, input: Vec<String>) -> NodeProto {
+    NodeProto {
+        output: vec![name.clone()],
+        input,
+        name,
+        op_type: op_type.to_string(),
+        ..NodeProto::default()
+    }
+}
+
+/// Converts a model to an ONNX graph computing `architecture` over its
+/// current parameters, so a model trained online can be served by other
+/// inference runtimes (ONNX Runtime, tract, ...).
+///
+/// The graph takes a `[batch, features]` tensor named `input` and returns a
+/// `[batch, outputs]` tensor named `output`, in `f32` or `f64` like the
+/// model. Every dense layer becomes a `MatMul` and an `Add` of constant
+/// initializers, followed by its activation. The model's id, name and
+/// parameters version are written as metadata properties.
+///
+/// # Examples
+///
+/// ```
+/// use oml::model::Model;
+/// use oml::onnx::{self, Architecture, ModelProto};
+/// use prost::Message;
+///
+/// // Two weights and a bias.
+/// let model = Model::with_parameters(vec![0.5f32, -1.0, 2.0]);
+/// let bytes = onnx::export(&model, &Architecture::Logistic).unwrap();
+/// let graph = ModelProto::decode(&bytes[..]).unwrap().graph.unwrap();
+/// let ops: Vec<_> = graph.node.iter().map(|n| n.op_type.as_str()).collect();
+/// assert_eq!(ops, ["MatMul", "Add", "Sigmoid"]);
+/// ```
+pub fn export<T: Float + Debug + Send + Sync>(
+    model: &Model<T>,
+    architecture: &Architecture,
+) -> Result<Vec<u8>, ModelError> {
+    if model.sparse_params().is_some() {
+        return Err(ModelError::Unsupported(
+            "sparse parameters cannot be exported to ONNX".to_string(),
+        ));
+    }
+    let data_type = match size_of::<T>() {
+        4 => FLOAT,
+        8 => DOUBLE,
+        _ => {
+            return Err(ModelError::Unsupported(format!(
+                "{} parameters cannot be exported to ONNX",
+                std::any::type_name::<T>()
+            )))
+        }
+    };
+    let layers = architecture.layers(model)?;
+
+    let mut graph = GraphProto {
+        name: model.name().unwrap_or("oml").to_string(),
+        input: vec![value_info(INPUT_NAME, layers[0].inputs, data_type)],
+        output: vec![value_info(
+            OUTPUT_NAME,
+            layers[layers.len() - 1].outputs,
+            data_type,
+        )],
+        ..GraphProto::default()
+    };
+    let mut current = INPUT_NAME.to_string();
+    for layer in &layers {
+        let weights = format!("{}.weights", layer.name);
+        let bias = format!("{}.bias", layer.name);
+        graph.initializer.push(tensor(
+            weights.clone(),
+            &[layer.inputs, layer.outputs],
+            &layer.weights,
+            data_type,
+        ));
+        graph.initializer.push(tensor(
+            bias.clone(),
+            &[layer.outputs],
+            &layer.bias,
+            data_type,
+        ));
+        let matmul = node(
+            "MatMul",
+            format!("{}.matmul", layer.name),
+            vec![current, weights],
+        );
+        let add = node(
+            "Add",
+            format!("{}.add", layer.name),
+            vec![matmul.name.clone(), bias],
+        );
+        current = add.name.clone();
+        graph.node.extend([matmul, add]);
+        if let Some(activation) = layer.activation {
+            let op_type = activation.op_type();
+            let name = format!("{}.{}", layer.name, op_type.to_lowercase());
+            graph.node.push(node(op_type, name, vec![current]));
+            current = graph.node[graph.node.len() - 1].name.clone();
+        }
+    }
+    // The last node writes the graph output.
+    if let Some(last) = graph.node.last_mut() {
+        last.output = vec![OUTPUT_NAME.to_string()];
+    }
+
+    let metadata = model.metadata();
+    let mut metadata_props = vec![
+        ("oml_model_id", metadata.id),
+        ("oml_parameters_version", model.version().to_string()),
+    ];
+    if let Some(name) = metadata.name {
+        metadata_props.push(("oml_model_name", name));
+    }
+    let onnx = ModelProto {
+        ir_version: IR_VERSION,
+        producer_name: "oml".to_string(),
+        producer_version: env!("CARGO_PKG_VERSION").to_string(),
+        model_version: model.version() as i64,
+        graph: Some(graph),
+        opset_import: vec![OperatorSetIdProto {
+            domain: String::new(),
+            version: OPSET_VERSION,
+        }],
+        metadata_props: metadata_props
+            .into_iter()
+            .map(|(key, value)| StringStringEntryProto {
+                key: key.to_string(),
+                value,
+            })
+            .collect(),
+    };
+    Ok(onnx.encode_to_vec())
+}
+
+/// Operators [`StaticOnnxAlgorithm`] evaluates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    MatMul,
+    Gemm {
+        alpha: f64,
+        beta: f64,
+        trans_a: bool,
+        trans_b: bool,
+    },
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Relu,
+    Sigmoid,
+    Tanh,
+    Softmax,
+    Flatten,
+    Identity,
+}
+
+impl Op {
+    fn parse(node: &NodeProto) -> Result<Self, ModelError> {
+        let attribute = |name: &str| node.attribute.iter().find(|a| a.name == name);
+        let flag = |name: &str| attribute(name).is_some_and(|a| a.i != 0);
+        let float = |name: &str| attribute(name).map_or(1.0, |a| a.f as f64);
+        let last_axis = |default: i64| match attribute("axis").map_or(default, |a| a.i) {
+            -1 | 1 => Ok(()),
+            axis => Err(ModelError::Unsupported(format!(
+                "{} over axis {} is not supported",
+                node.op_type, axis
+            ))),
+        };
+        Ok(match node.op_type.as_str() {
+            "MatMul" => Op::MatMul,
+            "Gemm" => Op::Gemm {
+                alpha: float("alpha"),
+                beta: float("beta"),
+                trans_a: flag("transA"),
+                trans_b: flag("transB"),
+            },
+            "Add" => Op::Add,
+            "Sub" => Op::Sub,
+            "Mul" => Op::Mul,
+            "Div" => Op::Div,
+            "Relu" => Op::Relu,
+            "Sigmoid" => Op::Sigmoid,
+            "Tanh" => Op::Tanh,
+            "Softmax" => {
+                last_axis(-1)?;
+                Op::Softmax
+            }
+            "Flatten" => {
+                last_axis(1)?;
+                Op::Flatten
+            }
+            "Identity" | "Dropout" => Op::Identity,
+            op => {
+                return Err(ModelError::Unsupported(format!(
+                    "ONNX operator {} is not supported",
+                    op
+                )))
+            }
+        })
+    }
+}
+
+/// A node of an imported graph.
+#[derive(Debug, Clone)]
+struct Step {
+    op: Op,
+    inputs: Vec<String>,
+    output: String,
+}
+
+/// A tensor of rank 0 to 2 during evaluation.
+#[derive(Debug, Clone)]
+struct Value {
+    shape: Vec<usize>,
+    data: Vec<f64>,
+}
+
+impl Value {
+    /// Rows and columns of a matrix, `[1, n]` for a vector.
+    fn matrix(&self) -> Result<(usize, usize), ModelError> {
+        match self.shape[..] {
+            [] => Ok((1, 1)),
+            [n] => Ok((1, n)),
+            [rows, columns] => Ok((rows, columns)),
+            _ => Err(ModelError::Unsupported(
+                "tensors of rank above 2 are not supported".to_string(),
+            )),
+        }
+    }
+
+    fn transposed(&self) -> Result<Value, ModelError> {
+        let (rows, columns) = self.matrix()?;
+        Ok(Value {
+            shape: vec![columns, rows],
+            data: (0..rows * columns)
+                .map(|i| self.data[(i % rows) * columns + i / rows])
+                .collect(),
+        })
+    }
+
+    fn matmul(&self, other: &Value) -> Result<Value, ModelError> {
+        let (n, k) = self.matrix()?;
+        let (rows, m) = match other.shape[..] {
+            // A vector right operand is a column.
+            [k] => (k, 1),
+            _ => other.matrix()?,
+        };
+        if rows != k {
+            return Err(ModelError::InvalidInput(format!(
+                "cannot multiply {:?} by {:?}",
+                self.shape, other.shape
+            )));
+        }
+        let mut data = vec![0.0; n * m];
+        for i in 0..n {
+            for j in 0..m {
+                data[i * m + j] = (0..k)
+                    .map(|l| self.data[i * k + l] * other.data[l * m + j])
+                    .sum();
+            }
+        }
+        let shape = match other.shape.len() {
+            1 => vec![n],
+            _ => vec![n, m],
+        };
+        Ok(Value { shape, data })
+    }
+
+    /// Combines two values elementwise with numpy-style broadcasting.
+    fn broadcast(&self, other: &Value, f: impl Fn(f64, f64) -> f64) -> Result<Value, ModelError> {
+        let rank = self.shape.len().max(other.shape.len());
+        let padded = |shape: &[usize]| {
+            let mut padded = vec![1; rank - shape.len()];
+            padded.extend_from_slice(shape);
+            padded
+        };
+        let (a, b) = (padded(&self.shape), padded(&other.shape));
+        let shape = a
+            .iter()
+            .zip(&b)
+            .map(|(&x, &y)| match (x, y) {
+                (x, y) if x == y || y == 1 => Ok(x),
+                (1, y) => Ok(y),
+                _ => Err(ModelError::InvalidInput(format!(
+                    "cannot broadcast {:?} with {:?}",
+                    self.shape, other.shape
+                ))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let index = |flat: usize, dims: &[usize]| {
+            let (mut rest, mut index, mut stride) = (flat, 0, 1);
+            for (d, &size) in shape.iter().zip(dims).rev() {
+                index += (rest % d % size) * stride;
+                rest /= d;
+                stride *= size;
+            }
+            index
+        };
+        let data = (0..shape.iter().product())
+            .map(|i| f(self.data[index(i, &a)], other.data[index(i, &b)]))
+            .collect();
+        Ok(Value { shape, data })
+    }
+
+    fn map(mut self, f: impl Fn(f64) -> f64) -> Value {
+        self.data.iter_mut().for_each(|x| *x = f(*x));
+        self
+    }
+}
+
+/// Serves a model trained elsewhere and exported to ONNX, without training
+/// it: training and feedback steps return [`ModelError::Unsupported`] (a
+/// `501` on `/training`), so the same server can front offline models next
+/// to online ones.
+///
+/// [`StaticOnnxAlgorithm::import`] splits a graph into the algorithm, which
+/// keeps its operators, and a model holding its initializers as layout
+/// groups, so the weights can still be inspected, replaced with
+/// `PUT /model/weights` or rolled back like any other. Graphs of matrix
+/// products (`MatMul`, `Gemm`), elementwise arithmetic with broadcasting
+/// and `Relu`, `Sigmoid`, `Tanh`, `Softmax` and `Flatten` on tensors of rank
+/// up to 2 are supported, which covers linear models and multilayer
+/// perceptrons, such as those written by [`export`].
+///
+/// The input is one feature vector, fed as a `[1, features]` batch, and the
+/// output the flattened values of the graph output.
+///
+/// # Examples
+///
+/// ```
+/// use oml::algorithm::Algorithm;
+/// use oml::model::Model;
+/// use oml::onnx::{self, Architecture, StaticOnnxAlgorithm};
+///
+/// let trained = Model::with_parameters(vec![2.0f64, -1.0, 0.5]);
+/// let bytes = onnx::export(&trained, &Architecture::Linear).unwrap();
+/// let (model, algorithm) = StaticOnnxAlgorithm::import::<f64>(&bytes).unwrap();
+/// assert_eq!(algorithm.inference_step(&model, vec![3.0, 1.0]).unwrap(), vec![5.5]);
+/// assert!(algorithm.training_step(&model, vec![3.0, 1.0]).is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct StaticOnnxAlgorithm {
+    input: String,
+    output: String,
+    steps: Vec<Step>,
+}
+
+impl StaticOnnxAlgorithm {
+    /// Parses an ONNX model into the algorithm evaluating its graph and a
+    /// model holding its initializers, named after the graph, with the
+    /// input dimension of the graph input if it is fixed.
+    pub fn import<T>(bytes: &[u8]) -> Result<(Model<T>, Self), ModelError>
+    where
+        T: Float + Debug + Send + Sync,
+    {
+        let onnx = ModelProto::decode(bytes)
+            .map_err(|e| ModelError::InvalidInput(format!("invalid ONNX model: {}", e)))?;
+        let graph = onnx
+            .graph
+            .ok_or_else(|| ModelError::InvalidInput("the ONNX model has no graph".to_string()))?;
+
+        let mut layout = ModelLayout::new();
+        let mut parameters = Vec::new();
+        for initializer in &graph.initializer {
+            let dims: Vec<usize> = initializer
+                .dims
+                .iter()
+                .map(|&d| d.max(0) as usize)
+                .collect();
+            let values = initializer_values(initializer)?;
+            if values.len() != dims.iter().product::<usize>() {
+                return Err(ModelError::InvalidInput(format!(
+                    "initializer {} holds {} values for shape {:?}",
+                    initializer.name,
+                    values.len(),
+                    dims
+                )));
+            }
+            parameters.extend(
+                values
+                    .into_iter()
+                    .map(|v| T::from(v).unwrap_or_else(T::nan)),
+            );
+            layout = layout.group(initializer.name.clone(), &dims);
+        }
+
+        // The graph input is the one not fed by an initializer.
+        let input = graph
+            .input
+            .iter()
+            .find(|i| layout.get(&i.name).is_none())
+            .ok_or_else(|| ModelError::InvalidInput("the ONNX graph has no input".to_string()))?;
+        let output = graph
+            .output
+            .first()
+            .ok_or_else(|| ModelError::InvalidInput("the ONNX graph has no output".to_string()))?;
+        let mut available: Vec<&str> = layout.groups().iter().map(|g| g.name.as_str()).collect();
+        available.push(&input.name);
+        let mut steps = Vec::with_capacity(graph.node.len());
+        for node in &graph.node {
+            let op = Op::parse(node)?;
+            let inputs: Vec<String> = node
+                .input
+                .iter()
+                .filter(|i| !i.is_empty())
+                .cloned()
+                .collect();
+            if let Some(missing) = inputs.iter().find(|i| !available.contains(&i.as_str())) {
+                return Err(ModelError::InvalidInput(format!(
+                    "node {} reads {}, which no earlier node produces",
+                    node.name, missing
+                )));
+            }
+            let arity = match op {
+                Op::MatMul | Op::Add | Op::Sub | Op::Mul | Op::Div => 2..=2,
+                Op::Gemm { .. } => 2..=3,
+                _ => 1..=1,
+            };
+            let [output] = &node.output[..] else {
+                return Err(ModelError::Unsupported(format!(
+                    "node {} has {} outputs",
+                    node.name,
+                    node.output.len()
+                )));
+            };
+            if !arity.contains(&inputs.len()) {
+                return Err(ModelError::InvalidInput(format!(
+                    "node {} has {} inputs",
+                    node.name,
+                    inputs.len()
+                )));
+            }
+            available.push(output);
+            steps.push(Step {
+                op,
+                inputs,
+                output: output.clone(),
+            });
+        }
+        if !available.contains(&output.name.as_str()) {
+            return Err(ModelError::InvalidInput(format!(
+                "no node produces the graph output {}",
+                output.name
+            )));
+        }
+
+        let mut model = Model::with_layout(layout, parameters)?;
+        if !graph.name.is_empty() {
+            model = model.with_name(graph.name.clone());
+        }
+        let features = input.r#type.as_ref().and_then(|t| {
+            t.tensor_type
+                .as_ref()?
+                .shape
+                .as_ref()?
+                .dim
+                .last()?
+                .dim_value
+        });
+        if let Some(features) = features.filter(|&f| f > 0) {
+            model = model.with_input_dimension(features as usize);
+        }
+        let algorithm = StaticOnnxAlgorithm {
+            input: input.name.clone(),
+            output: output.name.clone(),
+            steps,
+        };
+        Ok((model, algorithm))
+    }
+
+    /// Reads the ONNX model at `path`; see [`StaticOnnxAlgorithm::import`].
+    pub fn load<T>(path: impl AsRef<Path>) -> Result<(Model<T>, Self), ModelError>
+    where
+        T: Float + Debug + Send + Sync,
+    {
+        Self::import(&std::fs::read(path)?)
+    }
+
+    fn evaluate<T: Float + Debug + Send + Sync>(
+        &self,
+        model: &Model<T>,
+        features: &[T],
+    ) -> Result<Vec<f64>, ModelError> {
+        let params = model.read_params();
+        let mut values: HashMap<&str, Value> = HashMap::new();
+        values.insert(
+            &self.input,
+            Value {
+                shape: vec![1, features.len()],
+                data: features
+                    .iter()
+                    .map(|f| f.to_f64().unwrap_or(f64::NAN))
+                    .collect(),
+            },
+        );
+        let lookup = |values: &HashMap<&str, Value>, name: &str| {
+            if let Some(value) = values.get(name) {
+                return Ok(value.clone());
+            }
+            let group = model.layout().get(name).zip(params.group(name));
+            let (group, data) = group.ok_or_else(|| {
+                ModelError::InvalidInput(format!("the model has no {} parameter group", name))
+            })?;
+            Ok::<_, ModelError>(Value {
+                shape: group.shape.clone(),
+                data: data
+                    .iter()
+                    .map(|p| p.to_f64().unwrap_or(f64::NAN))
+                    .collect(),
+            })
+        };
+        for step in &self.steps {
+            let inputs = step
+                .inputs
+                .iter()
+                .map(|name| lookup(&values, name))
+                .collect::<Result<Vec<_>, _>>()?;
+            let result = match step.op {
+                Op::MatMul => inputs[0].matmul(&inputs[1])?,
+                Op::Gemm {
+                    alpha,
+                    beta,
+                    trans_a,
+                    trans_b,
+                } => {
+                    let a = if trans_a {
+                        inputs[0].transposed()?
+                    } else {
+                        inputs[0].clone()
+                    };
+                    let b = if trans_b {
+                        inputs[1].transposed()?
+                    } else {
+                        inputs[1].clone()
+                    };
+                    let product = a.matmul(&b)?.map(|x| alpha * x);
+                    match inputs.get(2) {
+                        Some(c) => product.broadcast(c, |x, c| x + beta * c)?,
+                        None => product,
+                    }
+                }
+                Op::Add => inputs[0].broadcast(&inputs[1], |a, b| a + b)?,
+                Op::Sub => inputs[0].broadcast(&inputs[1], |a, b| a - b)?,
+                Op::Mul => inputs[0].broadcast(&inputs[1], |a, b| a * b)?,
+                Op::Div => inputs[0].broadcast(&inputs[1], |a, b| a / b)?,
+                Op::Relu => inputs[0].clone().map(|x| x.max(0.0)),
+                Op::Sigmoid => inputs[0].clone().map(|x| 1.0 / (1.0 + (-x).exp())),
+                Op::Tanh => inputs[0].clone().map(f64::tanh),
+                Op::Softmax => {
+                    let mut value = inputs[0].clone();
+                    let (_, columns) = value.matrix()?;
+                    for row in value.data.chunks_mut(columns.max(1)) {
+                        let max = row.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                        row.iter_mut().for_each(|x| *x = (*x - max).exp());
+                        let sum: f64 = row.iter().sum();
+                        row.iter_mut().for_each(|x| *x /= sum);
+                    }
+                    value
+                }
+                Op::Flatten => {
+                    let mut value = inputs[0].clone();
+                    let rows = value.shape.first().copied().unwrap_or(1);
+                    value.shape = vec![rows, value.data.len() / rows.max(1)];
+                    value
+                }
+                Op::Identity => inputs[0].clone(),
+            };
+            values.insert(&step.output, result);
+        }
+        Ok(lookup(&values, &self.output)?.data)
+    }
+}
+
+/// Values of an initializer, whatever field holds them.
+fn initializer_values(tensor: &TensorProto) -> Result<Vec<f64>, ModelError> {
+    let unsupported = || {
+        ModelError::Unsupported(format!(
+            "initializer {} has unsupported data type {}",
+            tensor.name, tensor.data_type
+        ))
+    };
+    if tensor.raw_data.is_empty() {
+        return match tensor.data_type {
+            FLOAT => Ok(tensor.float_data.iter().map(|&v| v as f64).collect()),
+            DOUBLE => Ok(tensor.double_data.clone()),
+            _ => Err(unsupported()),
+        };
+    }
+    let width = match tensor.data_type {
+        FLOAT => 4,
+        DOUBLE => 8,
+        _ => return Err(unsupported()),
+    };
+    if !tensor.raw_data.len().is_multiple_of(width) {
+        return Err(ModelError::InvalidInput(format!(
+            "initializer {} has truncated raw data",
+            tensor.name
+        )));
+    }
+    Ok(tensor
+        .raw_data
+        .chunks_exact(width)
+        .map(|chunk| match *chunk {
+            [a, b, c, d] => f32::from_le_bytes([a, b, c, d]) as f64,
+            _ => {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(chunk);
+                f64::from_le_bytes(bytes)
+            }
+        })
+        .collect())
+}
+
+impl<T> Algorithm<T> for StaticOnnxAlgorithm
+where
+    T: Float + Debug + Send + Sync + Sum + Datum,
+{
+    type Input = Vec<T>;
+    type Output = Vec<T>;
+
+    fn training_step(&self, _model: &Model<T>, _x: Vec<T>) -> Result<(), ModelError> {
+        Err(ModelError::Unsupported(
+            "ONNX models are served for inference only".to_string(),
+        ))
+    }
+
+    fn inference_step(&self, model: &Model<T>, x: Vec<T>) -> Result<Vec<T>, ModelError> {
+        Ok(self
+            .evaluate(model, &x)?
+            .into_iter()
+            .map(|v| T::from(v).unwrap_or_else(T::nan))
+            .collect())
+    }
+
+    fn weighted_training_step(
+        &self,
+        model: &Model<T>,
+        x: Vec<T>,
+        _weight: f64,
+    ) -> Result<(), ModelError> {
+        self.training_step(model, x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::AppState;
+    use crate::server::configure;
+    use actix_web::{http, test as actix_test, web, App};
+
+    /// Runs an exported graph on one sample.
+    fn evaluate(bytes: &[u8], input: Vec<f64>) -> Vec<f64> {
+        let (model, algorithm) = StaticOnnxAlgorithm::import::<f64>(bytes).unwrap();
+        algorithm.inference_step(&model, input).unwrap()
+    }
+
+    #[test]
+    fn test_exported_mlp_computes_the_model() {
+        let layout = ModelLayout::new()
+            .group("hidden.weights", &[2, 2])
+            .group("hidden.bias", &[2])
+            .group("out.weights", &[2])
+            .group("out.bias", &[1]);
+        let params = vec![1.0, -1.0, 2.0, 1.0, 0.5, -4.0, 3.0, 10.0, -1.0];
+        let model = Model::with_layout(layout, params).unwrap().with_name("ctr");
+        let architecture = Architecture::Mlp(vec![
+            DenseLayer::new("hidden.weights", "hidden.bias").with_activation(Activation::Relu),
+            DenseLayer::new("out.weights", "out.bias").with_activation(Activation::Sigmoid),
+        ]);
+        let bytes = export(&model, &architecture).unwrap();
+        let onnx = ModelProto::decode(&bytes[..]).unwrap();
+        assert_eq!(onnx.opset_import[0].version, OPSET_VERSION);
+        assert!(onnx
+            .metadata_props
+            .iter()
+            .any(|p| p.key == "oml_model_name" && p.value == "ctr"));
+        let graph = onnx.graph.as_ref().unwrap();
+        let ops: Vec<_> = graph.node.iter().map(|n| n.op_type.as_str()).collect();
+        assert_eq!(ops, ["MatMul", "Add", "Relu", "MatMul", "Add", "Sigmoid"]);
+        assert_eq!(graph.initializer[2].dims, vec![2, 1]);
+        // hidden = relu([1 + 4 + 0.5, -1 + 2 - 4]) = [5.5, 0], out = 3 * 5.5 - 1.
+        let output = evaluate(&bytes, vec![1.0, 2.0]);
+        assert!((output[0] - 1.0 / (1.0 + (-15.5f64).exp())).abs() < 1e-12);
+
+        // A bare linear model: weights followed by the bias.
+        let linear = Model::with_parameters(vec![2.0, -1.0, 0.5]);
+        let bytes = export(&linear, &Architecture::Linear).unwrap();
+        assert_eq!(evaluate(&bytes, vec![3.0, 1.0]), vec![5.5]);
+
+        let mismatched = Architecture::Mlp(vec![DenseLayer::new("out.weights", "hidden.bias")]);
+        assert!(matches!(
+            export(&model, &mismatched),
+            Err(ModelError::InvalidInput(_))
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn test_imported_graph_served_for_inference_only() {
+        // A classifier as written by other exporters: a Gemm over a
+        // transposed weight matrix stored as raw bytes, then a softmax.
+        let weights = TensorProto {
+            name: "fc.weight".to_string(),
+            dims: vec![2, 3],
+            data_type: FLOAT,
+            raw_data: [1.0f32, 0.0, 0.0, 0.0, 1.0, 1.0]
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect(),
+            ..TensorProto::default()
+        };
+        let bias = tensor("fc.bias".to_string(), &[2], &[0.0f32, 1.0], FLOAT);
+        let gemm = NodeProto {
+            input: vec![
+                "x".to_string(),
+                "fc.weight".to_string(),
+                "fc.bias".to_string(),
+            ],
+            attribute: vec![AttributeProto {
+                name: "transB".to_string(),
+                i: 1,
+                ..AttributeProto::default()
+            }],
+            ..node("Gemm", "logits".to_string(), vec![])
+        };
+        let onnx = ModelProto {
+            graph: Some(GraphProto {
+                name: "classifier".to_string(),
+                node: vec![
+                    gemm,
+                    node("Softmax", "probs".to_string(), vec!["logits".to_string()]),
+                ],
+                initializer: vec![weights, bias],
+                input: vec![value_info("x", 3, FLOAT)],
+                output: vec![value_info("probs", 2, FLOAT)],
+            }),
+            ..ModelProto::default()
+        };
+        let (model, algorithm) = StaticOnnxAlgorithm::import::<f32>(&onnx.encode_to_vec()).unwrap();
+        assert_eq!(model.name(), Some("classifier"));
+        assert_eq!(model.read_params().group("fc.bias"), Some(&[0.0, 1.0][..]));
+
+        let state = AppState::new(model, algorithm);
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(configure::<f32, StaticOnnxAlgorithm>),
+        )
+        .await;
+        let post = |uri: &str| {
+            actix_test::TestRequest::post()
+                .uri(uri)
+                .set_json([2.0f32, 0.5, 0.5])
+                .to_request()
+        };
+        // Logits [2, 2]: both classes equally likely.
+        let probabilities: Vec<f32> =
+            actix_test::call_and_read_body_json(&app, post("/inference?format=bare")).await;
+        assert_eq!(probabilities, vec![0.5, 0.5]);
+        let trained = actix_test::call_service(&app, post("/training")).await;
+        assert_eq!(trained.status(), http::StatusCode::NOT_IMPLEMENTED);
+
+        let unsupported = ModelProto {
+            graph: Some(GraphProto {
+                node: vec![node("Conv", "y".to_string(), vec!["x".to_string()])],
+                ..onnx.graph.clone().unwrap()
+            }),
+            ..onnx
+        };
+        assert!(matches!(
+            StaticOnnxAlgorithm::import::<f32>(&unsupported.encode_to_vec()),
+            Err(ModelError::Unsupported(_))
+        ));
+    }
+}
@@ -0,0 +1,284 @@
+use crate::algorithm::{Algorithm, Datum};
+use crate::auth::PeerClient;
+use crate::cluster::Membership;
+use crate::errors::ModelError;
+use crate::model::Model;
+use num_traits::Float;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::task::JoinSet;
+
+/// Batch of training samples uploaded to `POST /jobs`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchJobRequest<I> {
+    pub inputs: Vec<I>,
+}
+
+/// One shard sent to a worker: the parameters to start from and its samples.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShardRequest<T, I = T> {
+    pub job_id: u64,
+    pub shard: usize,
+    pub parameters: Vec<T>,
+    pub inputs: Vec<I>,
+}
+
+/// Worker reply: the change its training made to the shipped parameters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShardResult<T> {
+    pub job_id: u64,
+    pub shard: usize,
+    pub delta: Vec<T>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShardStatus {
+    pub worker: String,
+    pub samples: usize,
+    pub state: JobState,
+    pub error: Option<String>,
+}
+
+/// Consolidated status of a batch job, as reported by `GET /jobs/{id}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobStatus {
+    pub id: u64,
+    pub state: JobState,
+    pub samples: usize,
+    pub shards: Vec<ShardStatus>,
+}
+
+/// Trains a copy of `parameters` on `inputs` and returns the resulting delta.
+///
+/// This is the worker side of a batch job; the worker's own model is untouched.
+pub fn train_shard<T, A>(
+    algorithm: &A,
+    parameters: &[T],
+    inputs: &[A::Input],
+) -> Result<Vec<T>, ModelError>
+where
+    T: Float + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let model = Model::with_parameters(parameters.to_vec());
+    for input in inputs {
+        algorithm.training_step(&model, input.clone())?;
+    }
+    let after = model.read_params();
+    Ok(after
+        .iter()
+        .zip(parameters.iter())
+        .map(|(after, before)| *after - *before)
+        .collect())
+}
+
+/// Distributes large training batches over registered worker instances.
+///
+/// A job splits its samples into one contiguous shard per worker, ships each
+/// shard with the current parameters to `POST {worker}/jobs/shards`, and once
+/// every shard returned adds the mean of the worker deltas to the model. If
+/// any shard fails the job fails and the model is left unchanged.
+#[derive(Debug)]
+pub struct Orchestrator {
+    workers: Mutex<Vec<String>>,
+    membership: OnceLock<Arc<Membership>>,
+    jobs: Mutex<BTreeMap<u64, JobStatus>>,
+    next_id: AtomicU64,
+    http: PeerClient,
+}
+
+impl Orchestrator {
+    pub fn new(workers: Vec<String>) -> Self {
+        Orchestrator {
+            workers: Mutex::new(workers),
+            membership: OnceLock::new(),
+            jobs: Mutex::new(BTreeMap::new()),
+            next_id: AtomicU64::new(1),
+            http: PeerClient::default(),
+        }
+    }
+
+    /// Client of the requests to peers, see [`AuthConfig`](crate::auth::AuthConfig).
+    pub(crate) fn peer_client(&self) -> &PeerClient {
+        &self.http
+    }
+
+    /// Adds the live members reported by `membership` to the workers.
+    pub fn set_membership(&self, membership: Arc<Membership>) {
+        let _ = self.membership.set(membership);
+    }
+
+    /// Registered workers plus, if set, the live cluster members.
+    pub fn workers(&self) -> Result<Vec<String>, ModelError> {
+        let mut workers = self.workers.lock()?.clone();
+        if let Some(membership) = self.membership.get() {
+            for peer in membership.live_peers() {
+                if !workers.contains(&peer) {
+                    workers.push(peer);
+                }
+            }
+        }
+        Ok(workers)
+    }
+
+    /// Adds a worker base URL; registering the same address twice is a no-op.
+    pub fn register_worker(&self, address: &str) -> Result<Vec<String>, ModelError> {
+        let mut workers = self.workers.lock()?;
+        if !workers.iter().any(|w| w == address) {
+            workers.push(address.to_string());
+        }
+        Ok(workers.clone())
+    }
+
+    pub fn job(&self, id: u64) -> Result<Option<JobStatus>, ModelError> {
+        Ok(self.jobs.lock()?.get(&id).cloned())
+    }
+
+    pub fn jobs(&self) -> Result<Vec<JobStatus>, ModelError> {
+        Ok(self.jobs.lock()?.values().cloned().collect())
+    }
+
+    /// Shards `inputs` across the workers and starts the job in the background.
+    pub fn submit<T, I>(
+        self: &Arc<Self>,
+        model: Arc<Model<T>>,
+        inputs: Vec<I>,
+    ) -> Result<JobStatus, ModelError>
+    where
+        T: Float + Serialize + DeserializeOwned + Debug + Send + Sync + 'static,
+        I: Datum,
+    {
+        if inputs.is_empty() {
+            return Err(ModelError::InvalidInput("job has no samples".to_string()));
+        }
+        let workers = self.workers()?;
+        if workers.is_empty() {
+            return Err(ModelError::InvalidInput(
+                "no workers are registered".to_string(),
+            ));
+        }
+        let shard_size = inputs.len().div_ceil(workers.len());
+        let shards: Vec<(String, Vec<I>)> = workers
+            .into_iter()
+            .zip(inputs.chunks(shard_size))
+            .map(|(worker, chunk)| (worker, chunk.to_vec()))
+            .collect();
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let status = JobStatus {
+            id,
+            state: JobState::Running,
+            samples: inputs.len(),
+            shards: shards
+                .iter()
+                .map(|(worker, chunk)| ShardStatus {
+                    worker: worker.clone(),
+                    samples: chunk.len(),
+                    state: JobState::Running,
+                    error: None,
+                })
+                .collect(),
+        };
+        self.jobs.lock()?.insert(id, status.clone());
+        tokio::spawn(self.clone().run(id, model, shards));
+        Ok(status)
+    }
+
+    async fn run<T, I>(
+        self: Arc<Self>,
+        id: u64,
+        model: Arc<Model<T>>,
+        shards: Vec<(String, Vec<I>)>,
+    ) where
+        T: Float + Serialize + DeserializeOwned + Debug + Send + Sync + 'static,
+        I: Datum,
+    {
+        let parameters = model.read_params().clone();
+        let mut tasks = JoinSet::new();
+        for (shard, (worker, inputs)) in shards.into_iter().enumerate() {
+            let request = ShardRequest {
+                job_id: id,
+                shard,
+                parameters: parameters.clone(),
+                inputs,
+            };
+            let http = self.http.clone();
+            tasks.spawn(async move {
+                let result = dispatch(&http, &worker, &request).await;
+                (shard, result)
+            });
+        }
+
+        let mut deltas = Vec::new();
+        while let Some(joined) = tasks.join_next().await {
+            let Ok((shard, result)) = joined else {
+                continue;
+            };
+            let outcome = match result {
+                Ok(reply) if reply.delta.len() == parameters.len() => {
+                    deltas.push(reply.delta);
+                    Ok(())
+                }
+                Ok(_) => Err("worker returned a delta of the wrong size".to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            if let Ok(mut jobs) = self.jobs.lock() {
+                if let Some(status) = jobs.get_mut(&id).and_then(|j| j.shards.get_mut(shard)) {
+                    status.state = if outcome.is_ok() {
+                        JobState::Completed
+                    } else {
+                        JobState::Failed
+                    };
+                    status.error = outcome.err();
+                }
+            }
+        }
+
+        let Ok(mut jobs) = self.jobs.lock() else {
+            return;
+        };
+        let Some(job) = jobs.get_mut(&id) else { return };
+        if deltas.len() != job.shards.len() {
+            job.state = JobState::Failed;
+            return;
+        }
+        let n = T::from(deltas.len()).unwrap_or_else(T::one);
+        let mut params = model.write_params();
+        for delta in &deltas {
+            params
+                .iter_mut()
+                .zip(delta.iter())
+                .for_each(|(p, d)| *p = *p + *d / n);
+        }
+        job.state = JobState::Completed;
+    }
+}
+
+async fn dispatch<T, I>(
+    http: &PeerClient,
+    worker: &str,
+    request: &ShardRequest<T, I>,
+) -> Result<ShardResult<T>, ModelError>
+where
+    T: Serialize + DeserializeOwned,
+    I: Serialize,
+{
+    let response = http
+        .post(format!("{}/jobs/shards", worker.trim_end_matches('/')))
+        .json(request)
+        .send()
+        .await?;
+    Ok(response.error_for_status()?.json().await?)
+}
@@ -0,0 +1,247 @@
+use crate::algorithm::Datum;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Statistic used to score a sample against the recent window.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutlierMethod {
+    /// `|x - mean| / std`.
+    ZScore,
+    /// `|x - median| / (1.4826 * MAD)`, robust to the outliers themselves.
+    Mad,
+}
+
+/// Configuration of an [`OutlierFilter`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutlierConfig {
+    pub method: OutlierMethod,
+    /// Samples scoring above this value are quarantined.
+    pub threshold: f64,
+    /// Number of recently accepted samples the statistics are computed over.
+    pub window: usize,
+    /// Samples are always accepted until the window holds this many values.
+    pub min_samples: usize,
+    /// Maximum number of quarantined samples kept for review (oldest dropped first).
+    pub quarantine_capacity: usize,
+}
+
+impl Default for OutlierConfig {
+    fn default() -> Self {
+        OutlierConfig {
+            method: OutlierMethod::Mad,
+            threshold: 3.5,
+            window: 256,
+            min_samples: 16,
+            quarantine_capacity: 1024,
+        }
+    }
+}
+
+/// A training sample held back by the filter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantinedSample<T> {
+    pub value: T,
+    pub score: f64,
+    /// Seconds since the Unix epoch at which the sample was quarantined.
+    pub quarantined_at: u64,
+}
+
+/// Outcome of running a sample through an [`OutlierFilter`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutlierVerdict {
+    Accept,
+    Quarantine { score: f64 },
+}
+
+/// Guard stage that scores training samples against recent statistics and
+/// quarantines extreme values into a review buffer instead of letting them
+/// reach the model.
+#[derive(Debug)]
+pub struct OutlierFilter<T>
+where
+    T: Datum,
+{
+    config: OutlierConfig,
+    window: VecDeque<f64>,
+    quarantine: VecDeque<QuarantinedSample<T>>,
+}
+
+impl<T> OutlierFilter<T>
+where
+    T: Datum,
+{
+    /// Creates a filter with an empty window.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oml::outliers::{OutlierConfig, OutlierFilter, OutlierVerdict};
+    ///
+    /// let mut filter: OutlierFilter<f32> = OutlierFilter::new(OutlierConfig::default());
+    /// assert_eq!(filter.check(1.0), OutlierVerdict::Accept);
+    /// ```
+    pub fn new(config: OutlierConfig) -> Self {
+        OutlierFilter {
+            config,
+            window: VecDeque::new(),
+            quarantine: VecDeque::new(),
+        }
+    }
+
+    pub fn config(&self) -> &OutlierConfig {
+        &self.config
+    }
+
+    /// Scores `x` against the current window without recording it.
+    ///
+    /// Returns `None` while the window is still warming up or has zero
+    /// spread, and for samples without a numeric value.
+    pub fn score(&self, x: &T) -> Option<f64> {
+        if self.window.len() < self.config.min_samples.max(1) {
+            return None;
+        }
+        let x = x.score()?;
+        let (center, spread) = match self.config.method {
+            OutlierMethod::ZScore => {
+                let n = self.window.len() as f64;
+                let mean = self.window.iter().sum::<f64>() / n;
+                let var = self.window.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+                (mean, var.sqrt())
+            }
+            OutlierMethod::Mad => {
+                let center = median(self.window.iter().copied().collect());
+                let mad = median(self.window.iter().map(|v| (v - center).abs()).collect());
+                (center, 1.4826 * mad)
+            }
+        };
+        // A degenerate window (e.g. a constant stream) carries no notion of
+        // scale, so nothing can be called extreme relative to it.
+        (spread > 0.0).then(|| (x - center).abs() / spread)
+    }
+
+    /// Scores `x` and either records it in the window (accepted) or stores it
+    /// in the quarantine buffer. Samples with a non-finite value (NaN or
+    /// infinite) are always quarantined, with an infinite score, so they
+    /// never reach the model nor the window.
+    pub fn check(&mut self, x: T) -> OutlierVerdict {
+        let score = match x.score() {
+            Some(value) if !value.is_finite() => Some(f64::INFINITY),
+            _ => self.score(&x),
+        };
+        match score {
+            Some(score) if score > self.config.threshold => {
+                if self.quarantine.len() >= self.config.quarantine_capacity {
+                    self.quarantine.pop_front();
+                }
+                if self.config.quarantine_capacity > 0 {
+                    self.quarantine.push_back(QuarantinedSample {
+                        value: x,
+                        score,
+                        quarantined_at: unix_now(),
+                    });
+                }
+                OutlierVerdict::Quarantine { score }
+            }
+            _ => {
+                if let Some(value) = x.score() {
+                    if self.window.len() >= self.config.window {
+                        self.window.pop_front();
+                    }
+                    self.window.push_back(value);
+                }
+                OutlierVerdict::Accept
+            }
+        }
+    }
+
+    /// Returns the quarantined samples, oldest first.
+    pub fn quarantined(&self) -> Vec<QuarantinedSample<T>> {
+        self.quarantine.iter().cloned().collect()
+    }
+
+    /// Empties the quarantine buffer, returning its previous contents.
+    pub fn drain_quarantine(&mut self) -> Vec<QuarantinedSample<T>> {
+        self.quarantine.drain(..).collect()
+    }
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn warmed_up(method: OutlierMethod) -> OutlierFilter<f64> {
+        let mut filter = OutlierFilter::new(OutlierConfig {
+            method,
+            threshold: 3.0,
+            window: 32,
+            min_samples: 8,
+            quarantine_capacity: 2,
+        });
+        for i in 0..16 {
+            assert_eq!(filter.check(10.0 + (i % 4) as f64), OutlierVerdict::Accept);
+        }
+        filter
+    }
+
+    #[test]
+    fn test_quarantines_non_finite_values() {
+        let mut filter = warmed_up(OutlierMethod::ZScore);
+        for value in [f64::NAN, f64::INFINITY] {
+            assert_eq!(
+                filter.check(value),
+                OutlierVerdict::Quarantine {
+                    score: f64::INFINITY
+                }
+            );
+        }
+        // The window is untouched, so scores stay finite.
+        assert!(filter.score(&11.0).unwrap().is_finite());
+        // Even before the window is warm.
+        let mut cold: OutlierFilter<f64> = OutlierFilter::new(OutlierConfig::default());
+        assert_ne!(cold.check(f64::NAN), OutlierVerdict::Accept);
+    }
+
+    #[test]
+    fn test_quarantines_extreme_values() {
+        for method in [OutlierMethod::ZScore, OutlierMethod::Mad] {
+            let mut filter = warmed_up(method);
+            assert_eq!(filter.check(11.5), OutlierVerdict::Accept);
+            assert!(matches!(
+                filter.check(1000.0),
+                OutlierVerdict::Quarantine { .. }
+            ));
+            assert_eq!(filter.quarantined().len(), 1);
+            assert_eq!(filter.quarantined()[0].value, 1000.0);
+        }
+    }
+
+    #[test]
+    fn test_quarantine_is_bounded() {
+        let mut filter = warmed_up(OutlierMethod::Mad);
+        for value in [500.0, 600.0, 700.0] {
+            filter.check(value);
+        }
+        let values: Vec<f64> = filter.drain_quarantine().iter().map(|s| s.value).collect();
+        assert_eq!(values, vec![600.0, 700.0]);
+        assert!(filter.quarantined().is_empty());
+    }
+}
@@ -0,0 +1,299 @@
+use crate::auth::PeerClient;
+use crate::errors::ModelError;
+use crate::model::Model;
+use num_traits::Float;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// What to do with a gradient computed against an outdated parameter snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StalenessPolicy {
+    /// Refuse any push whose staleness exceeds `max_staleness`.
+    Reject,
+    /// Accept pushes within `max_staleness`, scaling them by `1 / (1 + staleness)`.
+    Damp,
+}
+
+/// Configuration of a [`ParameterServer`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParameterServerConfig {
+    /// Step size applied to pushed gradients (`w -= learning_rate * g`).
+    pub learning_rate: f64,
+    /// Maximum number of updates a worker's snapshot may lag behind.
+    pub max_staleness: u64,
+    pub staleness_policy: StalenessPolicy,
+}
+
+impl Default for ParameterServerConfig {
+    fn default() -> Self {
+        ParameterServerConfig {
+            learning_rate: 0.01,
+            max_staleness: 8,
+            staleness_policy: StalenessPolicy::Damp,
+        }
+    }
+}
+
+/// Parameters as handed out to workers, tagged with the server clock.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParameterSnapshot<T> {
+    pub clock: u64,
+    pub values: Vec<T>,
+}
+
+/// A gradient computed by a worker against the snapshot taken at `base_clock`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GradientPush<T> {
+    pub worker_id: String,
+    pub base_clock: u64,
+    pub gradient: Vec<T>,
+}
+
+/// Acknowledgement of an applied gradient.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PushAck {
+    /// Server clock after the update was applied.
+    pub clock: u64,
+    pub staleness: u64,
+    /// Factor the gradient was scaled by before being applied.
+    pub scale: f64,
+}
+
+/// Coordinates parameter pulls and gradient pushes from remote workers.
+///
+/// Every applied push advances a logical clock; workers send back the clock
+/// of the snapshot their gradient was computed on so the server can bound
+/// (and damp) stale contributions. Pushes are serialized so the server is
+/// the model's only writer while it applies them.
+#[derive(Debug)]
+pub struct ParameterServer {
+    config: ParameterServerConfig,
+    clock: AtomicU64,
+    write_lock: Mutex<()>,
+}
+
+impl ParameterServer {
+    pub fn new(config: ParameterServerConfig) -> Self {
+        ParameterServer {
+            config,
+            clock: AtomicU64::new(0),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn config(&self) -> &ParameterServerConfig {
+        &self.config
+    }
+
+    /// Number of pushes applied so far.
+    pub fn clock(&self) -> u64 {
+        self.clock.load(Ordering::SeqCst)
+    }
+
+    /// Returns a copy of the current parameters tagged with the clock.
+    pub fn pull<T>(&self, model: &Model<T>) -> ParameterSnapshot<T>
+    where
+        T: Float + Debug + Send + Sync,
+    {
+        ParameterSnapshot {
+            clock: self.clock(),
+            values: model.read_params().clone(),
+        }
+    }
+
+    /// Applies a worker's gradient to `model`, subject to the staleness policy.
+    pub fn push<T>(&self, model: &Model<T>, push: &GradientPush<T>) -> Result<PushAck, ModelError>
+    where
+        T: Float + Debug + Send + Sync,
+    {
+        let _guard = self.write_lock.lock()?;
+        let clock = self.clock();
+        if push.base_clock > clock {
+            return Err(ModelError::InvalidInput(format!(
+                "base clock {} is ahead of server clock {}",
+                push.base_clock, clock
+            )));
+        }
+        let staleness = clock - push.base_clock;
+        if staleness > self.config.max_staleness {
+            return Err(ModelError::StaleUpdate {
+                staleness,
+                max_staleness: self.config.max_staleness,
+            });
+        }
+        let scale = match self.config.staleness_policy {
+            StalenessPolicy::Reject if staleness > 0 => {
+                return Err(ModelError::StaleUpdate {
+                    staleness,
+                    max_staleness: 0,
+                })
+            }
+            StalenessPolicy::Reject => 1.0,
+            StalenessPolicy::Damp => 1.0 / (1.0 + staleness as f64),
+        };
+
+        let count = model.read_params().len();
+        if push.gradient.len() != count {
+            return Err(ModelError::InvalidInput(format!(
+                "gradient has {} entries, model has {}",
+                push.gradient.len(),
+                count
+            )));
+        }
+        if let Some(i) = push.gradient.iter().position(|grad| !grad.is_finite()) {
+            return Err(ModelError::InvalidInput(format!(
+                "gradient entry {} is not finite",
+                i
+            )));
+        }
+
+        let mut params = model.write_trainable();
+        let step = T::from(self.config.learning_rate * scale).unwrap_or_else(T::zero);
+        params
+            .iter_mut()
+            .zip(push.gradient.iter())
+            .for_each(|(param, grad)| *param = *param - step * *grad);
+
+        let clock = self.clock.fetch_add(1, Ordering::SeqCst) + 1;
+        Ok(PushAck {
+            clock,
+            staleness,
+            scale,
+        })
+    }
+}
+
+/// HTTP client used by remote workers to talk to a parameter server.
+#[derive(Debug, Clone)]
+pub struct ParameterServerClient {
+    base_url: String,
+    http: PeerClient,
+}
+
+impl ParameterServerClient {
+    /// Creates a client for the server listening at `base_url` (e.g. `http://10.0.0.1:8080`).
+    pub fn new(base_url: &str) -> Self {
+        ParameterServerClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http: PeerClient::default(),
+        }
+    }
+
+    /// Authenticates with `token` against a server built with
+    /// [`AuthConfig`](crate::auth::AuthConfig).
+    pub fn with_bearer_token(self, token: &str) -> Self {
+        self.http.set_token(token);
+        self
+    }
+
+    /// Fetches the current parameters from the server.
+    pub async fn pull<T>(&self) -> Result<ParameterSnapshot<T>, ModelError>
+    where
+        T: DeserializeOwned,
+    {
+        let response = self
+            .http
+            .get(format!("{}/ps/parameters", self.base_url))
+            .send()
+            .await?;
+        Ok(response.error_for_status()?.json().await?)
+    }
+
+    /// Pushes a gradient computed against the snapshot at `push.base_clock`.
+    pub async fn push<T>(&self, push: &GradientPush<T>) -> Result<PushAck, ModelError>
+    where
+        T: Serialize,
+    {
+        let response = self
+            .http
+            .post(format!("{}/ps/gradients", self.base_url))
+            .json(push)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(ModelError::RemoteError(format!("{}: {}", status, body)));
+        }
+        Ok(response.json().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push(base_clock: u64, gradient: Vec<f64>) -> GradientPush<f64> {
+        GradientPush {
+            worker_id: "worker-1".to_string(),
+            base_clock,
+            gradient,
+        }
+    }
+
+    #[test]
+    fn test_push_applies_gradient_and_advances_clock() {
+        let model = Model::with_parameters(vec![1.0, 2.0]);
+        let server = ParameterServer::new(ParameterServerConfig {
+            learning_rate: 0.5,
+            ..Default::default()
+        });
+
+        let ack = server.push(&model, &push(0, vec![2.0, -2.0])).unwrap();
+        assert_eq!(ack.clock, 1);
+        assert_eq!(server.pull(&model).values, vec![0.0, 3.0]);
+
+        // One step stale: damped by 1/2.
+        let ack = server.push(&model, &push(0, vec![2.0, 0.0])).unwrap();
+        assert_eq!(ack.staleness, 1);
+        assert_eq!(server.pull(&model).values, vec![-0.5, 3.0]);
+
+        // Frozen parameters keep their values.
+        model.set_freeze_mask(vec![false, true]).unwrap();
+        server.push(&model, &push(2, vec![1.0, 1.0])).unwrap();
+        assert_eq!(server.pull(&model).values, vec![-1.0, 3.0]);
+    }
+
+    #[test]
+    fn test_push_rejects_stale_and_malformed_gradients() {
+        let model = Model::with_parameters(vec![0.0]);
+        let server = ParameterServer::new(ParameterServerConfig {
+            learning_rate: 1.0,
+            max_staleness: 1,
+            staleness_policy: StalenessPolicy::Damp,
+        });
+        for _ in 0..3 {
+            server
+                .push(&model, &push(server.clock(), vec![1.0]))
+                .unwrap();
+        }
+
+        let version = model.version();
+        assert!(matches!(
+            server.push(&model, &push(0, vec![1.0])),
+            Err(ModelError::StaleUpdate { staleness: 3, .. })
+        ));
+        assert!(matches!(
+            server.push(&model, &push(3, vec![1.0, 2.0])),
+            Err(ModelError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            server.push(&model, &push(3, vec![f64::NAN])),
+            Err(ModelError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            server.push(&model, &push(3, vec![f64::INFINITY])),
+            Err(ModelError::InvalidInput(_))
+        ));
+        assert_eq!(model.version(), version);
+        assert!(model.read_params()[0].is_finite());
+        assert!(matches!(
+            server.push(&model, &push(10, vec![1.0])),
+            Err(ModelError::InvalidInput(_))
+        ));
+        assert_eq!(server.clock(), 3);
+    }
+}
@@ -0,0 +1,442 @@
+use crate::algorithm::Algorithm;
+use crate::errors::ModelError;
+use crate::model::Model;
+use num_traits::Float;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+use std::fs;
+use std::iter::Sum;
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Header carrying the entity (user, device, ...) a training or inference
+/// request is personalized for.
+pub const ENTITY_KEY_HEADER: &str = "X-Oml-Entity-Key";
+
+/// Configuration of [`PersonalizedModels`].
+#[derive(Debug, Clone)]
+pub struct PersonalizationConfig {
+    /// Keys kept in memory; beyond it the least recently used one is evicted.
+    pub capacity: usize,
+    /// Number of most recently used keys written by [`PersonalizedModels::save`].
+    pub persisted: usize,
+    /// File the persisted keys are loaded from on build and saved to on stop
+    /// by the server builder.
+    pub file: Option<PathBuf>,
+    /// Keys trained on fewer samples are served by the global model.
+    pub min_samples: u64,
+    /// Number of samples past `min_samples` over which serving shifts
+    /// linearly from the global model to the personal one; 0 switches at once.
+    pub blend_samples: u64,
+}
+
+impl PersonalizationConfig {
+    /// Serves every trained key from its personal model.
+    pub fn new(capacity: usize) -> Self {
+        PersonalizationConfig {
+            capacity,
+            persisted: capacity,
+            file: None,
+            min_samples: 0,
+            blend_samples: 0,
+        }
+    }
+
+    /// Weight of the personal offsets of a key trained on `samples` samples,
+    /// from 0 (global model) to 1 (personal model).
+    pub fn weight(&self, samples: u64) -> f64 {
+        match samples.checked_sub(self.min_samples) {
+            None => 0.0,
+            Some(_) if self.blend_samples == 0 => 1.0,
+            Some(past) => (past as f64 / self.blend_samples as f64).min(1.0),
+        }
+    }
+}
+
+impl Default for PersonalizationConfig {
+    fn default() -> Self {
+        Self::new(10_000)
+    }
+}
+
+/// Offsets of one key from the global parameters and the number of
+/// samples they were trained on; also the persisted form of a key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeyState<T> {
+    offsets: Vec<T>,
+    /// Files written before sample counts were kept load as fully trained.
+    #[serde(default = "fully_trained")]
+    samples: u64,
+}
+
+fn fully_trained() -> u64 {
+    u64::MAX
+}
+
+/// Persisted state of a personalized key, as written by
+/// [`PersonalizedModels::save`] and carried in server archives.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedKey<T> {
+    key: String,
+    #[serde(flatten)]
+    state: KeyState<T>,
+}
+
+/// Key states, with the order and time in which the keys were last used.
+#[derive(Debug)]
+struct Lru<T> {
+    tick: u64,
+    entries: HashMap<String, (u64, KeyState<T>)>,
+    order: BTreeMap<u64, (Instant, String)>,
+}
+
+impl<T> Lru<T> {
+    fn get(&mut self, key: &str) -> Option<&KeyState<T>> {
+        self.tick += 1;
+        let (used, state) = self.entries.get_mut(key)?;
+        self.order.remove(used);
+        self.order
+            .insert(self.tick, (Instant::now(), key.to_string()));
+        *used = self.tick;
+        Some(state)
+    }
+
+    /// Stores `state` as the most recently used key; returns the key
+    /// evicted to make room, if any.
+    fn insert(&mut self, key: &str, state: KeyState<T>, capacity: usize) -> Option<String> {
+        self.tick += 1;
+        if let Some((used, _)) = self.entries.remove(key) {
+            self.order.remove(&used);
+        }
+        let evicted = match self.entries.len() >= capacity.max(1) {
+            true => self.order.pop_first().map(|(_, (_, oldest))| {
+                self.entries.remove(&oldest);
+                oldest
+            }),
+            false => None,
+        };
+        self.entries.insert(key.to_string(), (self.tick, state));
+        self.order
+            .insert(self.tick, (Instant::now(), key.to_string()));
+        evicted
+    }
+}
+
+/// Lightweight per-key models layered on a shared global model.
+///
+/// A key's model is the global parameters plus offsets learned from the
+/// key's own samples only: training for a key applies the algorithm to the
+/// layered parameters and keeps what the step changed as the new offsets, so
+/// the key follows the global model as it evolves. Keys without offsets are
+/// served by the global model, and keys with few samples by a blend of both
+/// (see [`PersonalizationConfig::weight`]). Suits algorithms whose whole
+/// state lives in the model parameters.
+///
+/// # Examples
+///
+/// ```
+/// use oml::model::Model;
+/// use oml::personalization::{PersonalizationConfig, PersonalizedModels};
+/// use oml::test::ScalingAlgorithm;
+///
+/// let global = Model::with_parameters(vec![1.0f64]);
+/// let personal = PersonalizedModels::new(PersonalizationConfig::new(100));
+/// personal.train("alice", &global, &ScalingAlgorithm, 3.0).unwrap();
+/// assert_eq!(personal.offsets("alice").unwrap(), Some(vec![2.0]));
+/// assert!(personal.model_for("bob", &global).unwrap().is_none());
+/// ```
+#[derive(Debug)]
+pub struct PersonalizedModels<T> {
+    config: PersonalizationConfig,
+    keys: Mutex<Lru<T>>,
+}
+
+impl<T> PersonalizedModels<T>
+where
+    T: Float + Debug + Serialize + DeserializeOwned + Send + Sync + Sum,
+{
+    pub fn new(config: PersonalizationConfig) -> Self {
+        PersonalizedModels {
+            config,
+            keys: Mutex::new(Lru {
+                tick: 0,
+                entries: HashMap::new(),
+                order: BTreeMap::new(),
+            }),
+        }
+    }
+
+    pub fn config(&self) -> &PersonalizationConfig {
+        &self.config
+    }
+
+    /// Number of keys with offsets in memory.
+    pub fn len(&self) -> Result<usize, ModelError> {
+        Ok(self.keys.lock()?.entries.len())
+    }
+
+    pub fn is_empty(&self) -> Result<bool, ModelError> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Offsets of `key` from the global parameters, if it has any.
+    pub fn offsets(&self, key: &str) -> Result<Option<Vec<T>>, ModelError> {
+        Ok(self
+            .keys
+            .lock()?
+            .get(key)
+            .map(|state| state.offsets.clone()))
+    }
+
+    /// Number of samples `key` was trained on.
+    pub fn samples(&self, key: &str) -> Result<u64, ModelError> {
+        Ok(self.keys.lock()?.get(key).map_or(0, |state| state.samples))
+    }
+
+    /// Forgets `key`; returns whether it had offsets.
+    pub fn remove(&self, key: &str) -> Result<bool, ModelError> {
+        let mut keys = self.keys.lock()?;
+        match keys.entries.remove(key) {
+            Some((used, _)) => {
+                keys.order.remove(&used);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Forgets the least recently used key; returns it, if there was one.
+    pub fn evict_oldest(&self) -> Result<Option<String>, ModelError> {
+        let mut keys = self.keys.lock()?;
+        Ok(keys.order.pop_first().map(|(_, (_, oldest))| {
+            keys.entries.remove(&oldest);
+            oldest
+        }))
+    }
+
+    /// Approximate bytes of memory the keys and their offsets hold.
+    pub fn memory_bytes(&self) -> Result<usize, ModelError> {
+        let keys = self.keys.lock()?;
+        Ok(keys
+            .entries
+            .iter()
+            .map(|(key, (_, state))| {
+                // The key is stored twice, in the map and the recency order.
+                2 * key.len()
+                    + state.offsets.len() * size_of::<T>()
+                    + size_of::<(String, (u64, KeyState<T>))>()
+                    + size_of::<(u64, (Instant, String))>()
+            })
+            .sum())
+    }
+
+    /// Forgets the keys unused for longer than `ttl`; returns how many.
+    pub fn expire(&self, ttl: Duration) -> Result<usize, ModelError> {
+        let mut keys = self.keys.lock()?;
+        let mut expired = 0;
+        while let Some(entry) = keys.order.first_entry() {
+            if entry.get().0.elapsed() <= ttl {
+                break;
+            }
+            let (_, key) = entry.remove();
+            keys.entries.remove(&key);
+            expired += 1;
+        }
+        Ok(expired)
+    }
+
+    /// The model serving `key`: the global parameters plus its offsets,
+    /// weighted by its sample count. `None` if the global model alone serves
+    /// it: the key has no offsets (or they no longer fit the global model) or
+    /// too few samples.
+    pub fn model_for(&self, key: &str, global: &Model<T>) -> Result<Option<Model<T>>, ModelError> {
+        let base = global.snapshot();
+        let mut keys = self.keys.lock()?;
+        let Some(state) = keys.get(key).filter(|s| s.offsets.len() == base.len()) else {
+            return Ok(None);
+        };
+        let weight = self.config.weight(state.samples);
+        if weight <= 0.0 {
+            return Ok(None);
+        }
+        let weight = T::from(weight).unwrap_or_else(T::one);
+        let layered = base
+            .iter()
+            .zip(&state.offsets)
+            .map(|(b, o)| *b + weight * *o)
+            .collect();
+        Model::with_layout(global.layout().clone(), layered).map(Some)
+    }
+
+    /// Runs a training step of `algorithm` on the model of `key` and keeps
+    /// the result as its offsets. Returns the key evicted to make room, if any.
+    pub fn train<A: Algorithm<T>>(
+        &self,
+        key: &str,
+        global: &Model<T>,
+        algorithm: &A,
+        x: A::Input,
+    ) -> Result<Option<String>, ModelError> {
+        let base = global.snapshot();
+        let mut keys = self.keys.lock()?;
+        let (layered, samples) = match keys.get(key).filter(|s| s.offsets.len() == base.len()) {
+            Some(state) => (
+                base.iter()
+                    .zip(&state.offsets)
+                    .map(|(b, o)| *b + *o)
+                    .collect(),
+                state.samples,
+            ),
+            None => (base.to_vec(), 0),
+        };
+        let model = Model::with_layout(global.layout().clone(), layered)?;
+        algorithm.training_step(&model, x)?;
+        let offsets = model
+            .read_params()
+            .iter()
+            .zip(base.iter())
+            .map(|(p, b)| *p - *b)
+            .collect();
+        let state = KeyState {
+            offsets,
+            samples: samples.saturating_add(1),
+        };
+        Ok(keys.insert(key, state, self.config.capacity))
+    }
+
+    /// Writes the `persisted` most recently used keys to `path`, replacing
+    /// the file atomically. Returns the number of keys written.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<usize, ModelError> {
+        let path = path.as_ref();
+        let saved = self.export()?;
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec(&saved)?)?;
+        fs::rename(&tmp, path)?;
+        Ok(saved.len())
+    }
+
+    /// Loads the keys saved at `path`, keeping their recency order. Returns
+    /// the number of keys loaded.
+    pub fn load(&self, path: impl AsRef<Path>) -> Result<usize, ModelError> {
+        let saved: Vec<SavedKey<T>> = serde_json::from_slice(&fs::read(path)?)?;
+        self.import(&saved)
+    }
+
+    /// The `persisted` most recently used keys, least recent first.
+    pub fn export(&self) -> Result<Vec<SavedKey<T>>, ModelError> {
+        let keys = self.keys.lock()?;
+        let cold = keys.order.len().saturating_sub(self.config.persisted);
+        Ok(keys
+            .order
+            .values()
+            .map(|(_, key)| key)
+            .skip(cold)
+            .map(|key| SavedKey {
+                key: key.clone(),
+                state: keys.entries[key].1.clone(),
+            })
+            .collect())
+    }
+
+    /// Adds `saved` keys as the most recently used, in order. Returns the
+    /// number of keys imported.
+    pub fn import(&self, saved: &[SavedKey<T>]) -> Result<usize, ModelError> {
+        let mut keys = self.keys.lock()?;
+        for entry in saved {
+            keys.insert(&entry.key, entry.state.clone(), self.config.capacity);
+        }
+        Ok(saved.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Moves the parameter halfway towards every observation.
+    struct Mean;
+
+    impl Algorithm<f64> for Mean {
+        type Input = f64;
+        type Output = f64;
+
+        fn training_step(&self, model: &Model<f64>, x: f64) -> Result<(), ModelError> {
+            let mut params = model.write_params();
+            params[0] += 0.5 * (x - params[0]);
+            Ok(())
+        }
+
+        fn inference_step(&self, model: &Model<f64>, _: f64) -> Result<f64, ModelError> {
+            Ok(model.read_params()[0])
+        }
+    }
+
+    #[test]
+    fn test_keys_layer_on_the_global_model_and_persist() {
+        let global = Model::with_parameters(vec![0.0]);
+        let mut config = PersonalizationConfig::new(2);
+        config.persisted = 1;
+        let personal = PersonalizedModels::new(config.clone());
+        let predict = |key: &str| {
+            let model = personal.model_for(key, &global).unwrap();
+            Mean.inference_step(model.as_ref().unwrap_or(&global), 0.0)
+                .unwrap()
+        };
+
+        for _ in 0..20 {
+            personal.train("high", &global, &Mean, 10.0).unwrap();
+        }
+        assert!((predict("high") - 10.0).abs() < 1e-3);
+        assert_eq!(predict("unknown"), 0.0);
+        // The key keeps its offset as the global model moves.
+        global.update_params(|p| p[0] = 4.0);
+        assert!((predict("high") - 14.0).abs() < 1e-3);
+
+        personal.train("low", &global, &Mean, 0.0).unwrap();
+        personal.model_for("high", &global).unwrap();
+        let evicted = personal.train("other", &global, &Mean, 0.0).unwrap();
+        assert_eq!(evicted.as_deref(), Some("low"));
+        assert_eq!(personal.len().unwrap(), 2);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys.json");
+        assert_eq!(personal.save(&path).unwrap(), 1);
+        let restarted = PersonalizedModels::<f64>::new(config);
+        assert_eq!(restarted.load(&path).unwrap(), 1);
+        assert_eq!(restarted.offsets("other").unwrap(), Some(vec![-2.0]));
+        assert!(restarted.offsets("high").unwrap().is_none());
+        assert_eq!(restarted.expire(Duration::from_secs(3600)).unwrap(), 0);
+        assert_eq!(restarted.expire(Duration::ZERO).unwrap(), 1);
+        assert!(restarted.is_empty().unwrap());
+        assert!(!restarted.remove("other").unwrap());
+    }
+
+    #[test]
+    fn test_few_samples_blend_towards_the_global_model() {
+        let mut config = PersonalizationConfig::new(10);
+        config.min_samples = 2;
+        config.blend_samples = 4;
+        assert_eq!(
+            [0, 1, 2, 4, 6, 100].map(|n| config.weight(n)),
+            [0.0, 0.0, 0.0, 0.5, 1.0, 1.0]
+        );
+
+        let global = Model::with_parameters(vec![0.0]);
+        let personal = PersonalizedModels::new(config);
+        let predict = || match personal.model_for("key", &global).unwrap() {
+            Some(model) => model.read_params()[0],
+            None => global.read_params()[0],
+        };
+        let mut served = Vec::new();
+        for _ in 0..6 {
+            personal.train("key", &global, &Mean, 16.0).unwrap();
+            served.push(predict());
+        }
+        assert_eq!(personal.samples("key").unwrap(), 6);
+        // Offsets 8, 12, 14, 15, 15.5, 15.75 weighted by 0, 0, 0.25, 0.5, 0.75, 1.
+        assert_eq!(served, vec![0.0, 0.0, 3.5, 7.5, 11.625, 15.75]);
+    }
+}
@@ -0,0 +1,345 @@
+use crate::algorithm::{Algorithm, Datum};
+use crate::errors::ModelError;
+use crate::handlers::AppState;
+use crate::replication::Replication;
+use crate::service::{apply_training_step, ServiceError};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+
+/// Sample errors listed in a [`BatchReport`]; later ones are only counted.
+pub const MAX_REPORTED_ERRORS: usize = 20;
+
+/// How `POST /training/batch` spreads its stages over threads, see
+/// [`train_pipelined`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PipelineConfig {
+    /// Samples buffered between two stages; a full buffer holds the stage
+    /// feeding it back, bounding the memory of a large upload.
+    pub capacity: usize,
+    /// Threads transforming the decoded samples. The samples are put back
+    /// in their upload order before training.
+    pub transform_workers: usize,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        PipelineConfig {
+            capacity: 1024,
+            transform_workers: 1,
+        }
+    }
+}
+
+impl PipelineConfig {
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    pub fn with_transform_workers(mut self, workers: usize) -> Self {
+        self.transform_workers = workers.max(1);
+        self
+    }
+}
+
+/// A sample of a batch that could not be trained.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SampleError {
+    /// Line of the sample in the upload, from 1.
+    pub line: usize,
+    pub error: String,
+}
+
+/// Response of `POST /training/batch`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BatchReport {
+    /// Non-empty lines received.
+    pub samples: usize,
+    pub trained: usize,
+    pub failed: usize,
+    /// The first [`MAX_REPORTED_ERRORS`] failures.
+    pub errors: Vec<SampleError>,
+}
+
+impl BatchReport {
+    fn fail(&mut self, line: usize, error: impl ToString) {
+        self.failed += 1;
+        if self.errors.len() < MAX_REPORTED_ERRORS {
+            self.errors.push(SampleError {
+                line,
+                error: error.to_string(),
+            });
+        }
+    }
+}
+
+/// Trains the serving model on `body`, one JSON sample per line, with
+/// its three stages running at once on their own threads: decoding the
+/// lines, transforming the samples (preprocessing script, input check and
+/// feature clipping) and applying the training steps, linked by bounded
+/// channels. Decoding and transforming a large upload thus overlaps with
+/// training instead of preceding it.
+///
+/// Parameter updates stay on a single thread and follow the upload order,
+/// so the model sees the same sequence of steps as with one
+/// `POST /training` per line, class weights included. Every step goes
+/// through what follows a step of `POST /training` (the replication log,
+/// the shared parameter store, consistency tracking, the challenger and
+/// checkpoints), but the per-request gates before it (trace recording,
+/// deduplication, outlier quarantine, forwarding to the writer) do not
+/// apply; replicas that do not own the model refuse the whole batch.
+/// Samples that fail are reported and skipped.
+pub fn train_pipelined<T, A>(
+    state: &AppState<T, A>,
+    body: &[u8],
+    config: &PipelineConfig,
+) -> Result<BatchReport, ServiceError>
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    if let Some(Replication::Follower(follower)) = state.replication.as_deref() {
+        return Err(ModelError::NotLeader(follower.leader_address()).into());
+    }
+    if state.shared_state.as_ref().is_some_and(|s| !s.is_writer()) {
+        return Err(ServiceError::Unavailable(
+            "this replica is not the writer".to_string(),
+        ));
+    }
+    if state.training_guard.as_ref().is_some_and(|g| g.is_paused()) {
+        return Err(ServiceError::Unavailable(
+            "training is paused by the guard".to_string(),
+        ));
+    }
+
+    let capacity = config.capacity.max(1);
+    let (decoded_tx, decoded_rx) = mpsc::sync_channel::<Sample<A::Input>>(capacity);
+    let (ready_tx, ready_rx) = mpsc::sync_channel::<Transformed<A::Input>>(capacity);
+    let decoded_rx = Mutex::new(decoded_rx);
+    let report = Mutex::new(BatchReport::default());
+    let fail = |line: usize, error: &dyn ToString| {
+        report
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .fail(line, error.to_string());
+    };
+
+    let applied = thread::scope(|scope| {
+        scope.spawn(|| decode(body, decoded_tx, &report, &fail));
+        for _ in 0..config.transform_workers.max(1) {
+            let ready_tx = ready_tx.clone();
+            let (decoded_rx, fail) = (&decoded_rx, &fail);
+            scope.spawn(move || transform(state, decoded_rx, ready_tx, fail));
+        }
+        drop(ready_tx);
+        apply(state, ready_rx, &fail)
+    })?;
+
+    let mut report = report.into_inner().unwrap_or_else(PoisonError::into_inner);
+    report.trained = applied;
+    state.metrics.add("training_steps", applied as u64);
+    state
+        .metrics
+        .add("batch_samples_failed", report.failed as u64);
+    if let Some(catalog) = &state.catalog {
+        catalog.record_training(&state.model, applied as u64)?;
+    }
+    Ok(report)
+}
+
+/// A decoded sample: its position among the decoded samples, its line and
+/// the sample.
+type Sample<I> = (usize, usize, I);
+
+/// A transformed sample, `None` if it failed; kept so the last stage can
+/// restore the order of the ones after it.
+type Transformed<I> = (usize, usize, Option<I>);
+
+/// First stage: one sample per non-empty line.
+fn decode<I: Datum>(
+    body: &[u8],
+    decoded: SyncSender<Sample<I>>,
+    report: &Mutex<BatchReport>,
+    fail: &dyn Fn(usize, &dyn ToString),
+) {
+    let mut sequence = 0;
+    for (i, line) in body.split(|b| *b == b'\n').enumerate() {
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        report
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .samples += 1;
+        match serde_json::from_slice(line) {
+            Ok(input) => {
+                if decoded.send((sequence, i + 1, input)).is_err() {
+                    return;
+                }
+                sequence += 1;
+            }
+            Err(e) => fail(i + 1, &e),
+        }
+    }
+}
+
+/// Second stage, possibly on several threads: the transformations
+/// `POST /training` applies before a step.
+fn transform<T, A>(
+    state: &AppState<T, A>,
+    decoded: &Mutex<Receiver<Sample<A::Input>>>,
+    ready: SyncSender<Transformed<A::Input>>,
+    fail: &dyn Fn(usize, &dyn ToString),
+) where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    loop {
+        let next = decoded
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .recv();
+        let Ok((sequence, line, input)) = next else {
+            return;
+        };
+        let transformed = state.preprocess(input).and_then(|input| {
+            state.model.check_input(&input)?;
+            Ok(match &state.clipper {
+                Some(clipper) => {
+                    let (input, clipped) = clipper.lock()?.clip(input);
+                    state.metrics.add("features_clipped", clipped as u64);
+                    input
+                }
+                None => input,
+            })
+        });
+        let transformed = transformed.map_err(|e| fail(line, &e)).ok();
+        if ready.send((sequence, line, transformed)).is_err() {
+            return;
+        }
+    }
+}
+
+/// Last stage, on the calling thread: the training steps, in upload
+/// order. Returns the number applied.
+fn apply<T, A>(
+    state: &AppState<T, A>,
+    ready: Receiver<Transformed<A::Input>>,
+    fail: &dyn Fn(usize, &dyn ToString),
+) -> Result<usize, ModelError>
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    let algorithm = state.active_algorithm();
+    let _writer = state
+        .algorithms
+        .as_ref()
+        .map(|r| r.lock_writer())
+        .transpose()?;
+    // Samples transformed ahead of their turn, by position.
+    let mut early = BTreeMap::new();
+    let mut next = 0;
+    let mut applied = 0;
+    for (sequence, line, input) in ready {
+        early.insert(sequence, (line, input));
+        while let Some((line, input)) = early.remove(&next) {
+            next += 1;
+            let Some(input) = input else {
+                continue;
+            };
+            let step = match input.class_label() {
+                Some(label) => state.model.observe_class(&label),
+                None => Ok(1.0),
+            }
+            .and_then(|weight| apply_training_step(state, &algorithm, input, weight));
+            match step {
+                Ok(()) => applied += 1,
+                Err(e) => fail(line, &e),
+            }
+        }
+    }
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::OmlMiddleware;
+    use crate::model::Model;
+    use crate::server::configure;
+    use crate::test::ScalingAlgorithm;
+    use actix_web::{test as actix_test, web, App};
+    use std::sync::Arc;
+
+    #[actix_rt::test]
+    async fn test_batch_trained_through_pipeline_in_order() {
+        let state = AppState::new(Model::with_parameters(vec![1.0f64]), ScalingAlgorithm)
+            .with_batch_pipeline(PipelineConfig::default().with_capacity(2));
+        let model = state.model.clone();
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(configure::<f64, ScalingAlgorithm>),
+        )
+        .await;
+
+        // More samples than the channels hold, a bad line and blank lines.
+        let mut body: String = (0..10).map(|_| "2.0\n").collect();
+        body.push_str("\nnot json\n3.0\n\n");
+        let request = actix_test::TestRequest::post()
+            .uri("/training/batch")
+            .set_payload(body)
+            .to_request();
+        let report: BatchReport = actix_test::call_and_read_body_json(&app, request).await;
+        assert_eq!((report.samples, report.trained, report.failed), (12, 11, 1));
+        assert_eq!(report.errors[0].line, 12);
+        assert_eq!(model.read_params()[0], 1024.0 * 3.0);
+        assert_eq!(model.training_steps(), 11);
+    }
+
+    /// Records the samples in the order their steps are applied.
+    struct Recorder(Mutex<Vec<f64>>);
+
+    impl OmlMiddleware<f64, f64> for Recorder {
+        fn on_training_applied(&self, input: &f64) {
+            self.0.lock().unwrap().push(*input);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_transform_workers_keep_upload_order() {
+        let recorder = Arc::new(Recorder(Mutex::new(Vec::new())));
+        let state = AppState::new(Model::with_parameters(vec![1.0f64]), ScalingAlgorithm)
+            .with_middleware(recorder.clone())
+            .with_batch_pipeline(
+                PipelineConfig::default()
+                    .with_capacity(2)
+                    .with_transform_workers(4),
+            );
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(configure::<f64, ScalingAlgorithm>),
+        )
+        .await;
+
+        let body: String = (1..=50)
+            .map(|i| format!("{}\n", 1.0 + i as f64 / 1e3))
+            .collect();
+        let request = actix_test::TestRequest::post()
+            .uri("/training/batch")
+            .set_payload(body)
+            .to_request();
+        let report: BatchReport = actix_test::call_and_read_body_json(&app, request).await;
+        assert_eq!(report.trained, 50);
+        let expected: Vec<f64> = (1..=50).map(|i| 1.0 + i as f64 / 1e3).collect();
+        assert_eq!(*recorder.0.lock().unwrap(), expected);
+    }
+}
@@ -0,0 +1,239 @@
+use crate::algorithm::Algorithm;
+use crate::errors::ModelError;
+use crate::model::Model;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::sync::{Arc, RwLock};
+use wasmtime::{Config, Engine, Instance, Linker, Memory, Module, Store};
+
+const REQUIRED_EXPORTS: [&str; 4] = ["memory", "oml_alloc", "oml_train", "oml_infer"];
+
+/// Currently loaded module, as reported by `GET /plugins/module`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PluginInfo {
+    /// Incremented on every successful load.
+    pub version: u64,
+    pub size_bytes: usize,
+}
+
+/// Algorithm delegating training and inference to a hot-swappable WASM module.
+///
+/// A plugin is a module without imports exporting:
+///
+/// - `memory`: its linear memory;
+/// - `oml_alloc(len: i32) -> i32`: returns a buffer of `len` bytes;
+/// - `oml_train(params: i32, params_len: i32, x: i32, x_len: i32) -> i32`:
+///   updates the `params_len` little-endian `f64` parameters at `params` in
+///   place from the `x_len` features at `x`, returning 0 on success;
+/// - `oml_infer(params: i32, params_len: i32, x: i32, x_len: i32) -> f64`.
+///
+/// Plugins keep no state of their own: every call runs in a fresh instance
+/// and the model parameters are copied in and out, so the same module can
+/// serve concurrent inference steps.
+pub struct WasmAlgorithm {
+    engine: Engine,
+    /// Fuel granted to each call; bounds the work a plugin can do.
+    fuel: Option<u64>,
+    loaded: RwLock<Option<(Arc<Module>, PluginInfo)>>,
+}
+
+impl WasmAlgorithm {
+    /// Creates the host with no module loaded; steps fail until [`load`](Self::load).
+    pub fn new(fuel: Option<u64>) -> Result<Self, ModelError> {
+        let mut config = Config::new();
+        config.consume_fuel(fuel.is_some());
+        let engine = Engine::new(&config).map_err(plugin_error)?;
+        Ok(WasmAlgorithm {
+            engine,
+            fuel,
+            loaded: RwLock::new(None),
+        })
+    }
+
+    /// Compiles and validates `bytes` (binary or text format) and swaps it in.
+    /// In-flight steps finish on the previous module.
+    pub fn load(&self, bytes: &[u8]) -> Result<PluginInfo, ModelError> {
+        let module = Module::new(&self.engine, bytes).map_err(plugin_error)?;
+        if module.imports().len() > 0 {
+            return Err(ModelError::InvalidInput(
+                "plugin modules must not have imports".to_string(),
+            ));
+        }
+        for name in REQUIRED_EXPORTS {
+            if module.get_export(name).is_none() {
+                return Err(ModelError::InvalidInput(format!(
+                    "plugin module does not export {}",
+                    name
+                )));
+            }
+        }
+        let mut loaded = self.loaded.write()?;
+        let info = PluginInfo {
+            version: loaded.as_ref().map_or(1, |(_, info)| info.version + 1),
+            size_bytes: bytes.len(),
+        };
+        *loaded = Some((Arc::new(module), info.clone()));
+        Ok(info)
+    }
+
+    pub fn info(&self) -> Option<PluginInfo> {
+        self.loaded
+            .read()
+            .ok()?
+            .as_ref()
+            .map(|(_, info)| info.clone())
+    }
+
+    fn instantiate(&self) -> Result<(Store<()>, Instance, Memory), ModelError> {
+        let module = match self.loaded.read()?.as_ref() {
+            Some((module, _)) => module.clone(),
+            None => {
+                return Err(ModelError::Unsupported(
+                    "no plugin module is loaded".to_string(),
+                ))
+            }
+        };
+        let mut store = Store::new(&self.engine, ());
+        if let Some(fuel) = self.fuel {
+            store.set_fuel(fuel).map_err(plugin_error)?;
+        }
+        let instance = Linker::new(&self.engine)
+            .instantiate(&mut store, &module)
+            .map_err(plugin_error)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| ModelError::InvalidInput("plugin exports no memory".to_string()))?;
+        Ok((store, instance, memory))
+    }
+
+    /// Copies `values` into a buffer allocated by the plugin.
+    fn write_values(
+        store: &mut Store<()>,
+        instance: &Instance,
+        memory: &Memory,
+        values: &[f64],
+    ) -> Result<(i32, i32), ModelError> {
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut *store, "oml_alloc")
+            .map_err(plugin_error)?;
+        let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let ptr = alloc
+            .call(&mut *store, bytes.len() as i32)
+            .map_err(plugin_error)?;
+        memory
+            .write(&mut *store, ptr as usize, &bytes)
+            .map_err(plugin_error)?;
+        Ok((ptr, values.len() as i32))
+    }
+
+    /// Runs `oml_train` on `parameters`, returning the updated values.
+    pub fn train(&self, parameters: &[f64], x: &[f64]) -> Result<Vec<f64>, ModelError> {
+        let (mut store, instance, memory) = self.instantiate()?;
+        let params = Self::write_values(&mut store, &instance, &memory, parameters)?;
+        let input = Self::write_values(&mut store, &instance, &memory, x)?;
+        let train = instance
+            .get_typed_func::<(i32, i32, i32, i32), i32>(&mut store, "oml_train")
+            .map_err(plugin_error)?;
+        let status = train
+            .call(&mut store, (params.0, params.1, input.0, input.1))
+            .map_err(plugin_error)?;
+        if status != 0 {
+            return Err(ModelError::InvalidInput(format!(
+                "plugin training step failed with status {}",
+                status
+            )));
+        }
+        let mut bytes = vec![0u8; parameters.len() * 8];
+        memory
+            .read(&store, params.0 as usize, &mut bytes)
+            .map_err(plugin_error)?;
+        Ok(bytes
+            .chunks_exact(8)
+            .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap_or_default()))
+            .collect())
+    }
+
+    /// Runs `oml_infer` on `parameters`.
+    pub fn infer(&self, parameters: &[f64], x: &[f64]) -> Result<f64, ModelError> {
+        let (mut store, instance, memory) = self.instantiate()?;
+        let params = Self::write_values(&mut store, &instance, &memory, parameters)?;
+        let input = Self::write_values(&mut store, &instance, &memory, x)?;
+        let infer = instance
+            .get_typed_func::<(i32, i32, i32, i32), f64>(&mut store, "oml_infer")
+            .map_err(plugin_error)?;
+        infer
+            .call(&mut store, (params.0, params.1, input.0, input.1))
+            .map_err(plugin_error)
+    }
+}
+
+fn plugin_error(error: impl std::fmt::Display) -> ModelError {
+    ModelError::InvalidInput(format!("plugin: {}", error))
+}
+
+fn to_f64<T: Float>(values: &[T]) -> Vec<f64> {
+    values
+        .iter()
+        .map(|v| v.to_f64().unwrap_or(f64::NAN))
+        .collect()
+}
+
+impl<T> Algorithm<T> for WasmAlgorithm
+where
+    T: Float + Debug + Send + Sync + Sum,
+{
+    type Input = Vec<f64>;
+    type Output = f64;
+
+    fn training_step(&self, model: &Model<T>, x: Vec<f64>) -> Result<(), ModelError> {
+        let mut parameters = model.write_trainable();
+        let updated = self.train(&to_f64(&parameters), &x)?;
+        for (parameter, value) in parameters.iter_mut().zip(updated) {
+            *parameter = T::from(value).unwrap_or_else(T::nan);
+        }
+        Ok(())
+    }
+
+    fn inference_step(&self, model: &Model<T>, x: Vec<f64>) -> Result<f64, ModelError> {
+        self.infer(&to_f64(&model.read_params()), &x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Adds the first feature to the first parameter; predicts their product.
+    const LINEAR: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (global $next (mut i32) (i32.const 1024))
+          (func (export "oml_alloc") (param $len i32) (result i32)
+            (local $ptr i32)
+            (local.set $ptr (global.get $next))
+            (global.set $next (i32.add (global.get $next) (local.get $len)))
+            (local.get $ptr))
+          (func (export "oml_train") (param $p i32) (param $pn i32) (param $x i32) (param $xn i32) (result i32)
+            (f64.store (local.get $p) (f64.add (f64.load (local.get $p)) (f64.load (local.get $x))))
+            (i32.const 0))
+          (func (export "oml_infer") (param $p i32) (param $pn i32) (param $x i32) (param $xn i32) (result f64)
+            (f64.mul (f64.load (local.get $p)) (f64.load (local.get $x)))))
+    "#;
+
+    #[test]
+    fn test_plugin_trains_and_infers() {
+        let plugin = WasmAlgorithm::new(Some(10_000)).unwrap();
+        let model = Model::with_parameters(vec![1.0f32, 5.0]);
+        assert!(plugin.inference_step(&model, vec![1.0]).is_err());
+
+        assert_eq!(plugin.load(LINEAR.as_bytes()).unwrap().version, 1);
+        plugin.training_step(&model, vec![2.0]).unwrap();
+        assert_eq!(model.read_params().clone(), vec![3.0, 5.0]);
+        assert_eq!(plugin.inference_step(&model, vec![2.0]).unwrap(), 6.0);
+
+        assert!(plugin.load(b"(module)").is_err());
+        assert_eq!(plugin.info().unwrap().version, 1);
+    }
+}
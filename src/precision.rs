@@ -0,0 +1,563 @@
+use crate::algorithm::{Algorithm, Contributions, Uncertainty};
+use crate::errors::ModelError;
+use crate::model::Model;
+use num_traits::{Float, Num, NumCast, One, ToPrimitive, Zero};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{self, Debug};
+use std::iter::Sum;
+use std::num::FpCategory;
+use std::ops::{Add, Div, Mul, Neg, Rem, Sub};
+use std::sync::{Arc, Mutex, PoisonError};
+
+macro_rules! delegate {
+    (unary: $($method:ident),*) => {$(
+        fn $method(self) -> Self {
+            Self(Float::$method(self.0))
+        }
+    )*};
+    (binary: $($method:ident),*) => {$(
+        fn $method(self, other: Self) -> Self {
+            Self(Float::$method(self.0, other.0))
+        }
+    )*};
+    (predicate: $($method:ident),*) => {$(
+        fn $method(self) -> bool {
+            Float::$method(self.0)
+        }
+    )*};
+    (constant: $($method:ident),*) => {$(
+        fn $method() -> Self {
+            Self(Float::$method())
+        }
+    )*};
+}
+
+macro_rules! half_float {
+    ($(#[$doc:meta])* $name:ident($inner:ty)) => {
+        $(#[$doc])*
+        #[derive(Clone, Copy, Default, PartialEq, PartialOrd)]
+        #[repr(transparent)]
+        pub struct $name(pub $inner);
+
+        impl $name {
+            /// The nearest representable value to `value`.
+            pub fn from_f32(value: f32) -> Self {
+                Self(<$inner>::from_f32(value))
+            }
+
+            pub fn to_f32(self) -> f32 {
+                self.0.to_f32()
+            }
+        }
+
+        impl From<$name> for f32 {
+            fn from(value: $name) -> f32 {
+                value.to_f32()
+            }
+        }
+
+        impl Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                Debug::fmt(&self.0, f)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Display::fmt(&self.0, f)
+            }
+        }
+
+        /// A number in human-readable formats, so JSON models and
+        /// responses read like those of other scalar types; the two bytes
+        /// of the value in binary ones.
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                match serializer.is_human_readable() {
+                    true => serializer.serialize_f32(self.0.to_f32()),
+                    false => serializer.serialize_u16(self.0.to_bits()),
+                }
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                match deserializer.is_human_readable() {
+                    true => f64::deserialize(deserializer).map(|v| Self(<$inner>::from_f64(v))),
+                    false => u16::deserialize(deserializer).map(|b| Self(<$inner>::from_bits(b))),
+                }
+            }
+        }
+
+        impl Add for $name {
+            type Output = Self;
+
+            fn add(self, other: Self) -> Self {
+                Self(self.0 + other.0)
+            }
+        }
+
+        impl Sub for $name {
+            type Output = Self;
+
+            fn sub(self, other: Self) -> Self {
+                Self(self.0 - other.0)
+            }
+        }
+
+        impl Mul for $name {
+            type Output = Self;
+
+            fn mul(self, other: Self) -> Self {
+                Self(self.0 * other.0)
+            }
+        }
+
+        impl Div for $name {
+            type Output = Self;
+
+            fn div(self, other: Self) -> Self {
+                Self(self.0 / other.0)
+            }
+        }
+
+        impl Rem for $name {
+            type Output = Self;
+
+            fn rem(self, other: Self) -> Self {
+                Self(self.0 % other.0)
+            }
+        }
+
+        impl Neg for $name {
+            type Output = Self;
+
+            fn neg(self) -> Self {
+                Self(-self.0)
+            }
+        }
+
+        /// Accumulates in `f32` and rounds once, so long sums (dot
+        /// products, norms) do not lose the small terms.
+        impl Sum for $name {
+            fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+                Self::from_f32(iter.map($name::to_f32).sum())
+            }
+        }
+
+        impl Zero for $name {
+            fn zero() -> Self {
+                Self(<$inner>::ZERO)
+            }
+
+            fn is_zero(&self) -> bool {
+                self.0 == <$inner>::ZERO || self.0 == <$inner>::NEG_ZERO
+            }
+        }
+
+        impl One for $name {
+            fn one() -> Self {
+                Self(<$inner>::ONE)
+            }
+        }
+
+        impl Num for $name {
+            type FromStrRadixErr = <f32 as Num>::FromStrRadixErr;
+
+            fn from_str_radix(s: &str, radix: u32) -> Result<Self, Self::FromStrRadixErr> {
+                <f32 as Num>::from_str_radix(s, radix).map(Self::from_f32)
+            }
+        }
+
+        impl ToPrimitive for $name {
+            fn to_i64(&self) -> Option<i64> {
+                self.0.to_f32().to_i64()
+            }
+
+            fn to_u64(&self) -> Option<u64> {
+                self.0.to_f32().to_u64()
+            }
+
+            fn to_f32(&self) -> Option<f32> {
+                Some(self.0.to_f32())
+            }
+
+            fn to_f64(&self) -> Option<f64> {
+                Some(self.0.to_f64())
+            }
+        }
+
+        impl NumCast for $name {
+            fn from<N: ToPrimitive>(n: N) -> Option<Self> {
+                n.to_f64().map(|v| Self(<$inner>::from_f64(v)))
+            }
+        }
+
+        impl Float for $name {
+            delegate!(constant: nan, infinity, neg_infinity, neg_zero, min_value,
+                min_positive_value, max_value, epsilon);
+            delegate!(predicate: is_nan, is_infinite, is_finite, is_normal,
+                is_sign_positive, is_sign_negative);
+            delegate!(unary: floor, ceil, round, trunc, fract, abs, signum, recip, sqrt,
+                exp, exp2, ln, log2, log10, cbrt, sin, cos, tan, asin, acos, atan, exp_m1,
+                ln_1p, sinh, cosh, tanh, asinh, acosh, atanh, to_degrees, to_radians);
+            delegate!(binary: powf, log, max, min, abs_sub, hypot, atan2);
+
+            fn classify(self) -> FpCategory {
+                Float::classify(self.0)
+            }
+
+            fn mul_add(self, a: Self, b: Self) -> Self {
+                Self::from_f32(self.to_f32().mul_add(a.to_f32(), b.to_f32()))
+            }
+
+            fn powi(self, n: i32) -> Self {
+                Self::from_f32(self.to_f32().powi(n))
+            }
+
+            fn sin_cos(self) -> (Self, Self) {
+                (self.sin(), self.cos())
+            }
+
+            fn integer_decode(self) -> (u64, i16, i8) {
+                Float::integer_decode(self.0)
+            }
+        }
+    };
+}
+
+half_float! {
+    /// IEEE 754 half-precision parameter (5 exponent, 10 mantissa bits):
+    /// half the memory of `f32`, about three significant digits, and
+    /// values up to 65504.
+    ///
+    /// Arithmetic rounds every operation to half precision; train such
+    /// models through [`MixedPrecision`] so steps are computed in `f32`.
+    F16(half::f16)
+}
+
+half_float! {
+    /// bfloat16 parameter (8 exponent, 7 mantissa bits): half the memory
+    /// of `f32` with its range, at about two significant digits.
+    ///
+    /// Arithmetic rounds every operation to bfloat16; train such models
+    /// through [`MixedPrecision`] so steps are computed in `f32`.
+    Bf16(half::bf16)
+}
+
+/// Runs an `f32` algorithm on a model stored in another precision, for
+/// [`F16`] and [`Bf16`] parameters: every step works on an `f32` copy of
+/// the parameters, so gradients and sums accumulate at full precision, and
+/// the result is rounded back once, halving the memory of the stored
+/// parameters of large, embedding-style models without compounding
+/// rounding errors within a step.
+///
+/// Updates smaller than half the spacing of the stored values around a
+/// parameter still round away; keep learning rates large enough for the
+/// stored precision. Frozen parameters and constraints apply as usual.
+/// Sparse and memory-mapped models are not supported.
+///
+/// Inference, contributions and uncertainty run on an `f32` copy of the
+/// model (and of its moving average) that is kept between requests and
+/// widened again only once the parameters change.
+///
+/// # Examples
+///
+/// ```
+/// use oml::algorithm::Algorithm;
+/// use oml::model::Model;
+/// use oml::precision::{MixedPrecision, F16};
+/// use oml::test::ScalingAlgorithm;
+///
+/// let model = Model::with_parameters(vec![F16::from_f32(1.5), F16::from_f32(-0.25)]);
+/// let algorithm = MixedPrecision::new(ScalingAlgorithm);
+/// algorithm.training_step(&model, 2.0).unwrap();
+/// assert_eq!(model.read_params()[0].to_f32(), 3.0);
+/// assert_eq!(algorithm.inference_step(&model, 1.0).unwrap(), 2.5);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MixedPrecision<A> {
+    inner: A,
+    /// `f32` copies inference runs on, most recently widened last.
+    widened: Arc<Mutex<Vec<Widened>>>,
+}
+
+/// An `f32` copy of the model `id`, current as long as the versions of the
+/// model and of its moving average are `versions`.
+#[derive(Debug)]
+struct Widened {
+    id: String,
+    versions: (u64, Option<u64>),
+    model: Arc<Model<f32>>,
+}
+
+/// Models whose `f32` copies are kept, e.g. a model and its challenger.
+const WIDENED_CAPACITY: usize = 4;
+
+impl<A> MixedPrecision<A> {
+    pub fn new(inner: A) -> Self {
+        MixedPrecision {
+            inner,
+            widened: Arc::default(),
+        }
+    }
+
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+}
+
+/// An `f32` model holding `params`, otherwise like `model`.
+fn widen<T>(model: &Model<T>, params: &[T]) -> Result<Model<f32>, ModelError>
+where
+    T: Float + Debug + Send + Sync,
+{
+    if model.sparse_params().is_some() {
+        return Err(ModelError::Unsupported(
+            "mixed precision does not support sparse models".to_string(),
+        ));
+    }
+    #[cfg(feature = "mmap")]
+    if model.mapped_params().is_some() {
+        return Err(ModelError::Unsupported(
+            "mixed precision does not support memory-mapped models".to_string(),
+        ));
+    }
+    model.converted(params, |p| p.to_f32().unwrap_or(f32::NAN))
+}
+
+fn narrow<T: Float>(value: f32) -> T {
+    T::from(value).unwrap_or_else(T::nan)
+}
+
+impl<A> MixedPrecision<A> {
+    /// The `f32` copy of `model` to serve from, widened again only if the
+    /// model or its moving average changed since the last one.
+    fn widened<T>(&self, model: &Model<T>) -> Result<Arc<Model<f32>>, ModelError>
+    where
+        T: Float + Debug + Send + Sync,
+    {
+        let id = model.metadata().id;
+        // Read before the parameters: a copy may hold newer values than its
+        // versions say, and is then merely widened again, never stale.
+        let versions = (model.version(), model.ema().map(Model::version));
+        let mut cache = self.widened.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(cached) = cache.iter().find(|w| w.id == id && w.versions == versions) {
+            return Ok(cached.model.clone());
+        }
+        let wide = Arc::new(widen(model, &model.read_params())?);
+        cache.retain(|w| w.id != id);
+        if cache.len() >= WIDENED_CAPACITY {
+            cache.remove(0);
+        }
+        cache.push(Widened {
+            id,
+            versions,
+            model: wide.clone(),
+        });
+        Ok(wide)
+    }
+
+    /// Runs `step` on an `f32` copy of the trainable parameters of `model`
+    /// and rounds the result back into them.
+    fn train<T>(
+        &self,
+        model: &Model<T>,
+        step: impl FnOnce(&Model<f32>) -> Result<(), ModelError>,
+    ) -> Result<(), ModelError>
+    where
+        T: Float + Debug + Send + Sync,
+    {
+        let mut params = model.write_trainable();
+        let stepped = widen(model, &params).and_then(|wide| step(&wide).map(|()| wide));
+        let wide = match stepped {
+            Ok(wide) => wide,
+            Err(e) => {
+                params.discard();
+                return Err(e);
+            }
+        };
+        for (param, value) in params.iter_mut().zip(wide.read_params().iter()) {
+            *param = T::from(*value).unwrap_or_else(T::nan);
+        }
+        Ok(())
+    }
+}
+
+impl<T, A> Algorithm<T> for MixedPrecision<A>
+where
+    T: Float + Debug + Send + Sync + Sum,
+    A: Algorithm<f32>,
+{
+    type Input = A::Input;
+    type Output = A::Output;
+
+    fn training_step(&self, model: &Model<T>, x: Self::Input) -> Result<(), ModelError> {
+        self.train(model, |wide| self.inner.training_step(wide, x))
+    }
+
+    fn inference_step(&self, model: &Model<T>, x: Self::Input) -> Result<Self::Output, ModelError> {
+        self.inner.inference_step(&*self.widened(model)?, x)
+    }
+
+    fn ema_inference_step(
+        &self,
+        model: &Model<T>,
+        x: Self::Input,
+    ) -> Result<Self::Output, ModelError> {
+        self.inner.ema_inference_step(&*self.widened(model)?, x)
+    }
+
+    fn feedback_step(
+        &self,
+        model: &Model<T>,
+        x: Self::Input,
+        outcome: Self::Output,
+    ) -> Result<(), ModelError> {
+        self.train(model, |wide| self.inner.feedback_step(wide, x, outcome))
+    }
+
+    fn weighted_training_step(
+        &self,
+        model: &Model<T>,
+        x: Self::Input,
+        weight: f64,
+    ) -> Result<(), ModelError> {
+        self.train(model, |wide| {
+            self.inner.weighted_training_step(wide, x, weight)
+        })
+    }
+
+    fn contributions(
+        &self,
+        model: &Model<T>,
+        x: Self::Input,
+    ) -> Result<Contributions<T>, ModelError> {
+        let contributions = self.inner.contributions(&*self.widened(model)?, x)?;
+        Ok(Contributions {
+            features: contributions.features.into_iter().map(narrow).collect(),
+            bias: narrow(contributions.bias),
+        })
+    }
+
+    fn uncertainty(&self, model: &Model<T>, x: Self::Input) -> Result<Uncertainty<T>, ModelError> {
+        let uncertainty = self.inner.uncertainty(&*self.widened(model)?, x)?;
+        Ok(Uncertainty {
+            variance: uncertainty.variance.map(narrow),
+            lower: uncertainty.lower.map(narrow),
+            upper: uncertainty.upper.map(narrow),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::{AppState, PredictionEnvelope};
+    use crate::model::SavedModel;
+    use crate::server::configure;
+    use crate::test::ScalingAlgorithm;
+    use actix_web::{test as actix_test, web, App};
+
+    #[test]
+    fn test_half_precision_storage_with_f32_steps() {
+        let values: Vec<f32> = (0..1000).map(|i| i as f32 / 1000.0).collect();
+        let wide = Model::with_parameters(values.clone());
+        let half = Model::with_parameters(values.iter().map(|v| F16::from_f32(*v)).collect());
+        assert_eq!(half.memory_bytes() * 2, wide.memory_bytes());
+
+        // A thousand terms summed in f16 would stall once the total outgrows
+        // the spacing of the values; the sum is exact to f16 rounding.
+        let algorithm = MixedPrecision::new(ScalingAlgorithm);
+        let exact: f32 = values.iter().sum();
+        let sum = algorithm.inference_step(&half, 1.0).unwrap();
+        assert!((sum - exact).abs() / exact < 1e-3);
+        let direct: F16 = half.read_params().iter().copied().sum();
+        assert!((direct.to_f32() - exact).abs() / exact < 1e-3);
+
+        algorithm.training_step(&half, 0.5).unwrap();
+        assert_eq!(half.read_params()[500].to_f32(), 0.25);
+        assert_eq!(half.training_steps(), 0);
+        assert_eq!(half.version(), 1);
+
+        // bfloat16 keeps the range of f32.
+        let large = Model::with_parameters(vec![Bf16::from_f32(1e30)]);
+        MixedPrecision::new(ScalingAlgorithm)
+            .training_step(&large, 2.0)
+            .unwrap();
+        assert!((large.read_params()[0].to_f32() / 2e30 - 1.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_widened_copy_reused_until_the_model_changes() {
+        let model = Model::with_parameters(vec![F16::from_f32(1.0), F16::from_f32(2.0)])
+            .with_input_dimension(1)
+            .with_ema(0.5);
+        let algorithm = MixedPrecision::new(ScalingAlgorithm);
+        let wide = algorithm.widened(&model).unwrap();
+        assert!(Arc::ptr_eq(&wide, &algorithm.widened(&model).unwrap()));
+        assert_eq!(wide.metadata().input_dimension, Some(1));
+        let contributions = algorithm.contributions(&model, 2.0).unwrap();
+        assert_eq!(
+            contributions.features,
+            vec![F16::from_f32(2.0), F16::from_f32(4.0)]
+        );
+
+        algorithm.training_step(&model, 2.0).unwrap();
+        model.record_training_step();
+        assert!(!Arc::ptr_eq(&wide, &algorithm.widened(&model).unwrap()));
+        assert_eq!(algorithm.inference_step(&model, 1.0).unwrap(), 6.0);
+        // The moving average is halfway between the old and new parameters.
+        assert_eq!(algorithm.ema_inference_step(&model, 1.0).unwrap(), 4.5);
+    }
+
+    #[test]
+    fn test_saved_half_precision_models_convert() {
+        let model = Model::with_parameters(vec![F16::from_f32(0.5), F16::from_f32(-2.0)]);
+        let json = serde_json::to_string(&model.to_saved()).unwrap();
+        assert!(json.contains("[0.5,-2.0]"));
+        let widened: SavedModel<f64> = serde_json::from_str(&json).unwrap();
+        assert_eq!(widened.parameters, vec![0.5, -2.0]);
+
+        let bytes = bincode::serialize(&model.to_saved()).unwrap();
+        let loaded: SavedModel<F16> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(loaded.parameters, model.read_params().to_vec());
+        assert!(bytes.len() < bincode::serialize(&widened).unwrap().len());
+
+        let safetensors = model.to_safetensors().unwrap();
+        let decoded: Model<f32> = Model::from_safetensors(&safetensors).unwrap();
+        assert_eq!(decoded.read_params().as_slice(), &[0.5, -2.0]);
+        let bf16 = Model::with_parameters(vec![Bf16::from_f32(3.0)]);
+        let decoded: Model<f32> = Model::from_safetensors(&bf16.to_safetensors().unwrap()).unwrap();
+        assert_eq!(decoded.read_params().as_slice(), &[3.0]);
+    }
+
+    #[actix_rt::test]
+    async fn test_half_precision_model_served() {
+        let state = AppState::new(
+            Model::with_parameters(vec![F16::from_f32(1.0), F16::from_f32(2.0)]),
+            MixedPrecision::new(ScalingAlgorithm),
+        );
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(configure::<F16, MixedPrecision<ScalingAlgorithm>>),
+        )
+        .await;
+        let request = actix_test::TestRequest::post()
+            .uri("/training")
+            .set_json(2.0)
+            .to_request();
+        assert!(actix_test::call_service(&app, request)
+            .await
+            .status()
+            .is_success());
+        let request = actix_test::TestRequest::post()
+            .uri("/inference")
+            .set_json(0.5)
+            .to_request();
+        let response: PredictionEnvelope<f32> =
+            actix_test::call_and_read_body_json(&app, request).await;
+        assert_eq!(response.prediction, 3.0);
+    }
+}
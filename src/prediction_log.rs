@@ -0,0 +1,291 @@
+use crate::errors::ModelError;
+use crate::rotation::{FileFormat, RotatingFiles};
+use crate::seed::{self, SeededRng};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// What a [`Redaction`] does to the field it names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactionMode {
+    /// Removes the field.
+    Drop,
+    /// Replaces the value with `"[redacted]"`.
+    Mask,
+    /// Replaces the value with a hash of it, so records of the same entity
+    /// can still be joined without storing it.
+    Hash,
+}
+
+/// Rule redacting a field of the logged features, e.g. an email address.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Redaction {
+    /// Dot-separated path of the field in the features' JSON form, e.g.
+    /// `user.email`.
+    pub field: String,
+    pub mode: RedactionMode,
+}
+
+impl Redaction {
+    pub fn new(field: impl Into<String>, mode: RedactionMode) -> Self {
+        Redaction {
+            field: field.into(),
+            mode,
+        }
+    }
+
+    /// Applies the rule to `features`; paths that are missing are ignored.
+    pub fn apply(&self, features: &mut Value) {
+        let mut path: Vec<&str> = self.field.split('.').collect();
+        let Some(last) = path.pop() else {
+            return;
+        };
+        let parent = path
+            .iter()
+            .try_fold(features, |value, key| value.as_object_mut()?.get_mut(*key));
+        let Some(object) = parent.and_then(Value::as_object_mut) else {
+            return;
+        };
+        match self.mode {
+            RedactionMode::Drop => {
+                object.remove(last);
+            }
+            RedactionMode::Mask => {
+                if let Some(value) = object.get_mut(last) {
+                    *value = Value::String("[redacted]".to_string());
+                }
+            }
+            RedactionMode::Hash => {
+                if let Some(value) = object.get_mut(last) {
+                    let hash = seed::derive_seed(0, &value.to_string());
+                    *value = Value::String(format!("{:016x}", hash));
+                }
+            }
+        }
+    }
+}
+
+/// Configuration of a [`PredictionLog`].
+#[derive(Debug, Clone)]
+pub struct PredictionLogConfig {
+    /// Directory holding the `predictions-<index>.ndjson` files; created if
+    /// missing.
+    pub directory: PathBuf,
+    /// Share of the predictions logged, from 0 to 1.
+    pub sample_rate: f64,
+    /// Rules applied to the features of every record before it is written.
+    pub redactions: Vec<Redaction>,
+    /// A new file is started once the current one would grow past this size.
+    pub max_file_bytes: u64,
+    /// Number of most recent files kept on disk (at least one).
+    pub retain: usize,
+}
+
+impl PredictionLogConfig {
+    /// Logs every prediction.
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        PredictionLogConfig {
+            directory: directory.into(),
+            sample_rate: 1.0,
+            redactions: Vec::new(),
+            max_file_bytes: 64 * 1024 * 1024,
+            retain: 8,
+        }
+    }
+
+    pub fn with_sample_rate(mut self, sample_rate: f64) -> Self {
+        self.sample_rate = sample_rate.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_redaction(mut self, redaction: Redaction) -> Self {
+        self.redactions.push(redaction);
+        self
+    }
+}
+
+/// One logged prediction, a line of a prediction log file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PredictionRecord {
+    pub timestamp_ms: u64,
+    /// Parameters version of the serving model, `None` for catalog models.
+    pub model_version: Option<u64>,
+    /// Time taken to answer the request.
+    pub latency_us: u64,
+    /// Features of the request, after redaction.
+    pub features: Value,
+    pub prediction: Value,
+}
+
+/// Naming of the prediction log files.
+const LOG_FILES: FileFormat = FileFormat {
+    prefix: "predictions-",
+    suffix: ".ndjson",
+    header: b"",
+};
+
+/// Opt-in log of a sample of the predictions a server makes, as NDJSON for
+/// offline monitoring and for building retraining datasets.
+///
+/// Unlike a [`TraceRecorder`], which keeps every request so it can be
+/// replayed, the log keeps a random share of the answered inference
+/// requests with what they were answered and how fast, and redacts
+/// personal fields of the features before they reach the disk. Sampling
+/// draws from [`seed::rng`], so it is repeatable in deterministic mode.
+///
+/// Files rotate like a trace recorder's, and writes are buffered until
+/// [`PredictionLog::flush`] (called when the server stops).
+///
+/// [`TraceRecorder`]: crate::recorder::TraceRecorder
+#[derive(Debug)]
+pub struct PredictionLog {
+    config: PredictionLogConfig,
+    rng: Mutex<SeededRng>,
+    files: RotatingFiles,
+}
+
+impl PredictionLog {
+    /// Nothing touches the disk before the first prediction is logged.
+    pub fn new(config: PredictionLogConfig) -> Self {
+        PredictionLog {
+            rng: Mutex::new(seed::rng("prediction_log")),
+            files: RotatingFiles::new(
+                LOG_FILES,
+                config.directory.clone(),
+                config.max_file_bytes,
+                config.retain,
+            ),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &PredictionLogConfig {
+        &self.config
+    }
+
+    /// Whether the next prediction is logged.
+    pub fn sample(&self) -> Result<bool, ModelError> {
+        Ok(self.rng.lock()?.gen_bool(self.config.sample_rate))
+    }
+
+    /// Redacts `features` and appends them with `prediction`, stamped with
+    /// the current time.
+    pub fn record(
+        &self,
+        features: &impl Serialize,
+        prediction: &impl Serialize,
+        model_version: Option<u64>,
+        latency: Duration,
+    ) -> Result<(), ModelError> {
+        let mut features = serde_json::to_value(features)?;
+        for redaction in &self.config.redactions {
+            redaction.apply(&mut features);
+        }
+        let record = PredictionRecord {
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            model_version,
+            latency_us: latency.as_micros() as u64,
+            features,
+            prediction: serde_json::to_value(prediction)?,
+        };
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        self.files.append(&[&line])
+    }
+
+    /// Writes out the buffered predictions, e.g. before reading the log
+    /// back with [`read_predictions`].
+    pub fn flush(&self) -> Result<(), ModelError> {
+        self.files.flush()
+    }
+}
+
+/// Prediction log files of a directory with their indices, oldest first.
+pub fn log_files(directory: &Path) -> Result<Vec<(u64, PathBuf)>, ModelError> {
+    LOG_FILES.files(directory)
+}
+
+/// Reads the records of the prediction log files in `directory`, oldest
+/// first. A line cut short by a crash is skipped.
+pub fn read_predictions(directory: &Path) -> Result<Vec<PredictionRecord>, ModelError> {
+    let mut records = Vec::new();
+    for (_, path) in log_files(directory)? {
+        let text = fs::read_to_string(path)?;
+        records.extend(
+            text.lines()
+                .filter_map(|line| serde_json::from_str(line).ok()),
+        );
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::AppState;
+    use crate::model::Model;
+    use crate::service::{InferenceRequest, OmlService};
+    use crate::test::ScalingAlgorithm;
+    use serde_json::json;
+
+    #[actix_rt::test]
+    async fn test_sampled_predictions_logged_with_redacted_features() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::new(Model::with_parameters(vec![2.0f64]), ScalingAlgorithm)
+            .with_prediction_log(PredictionLogConfig::new(dir.path()));
+        let service = OmlService::new(state);
+        service.infer(InferenceRequest::new(3.0)).await.unwrap();
+        let log = service.state().prediction_log.clone().unwrap();
+        log.flush().unwrap();
+        let records = read_predictions(dir.path()).unwrap();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(
+            (&record.features, &record.prediction, record.model_version),
+            (&json!(3.0), &json!(6.0), Some(0))
+        );
+        assert_eq!(service.metrics().counters["prediction_log_records"], 1);
+
+        // Nothing is logged at a zero sample rate.
+        let quiet = tempfile::tempdir().unwrap();
+        let state = AppState::new(Model::with_parameters(vec![2.0f64]), ScalingAlgorithm)
+            .with_prediction_log(PredictionLogConfig::new(quiet.path()).with_sample_rate(0.0));
+        let service = OmlService::new(state);
+        service.infer(InferenceRequest::new(3.0)).await.unwrap();
+        assert!(!quiet
+            .path()
+            .join("predictions-00000000000000000001.ndjson")
+            .exists());
+
+        let config = PredictionLogConfig::new(dir.path())
+            .with_redaction(Redaction::new("user.email", RedactionMode::Hash))
+            .with_redaction(Redaction::new("user.name", RedactionMode::Mask))
+            .with_redaction(Redaction::new("ssn", RedactionMode::Drop))
+            .with_redaction(Redaction::new("missing.field", RedactionMode::Drop));
+        let log = PredictionLog::new(config);
+        let features = |email: &str| json!({"user": {"email": email, "name": "Ada"}, "ssn": "123", "amount": 9.5});
+        for email in ["a@example.com", "a@example.com", "b@example.com"] {
+            log.record(&features(email), &1.0, None, Duration::from_micros(250))
+                .unwrap();
+        }
+        log.flush().unwrap();
+        let records = read_predictions(dir.path()).unwrap();
+        let redacted: Vec<&Value> = records[1..].iter().map(|r| &r.features).collect();
+        assert_eq!(redacted[0]["user"]["name"], "[redacted]");
+        assert_eq!(redacted[0]["amount"], 9.5);
+        assert!(redacted[0].get("ssn").is_none());
+        assert_ne!(redacted[0]["user"]["email"], "a@example.com");
+        // Hashes are stable, so records of one user can still be joined.
+        assert_eq!(redacted[0]["user"]["email"], redacted[1]["user"]["email"]);
+        assert_ne!(redacted[0]["user"]["email"], redacted[2]["user"]["email"]);
+        assert_eq!(records[1].latency_us, 250);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::algorithm::Algorithm;
+use crate::errors::ModelError;
+use crate::layout::ModelLayout;
+use crate::model::Model;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::marker::PhantomData;
+
+/// Consecutive parameters sharing a scale and zero point: a value `q` stands
+/// for `(q - zero_point) * scale`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuantizedBlock {
+    pub scale: f64,
+    pub zero_point: i8,
+    pub values: Vec<i8>,
+}
+
+impl QuantizedBlock {
+    /// Calibrates the scale and zero point on the range of `params`
+    /// (widened to include zero, which stays exact) and quantizes them.
+    /// Non-finite parameters are not representable and read back as zero.
+    pub fn calibrate<T: Float>(params: &[T]) -> Self {
+        let finite = params
+            .iter()
+            .filter_map(|p| p.to_f64())
+            .filter(|p| p.is_finite());
+        let (min, max) = finite.fold((0.0f64, 0.0f64), |(lo, hi), p| (lo.min(p), hi.max(p)));
+        let scale = match max - min {
+            range if range > 0.0 => range / 255.0,
+            _ => 1.0,
+        };
+        let zero_point = (-128.0 - min / scale).round().clamp(-128.0, 127.0) as i8;
+        let values = params
+            .iter()
+            .map(|p| {
+                let p = p.to_f64().filter(|p| p.is_finite()).unwrap_or(0.0);
+                (p / scale + zero_point as f64).round().clamp(-128.0, 127.0) as i8
+            })
+            .collect();
+        QuantizedBlock {
+            scale,
+            zero_point,
+            values,
+        }
+    }
+
+    pub fn dequantize<T: Float>(&self, index: usize) -> T {
+        let q = self.values[index] as f64 - self.zero_point as f64;
+        T::from(q * self.scale).unwrap_or_else(T::zero)
+    }
+
+    /// Largest difference between a (finite) calibrated parameter and its
+    /// dequantized value.
+    pub fn max_error(&self) -> f64 {
+        self.scale / 2.0
+    }
+}
+
+/// Parameters of a model stored as int8, one [`QuantizedBlock`] per group
+/// of its layout (or a single one), at a quarter of the memory of `f32`
+/// parameters and an eighth of `f64` ones.
+///
+/// Dequantized on the fly for inference, so a frozen model that is rarely
+/// queried, e.g. a retained catalog version, costs little memory.
+///
+/// # Examples
+///
+/// ```
+/// use oml::algorithm::Algorithm;
+/// use oml::model::Model;
+/// use oml::quantization::QuantizedModel;
+/// use oml::test::ScalingAlgorithm;
+///
+/// let model = Model::with_parameters(vec![0.5f32, -1.0, 2.0]);
+/// let quantized = QuantizedModel::from_model(&model);
+/// let exact = ScalingAlgorithm.inference_step(&model, 1.0).unwrap();
+/// let approx = quantized.infer(&ScalingAlgorithm, 1.0).unwrap();
+/// assert!((exact - approx).abs() <= 3.0 * quantized.max_error() as f32);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuantizedModel<T> {
+    layout: ModelLayout,
+    blocks: Vec<QuantizedBlock>,
+    #[serde(skip)]
+    _scalar: PhantomData<fn() -> T>,
+}
+
+impl<T> QuantizedModel<T>
+where
+    T: Float + Debug + Send + Sync + Sum,
+{
+    /// Quantizes the current parameters of `model`, calibrating every
+    /// group on its own range.
+    pub fn from_model(model: &Model<T>) -> Self {
+        let params = model.snapshot();
+        let layout = model.layout().clone();
+        let blocks = match layout.is_empty() {
+            true => vec![QuantizedBlock::calibrate(&params)],
+            false => layout
+                .groups()
+                .iter()
+                .map(|group| QuantizedBlock::calibrate(&params[group.range()]))
+                .collect(),
+        };
+        QuantizedModel {
+            layout,
+            blocks,
+            _scalar: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.iter().map(|b| b.values.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Approximate memory taken by the quantized values and their scales.
+    pub fn memory_bytes(&self) -> usize {
+        self.blocks
+            .iter()
+            .map(|b| b.values.len() + std::mem::size_of::<(f64, i8)>())
+            .sum()
+    }
+
+    /// Largest difference between a calibrated parameter and its
+    /// dequantized value, over all groups.
+    pub fn max_error(&self) -> f64 {
+        self.blocks
+            .iter()
+            .map(QuantizedBlock::max_error)
+            .fold(0.0, f64::max)
+    }
+
+    /// Dequantized parameters, in order.
+    pub fn dequantize(&self) -> Vec<T> {
+        self.blocks
+            .iter()
+            .flat_map(|b| (0..b.values.len()).map(move |i| b.dequantize(i)))
+            .collect()
+    }
+
+    /// A model holding the dequantized parameters, with the same layout.
+    pub fn to_model(&self) -> Result<Model<T>, ModelError> {
+        Model::with_layout(self.layout.clone(), self.dequantize())
+    }
+
+    /// Runs `algorithm`'s inference step on the dequantized parameters.
+    pub fn infer<A: Algorithm<T>>(
+        &self,
+        algorithm: &A,
+        x: A::Input,
+    ) -> Result<A::Output, ModelError> {
+        algorithm.inference_step(&self.to_model()?, x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::ModelLayout;
+    use crate::test::ScalingAlgorithm;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_dequantized_parameters_within_half_a_step() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(7);
+        let layout = ModelLayout::new()
+            .group("weights", &[1000])
+            .group("bias", &[1]);
+        let mut params: Vec<f32> = (0..1000).map(|_| rng.gen_range(-0.5..0.5)).collect();
+        params.push(40.0);
+        let model = Model::with_layout(layout, params.clone()).unwrap();
+        let quantized = QuantizedModel::from_model(&model);
+
+        // The bias has its own range, so it does not coarsen the weights.
+        assert!(quantized.blocks[0].max_error() < 0.5 / 255.0 + 1e-6);
+        let restored = quantized.to_model().unwrap();
+        for (group, block) in ["weights", "bias"].iter().zip(&quantized.blocks) {
+            let exact = model.read_params().group(group).unwrap().to_vec();
+            let approx = restored.read_params().group(group).unwrap().to_vec();
+            for (e, a) in exact.iter().zip(&approx) {
+                assert!(((e - a).abs() as f64) <= block.max_error() + 1e-6);
+            }
+        }
+        assert!(quantized.memory_bytes() * 3 < params.len() * std::mem::size_of::<f32>());
+
+        let exact = ScalingAlgorithm.inference_step(&model, 1.0).unwrap();
+        let approx = quantized.infer(&ScalingAlgorithm, 1.0).unwrap();
+        assert!((exact - approx).abs() / exact.abs() < 0.01);
+    }
+
+    #[test]
+    fn test_zero_and_constant_parameters_are_exact() {
+        let model = Model::with_parameters(vec![0.0f64, 0.0, f64::NAN]);
+        let quantized = QuantizedModel::from_model(&model);
+        assert_eq!(quantized.dequantize(), vec![0.0, 0.0, 0.0]);
+        let model = Model::with_parameters(vec![0.0f64, 3.0, -1.5]);
+        let dequantized = QuantizedModel::from_model(&model).dequantize();
+        assert_eq!(dequantized[0], 0.0);
+        assert!((dequantized[1] - 3.0).abs() <= 4.5 / 510.0 + 1e-12);
+    }
+}
@@ -0,0 +1,238 @@
+use crate::errors::ModelError;
+use crate::rotation::{FileFormat, RotatingFiles};
+use crate::simulation::{TraceEvent, TraceRecord};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// First bytes of a binary trace file, followed by length-prefixed bincode
+/// records (a little-endian `u32` length, then the record).
+pub const TRACE_MAGIC: &[u8; 8] = b"OMLTRC01";
+
+/// Configuration of a [`TraceRecorder`].
+#[derive(Debug, Clone)]
+pub struct RecorderConfig {
+    /// Directory holding the `trace-<index>.bin` files; created if missing.
+    pub directory: PathBuf,
+    /// A new file is started once the current one would grow past this size.
+    pub max_file_bytes: u64,
+    /// Number of most recent files kept on disk (at least one).
+    pub retain: usize,
+}
+
+impl RecorderConfig {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        RecorderConfig {
+            directory: directory.into(),
+            max_file_bytes: 64 * 1024 * 1024,
+            retain: 8,
+        }
+    }
+}
+
+/// Binary form of a [`TraceRecord`]: bincode cannot read the internally
+/// tagged JSON form.
+#[derive(Serialize, Deserialize)]
+struct BinaryRecord<I, O> {
+    timestamp_ms: Option<u64>,
+    model_version: Option<u64>,
+    event: BinaryEvent<I, O>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum BinaryEvent<I, O> {
+    Training(I),
+    Inference(I),
+    Outcome(I, O),
+}
+
+impl<I, O> From<TraceRecord<I, O>> for BinaryRecord<I, O> {
+    fn from(record: TraceRecord<I, O>) -> Self {
+        BinaryRecord {
+            timestamp_ms: record.timestamp_ms,
+            model_version: record.model_version,
+            event: match record.event {
+                TraceEvent::Training { input } => BinaryEvent::Training(input),
+                TraceEvent::Inference { input } => BinaryEvent::Inference(input),
+                TraceEvent::Outcome { input, label } => BinaryEvent::Outcome(input, label),
+            },
+        }
+    }
+}
+
+impl<I, O> From<BinaryRecord<I, O>> for TraceRecord<I, O> {
+    fn from(record: BinaryRecord<I, O>) -> Self {
+        TraceRecord {
+            timestamp_ms: record.timestamp_ms,
+            model_version: record.model_version,
+            event: match record.event {
+                BinaryEvent::Training(input) => TraceEvent::Training { input },
+                BinaryEvent::Inference(input) => TraceEvent::Inference { input },
+                BinaryEvent::Outcome(input, label) => TraceEvent::Outcome { input, label },
+            },
+        }
+    }
+}
+
+/// Naming and header of the trace files.
+const TRACE_FILES: FileFormat = FileFormat {
+    prefix: "trace-",
+    suffix: ".bin",
+    header: TRACE_MAGIC,
+};
+
+/// Opt-in shadow log of the requests a server receives, written to a
+/// compact binary trace that [`crate::simulation::read_trace`] replays.
+///
+/// Records go to numbered files in a directory; a new file is started when
+/// the current one reaches `max_file_bytes`, and the oldest beyond `retain`
+/// are removed. Writes are buffered: [`TraceRecorder::flush`] (called when
+/// the server stops) writes out the pending records.
+#[derive(Debug)]
+pub struct TraceRecorder<I, O = I> {
+    config: RecorderConfig,
+    files: RotatingFiles,
+    _marker: PhantomData<fn(I, O)>,
+}
+
+impl<I, O> TraceRecorder<I, O>
+where
+    I: Serialize + DeserializeOwned,
+    O: Serialize + DeserializeOwned,
+{
+    /// The directory is created when the first record is written.
+    pub fn new(config: RecorderConfig) -> Self {
+        TraceRecorder {
+            files: RotatingFiles::new(
+                TRACE_FILES,
+                config.directory.clone(),
+                config.max_file_bytes,
+                config.retain,
+            ),
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn config(&self) -> &RecorderConfig {
+        &self.config
+    }
+
+    /// Appends `event`, stamped with the current time and `model_version`.
+    pub fn record(
+        &self,
+        event: TraceEvent<I, O>,
+        model_version: Option<u64>,
+    ) -> Result<(), ModelError> {
+        let record = TraceRecord {
+            timestamp_ms: Some(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or(0),
+            ),
+            model_version,
+            event,
+        };
+        let frame = bincode::serialize(&BinaryRecord::from(record))?;
+        self.files
+            .append(&[&(frame.len() as u32).to_le_bytes(), &frame])
+    }
+
+    /// Writes out the buffered records.
+    pub fn flush(&self) -> Result<(), ModelError> {
+        self.files.flush()
+    }
+}
+
+/// Trace files of a recorder directory with their indices, oldest first.
+pub fn trace_files(directory: &Path) -> Result<Vec<(u64, PathBuf)>, ModelError> {
+    TRACE_FILES.files(directory)
+}
+
+/// Decodes the records of a binary trace file. A record cut short by a
+/// crash ends the trace.
+pub fn decode_trace<I, O>(bytes: &[u8]) -> Result<Vec<TraceRecord<I, O>>, ModelError>
+where
+    I: DeserializeOwned,
+    O: DeserializeOwned,
+{
+    let mut rest = bytes
+        .strip_prefix(TRACE_MAGIC.as_slice())
+        .ok_or_else(|| ModelError::InvalidInput("not a binary trace".to_string()))?;
+    let mut records = Vec::new();
+    while rest.len() >= 4 {
+        let len = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        let Some(frame) = rest.get(4..4 + len) else {
+            break;
+        };
+        let record: BinaryRecord<I, O> = bincode::deserialize(frame).map_err(|e| {
+            ModelError::InvalidInput(format!("trace record {}: {}", records.len() + 1, e))
+        })?;
+        records.push(record.into());
+        rest = &rest[4 + len..];
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::read_trace;
+    use std::fs;
+
+    #[test]
+    fn test_records_rotate_and_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = RecorderConfig::new(dir.path());
+        config.max_file_bytes = 100;
+        config.retain = 3;
+        let recorder = TraceRecorder::<f64>::new(config.clone());
+        for i in 0..20 {
+            recorder
+                .record(TraceEvent::Training { input: i as f64 }, Some(i))
+                .unwrap();
+        }
+        recorder
+            .record(
+                TraceEvent::Outcome {
+                    input: 1.0,
+                    label: 2.0,
+                },
+                None,
+            )
+            .unwrap();
+        recorder.flush().unwrap();
+
+        let files = trace_files(dir.path()).unwrap();
+        assert_eq!(files.len(), 3);
+        assert!(files
+            .iter()
+            .all(|(_, path)| fs::metadata(path).unwrap().len() <= 100));
+        let records = read_trace::<f64, f64>(dir.path()).unwrap();
+        let last = records.last().unwrap();
+        assert_eq!(
+            last.event,
+            TraceEvent::Outcome {
+                input: 1.0,
+                label: 2.0
+            }
+        );
+        assert!(last.timestamp_ms.is_some() && last.model_version.is_none());
+        assert_eq!(records[records.len() - 2].model_version, Some(19));
+
+        // A restarted recorder continues after the newest file; a torn
+        // record ends the trace.
+        let restarted = TraceRecorder::<f64>::new(config);
+        restarted
+            .record(TraceEvent::Inference { input: 0.0 }, None)
+            .unwrap();
+        restarted.flush().unwrap();
+        let newest = trace_files(dir.path()).unwrap().pop().unwrap();
+        assert_eq!(newest.0, files.last().unwrap().0 + 1);
+        let mut bytes = fs::read(&newest.1).unwrap();
+        bytes.truncate(bytes.len() - 1);
+        assert!(decode_trace::<f64, f64>(&bytes).unwrap().is_empty());
+    }
+}
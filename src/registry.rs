@@ -0,0 +1,184 @@
+use crate::algorithm::{Algorithm, Datum};
+use crate::errors::ModelError;
+use crate::model::Model;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// What a registered algorithm may do with the shared model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlgorithmRole {
+    /// Trains and serves inference.
+    Trainer,
+    /// Serves inference only, e.g. an evaluation scorer.
+    Scorer,
+}
+
+/// Listing entry of `GET /models/{name}/algorithms`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlgorithmInfo {
+    pub name: String,
+    pub role: AlgorithmRole,
+}
+
+/// Algorithm stored in the registry, erased to its input and output types.
+pub type SharedAlgorithm<T, I, O> = Arc<dyn Algorithm<T, Input = I, Output = O>>;
+
+/// Registry whose algorithms take and return the same types as `A`.
+pub type RegistryFor<T, A> =
+    AlgorithmRegistry<T, <A as Algorithm<T>>::Input, <A as Algorithm<T>>::Output>;
+
+/// Several named algorithms operating on the same model.
+///
+/// Inference runs concurrently as on the main routes. Training steps of all
+/// trainers, and of the main `/training` route while a registry is
+/// configured, are serialized through one writer lock, so the model never
+/// sees two writers at once.
+pub struct AlgorithmRegistry<T, I, O = I>
+where
+    T: Float + Debug + Send + Sync + Sum,
+    I: Datum,
+    O: Datum,
+{
+    model_name: String,
+    algorithms: BTreeMap<String, (AlgorithmRole, SharedAlgorithm<T, I, O>)>,
+    writer: Mutex<()>,
+}
+
+impl<T, I, O> AlgorithmRegistry<T, I, O>
+where
+    T: Float + Debug + Send + Sync + Sum,
+    I: Datum,
+    O: Datum,
+{
+    /// Creates an empty registry for the model served as `model_name`.
+    pub fn new(model_name: impl Into<String>) -> Self {
+        AlgorithmRegistry {
+            model_name: model_name.into(),
+            algorithms: BTreeMap::new(),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Registers `algorithm` as `name`, replacing any earlier one.
+    pub fn with_algorithm(
+        mut self,
+        name: impl Into<String>,
+        role: AlgorithmRole,
+        algorithm: impl Algorithm<T, Input = I, Output = O> + 'static,
+    ) -> Self {
+        self.algorithms
+            .insert(name.into(), (role, Arc::new(algorithm)));
+        self
+    }
+
+    pub fn model_name(&self) -> &str {
+        &self.model_name
+    }
+
+    pub fn list(&self) -> Vec<AlgorithmInfo> {
+        self.algorithms
+            .iter()
+            .map(|(name, (role, _))| AlgorithmInfo {
+                name: name.clone(),
+                role: *role,
+            })
+            .collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<(AlgorithmRole, SharedAlgorithm<T, I, O>)> {
+        self.algorithms
+            .get(name)
+            .map(|(role, algorithm)| (*role, algorithm.clone()))
+    }
+
+    /// Takes the writer lock; hold it for the duration of a training step.
+    pub fn lock_writer(&self) -> Result<MutexGuard<'_, ()>, ModelError> {
+        Ok(self.writer.lock()?)
+    }
+
+    /// Runs a training step of trainer `name` under the writer lock.
+    pub fn train(&self, model: &Model<T>, name: &str, x: I) -> Result<(), ModelError> {
+        let algorithm = match self.get(name) {
+            Some((AlgorithmRole::Trainer, algorithm)) => algorithm,
+            Some((AlgorithmRole::Scorer, _)) => {
+                return Err(ModelError::InvalidInput(format!(
+                    "algorithm {} is inference-only",
+                    name
+                )))
+            }
+            None => return Err(unknown_algorithm(name)),
+        };
+        let _writer = self.lock_writer()?;
+        algorithm.training_step(model, x)
+    }
+
+    /// Runs an inference step of algorithm `name`.
+    pub fn infer(&self, model: &Model<T>, name: &str, x: I) -> Result<O, ModelError> {
+        let (_, algorithm) = self.get(name).ok_or_else(|| unknown_algorithm(name))?;
+        algorithm.inference_step(model, x)
+    }
+}
+
+fn unknown_algorithm(name: &str) -> ModelError {
+    ModelError::InvalidInput(format!("unknown algorithm {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Adds the input to the parameter; predicts the parameter.
+    struct Accumulate;
+
+    impl Algorithm<f64> for Accumulate {
+        type Input = f64;
+        type Output = f64;
+
+        fn training_step(&self, model: &Model<f64>, x: f64) -> Result<(), ModelError> {
+            model.write_params()[0] += x;
+            Ok(())
+        }
+
+        fn inference_step(&self, model: &Model<f64>, _x: f64) -> Result<f64, ModelError> {
+            Ok(model.read_params()[0])
+        }
+    }
+
+    /// Predicts the distance between the input and the parameter.
+    struct Distance;
+
+    impl Algorithm<f64> for Distance {
+        type Input = f64;
+        type Output = f64;
+
+        fn training_step(&self, _model: &Model<f64>, _x: f64) -> Result<(), ModelError> {
+            Ok(())
+        }
+
+        fn inference_step(&self, model: &Model<f64>, x: f64) -> Result<f64, ModelError> {
+            Ok((x - model.read_params()[0]).abs())
+        }
+    }
+
+    #[test]
+    fn test_algorithms_share_the_model() {
+        let registry = AlgorithmRegistry::new("m")
+            .with_algorithm("sgd", AlgorithmRole::Trainer, Accumulate)
+            .with_algorithm("scorer", AlgorithmRole::Scorer, Distance);
+        let model = Model::with_parameters(vec![0.0]);
+
+        registry.train(&model, "sgd", 2.0).unwrap();
+        assert_eq!(registry.infer(&model, "scorer", 5.0).unwrap(), 3.0);
+        assert!(registry.train(&model, "scorer", 1.0).is_err());
+        assert!(registry.infer(&model, "missing", 1.0).is_err());
+        assert_eq!(
+            registry.list().iter().map(|a| a.role).collect::<Vec<_>>(),
+            vec![AlgorithmRole::Scorer, AlgorithmRole::Trainer]
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use crate::early_stopping::GuardConfig;
+use crate::errors::ModelError;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+/// Settings that can change while the server runs, without touching the
+/// in-memory model. Absent fields keep their current value.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuntimeConfig {
+    /// Interval between parameter checkpoints.
+    pub checkpoint_interval: Option<Duration>,
+    /// Degradation thresholds of the training guard.
+    pub training_guard: Option<GuardConfig>,
+}
+
+/// Outcome of applying a [`RuntimeConfig`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReloadReport {
+    /// Settings now in effect.
+    pub applied: Vec<String>,
+    /// Settings given for a stage this server does not run.
+    pub ignored: Vec<String>,
+}
+
+impl ReloadReport {
+    pub(crate) fn record(&mut self, setting: &str, applied: bool) {
+        match applied {
+            true => self.applied.push(setting.to_string()),
+            false => self.ignored.push(setting.to_string()),
+        }
+    }
+}
+
+/// Reads a JSON runtime configuration file.
+pub fn read_runtime_config(path: impl AsRef<Path>) -> Result<RuntimeConfig, ModelError> {
+    let bytes = std::fs::read(path)?;
+    serde_json::from_slice(&bytes)
+        .map_err(|e| ModelError::InvalidInput(format!("invalid runtime configuration: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_partial_config_file() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            file,
+            r#"{{"training_guard": {{"tolerance": 0.2, "patience": 5, "action": "revert"}}}}"#
+        )
+        .unwrap();
+        let config = read_runtime_config(file.path()).unwrap();
+        assert_eq!(config.checkpoint_interval, None);
+        assert_eq!(config.training_guard.unwrap().patience, 5);
+
+        write!(file, "}}").unwrap();
+        assert!(read_runtime_config(file.path()).is_err());
+    }
+}
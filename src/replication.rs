@@ -0,0 +1,354 @@
+use crate::algorithm::Algorithm;
+use crate::auth::PeerClient;
+use crate::cluster::Membership;
+use crate::errors::ModelError;
+use crate::metrics::Metrics;
+use crate::model::Model;
+use num_traits::Float;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+/// A training input applied by the leader, tagged with its position in the stream.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrainingEvent<I> {
+    pub seq: u64,
+    pub input: I,
+    /// Weight the step was applied with (class priors, late samples), see
+    /// [`Algorithm::weighted_training_step`].
+    #[serde(default = "full_weight")]
+    pub weight: f64,
+}
+
+fn full_weight() -> f64 {
+    1.0
+}
+
+/// Full parameter state of the leader right after event `seq` was applied.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplicationSnapshot<T> {
+    pub seq: u64,
+    pub values: Vec<T>,
+}
+
+/// Ordered, bounded log of the training events applied by the leader.
+///
+/// Applying an event and appending it happen under one lock, so a snapshot
+/// always corresponds exactly to the last sequence number it reports.
+#[derive(Debug)]
+pub struct ReplicationLog<I> {
+    capacity: usize,
+    events: Mutex<VecDeque<TrainingEvent<I>>>,
+    last_seq: AtomicU64,
+}
+
+impl<I: Clone> ReplicationLog<I> {
+    /// Creates a log retaining at most `capacity` events.
+    pub fn new(capacity: usize) -> Self {
+        ReplicationLog {
+            capacity: capacity.max(1),
+            events: Mutex::new(VecDeque::new()),
+            last_seq: AtomicU64::new(0),
+        }
+    }
+
+    /// Sequence number of the last applied event (0 if none).
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq.load(Ordering::SeqCst)
+    }
+
+    /// Runs `train` and, if it succeeds, records `input`, trained on with
+    /// `weight`, as the next event.
+    pub fn apply<F>(&self, input: I, weight: f64, train: F) -> Result<u64, ModelError>
+    where
+        F: FnOnce() -> Result<(), ModelError>,
+    {
+        let mut events = self.events.lock()?;
+        train()?;
+        let seq = self.last_seq.fetch_add(1, Ordering::SeqCst) + 1;
+        if events.len() >= self.capacity {
+            events.pop_front();
+        }
+        events.push_back(TrainingEvent { seq, input, weight });
+        Ok(seq)
+    }
+
+    /// Returns every event after `since`, or `None` if some of them were
+    /// already truncated and the caller must restart from a snapshot.
+    pub fn since(&self, since: u64) -> Result<Option<Vec<TrainingEvent<I>>>, ModelError> {
+        let events = self.events.lock()?;
+        let oldest = events.front().map(|e| e.seq).unwrap_or(self.last_seq() + 1);
+        if since + 1 < oldest {
+            return Ok(None);
+        }
+        Ok(Some(
+            events.iter().filter(|e| e.seq > since).cloned().collect(),
+        ))
+    }
+
+    /// Captures the model parameters together with the matching sequence number.
+    pub fn snapshot<T>(&self, model: &Model<T>) -> Result<ReplicationSnapshot<T>, ModelError>
+    where
+        T: Float + Debug + Send + Sync,
+    {
+        let _events = self.events.lock()?;
+        Ok(ReplicationSnapshot {
+            seq: self.last_seq(),
+            values: model.read_params().clone(),
+        })
+    }
+}
+
+/// How a follower reaches its leader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transport {
+    /// Polls `/replication/events` and replays the training inputs.
+    Http,
+    /// Streams parameter deltas over the `ParameterSync` protocol.
+    #[cfg(feature = "grpc")]
+    Grpc,
+}
+
+/// Follower side of replication: tails the leader's event stream.
+#[derive(Debug)]
+pub struct Follower {
+    leader_url: String,
+    poll_interval: Duration,
+    applied_seq: AtomicU64,
+    needs_snapshot: AtomicBool,
+    transport: Transport,
+    membership: OnceLock<Arc<Membership>>,
+    http: PeerClient,
+}
+
+impl Follower {
+    /// Creates a follower of the leader at `leader_url`.
+    ///
+    /// A new follower always starts by loading a snapshot from the leader.
+    pub fn new(leader_url: &str, poll_interval: Duration) -> Self {
+        Follower {
+            leader_url: leader_url.trim_end_matches('/').to_string(),
+            poll_interval,
+            applied_seq: AtomicU64::new(0),
+            needs_snapshot: AtomicBool::new(true),
+            transport: Transport::Http,
+            membership: OnceLock::new(),
+            http: PeerClient::default(),
+        }
+    }
+
+    /// Client of the requests to peers, see [`AuthConfig`](crate::auth::AuthConfig).
+    pub(crate) fn peer_client(&self) -> &PeerClient {
+        &self.http
+    }
+
+    /// Creates a follower of the `ParameterSync` endpoint at `leader_url`
+    /// (see [`crate::grpc::SyncService`]). It catches up from a snapshot
+    /// and then applies the parameter deltas the leader streams, instead of
+    /// replaying training inputs; `retry_interval` is the wait before
+    /// reconnecting once the stream breaks.
+    #[cfg(feature = "grpc")]
+    pub fn over_grpc(leader_url: &str, retry_interval: Duration) -> Self {
+        Follower {
+            transport: Transport::Grpc,
+            ..Follower::new(leader_url, retry_interval)
+        }
+    }
+
+    pub fn leader_url(&self) -> &str {
+        &self.leader_url
+    }
+
+    /// Resolves a leader given as a node id through `membership`.
+    pub fn set_membership(&self, membership: Arc<Membership>) {
+        let _ = self.membership.set(membership);
+    }
+
+    /// Base URL of the leader: the address of the live member named by the
+    /// leader URL, if membership is set and knows one, the URL otherwise.
+    pub fn leader_address(&self) -> String {
+        self.membership
+            .get()
+            .and_then(|membership| membership.address_of(&self.leader_url))
+            .unwrap_or_else(|| self.leader_url.clone())
+    }
+
+    /// Sequence number of the last leader event applied locally.
+    pub fn applied_seq(&self) -> u64 {
+        self.applied_seq.load(Ordering::SeqCst)
+    }
+
+    #[cfg(feature = "grpc")]
+    pub(crate) fn needs_snapshot(&self) -> bool {
+        self.needs_snapshot.load(Ordering::SeqCst)
+    }
+
+    #[cfg(feature = "grpc")]
+    pub(crate) fn loaded_snapshot(&self, seq: u64) {
+        self.applied_seq.store(seq, Ordering::SeqCst);
+        self.needs_snapshot.store(false, Ordering::SeqCst);
+    }
+
+    #[cfg(feature = "grpc")]
+    pub(crate) fn applied(&self, seq: u64) {
+        self.applied_seq.store(seq, Ordering::SeqCst);
+    }
+
+    #[cfg(feature = "grpc")]
+    pub(crate) fn fell_behind(&self) {
+        self.needs_snapshot.store(true, Ordering::SeqCst);
+    }
+
+    /// Brings `model` up to date with the leader, returning the number of
+    /// events replayed. Over gRPC this follows the delta stream until it
+    /// breaks.
+    pub async fn sync<T, A>(
+        &self,
+        model: &Arc<Model<T>>,
+        algorithm: &Arc<A>,
+    ) -> Result<usize, ModelError>
+    where
+        T: Float + Serialize + DeserializeOwned + Debug + Send + Sync + Sum + 'static,
+        A: Algorithm<T> + 'static,
+    {
+        match self.transport {
+            Transport::Http => {}
+            #[cfg(feature = "grpc")]
+            Transport::Grpc => return crate::grpc::follow(self, model, |_| {}).await,
+        }
+        let leader = self.leader_address();
+        if self.needs_snapshot.load(Ordering::SeqCst) {
+            let snapshot: ReplicationSnapshot<T> = self
+                .http
+                .get(format!("{}/replication/snapshot", leader))
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            *model.write_params() = snapshot.values;
+            self.applied_seq.store(snapshot.seq, Ordering::SeqCst);
+            self.needs_snapshot.store(false, Ordering::SeqCst);
+        }
+
+        let response = self
+            .http
+            .get(format!(
+                "{}/replication/events?since={}",
+                leader,
+                self.applied_seq()
+            ))
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::GONE {
+            // We fell behind the leader's retained log: restart from a snapshot.
+            self.needs_snapshot.store(true, Ordering::SeqCst);
+            return Ok(0);
+        }
+        let events: Vec<TrainingEvent<A::Input>> = response.error_for_status()?.json().await?;
+
+        let model = model.clone();
+        let algorithm = algorithm.clone();
+        let applied_from = self.applied_seq();
+        // The events applied before a failing one stay applied: the next
+        // sync resumes after them.
+        let (applied, result) = tokio::task::spawn_blocking(move || {
+            let mut last = applied_from;
+            for event in events.into_iter().filter(|e| e.seq > applied_from) {
+                let step = algorithm.weighted_training_step(&model, event.input, event.weight);
+                if let Err(e) = step {
+                    return (last, Err(e));
+                }
+                last = event.seq;
+            }
+            (last, Ok(()))
+        })
+        .await
+        .map_err(|e| ModelError::RemoteError(format!("Task failed: {:?}", e)))?;
+        self.applied_seq.store(applied, Ordering::SeqCst);
+        result.map(|()| (applied - applied_from) as usize)
+    }
+
+    /// Spawns the follower's polling loop on the current runtime.
+    pub fn spawn<T, A>(
+        self: Arc<Self>,
+        model: Arc<Model<T>>,
+        algorithm: Arc<A>,
+        metrics: Arc<Metrics>,
+    ) -> tokio::task::JoinHandle<()>
+    where
+        T: Float + Serialize + DeserializeOwned + Debug + Send + Sync + Sum + 'static,
+        A: Algorithm<T> + 'static,
+    {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.poll_interval);
+            loop {
+                interval.tick().await;
+                #[cfg(feature = "grpc")]
+                if self.transport == Transport::Grpc {
+                    let followed = crate::grpc::follow(&self, &model, |seq| {
+                        metrics.increment("replication_events_applied");
+                        metrics.set_gauge("replication_seq", seq as f64);
+                    })
+                    .await;
+                    if followed.is_err() {
+                        metrics.increment("replication_sync_failures");
+                    }
+                    continue;
+                }
+                match self.sync(&model, &algorithm).await {
+                    Ok(applied) => metrics.add("replication_events_applied", applied as u64),
+                    Err(_) => metrics.increment("replication_sync_failures"),
+                }
+                metrics.set_gauge("replication_seq", self.applied_seq() as f64);
+            }
+        })
+    }
+}
+
+/// Role of this instance in leader/follower replication.
+#[derive(Debug)]
+pub enum Replication<I> {
+    /// Applies training requests and serves the event stream.
+    Leader(ReplicationLog<I>),
+    /// Rejects direct training and replays the leader's stream instead.
+    Follower(Arc<Follower>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_reports_truncation() {
+        let log = ReplicationLog::new(2);
+        let model = Model::with_parameters(vec![0.0f32]);
+        for input in [1.0, 2.0, 3.0] {
+            log.apply(input, 1.0, || Ok(())).unwrap();
+        }
+        assert_eq!(log.last_seq(), 3);
+
+        let events = log.since(1).unwrap().unwrap();
+        assert_eq!(
+            events.iter().map(|e| e.input).collect::<Vec<_>>(),
+            vec![2.0, 3.0]
+        );
+        assert!(log.since(3).unwrap().unwrap().is_empty());
+        assert!(log.since(0).unwrap().is_none());
+        assert_eq!(log.snapshot(&model).unwrap().seq, 3);
+    }
+
+    #[test]
+    fn test_failed_training_is_not_logged() {
+        let log: ReplicationLog<f32> = ReplicationLog::new(4);
+        let result = log.apply(1.0, 1.0, || {
+            Err(ModelError::InvalidInput("bad".to_string()))
+        });
+        assert!(result.is_err());
+        assert_eq!(log.last_seq(), 0);
+    }
+}
@@ -0,0 +1,140 @@
+use crate::errors::ModelError;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Naming and header of the numbered files written by [`RotatingFiles`]:
+/// file `index` is `<prefix><index, 20 digits><suffix>`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FileFormat {
+    pub prefix: &'static str,
+    pub suffix: &'static str,
+    /// Bytes every file starts with, e.g. a magic number.
+    pub header: &'static [u8],
+}
+
+impl FileFormat {
+    fn path(&self, directory: &Path, index: u64) -> PathBuf {
+        directory.join(format!("{}{:020}{}", self.prefix, index, self.suffix))
+    }
+
+    /// Files of this format in `directory` with their indices, oldest first.
+    pub(crate) fn files(&self, directory: &Path) -> Result<Vec<(u64, PathBuf)>, ModelError> {
+        let mut files: Vec<(u64, PathBuf)> = fs::read_dir(directory)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let index = entry
+                    .file_name()
+                    .to_string_lossy()
+                    .strip_prefix(self.prefix)?
+                    .strip_suffix(self.suffix)?
+                    .parse()
+                    .ok()?;
+                Some((index, entry.path()))
+            })
+            .collect();
+        files.sort_unstable();
+        Ok(files)
+    }
+}
+
+#[derive(Debug)]
+struct OpenFile {
+    index: u64,
+    writer: BufWriter<File>,
+    bytes: u64,
+}
+
+/// Appends records to numbered files in a directory, the shared storage of
+/// [`TraceRecorder`] and [`PredictionLog`].
+///
+/// A new file is started when a record would take the current one past
+/// `max_file_bytes` (a record larger than that still gets a file of its
+/// own), and starting one removes the oldest beyond `retain`. Numbering
+/// resumes after the newest file already in the directory. Writes are
+/// buffered until [`RotatingFiles::flush`].
+///
+/// [`TraceRecorder`]: crate::recorder::TraceRecorder
+/// [`PredictionLog`]: crate::prediction_log::PredictionLog
+#[derive(Debug)]
+pub(crate) struct RotatingFiles {
+    format: FileFormat,
+    directory: PathBuf,
+    max_file_bytes: u64,
+    retain: usize,
+    current: Mutex<Option<OpenFile>>,
+}
+
+impl RotatingFiles {
+    /// The directory is created when the first record is appended.
+    pub(crate) fn new(
+        format: FileFormat,
+        directory: PathBuf,
+        max_file_bytes: u64,
+        retain: usize,
+    ) -> Self {
+        RotatingFiles {
+            format,
+            directory,
+            max_file_bytes,
+            retain: retain.max(1),
+            current: Mutex::new(None),
+        }
+    }
+
+    /// Appends the concatenation of `parts` as one record, never split
+    /// across files.
+    pub(crate) fn append(&self, parts: &[&[u8]]) -> Result<(), ModelError> {
+        let size: u64 = parts.iter().map(|part| part.len() as u64).sum();
+        let header = self.format.header.len() as u64;
+        let mut current = self.current.lock()?;
+        let full = current
+            .as_ref()
+            .is_none_or(|file| file.bytes > header && file.bytes + size > self.max_file_bytes);
+        if full {
+            let index = match current.take() {
+                Some(mut file) => {
+                    file.writer.flush()?;
+                    file.index + 1
+                }
+                None => self
+                    .format
+                    .files(&self.directory)
+                    .ok()
+                    .and_then(|files| files.last().map(|(index, _)| index + 1))
+                    .unwrap_or(1),
+            };
+            *current = Some(self.open(index)?);
+        }
+        let file = current.as_mut().expect("a file is open");
+        for part in parts {
+            file.writer.write_all(part)?;
+        }
+        file.bytes += size;
+        Ok(())
+    }
+
+    pub(crate) fn flush(&self) -> Result<(), ModelError> {
+        if let Some(file) = self.current.lock()?.as_mut() {
+            file.writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Starts file `index` and removes the files beyond the retention limit.
+    fn open(&self, index: u64) -> Result<OpenFile, ModelError> {
+        fs::create_dir_all(&self.directory)?;
+        let mut writer = BufWriter::new(File::create(self.format.path(&self.directory, index))?);
+        writer.write_all(self.format.header)?;
+        let files = self.format.files(&self.directory)?;
+        for (_, old) in &files[..files.len().saturating_sub(self.retain)] {
+            let _ = fs::remove_file(old);
+        }
+        Ok(OpenFile {
+            index,
+            writer,
+            bytes: self.format.header.len() as u64,
+        })
+    }
+}
@@ -0,0 +1,232 @@
+use crate::errors::ModelError;
+use crate::layout::ModelLayout;
+use crate::model::{ModelMetadata, SavedModel, MODEL_SCHEMA_VERSION};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::mem::size_of;
+
+/// Content type of safetensors weights, downloaded from and uploaded to
+/// `/model/weights`.
+pub const SAFETENSORS_CONTENT_TYPE: &str = "application/x-safetensors";
+
+/// Name of the single tensor holding the parameters of a model without a
+/// layout.
+pub const BARE_TENSOR: &str = "parameters";
+
+/// Key of the `__metadata__` entry carrying the oml metadata as JSON.
+const OML_METADATA: &str = "oml_metadata";
+
+/// Largest header accepted, as in the reference implementation.
+const MAX_HEADER_BYTES: usize = 100_000_000;
+
+/// Header entry of one tensor.
+#[derive(Debug, Serialize, Deserialize)]
+struct TensorInfo {
+    dtype: String,
+    shape: Vec<usize>,
+    data_offsets: [usize; 2],
+}
+
+/// Bytes per value of the element types read.
+fn dtype_size(dtype: &str) -> Option<usize> {
+    match dtype {
+        "F16" | "BF16" => Some(2),
+        "F32" => Some(4),
+        "F64" => Some(8),
+        _ => None,
+    }
+}
+
+/// Encodes a saved model in the [safetensors] format, to exchange weights
+/// with other ML tools: one tensor per layout group (or a single
+/// `parameters` tensor without a layout), as `F16`, `BF16`, `F32` or `F64`
+/// like the model, with the oml metadata under `__metadata__`.
+///
+/// Sparse parameters have no safetensors form and are rejected.
+///
+/// [safetensors]: https://github.com/huggingface/safetensors
+pub fn encode<T: Float>(saved: &SavedModel<T>) -> Result<Vec<u8>, ModelError> {
+    if saved.sparse.is_some() {
+        return Err(ModelError::Unsupported(
+            "sparse parameters cannot be written as safetensors".to_string(),
+        ));
+    }
+    let (dtype, width) = match size_of::<T>() {
+        // bfloat16 keeps 7 mantissa bits, binary16 10.
+        2 if T::epsilon().to_f64() == Some(half::bf16::EPSILON.to_f64()) => ("BF16", 2),
+        2 => ("F16", 2),
+        4 => ("F32", 4),
+        8 => ("F64", 8),
+        _ => {
+            return Err(ModelError::Unsupported(format!(
+                "{} parameters cannot be written as safetensors",
+                std::any::type_name::<T>()
+            )))
+        }
+    };
+    let tensors: Vec<(String, Vec<usize>, std::ops::Range<usize>)> = match saved.layout.is_empty() {
+        true => vec![(
+            BARE_TENSOR.to_string(),
+            vec![saved.parameters.len()],
+            0..saved.parameters.len(),
+        )],
+        false => saved
+            .layout
+            .groups()
+            .iter()
+            .map(|g| (g.name.clone(), g.shape.clone(), g.range()))
+            .collect(),
+    };
+
+    let mut header = Map::new();
+    let metadata = BTreeMap::from([(OML_METADATA, serde_json::to_string(&saved.metadata)?)]);
+    header.insert("__metadata__".to_string(), serde_json::to_value(metadata)?);
+    for (name, shape, range) in tensors {
+        let info = TensorInfo {
+            dtype: dtype.to_string(),
+            shape,
+            data_offsets: [range.start * width, range.end * width],
+        };
+        header.insert(name, serde_json::to_value(info)?);
+    }
+    let mut header = serde_json::to_vec(&header)?;
+    // Pad with spaces so the data starts 8-byte aligned.
+    header.resize(header.len().next_multiple_of(8), b' ');
+
+    let mut bytes = Vec::with_capacity(8 + header.len() + saved.parameters.len() * width);
+    bytes.extend_from_slice(&(header.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&header);
+    for p in &saved.parameters {
+        match (dtype, width) {
+            ("BF16", _) => bytes.extend_from_slice(&bf16_bits(*p).to_le_bytes()),
+            ("F16", _) => bytes.extend_from_slice(&f16_bits(*p).to_le_bytes()),
+            (_, 4) => bytes.extend_from_slice(&p.to_f32().unwrap_or(f32::NAN).to_le_bytes()),
+            (_, _) => bytes.extend_from_slice(&p.to_f64().unwrap_or(f64::NAN).to_le_bytes()),
+        }
+    }
+    Ok(bytes)
+}
+
+fn f16_bits<T: Float>(value: T) -> u16 {
+    half::f16::from_f64(value.to_f64().unwrap_or(f64::NAN)).to_bits()
+}
+
+fn bf16_bits<T: Float>(value: T) -> u16 {
+    half::bf16::from_f64(value.to_f64().unwrap_or(f64::NAN)).to_bits()
+}
+
+/// Decodes weights in the safetensors format, written by [`encode`] or
+/// another tool. The tensors become the layout groups, in the order of their
+/// data, and `F16`, `BF16`, `F32` and `F64` values are converted to `T`.
+/// Files without oml metadata get new metadata.
+pub fn decode<T: Float>(bytes: &[u8]) -> Result<SavedModel<T>, ModelError> {
+    let invalid =
+        |message: &str| ModelError::InvalidInput(format!("invalid safetensors: {}", message));
+    let Some((length, rest)) = bytes.split_first_chunk::<8>() else {
+        return Err(invalid("missing header length"));
+    };
+    let length = u64::from_le_bytes(*length) as usize;
+    if length > MAX_HEADER_BYTES || length > rest.len() {
+        return Err(invalid("header length out of bounds"));
+    }
+    let (header, data) = rest.split_at(length);
+    let mut header: BTreeMap<String, Value> =
+        serde_json::from_slice(header).map_err(|e| invalid(&e.to_string()))?;
+    let metadata = header
+        .remove("__metadata__")
+        .and_then(|m| m.get(OML_METADATA)?.as_str().map(str::to_string))
+        .and_then(|m| serde_json::from_str::<ModelMetadata>(&m).ok());
+    let mut tensors = header
+        .into_iter()
+        .map(|(name, info)| Ok((name, serde_json::from_value::<TensorInfo>(info)?)))
+        .collect::<Result<Vec<_>, serde_json::Error>>()
+        .map_err(|e| invalid(&e.to_string()))?;
+    tensors.sort_by_key(|(_, info)| info.data_offsets[0]);
+
+    let bare =
+        matches!(&tensors[..], [(name, info)] if name == BARE_TENSOR && info.shape.len() == 1);
+    let mut layout = ModelLayout::new();
+    let mut parameters = Vec::new();
+    for (name, info) in tensors {
+        let size = dtype_size(&info.dtype).ok_or_else(|| {
+            ModelError::Unsupported(format!("{} tensors are not supported", info.dtype))
+        })?;
+        let [start, end] = info.data_offsets;
+        let count: usize = info.shape.iter().product();
+        if start > end || end > data.len() || end - start != count * size {
+            return Err(invalid(&format!("data of tensor {} out of bounds", name)));
+        }
+        parameters.extend(data[start..end].chunks_exact(size).map(|chunk| {
+            let value = match (info.dtype.as_str(), chunk) {
+                ("F16", &[a, b]) => half::f16::from_le_bytes([a, b]).to_f64(),
+                ("BF16", &[a, b]) => half::bf16::from_le_bytes([a, b]).to_f64(),
+                ("F32", &[a, b, c, d]) => f32::from_le_bytes([a, b, c, d]) as f64,
+                (_, chunk) => {
+                    let mut bytes = [0; 8];
+                    bytes.copy_from_slice(chunk);
+                    f64::from_le_bytes(bytes)
+                }
+            };
+            T::from(value).unwrap_or_else(T::nan)
+        }));
+        if !bare {
+            layout = layout.group(name, &info.shape);
+        }
+    }
+
+    let mut metadata = metadata.unwrap_or_else(|| ModelMetadata::new::<T>(parameters.len()));
+    metadata.scalar = std::any::type_name::<T>().to_string();
+    metadata.parameter_count = parameters.len();
+    Ok(SavedModel {
+        schema_version: MODEL_SCHEMA_VERSION,
+        metadata,
+        layout,
+        parameters,
+        sparse: None,
+        labels: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Model;
+
+    #[test]
+    fn test_round_trip_and_foreign_half_precision_file() {
+        let layout = ModelLayout::new()
+            .group("weights", &[2, 2])
+            .group("bias", &[2]);
+        let model = Model::with_layout(layout, vec![1.0f32, -2.0, 0.5, 3.0, 0.0, 0.25])
+            .unwrap()
+            .with_name("ctr");
+        let bytes = model.to_safetensors().unwrap();
+        assert_eq!(u64::from_le_bytes(bytes[..8].try_into().unwrap()) % 8, 0);
+        // f32 weights read back as f64 keep their values, groups and identity.
+        let restored = Model::<f64>::from_safetensors(&bytes).unwrap();
+        assert_eq!(restored.layout(), model.layout());
+        assert_eq!(restored.read_params().group("bias"), Some(&[0.0, 0.25][..]));
+        assert_eq!(restored.metadata().id, model.metadata().id);
+        assert_eq!(restored.name(), Some("ctr"));
+
+        // A file written elsewhere: bf16 and f16 tensors, no oml metadata.
+        let header = br#"{"b":{"dtype":"F16","shape":[1],"data_offsets":[4,6]},"w":{"dtype":"BF16","shape":[2],"data_offsets":[0,4]}}"#;
+        let mut foreign = (header.len() as u64).to_le_bytes().to_vec();
+        foreign.extend_from_slice(header);
+        foreign.extend_from_slice(&[0x80, 0x3f, 0x00, 0xc0]); // bf16 1.0, -2.0
+        foreign.extend_from_slice(&[0x00, 0x38]); // f16 0.5
+        let saved = decode::<f32>(&foreign).unwrap();
+        assert_eq!(saved.parameters, vec![1.0, -2.0, 0.5]);
+        assert_eq!(saved.layout.groups()[0].name, "w");
+
+        assert!(decode::<f32>(&foreign[..foreign.len() - 1]).is_err());
+        assert!(decode::<f32>(&[1, 2, 3]).is_err());
+        let sparse = Model::<f32>::sparse(8);
+        assert!(matches!(
+            sparse.to_safetensors(),
+            Err(ModelError::Unsupported(_))
+        ));
+    }
+}
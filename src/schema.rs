@@ -0,0 +1,279 @@
+use crate::errors::ModelError;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// Payload schemas by catalog model name, `None` for the serving model.
+pub type PayloadSchemas = Vec<(Option<String>, Arc<PayloadSchema>)>;
+
+/// How one field of a payload becomes model features.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FeatureEncoder {
+    /// A number, as is.
+    Float,
+    /// A whole number, as is; fractional numbers are rejected.
+    Integer,
+    /// A boolean, as 1 or 0.
+    Boolean,
+    /// A string among `categories`, one-hot encoded; other strings encode
+    /// as all zeros.
+    OneHot { categories: Vec<String> },
+    /// An array of strings among `categories`, multi-hot encoded; other
+    /// strings are ignored.
+    MultiHot { categories: Vec<String> },
+    /// A free-form string, one-hot encoded into `buckets` hashed buckets.
+    Hashed { buckets: usize },
+}
+
+impl FeatureEncoder {
+    /// Number of features the field encodes to.
+    pub fn width(&self) -> usize {
+        match self {
+            FeatureEncoder::Float | FeatureEncoder::Integer | FeatureEncoder::Boolean => 1,
+            FeatureEncoder::OneHot { categories } | FeatureEncoder::MultiHot { categories } => {
+                categories.len()
+            }
+            FeatureEncoder::Hashed { buckets } => *buckets,
+        }
+    }
+
+    fn encode(&self, name: &str, value: &Value, out: &mut Vec<f64>) -> Result<(), ModelError> {
+        let invalid = |expected: &str| {
+            ModelError::InvalidInput(format!(
+                "field {} must be {}, got {}",
+                name, expected, value
+            ))
+        };
+        let start = out.len();
+        out.resize(start + self.width(), 0.0);
+        let slots = &mut out[start..];
+        match self {
+            FeatureEncoder::Float => {
+                slots[0] = value.as_f64().ok_or_else(|| invalid("a number"))?
+            }
+            FeatureEncoder::Integer => {
+                let integer = value
+                    .as_i64()
+                    .map(|i| i as f64)
+                    .or_else(|| value.as_u64().map(|u| u as f64))
+                    .ok_or_else(|| invalid("an integer"))?;
+                slots[0] = integer;
+            }
+            FeatureEncoder::Boolean => {
+                slots[0] = match value.as_bool().ok_or_else(|| invalid("a boolean"))? {
+                    true => 1.0,
+                    false => 0.0,
+                }
+            }
+            FeatureEncoder::OneHot { categories } => {
+                let category = value.as_str().ok_or_else(|| invalid("a string"))?;
+                if let Some(i) = categories.iter().position(|c| c == category) {
+                    slots[i] = 1.0;
+                }
+            }
+            FeatureEncoder::MultiHot { categories } => {
+                let values = value
+                    .as_array()
+                    .ok_or_else(|| invalid("an array of strings"))?;
+                for value in values {
+                    let category = value
+                        .as_str()
+                        .ok_or_else(|| invalid("an array of strings"))?;
+                    if let Some(i) = categories.iter().position(|c| c == category) {
+                        slots[i] = 1.0;
+                    }
+                }
+            }
+            FeatureEncoder::Hashed { buckets } => {
+                let string = value.as_str().ok_or_else(|| invalid("a string"))?;
+                if *buckets > 0 {
+                    let mut hasher = DefaultHasher::new();
+                    string.hash(&mut hasher);
+                    slots[(hasher.finish() % *buckets as u64) as usize] = 1.0;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A named field of a [`PayloadSchema`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldSpec {
+    pub name: String,
+    #[serde(flatten)]
+    pub encoder: FeatureEncoder,
+    /// Absent or null values encode as all zeros instead of being rejected.
+    #[serde(default)]
+    pub optional: bool,
+}
+
+/// The fields an inference endpoint accepts as a JSON object, each of its
+/// own type (numbers, integers, booleans, strings or arrays of categories),
+/// and how they are encoded into the feature vector the model takes, in
+/// field order. Clients then send raw values rather than pre-encoding them.
+///
+/// Payloads that are not objects are passed to the algorithm as they are,
+/// so clients sending encoded features keep working.
+///
+/// # Examples
+///
+/// ```
+/// use oml::schema::{FeatureEncoder, PayloadSchema};
+/// use serde_json::json;
+///
+/// let schema = PayloadSchema::new()
+///     .field("age", FeatureEncoder::Integer)
+///     .field("member", FeatureEncoder::Boolean)
+///     .field("plan", FeatureEncoder::OneHot {
+///         categories: vec!["free".to_string(), "pro".to_string()],
+///     });
+/// let payload = json!({"age": 42, "member": true, "plan": "pro"});
+/// assert_eq!(schema.encode(&payload).unwrap(), vec![42.0, 1.0, 0.0, 1.0]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PayloadSchema {
+    pub fields: Vec<FieldSpec>,
+}
+
+impl PayloadSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a required field.
+    pub fn field(mut self, name: &str, encoder: FeatureEncoder) -> Self {
+        self.fields.push(FieldSpec {
+            name: name.to_string(),
+            encoder,
+            optional: false,
+        });
+        self
+    }
+
+    /// Appends a field that payloads may leave out.
+    pub fn optional_field(mut self, name: &str, encoder: FeatureEncoder) -> Self {
+        self.fields.push(FieldSpec {
+            name: name.to_string(),
+            encoder,
+            optional: true,
+        });
+        self
+    }
+
+    /// Number of features payloads encode to.
+    pub fn width(&self) -> usize {
+        self.fields.iter().map(|f| f.encoder.width()).sum()
+    }
+
+    /// Encodes the object `payload` into features. Fields the schema does
+    /// not list are rejected, so misspelled ones do not go unnoticed.
+    pub fn encode(&self, payload: &Value) -> Result<Vec<f64>, ModelError> {
+        let object: &Map<String, Value> = payload.as_object().ok_or_else(|| {
+            ModelError::InvalidInput("the payload must be a JSON object".to_string())
+        })?;
+        if let Some(unknown) = object
+            .keys()
+            .find(|key| !self.fields.iter().any(|f| &f.name == *key))
+        {
+            return Err(ModelError::InvalidInput(format!(
+                "unknown field {}",
+                unknown
+            )));
+        }
+        let mut features = Vec::with_capacity(self.width());
+        for field in &self.fields {
+            match object.get(&field.name).filter(|v| !v.is_null()) {
+                Some(value) => field.encoder.encode(&field.name, value, &mut features)?,
+                None if field.optional => {
+                    features.resize(features.len() + field.encoder.width(), 0.0)
+                }
+                None => {
+                    return Err(ModelError::InvalidInput(format!(
+                        "missing field {}",
+                        field.name
+                    )))
+                }
+            }
+        }
+        Ok(features)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bayesian::BayesianLinearRegression;
+    use crate::handlers::{AppState, PredictionEnvelope};
+    use crate::model::Model;
+    use crate::server::configure;
+    use actix_web::{http, test as actix_test, web, App};
+    use serde_json::json;
+
+    #[test]
+    fn test_schema_round_trips_and_rejects_bad_payloads() {
+        let json = json!({"fields": [
+            {"name": "tags", "type": "multi_hot", "categories": ["a", "b", "c"]},
+            {"name": "city", "type": "hashed", "buckets": 4, "optional": true},
+            {"name": "score", "type": "float"},
+        ]});
+        let schema: PayloadSchema = serde_json::from_value(json).unwrap();
+        assert_eq!(schema.width(), 8);
+        let features = schema
+            .encode(&json!({"tags": ["c", "a", "z"], "score": 0.5}))
+            .unwrap();
+        assert_eq!(features, vec![1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.5]);
+        let features = schema
+            .encode(&json!({"tags": [], "city": "Turin", "score": 1}))
+            .unwrap();
+        assert_eq!(features[3..7].iter().sum::<f64>(), 1.0);
+
+        assert!(schema.encode(&json!({"tags": []})).is_err());
+        assert!(schema.encode(&json!({"tags": "a", "score": 1})).is_err());
+        assert!(schema
+            .encode(&json!({"tags": [], "score": 1, "extra": 1}))
+            .is_err());
+        assert!(schema.encode(&json!([1.0])).is_err());
+        let integer = PayloadSchema::new().field("n", FeatureEncoder::Integer);
+        assert!(integer.encode(&json!({"n": 1.5})).is_err());
+    }
+
+    #[actix_rt::test]
+    async fn test_mixed_payload_encoded_for_inference() {
+        let schema = PayloadSchema::new()
+            .field("rooms", FeatureEncoder::Integer)
+            .field("garden", FeatureEncoder::Boolean);
+        let algorithm = BayesianLinearRegression::new(2, 1.0f64, 1.0);
+        let model = Model::with_layout(
+            algorithm.prior_model().layout().clone(),
+            vec![2.0, 3.0, 0.5],
+        )
+        .unwrap();
+        let state = AppState::new(model, algorithm).with_payload_schema(schema);
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(configure::<f64, BayesianLinearRegression<f64>>),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::post()
+            .uri("/inference")
+            .set_json(json!({"rooms": 4, "garden": true}))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let response: PredictionEnvelope<f64> = actix_test::read_body_json(resp).await;
+        assert_eq!(response.prediction, 11.5);
+
+        let req = actix_test::TestRequest::post()
+            .uri("/inference")
+            .set_json(json!({"rooms": "four", "garden": true}))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+    }
+}
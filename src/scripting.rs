@@ -0,0 +1,156 @@
+use crate::algorithm::Datum;
+use crate::errors::ModelError;
+use rhai::{Dynamic, Engine, Scope, AST};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+/// Point of the request pipeline a script is attached to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookStage {
+    /// Runs on every training and inference input before the algorithm,
+    /// with the input bound to `x`; evaluates to the input to use.
+    Preprocess,
+    /// Runs on every inference output after calibration, with the request
+    /// input bound to `x` and the output to `prediction`; evaluates to the
+    /// output to return.
+    Postprocess,
+}
+
+/// An attached script, as listed by `GET /scripts`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HookInfo {
+    pub stage: HookStage,
+    /// Incremented every time a script is attached to this stage.
+    pub version: u64,
+    pub source: String,
+}
+
+/// Operator-supplied Rhai scripts run around the algorithm.
+///
+/// Scripts are compiled once when attached and can be replaced at any time;
+/// requests already running keep the script they started with. Each run is
+/// bounded to `max_operations` so a faulty script cannot stall a worker.
+pub struct ScriptHooks {
+    engine: Engine,
+    scripts: RwLock<BTreeMap<HookStage, (Arc<AST>, HookInfo)>>,
+}
+
+impl ScriptHooks {
+    pub fn new(max_operations: u64) -> Self {
+        let mut engine = Engine::new();
+        engine.set_max_operations(max_operations);
+        ScriptHooks {
+            engine,
+            scripts: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Compiles `source` and attaches it to `stage`, replacing any earlier script.
+    pub fn set(&self, stage: HookStage, source: &str) -> Result<HookInfo, ModelError> {
+        let ast = self
+            .engine
+            .compile(source)
+            .map_err(|e| ModelError::InvalidInput(format!("script does not compile: {}", e)))?;
+        let mut scripts = self.scripts.write()?;
+        let info = HookInfo {
+            stage,
+            version: scripts.get(&stage).map_or(1, |(_, info)| info.version + 1),
+            source: source.to_string(),
+        };
+        scripts.insert(stage, (Arc::new(ast), info.clone()));
+        Ok(info)
+    }
+
+    /// Detaches the script of `stage`. Returns `false` if there was none.
+    pub fn remove(&self, stage: HookStage) -> Result<bool, ModelError> {
+        Ok(self.scripts.write()?.remove(&stage).is_some())
+    }
+
+    pub fn list(&self) -> Result<Vec<HookInfo>, ModelError> {
+        Ok(self
+            .scripts
+            .read()?
+            .values()
+            .map(|(_, info)| info.clone())
+            .collect())
+    }
+
+    fn script(&self, stage: HookStage) -> Result<Option<Arc<AST>>, ModelError> {
+        Ok(self.scripts.read()?.get(&stage).map(|(ast, _)| ast.clone()))
+    }
+
+    /// Transforms an algorithm input; the identity without a script.
+    pub fn preprocess<I: Datum>(&self, x: I) -> Result<I, ModelError> {
+        let Some(ast) = self.script(HookStage::Preprocess)? else {
+            return Ok(x);
+        };
+        let mut scope = Scope::new();
+        scope.push_dynamic("x", to_dynamic(&x)?);
+        self.run(HookStage::Preprocess, &ast, &mut scope)
+    }
+
+    /// Transforms the prediction served for `x`; the identity without a script.
+    pub fn postprocess<I: Datum, O: Datum>(&self, x: &I, prediction: O) -> Result<O, ModelError> {
+        let Some(ast) = self.script(HookStage::Postprocess)? else {
+            return Ok(prediction);
+        };
+        let mut scope = Scope::new();
+        scope.push_dynamic("x", to_dynamic(x)?);
+        scope.push_dynamic("prediction", to_dynamic(&prediction)?);
+        self.run(HookStage::Postprocess, &ast, &mut scope)
+    }
+
+    fn run<V: Datum>(
+        &self,
+        stage: HookStage,
+        ast: &AST,
+        scope: &mut Scope,
+    ) -> Result<V, ModelError> {
+        let failed = |e: &dyn std::fmt::Display| {
+            ModelError::InvalidInput(format!("{:?} script failed: {}", stage, e))
+        };
+        let result: Dynamic = self
+            .engine
+            .eval_ast_with_scope(scope, ast)
+            .map_err(|e| failed(&e))?;
+        // Rhai only has 64-bit numbers; going through JSON narrows them (and
+        // widens integer results) to whatever `V` expects.
+        let value: serde_json::Value =
+            rhai::serde::from_dynamic(&result).map_err(|e| failed(&e))?;
+        serde_json::from_value(value).map_err(|e| failed(&e))
+    }
+}
+
+fn to_dynamic<V: Serialize>(value: &V) -> Result<Dynamic, ModelError> {
+    rhai::serde::to_dynamic(value).map_err(|e| ModelError::InvalidInput(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripts_transform_inputs_and_predictions() {
+        let hooks = ScriptHooks::new(10_000);
+        assert_eq!(hooks.preprocess(vec![1.0f32, 2.0]).unwrap(), vec![1.0, 2.0]);
+
+        hooks
+            .set(HookStage::Preprocess, "x.map(|v| v * 2.0)")
+            .unwrap();
+        hooks
+            .set(
+                HookStage::Postprocess,
+                "if prediction > 0.5 { 1.0 } else { 0.0 }",
+            )
+            .unwrap();
+        assert_eq!(hooks.preprocess(vec![1.0f32, 2.0]).unwrap(), vec![2.0, 4.0]);
+        assert_eq!(hooks.postprocess(&vec![1.0f32], 0.7f32).unwrap(), 1.0);
+
+        assert!(hooks.set(HookStage::Preprocess, "x +").is_err());
+        assert_eq!(hooks.list().unwrap()[0].version, 1);
+        hooks.set(HookStage::Preprocess, "loop {}").unwrap();
+        assert!(hooks.preprocess(1.0f32).is_err());
+    }
+}
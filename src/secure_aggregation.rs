@@ -0,0 +1,259 @@
+use crate::errors::ModelError;
+use crate::model::Model;
+use num_traits::Float;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Debug;
+
+/// Fixed-point scale used to encode updates before masking (16 fractional bits).
+pub const FIXED_POINT_SCALE: f64 = 65536.0;
+
+fn encode(value: f64) -> u64 {
+    (value * FIXED_POINT_SCALE).round() as i64 as u64
+}
+
+fn decode(value: u64) -> f64 {
+    value as i64 as f64 / FIXED_POINT_SCALE
+}
+
+/// Client side of pairwise-masking secure aggregation.
+///
+/// Every pair of clients `(i, j)` shares a secret seed (in a real deployment
+/// derived through a key agreement). For each round both derive the same
+/// pseudo-random mask from it; the lower id adds it and the higher id
+/// subtracts it. Updates are encoded as 64-bit fixed point with wrapping
+/// arithmetic, so masks cancel exactly in the sum and the coordinator learns
+/// nothing but the aggregate.
+#[derive(Debug, Clone)]
+pub struct MaskingClient {
+    client_id: u32,
+    pair_seeds: BTreeMap<u32, u64>,
+}
+
+impl MaskingClient {
+    /// Creates a client holding one shared seed per peer client id.
+    pub fn new(client_id: u32, pair_seeds: BTreeMap<u32, u64>) -> Self {
+        MaskingClient {
+            client_id,
+            pair_seeds,
+        }
+    }
+
+    pub fn client_id(&self) -> u32 {
+        self.client_id
+    }
+
+    /// Encodes and masks `update` for aggregation round `round`.
+    pub fn mask(&self, round: u64, update: &[f64]) -> MaskedUpdate {
+        let mut values: Vec<u64> = update.iter().map(|v| encode(*v)).collect();
+        for (&peer, &seed) in &self.pair_seeds {
+            if peer == self.client_id {
+                continue;
+            }
+            let mut rng = ChaCha20Rng::seed_from_u64(seed);
+            rng.set_stream(round);
+            for value in values.iter_mut() {
+                let mask = rng.next_u64();
+                *value = if self.client_id < peer {
+                    value.wrapping_add(mask)
+                } else {
+                    value.wrapping_sub(mask)
+                };
+            }
+        }
+        MaskedUpdate {
+            client_id: self.client_id,
+            round,
+            values,
+        }
+    }
+}
+
+/// A client's masked contribution to one round.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaskedUpdate {
+    pub client_id: u32,
+    pub round: u64,
+    pub values: Vec<u64>,
+}
+
+/// Progress of the current aggregation round.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoundStatus {
+    pub round: u64,
+    /// Clients taking part in the round, whose masks must cancel.
+    pub clients: Vec<u32>,
+    pub received: usize,
+    pub expected: usize,
+    /// Set once every client submitted and the averaged update was applied.
+    pub completed: bool,
+}
+
+/// Coordinator side: collects masked updates and applies their mean.
+///
+/// Only the running wrapping sum is kept, so individual contributions are
+/// never stored. Every expected client must submit for the masks to cancel;
+/// client dropout is not supported by this scheme.
+#[derive(Debug)]
+pub struct SecureAggregator {
+    clients: BTreeSet<u32>,
+    round: u64,
+    submitted: BTreeSet<u32>,
+    sum: Vec<u64>,
+}
+
+impl SecureAggregator {
+    pub fn new(clients: impl IntoIterator<Item = u32>) -> Self {
+        SecureAggregator {
+            clients: clients.into_iter().collect(),
+            round: 0,
+            submitted: BTreeSet::new(),
+            sum: Vec::new(),
+        }
+    }
+
+    pub fn status(&self) -> RoundStatus {
+        RoundStatus {
+            round: self.round,
+            clients: self.clients.iter().copied().collect(),
+            received: self.submitted.len(),
+            expected: self.clients.len(),
+            completed: false,
+        }
+    }
+
+    /// Replaces the clients of the aggregation, e.g. by the live cluster
+    /// members, unless the current round already received updates: its
+    /// masks only cancel among the clients it started with. Returns whether
+    /// they were replaced.
+    pub fn set_clients(&mut self, clients: impl IntoIterator<Item = u32>) -> bool {
+        if !self.submitted.is_empty() {
+            return false;
+        }
+        self.clients = clients.into_iter().collect();
+        true
+    }
+
+    /// Adds a masked update to the current round; once the last expected
+    /// client submitted, the mean update is added to `model` and the next
+    /// round starts.
+    pub fn submit<T>(
+        &mut self,
+        model: &Model<T>,
+        update: &MaskedUpdate,
+    ) -> Result<RoundStatus, ModelError>
+    where
+        T: Float + Debug + Send + Sync,
+    {
+        if update.round != self.round {
+            return Err(ModelError::InvalidInput(format!(
+                "update is for round {}, current round is {}",
+                update.round, self.round
+            )));
+        }
+        if !self.clients.contains(&update.client_id) {
+            return Err(ModelError::InvalidInput(format!(
+                "client {} is not part of the aggregation",
+                update.client_id
+            )));
+        }
+        if self.submitted.contains(&update.client_id) {
+            return Err(ModelError::InvalidInput(format!(
+                "client {} already submitted for round {}",
+                update.client_id, self.round
+            )));
+        }
+        let dimension = model.read_params().len();
+        if update.values.len() != dimension {
+            return Err(ModelError::InvalidInput(format!(
+                "update has {} entries, model has {}",
+                update.values.len(),
+                dimension
+            )));
+        }
+
+        if self.sum.is_empty() {
+            self.sum = vec![0; dimension];
+        }
+        self.sum
+            .iter_mut()
+            .zip(update.values.iter())
+            .for_each(|(acc, v)| *acc = acc.wrapping_add(*v));
+        self.submitted.insert(update.client_id);
+
+        let mut status = self.status();
+        if self.submitted.len() == self.clients.len() {
+            let n = self.clients.len() as f64;
+            let mut params = model.write_params();
+            for (param, total) in params.iter_mut().zip(self.sum.iter()) {
+                *param = *param + T::from(decode(*total) / n).unwrap_or_else(T::zero);
+            }
+            self.round += 1;
+            self.submitted.clear();
+            self.sum.clear();
+            status.completed = true;
+        }
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clients(ids: &[u32]) -> Vec<MaskingClient> {
+        // Seed for pair (i, j) known to both i and j only.
+        let seed = |a: u32, b: u32| (a.min(b) as u64) << 32 | b.max(a) as u64;
+        ids.iter()
+            .map(|&i| {
+                let seeds = ids
+                    .iter()
+                    .filter(|&&j| j != i)
+                    .map(|&j| (j, seed(i, j)))
+                    .collect();
+                MaskingClient::new(i, seeds)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_masks_cancel_in_the_sum() {
+        let model = Model::with_parameters(vec![0.0f64, 0.0]);
+        let mut aggregator = SecureAggregator::new([1, 2, 3]);
+        let updates = [vec![1.0, -3.0], vec![2.0, 0.5], vec![0.0, 1.0]];
+
+        let mut status = None;
+        for (client, update) in clients(&[1, 2, 3]).iter().zip(updates.iter()) {
+            let masked = client.mask(0, update);
+            // Individual masked values reveal nothing recognizable.
+            assert_ne!(decode(masked.values[0]), update[0]);
+            status = Some(aggregator.submit(&model, &masked).unwrap());
+        }
+
+        assert!(status.unwrap().completed);
+        let params = model.read_params().clone();
+        assert!((params[0] - 1.0).abs() < 1e-4);
+        assert!((params[1] + 0.5).abs() < 1e-4);
+        assert_eq!(aggregator.status().round, 1);
+    }
+
+    #[test]
+    fn test_rejects_duplicate_and_foreign_submissions() {
+        let model = Model::with_parameters(vec![0.0f64]);
+        let mut aggregator = SecureAggregator::new([1, 2]);
+        let client = &clients(&[1, 2])[0];
+        aggregator.submit(&model, &client.mask(0, &[1.0])).unwrap();
+        assert!(aggregator.submit(&model, &client.mask(0, &[1.0])).is_err());
+        assert!(aggregator.submit(&model, &client.mask(1, &[1.0])).is_err());
+
+        // The clients of a started round stay as they are.
+        assert!(!aggregator.set_clients([1]));
+        assert_eq!(aggregator.status().clients, vec![1, 2]);
+        let other = &clients(&[1, 2])[1];
+        aggregator.submit(&model, &other.mask(0, &[1.0])).unwrap();
+        assert!(aggregator.set_clients([1]));
+        assert_eq!(aggregator.status().clients, vec![1]);
+    }
+}
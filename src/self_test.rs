@@ -0,0 +1,270 @@
+use crate::algorithm::{Algorithm, Datum};
+use crate::model::Model;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// A known input and the range its prediction must fall in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Probe<I> {
+    pub name: String,
+    pub input: I,
+    /// Smallest acceptable prediction, compared by its numeric view.
+    #[serde(default)]
+    pub min: Option<f64>,
+    /// Largest acceptable prediction.
+    #[serde(default)]
+    pub max: Option<f64>,
+}
+
+impl<I> Probe<I> {
+    /// A probe that only checks that `input` gets a finite prediction.
+    pub fn new(name: impl Into<String>, input: I) -> Self {
+        Probe {
+            name: name.into(),
+            input,
+            min: None,
+            max: None,
+        }
+    }
+
+    /// Requires the prediction to lie within `[min, max]`.
+    pub fn with_range(mut self, min: f64, max: f64) -> Self {
+        self.min = Some(min);
+        self.max = Some(max);
+        self
+    }
+}
+
+/// Validation probes run by a [`SelfTest`].
+#[derive(Debug, Clone)]
+pub struct SelfTestConfig<I> {
+    pub probes: Vec<Probe<I>>,
+    /// Longest an inference step on a probe may take.
+    pub latency_budget: Option<Duration>,
+    /// Whether a NaN or infinite parameter fails the test.
+    pub check_parameters: bool,
+}
+
+impl<I> SelfTestConfig<I> {
+    pub fn new(probes: Vec<Probe<I>>) -> Self {
+        SelfTestConfig {
+            probes,
+            latency_budget: None,
+            check_parameters: true,
+        }
+    }
+
+    pub fn with_latency_budget(mut self, budget: Duration) -> Self {
+        self.latency_budget = Some(budget);
+        self
+    }
+}
+
+/// How one probe fared.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProbeOutcome {
+    pub name: String,
+    pub passed: bool,
+    /// The prediction, if the inference step succeeded.
+    pub prediction: Option<serde_json::Value>,
+    pub latency_ms: f64,
+    /// Why the probe failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<String>,
+}
+
+/// Result of a self-test run, returned by `POST /admin/selftest` and, while
+/// it failed, by `GET /ready`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SelfTestReport {
+    pub passed: bool,
+    /// Milliseconds since the Unix epoch.
+    pub ran_at_ms: u64,
+    /// Version of the parameters tested.
+    pub parameters_version: u64,
+    /// Dense parameters that are NaN or infinite.
+    pub non_finite_parameters: usize,
+    pub probes: Vec<ProbeOutcome>,
+}
+
+/// Validates the serving model before it reports ready: every probe's input
+/// must get a finite prediction within its range and the latency budget,
+/// and (by default) every parameter must be finite.
+///
+/// The server runs the test when it is built and on `POST /admin/selftest`
+/// (`AppState::with_self_test`); until a run passes, `GET /ready` answers
+/// `503`, so orchestrators keep traffic away from a model that loaded
+/// corrupted or incompatible weights.
+///
+/// # Examples
+///
+/// ```
+/// use oml::model::Model;
+/// use oml::self_test::{Probe, SelfTest, SelfTestConfig};
+/// use oml::test::ScalingAlgorithm;
+///
+/// let probes = vec![Probe::new("unit", 1.0).with_range(0.0, 10.0)];
+/// let self_test = SelfTest::new(SelfTestConfig::new(probes));
+/// let model = Model::with_parameters(vec![2.0f64, 3.0]);
+/// assert!(self_test.run(&model, &ScalingAlgorithm).passed);
+/// model.update_params(|params| params[0] = f64::NAN);
+/// assert!(!self_test.run(&model, &ScalingAlgorithm).passed);
+/// assert!(!self_test.is_ready());
+/// ```
+#[derive(Debug)]
+pub struct SelfTest<I> {
+    config: SelfTestConfig<I>,
+    last: Mutex<Option<SelfTestReport>>,
+}
+
+impl<I: Datum> SelfTest<I> {
+    pub fn new(config: SelfTestConfig<I>) -> Self {
+        SelfTest {
+            config,
+            last: Mutex::new(None),
+        }
+    }
+
+    pub fn config(&self) -> &SelfTestConfig<I> {
+        &self.config
+    }
+
+    /// Runs every probe through `algorithm` on `model` and keeps the report.
+    pub fn run<T, A>(&self, model: &Model<T>, algorithm: &A) -> SelfTestReport
+    where
+        T: Float + Debug + Send + Sync + Sum,
+        A: Algorithm<T, Input = I>,
+    {
+        let non_finite_parameters = match self.config.check_parameters {
+            true => model
+                .read_params()
+                .iter()
+                .filter(|p| !p.is_finite())
+                .count(),
+            false => 0,
+        };
+        let probes: Vec<ProbeOutcome> = self
+            .config
+            .probes
+            .iter()
+            .map(|probe| self.probe(model, algorithm, probe))
+            .collect();
+        let report = SelfTestReport {
+            passed: non_finite_parameters == 0 && probes.iter().all(|p| p.passed),
+            ran_at_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            parameters_version: model.version(),
+            non_finite_parameters,
+            probes,
+        };
+        *self.last.lock().unwrap_or_else(PoisonError::into_inner) = Some(report.clone());
+        report
+    }
+
+    fn probe<T, A>(&self, model: &Model<T>, algorithm: &A, probe: &Probe<I>) -> ProbeOutcome
+    where
+        T: Float + Debug + Send + Sync + Sum,
+        A: Algorithm<T, Input = I>,
+    {
+        let started = Instant::now();
+        let result = algorithm.inference_step(model, probe.input.clone());
+        let latency = started.elapsed();
+        let over_budget = self
+            .config
+            .latency_budget
+            .filter(|budget| latency > *budget)
+            .map(|budget| format!("took {:?}, over the {:?} budget", latency, budget));
+        let failure = match &result {
+            Err(e) => Some(format!("inference failed: {}", e)),
+            Ok(output) => match output.score() {
+                Some(score) if !score.is_finite() => Some(format!("prediction is {}", score)),
+                Some(score) => match (
+                    probe.min.filter(|min| score < *min),
+                    probe.max.filter(|max| score > *max),
+                ) {
+                    (Some(min), _) => Some(format!("prediction {} is below {}", score, min)),
+                    (_, Some(max)) => Some(format!("prediction {} is above {}", score, max)),
+                    _ => over_budget,
+                },
+                None if probe.min.is_some() || probe.max.is_some() => {
+                    Some("prediction has no numeric value to compare".to_string())
+                }
+                None => over_budget,
+            },
+        };
+        ProbeOutcome {
+            name: probe.name.clone(),
+            passed: failure.is_none(),
+            prediction: result.ok().and_then(|o| serde_json::to_value(o).ok()),
+            latency_ms: latency.as_secs_f64() * 1000.0,
+            failure,
+        }
+    }
+
+    /// Report of the last run, if any.
+    pub fn last_report(&self) -> Option<SelfTestReport> {
+        self.last
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Whether the last run passed; `false` before the first one.
+    pub fn is_ready(&self) -> bool {
+        self.last_report().is_some_and(|report| report.passed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::handlers::AppState;
+    use crate::model::Model;
+    use crate::self_test::{Probe, SelfTestConfig, SelfTestReport};
+    use crate::server::ServerBuilder;
+    use crate::test::ScalingAlgorithm;
+    use std::time::Duration;
+
+    #[actix_rt::test]
+    async fn test_failing_probe_keeps_server_unready_until_fixed() {
+        let config = SelfTestConfig::new(vec![
+            Probe::new("unit", 1.0).with_range(0.0, 5.0),
+            Probe::new("large", 100.0),
+        ])
+        .with_latency_budget(Duration::from_secs(5));
+        // sum(params) * 1.0 = 6 is out of range.
+        let state = AppState::new(Model::with_parameters(vec![2.0f64, 4.0]), ScalingAlgorithm)
+            .with_self_test(config);
+        let server = ServerBuilder::new()
+            .bind("127.0.0.1:0")
+            .state(state)
+            .build()
+            .unwrap();
+        let state = server.state().clone();
+        let handle = server.start().unwrap();
+        let base = format!("http://{}", handle.addrs()[0]);
+        let client = reqwest::Client::new();
+
+        let ready = client.get(format!("{}/ready", base)).send().await.unwrap();
+        assert_eq!(ready.status(), 503);
+        let report: SelfTestReport = ready.json().await.unwrap();
+        assert!(!report.probes[0].passed && report.probes[1].passed);
+        assert_eq!(state.metrics.gauge("self_test_passed"), Some(0.0));
+
+        state.model.update_params(|params| params[1] = 1.0);
+        let rerun = client
+            .post(format!("{}/admin/selftest", base))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(rerun.status(), 200);
+        let ready = client.get(format!("{}/ready", base)).send().await.unwrap();
+        assert_eq!(ready.status(), 200);
+        handle.stop(true).await.unwrap();
+    }
+}
@@ -1,32 +1,928 @@
 use crate::algorithm::Algorithm;
+use crate::auth::AuthConfig;
+use crate::bootstrap::ModelSource;
+use crate::checkpoint::{CheckpointConfig, Checkpointer};
+use crate::encryption::EncryptionKey;
+use crate::handlers::algorithms::{
+    handle_list_algorithms, handle_named_inference, handle_named_training,
+};
+use crate::handlers::archive::{handle_export, handle_import};
+use crate::handlers::canary::{handle_abort_canary, handle_canary_status};
+use crate::handlers::catalog::{
+    handle_archive_model, handle_archived_models, handle_create_model, handle_delete_model,
+    handle_fork_model, handle_list_models, handle_model_inference, handle_model_lineage,
+    handle_model_training, handle_model_versions, handle_restore_model, handle_snapshot_model,
+};
+use crate::handlers::challenger::{handle_challenger_report, handle_promote_challenger};
+use crate::handlers::checkpoint::handle_checkpoint;
+use crate::handlers::cluster::{handle_cluster_state, handle_heartbeat};
+use crate::handlers::consistency::{handle_consistency_clock, handle_versioned_delta};
+use crate::handlers::constraints::{handle_constraints_status, handle_set_constraints};
+use crate::handlers::dry_run::handle_dry_run;
+use crate::handlers::ensemble::{
+    handle_define_ensemble, handle_delete_ensemble, handle_ensemble_feedback,
+    handle_ensemble_inference, handle_list_ensembles,
+};
+use crate::handlers::evaluation::handle_evaluate;
+use crate::handlers::event_time::{handle_reset_watermark, handle_watermark};
+use crate::handlers::federation::{handle_federation_status, handle_masked_update};
+use crate::handlers::feedback::{handle_feedback, handle_outcome};
+use crate::handlers::freeze::{handle_freeze_status, handle_set_freeze};
+use crate::handlers::gossip::handle_gossip_exchange;
+use crate::handlers::guard::{handle_guard_status, handle_resume_training};
+use crate::handlers::holdout::{handle_holdout_history, handle_set_holdout};
+use crate::handlers::jobs::{
+    handle_job_status, handle_list_jobs, handle_register_worker, handle_submit_job,
+    handle_train_shard,
+};
+use crate::handlers::parameter_server::{handle_ps_pull, handle_ps_push};
+use crate::handlers::pipeline::{handle_batch_training, MAX_BATCH_BYTES};
+use crate::handlers::quarantine::handle_quarantine;
+use crate::handlers::reload::handle_reload;
+use crate::handlers::replication::{handle_replication_events, handle_replication_snapshot};
+#[cfg(feature = "scripting")]
+use crate::handlers::scripts::{handle_list_scripts, handle_remove_script, handle_set_script};
+use crate::handlers::self_test::{handle_ready, handle_self_test};
+use crate::handlers::tuning::{handle_tuning_sample, handle_tuning_status};
+use crate::handlers::versions::{
+    handle_download_weights, handle_model_delta, handle_model_info, handle_model_labels,
+    handle_model_priors, handle_model_stats, handle_model_version, handle_replace_weights,
+    handle_reset, handle_rollback, handle_snapshot_diff, MAX_WEIGHTS_BYTES,
+};
 use crate::handlers::AppState;
-use crate::handlers::{handle_inference_step, handle_training_step};
-use crate::model::Model;
-use actix_web::{web, App, HttpServer};
+use crate::handlers::{handle_inference_step, handle_metrics, handle_training_step};
+use crate::metrics::Metrics;
+use crate::middleware::{OmlMiddleware, SharedMiddleware};
+use crate::model::{Model, SavedModel};
+use crate::replication::Replication;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::header::WWW_AUTHENTICATE;
+use actix_web::{web, App, HttpResponse, HttpServer};
 use num_traits::Float;
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
+use std::future::Future;
 use std::iter::Sum;
+use std::net::{SocketAddr, TcpListener};
+use std::path::PathBuf;
+use std::pin::Pin;
 use std::sync::Arc;
 
-// Starts an Actix web server with endpoints for inference and training steps.
-pub async fn run_server<T, A>(address: &str, model: Model<T>, algorithm: A) -> std::io::Result<()>
+/// Fluent configuration of an oml server.
+///
+/// Either pass a model and an algorithm, or a fully configured [`AppState`]
+/// via [`ServerBuilder::state`]. [`ServerBuilder::build`] binds the listeners;
+/// nothing runs until [`Server::start`].
+pub struct ServerBuilder<T, A>
 where
     T: Float + Serialize + for<'de> Deserialize<'de> + 'static + Debug + Send + Sync + Sum,
     A: Algorithm<T> + 'static + Send + Sync,
 {
-    let shared_state = web::Data::new(AppState {
-        model: Arc::new(model),
-        algorithm: Arc::new(algorithm),
-    });
-
-    HttpServer::new(move || {
-        App::new()
-            .app_data(shared_state.clone())
-            .route("/inference", web::post().to(handle_inference_step::<T, A>))
-            .route("/training", web::post().to(handle_training_step::<T, A>))
-    })
-    .bind(address)?
-    .run()
-    .await
+    addresses: Vec<String>,
+    model: Option<Model<T>>,
+    algorithm: Option<A>,
+    state: Option<AppState<T, A>>,
+    metrics: Option<Arc<Metrics>>,
+    auth: Option<AuthConfig>,
+    checkpointing: Option<CheckpointConfig>,
+    config_file: Option<PathBuf>,
+    model_file: Option<PathBuf>,
+    model_url: Option<ModelSource>,
+    encryption: Option<EncryptionKey>,
+    routes: Vec<fn(&mut web::ServiceConfig)>,
+    middleware: Vec<SharedMiddleware<A::Input, A::Output>>,
+}
+
+impl<T, A> Default for ServerBuilder<T, A>
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + 'static + Debug + Send + Sync + Sum,
+    A: Algorithm<T> + 'static + Send + Sync,
+{
+    fn default() -> Self {
+        ServerBuilder {
+            addresses: Vec::new(),
+            model: None,
+            algorithm: None,
+            state: None,
+            metrics: None,
+            auth: None,
+            checkpointing: None,
+            config_file: None,
+            model_file: None,
+            model_url: None,
+            encryption: None,
+            routes: Vec::new(),
+            middleware: Vec::new(),
+        }
+    }
+}
+
+impl<T, A> ServerBuilder<T, A>
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + 'static + Debug + Send + Sync + Sum,
+    A: Algorithm<T> + 'static + Send + Sync,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a listen address; may be called several times. Defaults to
+    /// `127.0.0.1:8080`. Port 0 picks a free port, see [`ServerHandle::addrs`].
+    pub fn bind(mut self, address: impl Into<String>) -> Self {
+        self.addresses.push(address.into());
+        self
+    }
+
+    pub fn model(mut self, model: Model<T>) -> Self {
+        self.model = Some(model);
+        self
+    }
+
+    pub fn algorithm(mut self, algorithm: A) -> Self {
+        self.algorithm = Some(algorithm);
+        self
+    }
+
+    /// Serves a preconfigured state instead of a bare model and algorithm.
+    pub fn state(mut self, state: AppState<T, A>) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Rejects requests without an accepted bearer token with 401, and has
+    /// the state's peer clients send the peer token (see [`AuthConfig`]).
+    pub fn with_auth(mut self, auth: AuthConfig) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// Restores the parameters from the latest checkpoint on build, then
+    /// saves them periodically and once more on stop.
+    pub fn with_checkpointing(mut self, config: CheckpointConfig) -> Self {
+        self.checkpointing = Some(config);
+        self
+    }
+
+    /// Loads the parameters saved at `path` (see [`Model::save`]) on build,
+    /// if the file exists, and saves them there again on stop.
+    pub fn with_model_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.model_file = Some(path.into());
+        self
+    }
+
+    /// Downloads the weights at `source` on build, checks their checksum and
+    /// loads them before the ports are bound, to warm-start a fresh
+    /// replica. The model file and the checkpoints, if any, are restored
+    /// after them and take precedence.
+    pub fn with_model_url(mut self, source: ModelSource) -> Self {
+        self.model_url = Some(source);
+        self
+    }
+
+    /// Encrypts the model file and the checkpoints written to disk with
+    /// `key` (see [`EncryptionKey`]), unless the checkpoint configuration
+    /// has a key of its own. Plain files are still read.
+    pub fn with_encryption(mut self, key: EncryptionKey) -> Self {
+        self.encryption = Some(key);
+        self
+    }
+
+    /// Applies the runtime settings of a JSON file on build and re-reads it
+    /// on SIGHUP or `POST /admin/reload`.
+    pub fn with_config_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config_file = Some(path.into());
+        self
+    }
+
+    /// Records into an existing registry, e.g. one shared with the embedding
+    /// application.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Registers request/response hooks, run after those of a preconfigured state.
+    pub fn with_middleware(
+        mut self,
+        middleware: impl OmlMiddleware<A::Input, A::Output> + 'static,
+    ) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Registers further routes next to the oml ones, e.g.
+    /// `configure_plugins` for a server running a WASM plugin.
+    pub fn configure(mut self, routes: fn(&mut web::ServiceConfig)) -> Self {
+        self.routes.push(routes);
+        self
+    }
+
+    /// Assembles the state, restores the checkpoint, runs the self-test, if
+    /// any, and binds the listeners.
+    pub fn build(self) -> std::io::Result<Server<T, A>> {
+        let invalid =
+            |message: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, message);
+        let mut state = match (self.state, self.model, self.algorithm) {
+            (Some(state), None, None) => state,
+            (None, Some(model), Some(algorithm)) => AppState::new(model, algorithm),
+            (Some(_), _, _) => {
+                return Err(invalid(
+                    "a preconfigured state already holds the model and algorithm".to_string(),
+                ))
+            }
+            _ => return Err(invalid("a model and an algorithm are required".to_string())),
+        };
+        if let Some(metrics) = self.metrics {
+            state.metrics = metrics;
+        }
+        state.middleware.extend(self.middleware);
+        if let Some(token) = self.auth.as_ref().and_then(AuthConfig::peer_token) {
+            state.set_peer_token(token);
+        }
+        if let Some(source) = &self.model_url {
+            let saved = source
+                .fetch(self.encryption.as_ref())
+                .map_err(|e| invalid(format!("{}: {}", source.url, e)))?;
+            load_parameters(&state.model, &source.url, saved.parameters)?;
+            state.metrics.increment("model_bootstraps");
+        }
+        if let Some(path) = &self.model_file {
+            if path.exists() {
+                let saved = match &self.encryption {
+                    Some(key) => SavedModel::read_encrypted(path, key),
+                    None => SavedModel::read(path),
+                }
+                .map_err(|e| invalid(e.to_string()))?;
+                load_parameters(&state.model, &path.display().to_string(), saved.parameters)?;
+            }
+        }
+        if let Some(personal) = &state.personalization {
+            if let Some(path) = personal.config().file.as_ref().filter(|p| p.exists()) {
+                personal.load(path).map_err(|e| invalid(e.to_string()))?;
+            }
+        }
+        if let Some(mut config) = self.checkpointing {
+            if config.encryption.is_none() {
+                config.encryption = self.encryption.clone();
+            }
+            let checkpointer = Checkpointer::new(config).map_err(|e| invalid(e.to_string()))?;
+            checkpointer
+                .restore(&state.model)
+                .map_err(|e| invalid(e.to_string()))?;
+            if checkpointer.log().is_some() {
+                state.metrics.set_gauge(
+                    "wal_replayed_entries",
+                    checkpointer.replayed_entries() as f64,
+                );
+            }
+            state.checkpointer = Some(Arc::new(checkpointer));
+        }
+        if let Some(path) = self.config_file {
+            state.config_file = Some(path);
+            state
+                .reload_from_file()
+                .map_err(|e| invalid(e.to_string()))?;
+        }
+        // A failing model is still served, but does not report ready.
+        state.run_self_test();
+
+        let addresses = if self.addresses.is_empty() {
+            vec!["127.0.0.1:8080".to_string()]
+        } else {
+            self.addresses
+        };
+        let listeners = addresses
+            .iter()
+            .map(TcpListener::bind)
+            .collect::<std::io::Result<Vec<_>>>()?;
+        Ok(Server {
+            state: web::Data::new(state),
+            listeners,
+            auth: self.auth.map(Arc::new),
+            routes: self.routes,
+            model_file: self.model_file,
+            encryption: self.encryption,
+        })
+    }
+}
+
+/// A configured server with bound listeners, not yet accepting requests.
+pub struct Server<T, A>
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + 'static + Debug + Send + Sync + Sum,
+    A: Algorithm<T> + 'static + Send + Sync,
+{
+    state: web::Data<AppState<T, A>>,
+    listeners: Vec<TcpListener>,
+    auth: Option<Arc<AuthConfig>>,
+    routes: Vec<fn(&mut web::ServiceConfig)>,
+    model_file: Option<PathBuf>,
+    encryption: Option<EncryptionKey>,
+}
+
+impl<T, A> Server<T, A>
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + 'static + Debug + Send + Sync + Sum,
+    A: Algorithm<T> + 'static + Send + Sync,
+{
+    pub fn state(&self) -> &web::Data<AppState<T, A>> {
+        &self.state
+    }
+
+    /// Starts the background loops and the HTTP server on the current runtime.
+    pub fn start(self) -> std::io::Result<ServerHandle> {
+        let shared_state = self.state;
+        let mut tasks = Vec::new();
+        if let Some(membership) = &shared_state.membership {
+            if let Some(gossip) = &shared_state.gossip {
+                gossip.set_membership(membership.clone());
+            }
+            if let Some(orchestrator) = &shared_state.orchestrator {
+                orchestrator.set_membership(membership.clone());
+            }
+            if let Some(Replication::Follower(follower)) = shared_state.replication.as_deref() {
+                follower.set_membership(membership.clone());
+            }
+            tasks.push(membership.clone().spawn(shared_state.metrics.clone()));
+        }
+        if let Some(gossip) = &shared_state.gossip {
+            tasks.push(
+                gossip
+                    .clone()
+                    .spawn(shared_state.model.clone(), shared_state.metrics.clone()),
+            );
+        }
+        // Local deltas go to the gossip peers, or else the live members.
+        if let Some(consistency) = &shared_state.consistency {
+            let gossip = shared_state.gossip.clone();
+            let membership = shared_state.membership.clone();
+            if gossip.is_some() || membership.is_some() {
+                let peers = move || match (&gossip, &membership) {
+                    (Some(gossip), _) => gossip.peers(),
+                    (None, Some(membership)) => membership.live_peers(),
+                    (None, None) => Vec::new(),
+                };
+                tasks.push(
+                    consistency
+                        .clone()
+                        .spawn(peers, shared_state.metrics.clone()),
+                );
+            }
+        }
+        if let Some(shared) = &shared_state.shared_state {
+            tasks.push(
+                shared
+                    .clone()
+                    .spawn(shared_state.model.clone(), shared_state.metrics.clone()),
+            );
+        }
+        if let Some(holdout) = &shared_state.holdout {
+            let state = shared_state.clone();
+            tasks.push(holdout.clone().spawn(
+                shared_state.model.clone(),
+                shared_state.algorithm.clone(),
+                shared_state.metrics.clone(),
+                move |record| state.observe_evaluation(&record.report),
+            ));
+        }
+        #[cfg(feature = "grpc")]
+        if let Some((sync, address)) = &shared_state.parameter_sync {
+            tasks.push(sync.clone().spawn(*address, shared_state.metrics.clone()));
+        }
+        if let Some(Replication::Follower(follower)) = shared_state.replication.as_deref() {
+            tasks.push(follower.clone().spawn(
+                shared_state.model.clone(),
+                shared_state.algorithm.clone(),
+                shared_state.metrics.clone(),
+            ));
+        }
+        for (name, decay) in &shared_state.decay {
+            let state = shared_state.clone();
+            let personalization = match name {
+                Some(_) => None,
+                None => shared_state.personalization.clone(),
+            };
+            let name = name.clone();
+            let model = move || match &name {
+                Some(name) => state
+                    .catalog
+                    .as_ref()
+                    .and_then(|c| c.resolve(name, None).ok().flatten())
+                    .map(|(model, _)| model),
+                None => Some(state.model.clone()),
+            };
+            tasks.push(
+                decay
+                    .clone()
+                    .spawn(model, personalization, shared_state.metrics.clone()),
+            );
+        }
+        #[cfg(unix)]
+        if shared_state.config_file.is_some() {
+            let state = shared_state.clone();
+            tasks.push(tokio::spawn(async move {
+                use tokio::signal::unix::{signal, SignalKind};
+                let Ok(mut hangups) = signal(SignalKind::hangup()) else {
+                    return;
+                };
+                while hangups.recv().await.is_some() {
+                    let state = state.clone();
+                    let _ = tokio::task::spawn_blocking(move || state.reload_from_file()).await;
+                }
+            }));
+        }
+        let mut on_stop: Vec<Box<dyn FnOnce() + Send>> = Vec::new();
+        if let Some(checkpointer) = shared_state.checkpointer.clone() {
+            tasks.push(
+                checkpointer
+                    .clone()
+                    .spawn(shared_state.model.clone(), shared_state.metrics.clone()),
+            );
+            let model = shared_state.model.clone();
+            on_stop.push(Box::new(move || {
+                let _ = checkpointer.save(&model);
+            }));
+        }
+        if let Some(personal) = shared_state.personalization.clone() {
+            if let Some(path) = personal.config().file.clone() {
+                on_stop.push(Box::new(move || {
+                    let _ = personal.save(path);
+                }));
+            }
+        }
+        if let Some(recorder) = shared_state.recorder.clone() {
+            on_stop.push(Box::new(move || {
+                let _ = recorder.flush();
+            }));
+        }
+        if let Some(log) = shared_state.prediction_log.clone() {
+            on_stop.push(Box::new(move || {
+                let _ = log.flush();
+            }));
+        }
+        if let Some(path) = self.model_file {
+            let model = shared_state.model.clone();
+            let encryption = self.encryption;
+            on_stop.push(Box::new(move || {
+                let _ = match &encryption {
+                    Some(key) => model.save_encrypted(path, key),
+                    None => model.save(path),
+                };
+            }));
+        }
+
+        let auth = self.auth;
+        let routes = self.routes;
+        let app_state = shared_state.clone();
+        let mut server = HttpServer::new(move || {
+            let auth = auth.clone();
+            let app = App::new()
+                .app_data(app_state.clone())
+                .wrap_fn(move |req, srv| -> BoxedResponse {
+                    match &auth {
+                        Some(auth) if !auth.authorizes(req.request()) => {
+                            let response = HttpResponse::Unauthorized()
+                                .insert_header((WWW_AUTHENTICATE, "Bearer"))
+                                .finish();
+                            Box::pin(std::future::ready(Ok(req.into_response(response))))
+                        }
+                        _ => Box::pin(srv.call(req)),
+                    }
+                })
+                .configure(configure::<T, A>);
+            routes
+                .iter()
+                .fold(app, |app, routes| app.configure(*routes))
+        });
+        for listener in self.listeners {
+            server = server.listen(listener)?;
+        }
+        let addrs = server.addrs();
+        let server = server.run();
+        let handle = server.handle();
+        Ok(ServerHandle {
+            addrs,
+            server: handle,
+            running: tokio::spawn(server),
+            tasks,
+            on_stop,
+        })
+    }
+
+    /// Starts the server and waits until it stops.
+    pub async fn run(self) -> std::io::Result<()> {
+        self.start()?.wait().await
+    }
+}
+
+type BoxedResponse = Pin<Box<dyn Future<Output = Result<ServiceResponse, actix_web::Error>>>>;
+
+/// Handle to a running server.
+pub struct ServerHandle {
+    addrs: Vec<SocketAddr>,
+    server: actix_web::dev::ServerHandle,
+    running: tokio::task::JoinHandle<std::io::Result<()>>,
+    tasks: Vec<tokio::task::JoinHandle<()>>,
+    /// Final checkpoint and model file writes.
+    on_stop: Vec<Box<dyn FnOnce() + Send>>,
+}
+
+impl ServerHandle {
+    /// Addresses the server listens on.
+    pub fn addrs(&self) -> &[SocketAddr] {
+        &self.addrs
+    }
+
+    /// Stops accepting requests (letting in-flight ones finish if
+    /// `graceful`), stops the background loops and writes a last checkpoint
+    /// and the model file, if configured.
+    pub async fn stop(self, graceful: bool) -> std::io::Result<()> {
+        self.server.stop(graceful).await;
+        self.wait().await
+    }
+
+    /// Waits until the server stops, e.g. on SIGINT, then shuts down like
+    /// [`ServerHandle::stop`].
+    pub async fn wait(self) -> std::io::Result<()> {
+        let result = self.running.await.map_err(std::io::Error::other);
+        for task in &self.tasks {
+            task.abort();
+        }
+        for on_stop in self.on_stop {
+            on_stop();
+        }
+        result?
+    }
+}
+
+/// Registers every oml route on an actix `App`.
+pub fn configure<T, A>(cfg: &mut web::ServiceConfig)
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + 'static + Debug + Send + Sync + Sum,
+    A: Algorithm<T> + 'static + Send + Sync,
+{
+    cfg.route("/inference", web::post().to(handle_inference_step::<T, A>))
+        .route("/admin/reload", web::post().to(handle_reload::<T, A>))
+        .route(
+            "/admin/checkpoint",
+            web::post().to(handle_checkpoint::<T, A>),
+        )
+        .route("/admin/rollback", web::post().to(handle_rollback::<T, A>))
+        .route("/admin/selftest", web::post().to(handle_self_test::<T, A>))
+        .route("/ready", web::get().to(handle_ready::<T, A>))
+        .route("/admin/reset", web::post().to(handle_reset::<T, A>))
+        .route("/admin/export", web::get().to(handle_export::<T, A>))
+        .service(
+            web::resource("/admin/import")
+                .app_data(web::PayloadConfig::new(MAX_WEIGHTS_BYTES))
+                .route(web::post().to(handle_import::<T, A>)),
+        )
+        .route("/admin/freeze", web::get().to(handle_freeze_status::<T, A>))
+        .route("/admin/freeze", web::put().to(handle_set_freeze::<T, A>))
+        .route(
+            "/admin/constraints",
+            web::get().to(handle_constraints_status::<T, A>),
+        )
+        .route(
+            "/admin/constraints",
+            web::put().to(handle_set_constraints::<T, A>),
+        )
+        .route("/admin/canary", web::get().to(handle_canary_status::<T, A>))
+        .route(
+            "/admin/canary",
+            web::delete().to(handle_abort_canary::<T, A>),
+        )
+        .route(
+            "/model/version",
+            web::get().to(handle_model_version::<T, A>),
+        )
+        .route("/model/info", web::get().to(handle_model_info::<T, A>))
+        .route("/model/stats", web::get().to(handle_model_stats::<T, A>))
+        .route("/model/labels", web::get().to(handle_model_labels::<T, A>))
+        .route("/model/priors", web::get().to(handle_model_priors::<T, A>))
+        .route("/model/delta", web::get().to(handle_model_delta::<T, A>))
+        .route(
+            "/model/snapshots/{a}/diff/{b}",
+            web::get().to(handle_snapshot_diff::<T, A>),
+        )
+        .service(
+            web::resource("/model/weights")
+                .app_data(web::PayloadConfig::new(MAX_WEIGHTS_BYTES))
+                .route(web::get().to(handle_download_weights::<T, A>))
+                .route(web::put().to(handle_replace_weights::<T, A>)),
+        )
+        .route("/training", web::post().to(handle_training_step::<T, A>))
+        .route("/training/dry-run", web::post().to(handle_dry_run::<T, A>))
+        .service(
+            web::resource("/training/batch")
+                .app_data(web::PayloadConfig::new(MAX_BATCH_BYTES))
+                .route(web::post().to(handle_batch_training::<T, A>)),
+        )
+        .route(
+            "/training/guard",
+            web::get().to(handle_guard_status::<T, A>),
+        )
+        .route(
+            "/training/resume",
+            web::post().to(handle_resume_training::<T, A>),
+        )
+        .route(
+            "/training/watermark",
+            web::get().to(handle_watermark::<T, A>),
+        )
+        .route(
+            "/training/watermark/reset",
+            web::post().to(handle_reset_watermark::<T, A>),
+        )
+        .route("/evaluate", web::post().to(handle_evaluate::<T, A>))
+        .route("/feedback", web::post().to(handle_outcome::<T, A>))
+        .route("/feedback/{id}", web::post().to(handle_feedback::<T, A>))
+        .route("/model/holdout", web::put().to(handle_set_holdout::<T, A>))
+        .route(
+            "/model/evaluations",
+            web::get().to(handle_holdout_history::<T, A>),
+        )
+        .route(
+            "/training/quarantine",
+            web::get().to(handle_quarantine::<T, A>),
+        )
+        .route("/metrics", web::get().to(handle_metrics::<T, A>))
+        .route("/ps/parameters", web::get().to(handle_ps_pull::<T, A>))
+        .route("/ps/gradients", web::post().to(handle_ps_push::<T, A>))
+        .route(
+            "/gossip/exchange",
+            web::post().to(handle_gossip_exchange::<T, A>),
+        )
+        .route(
+            "/replication/events",
+            web::get().to(handle_replication_events::<T, A>),
+        )
+        .route(
+            "/replication/snapshot",
+            web::get().to(handle_replication_snapshot::<T, A>),
+        )
+        .route(
+            "/federation/round",
+            web::get().to(handle_federation_status::<T, A>),
+        )
+        .route(
+            "/federation/updates",
+            web::post().to(handle_masked_update::<T, A>),
+        )
+        .route("/cluster", web::get().to(handle_cluster_state::<T, A>))
+        .route(
+            "/cluster/heartbeat",
+            web::post().to(handle_heartbeat::<T, A>),
+        )
+        .route(
+            "/consistency/clock",
+            web::get().to(handle_consistency_clock::<T, A>),
+        )
+        .route(
+            "/consistency/deltas",
+            web::post().to(handle_versioned_delta::<T, A>),
+        )
+        .route("/jobs", web::post().to(handle_submit_job::<T, A>))
+        .route("/jobs", web::get().to(handle_list_jobs::<T, A>))
+        .route(
+            "/jobs/workers",
+            web::post().to(handle_register_worker::<T, A>),
+        )
+        .route("/jobs/shards", web::post().to(handle_train_shard::<T, A>))
+        .route("/jobs/{id}", web::get().to(handle_job_status::<T, A>))
+        .route(
+            "/challenger",
+            web::get().to(handle_challenger_report::<T, A>),
+        )
+        .route(
+            "/challenger/promote",
+            web::post().to(handle_promote_challenger::<T, A>),
+        )
+        .route("/models", web::get().to(handle_list_models::<T, A>))
+        .route(
+            "/models/{name}",
+            web::post().to(handle_create_model::<T, A>),
+        )
+        .route(
+            "/models/{name}",
+            web::delete().to(handle_delete_model::<T, A>),
+        )
+        .route(
+            "/models/archived",
+            web::get().to(handle_archived_models::<T, A>),
+        )
+        .route(
+            "/models/{name}/archive",
+            web::post().to(handle_archive_model::<T, A>),
+        )
+        .route(
+            "/models/{name}/fork",
+            web::post().to(handle_fork_model::<T, A>),
+        )
+        .route(
+            "/models/{name}/restore",
+            web::post().to(handle_restore_model::<T, A>),
+        )
+        .route(
+            "/models/{name}/training",
+            web::post().to(handle_model_training::<T, A>),
+        )
+        .route(
+            "/models/{name}/inference",
+            web::post().to(handle_model_inference::<T, A>),
+        )
+        .route(
+            "/models/{name}/algorithms",
+            web::get().to(handle_list_algorithms::<T, A>),
+        )
+        .route(
+            "/models/{name}/versions",
+            web::get().to(handle_model_versions::<T, A>),
+        )
+        .route(
+            "/models/{name}/versions",
+            web::post().to(handle_snapshot_model::<T, A>),
+        )
+        .route(
+            "/models/{name}/lineage",
+            web::get().to(handle_model_lineage::<T, A>),
+        )
+        .route(
+            "/models/{name}/algorithms/{algo}/inference",
+            web::post().to(handle_named_inference::<T, A>),
+        )
+        .route(
+            "/models/{name}/algorithms/{algo}/training",
+            web::post().to(handle_named_training::<T, A>),
+        )
+        .route("/ensembles", web::get().to(handle_list_ensembles::<T, A>))
+        .route(
+            "/ensembles/{name}",
+            web::put().to(handle_define_ensemble::<T, A>),
+        )
+        .route(
+            "/ensembles/{name}",
+            web::delete().to(handle_delete_ensemble::<T, A>),
+        )
+        .route(
+            "/ensembles/{name}/inference",
+            web::post().to(handle_ensemble_inference::<T, A>),
+        )
+        .route(
+            "/ensembles/{name}/feedback",
+            web::post().to(handle_ensemble_feedback::<T, A>),
+        )
+        .route("/tuning", web::get().to(handle_tuning_status::<T, A>))
+        .route(
+            "/tuning/samples",
+            web::post().to(handle_tuning_sample::<T, A>),
+        );
+    #[cfg(feature = "scripting")]
+    cfg.route("/scripts", web::get().to(handle_list_scripts::<T, A>))
+        .route("/scripts/{stage}", web::put().to(handle_set_script::<T, A>))
+        .route(
+            "/scripts/{stage}",
+            web::delete().to(handle_remove_script::<T, A>),
+        );
+}
+
+/// Replaces the parameters of `model` with ones loaded from `origin`.
+fn load_parameters<T>(model: &Model<T>, origin: &str, loaded: Vec<T>) -> std::io::Result<()>
+where
+    T: Float + Debug + Send + Sync,
+{
+    let mut parameters = model.write_params();
+    if loaded.len() != parameters.len() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!(
+                "{} has {} parameters, model has {}",
+                origin,
+                loaded.len(),
+                parameters.len()
+            ),
+        ));
+    }
+    *parameters = loaded;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::DummyAlgorithm;
+    use crate::test::ScalingAlgorithm;
+    use std::time::Duration;
+
+    #[actix_rt::test]
+    async fn test_builder_serves_with_auth_and_checkpoints_on_stop() {
+        let dir = tempfile::tempdir().unwrap();
+        let handle = ServerBuilder::new()
+            .bind("127.0.0.1:0")
+            .model(Model::with_parameters(vec![1.0f32, 2.0]))
+            .algorithm(DummyAlgorithm)
+            .with_auth(AuthConfig::bearer(["secret"]))
+            .with_checkpointing(CheckpointConfig::new(dir.path()))
+            .build()
+            .unwrap()
+            .start()
+            .unwrap();
+        let url = format!("http://{}/inference", handle.addrs()[0]);
+
+        let client = reqwest::Client::new();
+        let denied = client.post(&url).json(&1.0f32).send().await.unwrap();
+        assert_eq!(denied.status(), 401);
+        let allowed = client
+            .post(&url)
+            .bearer_auth("secret")
+            .json(&1.0f32)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(allowed.status(), 200);
+
+        handle.stop(true).await.unwrap();
+        assert!(dir
+            .path()
+            .join(format!("checkpoint-{:020}.json", 1))
+            .exists());
+    }
+
+    #[actix_rt::test]
+    async fn test_follower_authenticates_with_leader() {
+        let auth = AuthConfig::bearer(["secret"]);
+        let leader = ServerBuilder::new()
+            .bind("127.0.0.1:0")
+            .state(
+                AppState::new(Model::with_parameters(vec![1.0f32]), ScalingAlgorithm)
+                    .with_replication_leader(16),
+            )
+            .with_auth(auth.clone())
+            .build()
+            .unwrap()
+            .start()
+            .unwrap();
+        let leader_url = format!("http://{}", leader.addrs()[0]);
+        let follower = ServerBuilder::new()
+            .bind("127.0.0.1:0")
+            .state(
+                AppState::new(Model::with_parameters(vec![1.0f32]), ScalingAlgorithm)
+                    .with_replication_follower(&leader_url, Duration::from_millis(20)),
+            )
+            .with_auth(auth)
+            .build()
+            .unwrap();
+        let state = follower.state().clone();
+        let follower = follower.start().unwrap();
+
+        let trained = reqwest::Client::new()
+            .post(format!("{}/training", leader_url))
+            .bearer_auth("secret")
+            .json(&3.0f32)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(trained.status(), 200);
+        for _ in 0..100 {
+            if state.model.read_params()[0] == 3.0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(state.model.read_params()[0], 3.0);
+        follower.stop(true).await.unwrap();
+        leader.stop(true).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_model_file_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.bin");
+        let start = |params: Vec<f32>| {
+            ServerBuilder::new()
+                .bind("127.0.0.1:0")
+                .model(Model::with_parameters(params))
+                .algorithm(DummyAlgorithm)
+                .with_model_file(&path)
+                .build()
+                .unwrap()
+        };
+
+        let server = start(vec![0.0, 0.0]);
+        server.state().model.update_params(|p| *p = vec![3.0, 4.0]);
+        server.start().unwrap().stop(false).await.unwrap();
+
+        let restarted = start(vec![0.0, 0.0]);
+        assert_eq!(
+            restarted.state().model.read_params().as_slice(),
+            &[3.0, 4.0]
+        );
+        assert!(ServerBuilder::new()
+            .bind("127.0.0.1:0")
+            .model(Model::with_parameters(vec![0.0f32]))
+            .algorithm(DummyAlgorithm)
+            .with_model_file(&path)
+            .build()
+            .is_err());
+    }
 }
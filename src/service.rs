@@ -0,0 +1,809 @@
+use crate::algorithm::{Algorithm, Datum};
+use crate::errors::ModelError;
+use crate::event_time::Admission;
+use crate::handlers::{AppState, DetailedPrediction};
+use crate::metrics::MetricsSnapshot;
+use crate::outliers::OutlierVerdict;
+use crate::replication::Replication;
+use crate::simulation::TraceEvent;
+use crate::warmup::WarmupFallback;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Debug};
+use std::iter::Sum;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Failure of an [`OmlService`] call, classified the way transports report it.
+#[derive(Debug)]
+pub enum ServiceError {
+    /// A stage rejected or failed the request; the [`ModelError`] kind
+    /// tells why (invalid input, conflict, remote failure, ...).
+    Model(ModelError),
+    /// The algorithm's own step failed.
+    Step(ModelError),
+    /// The addressed model or feature does not exist.
+    NotFound(String),
+    /// The request cannot be served right now (training paused, not the writer).
+    Unavailable(String),
+    /// Internal failure such as a poisoned lock or a panicked task.
+    Internal(String),
+}
+
+impl fmt::Display for ServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceError::Model(e) | ServiceError::Step(e) => write!(f, "{}", e),
+            ServiceError::NotFound(message)
+            | ServiceError::Unavailable(message)
+            | ServiceError::Internal(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ServiceError {}
+
+impl From<ModelError> for ServiceError {
+    fn from(error: ModelError) -> Self {
+        ServiceError::Model(error)
+    }
+}
+
+/// An inference call and its options.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InferenceRequest<I> {
+    pub input: I,
+    /// Also compute the per-feature contributions of the prediction.
+    pub explain: bool,
+    /// Also compute the uncertainty of the prediction.
+    pub uncertainty: bool,
+    /// Serve from the moving average of the parameters (see
+    /// [`Model::with_ema`](crate::model::Model::with_ema)) rather than the
+    /// parameters themselves.
+    pub ema: bool,
+    /// Keeps the prediction under this id until its delayed label arrives.
+    pub event_id: Option<String>,
+    /// Catalog model to serve from instead of the serving model.
+    pub model: Option<String>,
+    /// Historical version of `model`; its live parameters otherwise.
+    pub model_version: Option<u64>,
+    /// Entity whose personalized model serves the request, if it has one.
+    pub key: Option<String>,
+    /// Segment (country, device, ...) the request's metrics are sliced by.
+    pub segment: Option<String>,
+}
+
+impl<I> InferenceRequest<I> {
+    /// A plain prediction of the serving model.
+    pub fn new(input: I) -> Self {
+        InferenceRequest {
+            input,
+            explain: false,
+            uncertainty: false,
+            ema: false,
+            event_id: None,
+            model: None,
+            model_version: None,
+            key: None,
+            segment: None,
+        }
+    }
+}
+
+/// Result of an inference call: the bare prediction, or a
+/// [`DetailedPrediction`] when contributions or uncertainty were requested.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum InferenceResponse<T, O = T> {
+    Prediction(O),
+    Detailed(DetailedPrediction<T, O>),
+}
+
+/// What happened to a training sample.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrainingOutcome {
+    /// The step was applied to the model.
+    Applied,
+    /// The sample was sent to the shared-state writer.
+    Forwarded,
+    /// The outlier filter held the sample back.
+    Quarantined { score: f64 },
+    /// The sample was behind the event-time watermark by `lateness_ms` and
+    /// dropped (see [`crate::event_time::LatePolicy::Discard`]).
+    Discarded { lateness_ms: u64 },
+    /// The sample duplicated one trained on within the dedup window and
+    /// was skipped.
+    Duplicate,
+}
+
+/// The request pipeline of an oml server (validation, optional stages,
+/// algorithm dispatch and metrics) behind plain async methods.
+///
+/// The actix handlers are a thin adapter over it; other transports (axum,
+/// warp, in-process callers) can wrap the same service. Calls must run on a
+/// tokio runtime, as blocking steps go through `spawn_blocking`.
+pub struct OmlService<T, A>
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    state: Arc<AppState<T, A>>,
+}
+
+impl<T, A> Clone for OmlService<T, A>
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    fn clone(&self) -> Self {
+        OmlService {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<T, A> OmlService<T, A>
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    pub fn new(state: AppState<T, A>) -> Self {
+        Self::from_shared(Arc::new(state))
+    }
+
+    /// Wraps a state shared with other handlers (e.g. actix's `web::Data`).
+    pub fn from_shared(state: Arc<AppState<T, A>>) -> Self {
+        OmlService { state }
+    }
+
+    pub fn state(&self) -> &Arc<AppState<T, A>> {
+        &self.state
+    }
+
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.state.metrics.snapshot()
+    }
+
+    /// Runs [`OmlService::infer`], serving the route's fallback model instead
+    /// if the primary step fails or times out; the flag is set when it did.
+    /// Errors that are the caller's, such as an unknown model or an input of
+    /// the wrong dimension, are returned as is.
+    pub async fn infer_or_fallback(
+        &self,
+        request: InferenceRequest<A::Input>,
+    ) -> Result<(InferenceResponse<T, A::Output>, bool), ServiceError> {
+        let data = &self.state;
+        let Some(fallback) = data.fallback_for(request.model.as_deref()) else {
+            return Ok((self.infer(request).await?, false));
+        };
+        let input = request.input.clone();
+        let primary = match fallback.timeout() {
+            Some(timeout) => tokio::time::timeout(timeout, self.infer(request)).await,
+            None => Ok(self.infer(request).await),
+        };
+        match primary {
+            Ok(Ok(response)) => return Ok((response, false)),
+            Ok(Err(e @ ServiceError::Step(ModelError::DimensionMismatch { .. }))) => return Err(e),
+            Ok(Err(ServiceError::Step(_) | ServiceError::Internal(_))) => {
+                data.metrics.increment("primary_inference_failures");
+            }
+            Ok(Err(e)) => return Err(e),
+            Err(_) => data.metrics.increment("fallback_timeouts"),
+        }
+
+        let state = data.clone(); // clone the Arc (not the state)
+        let prediction =
+            tokio::task::spawn_blocking(move || fallback.infer(state.preprocess(input)?))
+                .await
+                .map_err(|e| ServiceError::Internal(format!("Task failed: {:?}", e)))?
+                .map_err(ServiceError::Step)?;
+        data.metrics.increment("fallback_model_inferences");
+        Ok((InferenceResponse::Prediction(prediction), true))
+    }
+
+    /// Runs an inference step through middleware, scripts, calibration and
+    /// delayed-label bookkeeping. While the serving model warms up, the
+    /// fallback of its warm-up policy may answer instead.
+    pub async fn infer(
+        &self,
+        request: InferenceRequest<A::Input>,
+    ) -> Result<InferenceResponse<T, A::Output>, ServiceError> {
+        let data = &self.state;
+        let received = Instant::now();
+        let snapshot = match request.model {
+            Some(_) => None,
+            None => Some(data.serving_snapshot()?),
+        };
+        let version = snapshot.as_ref().map(|s| s.version);
+        data.record(
+            TraceEvent::Inference {
+                input: request.input.clone(),
+            },
+            version,
+        );
+        let (model, algorithm) = match &request.model {
+            Some(name) => {
+                let Some(catalog) = &data.catalog else {
+                    return Err(ServiceError::NotFound(
+                        "model catalog is not enabled".to_string(),
+                    ));
+                };
+                let selected = catalog
+                    .resolve(name, request.model_version)?
+                    .ok_or_else(|| {
+                        ServiceError::NotFound(format!(
+                            "unknown model {} (version {:?})",
+                            name, request.model_version
+                        ))
+                    })?;
+                data.metrics.increment("inference_model_overrides");
+                selected
+            }
+            None => {
+                let model = snapshot.map_or_else(|| data.model.clone(), |s| s.model);
+                (model, data.active_algorithm())
+            }
+        };
+        if request.ema && model.ema().is_none() {
+            return Err(ServiceError::NotFound(
+                "EMA weights are not enabled".to_string(),
+            ));
+        }
+        // Until the serving model is warm a fallback may stand in for it.
+        let cold = request.model.is_none() && data.is_cold();
+        if cold {
+            data.metrics.increment("cold_inferences");
+        }
+        let prior = match data.warmup.as_ref().map(|w| w.fallback()) {
+            Some(WarmupFallback::Constant(value)) if cold => {
+                data.metrics.increment("fallback_inferences");
+                let prediction = A::Output::from_score(*value).ok_or_else(|| {
+                    ServiceError::Internal("the fallback constant is not an output".to_string())
+                })?;
+                return Ok(InferenceResponse::Prediction(prediction));
+            }
+            Some(WarmupFallback::Prior(prior)) if cold => {
+                data.metrics.increment("fallback_inferences");
+                Some(prior.clone())
+            }
+            _ => None,
+        };
+        let model = prior.clone().unwrap_or(model);
+        let personal = match (&request.key, &request.model) {
+            (Some(_), None) => Some(data.personalization.clone().ok_or_else(|| {
+                ServiceError::NotFound("personalized models are not enabled".to_string())
+            })?),
+            _ => None,
+        };
+        // A share of the plain requests for the serving model goes to the
+        // canary parameters during a rollout.
+        let canary = match (&data.canary, &request.model, &request.key) {
+            (Some(canary), None, None) if !request.ema && prior.is_none() => {
+                Some((canary.clone(), canary.route()?))
+            }
+            _ => None,
+        };
+        let model = match &canary {
+            Some((_, Some(candidate))) => {
+                data.metrics.increment("canary_inferences");
+                candidate.clone()
+            }
+            _ => model,
+        };
+        let state = data.clone(); // clone the Arc (not the state)
+        let input = request.input;
+        let key = request.key;
+        let (explain, uncertainty, ema) = (request.explain, request.uncertainty, request.ema);
+
+        let x = input.clone();
+        let task = tokio::task::spawn_blocking(move || {
+            let layered = match (&personal, &key) {
+                (Some(personal), Some(key)) => personal.model_for(key, &model)?,
+                _ => None,
+            };
+            if layered.is_some() {
+                state.metrics.increment("personalized_inferences");
+            }
+            let model = layered.as_ref().unwrap_or(&*model);
+            let x = state
+                .middleware
+                .iter()
+                .try_fold(x, |x, m| m.on_inference_request(x))?;
+            let x = state.preprocess(x)?;
+            model.check_input(&x)?;
+            let started = Instant::now();
+            // Personalized models keep no moving average and serve as is.
+            let result = match ema {
+                true => algorithm.ema_inference_step(model, x.clone())?,
+                false => algorithm.inference_step(model, x.clone())?,
+            };
+            let model = match ema {
+                true => model.ema().unwrap_or(model),
+                false => model,
+            };
+            if let Some((canary, candidate)) = &canary {
+                let change =
+                    canary.observe_latency(candidate.is_some(), started.elapsed(), &state.model);
+                state.canary_changed(change);
+            }
+            let details = || {
+                Ok::<_, ModelError>((
+                    explain
+                        .then(|| algorithm.contributions(model, x.clone()))
+                        .transpose()?,
+                    uncertainty
+                        .then(|| algorithm.uncertainty(model, x.clone()))
+                        .transpose()?,
+                ))
+            };
+            Ok::<_, ModelError>((result, (explain || uncertainty).then(details)))
+        });
+        let (result, details) = task
+            .await
+            .map_err(|e| ServiceError::Internal(format!("Task failed: {:?}", e)))?
+            .map_err(ServiceError::Step)?;
+
+        data.metrics.increment("inference_steps");
+        if ema {
+            data.metrics.increment("ema_inferences");
+        }
+        if let Some(segment) = &request.segment {
+            data.metrics.record_segment_inference(segment);
+        }
+        if let (Some(pending), Some(event_id)) = (&data.pending_predictions, &request.event_id) {
+            if !data.enforce_memory_budget()? {
+                // Serve the prediction, but it cannot be joined with its label.
+                data.metrics.increment("memory_rejections");
+            } else {
+                pending.record_in_segment(
+                    event_id,
+                    request.segment.clone(),
+                    input.clone(),
+                    result.clone(),
+                )?;
+                data.metrics
+                    .set_gauge("pending_predictions", pending.len() as f64);
+            }
+        }
+        let result = match &data.calibrator {
+            Some(calibrator) => {
+                let calibrator = calibrator
+                    .lock()
+                    .map_err(|e| ServiceError::Internal(e.to_string()))?;
+                result
+                    .score()
+                    .and_then(|score| A::Output::from_score(calibrator.calibrate(score)))
+                    .unwrap_or(result)
+            }
+            None => result,
+        };
+        let result = data.postprocess(&input, result).and_then(|result| {
+            data.middleware
+                .iter()
+                .try_fold(result, |result, m| m.on_prediction(&input, result))
+        })?;
+        data.log_prediction(&input, &result, version, received.elapsed());
+        Ok(match details {
+            Some(details) => {
+                let (contributions, uncertainty) = details?;
+                InferenceResponse::Detailed(DetailedPrediction {
+                    prediction: result,
+                    contributions,
+                    uncertainty,
+                })
+            }
+            None => InferenceResponse::Prediction(result),
+        })
+    }
+
+    /// Runs [`OmlService::train`], then, if the step was applied, a step of
+    /// the personalized model of `key` (see [`PersonalizedModels`]).
+    ///
+    /// [`PersonalizedModels`]: crate::personalization::PersonalizedModels
+    pub async fn train_for(
+        &self,
+        key: &str,
+        input: A::Input,
+        forwarded: bool,
+    ) -> Result<TrainingOutcome, ServiceError> {
+        let data = &self.state;
+        let Some(personal) = data.personalization.clone() else {
+            return Err(ServiceError::NotFound(
+                "personalized models are not enabled".to_string(),
+            ));
+        };
+        let outcome = self.train(input.clone(), forwarded).await?;
+        if outcome != TrainingOutcome::Applied {
+            return Ok(outcome);
+        }
+
+        let input = data.preprocess(input)?;
+        let model = data.model.clone(); // clone the Arc (not the model)
+        let algorithm = data.active_algorithm();
+        let key = key.to_string();
+        let (evicted, keys) = tokio::task::spawn_blocking(move || {
+            let evicted = personal.train(&key, &model, &*algorithm, input)?;
+            Ok::<_, ModelError>((evicted, personal.len()?))
+        })
+        .await
+        .map_err(|e| ServiceError::Internal(format!("Task failed: {:?}", e)))?
+        .map_err(ServiceError::Step)?;
+
+        data.metrics.increment("personalized_training_steps");
+        if evicted.is_some() {
+            data.metrics.increment("personalized_evictions");
+        }
+        data.metrics.set_gauge("personalized_keys", keys as f64);
+        Ok(outcome)
+    }
+
+    /// Runs a training step on the live catalog model `name`. The stages of
+    /// the serving model (scripts, filters, replication, ...) do not apply.
+    pub async fn train_model(&self, name: &str, input: A::Input) -> Result<(), ServiceError> {
+        let data = &self.state;
+        let Some(catalog) = &data.catalog else {
+            return Err(ServiceError::NotFound(
+                "model catalog is not enabled".to_string(),
+            ));
+        };
+        let (model, algorithm) = catalog
+            .resolve(name, None)?
+            .ok_or_else(|| ServiceError::NotFound(format!("unknown model {}", name)))?;
+        let trained = model.clone(); // clone the Arc (not the model)
+        tokio::task::spawn_blocking(move || {
+            trained.check_input(&input)?;
+            let weight = match input.class_label() {
+                Some(label) => trained.observe_class(&label)?,
+                None => 1.0,
+            };
+            algorithm.weighted_training_step(&trained, input, weight)?;
+            trained.record_training_step();
+            Ok(())
+        })
+        .await
+        .map_err(|e| ServiceError::Internal(format!("Task failed: {:?}", e)))?
+        .map_err(ServiceError::Step)?;
+        catalog.record_training(&model, 1)?;
+        data.metrics.increment("catalog_training_steps");
+        Ok(())
+    }
+
+    /// Runs a training step through the dedup window, scripts, the outlier
+    /// filter, the feature clipper, the guard, replication, shared state, consistency tracking
+    /// and the challenger.
+    ///
+    /// Replicas that are not the shared-state writer forward the sample to
+    /// it, unless `forwarded` says it already came from another replica.
+    pub async fn train(
+        &self,
+        input: A::Input,
+        forwarded: bool,
+    ) -> Result<TrainingOutcome, ServiceError> {
+        self.train_weighted(input, 1.0, forwarded).await
+    }
+
+    /// Runs a training step like [`OmlService::train`] for a sample that
+    /// happened at `event_time_ms`, admitted by the event clock (see
+    /// [`AppState::with_event_time`]): late samples are applied, discarded
+    /// or down-weighted by its policy. Without an event clock the time is
+    /// ignored.
+    pub async fn train_at(
+        &self,
+        input: A::Input,
+        event_time_ms: u64,
+        forwarded: bool,
+    ) -> Result<TrainingOutcome, ServiceError> {
+        let data = &self.state;
+        let Some(clock) = &data.event_clock else {
+            return self.train(input, forwarded).await;
+        };
+        let admission = clock.admit(event_time_ms);
+        if let Some(watermark) = clock.watermark_ms() {
+            data.metrics
+                .set_gauge("event_time_watermark_ms", watermark as f64);
+        }
+        let weight = match admission {
+            Admission::OnTime { out_of_order } => {
+                if out_of_order {
+                    data.metrics.increment("out_of_order_samples");
+                }
+                1.0
+            }
+            Admission::Late { weight, .. } => {
+                data.metrics.increment("late_samples");
+                weight
+            }
+            Admission::Discarded { lateness_ms } => {
+                data.metrics.increment("late_samples_discarded");
+                return Ok(TrainingOutcome::Discarded { lateness_ms });
+            }
+            Admission::Future { ahead_ms } => {
+                data.metrics.increment("future_samples_rejected");
+                return Err(ModelError::InvalidInput(format!(
+                    "the event time is {} ms ahead of the clock",
+                    ahead_ms
+                ))
+                .into());
+            }
+        };
+        self.train_weighted(input, weight, forwarded).await
+    }
+
+    /// Runs a training step like [`OmlService::train`] that counts `input`
+    /// with `weight` in `[0, 1]`; see [`Algorithm::weighted_training_step`].
+    pub async fn train_weighted(
+        &self,
+        input: A::Input,
+        weight: f64,
+        forwarded: bool,
+    ) -> Result<TrainingOutcome, ServiceError> {
+        let data = &self.state;
+        if !forwarded {
+            data.record(
+                TraceEvent::Training {
+                    input: input.clone(),
+                },
+                Some(data.model.version()),
+            );
+        }
+        if !data.enforce_memory_budget()? {
+            data.metrics.increment("memory_rejections");
+            return Err(ServiceError::Unavailable(
+                "the memory budget is exhausted".to_string(),
+            ));
+        }
+        let Some(dedup) = data.dedup.as_ref().filter(|_| !forwarded) else {
+            return self.train_unique(input, weight, forwarded).await;
+        };
+        if dedup.is_duplicate(&input)? {
+            data.metrics.increment("duplicate_samples_skipped");
+            return Ok(TrainingOutcome::Duplicate);
+        }
+        let result = self.train_unique(input.clone(), weight, forwarded).await;
+        if result.is_err() {
+            // Not trained on, so the client's retry must not be skipped.
+            dedup.forget(&input)?;
+        }
+        result
+    }
+
+    /// [`OmlService::train_weighted`] past the dedup window.
+    async fn train_unique(
+        &self,
+        input: A::Input,
+        weight: f64,
+        forwarded: bool,
+    ) -> Result<TrainingOutcome, ServiceError> {
+        let data = &self.state;
+        if let Some(shared) = data.shared_state.as_ref().filter(|s| !s.is_writer()) {
+            if forwarded {
+                return Err(ServiceError::Unavailable(
+                    "this replica is not the writer".to_string(),
+                ));
+            }
+            shared.forward_training(&input, weight).await?;
+            data.metrics.increment("training_forwarded");
+            return Ok(TrainingOutcome::Forwarded);
+        }
+
+        let input = data.preprocess(input)?;
+        data.model.check_input(&input)?;
+
+        if let Some(filter) = &data.outlier_filter {
+            let verdict = filter
+                .lock()
+                .map_err(|e| ServiceError::Internal(e.to_string()))?
+                .check(input.clone());
+            if let OutlierVerdict::Quarantine { score } = verdict {
+                data.metrics.increment("outliers_quarantined");
+                return Ok(TrainingOutcome::Quarantined { score });
+            }
+        }
+
+        let input = match &data.clipper {
+            Some(clipper) => {
+                let (input, clipped) = clipper
+                    .lock()
+                    .map_err(|e| ServiceError::Internal(e.to_string()))?
+                    .clip(input);
+                data.metrics.add("features_clipped", clipped as u64);
+                input
+            }
+            None => input,
+        };
+
+        if let Some(Replication::Follower(follower)) = data.replication.as_deref() {
+            return Err(ModelError::NotLeader(follower.leader_address()).into());
+        }
+
+        if data.training_guard.as_ref().is_some_and(|g| g.is_paused()) {
+            return Err(ServiceError::Unavailable(
+                "training is paused by the guard".to_string(),
+            ));
+        }
+
+        let weight = match input.class_label() {
+            Some(label) => weight * data.model.observe_class(&label)?,
+            None => weight,
+        };
+
+        let state = data.clone(); // clone the Arc (not the state)
+        tokio::task::spawn_blocking(move || {
+            let _writer = state
+                .algorithms
+                .as_ref()
+                .map(|r| r.lock_writer())
+                .transpose()?;
+            apply_training_step(&state, &state.active_algorithm(), input, weight)
+        })
+        .await
+        .map_err(|e| ServiceError::Internal(format!("Task failed: {:?}", e)))?
+        .map_err(ServiceError::Step)?;
+
+        data.metrics.increment("training_steps");
+        if let Some(catalog) = &data.catalog {
+            catalog.record_training(&data.model, 1)?;
+        }
+        Ok(TrainingOutcome::Applied)
+    }
+}
+
+/// Applies a training step of `algorithm` to the serving model of `state`
+/// with everything that follows one: the replication log of a leader, the
+/// shared parameter store, consistency tracking, the middleware, the
+/// challenger and the checkpointer. Callers hold the registry's writer
+/// lock, if any, and count the step.
+pub(crate) fn apply_training_step<T, A>(
+    state: &AppState<T, A>,
+    algorithm: &A,
+    input: A::Input,
+    weight: f64,
+) -> Result<(), ModelError>
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    let model = &state.model;
+    let train = || algorithm.weighted_training_step(model, input.clone(), weight);
+    let synced = || {
+        #[cfg(feature = "grpc")]
+        if let Some((sync, _)) = &state.parameter_sync {
+            return sync.record_local(train);
+        }
+        train()
+    };
+    // The consistency tracker stamps the step itself, so the delta shipped
+    // to other writers holds only its changes.
+    let step = || match &state.consistency {
+        Some(consistency) => consistency.record_local(model, synced).map(|((), _)| ()),
+        None => synced(),
+    };
+    match state.replication.as_deref() {
+        Some(Replication::Leader(log)) => log
+            .apply(input.clone(), weight, || model.logged_step(step))
+            .map(|_| ())?,
+        _ => model.logged_step(step)?,
+    }
+    model.record_training_step();
+    if let Some(shared) = &state.shared_state {
+        shared.publish(model)?;
+    }
+    for m in &state.middleware {
+        m.on_training_applied(&input);
+    }
+    if let Some(challenger) = state.challenger.as_ref().filter(|c| !c.is_promoted()) {
+        challenger.train(input)?;
+    }
+    match state.checkpointer.as_ref().map(|c| c.record_step(model)) {
+        Some(Ok(Some(version))) => state
+            .metrics
+            .set_gauge("checkpoint_version", version as f64),
+        Some(Err(_)) => state.metrics.increment("checkpoint_failures"),
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::Algorithm;
+    use crate::model::Model;
+    use crate::personalization::PersonalizationConfig;
+    use crate::recorder::RecorderConfig;
+    use crate::simulation::read_trace;
+
+    /// Adds the input to the parameter; predicts the parameter times the input.
+    struct Scale;
+
+    impl Algorithm<f32> for Scale {
+        type Input = f32;
+        type Output = f32;
+
+        fn training_step(&self, model: &Model<f32>, x: f32) -> Result<(), ModelError> {
+            model.write_params()[0] += x;
+            Ok(())
+        }
+
+        fn inference_step(&self, model: &Model<f32>, x: f32) -> Result<f32, ModelError> {
+            Ok(model.read_params()[0] * x)
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_in_process_calls() {
+        let service = OmlService::new(AppState::new(Model::with_parameters(vec![1.0]), Scale));
+        assert_eq!(
+            service.train(2.0, false).await.unwrap(),
+            TrainingOutcome::Applied
+        );
+        match service.infer(InferenceRequest::new(2.0)).await.unwrap() {
+            InferenceResponse::Prediction(prediction) => assert_eq!(prediction, 6.0),
+            other => panic!("unexpected response {:?}", other),
+        }
+
+        let mut request = InferenceRequest::new(1.0);
+        request.model = Some("canary".to_string());
+        assert!(matches!(
+            service.infer(request).await,
+            Err(ServiceError::NotFound(_))
+        ));
+        assert_eq!(service.metrics().counters["training_steps"], 1);
+    }
+
+    #[actix_rt::test]
+    async fn test_personalized_training_and_inference() {
+        let state = AppState::new(Model::with_parameters(vec![1.0]), Scale)
+            .with_personalization(PersonalizationConfig::new(10));
+        let service = OmlService::new(state);
+        service.train_for("alice", 2.0, false).await.unwrap();
+        let predict = |key: Option<&str>| {
+            let mut request = InferenceRequest::new(1.0);
+            request.key = key.map(str::to_string);
+            let service = service.clone();
+            async move {
+                match service.infer(request).await.unwrap() {
+                    InferenceResponse::Prediction(prediction) => prediction,
+                    other => panic!("unexpected response {:?}", other),
+                }
+            }
+        };
+        // The global model took the step, alice's model one more on top of it.
+        assert_eq!(predict(None).await, 3.0);
+        assert_eq!(predict(Some("alice")).await, 5.0);
+        assert_eq!(predict(Some("bob")).await, 3.0);
+        assert_eq!(service.metrics().counters["personalized_inferences"], 1);
+
+        let plain = OmlService::new(AppState::new(Model::with_parameters(vec![1.0]), Scale));
+        assert!(matches!(
+            plain.train_for("alice", 2.0, false).await,
+            Err(ServiceError::NotFound(_))
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn test_requests_recorded_for_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::new(Model::with_parameters(vec![1.0]), Scale)
+            .with_trace_recorder(RecorderConfig::new(dir.path()));
+        let service = OmlService::new(state);
+        service.train(2.0, false).await.unwrap();
+        service.infer(InferenceRequest::new(4.0)).await.unwrap();
+        service.state().recorder.as_ref().unwrap().flush().unwrap();
+
+        let records = read_trace::<f32, f32>(dir.path()).unwrap();
+        let events: Vec<_> = records
+            .iter()
+            .map(|r| (r.event.clone(), r.model_version))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (TraceEvent::Training { input: 2.0 }, Some(0)),
+                (TraceEvent::Inference { input: 4.0 }, Some(1)),
+            ]
+        );
+        assert_eq!(service.metrics().counters["trace_records"], 2);
+    }
+}
@@ -0,0 +1,423 @@
+use crate::auth::PeerClient;
+use crate::errors::ModelError;
+use crate::metrics::Metrics;
+use crate::model::Model;
+use num_traits::Float;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt::Debug;
+use std::fs;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Parameters as published to a shared store by the current writer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredParameters<T> {
+    pub version: u64,
+    pub values: Vec<T>,
+}
+
+/// Exclusive, time-limited right to train the shared model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WriterLease {
+    pub holder: String,
+    /// Address other replicas forward training requests to.
+    pub address: String,
+    /// Milliseconds since the Unix epoch after which the lease can be taken over.
+    pub expires_at_ms: u64,
+}
+
+impl WriterLease {
+    pub fn is_expired(&self, now_ms: u64) -> bool {
+        now_ms >= self.expires_at_ms
+    }
+}
+
+/// Backing store shared by every replica (Redis, an object store, a shared volume, ...).
+pub trait ParameterStore<T>: Send + Sync {
+    /// Loads the latest published parameters, if any were published yet.
+    fn load(&self) -> Result<Option<StoredParameters<T>>, ModelError>;
+
+    /// Publishes a new parameter version.
+    fn save(&self, parameters: &StoredParameters<T>) -> Result<(), ModelError>;
+
+    /// Takes (or renews) the writer lease for `holder` if it is free, expired
+    /// or already held by `holder`. Returns whichever lease is in force afterwards.
+    fn acquire_lease(
+        &self,
+        holder: &str,
+        address: &str,
+        ttl: Duration,
+    ) -> Result<WriterLease, ModelError>;
+}
+
+/// Process-local store, mostly useful for tests and single-host setups.
+#[derive(Debug, Default)]
+pub struct InMemoryParameterStore<T> {
+    parameters: Mutex<Option<StoredParameters<T>>>,
+    lease: Mutex<Option<WriterLease>>,
+}
+
+impl<T> InMemoryParameterStore<T> {
+    pub fn new() -> Self {
+        InMemoryParameterStore {
+            parameters: Mutex::new(None),
+            lease: Mutex::new(None),
+        }
+    }
+}
+
+impl<T> ParameterStore<T> for InMemoryParameterStore<T>
+where
+    T: Clone + Send + Sync,
+{
+    fn load(&self) -> Result<Option<StoredParameters<T>>, ModelError> {
+        Ok(self.parameters.lock()?.clone())
+    }
+
+    fn save(&self, parameters: &StoredParameters<T>) -> Result<(), ModelError> {
+        *self.parameters.lock()? = Some(parameters.clone());
+        Ok(())
+    }
+
+    fn acquire_lease(
+        &self,
+        holder: &str,
+        address: &str,
+        ttl: Duration,
+    ) -> Result<WriterLease, ModelError> {
+        let mut lease = self.lease.lock()?;
+        let next = next_lease(lease.as_ref(), holder, address, ttl);
+        *lease = Some(next.clone());
+        Ok(next)
+    }
+}
+
+/// Store keeping parameters and lease as JSON files in a shared directory
+/// (e.g. an NFS volume or a mounted object-store bucket).
+///
+/// Writes go through a temporary file and a rename so readers never see a
+/// partially written file. Lease takeover is last-rename-wins, which is good
+/// enough when the lease TTL is much larger than the refresh interval.
+#[derive(Debug)]
+pub struct FileParameterStore<T> {
+    directory: PathBuf,
+    _marker: PhantomData<T>,
+}
+
+impl<T> FileParameterStore<T> {
+    pub fn new(directory: impl Into<PathBuf>) -> Result<Self, ModelError> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+        Ok(FileParameterStore {
+            directory,
+            _marker: PhantomData,
+        })
+    }
+
+    fn write_atomic(&self, name: &str, contents: &[u8]) -> Result<(), ModelError> {
+        let tmp = self.directory.join(format!(".{}.tmp", name));
+        fs::write(&tmp, contents)?;
+        fs::rename(&tmp, self.directory.join(name))?;
+        Ok(())
+    }
+
+    fn read_json<V: DeserializeOwned>(&self, name: &str) -> Result<Option<V>, ModelError> {
+        match fs::read(self.directory.join(name)) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl<T> ParameterStore<T> for FileParameterStore<T>
+where
+    T: Serialize + DeserializeOwned + Send + Sync,
+{
+    fn load(&self) -> Result<Option<StoredParameters<T>>, ModelError> {
+        self.read_json("parameters.json")
+    }
+
+    fn save(&self, parameters: &StoredParameters<T>) -> Result<(), ModelError> {
+        self.write_atomic("parameters.json", &serde_json::to_vec(parameters)?)
+    }
+
+    fn acquire_lease(
+        &self,
+        holder: &str,
+        address: &str,
+        ttl: Duration,
+    ) -> Result<WriterLease, ModelError> {
+        let current: Option<WriterLease> = self.read_json("lease.json")?;
+        let next = next_lease(current.as_ref(), holder, address, ttl);
+        if current.as_ref() != Some(&next) {
+            self.write_atomic("lease.json", &serde_json::to_vec(&next)?)?;
+        }
+        Ok(next)
+    }
+}
+
+fn next_lease(
+    current: Option<&WriterLease>,
+    holder: &str,
+    address: &str,
+    ttl: Duration,
+) -> WriterLease {
+    let now = now_ms();
+    match current {
+        Some(lease) if lease.holder != holder && !lease.is_expired(now) => lease.clone(),
+        _ => WriterLease {
+            holder: holder.to_string(),
+            address: address.to_string(),
+            expires_at_ms: now + ttl.as_millis() as u64,
+        },
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Configuration of a replica taking part in shared-state scaling.
+#[derive(Debug, Clone)]
+pub struct SharedStateConfig {
+    /// Unique identifier of this replica.
+    pub node_id: String,
+    /// Base URL other replicas use to forward training requests here.
+    pub advertised_address: String,
+    pub lease_ttl: Duration,
+    /// How often the lease is renewed and followers refresh their parameters.
+    pub refresh_interval: Duration,
+}
+
+/// One of N replicas serving inference from a shared parameter store.
+///
+/// Exactly one replica (the lease holder) trains and publishes parameters
+/// after every step; the others refresh from the store and forward training
+/// requests to it. If the writer stops renewing its lease, the next replica
+/// to refresh takes over.
+pub struct SharedStateReplica<T> {
+    config: SharedStateConfig,
+    store: Arc<dyn ParameterStore<T>>,
+    is_writer: AtomicBool,
+    writer_address: Mutex<Option<String>>,
+    version: AtomicU64,
+    /// Held while a version is loaded or published, so that concurrent
+    /// steps publish distinct versions in the order of their parameters.
+    publishing: Mutex<()>,
+    http: PeerClient,
+}
+
+impl<T> SharedStateReplica<T>
+where
+    T: Float + Serialize + DeserializeOwned + Debug + Send + Sync + 'static,
+{
+    pub fn new(config: SharedStateConfig, store: Arc<dyn ParameterStore<T>>) -> Self {
+        SharedStateReplica {
+            config,
+            store,
+            is_writer: AtomicBool::new(false),
+            writer_address: Mutex::new(None),
+            version: AtomicU64::new(0),
+            publishing: Mutex::new(()),
+            http: PeerClient::default(),
+        }
+    }
+
+    /// Client of the requests to peers, see [`AuthConfig`](crate::auth::AuthConfig).
+    pub(crate) fn peer_client(&self) -> &PeerClient {
+        &self.http
+    }
+
+    pub fn is_writer(&self) -> bool {
+        self.is_writer.load(Ordering::SeqCst)
+    }
+
+    /// Address of the current writer, as of the last refresh.
+    pub fn writer_address(&self) -> Option<String> {
+        self.writer_address.lock().ok().and_then(|a| a.clone())
+    }
+
+    /// Version of the parameters currently loaded in (or published from) this replica.
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
+    }
+
+    /// Renews or contends for the writer lease and, when not the writer,
+    /// loads any newer parameters from the store.
+    pub fn refresh(&self, model: &Model<T>) -> Result<(), ModelError> {
+        let lease = self.store.acquire_lease(
+            &self.config.node_id,
+            &self.config.advertised_address,
+            self.config.lease_ttl,
+        )?;
+        let was_writer = self.is_writer();
+        let is_writer = lease.holder == self.config.node_id;
+        *self.writer_address.lock()? = Some(lease.address);
+        if is_writer && was_writer {
+            return Ok(());
+        }
+        // Followers, and a replica that was just promoted, catch up first.
+        let _publishing = self.publishing.lock()?;
+        if let Some(stored) = self.store.load()? {
+            if stored.version > self.version() {
+                *model.write_params() = stored.values;
+                self.version.store(stored.version, Ordering::SeqCst);
+            }
+        }
+        self.is_writer.store(is_writer, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Publishes the writer's parameters after a training step.
+    pub fn publish(&self, model: &Model<T>) -> Result<u64, ModelError> {
+        let _publishing = self.publishing.lock()?;
+        let version = self.version() + 1;
+        self.store.save(&StoredParameters {
+            version,
+            values: model.read_params().clone(),
+        })?;
+        self.version.store(version, Ordering::SeqCst);
+        Ok(version)
+    }
+
+    /// Forwards a training input to the current writer, along with the
+    /// weight it is trained with (see [`SAMPLE_WEIGHT_HEADER`]).
+    pub async fn forward_training<I: Serialize>(
+        &self,
+        input: &I,
+        weight: f64,
+    ) -> Result<(), ModelError> {
+        let writer = self
+            .writer_address()
+            .ok_or_else(|| ModelError::RemoteError("no writer is known yet".to_string()))?;
+        let response = self
+            .http
+            .post(format!("{}/training", writer.trim_end_matches('/')))
+            .header(FORWARDED_HEADER, &self.config.node_id)
+            .header(SAMPLE_WEIGHT_HEADER, weight.to_string())
+            .json(input)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(ModelError::RemoteError(format!("{}: {}", status, body)));
+        }
+        Ok(())
+    }
+
+    /// Spawns the lease/refresh loop on the current runtime.
+    pub fn spawn(
+        self: Arc<Self>,
+        model: Arc<Model<T>>,
+        metrics: Arc<Metrics>,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.config.refresh_interval);
+            loop {
+                interval.tick().await;
+                let replica = self.clone();
+                let model = model.clone();
+                let refreshed = tokio::task::spawn_blocking(move || replica.refresh(&model)).await;
+                if !matches!(refreshed, Ok(Ok(()))) {
+                    metrics.increment("shared_state_refresh_failures");
+                }
+                metrics.set_gauge("shared_state_is_writer", self.is_writer() as u8 as f64);
+                metrics.set_gauge("shared_state_version", self.version() as f64);
+            }
+        })
+    }
+}
+
+/// Header marking a training request forwarded by another replica, so a
+/// replica that is not (or no longer) the writer never forwards it again.
+pub const FORWARDED_HEADER: &str = "X-Oml-Forwarded-By";
+
+/// Header carrying the weight in `[0, 1]` of a forwarded training sample,
+/// e.g. a late one down-weighted by the forwarding replica's event clock.
+/// Only honored together with [`FORWARDED_HEADER`].
+pub const SAMPLE_WEIGHT_HEADER: &str = "X-Oml-Sample-Weight";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(node_id: &str) -> SharedStateConfig {
+        SharedStateConfig {
+            node_id: node_id.to_string(),
+            advertised_address: format!("http://{}", node_id),
+            lease_ttl: Duration::from_millis(50),
+            refresh_interval: Duration::from_millis(10),
+        }
+    }
+
+    #[test]
+    fn test_single_writer_and_failover() {
+        let store: Arc<dyn ParameterStore<f32>> = Arc::new(InMemoryParameterStore::new());
+        let model_a = Model::with_parameters(vec![0.0]);
+        let model_b = Model::with_parameters(vec![0.0]);
+        let a = SharedStateReplica::new(config("a"), store.clone());
+        let b = SharedStateReplica::new(config("b"), store.clone());
+
+        a.refresh(&model_a).unwrap();
+        b.refresh(&model_b).unwrap();
+        assert!(a.is_writer());
+        assert!(!b.is_writer());
+        assert_eq!(b.writer_address().as_deref(), Some("http://a"));
+
+        model_a.write_params()[0] = 3.0;
+        a.publish(&model_a).unwrap();
+        b.refresh(&model_b).unwrap();
+        assert_eq!(model_b.read_params().clone(), vec![3.0]);
+
+        // `a` stops renewing; once the lease expires `b` takes over.
+        std::thread::sleep(Duration::from_millis(60));
+        b.refresh(&model_b).unwrap();
+        assert!(b.is_writer());
+    }
+
+    #[test]
+    fn test_concurrent_publishes_get_distinct_versions() {
+        let store = Arc::new(InMemoryParameterStore::new());
+        let replica = SharedStateReplica::new(config("a"), store.clone());
+        let model = Model::with_parameters(vec![0.0f32]);
+        let mut versions: Vec<u64> = std::thread::scope(|scope| {
+            let publishers: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| replica.publish(&model).unwrap()))
+                .collect();
+            publishers.into_iter().map(|p| p.join().unwrap()).collect()
+        });
+        versions.sort_unstable();
+        assert_eq!(versions, (1..=8).collect::<Vec<_>>());
+        assert_eq!(store.load().unwrap().unwrap().version, 8);
+    }
+
+    #[test]
+    fn test_file_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileParameterStore::<f64>::new(dir.path()).unwrap();
+        assert_eq!(store.load().unwrap(), None);
+
+        let stored = StoredParameters {
+            version: 2,
+            values: vec![1.0, 2.0],
+        };
+        store.save(&stored).unwrap();
+        assert_eq!(store.load().unwrap(), Some(stored));
+
+        let lease = store
+            .acquire_lease("a", "http://a", Duration::from_secs(60))
+            .unwrap();
+        let other = store
+            .acquire_lease("b", "http://b", Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(lease, other);
+    }
+}
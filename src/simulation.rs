@@ -0,0 +1,201 @@
+use crate::algorithm::{Algorithm, Datum};
+use crate::errors::ModelError;
+use crate::evaluation::{evaluate, EvaluationReport};
+use crate::model::Model;
+use crate::recorder::{decode_trace, trace_files, TRACE_MAGIC};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::io::BufRead;
+use std::iter::Sum;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// One recorded request of a traffic trace.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TraceEvent<I, O = I> {
+    Training {
+        input: I,
+    },
+    Inference {
+        input: I,
+    },
+    /// A labeled outcome; the model is scored on it as if it were served.
+    Outcome {
+        input: I,
+        label: O,
+    },
+}
+
+/// A trace entry: the event and, optionally, when it was recorded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceRecord<I, O = I> {
+    /// Milliseconds since the Unix epoch; used to pace the replay.
+    pub timestamp_ms: Option<u64>,
+    /// Version of the parameters when the request was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_version: Option<u64>,
+    #[serde(flatten)]
+    pub event: TraceEvent<I, O>,
+}
+
+/// Configuration of a trace replay.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulationConfig {
+    /// Replay speed relative to the recorded timestamps (2.0 = twice as
+    /// fast). Zero or a non-finite value replays as fast as possible.
+    pub speed: f64,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig { speed: 0.0 }
+    }
+}
+
+/// What the model would have achieved on the replayed traffic.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimulationReport {
+    pub records: usize,
+    pub training_steps: usize,
+    pub inference_steps: usize,
+    /// Algorithm calls that returned an error.
+    pub failures: usize,
+    pub elapsed_ms: u64,
+    /// Metrics over the `outcome` records; `None` if the trace has none.
+    pub evaluation: Option<EvaluationReport>,
+}
+
+/// Reads a trace stored as JSON lines, one [`TraceRecord`] per line, or
+/// written by a [`TraceRecorder`](crate::recorder::TraceRecorder): a binary
+/// trace file, or a recorder directory whose files are read oldest first.
+pub fn read_trace<I, O>(path: impl AsRef<Path>) -> Result<Vec<TraceRecord<I, O>>, ModelError>
+where
+    I: Datum,
+    O: Datum,
+{
+    let path = path.as_ref();
+    if path.is_dir() {
+        let mut records = Vec::new();
+        for (_, file) in trace_files(path)? {
+            records.extend(read_trace(file)?);
+        }
+        return Ok(records);
+    }
+    let bytes = std::fs::read(path)?;
+    if bytes.starts_with(TRACE_MAGIC) {
+        return decode_trace(&bytes);
+    }
+    let mut records = Vec::new();
+    for (index, line) in bytes.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line)
+            .map_err(|e| ModelError::InvalidInput(format!("trace line {}: {}", index + 1, e)))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Replays `records` in order against `model`, pacing them by their
+/// timestamps at the configured speed.
+///
+/// Training records update the model, outcome records are predicted first
+/// and scored against their label, so the report reflects the model as it
+/// evolved over the trace. Blocks for the duration of the replay.
+pub fn simulate<T, A>(
+    model: &Model<T>,
+    algorithm: &A,
+    records: &[TraceRecord<A::Input, A::Output>],
+    config: &SimulationConfig,
+) -> Result<SimulationReport, ModelError>
+where
+    T: Float + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    let started = Instant::now();
+    let paced = config.speed.is_finite() && config.speed > 0.0;
+    let first_timestamp = records.iter().find_map(|r| r.timestamp_ms);
+    let mut report = SimulationReport {
+        records: records.len(),
+        training_steps: 0,
+        inference_steps: 0,
+        failures: 0,
+        elapsed_ms: 0,
+        evaluation: None,
+    };
+    let mut pairs = Vec::new();
+
+    for record in records {
+        if let (true, Some(first), Some(at)) = (paced, first_timestamp, record.timestamp_ms) {
+            let due =
+                Duration::from_secs_f64(at.saturating_sub(first) as f64 / 1000.0 / config.speed);
+            if let Some(wait) = due.checked_sub(started.elapsed()) {
+                std::thread::sleep(wait);
+            }
+        }
+        match record.event.clone() {
+            TraceEvent::Training { input } => match algorithm.training_step(model, input) {
+                Ok(()) => report.training_steps += 1,
+                Err(_) => report.failures += 1,
+            },
+            TraceEvent::Inference { input } => match algorithm.inference_step(model, input) {
+                Ok(_) => report.inference_steps += 1,
+                Err(_) => report.failures += 1,
+            },
+            TraceEvent::Outcome { input, label } => match algorithm.inference_step(model, input) {
+                Ok(prediction) => {
+                    report.inference_steps += 1;
+                    pairs.push((
+                        prediction.score().unwrap_or(f64::NAN),
+                        label.score().unwrap_or(f64::NAN),
+                    ));
+                }
+                Err(_) => report.failures += 1,
+            },
+        }
+    }
+
+    if !pairs.is_empty() {
+        report.evaluation = Some(evaluate(&pairs)?);
+    }
+    report.elapsed_ms = started.elapsed().as_millis() as u64;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::LastValueAlgorithm;
+    use std::io::Write;
+
+    #[test]
+    fn test_replay_scores_outcomes_as_the_model_evolves() {
+        let mut trace = tempfile::NamedTempFile::new().unwrap();
+        writeln!(trace, r#"{{"kind":"outcome","input":0.0,"label":1.0}}"#).unwrap();
+        writeln!(
+            trace,
+            r#"{{"kind":"training","input":1.0,"timestamp_ms":5}}"#
+        )
+        .unwrap();
+        writeln!(trace).unwrap();
+        writeln!(trace, r#"{{"kind":"outcome","input":0.0,"label":1.0}}"#).unwrap();
+        let records = read_trace::<f64, f64>(trace.path()).unwrap();
+        assert_eq!(records.len(), 3);
+
+        let model = Model::with_parameters(vec![0.0]);
+        let report = simulate(
+            &model,
+            &LastValueAlgorithm,
+            &records,
+            &SimulationConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(report.training_steps, 1);
+        assert_eq!(report.inference_steps, 2);
+        assert_eq!(report.evaluation.unwrap().mae, 0.5);
+    }
+}
@@ -0,0 +1,319 @@
+use crate::algorithm::{numeric_class_label, Algorithm, Contributions, Datum, Uncertainty};
+use crate::bayesian::RegressionInput;
+use crate::errors::ModelError;
+use crate::model::Model;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::iter::Sum;
+use std::sync::Mutex;
+
+/// Approximate counts of string keys in fixed memory.
+///
+/// Estimates never undercount; with `width = ⌈e/ε⌉` and
+/// `depth = ⌈ln(1/δ)⌉` they overcount by more than `ε · total` with
+/// probability at most `δ`.
+#[derive(Debug, Clone)]
+pub struct CountMinSketch {
+    width: usize,
+    depth: usize,
+    counts: Vec<u64>,
+    total: u64,
+}
+
+impl CountMinSketch {
+    pub fn new(width: usize, depth: usize) -> Self {
+        let (width, depth) = (width.max(1), depth.max(1));
+        CountMinSketch {
+            width,
+            depth,
+            counts: vec![0; width * depth],
+            total: 0,
+        }
+    }
+
+    /// A sketch overcounting by at most `epsilon · total` with probability
+    /// `1 - delta`.
+    pub fn with_error(epsilon: f64, delta: f64) -> Self {
+        let width = (std::f64::consts::E / epsilon).ceil() as usize;
+        let depth = (1.0 / delta).ln().ceil() as usize;
+        Self::new(width, depth)
+    }
+
+    fn cells<'a>(&'a self, key: &'a str) -> impl Iterator<Item = usize> + 'a {
+        (0..self.depth).map(move |row| {
+            let mut hasher = DefaultHasher::new();
+            (row, key).hash(&mut hasher);
+            row * self.width + (hasher.finish() % self.width as u64) as usize
+        })
+    }
+
+    pub fn add(&mut self, key: &str, count: u64) {
+        let cells: Vec<usize> = self.cells(key).collect();
+        for cell in cells {
+            self.counts[cell] += count;
+        }
+        self.total += count;
+    }
+
+    pub fn estimate(&self, key: &str) -> u64 {
+        self.cells(key)
+            .map(|cell| self.counts[cell])
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Sum of every count added.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+}
+
+/// Sketched frequencies of one categorical field and its most frequent values.
+#[derive(Debug, Clone)]
+struct FieldCounts {
+    sketch: CountMinSketch,
+    /// Candidate heavy hitters with their estimated counts.
+    top: HashMap<String, u64>,
+}
+
+impl FieldCounts {
+    fn add(&mut self, value: &str, capacity: usize) {
+        self.sketch.add(value, 1);
+        let estimate = self.sketch.estimate(value);
+        if self.top.contains_key(value) || self.top.len() < capacity {
+            self.top.insert(value.to_string(), estimate);
+            return;
+        }
+        let smallest = self
+            .top
+            .iter()
+            .min_by_key(|(_, count)| **count)
+            .map(|(key, count)| (key.clone(), *count));
+        if let Some((key, _)) = smallest.filter(|(_, count)| *count < estimate) {
+            self.top.remove(&key);
+            self.top.insert(value.to_string(), estimate);
+        }
+    }
+}
+
+/// Sample with numeric features, categorical values and, for training, a
+/// label.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CategoricalInput<T> {
+    pub features: Vec<T>,
+    /// One value per categorical field, e.g. a merchant or device id.
+    pub categories: Vec<String>,
+    pub label: Option<T>,
+}
+
+impl<T: Datum> Datum for CategoricalInput<T> {
+    fn class_label(&self) -> Option<String> {
+        numeric_class_label(self.label.as_ref())
+    }
+
+    fn features(&self) -> Option<Vec<f64>> {
+        self.features.iter().map(Datum::score).collect()
+    }
+
+    fn with_features(&self, features: &[f64]) -> Option<Self> {
+        Some(CategoricalInput {
+            features: self.features.with_features(features)?,
+            categories: self.categories.clone(),
+            label: self.label.clone(),
+        })
+    }
+}
+
+/// Wraps a regression-style algorithm, appending to the numeric features the
+/// estimated frequency of each categorical value seen in training so far
+/// (its share of the samples, from a count-min sketch per field).
+///
+/// The counts are kept by the wrapper, in fixed memory, and only training
+/// steps add to them. The most frequent values of each field are tracked as
+/// heavy hitters.
+#[derive(Debug)]
+pub struct FrequencyFeatures<A> {
+    inner: A,
+    fields: usize,
+    heavy_hitters: usize,
+    counts: Mutex<Vec<FieldCounts>>,
+}
+
+impl<A> FrequencyFeatures<A> {
+    /// Sketches `fields` categorical fields with a 1% error at 99%
+    /// confidence, tracking the 10 most frequent values of each.
+    pub fn new(inner: A, fields: usize) -> Self {
+        Self::with_sketch(inner, fields, CountMinSketch::with_error(0.01, 0.01))
+    }
+
+    /// Uses copies of `sketch` (usually empty) for every field.
+    pub fn with_sketch(inner: A, fields: usize, sketch: CountMinSketch) -> Self {
+        let field = FieldCounts {
+            sketch,
+            top: HashMap::new(),
+        };
+        FrequencyFeatures {
+            inner,
+            fields,
+            heavy_hitters: 10,
+            counts: Mutex::new(vec![field; fields]),
+        }
+    }
+
+    /// Tracks the `k` most frequent values of each field.
+    pub fn with_heavy_hitters(mut self, k: usize) -> Self {
+        self.heavy_hitters = k;
+        self
+    }
+
+    /// Most frequent values of `field` with their estimated counts, most
+    /// frequent first.
+    pub fn heavy_hitters(&self, field: usize) -> Result<Vec<(String, u64)>, ModelError> {
+        let counts = self.counts.lock()?;
+        let field = counts
+            .get(field)
+            .ok_or_else(|| ModelError::InvalidInput(format!("no categorical field {}", field)))?;
+        let mut top: Vec<(String, u64)> = field.top.clone().into_iter().collect();
+        top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Ok(top)
+    }
+
+    /// The inner algorithm's input: the features followed by one frequency
+    /// per field. Training inputs are counted first.
+    fn derive<T: Float>(
+        &self,
+        x: CategoricalInput<T>,
+        count: bool,
+    ) -> Result<RegressionInput<T>, ModelError> {
+        if x.categories.len() != self.fields {
+            return Err(ModelError::InvalidInput(format!(
+                "expected {} categorical values, got {}",
+                self.fields,
+                x.categories.len()
+            )));
+        }
+        let mut counts = self.counts.lock()?;
+        let mut features = x.features;
+        for (field, value) in counts.iter_mut().zip(&x.categories) {
+            if count {
+                field.add(value, self.heavy_hitters);
+            }
+            let total = field.sketch.total().max(1);
+            let share = field.sketch.estimate(value) as f64 / total as f64;
+            features.push(T::from(share).unwrap_or_else(T::zero));
+        }
+        Ok(RegressionInput {
+            features,
+            label: x.label,
+        })
+    }
+}
+
+impl<T, A> Algorithm<T> for FrequencyFeatures<A>
+where
+    T: Float + Debug + Send + Sync + Sum + Datum,
+    A: Algorithm<T, Input = RegressionInput<T>>,
+{
+    type Input = CategoricalInput<T>;
+    type Output = A::Output;
+
+    fn training_step(&self, model: &Model<T>, x: CategoricalInput<T>) -> Result<(), ModelError> {
+        let x = self.derive(x, true)?;
+        self.inner.training_step(model, x)
+    }
+
+    fn inference_step(
+        &self,
+        model: &Model<T>,
+        x: CategoricalInput<T>,
+    ) -> Result<A::Output, ModelError> {
+        let x = self.derive(x, false)?;
+        self.inner.inference_step(model, x)
+    }
+
+    fn feedback_step(
+        &self,
+        model: &Model<T>,
+        x: CategoricalInput<T>,
+        outcome: A::Output,
+    ) -> Result<(), ModelError> {
+        let x = self.derive(x, false)?;
+        self.inner.feedback_step(model, x, outcome)
+    }
+
+    fn contributions(
+        &self,
+        model: &Model<T>,
+        x: CategoricalInput<T>,
+    ) -> Result<Contributions<T>, ModelError> {
+        let x = self.derive(x, false)?;
+        self.inner.contributions(model, x)
+    }
+
+    fn uncertainty(
+        &self,
+        model: &Model<T>,
+        x: CategoricalInput<T>,
+    ) -> Result<Uncertainty<T>, ModelError> {
+        let x = self.derive(x, false)?;
+        self.inner.uncertainty(model, x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bayesian::BayesianLinearRegression;
+
+    #[test]
+    fn test_sketch_bounds_and_heavy_hitters() {
+        let mut sketch = CountMinSketch::with_error(0.01, 0.01);
+        for i in 0..1000u64 {
+            sketch.add(&format!("key-{}", i % 100), 1);
+        }
+        sketch.add("hot", 500);
+        assert_eq!(sketch.total(), 1500);
+        assert!((500..=515).contains(&sketch.estimate("hot")));
+        assert!(sketch.estimate("key-7") >= 10);
+
+        // Rare merchants look suspicious: the label is 1 unless the merchant is common.
+        let algorithm = FrequencyFeatures::new(BayesianLinearRegression::new(2, 100.0, 1.0), 1)
+            .with_heavy_hitters(2);
+        let model = algorithm.inner.prior_model();
+        for i in 0..300 {
+            let merchant = match i % 3 {
+                0 => format!("rare-{}", i),
+                _ => "shop".to_string(),
+            };
+            let input = CategoricalInput {
+                features: vec![1.0],
+                label: Some(if merchant == "shop" { 0.0 } else { 1.0 }),
+                categories: vec![merchant],
+            };
+            algorithm.training_step(&model, input).unwrap();
+        }
+        let predict = |merchant: &str| {
+            let input = CategoricalInput {
+                features: vec![1.0],
+                categories: vec![merchant.to_string()],
+                label: None,
+            };
+            algorithm.inference_step(&model, input).unwrap()
+        };
+        assert!(predict("shop") < 0.2 && predict("never-seen") > 0.8);
+
+        let top = algorithm.heavy_hitters(0).unwrap();
+        assert_eq!(top[0], ("shop".to_string(), 200));
+        assert!(algorithm.heavy_hitters(1).is_err());
+        let wrong = CategoricalInput {
+            features: vec![1.0],
+            categories: vec![],
+            label: None,
+        };
+        assert!(algorithm.inference_step(&model, wrong).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use crate::layout::ModelLayout;
+use crate::model::StoredVersion;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Number of most moved parameters a [`SnapshotDiff`] lists by default.
+pub const DEFAULT_TOP_MOVED: usize = 10;
+
+/// One of the parameters that moved the most between two versions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MovedParameter<T> {
+    pub index: usize,
+    /// Layout group holding the parameter, if the model has a layout.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    pub from: T,
+    pub to: T,
+}
+
+/// A metadata field that differs between two versions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetadataChange {
+    pub field: String,
+    pub from: Value,
+    pub to: Value,
+}
+
+/// How the dense parameters changed between two stored versions of a model,
+/// served on `GET /model/snapshots/{a}/diff/{b}` for post-incident analysis:
+/// how far the parameters moved overall and by layout group, which ones
+/// moved the most, and what else changed.
+///
+/// Versions of different sizes are compared over the parameters they have
+/// in common. A change to or from a non-finite value counts as infinite, so
+/// such parameters rank first, and the norms become `null`.
+///
+/// # Examples
+///
+/// ```
+/// use oml::model::Model;
+/// use oml::snapshot_diff::SnapshotDiff;
+///
+/// let model = Model::with_parameters(vec![1.0f64, 2.0, 3.0]).with_delta_log(8);
+/// model.update_params(|params| {
+///     params[0] = 4.0;
+///     params[2] = -1.0;
+/// });
+/// let diff = SnapshotDiff::between(
+///     &model.stored_version(0).unwrap(),
+///     &model.stored_version(1).unwrap(),
+///     model.layout(),
+///     1,
+/// );
+/// assert_eq!((diff.changed, diff.delta_norm), (2, Some(5.0)));
+/// assert_eq!(diff.top_moved[0].index, 2);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotDiff<T> {
+    pub from_version: u64,
+    pub to_version: u64,
+    /// Number of parameters that differ.
+    pub changed: usize,
+    /// L2 norm of the change.
+    pub delta_norm: Option<f64>,
+    /// Largest change of a single parameter.
+    pub max_abs_delta: Option<f64>,
+    /// L2 norm of the change of each layout group.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub group_norms: BTreeMap<String, Option<f64>>,
+    /// Parameters that moved the most, largest change first.
+    pub top_moved: Vec<MovedParameter<T>>,
+    pub metadata_changes: Vec<MetadataChange>,
+}
+
+impl<T: Float> SnapshotDiff<T> {
+    /// Compares version `from` with version `to`, listing the `top` most
+    /// moved parameters.
+    pub fn between(
+        from: &StoredVersion<T>,
+        to: &StoredVersion<T>,
+        layout: &ModelLayout,
+        top: usize,
+    ) -> Self {
+        let group_of = |index: usize| {
+            layout
+                .groups()
+                .iter()
+                .find(|g| g.range().contains(&index))
+                .map(|g| g.name.clone())
+        };
+        let mut moved: Vec<(usize, f64)> = from
+            .parameters
+            .iter()
+            .zip(to.parameters.iter())
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(i, (a, b))| {
+                let delta = (*b - *a).abs().to_f64().unwrap_or(f64::INFINITY);
+                (i, if delta.is_nan() { f64::INFINITY } else { delta })
+            })
+            .collect();
+        let finite = |value: f64| Some(value).filter(|v| v.is_finite());
+        let mut group_norms: BTreeMap<String, f64> = layout
+            .groups()
+            .iter()
+            .map(|g| (g.name.clone(), 0.0))
+            .collect();
+        for &(i, delta) in &moved {
+            if let Some(norm) = group_of(i).and_then(|g| group_norms.get_mut(&g)) {
+                *norm += delta * delta;
+            }
+        }
+        let delta_norm = moved.iter().map(|(_, d)| d * d).sum::<f64>().sqrt();
+        let max_abs_delta = moved.iter().map(|(_, d)| *d).fold(0.0, f64::max);
+        let changed = moved.len();
+
+        moved.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        let top_moved = moved
+            .into_iter()
+            .take(top)
+            .map(|(index, _)| MovedParameter {
+                index,
+                group: group_of(index),
+                from: from.parameters[index],
+                to: to.parameters[index],
+            })
+            .collect();
+
+        let mut metadata_changes = Vec::new();
+        let mut compare = |field: &str, a: Value, b: Value| {
+            if a != b {
+                metadata_changes.push(MetadataChange {
+                    field: field.to_string(),
+                    from: a,
+                    to: b,
+                });
+            }
+        };
+        compare(
+            "parameter_count",
+            from.parameters.len().into(),
+            to.parameters.len().into(),
+        );
+        compare(
+            "published_at_ms",
+            from.published_at_ms.into(),
+            to.published_at_ms.into(),
+        );
+
+        SnapshotDiff {
+            from_version: from.version,
+            to_version: to.version,
+            changed,
+            delta_norm: finite(delta_norm),
+            max_abs_delta: finite(max_abs_delta),
+            group_norms: group_norms
+                .into_iter()
+                .map(|(name, norm)| (name, finite(norm.sqrt())))
+                .collect(),
+            top_moved,
+            metadata_changes,
+        }
+    }
+}
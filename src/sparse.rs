@@ -0,0 +1,316 @@
+use crate::algorithm::{numeric_class_label, Algorithm, Contributions, Datum};
+use crate::errors::ModelError;
+use crate::model::Model;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::iter::Sum;
+use std::sync::{PoisonError, RwLock};
+
+/// Lock shards of a [`SparseParameters`]; writers to different shards do
+/// not contend with readers of the others.
+const SHARDS: usize = 16;
+
+/// Parameters of a very high-dimensional model stored as a map from index
+/// to value, for models (e.g. over hashed features) where most parameters
+/// are never touched. Absent indices read as zero, and values set back to
+/// zero are dropped.
+///
+/// Created through [`Model::sparse`] and updated through
+/// [`Model::update_sparse`], which bumps the model version. A multi-index
+/// update is applied shard by shard, so a concurrent inference may see part
+/// of a step; the dense parameters of the model keep their snapshot
+/// guarantees.
+#[derive(Debug)]
+pub struct SparseParameters<T> {
+    dimension: usize,
+    shards: Vec<RwLock<HashMap<usize, T>>>,
+}
+
+/// The stored entries of sparse parameters, as saved with a model.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SparseEntries<T> {
+    pub dimension: usize,
+    /// Index and value of every non-zero parameter, by index.
+    pub entries: Vec<(usize, T)>,
+}
+
+impl<T: Float> SparseParameters<T> {
+    pub fn new(dimension: usize) -> Self {
+        SparseParameters {
+            dimension,
+            shards: (0..SHARDS).map(|_| RwLock::new(HashMap::new())).collect(),
+        }
+    }
+
+    /// Number of addressable parameters.
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// Number of non-zero parameters actually stored.
+    pub fn stored(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.read().unwrap_or_else(PoisonError::into_inner).len())
+            .sum()
+    }
+
+    fn check(&self, index: usize) -> Result<(), ModelError> {
+        if index >= self.dimension {
+            return Err(ModelError::InvalidInput(format!(
+                "index {} is out of the {} sparse parameters",
+                index, self.dimension
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, index: usize) -> Result<T, ModelError> {
+        self.check(index)?;
+        let shard = self.shards[index % SHARDS].read()?;
+        Ok(shard.get(&index).copied().unwrap_or_else(T::zero))
+    }
+
+    /// Values at `indices`, in order.
+    pub fn gather(&self, indices: &[usize]) -> Result<Vec<T>, ModelError> {
+        indices.iter().map(|i| self.get(*i)).collect()
+    }
+
+    /// Sets every index in `indices` to `update(index, current value)`.
+    pub(crate) fn update(
+        &self,
+        indices: &[usize],
+        mut update: impl FnMut(usize, T) -> T,
+    ) -> Result<(), ModelError> {
+        for index in indices {
+            self.check(*index)?;
+        }
+        for index in indices {
+            let mut shard = self.shards[index % SHARDS].write()?;
+            let current = shard.get(index).copied().unwrap_or_else(T::zero);
+            let value = update(*index, current);
+            if value == T::zero() {
+                shard.remove(index);
+            } else {
+                shard.insert(*index, value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces every parameter with those of `saved`, which must have the
+    /// same dimension.
+    pub(crate) fn replace(&self, saved: &SparseEntries<T>) -> Result<(), ModelError> {
+        if saved.dimension != self.dimension {
+            return Err(ModelError::InvalidInput(format!(
+                "expected {} sparse parameters, got {}",
+                self.dimension, saved.dimension
+            )));
+        }
+        for (index, _) in &saved.entries {
+            self.check(*index)?;
+        }
+        for shard in &self.shards {
+            shard.write()?.clear();
+        }
+        for (index, value) in &saved.entries {
+            self.shards[index % SHARDS].write()?.insert(*index, *value);
+        }
+        Ok(())
+    }
+
+    pub fn entries(&self) -> SparseEntries<T> {
+        let mut entries: Vec<(usize, T)> = self
+            .shards
+            .iter()
+            .flat_map(|shard| {
+                let shard = shard.read().unwrap_or_else(PoisonError::into_inner);
+                shard.iter().map(|(i, v)| (*i, *v)).collect::<Vec<_>>()
+            })
+            .collect();
+        entries.sort_by_key(|(index, _)| *index);
+        SparseEntries {
+            dimension: self.dimension,
+            entries,
+        }
+    }
+
+    pub fn from_entries(saved: &SparseEntries<T>) -> Result<Self, ModelError> {
+        let sparse = Self::new(saved.dimension);
+        for (index, value) in &saved.entries {
+            sparse.check(*index)?;
+            sparse.shards[index % SHARDS]
+                .write()?
+                .insert(*index, *value);
+        }
+        Ok(sparse)
+    }
+}
+
+/// Sample with named features, hashed into the parameters of a
+/// [`HashedLinearRegression`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HashedInput<T> {
+    /// Feature names and values; absent features are zero.
+    pub features: Vec<(String, T)>,
+    pub label: Option<T>,
+}
+
+impl<T: Datum> Datum for HashedInput<T> {
+    fn class_label(&self) -> Option<String> {
+        numeric_class_label(self.label.as_ref())
+    }
+}
+
+/// Linear regression over hashed features, trained by SGD on the sparse
+/// parameters of a [`Model::sparse`] model: a step reads and writes only
+/// the parameters of the features present in the sample (and the bias,
+/// parameter 0), whatever the dimension.
+#[derive(Debug, Clone)]
+pub struct HashedLinearRegression {
+    dimension: usize,
+    learning_rate: f64,
+}
+
+impl HashedLinearRegression {
+    /// Hashes features into `dimension - 1` weights, with a learning rate of 0.01.
+    pub fn new(dimension: usize) -> Self {
+        HashedLinearRegression {
+            dimension: dimension.max(2),
+            learning_rate: 0.01,
+        }
+    }
+
+    pub fn with_learning_rate(mut self, learning_rate: f64) -> Self {
+        self.learning_rate = learning_rate;
+        self
+    }
+
+    /// A model with `dimension` sparse parameters, all zero.
+    pub fn initial_model<T: Float + Debug + Send + Sync>(&self) -> Model<T> {
+        Model::sparse(self.dimension)
+    }
+
+    /// Parameter index of feature `name`.
+    pub fn index(&self, name: &str) -> usize {
+        let mut hasher = DefaultHasher::new();
+        name.hash(&mut hasher);
+        1 + (hasher.finish() % (self.dimension as u64 - 1)) as usize
+    }
+
+    /// Indices and values of the sample's features, the bias first.
+    fn terms<T: Float>(&self, x: &HashedInput<T>) -> (Vec<usize>, Vec<T>) {
+        let mut terms: Vec<(usize, T)> = vec![(0, T::one())];
+        for (name, value) in &x.features {
+            let index = self.index(name);
+            match terms.iter_mut().find(|(i, _)| *i == index) {
+                Some((_, sum)) => *sum = *sum + *value,
+                None => terms.push((index, *value)),
+            }
+        }
+        terms.into_iter().unzip()
+    }
+
+    fn sparse<'a, T>(&self, model: &'a Model<T>) -> Result<&'a SparseParameters<T>, ModelError>
+    where
+        T: Float + Debug + Send + Sync,
+    {
+        model
+            .sparse_params()
+            .filter(|sparse| sparse.dimension() == self.dimension)
+            .ok_or_else(|| {
+                ModelError::InvalidInput(format!(
+                    "the model must hold {} sparse parameters",
+                    self.dimension
+                ))
+            })
+    }
+}
+
+impl<T> Algorithm<T> for HashedLinearRegression
+where
+    T: Float + Debug + Send + Sync + Sum + Datum,
+{
+    type Input = HashedInput<T>;
+    type Output = T;
+
+    fn training_step(&self, model: &Model<T>, x: HashedInput<T>) -> Result<(), ModelError> {
+        let label = x
+            .label
+            .ok_or_else(|| ModelError::InvalidInput("training samples need a label".to_string()))?;
+        let (indices, values) = self.terms(&x);
+        let weights = self.sparse(model)?.gather(&indices)?;
+        let prediction: T = weights.iter().zip(&values).map(|(w, v)| *w * *v).sum();
+        let step = T::from(self.learning_rate).unwrap_or_else(T::zero) * (label - prediction);
+        let mut values = values.into_iter();
+        model.update_sparse(&indices, |_, weight| {
+            weight + step * values.next().unwrap_or_else(T::zero)
+        })
+    }
+
+    fn inference_step(&self, model: &Model<T>, x: HashedInput<T>) -> Result<T, ModelError> {
+        let (indices, values) = self.terms(&x);
+        let weights = self.sparse(model)?.gather(&indices)?;
+        Ok(weights.iter().zip(&values).map(|(w, v)| *w * *v).sum())
+    }
+
+    fn contributions(
+        &self,
+        model: &Model<T>,
+        x: HashedInput<T>,
+    ) -> Result<Contributions<T>, ModelError> {
+        let sparse = self.sparse(model)?;
+        Ok(Contributions {
+            features: x
+                .features
+                .iter()
+                .map(|(name, value)| Ok(sparse.get(self.index(name))? * *value))
+                .collect::<Result<_, ModelError>>()?,
+            bias: sparse.get(0)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashed_regression_touches_only_its_features() {
+        let algorithm = HashedLinearRegression::new(1 << 24).with_learning_rate(0.1);
+        let model = algorithm.initial_model::<f64>();
+        let sample = |user: &str, clicked: f64| HashedInput {
+            features: vec![(format!("user={}", user), 1.0), ("ads".to_string(), 1.0)],
+            label: Some(clicked),
+        };
+        for _ in 0..200 {
+            algorithm.training_step(&model, sample("a", 1.0)).unwrap();
+            algorithm.training_step(&model, sample("b", 0.0)).unwrap();
+        }
+        let predict = |user: &str| algorithm.inference_step(&model, sample(user, 0.0)).unwrap();
+        assert!((predict("a") - 1.0).abs() < 0.05 && predict("b").abs() < 0.05);
+
+        let sparse = model.sparse_params().unwrap();
+        assert_eq!(sparse.stored(), 4);
+        assert_eq!(model.version(), 400);
+        assert!(model.read_params().is_empty());
+        assert!(sparse.get(1 << 24).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        for file in ["model.json", "model.bin"] {
+            model.save(dir.path().join(file)).unwrap();
+            let restored = Model::<f64>::load(dir.path().join(file)).unwrap();
+            let entries = restored.sparse_params().unwrap().entries().entries;
+            assert_eq!(entries.len(), 4);
+            for ((i, v), (j, w)) in entries.iter().zip(sparse.entries().entries) {
+                assert!(*i == j && (v - w).abs() < 1e-12);
+            }
+        }
+        let wrong = HashedLinearRegression::new(10);
+        assert!(wrong.inference_step(&model, sample("a", 0.0)).is_err());
+    }
+}
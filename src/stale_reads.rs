@@ -0,0 +1,207 @@
+use crate::errors::ModelError;
+use crate::model::Model;
+use arc_swap::ArcSwapOption;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Response header carrying how stale the parameters that served an
+/// inference request were, in milliseconds; 0 for the live parameters.
+pub const STALENESS_HEADER: &str = "X-Oml-Staleness-Ms";
+
+/// How fresh the parameters serving the plain inference requests must be
+/// (see [`AppState::with_read_consistency`]).
+///
+/// [`AppState::with_read_consistency`]: crate::handlers::AppState::with_read_consistency
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum ReadConsistency {
+    /// Every request reads the live parameters.
+    #[default]
+    Fresh,
+    /// Requests read a copy of the model refreshed at most every
+    /// `refresh_ms` milliseconds, so inference never touches the
+    /// parameters training is publishing to.
+    Bounded { refresh_ms: u64 },
+}
+
+/// Parameters an inference request is served from.
+#[derive(Debug)]
+pub struct ServingSnapshot<T>
+where
+    T: Float + Debug + Send + Sync,
+{
+    pub model: Arc<Model<T>>,
+    /// Parameters version of the serving model the copy was taken at (at
+    /// least).
+    pub version: u64,
+    /// Time since the copy was taken, if training moved the live parameters
+    /// past it since; zero otherwise.
+    pub staleness: Duration,
+}
+
+#[derive(Debug)]
+struct Cached<T>
+where
+    T: Float + Debug + Send + Sync,
+{
+    model: Arc<Model<T>>,
+    version: u64,
+    taken: Instant,
+}
+
+/// Copy of the serving model that inference reads under
+/// [`ReadConsistency::Bounded`], taken on the first read and refreshed on
+/// the first read after the interval once training has moved the live
+/// parameters.
+///
+/// One reader takes the new copy while the others keep being served the
+/// current one, so a refresh never holds a request behind another.
+#[derive(Debug)]
+pub struct SnapshotCache<T>
+where
+    T: Float + Debug + Send + Sync,
+{
+    refresh: Duration,
+    current: ArcSwapOption<Cached<T>>,
+    refreshing: Mutex<()>,
+}
+
+impl<T> SnapshotCache<T>
+where
+    T: Float + Debug + Send + Sync,
+{
+    pub fn new(refresh: Duration) -> Self {
+        SnapshotCache {
+            refresh,
+            current: ArcSwapOption::empty(),
+            refreshing: Mutex::new(()),
+        }
+    }
+
+    pub fn refresh_interval(&self) -> Duration {
+        self.refresh
+    }
+
+    fn due(&self, cached: &Cached<T>, live: &Model<T>) -> bool {
+        cached.version != live.version() && cached.taken.elapsed() >= self.refresh
+    }
+
+    /// The copy of `live` to serve from, taken first if it is due. Returns
+    /// whether this call took it.
+    pub fn get(&self, live: &Model<T>) -> Result<(ServingSnapshot<T>, bool), ModelError> {
+        let current = self.current.load_full();
+        if let Some(cached) = current.as_ref().filter(|c| !self.due(c, live)) {
+            return Ok((serve(cached, live), false));
+        }
+        let _refreshing = match (&current, self.refreshing.try_lock()) {
+            (_, Ok(guard)) => guard,
+            (Some(cached), Err(_)) => return Ok((serve(cached, live), false)),
+            (None, Err(_)) => self
+                .refreshing
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        };
+        // Another reader may have refreshed it in the meantime.
+        if let Some(cached) = self.current.load_full().filter(|c| !self.due(c, live)) {
+            return Ok((serve(&cached, live), false));
+        }
+        // Read the version first: the copy may be newer, never older.
+        let version = live.version();
+        let cached = Arc::new(Cached {
+            model: Arc::new(live.fork()?),
+            version,
+            taken: Instant::now(),
+        });
+        self.current.store(Some(cached.clone()));
+        Ok((serve(&cached, live), true))
+    }
+}
+
+fn serve<T>(cached: &Cached<T>, live: &Model<T>) -> ServingSnapshot<T>
+where
+    T: Float + Debug + Send + Sync,
+{
+    let staleness = match cached.version == live.version() {
+        true => Duration::ZERO,
+        false => cached.taken.elapsed(),
+    };
+    ServingSnapshot {
+        model: cached.model.clone(),
+        version: cached.version,
+        staleness,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::versions::PARAMETERS_VERSION_HEADER;
+    use crate::handlers::AppState;
+    use crate::server::configure;
+    use crate::test::ScalingAlgorithm;
+    use actix_web::{test as actix_test, web, App};
+
+    #[actix_rt::test]
+    async fn test_bounded_reads_served_from_refreshed_snapshot() {
+        let refresh_ms = 200;
+        let state = AppState::new(Model::with_parameters(vec![1.0f64]), ScalingAlgorithm)
+            .with_read_consistency(ReadConsistency::Bounded { refresh_ms });
+        let model = state.model.clone();
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(configure::<f64, ScalingAlgorithm>),
+        )
+        .await;
+        let infer = || {
+            actix_test::TestRequest::post()
+                .uri("/inference?format=bare")
+                .set_json(1.0)
+                .to_request()
+        };
+        let header = |response: &actix_web::dev::ServiceResponse, name| {
+            response
+                .headers()
+                .get(name)
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_string()
+        };
+
+        let response = actix_test::call_service(&app, infer()).await;
+        assert_eq!(header(&response, STALENESS_HEADER), "0");
+        model.update_params(|params| params[0] = 5.0);
+        // Within the interval the copy is served, and reported stale.
+        let response = actix_test::call_service(&app, infer()).await;
+        assert_eq!(header(&response, PARAMETERS_VERSION_HEADER), "0");
+        assert_ne!(header(&response, STALENESS_HEADER), "0");
+        let prediction: f64 = actix_test::read_body_json(response).await;
+        assert_eq!(prediction, 1.0);
+
+        tokio::time::sleep(Duration::from_millis(refresh_ms)).await;
+        let response = actix_test::call_service(&app, infer()).await;
+        assert_eq!(header(&response, STALENESS_HEADER), "0");
+        assert_eq!(header(&response, PARAMETERS_VERSION_HEADER), "1");
+        let prediction: f64 = actix_test::read_body_json(response).await;
+        assert_eq!(prediction, 5.0);
+
+        // Fresh reads always see the live parameters.
+        let state = AppState::new(Model::with_parameters(vec![1.0f64]), ScalingAlgorithm);
+        let model = state.model.clone();
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(configure::<f64, ScalingAlgorithm>),
+        )
+        .await;
+        model.update_params(|params| params[0] = 5.0);
+        let response = actix_test::call_service(&app, infer()).await;
+        assert_eq!(header(&response, STALENESS_HEADER), "0");
+        let prediction: f64 = actix_test::read_body_json(response).await;
+        assert_eq!(prediction, 5.0);
+    }
+}
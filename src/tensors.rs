@@ -1,4 +1,6 @@
-#[derive(Debug)]
+use num_traits::Float;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Tensor<T> {
     shape: Vec<usize>,
     data: Vec<T>,
@@ -19,4 +21,72 @@ impl<T: Copy + Clone> Tensor<T> {
     pub fn get_data(&self) -> Vec<T> {
         self.data.clone()
     }
+
+    /// Element at `index` (one coordinate per dimension, row-major layout).
+    pub fn get(&self, index: &[usize]) -> T {
+        self.data[self.offset(index)]
+    }
+
+    fn offset(&self, index: &[usize]) -> usize {
+        if index.len() != self.shape.len() || index.iter().zip(&self.shape).any(|(i, n)| i >= n) {
+            panic!(
+                "Index {:?} is out of bounds for shape {:?}.",
+                index, self.shape
+            );
+        }
+        index
+            .iter()
+            .zip(&self.shape)
+            .fold(0, |offset, (i, n)| offset * n + i)
+    }
+}
+
+impl<T: Float> Tensor<T> {
+    /// `n × n` matrix with `value` on the diagonal.
+    pub fn diagonal(n: usize, value: T) -> Self {
+        let mut data = vec![T::zero(); n * n];
+        data.iter_mut().step_by(n + 1).for_each(|x| *x = value);
+        Tensor::new(vec![n, n], data)
+    }
+
+    /// Product of this matrix with the vector `v`.
+    pub fn matvec(&self, v: &[T]) -> Vec<T> {
+        let (rows, cols) = self.matrix_shape();
+        assert_eq!(cols, v.len(), "Vector does not match matrix shape.");
+        self.data
+            .chunks(cols.max(1))
+            .take(rows)
+            .map(|row| {
+                row.iter()
+                    .zip(v)
+                    .fold(T::zero(), |sum, (a, b)| sum + *a * *b)
+            })
+            .collect()
+    }
+
+    /// Adds `alpha · a bᵀ` to this matrix in place.
+    pub fn add_outer(&mut self, alpha: T, a: &[T], b: &[T]) {
+        let (rows, cols) = self.matrix_shape();
+        assert!(
+            rows == a.len() && cols == b.len(),
+            "Vectors do not match matrix shape."
+        );
+        for (row, ai) in self.data.chunks_mut(cols.max(1)).zip(a) {
+            for (x, bj) in row.iter_mut().zip(b) {
+                *x = *x + alpha * *ai * *bj;
+            }
+        }
+    }
+
+    /// Multiplies every element by `alpha` in place.
+    pub fn scale(&mut self, alpha: T) {
+        self.data.iter_mut().for_each(|x| *x = *x * alpha);
+    }
+
+    fn matrix_shape(&self) -> (usize, usize) {
+        match self.shape.as_slice() {
+            [rows, cols] => (*rows, *cols),
+            shape => panic!("Expected a matrix, got shape {:?}.", shape),
+        }
+    }
 }
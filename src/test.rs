@@ -0,0 +1,378 @@
+use crate::algorithm::{Algorithm, Contributions, Datum};
+use crate::errors::ModelError;
+use crate::evaluation::LabeledSample;
+use crate::handlers::{AppState, PredictionEnvelope};
+use crate::metrics::MetricsSnapshot;
+use crate::model::Model;
+use crate::seed;
+use crate::server::{ServerBuilder, ServerHandle};
+use actix_web::web;
+use num_traits::Float;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// [`DummyAlgorithm`](crate::algorithm::DummyAlgorithm) without the
+/// simulated delays: training multiplies every parameter by the input,
+/// inference returns the sum of the parameters times the input.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ScalingAlgorithm;
+
+impl<T> Algorithm<T> for ScalingAlgorithm
+where
+    T: Float + Debug + Send + Sync + Sum + Datum,
+{
+    type Input = T;
+    type Output = T;
+
+    fn training_step(&self, model: &Model<T>, x: T) -> Result<(), ModelError> {
+        let mut params = model.write_trainable();
+        params.iter_mut().for_each(|param| *param = *param * x);
+        Ok(())
+    }
+
+    fn inference_step(&self, model: &Model<T>, x: T) -> Result<T, ModelError> {
+        let params = model.read_params();
+        Ok(params.iter().map(|param| *param * x).sum())
+    }
+
+    fn contributions(&self, model: &Model<T>, x: T) -> Result<Contributions<T>, ModelError> {
+        let params = model.read_params();
+        Ok(Contributions {
+            features: params.iter().map(|param| *param * x).collect(),
+            bias: T::zero(),
+        })
+    }
+}
+
+/// Adds the input, times `rate`, to every parameter; inference returns the
+/// sum of the parameters times the input, like [`ScalingAlgorithm`].
+#[derive(Debug, Clone, Copy)]
+pub struct AdditiveAlgorithm {
+    pub rate: f64,
+}
+
+impl AdditiveAlgorithm {
+    pub fn new(rate: f64) -> Self {
+        AdditiveAlgorithm { rate }
+    }
+}
+
+impl Default for AdditiveAlgorithm {
+    fn default() -> Self {
+        AdditiveAlgorithm::new(1.0)
+    }
+}
+
+impl<T> Algorithm<T> for AdditiveAlgorithm
+where
+    T: Float + Debug + Send + Sync + Sum + Datum,
+{
+    type Input = T;
+    type Output = T;
+
+    fn training_step(&self, model: &Model<T>, x: T) -> Result<(), ModelError> {
+        let step = T::from(self.rate).unwrap_or_else(T::nan) * x;
+        let mut params = model.write_trainable();
+        params.iter_mut().for_each(|param| *param = *param + step);
+        Ok(())
+    }
+
+    fn inference_step(&self, model: &Model<T>, x: T) -> Result<T, ModelError> {
+        let params = model.read_params();
+        Ok(params.iter().map(|param| *param * x).sum())
+    }
+}
+
+/// Predicts the first parameter; training sets it to the input and
+/// feedback to the outcome.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LastValueAlgorithm;
+
+impl<T> Algorithm<T> for LastValueAlgorithm
+where
+    T: Float + Debug + Send + Sync + Sum + Datum,
+{
+    type Input = T;
+    type Output = T;
+
+    fn training_step(&self, model: &Model<T>, x: T) -> Result<(), ModelError> {
+        if let Some(param) = model.write_trainable().first_mut() {
+            *param = x;
+        }
+        Ok(())
+    }
+
+    fn inference_step(&self, model: &Model<T>, _x: T) -> Result<T, ModelError> {
+        Ok(model.read_params().first().copied().unwrap_or_else(T::zero))
+    }
+
+    fn feedback_step(&self, model: &Model<T>, _x: T, outcome: T) -> Result<(), ModelError> {
+        self.training_step(model, outcome)
+    }
+}
+
+/// An algorithm whose every step fails with [`ModelError::InvalidInput`],
+/// for exercising error paths.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FailingAlgorithm;
+
+impl<T> Algorithm<T> for FailingAlgorithm
+where
+    T: Float + Debug + Send + Sync + Sum + Datum,
+{
+    type Input = T;
+    type Output = T;
+
+    fn training_step(&self, _model: &Model<T>, _x: T) -> Result<(), ModelError> {
+        Err(ModelError::InvalidInput(
+            "training always fails".to_string(),
+        ))
+    }
+
+    fn inference_step(&self, _model: &Model<T>, _x: T) -> Result<T, ModelError> {
+        Err(ModelError::InvalidInput(
+            "inference always fails".to_string(),
+        ))
+    }
+}
+
+/// Wraps an algorithm and counts the steps it ran.
+#[derive(Debug, Default)]
+pub struct CountingAlgorithm<A> {
+    pub inner: A,
+    training_steps: AtomicUsize,
+    inference_steps: AtomicUsize,
+}
+
+impl<A> CountingAlgorithm<A> {
+    pub fn new(inner: A) -> Self {
+        CountingAlgorithm {
+            inner,
+            training_steps: AtomicUsize::new(0),
+            inference_steps: AtomicUsize::new(0),
+        }
+    }
+
+    /// Training steps run so far, successful or not.
+    pub fn training_steps(&self) -> usize {
+        self.training_steps.load(Ordering::SeqCst)
+    }
+
+    /// Inference steps run so far, successful or not.
+    pub fn inference_steps(&self) -> usize {
+        self.inference_steps.load(Ordering::SeqCst)
+    }
+}
+
+impl<T, A> Algorithm<T> for CountingAlgorithm<A>
+where
+    T: Float + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    type Input = A::Input;
+    type Output = A::Output;
+
+    fn training_step(&self, model: &Model<T>, x: A::Input) -> Result<(), ModelError> {
+        self.training_steps.fetch_add(1, Ordering::SeqCst);
+        self.inner.training_step(model, x)
+    }
+
+    fn inference_step(&self, model: &Model<T>, x: A::Input) -> Result<A::Output, ModelError> {
+        self.inference_steps.fetch_add(1, Ordering::SeqCst);
+        self.inner.inference_step(model, x)
+    }
+}
+
+/// Endless stream of values drawn uniformly from `[low, high)`, repeatable
+/// for a given `seed`.
+pub fn uniform_stream<T: Float>(low: f64, high: f64, seed: u64) -> impl Iterator<Item = T> {
+    let mut rng = seed::rng_with(Some(seed), "test::uniform_stream");
+    std::iter::repeat_with(move || T::from(rng.gen_range(low..high)).unwrap_or_else(T::zero))
+}
+
+/// Endless stream of samples of the linear relation `weights · x + bias`,
+/// with features uniform in `[-1, 1]` and label noise uniform in
+/// `[-noise, noise]`; repeatable for a given `seed`.
+pub fn linear_stream<T: Float>(
+    weights: Vec<T>,
+    bias: T,
+    noise: f64,
+    seed: u64,
+) -> impl Iterator<Item = LabeledSample<Vec<T>, T>> {
+    let mut rng = seed::rng_with(Some(seed), "test::linear_stream");
+    std::iter::repeat_with(move || {
+        let input: Vec<T> = weights
+            .iter()
+            .map(|_| T::from(rng.gen_range(-1.0..=1.0)).unwrap_or_else(T::zero))
+            .collect();
+        let error = T::from(noise * rng.gen_range(-1.0..=1.0)).unwrap_or_else(T::zero);
+        let label = weights
+            .iter()
+            .zip(&input)
+            .fold(bias + error, |sum, (w, x)| sum + *w * *x);
+        LabeledSample { input, label }
+    })
+}
+
+/// Copy of the current parameters of `model`.
+pub fn parameters<T: Float + Debug + Send + Sync>(model: &Model<T>) -> Vec<T> {
+    model.read_params().clone()
+}
+
+/// Panics unless every parameter of `model` is within `tolerance` of `expected`.
+#[track_caller]
+pub fn assert_parameters_close<T>(model: &Model<T>, expected: &[T], tolerance: T)
+where
+    T: Float + Debug + Send + Sync,
+{
+    let actual = parameters(model);
+    let close = actual.len() == expected.len()
+        && actual
+            .iter()
+            .zip(expected)
+            .all(|(a, e)| (*a - *e).abs() <= tolerance);
+    assert!(
+        close,
+        "parameters {:?} are not within {:?} of {:?}",
+        actual, tolerance, expected
+    );
+}
+
+/// Panics if any parameter of `model` is NaN or infinite.
+#[track_caller]
+pub fn assert_parameters_finite<T: Float + Debug + Send + Sync>(model: &Model<T>) {
+    let actual = parameters(model);
+    assert!(
+        actual.iter().all(|p| p.is_finite()),
+        "parameters {:?} are not all finite",
+        actual
+    );
+}
+
+/// Panics unless counter `name` of `metrics` equals `expected`; a counter
+/// never incremented counts as 0.
+#[track_caller]
+pub fn assert_counter(metrics: &MetricsSnapshot, name: &str, expected: u64) {
+    let actual = metrics.counters.get(name).copied().unwrap_or(0);
+    assert_eq!(actual, expected, "counter {}", name);
+}
+
+/// An oml server running in-process on an ephemeral localhost port, with
+/// a client for its endpoints. Must be started inside a tokio runtime, e.g.
+/// from an `#[actix_rt::test]`.
+pub struct TestServer<T, A>
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + 'static + Debug + Send + Sync + Sum,
+    A: Algorithm<T> + 'static + Send + Sync,
+{
+    state: web::Data<AppState<T, A>>,
+    handle: ServerHandle,
+    client: reqwest::Client,
+}
+
+impl<T, A> TestServer<T, A>
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + 'static + Debug + Send + Sync + Sum,
+    A: Algorithm<T> + 'static + Send + Sync,
+{
+    /// Serves `model` with `algorithm` and every optional stage disabled.
+    pub fn start(model: Model<T>, algorithm: A) -> std::io::Result<Self> {
+        Self::from_builder(ServerBuilder::new().model(model).algorithm(algorithm))
+    }
+
+    /// Starts a configured builder, additionally listening on an ephemeral
+    /// localhost port that [`TestServer::url`] points at.
+    pub fn from_builder(builder: ServerBuilder<T, A>) -> std::io::Result<Self> {
+        let server = builder.bind("127.0.0.1:0").build()?;
+        let state = server.state().clone();
+        Ok(TestServer {
+            state,
+            handle: server.start()?,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        *self
+            .handle
+            .addrs()
+            .last()
+            .expect("the server listens on a port")
+    }
+
+    /// Absolute URL of `path` on the server.
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr(), path)
+    }
+
+    pub fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    pub fn state(&self) -> &web::Data<AppState<T, A>> {
+        &self.state
+    }
+
+    pub fn model(&self) -> &Model<T> {
+        &self.state.model
+    }
+
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.state.metrics.snapshot()
+    }
+
+    /// `POST /inference`, returning the prediction of the envelope; a
+    /// non-2xx answer becomes [`ModelError::RemoteError`].
+    pub async fn infer(&self, input: &A::Input) -> Result<A::Output, ModelError> {
+        let response = self.post("/inference", input).await?;
+        let envelope: PredictionEnvelope<T, A::Output> = response.json().await?;
+        Ok(envelope.prediction)
+    }
+
+    /// `POST /training`; a non-2xx answer becomes [`ModelError::RemoteError`].
+    pub async fn train(&self, input: &A::Input) -> Result<(), ModelError> {
+        self.post("/training", input).await.map(|_| ())
+    }
+
+    /// Posts `body` as JSON to `path`, failing on a non-2xx answer.
+    pub async fn post<B: Serialize>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<reqwest::Response, ModelError> {
+        let response = self.client.post(self.url(path)).json(body).send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(ModelError::RemoteError(format!("{}: {}", status, body)));
+        }
+        Ok(response)
+    }
+
+    /// Stops the server without waiting for idle keep-alive connections.
+    pub async fn stop(self) -> std::io::Result<()> {
+        self.handle.stop(false).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_streams_are_repeatable() {
+        let a: Vec<f64> = uniform_stream(0.0, 1.0, 7).take(5).collect();
+        let b: Vec<f64> = uniform_stream(0.0, 1.0, 7).take(5).collect();
+        assert_eq!(a, b);
+        assert!(a.iter().all(|x| (0.0..1.0).contains(x)));
+
+        for sample in linear_stream(vec![2.0f64, -1.0], 0.5, 0.0, 3).take(10) {
+            let expected = 2.0 * sample.input[0] - sample.input[1] + 0.5;
+            assert!((sample.label - expected).abs() < 1e-12);
+        }
+    }
+}
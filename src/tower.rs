@@ -0,0 +1,155 @@
+use crate::algorithm::Algorithm;
+use crate::service::{
+    InferenceRequest, InferenceResponse, OmlService, ServiceError, TrainingOutcome,
+};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::future::Future;
+use std::iter::Sum;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Future returned by the [`::tower::Service`] implementations of
+/// [`OmlService`].
+pub type ServiceFuture<R> = Pin<Box<dyn Future<Output = Result<R, ServiceError>> + Send>>;
+
+/// A training call, the request type of `OmlService` as a training
+/// [`::tower::Service`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrainingRequest<I> {
+    pub input: I,
+    /// When the sample happened, admitted by the event clock (see
+    /// [`OmlService::train_at`]).
+    pub event_time_ms: Option<u64>,
+    /// The sample was forwarded by another replica and is not forwarded
+    /// again.
+    pub forwarded: bool,
+}
+
+impl<I> TrainingRequest<I> {
+    pub fn new(input: I) -> Self {
+        TrainingRequest {
+            input,
+            event_time_ms: None,
+            forwarded: false,
+        }
+    }
+}
+
+/// Inference through [`OmlService::infer`], so standard tower layers
+/// (timeout, concurrency limit, load shedding, buffering, retries) can wrap
+/// the in-process service as they would an HTTP client.
+///
+/// The service itself is always ready: backpressure comes from the layers,
+/// e.g. a concurrency limit under a load shedder.
+///
+/// # Examples
+///
+/// ```
+/// use oml::algorithm::DummyAlgorithm;
+/// use oml::handlers::AppState;
+/// use oml::model::Model;
+/// use oml::service::{InferenceRequest, InferenceResponse, OmlService};
+/// use std::time::Duration;
+/// use tower::{ServiceBuilder, ServiceExt};
+///
+/// # #[tokio::main] async fn main() {
+/// let state = AppState::new(Model::with_parameters(vec![1.0f32, 2.0]), DummyAlgorithm);
+/// let service = ServiceBuilder::new()
+///     .timeout(Duration::from_secs(1))
+///     .concurrency_limit(64)
+///     .service(OmlService::new(state));
+/// let response = service.oneshot(InferenceRequest::new(2.0)).await.unwrap();
+/// assert!(matches!(response, InferenceResponse::Prediction(p) if p == 6.0));
+/// # }
+/// ```
+impl<T, A> ::tower::Service<InferenceRequest<A::Input>> for OmlService<T, A>
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    type Response = InferenceResponse<T, A::Output>;
+    type Error = ServiceError;
+    type Future = ServiceFuture<Self::Response>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: InferenceRequest<A::Input>) -> Self::Future {
+        let service = self.clone();
+        Box::pin(async move { service.infer_or_fallback(request).await.map(|(r, _)| r) })
+    }
+}
+
+/// Training through [`OmlService::train`], or [`OmlService::train_at`] for
+/// samples with an event time.
+impl<T, A> ::tower::Service<TrainingRequest<A::Input>> for OmlService<T, A>
+where
+    T: Float + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + Sum + 'static,
+    A: Algorithm<T> + 'static,
+{
+    type Response = TrainingOutcome;
+    type Error = ServiceError;
+    type Future = ServiceFuture<Self::Response>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: TrainingRequest<A::Input>) -> Self::Future {
+        let service = self.clone();
+        Box::pin(async move {
+            match request.event_time_ms {
+                Some(time) => {
+                    service
+                        .train_at(request.input, time, request.forwarded)
+                        .await
+                }
+                None => service.train(request.input, request.forwarded).await,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::AppState;
+    use crate::model::Model;
+    use crate::test::ScalingAlgorithm;
+    use std::time::Duration;
+    use tower::{ServiceBuilder, ServiceExt};
+
+    #[actix_rt::test]
+    async fn test_layers_compose_around_in_process_service() {
+        let state = AppState::new(Model::with_parameters(vec![1.0f64]), ScalingAlgorithm);
+        let service = OmlService::new(state);
+        let trainer = ServiceBuilder::new()
+            .concurrency_limit(1)
+            .service(service.clone());
+        let outcome = trainer.oneshot(TrainingRequest::new(2.0)).await.unwrap();
+        assert_eq!(outcome, TrainingOutcome::Applied);
+
+        let inference = ServiceBuilder::new()
+            .timeout(Duration::from_secs(5))
+            .service(service.clone());
+        let response = inference
+            .clone()
+            .oneshot(InferenceRequest::new(3.0))
+            .await
+            .unwrap();
+        assert!(matches!(response, InferenceResponse::Prediction(p) if p == 6.0));
+
+        // Errors keep their kind through the boxing layers.
+        let mut request = InferenceRequest::new(3.0);
+        request.model = Some("missing".to_string());
+        let error = inference.oneshot(request).await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ServiceError>(),
+            Some(ServiceError::NotFound(_))
+        ));
+        assert_eq!(service.metrics().counters["training_steps"], 1);
+    }
+}
@@ -0,0 +1,272 @@
+use crate::algorithm::{Algorithm, Datum};
+use crate::errors::ModelError;
+use crate::evaluation::LabeledSample;
+use crate::model::Model;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::sync::{Arc, Mutex};
+
+/// Configuration of the successive-halving tuner.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TunerConfig {
+    /// Labeled samples each surviving candidate sees per rung.
+    pub rung_samples: u64,
+    /// Only `1 / reduction` of the candidates (rounded up) survive a rung.
+    pub reduction: usize,
+}
+
+impl Default for TunerConfig {
+    fn default() -> Self {
+        TunerConfig {
+            rung_samples: 100,
+            reduction: 2,
+        }
+    }
+}
+
+/// Progress of one candidate configuration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CandidateStatus {
+    pub name: String,
+    pub alive: bool,
+    pub samples: u64,
+    /// Mean absolute prequential error over the current (or last) rung.
+    pub rung_error: f64,
+    /// Rung after which the candidate was dropped.
+    pub eliminated_at: Option<u32>,
+}
+
+/// State of the search, as reported by `GET /tuning`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TunerStatus {
+    pub rung: u32,
+    pub candidates: Vec<CandidateStatus>,
+    /// Name of the promoted configuration once the race is decided.
+    pub winner: Option<String>,
+}
+
+struct Candidate<T, A>
+where
+    T: Float + Debug + Send + Sync,
+{
+    name: String,
+    algorithm: Arc<A>,
+    model: Model<T>,
+    samples: u64,
+    rung_error: f64,
+    rung_samples: u64,
+    eliminated_at: Option<u32>,
+}
+
+struct TunerState<T, A>
+where
+    T: Float + Debug + Send + Sync,
+{
+    candidates: Vec<Candidate<T, A>>,
+    rung: u32,
+    winner: Option<usize>,
+}
+
+/// Online hyperparameter search by racing configurations on the live stream.
+///
+/// Every candidate trains its own copy of the model on each labeled sample
+/// after first predicting it (prequential evaluation). At the end of each
+/// rung the candidates with the highest mean error are dropped until one
+/// remains; that winner is promoted to the serving slot.
+pub struct Tuner<T, A>
+where
+    T: Float + Debug + Send + Sync,
+{
+    config: TunerConfig,
+    state: Mutex<TunerState<T, A>>,
+}
+
+impl<T, A> Tuner<T, A>
+where
+    T: Float + Debug + Send + Sync + Sum,
+    A: Algorithm<T>,
+{
+    /// Starts a race between `candidates`, each from a copy of `parameters`.
+    pub fn new(config: TunerConfig, candidates: Vec<(String, A)>, parameters: &[T]) -> Self {
+        let candidates = candidates
+            .into_iter()
+            .map(|(name, algorithm)| Candidate {
+                name,
+                algorithm: Arc::new(algorithm),
+                model: Model::with_parameters(parameters.to_vec()),
+                samples: 0,
+                rung_error: 0.0,
+                rung_samples: 0,
+                eliminated_at: None,
+            })
+            .collect::<Vec<_>>();
+        let winner = (candidates.len() == 1).then_some(0);
+        Tuner {
+            config,
+            state: Mutex::new(TunerState {
+                candidates,
+                rung: 0,
+                winner,
+            }),
+        }
+    }
+
+    /// Predicts then trains every surviving candidate on `sample`.
+    ///
+    /// Returns `true` when this sample decided the race.
+    pub fn observe(&self, sample: &LabeledSample<A::Input, A::Output>) -> Result<bool, ModelError> {
+        let mut state = self.state.lock()?;
+        if state.winner.is_some() {
+            return Ok(false);
+        }
+        let label = sample.label.score().unwrap_or(f64::NAN);
+        for candidate in state
+            .candidates
+            .iter_mut()
+            .filter(|c| c.eliminated_at.is_none())
+        {
+            let prediction = candidate
+                .algorithm
+                .inference_step(&candidate.model, sample.input.clone())?;
+            let error = (prediction.score().unwrap_or(f64::NAN) - label).abs();
+            // A diverged candidate is ranked last rather than poisoning the mean.
+            candidate.rung_error += if error.is_finite() { error } else { f64::MAX };
+            candidate.rung_samples += 1;
+            candidate.samples += 1;
+            candidate
+                .algorithm
+                .training_step(&candidate.model, sample.input.clone())?;
+        }
+
+        let rung_done = state
+            .candidates
+            .iter()
+            .filter(|c| c.eliminated_at.is_none())
+            .all(|c| c.rung_samples >= self.config.rung_samples);
+        if !rung_done {
+            return Ok(false);
+        }
+
+        let rung = state.rung + 1;
+        let mut alive: Vec<(usize, f64)> = state
+            .candidates
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.eliminated_at.is_none())
+            .map(|(i, c)| (i, c.rung_error / c.rung_samples as f64))
+            .collect();
+        alive.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let keep = alive.len().div_ceil(self.config.reduction.max(2));
+        for &(index, _) in &alive[keep..] {
+            state.candidates[index].eliminated_at = Some(rung);
+        }
+        state.rung = rung;
+        if keep == 1 {
+            state.winner = Some(alive[0].0);
+        }
+        for candidate in state.candidates.iter_mut() {
+            if candidate.eliminated_at.is_none() {
+                candidate.rung_error = 0.0;
+                candidate.rung_samples = 0;
+            }
+        }
+        Ok(keep == 1)
+    }
+
+    /// Algorithm of the winning configuration, once decided.
+    pub fn winning_algorithm(&self) -> Option<Arc<A>> {
+        let state = self.state.lock().ok()?;
+        state
+            .winner
+            .map(|index| state.candidates[index].algorithm.clone())
+    }
+
+    /// Parameters trained by the winning configuration, once decided.
+    pub fn winning_parameters(&self) -> Result<Option<Vec<T>>, ModelError> {
+        let state = self.state.lock()?;
+        Ok(state
+            .winner
+            .map(|index| state.candidates[index].model.read_params().clone()))
+    }
+
+    pub fn status(&self) -> Result<TunerStatus, ModelError> {
+        let state = self.state.lock()?;
+        Ok(TunerStatus {
+            rung: state.rung,
+            candidates: state
+                .candidates
+                .iter()
+                .map(|c| CandidateStatus {
+                    name: c.name.clone(),
+                    alive: c.eliminated_at.is_none(),
+                    samples: c.samples,
+                    rung_error: if c.rung_samples > 0 {
+                        c.rung_error / c.rung_samples as f64
+                    } else {
+                        0.0
+                    },
+                    eliminated_at: c.eliminated_at,
+                })
+                .collect(),
+            winner: state.winner.map(|i| state.candidates[i].name.clone()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Moves the single parameter toward the input by `rate`; predicts the parameter.
+    struct TrackAlgorithm {
+        rate: f32,
+    }
+
+    impl Algorithm<f32> for TrackAlgorithm {
+        type Input = f32;
+        type Output = f32;
+
+        fn training_step(&self, model: &Model<f32>, x: f32) -> Result<(), ModelError> {
+            let mut params = model.write_params();
+            params[0] += self.rate * (x - params[0]);
+            Ok(())
+        }
+
+        fn inference_step(&self, model: &Model<f32>, _x: f32) -> Result<f32, ModelError> {
+            Ok(model.read_params()[0])
+        }
+    }
+
+    #[test]
+    fn test_successive_halving_promotes_best_configuration() {
+        let candidates = [0.0, 0.5, 1.0]
+            .iter()
+            .map(|&rate| (format!("rate={}", rate), TrackAlgorithm { rate }))
+            .collect();
+        let tuner = Tuner::new(
+            TunerConfig {
+                rung_samples: 2,
+                reduction: 2,
+            },
+            candidates,
+            &[0.0],
+        );
+        let sample = LabeledSample {
+            input: 3.0,
+            label: 3.0,
+        };
+
+        let mut decided = false;
+        for _ in 0..4 {
+            decided = tuner.observe(&sample).unwrap();
+        }
+        assert!(decided);
+        let status = tuner.status().unwrap();
+        assert_eq!(status.winner.as_deref(), Some("rate=1"));
+        assert_eq!(status.candidates[0].eliminated_at, Some(1));
+        assert_eq!(status.candidates[1].eliminated_at, Some(2));
+        assert_eq!(tuner.winning_parameters().unwrap().unwrap(), vec![3.0]);
+    }
+}
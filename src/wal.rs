@@ -0,0 +1,290 @@
+use crate::errors::ModelError;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+/// One logged update: the new values of the parameters it changed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Position of the entry in the log, from 1.
+    pub sequence: u64,
+    /// Index and new value of each changed parameter.
+    pub changes: Vec<(usize, f64)>,
+}
+
+#[derive(Debug)]
+struct LogState {
+    /// Sequence of the last entry written.
+    position: u64,
+    /// Segment being appended to; `None` starts a new one.
+    segment: Option<File>,
+}
+
+/// Append-only log of the updates published to a model's dense parameters,
+/// so a crashed server can rebuild them from its last checkpoint and the
+/// entries logged after it (see [`CheckpointConfig::with_write_ahead_log`]).
+///
+/// The model appends an entry while publishing each write (see
+/// [`Model::set_write_ahead_log`]), before readers see it, so the log
+/// follows the order of the versions. Entries are appended in bincode to
+/// `wal-<sequence>.log` segments; a checkpoint starts a new segment and
+/// removes those it covers. An entry torn by a crash is ignored, as is
+/// everything after it.
+///
+/// Every entry reaches the operating system before the write is published,
+/// which survives a crash of the process; [`WriteAheadLog::with_sync`] also
+/// flushes it to the disk, which survives a crash of the machine at the cost
+/// of a disk flush per training step. A write whose append fails is not
+/// published and fails the training step that made it (see
+/// [`Model::logged_step`]); such appends are counted in
+/// [`WriteAheadLog::failures`].
+///
+/// [`CheckpointConfig::with_write_ahead_log`]: crate::checkpoint::CheckpointConfig::with_write_ahead_log
+/// [`Model::set_write_ahead_log`]: crate::model::Model::set_write_ahead_log
+/// [`Model::logged_step`]: crate::model::Model::logged_step
+#[derive(Debug)]
+pub struct WriteAheadLog {
+    directory: PathBuf,
+    sync: bool,
+    state: Mutex<LogState>,
+    failures: AtomicU64,
+}
+
+impl WriteAheadLog {
+    /// Opens the log in `directory`, created if missing, continuing after
+    /// the last entry found there.
+    pub fn open(directory: impl Into<PathBuf>) -> Result<Self, ModelError> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+        let log = WriteAheadLog {
+            directory,
+            sync: false,
+            state: Mutex::new(LogState {
+                position: 0,
+                segment: None,
+            }),
+            failures: AtomicU64::new(0),
+        };
+        let position = match log.segments()?.last() {
+            Some(first) => read_segment(&log.path(*first))
+                .last()
+                .map_or(first.saturating_sub(1), |entry| entry.sequence),
+            None => 0,
+        };
+        log.lock().position = position;
+        Ok(log)
+    }
+
+    /// Flushes every entry to the disk before the write is published.
+    pub fn with_sync(mut self, sync: bool) -> Self {
+        self.sync = sync;
+        self
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LogState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn path(&self, first: u64) -> PathBuf {
+        self.directory.join(format!("wal-{:020}.log", first))
+    }
+
+    /// First sequences of the segments on disk, oldest first.
+    fn segments(&self) -> Result<Vec<u64>, ModelError> {
+        let mut segments: Vec<u64> = fs::read_dir(&self.directory)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .strip_prefix("wal-")?
+                    .strip_suffix(".log")?
+                    .parse()
+                    .ok()
+            })
+            .collect();
+        segments.sort_unstable();
+        Ok(segments)
+    }
+
+    /// Sequence of the last entry written, 0 for an empty log.
+    pub fn position(&self) -> u64 {
+        self.lock().position
+    }
+
+    /// Appends the changes of an update and returns the entry's sequence.
+    pub fn append(&self, changes: Vec<(usize, f64)>) -> Result<u64, ModelError> {
+        let mut state = self.lock();
+        let entry = LogEntry {
+            sequence: state.position + 1,
+            changes,
+        };
+        let bytes = bincode::serialize(&entry)?;
+        let path = self.path(entry.sequence);
+        let written = (|| {
+            let file = match &mut state.segment {
+                Some(file) => file,
+                None => state
+                    .segment
+                    .insert(OpenOptions::new().create(true).append(true).open(path)?),
+            };
+            file.write_all(&bytes)?;
+            if self.sync {
+                file.sync_data()?;
+            }
+            Ok::<_, std::io::Error>(())
+        })();
+        // A failed append may leave a torn entry behind: later ones go to a
+        // new segment, past its sequence.
+        state.position = entry.sequence;
+        if let Err(e) = written {
+            state.segment = None;
+            return Err(e.into());
+        }
+        Ok(entry.sequence)
+    }
+
+    /// Counts an append that failed.
+    pub(crate) fn record_failure(&self) {
+        self.failures.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Appends that failed since the log was opened.
+    pub fn failures(&self) -> u64 {
+        self.failures.load(Ordering::SeqCst)
+    }
+
+    /// Entries logged after sequence `position`, in order.
+    pub fn entries_after(&self, position: u64) -> Result<Vec<LogEntry>, ModelError> {
+        let segments = self.segments()?;
+        let mut entries = Vec::new();
+        for (i, first) in segments.iter().enumerate() {
+            // Skip segments ending before `position`.
+            if segments
+                .get(i + 1)
+                .is_some_and(|next| *next <= position + 1)
+            {
+                continue;
+            }
+            entries.extend(
+                read_segment(&self.path(*first))
+                    .into_iter()
+                    .filter(|entry| entry.sequence > position),
+            );
+        }
+        Ok(entries)
+    }
+
+    /// Starts a new segment and removes the segments holding only entries
+    /// up to `position`, once a checkpoint covers them.
+    pub fn truncate_through(&self, position: u64) -> Result<(), ModelError> {
+        let mut state = self.lock();
+        state.segment = None;
+        let segments = self.segments()?;
+        for (i, first) in segments.iter().enumerate() {
+            let last = match segments.get(i + 1) {
+                Some(next) => next - 1,
+                None => state.position,
+            };
+            if last <= position {
+                fs::remove_file(self.path(*first))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Entries of a segment, up to the first unreadable one.
+fn read_segment(path: &Path) -> Vec<LogEntry> {
+    let Ok(bytes) = fs::read(path) else {
+        return Vec::new();
+    };
+    let mut rest = bytes.as_slice();
+    let mut entries = Vec::new();
+    while !rest.is_empty() {
+        match bincode::deserialize_from(&mut rest) {
+            Ok(entry) => entries.push(entry),
+            Err(_) => break,
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::{CheckpointConfig, Checkpointer};
+    use crate::model::Model;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_crashed_model_rebuilt_from_checkpoint_and_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = CheckpointConfig::new(dir.path()).with_write_ahead_log(false);
+        let checkpointer = Checkpointer::<f64>::new(config.clone()).unwrap();
+        let model = Model::with_parameters(vec![0.0f64; 4]);
+        assert_eq!(checkpointer.restore(&model).unwrap(), None);
+        for step in 1..=3 {
+            model.update_params(|params| params[0] = step as f64);
+        }
+        checkpointer.save(&model).unwrap();
+        for step in 4..=6 {
+            model.update_params(|params| params[step % 4] = step as f64);
+        }
+        // Replacing the parameters is logged like a training step.
+        model.replace_parameters(vec![7.0, 4.0, 5.0, 6.0]).unwrap();
+        let log = checkpointer.log().unwrap();
+        assert_eq!(log.position(), 7);
+        // Only the entries after the checkpoint are kept.
+        assert_eq!(fs::read_dir(dir.path().join("wal")).unwrap().count(), 1);
+        assert_eq!(log.entries_after(0).unwrap()[0].sequence, 4);
+        let expected = model.read_params().clone();
+
+        // The process dies without a final checkpoint, mid-way through an
+        // append.
+        let segment = fs::read_dir(dir.path().join("wal"))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        let mut bytes = fs::read(&segment).unwrap();
+        bytes.extend_from_slice(&[8, 0, 0]);
+        fs::write(&segment, bytes).unwrap();
+        drop(model);
+        drop(checkpointer);
+
+        let restarted = Checkpointer::<f64>::new(config).unwrap();
+        let model = Model::with_parameters(vec![0.0f64; 4]);
+        assert_eq!(restarted.restore(&model).unwrap(), Some(1));
+        assert_eq!(restarted.replayed_entries(), 4);
+        assert_eq!(model.read_params().clone(), expected);
+        // New entries continue the sequence.
+        model.update_params(|params| params[0] = 8.0);
+        assert_eq!(restarted.log().unwrap().position(), 8);
+        assert_eq!(restarted.log().unwrap().failures(), 0);
+    }
+
+    #[test]
+    fn test_failed_append_fails_the_step() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = Arc::new(WriteAheadLog::open(dir.path().join("wal")).unwrap());
+        let model = Model::with_parameters(vec![0.0f64; 2]);
+        model.set_write_ahead_log(log.clone()).unwrap();
+        let version = model.version();
+
+        // The segment cannot be created any more.
+        fs::remove_dir_all(dir.path().join("wal")).unwrap();
+        let step = model.logged_step(|| {
+            model.write_params()[0] = 1.0;
+            Ok(())
+        });
+        assert!(matches!(step, Err(ModelError::StorageError(_))));
+        assert_eq!(model.read_params()[0], 0.0);
+        assert_eq!(model.version(), version);
+        assert_eq!(log.failures(), 1);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::metrics::Metrics;
+use crate::model::Model;
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Response header flagging a prediction of a model still warming up.
+pub const COLD_HEADER: &str = "X-Oml-Cold";
+
+/// A gauge the model must bring past `value` to count as warm, e.g.
+/// `holdout_mae` below 0.1 or `holdout_accuracy` above 0.8.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricThreshold {
+    pub gauge: String,
+    pub value: f64,
+    /// Warm once the gauge is at least `value`; at most `value` otherwise.
+    #[serde(default)]
+    pub higher_is_better: bool,
+}
+
+impl MetricThreshold {
+    fn passed(&self, metrics: &Metrics) -> bool {
+        match metrics.gauge(&self.gauge) {
+            Some(current) if self.higher_is_better => current >= self.value,
+            Some(current) => current <= self.value,
+            None => false,
+        }
+    }
+}
+
+/// When the serving model counts as warm.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WarmupConfig {
+    /// Training steps the model must have seen, saved ones included.
+    pub min_samples: u64,
+    /// Also warm once this threshold is passed, whatever the sample count.
+    #[serde(default)]
+    pub metric: Option<MetricThreshold>,
+}
+
+/// What serves inference requests while the model is cold.
+#[derive(Debug, Clone)]
+pub enum WarmupFallback<T>
+where
+    T: Float + Debug + Send + Sync,
+{
+    /// The model itself, with its responses flagged as cold.
+    Flag,
+    /// A constant prediction, e.g. the base rate; ignores `explain` and
+    /// `uncertainty`.
+    Constant(f64),
+    /// A prior model run by the same algorithm, e.g. trained offline.
+    Prior(Arc<Model<T>>),
+}
+
+/// Warm-up policy of the serving model: until it has seen
+/// [`WarmupConfig::min_samples`] training samples or passed its metric
+/// threshold, inference is flagged with [`COLD_HEADER`] or served by a
+/// [`WarmupFallback`] rather than by an unfitted model.
+///
+/// A model stays warm once it has been warm, so a metric dipping back
+/// over the threshold does not switch the fallback on again.
+///
+/// # Examples
+///
+/// ```
+/// use oml::metrics::Metrics;
+/// use oml::model::Model;
+/// use oml::warmup::{Warmup, WarmupConfig, WarmupFallback};
+///
+/// let config = WarmupConfig { min_samples: 2, metric: None };
+/// let warmup = Warmup::new(config, WarmupFallback::Constant(0.5));
+/// let (model, metrics) = (Model::with_parameters(vec![0.0f64]), Metrics::new());
+/// model.record_training_step();
+/// assert!(warmup.is_cold(&model, &metrics));
+/// model.record_training_step();
+/// assert!(!warmup.is_cold(&model, &metrics));
+/// ```
+#[derive(Debug)]
+pub struct Warmup<T>
+where
+    T: Float + Debug + Send + Sync,
+{
+    config: WarmupConfig,
+    fallback: WarmupFallback<T>,
+    warm: AtomicBool,
+}
+
+impl<T> Warmup<T>
+where
+    T: Float + Debug + Send + Sync,
+{
+    pub fn new(config: WarmupConfig, fallback: WarmupFallback<T>) -> Self {
+        Warmup {
+            config,
+            fallback,
+            warm: AtomicBool::new(false),
+        }
+    }
+
+    pub fn config(&self) -> &WarmupConfig {
+        &self.config
+    }
+
+    pub fn fallback(&self) -> &WarmupFallback<T> {
+        &self.fallback
+    }
+
+    /// Whether `model` is still warming up, judged by its training step
+    /// count and the gauges in `metrics`.
+    pub fn is_cold(&self, model: &Model<T>, metrics: &Metrics) -> bool {
+        if self.warm.load(Ordering::SeqCst) {
+            return false;
+        }
+        let warm = model.training_steps() >= self.config.min_samples
+            || self
+                .config
+                .metric
+                .as_ref()
+                .is_some_and(|threshold| threshold.passed(metrics));
+        if warm {
+            self.warm.store(true, Ordering::SeqCst);
+        }
+        !warm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metric_threshold_warms_model_for_good() {
+        let config = WarmupConfig {
+            min_samples: 100,
+            metric: Some(MetricThreshold {
+                gauge: "holdout_mae".to_string(),
+                value: 0.1,
+                higher_is_better: false,
+            }),
+        };
+        let warmup = Warmup::new(config, WarmupFallback::Flag);
+        let (model, metrics) = (Model::with_parameters(vec![0.0f32]), Metrics::new());
+        assert!(warmup.is_cold(&model, &metrics));
+        metrics.set_gauge("holdout_mae", 0.5);
+        assert!(warmup.is_cold(&model, &metrics));
+        metrics.set_gauge("holdout_mae", 0.05);
+        assert!(!warmup.is_cold(&model, &metrics));
+        metrics.set_gauge("holdout_mae", 0.5);
+        assert!(!warmup.is_cold(&model, &metrics));
+    }
+}
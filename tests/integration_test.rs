@@ -1 +1,69 @@
+use oml::evaluation::{EvaluationReport, EvaluationRequest, LabeledSample};
+use oml::model::Model;
+use oml::test::{
+    assert_counter, assert_parameters_close, linear_stream, uniform_stream, CountingAlgorithm,
+    FailingAlgorithm, ScalingAlgorithm, TestServer,
+};
 
+#[actix_rt::test]
+async fn test_training_and_inference_over_http() {
+    let server = TestServer::start(
+        Model::with_parameters(vec![1.0f64, 2.0]),
+        CountingAlgorithm::new(ScalingAlgorithm),
+    )
+    .unwrap();
+
+    let inputs: Vec<f64> = uniform_stream(0.5, 1.5, 42).take(3).collect();
+    for x in &inputs {
+        server.train(x).await.unwrap();
+    }
+    let factor: f64 = inputs.iter().product();
+    assert_parameters_close(server.model(), &[factor, 2.0 * factor], 1e-9);
+
+    let prediction = server.infer(&2.0).await.unwrap();
+    assert!((prediction - 6.0 * factor).abs() < 1e-9);
+    assert_eq!(server.state().algorithm.training_steps(), 3);
+    assert_eq!(server.state().algorithm.inference_steps(), 1);
+    assert_counter(&server.metrics(), "training_steps", 3);
+    assert_counter(&server.metrics(), "inference_steps", 1);
+
+    server.stop().await.unwrap();
+}
+
+#[actix_rt::test]
+async fn test_algorithm_failures_are_reported() {
+    let server = TestServer::start(Model::with_parameters(vec![1.0f32]), FailingAlgorithm).unwrap();
+
+    assert!(server.train(&1.0).await.is_err());
+    assert!(server.infer(&1.0).await.is_err());
+    assert_parameters_close(server.model(), &[1.0], 0.0);
+    assert_counter(&server.metrics(), "training_steps", 0);
+
+    server.stop().await.unwrap();
+}
+
+#[actix_rt::test]
+async fn test_evaluate_scores_a_generated_batch() {
+    let server = TestServer::start(Model::with_parameters(vec![3.0f64]), ScalingAlgorithm).unwrap();
+
+    let samples = linear_stream(vec![3.0], 0.0, 0.0, 7)
+        .take(20)
+        .map(|sample| LabeledSample {
+            input: sample.input[0],
+            label: sample.label,
+        })
+        .collect();
+    let report: EvaluationReport = server
+        .post("/evaluate", &EvaluationRequest { samples })
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report.samples, 20);
+    assert!(report.mae < 1e-9);
+    // Evaluation never trains.
+    assert_parameters_close(server.model(), &[3.0], 0.0);
+
+    server.stop().await.unwrap();
+}